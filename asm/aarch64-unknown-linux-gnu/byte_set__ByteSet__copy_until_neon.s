@@ -0,0 +1,111 @@
+	stp	x29, x30, [sp, #-48]!
+	stp	x22, x21, [sp, #16]
+	stp	x20, x19, [sp, #32]
+	mov	x29, sp
+	mov	x19, x1
+	ldr	x1, [x3, #16]
+	ldr	x8, [x3]
+	sub	x8, x8, x1
+	cmp	x2, x8
+	b.hi	.LBB_13
+	ldr	x9, [x0, #264]
+	ldr	x8, [x3, #8]
+	cmp	x9, #9
+	add	x10, x8, x1
+	b.hs	.LBB_14
+.LBB_2:
+	ldr	d0, [x0, #272]
+	dup	v0.16b, v0.b[0]
+	cbz	x9, .LBB_8
+	mov	x11, xzr
+.LBB_4:
+	mov	x8, x11
+	add	x11, x11, #16
+	cmp	x11, x2
+	b.hi	.LBB_16
+	ldr	q2, [x19, x8]
+	mov	x12, x9
+	add	x13, x0, #256
+	str	q2, [x10, x8]
+	cmhi	v1.16b, v0.16b, v2.16b
+.LBB_6:
+	ld1r	{ v3.16b }, [x13], #1
+	subs	x12, x12, #1
+	cmeq	v3.16b, v2.16b, v3.16b
+	orr	v1.16b, v3.16b, v1.16b
+	b.ne	.LBB_6
+	umaxv	b2, v1.16b
+	fmov	w12, s2
+	cbz	w12, .LBB_4
+	b	.LBB_12
+.LBB_8:
+	mov	x8, xzr
+.LBB_9:
+	add	x9, x8, #16
+	cmp	x9, x2
+	b.hi	.LBB_16
+	ldr	q2, [x19, x8]
+	str	q2, [x10, x8]
+	mov	x8, x9
+	cmhi	v1.16b, v0.16b, v2.16b
+	umaxv	b3, v1.16b
+	fmov	w11, s3
+	tbz	w11, #0, .LBB_9
+	sub	x8, x9, #16
+.LBB_12:
+	shrn	v0.8b, v1.8h, #4
+	fmov	x9, d0
+	rbit	x9, x9
+	clz	x9, x9
+	add	x8, x8, x9, lsr #2
+	b	.LBB_20
+.LBB_13:
+	mov	x21, x0
+	mov	x0, x3
+	mov	x22, x3
+	mov	w3, #1
+	mov	w4, #1
+	mov	x20, x2
+	bl	_ZN5alloc7raw_vec20RawVecInner$LT$A$GT$7reserve21do_reserve_and_handleE
+	ldr	x1, [x22, #16]
+	mov	x0, x21
+	mov	x2, x20
+	mov	x3, x22
+	ldr	x9, [x21, #264]
+	ldr	x8, [x22, #8]
+	cmp	x9, #9
+	add	x10, x8, x1
+	b.lo	.LBB_2
+.LBB_14:
+	cmp	x2, #16
+	b.hs	.LBB_21
+	mov	x8, xzr
+.LBB_16:
+	cmp	x8, x2
+	b.hs	.LBB_20
+.LBB_17:
+	ldrb	w9, [x19, x8]
+	ldrb	w11, [x0, x9]
+	tbnz	w11, #0, .LBB_20
+	strb	w9, [x10, x8]
+	add	x8, x8, #1
+	cmp	x2, x8
+	b.ne	.LBB_17
+	mov	x8, x2
+.LBB_20:
+	add	x9, x8, x1
+	mov	x0, x8
+	str	x9, [x3, #16]
+	ldp	x20, x19, [sp, #32]
+	ldp	x22, x21, [sp, #16]
+	ldp	x29, x30, [sp], #48
+	ret
+.LBB_21:
+	ldr	q0, [x19]
+	adrp	x3, .Lanon
+	add	x3, x3, :lo12:.Lanon
+	mov	x0, xzr
+	mov	x1, x9
+	mov	w2, #8
+	str	q0, [x10]
+	bl	_RNvNtNtCs6Hz1PecaLG4_4core5slice5index16slice_index_fail
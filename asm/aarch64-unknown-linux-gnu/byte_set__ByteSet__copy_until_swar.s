@@ -0,0 +1,169 @@
+	stp	x29, x30, [sp, #-48]!
+	stp	x22, x21, [sp, #16]
+	stp	x20, x19, [sp, #32]
+	mov	x29, sp
+	mov	x19, x1
+	ldr	x1, [x3, #16]
+	ldr	x8, [x3]
+	sub	x8, x8, x1
+	cmp	x2, x8
+	b.hi	.LBB_29
+	ldr	x9, [x0, #264]
+	ldr	x8, [x3, #8]
+	cmp	x9, #8
+	add	x10, x8, x1
+	b.hi	.LBB_30
+.LBB_2:
+	ldrb	w8, [x0, #272]
+	mov	w11, #128
+	mov	x12, #72340172838076673
+	sub	x11, x11, w8, uxtb
+	tst	w8, #0xff
+	mul	x11, x11, x12
+	cbz	x9, .LBB_9
+	b.eq	.LBB_14
+	mov	x13, xzr
+	mov	x14, #-9187201950435737472
+	mov	x15, #72340172838076673
+	mov	x16, #9187201950435737471
+.LBB_5:
+	mov	x8, x13
+	add	x13, x13, #8
+	cmp	x13, x2
+	b.hi	.LBB_24
+	ldr	x17, [x19, x8]
+	mov	x18, x9
+	add	x4, x0, #256
+	str	x17, [x10, x8]
+	and	x12, x17, #0x7f7f7f7f7f7f7f7f
+	add	x12, x11, x12
+	orr	x12, x12, x17
+	bic	x12, x14, x12
+.LBB_7:
+	ldrb	w5, [x4], #1
+	subs	x18, x18, #1
+	mul	x5, x5, x15
+	eor	x5, x5, x17
+	and	x6, x5, #0x7f7f7f7f7f7f7f7f
+	add	x6, x6, x16
+	orr	x5, x6, x5
+	bic	x5, x14, x5
+	orr	x12, x5, x12
+	b.ne	.LBB_7
+	cbz	x12, .LBB_5
+	b	.LBB_19
+.LBB_9:
+	b.eq	.LBB_20
+	mov	x8, xzr
+	mov	x9, #-9187201950435737472
+.LBB_11:
+	add	x13, x8, #8
+	cmp	x13, x2
+	b.hi	.LBB_24
+	ldr	x14, [x19, x8]
+	str	x14, [x10, x8]
+	mov	x8, x13
+	and	x12, x14, #0x7f7f7f7f7f7f7f7f
+	add	x12, x11, x12
+	orr	x12, x12, x14
+	and	x12, x12, #0x8080808080808080
+	cmp	x12, x9
+	b.eq	.LBB_11
+	eor	x12, x12, #0x8080808080808080
+	sub	x8, x13, #8
+	b	.LBB_19
+.LBB_14:
+	mov	x11, xzr
+	mov	x13, #72340172838076673
+	mov	x14, #9187201950435737471
+	mov	x15, #-9187201950435737472
+.LBB_15:
+	mov	x8, x11
+	add	x11, x11, #8
+	cmp	x11, x2
+	b.hi	.LBB_24
+	ldr	x16, [x19, x8]
+	mov	x12, xzr
+	mov	x17, x9
+	add	x18, x0, #256
+	str	x16, [x10, x8]
+.LBB_17:
+	ldrb	w4, [x18], #1
+	subs	x17, x17, #1
+	mul	x4, x4, x13
+	eor	x4, x4, x16
+	and	x5, x4, #0x7f7f7f7f7f7f7f7f
+	add	x5, x5, x14
+	orr	x4, x5, x4
+	bic	x4, x15, x4
+	orr	x12, x4, x12
+	b.ne	.LBB_17
+	cbz	x12, .LBB_15
+.LBB_19:
+	rbit	x9, x12
+	clz	x9, x9
+	orr	x8, x8, x9, lsr #3
+	b	.LBB_28
+.LBB_20:
+	cmp	x2, #8
+	b.hs	.LBB_22
+.LBB_21:
+	mov	x8, xzr
+	b	.LBB_24
+.LBB_22:
+	mov	x8, xzr
+.LBB_23:
+	add	x9, x8, #16
+	ldr	x11, [x19, x8]
+	cmp	x9, x2
+	str	x11, [x10, x8]
+	add	x8, x8, #8
+	b.ls	.LBB_23
+.LBB_24:
+	cmp	x8, x2
+	b.hs	.LBB_28
+.LBB_25:
+	ldrb	w9, [x19, x8]
+	ldrb	w11, [x0, x9]
+	tbnz	w11, #0, .LBB_28
+	strb	w9, [x10, x8]
+	add	x8, x8, #1
+	cmp	x2, x8
+	b.ne	.LBB_25
+	mov	x8, x2
+.LBB_28:
+	add	x9, x8, x1
+	mov	x0, x8
+	str	x9, [x3, #16]
+	ldp	x20, x19, [sp, #32]
+	ldp	x22, x21, [sp, #16]
+	ldp	x29, x30, [sp], #48
+	ret
+.LBB_29:
+	mov	x21, x0
+	mov	x0, x3
+	mov	x22, x3
+	mov	w3, #1
+	mov	w4, #1
+	mov	x20, x2
+	bl	_ZN5alloc7raw_vec20RawVecInner$LT$A$GT$7reserve21do_reserve_and_handleE
+	ldr	x1, [x22, #16]
+	mov	x0, x21
+	mov	x2, x20
+	mov	x3, x22
+	ldr	x9, [x21, #264]
+	ldr	x8, [x22, #8]
+	cmp	x9, #8
+	add	x10, x8, x1
+	b.ls	.LBB_2
+.LBB_30:
+	cmp	x2, #7
+	b.ls	.LBB_21
+	ldr	x8, [x19]
+	adrp	x3, .Lanon
+	add	x3, x3, :lo12:.Lanon
+	mov	x0, xzr
+	mov	x1, x9
+	mov	w2, #8
+	str	x8, [x10]
+	bl	_RNvNtNtCs6Hz1PecaLG4_4core5slice5index16slice_index_fail
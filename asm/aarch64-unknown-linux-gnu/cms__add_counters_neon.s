@@ -0,0 +1,35 @@
+	cmp	x3, x1
+	csel	x8, x3, x1, lo
+	cmp	x8, #4
+	b.hs	.LBB_2
+	mov	x9, xzr
+	b	.LBB_4
+.LBB_2:
+	mov	x9, xzr
+	mov	x10, x0
+	mov	x11, x2
+.LBB_3:
+	ldr	q0, [x10]
+	ldr	q1, [x11], #16
+	add	x12, x9, #8
+	add	x9, x9, #4
+	uqadd	v0.4s, v0.4s, v1.4s
+	cmp	x12, x8
+	str	q0, [x10], #16
+	b.ls	.LBB_3
+.LBB_4:
+	subs	x8, x8, x9
+	b.eq	.LBB_7
+	lsl	x10, x9, #2
+	add	x9, x0, x10
+	add	x10, x2, x10
+.LBB_6:
+	ldr	w11, [x10], #4
+	ldr	w12, [x9]
+	adds	w11, w12, w11
+	csinv	w11, w11, wzr, lo
+	subs	x8, x8, #1
+	str	w11, [x9], #4
+	b.ne	.LBB_6
+.LBB_7:
+	ret
@@ -0,0 +1,54 @@
+	cmp	x3, x1
+	csel	x9, x3, x1, lo
+	ands	x8, x9, #0x7fffffffffffffc0
+	b.eq	.LBB_4
+	and	x10, x9, #0xffffffffffffffc0
+	mov	x11, x0
+	mov	x12, x2
+	neg	x10, x10
+.LBB_2:
+	cbz	x10, .LBB_4
+	ldp	q0, q3, [x12]
+	adds	x10, x10, #64
+	ldp	q1, q2, [x11]
+	ldp	q4, q5, [x12, #32]
+	add	x12, x12, #64
+	umax	v16.16b, v1.16b, v0.16b
+	ldp	q0, q1, [x11, #32]
+	umax	v17.16b, v2.16b, v3.16b
+	umax	v18.16b, v0.16b, v4.16b
+	umax	v19.16b, v1.16b, v5.16b
+	st1	{ v16.16b, v17.16b, v18.16b, v19.16b }, [x11], #64
+	b.ne	.LBB_2
+.LBB_4:
+	ands	x10, x9, #0x30
+	add	x11, x0, x8
+	add	x12, x2, x8
+	and	x8, x9, #0xf
+	b.eq	.LBB_8
+	and	x9, x9, #0x30
+	mov	x13, x11
+	mov	x14, x12
+	neg	x9, x9
+.LBB_6:
+	cbz	x9, .LBB_8
+	ldr	q0, [x13]
+	ldr	q1, [x14], #16
+	adds	x9, x9, #16
+	umax	v0.16b, v0.16b, v1.16b
+	str	q0, [x13], #16
+	b.ne	.LBB_6
+.LBB_8:
+	cbz	x8, .LBB_11
+	add	x9, x11, x10
+	add	x10, x12, x10
+.LBB_10:
+	ldrb	w11, [x10], #1
+	ldrb	w12, [x9]
+	cmp	w11, w12
+	csel	w11, w11, w12, hi
+	subs	x8, x8, #1
+	strb	w11, [x9], #1
+	b.ne	.LBB_10
+.LBB_11:
+	ret
@@ -0,0 +1,44 @@
+	ands	x8, x1, #0x7ffffffffffffff8
+	and	x9, x1, #0x7
+	and	x10, x3, #0x7
+	b.eq	.LBB_4
+	and	x11, x1, #0xfffffffffffffff8
+	and	x12, x3, #0xfffffffffffffff8
+	mov	x13, x0
+	neg	x11, x11
+	neg	x12, x12
+	mov	x14, x2
+.LBB_2:
+	cbz	x12, .LBB_4
+	ldr	x15, [x13]
+	ldr	x16, [x14], #8
+	adds	x11, x11, #8
+	add	x12, x12, #8
+	orr	x17, x15, #0x8080808080808080
+	sub	x17, x17, x16
+	lsr	x17, x17, #7
+	and	x17, x17, #0x101010101010101
+	lsl	x18, x17, #8
+	sub	x17, x18, x17
+	and	x15, x17, x15
+	bic	x16, x16, x17
+	orr	x15, x15, x16
+	str	x15, [x13], #8
+	b.ne	.LBB_2
+.LBB_4:
+	cmp	x10, x9
+	csel	x9, x10, x9, lo
+	cbz	x9, .LBB_7
+	and	x10, x3, #0x7ffffffffffffff8
+	add	x8, x0, x8
+	add	x10, x2, x10
+.LBB_6:
+	ldrb	w11, [x10], #1
+	ldrb	w12, [x8]
+	cmp	w11, w12
+	csel	w11, w11, w12, hi
+	subs	x9, x9, #1
+	strb	w11, [x8], #1
+	b.ne	.LBB_6
+.LBB_7:
+	ret
@@ -0,0 +1,56 @@
+	ands	x10, x1, #0x7ffffffffffffff0
+	and	x9, x1, #0xf
+	b.eq	.LBB_5
+	movi	v0.2d, #0000000000000000
+	movi	v1.16b, #32
+	and	x12, x1, #0xfffffffffffffff0
+	movi	v2.16b, #34
+	movi	v3.16b, #92
+	mov	x8, xzr
+	mov	w11, wzr
+	neg	x12, x12
+	mov	x13, x0
+	b	.LBB_3
+.LBB_2:
+	adds	x12, x12, #16
+	b.eq	.LBB_6
+.LBB_3:
+	ldr	q4, [x13], #16
+	add	w11, w11, #1
+	cmp	w11, #255
+	cmeq	v5.16b, v4.16b, v2.16b
+	cmeq	v6.16b, v4.16b, v3.16b
+	cmhi	v4.16b, v1.16b, v4.16b
+	orr	v5.16b, v5.16b, v6.16b
+	orr	v4.16b, v4.16b, v5.16b
+	sub	v0.16b, v0.16b, v4.16b
+	b.ne	.LBB_2
+	uaddlv	h4, v0.16b
+	movi	v0.2d, #0000000000000000
+	mov	w11, wzr
+	fmov	w14, s4
+	add	x8, x8, x14
+	b	.LBB_2
+.LBB_5:
+	movi	v0.2d, #0000000000000000
+	mov	x8, xzr
+.LBB_6:
+	mov	x11, xzr
+	cbz	x9, .LBB_9
+	add	x10, x0, x10
+	mov	w12, #92
+	mov	w13, #32
+.LBB_8:
+	ldrb	w14, [x10], #1
+	cmp	w14, #34
+	ccmp	w14, w12, #4, ne
+	ccmp	w14, w13, #0, ne
+	cinc	x11, x11, lo
+	subs	x9, x9, #1
+	b.ne	.LBB_8
+.LBB_9:
+	uaddlv	h0, v0.16b
+	fmov	w9, s0
+	add	x8, x8, x9
+	add	x0, x11, x8
+	ret
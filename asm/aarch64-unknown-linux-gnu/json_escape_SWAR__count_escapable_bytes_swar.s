@@ -0,0 +1,53 @@
+	ands	x10, x1, #0x7ffffffffffffff8
+	and	x9, x1, #0x7
+	mov	x8, xzr
+	b.eq	.LBB_3
+	and	x13, x1, #0xfffffffffffffff8
+	mov	x11, #9187201950435737471
+	mov	x15, #2025524839466146844
+	movk	x11, #32608
+	mov	x12, #6944656592455360608
+	neg	x13, x13
+	mov	x14, #9187201950435737471
+	orr	x15, x15, #0x4444444444444444
+	mov	x16, x0
+.LBB_2:
+	ldr	x17, [x16], #8
+	adds	x13, x13, #8
+	and	x18, x17, #0x7f7f7f7f7f7f7f7f
+	and	x2, x17, x11
+	eor	x1, x18, #0x2222222222222222
+	add	x2, x2, x12
+	eor	x18, x18, x15
+	add	x1, x1, x14
+	orr	x2, x2, x17
+	add	x18, x18, x14
+	orr	x1, x1, x17
+	orr	x17, x18, x17
+	mvn	x1, x1
+	orn	x1, x1, x2
+	orn	x17, x1, x17
+	and	x17, x17, #0x8080808080808080
+	fmov	d0, x17
+	cnt	v0.8b, v0.8b
+	addv	b0, v0.8b
+	fmov	x17, d0
+	add	x8, x17, x8
+	b.ne	.LBB_2
+.LBB_3:
+	mov	x11, xzr
+	cbz	x9, .LBB_6
+	add	x10, x0, x10
+	mov	w12, #92
+	mov	w13, #32
+.LBB_5:
+	ldrb	w14, [x10], #1
+	cmp	w14, #34
+	ccmp	w14, w12, #4, ne
+	ccmp	w14, w13, #0, ne
+	cinc	x11, x11, lo
+	subs	x9, x9, #1
+	b.ne	.LBB_5
+.LBB_6:
+	add	x0, x11, x8
+	ret
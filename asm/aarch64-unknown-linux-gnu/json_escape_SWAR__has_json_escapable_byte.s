@@ -0,0 +1,133 @@
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	mov	x11, #-72340172838076674
+	mov	x12, #-2314885530818453537
+	mov	x13, #2025524839466146844
+	movk	x11, #65279
+	and	x10, x1, #0x7ffffffffffffff8
+	mov	w8, #4
+	movk	x12, #57312
+	orr	x13, x13, #0x4444444444444444
+.LBB_1:
+	add	x9, x8, #4
+	cmp	x9, x1
+	b.hi	.LBB_12
+	sub	x9, x8, #4
+	cmp	x9, x1
+	b.hs	.LBB_26
+	sub	x9, x8, #3
+	cmp	x9, x1
+	b.hs	.LBB_22
+	sub	x9, x8, #2
+	cmp	x9, x1
+	b.hs	.LBB_25
+	sub	x9, x8, #1
+	cmp	x9, x1
+	b.hs	.LBB_21
+	cmp	x8, x1
+	b.hs	.LBB_24
+	add	x9, x8, #1
+	cmp	x9, x1
+	b.hs	.LBB_20
+	add	x9, x8, #2
+	cmp	x9, x1
+	b.hs	.LBB_23
+	add	x9, x8, #3
+	cmp	x9, x1
+	b.hs	.LBB_19
+	add	x9, x0, x8
+	add	x15, x0, x8
+	ldurb	w14, [x9, #-4]
+	ldurb	w9, [x9, #-3]
+	ldurb	w16, [x15, #-2]
+	orr	x9, x14, x9, lsl #8
+	ldurb	w14, [x15, #-1]
+	ldrb	w15, [x0, x8]
+	orr	x9, x9, x16, lsl #16
+	orr	x9, x9, x14, lsl #24
+	add	x14, x0, x8
+	ldrb	w14, [x14, #1]
+	orr	x9, x9, x15, lsl #32
+	add	x15, x0, x8
+	add	x8, x8, #8
+	ldrb	w16, [x15, #2]
+	orr	x9, x9, x14, lsl #40
+	ldrb	w14, [x15, #3]
+	orr	x9, x9, x16, lsl #48
+	orr	x9, x9, x14, lsl #56
+	eor	x14, x9, #0x2222222222222222
+	add	x15, x9, x12
+	eor	x16, x9, x13
+	add	x14, x14, x11
+	add	x16, x16, x11
+	orr	x14, x14, x15
+	orr	x14, x14, x16
+	bic	x9, x14, x9
+	tst	x9, #0x8080808080808080
+	b.eq	.LBB_1
+.LBB_11:
+	mov	w0, #1
+	ldp	x29, x30, [sp], #16
+	ret
+.LBB_12:
+	add	x8, x0, x10
+	sub	x9, x1, x10
+	mov	w0, #1
+.LBB_13:
+	cbz	x9, .LBB_18
+	ldrb	w10, [x8], #1
+	cmp	w10, #32
+	b.lo	.LBB_11
+	cmp	w10, #92
+	b.eq	.LBB_17
+	cmp	w10, #34
+	sub	x9, x9, #1
+	b.ne	.LBB_13
+.LBB_17:
+	ldp	x29, x30, [sp], #16
+	ret
+.LBB_18:
+	mov	w0, wzr
+	ldp	x29, x30, [sp], #16
+	ret
+.LBB_19:
+	adrp	x2, .Lanon
+	add	x2, x2, :lo12:.Lanon
+	mov	x0, x9
+	bl	_RNvNtCs6Hz1PecaLG4_4core9panicking18panic_bounds_check
+.LBB_20:
+	adrp	x2, .Lanon
+	add	x2, x2, :lo12:.Lanon
+	mov	x0, x9
+	bl	_RNvNtCs6Hz1PecaLG4_4core9panicking18panic_bounds_check
+.LBB_21:
+	adrp	x2, .Lanon
+	add	x2, x2, :lo12:.Lanon
+	mov	x0, x9
+	bl	_RNvNtCs6Hz1PecaLG4_4core9panicking18panic_bounds_check
+.LBB_22:
+	adrp	x2, .Lanon
+	add	x2, x2, :lo12:.Lanon
+	mov	x0, x9
+	bl	_RNvNtCs6Hz1PecaLG4_4core9panicking18panic_bounds_check
+.LBB_23:
+	adrp	x2, .Lanon
+	add	x2, x2, :lo12:.Lanon
+	mov	x0, x9
+	bl	_RNvNtCs6Hz1PecaLG4_4core9panicking18panic_bounds_check
+.LBB_24:
+	adrp	x2, .Lanon
+	add	x2, x2, :lo12:.Lanon
+	mov	x0, x8
+	bl	_RNvNtCs6Hz1PecaLG4_4core9panicking18panic_bounds_check
+.LBB_25:
+	adrp	x2, .Lanon
+	add	x2, x2, :lo12:.Lanon
+	mov	x0, x9
+	bl	_RNvNtCs6Hz1PecaLG4_4core9panicking18panic_bounds_check
+.LBB_26:
+	add	x8, x1, #7
+	adrp	x2, .Lanon
+	add	x2, x2, :lo12:.Lanon
+	and	x0, x8, #0xfffffffffffffff8
+	bl	_RNvNtCs6Hz1PecaLG4_4core9panicking18panic_bounds_check
@@ -0,0 +1,41 @@
+	movi	v0.8h, #32
+	movi	v1.8h, #34
+	and	x10, x1, #0x3ffffffffffffff8
+	movi	v2.8h, #92
+	add	x8, x0, x10, lsl #1
+	and	x9, x1, #0x7
+	eor	x10, x10, #0xfffffffffffffff8
+.LBB_1:
+	adds	x10, x10, #8
+	b.eq	.LBB_4
+	ldr	q3, [x0], #16
+	cmeq	v4.8h, v3.8h, v1.8h
+	cmeq	v5.8h, v3.8h, v2.8h
+	cmhi	v3.8h, v0.8h, v3.8h
+	orr	v4.16b, v4.16b, v5.16b
+	orr	v3.16b, v3.16b, v4.16b
+	xtn	v3.8b, v3.8h
+	umaxv	b3, v3.8b
+	fmov	w11, s3
+	tbz	w11, #0, .LBB_1
+.LBB_3:
+	mov	w0, #1
+	ret
+.LBB_4:
+	lsl	x9, x9, #1
+	mov	w0, #1
+.LBB_5:
+	cbz	x9, .LBB_10
+	ldrh	w10, [x8], #2
+	cmp	w10, #32
+	b.lo	.LBB_3
+	cmp	w10, #92
+	b.eq	.LBB_9
+	cmp	w10, #34
+	sub	x9, x9, #2
+	b.ne	.LBB_5
+.LBB_9:
+	ret
+.LBB_10:
+	mov	w0, wzr
+	ret
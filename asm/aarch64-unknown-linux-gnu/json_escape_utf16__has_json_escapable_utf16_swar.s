@@ -0,0 +1,51 @@
+	and	x8, x1, #0x3ffffffffffffffc
+	mov	x9, #9223231297218904063
+	and	x11, x1, #0xfffffffffffffffc
+	add	x8, x0, x8, lsl #1
+	mov	x13, #562958543486978
+	mov	x15, #7881419608817692
+	movk	x9, #32736
+	and	x10, x1, #0x3
+	neg	x11, x11
+	mov	x12, #9214505439794855904
+	orr	x13, x13, #0x20002000200020
+	mov	x14, #9223231297218904063
+	orr	x15, x15, #0x40004000400040
+	mov	x16, #-9223231297218904064
+.LBB_1:
+	cbz	x11, .LBB_4
+	ldr	x17, [x0], #8
+	add	x11, x11, #4
+	and	x18, x17, #0x7fff7fff7fff7fff
+	and	x1, x17, x9
+	eor	x2, x18, x13
+	add	x1, x1, x12
+	eor	x18, x18, x15
+	add	x2, x2, x14
+	add	x18, x18, x14
+	and	x1, x2, x1
+	and	x18, x1, x18
+	orr	x17, x18, x17
+	bics	xzr, x16, x17
+	b.eq	.LBB_1
+.LBB_3:
+	mov	w0, #1
+	ret
+.LBB_4:
+	lsl	x9, x10, #1
+	mov	w0, #1
+.LBB_5:
+	cbz	x9, .LBB_10
+	ldrh	w10, [x8], #2
+	cmp	w10, #32
+	b.lo	.LBB_3
+	cmp	w10, #92
+	b.eq	.LBB_9
+	cmp	w10, #34
+	sub	x9, x9, #2
+	b.ne	.LBB_5
+.LBB_9:
+	ret
+.LBB_10:
+	mov	w0, wzr
+	ret
@@ -0,0 +1,233 @@
+	sub	sp, sp, #112
+	stp	x29, x30, [sp, #16]
+	stp	x28, x27, [sp, #32]
+	stp	x26, x25, [sp, #48]
+	stp	x24, x23, [sp, #64]
+	stp	x22, x21, [sp, #80]
+	stp	x20, x19, [sp, #96]
+	add	x29, sp, #16
+	mov	x22, x3
+	mov	x21, x1
+	mov	x20, x0
+	str	xzr, [x3, #16]
+	cbz	x2, .LBB_4
+	udiv	x8, x21, x2
+	ldr	x9, [x22]
+	mov	x23, x2
+	add	x8, x21, x8
+	add	x2, x8, #32
+	cmp	x2, x9
+	b.hi	.LBB_8
+	ldr	x25, [x22, #8]
+	cmp	x23, x21
+	b.ls	.LBB_9
+.LBB_3:
+	mov	x24, xzr
+	mov	x19, xzr
+	str	xzr, [x22, #16]
+	b	.LBB_27
+.LBB_4:
+	ldr	x8, [x22]
+	cmp	x21, x8
+	b.hi	.LBB_38
+	mov	x19, xzr
+	mov	x8, xzr
+	cbz	x21, .LBB_7
+.LBB_6:
+	ldr	x8, [x22, #8]
+	mov	x1, x20
+	mov	x2, x21
+	add	x0, x8, x19
+	bl	memcpy
+	mov	x8, x19
+.LBB_7:
+	add	x8, x8, x21
+	b	.LBB_31
+.LBB_8:
+	mov	x0, x22
+	mov	x1, xzr
+	mov	w3, #1
+	mov	w4, #1
+	bl	_ZN5alloc7raw_vec20RawVecInner$LT$A$GT$7reserve21do_reserve_and_handleE
+	ldr	x25, [x22, #8]
+	cmp	x23, x21
+	b.hi	.LBB_3
+.LBB_9:
+	cmp	x23, #33
+	str	x22, [sp, #8]
+	b.hs	.LBB_15
+	cmp	x23, #32
+	b.ne	.LBB_20
+	mov	x19, xzr
+	mov	w10, #16
+	mov	w8, #10
+	mov	x9, x25
+	b	.LBB_13
+.LBB_12:
+	add	x11, x20, x10
+	add	x12, x10, #48
+	strb	w8, [x9, #32]
+	ldur	q1, [x11, #-16]
+	add	x11, x10, #32
+	cmp	x12, x21
+	add	x19, x19, #33
+	mov	x10, x11
+	stp	q1, q0, [x9]
+	add	x9, x9, #33
+	b.hi	.LBB_25
+.LBB_13:
+	movi	v0.2d, #0000000000000000
+	cmp	x10, x21
+	b.hs	.LBB_12
+	ldr	q0, [x20, x10]
+	b	.LBB_12
+.LBB_15:
+	mov	w26, #32
+	mov	x24, xzr
+	mov	x19, xzr
+	bfxil	x26, x23, #0, #5
+	mov	w27, #10
+	mov	x2, x23
+	b	.LBB_17
+.LBB_16:
+	add	x8, x24, x23
+	strb	w27, [x25, x19]
+	add	x19, x19, #1
+	cmp	x8, x21
+	mov	x2, x23
+	b.hi	.LBB_26
+.LBB_17:
+	add	x8, x20, x24
+	sub	x2, x2, #32
+	mov	x28, x24
+	ldp	q0, q1, [x8]
+	mov	x22, x19
+	add	x9, x25, x19
+	cmp	x2, #31
+	add	x19, x19, #32
+	add	x24, x24, #32
+	stp	q0, q1, [x9]
+	b.hi	.LBB_17
+	cbz	x2, .LBB_16
+	add	x0, x25, x19
+	add	x1, x20, x24
+	bl	memcpy
+	add	x19, x22, x26
+	add	x24, x28, x26
+	b	.LBB_16
+.LBB_20:
+	adrp	x8, :got:_ZN10scratchpad23line_feed_every_k_bytes18SHUFFLE_MASKS_NEONE
+	movi	v2.2d, #0xffffffffffffffff
+	cmp	x23, #16
+	ldr	x8, [x8, :got_lo12:_ZN10scratchpad23line_feed_every_k_bytes18SHUFFLE_MASKS_NEONE]
+	mov	x19, xzr
+	add	x9, x8, x23, lsl #4
+	add	x8, x23, #1
+	b.lo	.LBB_32
+	ldur	q0, [x9, #-256]
+	movi	v1.16b, #10
+	mov	x24, xzr
+	cmeq	v2.16b, v0.16b, v2.16b
+	b	.LBB_23
+.LBB_22:
+	tbl	v3.16b, { v3.16b }, v0.16b
+	ldr	q4, [x20, x24]
+	add	x24, x24, x23
+	add	x10, x23, x24
+	add	x9, x25, x19
+	add	x19, x19, x8
+	cmp	x10, x21
+	bit	v3.16b, v1.16b, v2.16b
+	stp	q4, q3, [x9]
+	b.hi	.LBB_26
+.LBB_23:
+	movi	v3.2d, #0000000000000000
+	add	x9, x24, #16
+	cmp	x9, x21
+	b.hs	.LBB_22
+	add	x9, x20, x24
+	ldr	q3, [x9, #16]
+	b	.LBB_22
+.LBB_25:
+	sub	x24, x11, #16
+.LBB_26:
+	ldr	x22, [sp, #8]
+	cmp	x24, x21
+	str	x19, [x22, #16]
+	b.hi	.LBB_36
+.LBB_27:
+	ldr	x8, [x22]
+	sub	x23, x21, x24
+	sub	x8, x8, x19
+	cmp	x23, x8
+	b.hi	.LBB_37
+	cmp	x21, x24
+	b.eq	.LBB_30
+.LBB_29:
+	add	x0, x25, x19
+	add	x1, x20, x24
+	mov	x2, x23
+	bl	memcpy
+.LBB_30:
+	add	x8, x19, x23
+.LBB_31:
+	str	x8, [x22, #16]
+	ldp	x20, x19, [sp, #96]
+	ldp	x22, x21, [sp, #80]
+	ldp	x24, x23, [sp, #64]
+	ldp	x26, x25, [sp, #48]
+	ldp	x28, x27, [sp, #32]
+	ldp	x29, x30, [sp, #16]
+	add	sp, sp, #112
+	ret
+.LBB_32:
+	ldr	q0, [x9]
+	movi	v1.16b, #10
+	mov	x24, xzr
+	cmeq	v2.16b, v0.16b, v2.16b
+	b	.LBB_34
+.LBB_33:
+	ldr	q4, [x20, x24]
+	add	x24, x24, x23
+	add	x9, x25, x19
+	add	x10, x23, x24
+	add	x19, x19, x8
+	tbl	v5.16b, { v4.16b }, v0.16b
+	ext	v3.16b, v4.16b, v3.16b, #15
+	cmp	x10, x21
+	bit	v5.16b, v1.16b, v2.16b
+	stp	q5, q3, [x9]
+	b.hi	.LBB_26
+.LBB_34:
+	movi	v3.2d, #0000000000000000
+	add	x9, x24, #16
+	cmp	x9, x21
+	b.hs	.LBB_33
+	add	x9, x20, x24
+	ldr	q3, [x9, #16]
+	b	.LBB_33
+.LBB_36:
+	adrp	x3, .Lanon
+	add	x3, x3, :lo12:.Lanon
+	mov	x0, x24
+	mov	x1, x21
+	mov	x2, x21
+	bl	_RNvNtNtCs6Hz1PecaLG4_4core5slice5index16slice_index_fail
+.LBB_37:
+	mov	x0, x22
+	mov	x1, x19
+	mov	x2, x23
+	mov	w3, #1
+	mov	w4, #1
+	bl	_ZN5alloc7raw_vec20RawVecInner$LT$A$GT$7reserve21do_reserve_and_handleE
+	ldp	x25, x19, [x22, #8]
+	b	.LBB_29
+.LBB_38:
+	mov	x0, x22
+	mov	x1, xzr
+	mov	x2, x21
+	mov	w3, #1
+	mov	w4, #1
+	bl	_ZN5alloc7raw_vec20RawVecInner$LT$A$GT$7reserve21do_reserve_and_handleE
+	ldr	x19, [x22, #16]
+	b	.LBB_6
@@ -0,0 +1,60 @@
+	ldp	q0, q1, [x0]
+	ands	x8, x2, #0x7fffffffffffffc0
+	b.eq	.LBB_8
+	sub	sp, sp, #64
+	mov	x9, sp
+	adrp	x10, .Lanon
+	add	x10, x10, :lo12:.Lanon
+	b	.LBB_3
+.LBB_2:
+	add	v0.4s, v4.4s, v0.4s
+	add	v1.4s, v2.4s, v1.4s
+	subs	x8, x8, #64
+	add	x1, x1, #64
+	b.eq	.LBB_7
+.LBB_3:
+	ldp	q2, q3, [x1]
+	mov	x11, xzr
+	ldp	q4, q5, [x1, #32]
+	rev32	v2.16b, v2.16b
+	rev32	v3.16b, v3.16b
+	rev32	v4.16b, v4.16b
+	rev32	v5.16b, v5.16b
+	stp	q2, q3, [sp]
+	mov	v3.16b, v0.16b
+	mov	v2.16b, v1.16b
+	stp	q4, q5, [sp, #32]
+	b	.LBB_5
+.LBB_4:
+	ldr	q5, [x10, x11, lsl #4]
+	cmp	x12, #16
+	mov	x11, x12
+	add	v5.4s, v5.4s, v4.4s
+	mov	v4.16b, v3.16b
+	sha256h	q4, q2, v5.4s
+	sha256h2	q2, q3, v5.4s
+	mov	v3.16b, v4.16b
+	b.eq	.LBB_2
+.LBB_5:
+	and	x13, x11, #0x3
+	cmp	x11, #12
+	add	x12, x11, #1
+	ldr	q4, [x9, x13, lsl #4]
+	b.hs	.LBB_4
+	and	x14, x12, #0x3
+	mov	v6.16b, v4.16b
+	eor	x15, x13, #0x2
+	ldr	q5, [x9, x14, lsl #4]
+	sub	w14, w11, #1
+	and	x14, x14, #0x3
+	sha256su0	v6.4s, v5.4s
+	ldr	q5, [x9, x15, lsl #4]
+	ldr	q7, [x9, x14, lsl #4]
+	sha256su1	v6.4s, v5.4s, v7.4s
+	str	q6, [x9, x13, lsl #4]
+	b	.LBB_4
+.LBB_7:
+	add	sp, sp, #64
+.LBB_8:
+	stp	q0, q1, [x0]
+	ret
@@ -0,0 +1,265 @@
+	cmp	x1, #64
+	b.hs	.LBB_30
+	mov	x9, xzr
+.LBB_2:
+	cmp	x9, x1
+	b.lo	.LBB_5
+.LBB_3:
+	mov	x0, x9
+	ret
+.LBB_4:
+	cmp	x8, x1
+	mov	x9, x8
+	b.hs	.LBB_64
+.LBB_5:
+	ldrsb	w10, [x0, x9]
+	add	x8, x9, #1
+	tbz	w10, #31, .LBB_4
+	cmp	x8, x1
+	b.hs	.LBB_3
+	and	w10, w10, #0xff
+	ldrb	w8, [x0, x8]
+	sub	w11, w10, #194
+	cmp	w11, #30
+	b.hs	.LBB_9
+	mov	w10, #2
+	sxtb	w8, w8
+	cmn	w8, #65
+	b.le	.LBB_27
+	b	.LBB_3
+.LBB_9:
+	cmp	w10, #239
+	b.gt	.LBB_13
+	cmp	w10, #224
+	b.eq	.LBB_19
+	cmp	w10, #237
+	b.ne	.LBB_16
+	sxtb	w8, w8
+	cmn	w8, #96
+	b.lt	.LBB_20
+	b	.LBB_3
+.LBB_13:
+	cmp	w10, #240
+	b.eq	.LBB_22
+	cmp	w10, #244
+	b.ne	.LBB_16
+	sxtb	w8, w8
+	cmn	w8, #112
+	b.lt	.LBB_23
+	b	.LBB_3
+.LBB_16:
+	sub	w11, w10, #225
+	cmp	w11, #12
+	b.lo	.LBB_18
+	and	w11, w10, #0xfe
+	cmp	w11, #238
+	b.ne	.LBB_28
+.LBB_18:
+	sxtb	w8, w8
+	cmn	w8, #64
+	b.lt	.LBB_20
+	b	.LBB_3
+.LBB_19:
+	and	w8, w8, #0xe0
+	cmp	w8, #160
+	b.ne	.LBB_3
+.LBB_20:
+	add	x8, x9, #2
+	cmp	x8, x1
+	b.hs	.LBB_3
+	mov	w10, #3
+	ldrb	w8, [x0, x8]
+	sxtb	w8, w8
+	cmn	w8, #65
+	b.le	.LBB_27
+	b	.LBB_3
+.LBB_22:
+	sub	w8, w8, #144
+	cmp	w8, #48
+	b.hs	.LBB_3
+.LBB_23:
+	add	x8, x9, #2
+	cmp	x8, x1
+	b.hs	.LBB_3
+	ldrsb	w8, [x0, x8]
+	cmn	w8, #65
+	b.gt	.LBB_3
+	add	x8, x9, #3
+	cmp	x8, x1
+	b.hs	.LBB_3
+	mov	w10, #4
+	ldrb	w8, [x0, x8]
+	sxtb	w8, w8
+	cmn	w8, #65
+	b.gt	.LBB_3
+.LBB_27:
+	add	x8, x10, x9
+	cmp	x8, x1
+	mov	x9, x8
+	b.lo	.LBB_5
+	b	.LBB_64
+.LBB_28:
+	sub	w10, w10, #241
+	cmp	w10, #2
+	b.hi	.LBB_3
+	sxtb	w8, w8
+	cmn	w8, #64
+	b.lt	.LBB_23
+	b	.LBB_3
+.LBB_30:
+	adrp	x9, .LCPI_0
+	mov	x8, xzr
+	mov	w10, #64
+	ldr	q0, [x9, :lo12:.LCPI_0]
+.LBB_31:
+	add	x9, x0, x8
+	ldp	q4, q3, [x9]
+	ldp	q2, q1, [x9, #32]
+	mov	x9, x10
+	orr	v5.16b, v3.16b, v4.16b
+	orr	v6.16b, v2.16b, v1.16b
+	orr	v5.16b, v5.16b, v6.16b
+	umaxv	b5, v5.16b
+	fmov	w11, s5
+	tbnz	w11, #7, .LBB_34
+.LBB_32:
+	cmn	x9, #65
+	b.hi	.LBB_2
+	add	x10, x9, #64
+	mov	x8, x9
+	cmp	x10, x1
+	b.ls	.LBB_31
+	b	.LBB_2
+.LBB_34:
+	cmlt	v4.16b, v4.16b, #0
+	cmlt	v3.16b, v3.16b, #0
+	cmlt	v2.16b, v2.16b, #0
+	cmlt	v1.16b, v1.16b, #0
+	and	v4.16b, v4.16b, v0.16b
+	and	v3.16b, v3.16b, v0.16b
+	and	v2.16b, v2.16b, v0.16b
+	and	v1.16b, v1.16b, v0.16b
+	uzp1	v5.16b, v4.16b, v3.16b
+	uzp2	v3.16b, v4.16b, v3.16b
+	uzp1	v4.16b, v2.16b, v1.16b
+	uzp2	v1.16b, v2.16b, v1.16b
+	orr	v2.16b, v5.16b, v3.16b
+	orr	v1.16b, v4.16b, v1.16b
+	uzp1	v3.16b, v2.16b, v1.16b
+	uzp2	v1.16b, v2.16b, v1.16b
+	orr	v1.16b, v3.16b, v1.16b
+	xtn	v2.8b, v1.8h
+	uzp2	v1.16b, v1.16b, v0.16b
+	add	v1.16b, v2.16b, v1.16b
+	fmov	x9, d1
+	rbit	x9, x9
+	clz	x9, x9
+	add	x9, x9, x8
+	b	.LBB_37
+.LBB_35:
+	mov	w10, #2
+	sxtb	w8, w8
+	cmn	w8, #64
+	b.ge	.LBB_3
+.LBB_36:
+	add	x9, x10, x9
+.LBB_37:
+	cmp	x9, x1
+	b.hs	.LBB_32
+	ldrsb	w8, [x0, x9]
+	tbnz	w8, #31, .LBB_42
+	add	x8, x9, #8
+	cmp	x8, x1
+	b.hi	.LBB_32
+	ldr	x8, [x0, x9]
+	ands	x8, x8, #0x8080808080808080
+	b.eq	.LBB_32
+	rbit	x8, x8
+	clz	x8, x8
+	lsr	x10, x8, #3
+	b	.LBB_36
+.LBB_42:
+	add	x11, x9, #1
+	cmp	x11, x1
+	b.hs	.LBB_3
+	and	w10, w8, #0xff
+	ldrb	w8, [x0, x11]
+	sub	w12, w10, #194
+	cmp	w12, #30
+	b.lo	.LBB_35
+	cmp	w10, #239
+	b.gt	.LBB_48
+	cmp	w10, #224
+	b.eq	.LBB_54
+	cmp	w10, #237
+	b.ne	.LBB_51
+	sxtb	w8, w8
+	cmn	w8, #96
+	b.lt	.LBB_55
+	b	.LBB_3
+.LBB_48:
+	cmp	w10, #240
+	b.eq	.LBB_57
+	cmp	w10, #244
+	b.ne	.LBB_51
+	sxtb	w8, w8
+	cmn	w8, #112
+	b.lt	.LBB_58
+	b	.LBB_3
+.LBB_51:
+	sub	w11, w10, #225
+	cmp	w11, #12
+	b.lo	.LBB_53
+	and	w11, w10, #0xfe
+	cmp	w11, #238
+	b.ne	.LBB_62
+.LBB_53:
+	sxtb	w8, w8
+	cmn	w8, #64
+	b.lt	.LBB_55
+	b	.LBB_3
+.LBB_54:
+	and	w8, w8, #0xe0
+	cmp	w8, #160
+	b.ne	.LBB_3
+.LBB_55:
+	add	x8, x9, #2
+	cmp	x8, x1
+	b.hs	.LBB_3
+	mov	w10, #3
+	ldrb	w8, [x0, x8]
+	sxtb	w8, w8
+	cmn	w8, #64
+	b.lt	.LBB_36
+	b	.LBB_3
+.LBB_57:
+	sub	w8, w8, #144
+	cmp	w8, #48
+	b.hs	.LBB_3
+.LBB_58:
+	add	x8, x9, #2
+	cmp	x8, x1
+	b.hs	.LBB_3
+	ldrsb	w8, [x0, x8]
+	cmn	w8, #65
+	b.gt	.LBB_3
+	add	x8, x9, #3
+	cmp	x8, x1
+	b.hs	.LBB_3
+	mov	w10, #4
+	ldrb	w8, [x0, x8]
+	sxtb	w8, w8
+	cmn	w8, #64
+	b.lt	.LBB_36
+	b	.LBB_3
+.LBB_62:
+	sub	w10, w10, #241
+	cmp	w10, #2
+	b.hi	.LBB_3
+	sxtb	w8, w8
+	cmn	w8, #64
+	b.lt	.LBB_58
+	b	.LBB_3
+.LBB_64:
+	mov	x0, x8
+	ret
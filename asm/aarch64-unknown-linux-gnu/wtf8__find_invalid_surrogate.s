@@ -0,0 +1,178 @@
+	cbz	x1, .LBB_10
+	sub	sp, sp, #176
+	stp	x29, x30, [sp, #80]
+	stp	x28, x27, [sp, #96]
+	stp	x26, x25, [sp, #112]
+	stp	x24, x23, [sp, #128]
+	stp	x22, x21, [sp, #144]
+	stp	x20, x19, [sp, #160]
+	add	x29, sp, #80
+	movi	v17.16b, #237
+	mov	x25, #16512
+	adrp	x8, .LCPI_0
+	movk	x25, #4128, lsl #16
+	mov	x24, #2314885530818453536
+	ldr	q18, [x8, :lo12:.LCPI_0]
+	movk	x25, #1032, lsl #32
+	mov	x19, x1
+	mov	x20, x0
+	mov	x23, xzr
+	orr	x24, x24, #0x8080808080808080
+	mov	x22, #-1
+	movk	x25, #258, lsl #48
+	mov	w26, #64
+	mov	x27, #9187201950435737471
+	mov	x28, #72340172838076673
+	str	q18, [sp]
+	b	.LBB_3
+.LBB_2:
+	mov	x23, xzr
+	sub	x19, x19, x21
+	add	x20, x20, x21
+	add	x22, x22, #64
+	cbz	x19, .LBB_9
+.LBB_3:
+	cmp	x19, #64
+	csel	x21, x19, x26, lo
+	cmp	x19, #63
+	b.ls	.LBB_5
+	ldp	q2, q3, [x20]
+	ldp	q1, q0, [x20, #32]
+	b	.LBB_6
+.LBB_5:
+	add	x8, sp, #16
+	sub	x2, x26, x21
+	mov	w1, wzr
+	add	x0, x8, x21
+	bl	memset
+	add	x0, sp, #16
+	mov	x1, x20
+	mov	x2, x21
+	bl	memcpy
+	movi	v17.16b, #237
+	ldp	q18, q2, [sp]
+	ldp	q3, q1, [sp, #32]
+	ldr	q0, [sp, #64]
+.LBB_6:
+	cmeq	v4.16b, v2.16b, v17.16b
+	cmeq	v5.16b, v3.16b, v17.16b
+	cmeq	v6.16b, v1.16b, v17.16b
+	cmeq	v7.16b, v0.16b, v17.16b
+	and	v4.16b, v4.16b, v18.16b
+	and	v5.16b, v5.16b, v18.16b
+	and	v6.16b, v6.16b, v18.16b
+	and	v7.16b, v7.16b, v18.16b
+	uzp1	v16.16b, v4.16b, v5.16b
+	uzp2	v4.16b, v4.16b, v5.16b
+	uzp1	v5.16b, v6.16b, v7.16b
+	uzp2	v6.16b, v6.16b, v7.16b
+	orr	v4.16b, v16.16b, v4.16b
+	orr	v5.16b, v5.16b, v6.16b
+	uzp1	v6.16b, v4.16b, v5.16b
+	uzp2	v4.16b, v4.16b, v5.16b
+	orr	v4.16b, v6.16b, v4.16b
+	xtn	v5.8b, v4.8h
+	uzp2	v4.16b, v4.16b, v0.16b
+	add	v4.16b, v5.16b, v4.16b
+	fmov	x8, d4
+	orr	x9, x8, x23
+	cbz	x9, .LBB_2
+	fmov	x9, d2
+	fmov	x11, d3
+	mov	x10, v3.d[1]
+	fmov	x14, d1
+	mov	x13, v2.d[1]
+	fmov	x17, d0
+	eor	x9, x9, x24
+	eor	x11, x11, x24
+	and	x12, x9, #0x6060606060606060
+	and	x15, x11, #0x6060606060606060
+	eor	x10, x10, x24
+	add	x12, x12, x27
+	eor	x14, x14, x24
+	eor	x13, x13, x24
+	orr	x9, x12, x9
+	add	x12, x15, x27
+	mov	x15, v1.d[1]
+	orr	x11, x12, x11
+	and	x12, x10, #0x6060606060606060
+	and	x16, x14, #0x6060606060606060
+	add	x12, x12, x27
+	bic	x11, x28, x11, lsr #7
+	bic	x9, x28, x9, lsr #7
+	orr	x10, x12, x10
+	add	x12, x16, x27
+	mov	x16, v0.d[1]
+	orr	x12, x12, x14
+	and	x14, x13, #0x6060606060606060
+	mul	x11, x11, x25
+	add	x14, x14, x27
+	bic	x10, x28, x10, lsr #7
+	bic	x12, x28, x12, lsr #7
+	orr	x13, x14, x13
+	eor	x14, x15, x24
+	mul	x9, x9, x25
+	bic	x13, x28, x13, lsr #7
+	and	x15, x14, #0x6060606060606060
+	eor	x16, x16, x24
+	mul	x10, x10, x25
+	add	x15, x15, x27
+	orr	x14, x15, x14
+	eor	x15, x17, x24
+	lsr	x11, x11, #40
+	mul	x13, x13, x25
+	and	x17, x15, #0x6060606060606060
+	bic	x14, x28, x14, lsr #7
+	add	x17, x17, x27
+	and	x11, x11, #0xff0000
+	mul	x12, x12, x25
+	orr	x15, x17, x15
+	and	x17, x16, #0x6060606060606060
+	lsr	x10, x10, #56
+	add	x17, x17, x27
+	bfxil	x11, x9, #56, #8
+	mul	x14, x14, x25
+	bic	x15, x28, x15, lsr #7
+	lsr	x9, x13, #56
+	orr	x13, x17, x16
+	orr	x10, x11, x10, lsl #24
+	bic	x13, x28, x13, lsr #7
+	mul	x15, x15, x25
+	lsr	x11, x12, #56
+	orr	x9, x10, x9, lsl #8
+	mul	x12, x13, x25
+	lsr	x10, x14, #56
+	orr	x9, x9, x11, lsl #32
+	orr	x9, x9, x10, lsl #40
+	lsr	x10, x15, #56
+	and	x11, x12, #0xff00000000000000
+	orr	x9, x9, x11
+	add	x11, x23, x8, lsl #1
+	orr	x9, x9, x10, lsl #48
+	ands	x9, x9, x11
+	b.ne	.LBB_11
+	lsr	x23, x8, #63
+	sub	x19, x19, x21
+	add	x20, x20, x21
+	add	x22, x22, #64
+	cbnz	x19, .LBB_3
+.LBB_9:
+	mov	x0, xzr
+	b	.LBB_12
+.LBB_10:
+	mov	x0, xzr
+	ret
+.LBB_11:
+	rbit	x8, x9
+	mov	w0, #1
+	clz	x8, x8
+	add	x1, x8, x22
+.LBB_12:
+	ldp	x20, x19, [sp, #160]
+	ldp	x22, x21, [sp, #144]
+	ldp	x24, x23, [sp, #128]
+	ldp	x26, x25, [sp, #112]
+	ldp	x28, x27, [sp, #96]
+	ldp	x29, x30, [sp, #80]
+	add	sp, sp, #176
+	ret
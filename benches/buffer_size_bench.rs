@@ -1,6 +1,6 @@
-use std::time::Instant;
 use std::fs::{self, File};
 use std::io::{Write, Read};
+use scratchpad::bench::{format_size, geometric_sizes, measure_for, sweep_with, target_time, Labels};
 
 const TEST_FILE: &str = "/tmp/test_buffer_size.csv";
 
@@ -76,25 +76,6 @@ fn count_pattern_with_buffer_size(
     Ok(line_count)
 }
 
-fn bench_buffer_size(buffer_size: usize, iterations: usize, file_size: u64) -> f64 {
-    // Warmup
-    for _ in 0..5 {
-        let _ = count_pattern_with_buffer_size(TEST_FILE, b"Harvard", buffer_size);
-    }
-
-    let start = Instant::now();
-    for _ in 0..iterations {
-        let count = count_pattern_with_buffer_size(TEST_FILE, b"Harvard", buffer_size).unwrap();
-        std::hint::black_box(count);
-    }
-    let elapsed = start.elapsed().as_secs_f64();
-
-    let total_bytes = file_size as f64 * iterations as f64;
-    let throughput_gb_s = total_bytes / elapsed / 1_000_000_000.0;
-
-    throughput_gb_s
-}
-
 fn main() {
     println!("=== Buffer Size Optimization Benchmark ===\n");
 
@@ -104,62 +85,23 @@ fn main() {
     let file_size = fs::metadata(TEST_FILE).unwrap().len();
     println!("File size: {:.2} MB\n", file_size as f64 / 1_000_000.0);
 
-    let iterations = 100;
-
-    // Test different buffer sizes
-    let buffer_sizes = vec![
-        512,      // 512 B
-        1024,     // 1 KB
-        2048,     // 2 KB
-        4096,     // 4 KB (blog post)
-        8192,     // 8 KB
-        16384,    // 16 KB
-        32768,    // 32 KB
-        65536,    // 64 KB
-        131072,   // 128 KB
-        262144,   // 256 KB
-    ];
-
-    println!("{:>12} {:>15} {:>15}", "Buffer Size", "Throughput", "vs 4KB");
-    println!("{}", "-".repeat(45));
-
-    let mut results = Vec::new();
-    let mut baseline_throughput = 0.0;
-
-    for &size in &buffer_sizes {
-        let throughput = bench_buffer_size(size, iterations, file_size);
-        results.push((size, throughput));
-
-        if size == 4096 {
-            baseline_throughput = throughput;
-        }
-
-        let size_str = if size >= 1024 {
-            format!("{} KB", size / 1024)
-        } else {
-            format!("{} B", size)
-        };
-
-        let speedup = if baseline_throughput > 0.0 {
-            format!("{:+.1}%", (throughput / baseline_throughput - 1.0) * 100.0)
-        } else {
-            "baseline".to_string()
-        };
-
-        println!("{:>12} {:>12.2} GB/s {:>15}", size_str, throughput, speedup);
-    }
+    // 512 B to 256 KB, 4 KB (the blog post's) included
+    let sizes = geometric_sizes(512, 256 << 10, 1);
+    let labels = Labels::new("count_pattern_with_buffer_size", "read");
+    let curve = sweep_with("Buffer size", &labels, &sizes, |buffer_size| {
+        let count = || count_pattern_with_buffer_size(TEST_FILE, b"Harvard", buffer_size).unwrap();
+        measure_for(count, target_time(), |_| file_size as usize)
+    });
 
-    // Find optimal
-    let optimal = results.iter().max_by(|a, b| a.1.partial_cmp(&b.1).unwrap()).unwrap();
-    let optimal_size_str = if optimal.0 >= 1024 {
-        format!("{} KB", optimal.0 / 1024)
-    } else {
-        format!("{} B", optimal.0)
-    };
+    let baseline_throughput = curve.at(4096).unwrap().throughput_gb_s();
+    let (optimal_size, optimal) = curve.best().unwrap();
+    let optimal_throughput = optimal.throughput_gb_s();
+    let improvement = (optimal_throughput / baseline_throughput - 1.0) * 100.0;
 
     println!("\n{}", "=".repeat(45));
-    println!("Optimal buffer size: {} ({:.2} GB/s)", optimal_size_str, optimal.1);
-    println!("Improvement over 4KB: {:.1}%", (optimal.1 / baseline_throughput - 1.0) * 100.0);
+    let optimal_size = format_size(*optimal_size);
+    println!("Optimal buffer size: {} ({:.2} GB/s)", optimal_size, optimal_throughput);
+    println!("Improvement over 4KB: {:.1}%", improvement);
 
     // Clean up
     let _ = fs::remove_file(TEST_FILE);
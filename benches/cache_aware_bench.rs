@@ -1,6 +1,8 @@
-use std::time::Instant;
 use std::fs::{self, File};
 use std::io::{Write, Read};
+use scratchpad::bench::{format_size, geometric_sizes, measure_for, sweep_with, target_time, Labels};
+use scratchpad::cpu;
+use scratchpad::cpu_info::optimal_buffer_size;
 
 const TEST_FILE: &str = "/tmp/test_cache_aware.csv";
 
@@ -59,91 +61,67 @@ fn count_with_buffer(file_path: &str, pattern: &[u8], buffer_size: usize) -> std
     Ok(line_count)
 }
 
-fn bench(buffer_size: usize, iterations: usize, file_size: u64) -> (f64, f64) {
-    // Warmup
-    for _ in 0..5 {
-        let _ = count_with_buffer(TEST_FILE, b"Harvard", buffer_size);
-    }
-
-    let start = Instant::now();
-    for _ in 0..iterations {
-        let count = count_with_buffer(TEST_FILE, b"Harvard", buffer_size).unwrap();
-        std::hint::black_box(count);
-    }
-    let elapsed = start.elapsed().as_secs_f64();
-
-    let total_bytes = file_size as f64 * iterations as f64;
-    let throughput = total_bytes / elapsed / 1_000_000_000.0;
-    let time_per_op_us = (elapsed / iterations as f64) * 1_000_000.0;
-
-    (throughput, time_per_op_us)
-}
-
 fn main() {
     println!("=== Cache-Aware Buffer Size Analysis ===\n");
 
-    // Get cache info
-    println!("CPU Cache Architecture (ARM M-series):");
-    println!("  L1 Data Cache:  64 KB  (per P-core)");
-    println!("  L1 Data Cache: 128 KB  (per E-core)");
-    println!("  L2 Cache:        4 MB  (shared)\n");
+    // Detected cache sizes (sysfs on Linux, sysctl on macOS)
+    let caches = cpu::info().caches;
+    let optimal_default = optimal_buffer_size();
+    let kb = |size: Option<usize>| {
+        size.map_or("unknown".to_string(), |size| format!("{} KB", size / 1024))
+    };
+    println!("CPU Caches (detected):");
+    println!("  L1 Data Cache: {}", kb(caches.l1d));
+    println!("  L2 Cache:      {}", kb(caches.l2));
+    println!("  L3 Cache:      {}", kb(caches.l3));
+    println!("  optimal_buffer_size(): {} KB\n", optimal_default / 1024);
+    // The boundary the notes refer to (64 KB, an M-series E-core, if unknown)
+    let l1 = caches.l1d.unwrap_or(65536);
 
     println!("Generating test file...");
     write_test_file(200_000).unwrap();
     let file_size = fs::metadata(TEST_FILE).unwrap().len();
     println!("File size: {:.2} MB\n", file_size as f64 / 1_000_000.0);
 
-    let iterations = 100;
-
-    // Test buffer sizes around cache boundaries
-    let test_configs = vec![
-        // Sub-L1 cache sizes
-        ("1 KB", 1024, "Much smaller than L1"),
-        ("4 KB", 4096, "Blog post (page size)"),
-        ("8 KB", 8192, ""),
-        ("16 KB", 16384, "1/4 of L1"),
-        ("32 KB", 32768, "1/2 of L1"),
-
-        // Around L1 boundary (64-128 KB)
-        ("48 KB", 49152, "3/4 of L1"),
-        ("64 KB", 65536, "⚠️  L1 boundary (P-core)"),
-        ("80 KB", 81920, "Just above L1 (P-core)"),
-        ("96 KB", 98304, ""),
-        ("112 KB", 114688, ""),
-        ("128 KB", 131072, "⚠️  L1 boundary (E-core)"),
-        ("160 KB", 163840, "Just above L1"),
-
-        // L2 but > L1
-        ("192 KB", 196608, ""),
-        ("256 KB", 262144, "Previous optimal"),
-        ("512 KB", 524288, ""),
+    // Two sizes per doubling from 1 KB to 512 KB, the detected ones included
+    let mut sizes = geometric_sizes(1 << 10, 512 << 10, 2);
+    sizes.extend([l1, optimal_default]);
+    sizes.sort();
+    sizes.dedup();
+    let above_l1 = sizes.iter().copied().find(|&size| size > l1);
+
+    let labels = Labels::new("count_with_buffer", "read");
+    let curve = sweep_with("Buffer", &labels, &sizes, |buffer_size| {
+        let count = || count_with_buffer(TEST_FILE, b"Harvard", buffer_size).unwrap();
+        measure_for(count, target_time(), |_| file_size as usize)
+    });
+    let baseline_throughput = curve.at(4096).unwrap().throughput_gb_s();
+
+    let mut marks = vec![
+        (4096, "Blog post (page size)"),
+        (optimal_default, "optimal_buffer_size()"),
+        (l1, "⚠️  L1 boundary"),
     ];
+    marks.extend(above_l1.map(|size| (size, "Just above L1")));
+    marks.push((262144, "Previous optimal"));
 
-    println!("{:>10} {:>15} {:>12} {:>12} {}",
-             "Buffer", "Throughput", "Time/Op", "vs 4KB", "Notes");
+    println!("\n{:>10} {:>15} {:>12} {:>12} Notes",
+             "Buffer", "Throughput", "Time/Op", "vs 4KB");
     println!("{}", "=".repeat(80));
-
-    let mut results = Vec::new();
-    let mut baseline_throughput = 0.0;
-
-    for (name, size, note) in test_configs {
-        let (throughput, time_us) = bench(size, iterations, file_size);
-        results.push((name, size, throughput, time_us));
-
-        if size == 4096 {
-            baseline_throughput = throughput;
-        }
-
-        let speedup = if baseline_throughput > 0.0 {
-            format!("{:+.1}%", (throughput / baseline_throughput - 1.0) * 100.0)
-        } else {
-            "".to_string()
-        };
-
-        println!("{:>10} {:>12.2} GB/s {:>9.1} μs {:>12} {}",
-                 name, throughput, time_us, speedup, note);
+    for (size, note) in marks {
+        let measurement = curve.at(size).unwrap();
+        let throughput = measurement.throughput_gb_s();
+        println!("{:>10} {:>12.2} GB/s {:>9.1} μs {:>+11.1}% {}",
+                 format_size(size), throughput, measurement.ms_per_run() * 1000.0,
+                 (throughput / baseline_throughput - 1.0) * 100.0, note);
     }
 
+    let results: Vec<(String, usize, f64)> = curve
+        .points
+        .iter()
+        .map(|(size, measurement)| (format_size(*size), *size, measurement.throughput_gb_s()))
+        .collect();
+
     // Analysis
     let optimal = results.iter().max_by(|a, b| a.2.partial_cmp(&b.2).unwrap()).unwrap();
 
@@ -153,29 +131,29 @@ fn main() {
              optimal.0, optimal.2, (optimal.2 / baseline_throughput - 1.0) * 100.0);
 
     // Find L1 cache boundary performance
-    let l1_64kb = results.iter().find(|r| r.1 == 65536).unwrap();
-    let l1_128kb = results.iter().find(|r| r.1 == 131072).unwrap();
+    let at_l1 = results.iter().find(|r| r.1 == l1).unwrap();
+    let past_l1 = results.iter().find(|r| r.1 >= 2 * l1).unwrap_or(results.last().unwrap());
+    let default = results.iter().find(|r| r.1 == optimal_default).unwrap();
 
     println!("\n  L1 Cache Boundary Effects:");
-    println!("    64 KB (P-core L1):  {:.2} GB/s", l1_64kb.2);
-    println!("    128 KB (E-core L1): {:.2} GB/s", l1_128kb.2);
+    println!("    {:>7} (L1):                    {:.2} GB/s", at_l1.0, at_l1.2);
+    println!("    {:>7} (2x L1 or more):         {:.2} GB/s", past_l1.0, past_l1.2);
+    println!("    {:>7} (optimal_buffer_size()): {:.2} GB/s", default.0, default.2);
 
-    if optimal.1 <= 65536 {
-        println!("\n  ✓ Optimal buffer fits entirely in L1 cache (P-core)");
-    } else if optimal.1 <= 131072 {
-        println!("\n  ⚠ Optimal buffer fits in L1 on E-cores, but not P-cores");
+    if optimal.1 <= l1 {
+        println!("\n  ✓ Optimal buffer fits entirely in L1 cache");
     } else {
         println!("\n  ⚠ Optimal buffer exceeds L1, relies on L2 cache");
     }
 
     println!("\nConclusion:");
-    if optimal.1 <= 65536 {
-        println!("  Buffer size ≤ 64 KB keeps data in L1 cache (fastest access)");
-        println!("  This explains the performance plateau around 64 KB");
+    if optimal.1 <= l1 {
+        println!("  Buffer size ≤ {} keeps data in L1 cache (fastest access)", at_l1.0);
+        println!("  This explains the performance plateau below the L1 size");
     } else {
         println!("  Larger buffers ({}) perform better despite exceeding L1", optimal.0);
         println!("  Benefit of fewer syscalls outweighs L1 cache misses");
-        println!("  L2 cache (4 MB) is still fast enough");
+        println!("  L2 cache ({}) is still fast enough", kb(caches.l2));
     }
 
     let _ = fs::remove_file(TEST_FILE);
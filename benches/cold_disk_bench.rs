@@ -1,8 +1,16 @@
 use std::time::Instant;
 use std::fs::{self, File};
 use std::io::Write;
-use std::process::Command;
-use scratchpad::csv_parse_buffer_size_impact::{count_pattern_matches_from_file, count_pattern_matches_in_memory};
+use scratchpad::csv_parse_buffer_size_impact::{
+    count_pattern_matches_from_file, count_pattern_matches_in_memory,
+    count_pattern_matches_in_reader, count_pattern_matches_prefetch,
+    count_pattern_matches_uncached,
+};
+use scratchpad::cache_control::CacheControl;
+use scratchpad::prefetch::PrefetchReader;
+use scratchpad::uncached::UncachedReader;
+#[cfg(all(feature = "uring", target_os = "linux"))]
+use scratchpad::csv_parse_buffer_size_impact::count_pattern_matches_uring;
 
 fn write_test_file(file_path: &str, num_rows: usize) -> std::io::Result<()> {
     let mut file = File::create(file_path)?;
@@ -17,22 +25,16 @@ fn write_test_file(file_path: &str, num_rows: usize) -> std::io::Result<()> {
     Ok(())
 }
 
-fn clear_os_cache() {
-    // On macOS, purge clears the disk cache
-    // Note: This requires sudo, so it may not work without privileges
-    let _ = Command::new("purge").output();
+/// Evicts `.1` from the page cache with `.0` before each timed run.
+type Eviction<'a> = Option<(&'a CacheControl, &'a str)>;
 
-    // Small delay to ensure cache is cleared
-    std::thread::sleep(std::time::Duration::from_millis(100));
-}
-
-fn bench_cold(name: &str, f: impl Fn() -> usize, iterations: usize, file_size: u64, clear_cache: bool) -> (f64, f64, f64) {
+fn bench_cold(name: &str, f: impl Fn() -> usize, iterations: usize, file_size: u64, evict: Eviction) -> (f64, f64, f64) {
     let mut times = Vec::new();
 
     for i in 0..iterations {
-        if clear_cache && i > 0 {
+        if let Some((cache, path)) = evict.filter(|_| i > 0) {
             // Clear cache between iterations (except first for warmup)
-            clear_os_cache();
+            cache.evict(path).expect("Failed to evict the test file");
         }
 
         let start = Instant::now();
@@ -63,17 +65,16 @@ fn main() {
     println!("=== Cold Disk vs Hot Cache Comparison ===\n");
     println!("Testing with OS page cache cleared between runs\n");
 
-    // Check if we can clear cache
-    let can_purge = Command::new("which").arg("purge").output()
-        .map(|o| o.status.success())
-        .unwrap_or(false);
-
-    if !can_purge {
-        println!("⚠️  Warning: 'purge' command not available.");
-        println!("   Results will show cached performance only.\n");
-    } else {
-        println!("✓ Using 'purge' to clear OS cache between iterations\n");
+    // The whole cache as root, the test file alone otherwise (Linux), and
+    // a slow, partial eviction by a dummy file where nothing else works
+    let cache = CacheControl::detect();
+    let slow_eviction = matches!(cache, CacheControl::DummyFile { .. });
+    println!("✓ Using {} to clear OS cache between iterations", cache);
+    if slow_eviction {
+        println!("⚠️  Warning: seconds per eviction, and some pages survive it.");
+        println!("   Run as root for 'purge', or compare with the uncached reads (F_NOCACHE).");
     }
+    println!();
 
     let test_cases = vec![
         ("Small (1K rows, ~50 KB)", 1_000, "/tmp/test_cold_1k.csv"),
@@ -88,32 +89,66 @@ fn main() {
         let file_size = fs::metadata(test_file).unwrap().len();
         println!("File size: {:.2} MB\n", file_size as f64 / 1_000_000.0);
 
-        let iterations = if can_purge { 11 } else { 101 }; // 1 warmup + 10 or 100 measurements
+        let iterations = 101; // 1 warmup + 100 measurements
+        let cold_iterations = if slow_eviction { 4 } else { 11 };
+        let evict = Some((&cache, test_file));
+
+        // Test with COLD cache (evicted between iterations)
+        println!("--- COLD DISK (cache cleared) ---");
+
+        let (tp_disk_cold, _, max_disk_cold) = bench_cold(
+            "Disk (buffered)",
+            || count_pattern_matches_from_file(test_file, b"Harvard").unwrap(),
+            cold_iterations,
+            file_size,
+            evict,
+        );
+
+        let (tp_mem_cold, _, max_mem_cold) = bench_cold(
+            "In-Memory (load all)",
+            || count_pattern_matches_in_memory(test_file, b"Harvard").unwrap(),
+            cold_iterations,
+            file_size,
+            evict,
+        );
+
+        #[cfg(all(feature = "uring", target_os = "linux"))]
+        bench_cold(
+            "io_uring (4x256KB in flight)",
+            || count_pattern_matches_uring(test_file, b"Harvard").unwrap(),
+            cold_iterations,
+            file_size,
+            evict,
+        );
+
+        let speedup_cold = tp_mem_cold / tp_disk_cold;
+        println!("  → Cold: In-Memory is {:.2}x {}",
+                 speedup_cold,
+                 if speedup_cold >= 1.0 { "faster" } else { "slower" });
+        println!();
 
-        // Test with COLD cache (purge between iterations)
-        if can_purge {
-            println!("--- COLD DISK (cache cleared) ---");
+        // Cold reads without eviction: the page cache is bypassed instead
+        if count_pattern_matches_uncached(test_file, b"Harvard").is_ok() {
+            println!("--- COLD DISK (O_DIRECT / F_NOCACHE) ---");
 
-            let (tp_disk_cold, _, max_disk_cold) = bench_cold(
-                "Disk (4KB buffered)",
-                || count_pattern_matches_from_file(test_file, b"Harvard").unwrap(),
+            bench_cold(
+                "Disk (uncached, 256KB)",
+                || count_pattern_matches_uncached(test_file, b"Harvard").unwrap(),
                 iterations,
                 file_size,
-                true, // Clear cache
+                None, // Nothing to clear
             );
 
-            let (tp_mem_cold, _, max_mem_cold) = bench_cold(
-                "In-Memory (load all)",
-                || count_pattern_matches_in_memory(test_file, b"Harvard").unwrap(),
+            bench_cold(
+                "Uncached + prefetch thread",
+                || {
+                    let reader = PrefetchReader::new(UncachedReader::open(test_file).unwrap());
+                    count_pattern_matches_in_reader(reader, b"Harvard").unwrap()
+                },
                 iterations,
                 file_size,
-                true, // Clear cache
+                None, // Nothing to clear
             );
-
-            let speedup_cold = tp_mem_cold / tp_disk_cold;
-            println!("  → Cold: In-Memory is {:.2}x {}",
-                     speedup_cold,
-                     if speedup_cold >= 1.0 { "faster" } else { "slower" });
             println!();
         }
 
@@ -121,11 +156,11 @@ fn main() {
         println!("--- HOT CACHE (already in RAM) ---");
 
         let (tp_disk_hot, _, _) = bench_cold(
-            "Disk (4KB buffered)",
+            "Disk (buffered)",
             || count_pattern_matches_from_file(test_file, b"Harvard").unwrap(),
             iterations,
             file_size,
-            false, // Don't clear cache
+            None, // Don't clear cache
         );
 
         let (tp_mem_hot, _, _) = bench_cold(
@@ -133,7 +168,24 @@ fn main() {
             || count_pattern_matches_in_memory(test_file, b"Harvard").unwrap(),
             iterations,
             file_size,
-            false, // Don't clear cache
+            None, // Don't clear cache
+        );
+
+        bench_cold(
+            "Prefetch thread (2x256KB)",
+            || count_pattern_matches_prefetch(test_file, b"Harvard").unwrap(),
+            iterations,
+            file_size,
+            None, // Don't clear cache
+        );
+
+        #[cfg(all(feature = "uring", target_os = "linux"))]
+        bench_cold(
+            "io_uring (4x256KB in flight)",
+            || count_pattern_matches_uring(test_file, b"Harvard").unwrap(),
+            iterations,
+            file_size,
+            None, // Don't clear cache
         );
 
         let speedup_hot = tp_mem_hot / tp_disk_hot;
@@ -144,24 +196,17 @@ fn main() {
     }
 
     println!("\n=== Analysis ===");
-    if can_purge {
-        println!("\nCOLD DISK (cache cleared):");
-        println!("  - Both methods must read from actual disk");
-        println!("  - Disk I/O time dominates (~5-20ms for mechanical, ~0.1-1ms for SSD)");
-        println!("  - Processing time becomes negligible compared to I/O");
-        println!("  - Buffered approach may be more consistent (streaming)");
-
-        println!("\nHOT CACHE (already in RAM):");
-        println!("  - File is already in OS page cache");
-        println!("  - No actual disk I/O occurs");
-        println!("  - In-memory is faster due to single syscall vs many");
-        println!("  - This was our previous benchmark scenario");
-    } else {
-        println!("\nHOT CACHE only (cache not cleared):");
-        println!("  - Results show best-case scenario");
-        println!("  - Real-world: first access is cold, subsequent are hot");
-        println!("  - Run with sudo to enable cache clearing");
-    }
+    println!("\nCOLD DISK (cache cleared):");
+    println!("  - Both methods must read from actual disk");
+    println!("  - Disk I/O time dominates (~5-20ms for mechanical, ~0.1-1ms for SSD)");
+    println!("  - Processing time becomes negligible compared to I/O");
+    println!("  - Buffered approach may be more consistent (streaming)");
+
+    println!("\nHOT CACHE (already in RAM):");
+    println!("  - File is already in OS page cache");
+    println!("  - No actual disk I/O occurs");
+    println!("  - In-memory is faster due to single syscall vs many");
+    println!("  - This was our previous benchmark scenario");
 
     println!("\nKey Insight:");
     println!("  When disk I/O dominates (cold cache), buffering strategy matters less.");
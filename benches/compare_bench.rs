@@ -0,0 +1,272 @@
+//! Each kernel against the crate one would reach for instead, on the same
+//! input, the outputs checked equal before timing: is the hand-rolled
+//! version worth it?
+//!
+//!   cargo bench --features compare --bench compare_bench
+//!   cargo bench --features compare --bench compare_bench -- --json compare.jsonl
+//!
+//! | Kernel                          | Reference                          |
+//! |---------------------------------|------------------------------------|
+//! | `needle::find`                  | `memchr::memmem::Finder`           |
+//! | `CsvReader`, `parse_csv_index`  | `csv::Reader` with a `ByteRecord`  |
+//! | `escape_json_into`              | `serde_json::to_writer` of a `str` |
+//! | `insert_line_feed`, K=76        | a `chunks(76)` loop                |
+//!
+//! There is no base64 encoder here: what this crate has of MIME base64 is
+//! the line feed every 76 characters. That row encodes with the `base64`
+//! crate and wraps its output both ways, and the summary says what share
+//! of the whole the wrap is.
+//!
+//! The summary ends the run: ours / reference for every case, above 1 where
+//! ours is faster. On the x86-64 VM of `cpu_info` (AVX2, so `find` filters
+//! with AVX2 and `insert_line_feed` is scalar), with `--time 0.1`:
+//!
+//! | Kernel            | ours / reference | Cases                                  |
+//! |-------------------|------------------|----------------------------------------|
+//! | `find`            | 0.46-0.91x       | behind on all four needles             |
+//! | `parse_csv_index` | 2.50-4.89x       | ahead on all six fixtures              |
+//! | `CsvReader`       | 0.76-5.40x       | behind on the wide and ragged fixtures |
+//! | `escape_json`     | 0.19-1.82x       | ahead on clean strings only            |
+//! | MIME wrap         | 1.00x            | 12% of encode + wrap, either way       |
+//!
+//! `find` is furthest behind on `"service":"billing"`, whose first and last
+//! bytes are quotes, found on every field of NDJSON: the first+last filter
+//! stops at each of them. `escape_json_into` loses on NDJSON logs (0.63x)
+//! and on nothing but escapes (0.19x). What SIMD line feeds could save in a
+//! MIME encoder is at most the 12% of the wrap.
+
+use base64::Engine;
+use scratchpad::bench::{bench_labeled, Labels};
+use scratchpad::csv_dialect::strip_bom;
+use scratchpad::csv_index::parse_csv_index;
+use scratchpad::csv_reader::CsvReader;
+use scratchpad::datagen::{fixtures, ndjson, Size};
+use scratchpad::json_escape_SWAR::escape_json_into;
+use scratchpad::line_feed_every_k_bytes::insert_line_feed;
+use scratchpad::needle;
+
+const OURS: &str = "scratchpad";
+
+/// Line length of MIME base64 (RFC 2045).
+const MIME_LINE: usize = 76;
+
+/// One case: our throughput over the reference's.
+struct Comparison {
+    kernel: &'static str,
+    case: String,
+    reference: &'static str,
+    ratio: f64,
+}
+
+/// Time ours, then the reference, on `input_size` bytes and keep the ratio.
+/// Returns both throughputs.
+fn compare<A, B>(
+    results: &mut Vec<Comparison>,
+    (kernel, case): (&'static str, &str),
+    ours: impl FnMut() -> A,
+    (reference, theirs): (&'static str, impl FnMut() -> B),
+    input_size: usize,
+) -> (f64, f64) {
+    let name = |backend: &str| format!("  {} ({})", backend, case);
+    let ours = bench_labeled(&name(OURS), &Labels::new(kernel, OURS), ours, input_size);
+    let labels = Labels::new(kernel, reference);
+    let theirs = bench_labeled(&name(reference), &labels, theirs, input_size);
+    println!("{:30} {:.2}x", "", ours / theirs);
+    results.push(Comparison { kernel, case: case.to_string(), reference, ratio: ours / theirs });
+    (ours, theirs)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Kernels
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Every occurrence (overlapping), resuming one byte after each match.
+fn count_all(haystack: &[u8], find: impl Fn(&[u8]) -> Option<usize>) -> usize {
+    let (mut count, mut i) = (0, 0);
+    while let Some(pos) = find(&haystack[i..]) {
+        count += 1;
+        i += pos + 1;
+    }
+    count
+}
+
+fn find(results: &mut Vec<Comparison>) {
+    println!("--- needle::find vs memchr::memmem (8 MB of NDJSON logs) ---");
+    let haystack = ndjson(Size::Bytes(8 << 20), 1);
+    let needles: [&[u8]; 4] = [
+        b"ERROR",
+        b"\"service\":\"billing\"",
+        b"/invoices/42",
+        b"timeout",
+    ];
+
+    for needle in needles {
+        let finder = memchr::memmem::Finder::new(needle);
+        let count = count_all(&haystack, |h| finder.find(h));
+        assert_eq!(count_all(&haystack, |h| needle::find(h, needle)), count);
+        let case = format!("{}-byte needle, {} matches", needle.len(), count);
+        compare(
+            results,
+            ("find", &case),
+            || count_all(&haystack, |h| needle::find(h, needle)),
+            ("memchr::memmem", || count_all(&haystack, |h| finder.find(h))),
+            haystack.len(),
+        );
+    }
+    println!();
+}
+
+/// Fields and rows, the way the csv crate is usually read.
+fn csv_crate_counts(data: &[u8]) -> (usize, usize) {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(data);
+    let mut record = csv::ByteRecord::new();
+    let (mut fields, mut rows) = (0, 0);
+    while reader.read_byte_record(&mut record).unwrap() {
+        fields += record.len();
+        rows += 1;
+    }
+    (fields, rows)
+}
+
+fn csv_reader_counts(data: &[u8]) -> (usize, usize) {
+    let mut reader = CsvReader::new(data);
+    let (mut fields, mut rows) = (0, 0);
+    while let Some(record) = reader.next_record() {
+        fields += record.len();
+        rows += 1;
+    }
+    (fields, rows)
+}
+
+fn csv(results: &mut Vec<Comparison>) {
+    println!("--- CsvReader and parse_csv_index vs the csv crate ---");
+    for (fixture, data) in fixtures(200_000) {
+        let expected = csv_crate_counts(&data);
+        // The csv crate strips the BOM itself; CsvReader leaves it to the caller
+        let unbommed = strip_bom(&data);
+        assert_eq!(csv_reader_counts(unbommed), expected, "CsvReader on {}", fixture);
+        assert_eq!(parse_csv_index(&data), expected, "parse_csv_index on {}", fixture);
+
+        let case = format!("{}, {:.1} MB", fixture, data.len() as f64 / 1e6);
+        compare(
+            results,
+            ("CsvReader", &case),
+            || csv_reader_counts(unbommed),
+            ("csv crate", || csv_crate_counts(&data)),
+            data.len(),
+        );
+        compare(
+            results,
+            ("parse_csv_index", &case),
+            || parse_csv_index(&data),
+            ("csv crate", || csv_crate_counts(&data)),
+            data.len(),
+        );
+    }
+    println!();
+}
+
+fn escape(results: &mut Vec<Comparison>) {
+    println!("--- escape_json_into vs serde_json (1 MB strings) ---");
+    let size = 1 << 20;
+    let printable = (32..127u8).filter(|&b| b != b'"' && b != b'\\');
+    let logs = ndjson(Size::Bytes(size), 2);
+    let inputs: [(&str, String); 3] = [
+        ("clean", printable.map(char::from).cycle().take(size).collect()),
+        ("NDJSON logs", String::from_utf8(logs).expect("ndjson is UTF-8")),
+        ("all escapes", "\n".repeat(size)),
+    ];
+
+    // Both into a reused buffer, quotes included
+    let ours = |s: &str, out: &mut Vec<u8>| {
+        out.clear();
+        out.push(b'"');
+        escape_json_into(s.as_bytes(), out);
+        out.push(b'"');
+        out.len()
+    };
+    let theirs = |s: &str, out: &mut Vec<u8>| {
+        out.clear();
+        serde_json::to_writer(&mut *out, s).unwrap();
+        out.len()
+    };
+    for (case, input) in &inputs {
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        ours(input, &mut a);
+        theirs(input, &mut b);
+        assert_eq!(a, b, "escape_json_into and serde_json disagree on {}", case);
+        compare(
+            results,
+            ("escape_json", case),
+            || ours(input, &mut a),
+            ("serde_json", || theirs(input, &mut b)),
+            input.len(),
+        );
+    }
+    println!();
+}
+
+/// A line feed after every full line, as `insert_line_feed` does.
+fn wrap_chunks(input: &[u8], k: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len() + input.len() / k);
+    for line in input.chunks(k) {
+        out.extend_from_slice(line);
+        if line.len() == k {
+            out.push(b'\n');
+        }
+    }
+    out
+}
+
+fn base64_mime(results: &mut Vec<Comparison>) {
+    println!("--- MIME base64: base64 crate, wrapped by insert_line_feed or chunks(76) ---");
+    let data: Vec<u8> = (0..3 << 20)
+        .map(|i: u32| (i.wrapping_mul(2654435761) >> 24) as u8)
+        .collect();
+    let engine = base64::engine::general_purpose::STANDARD;
+    let encoded = engine.encode(&data);
+    assert_eq!(
+        insert_line_feed(encoded.as_bytes(), MIME_LINE),
+        wrap_chunks(encoded.as_bytes(), MIME_LINE)
+    );
+
+    let encode = bench_labeled(
+        "  base64 crate (encode only)",
+        &Labels::new("base64_mime", "base64 crate"),
+        || engine.encode(&data),
+        data.len(),
+    );
+    let (ours, theirs) = compare(
+        results,
+        ("base64_mime", "encode + wrap"),
+        || insert_line_feed(engine.encode(&data).as_bytes(), MIME_LINE),
+        ("chunks(76)", || wrap_chunks(engine.encode(&data).as_bytes(), MIME_LINE)),
+        data.len(),
+    );
+    // Times per byte add up: the wrap is what encoding alone does not take
+    let share = |wrapped: f64| 100.0 * (1.0 - wrapped / encode);
+    println!(
+        "{:30} wrap share: {:.0}% with insert_line_feed, {:.0}% with chunks(76)",
+        "",
+        share(ours),
+        share(theirs)
+    );
+    println!();
+}
+
+fn main() {
+    println!("=== Our kernels vs the reference crates ===\n");
+    let mut results = Vec::new();
+    find(&mut results);
+    csv(&mut results);
+    escape(&mut results);
+    base64_mime(&mut results);
+
+    println!("=== Summary: ours / reference (above 1: ours is faster) ===");
+    for c in &results {
+        println!("  {:16} {:36} vs {:16} {:.2}x", c.kernel, c.case, c.reference, c.ratio);
+    }
+}
@@ -0,0 +1,147 @@
+//! The comparisons of the plain benches under Criterion: many samples per
+//! measurement, outliers flagged, changes reported against the previous run,
+//! HTML reports in `target/criterion`.
+//!
+//!   cargo bench --bench criterion_bench                 all four groups
+//!   cargo bench --bench criterion_bench -- csv          one group
+//!
+//! The plain benches (`cargo bench --bench csv_parse_bench`, ...) stay for a
+//! quick table in the terminal.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use scratchpad::csv_index::parse_csv_index;
+use scratchpad::csv_parse_buffer_size_impact::{
+    count_pattern_matches_from_file, count_pattern_matches_in_memory,
+    count_pattern_matches_parallel, count_pattern_matches_prefetch, for_each_buffer,
+};
+use scratchpad::csv_state_machine::{parse_csv_hybrid, parse_csv_if_else, parse_csv_state_machine};
+use scratchpad::datagen::fixtures;
+use scratchpad::json_escape_SWAR::{
+    count_escapable_bytes, count_escapable_bytes_scalar, escape_json, escape_json_scalar,
+};
+use scratchpad::line_feed_every_k_bytes::{insert_line_feed, insert_line_feed_scalar};
+use std::fs::{self, File};
+use std::ops::ControlFlow;
+
+const TEST_FILE: &str = "/tmp/test_criterion.csv";
+
+type Counter = fn(&[u8]) -> (usize, usize);
+
+/// Scalar against the best version for this CPU (NEON on aarch64).
+fn line_feed(c: &mut Criterion) {
+    let mut group = c.benchmark_group("line_feed");
+    for size in [4 << 10, 1 << 20] {
+        let input: Vec<u8> = (0..size).map(|i| (i % 256) as u8).collect();
+        group.throughput(Throughput::Bytes(size as u64));
+
+        for k in [32, 64, 72, 128] {
+            let parameter = format!("{} KB, K={}", size >> 10, k);
+            group.bench_with_input(BenchmarkId::new("scalar", &parameter), &input, |b, input| {
+                b.iter(|| insert_line_feed_scalar(input, k))
+            });
+            group.bench_with_input(BenchmarkId::new("best", &parameter), &input, |b, input| {
+                b.iter(|| insert_line_feed(input, k))
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Counting and escaping, scalar against SWAR, from no escapes to nothing
+/// but escapes.
+fn json_escape(c: &mut Criterion) {
+    let size = 1 << 20;
+    let printable = (32..127).filter(|&b| b != b'"' && b != b'\\');
+    let quotes = (0..size).map(|i| if i % 100 == 10 { b'"' } else { b'A' });
+    let inputs: [(&str, Vec<u8>); 3] = [
+        ("clean", printable.cycle().take(size).collect()),
+        ("1% escapes", quotes.collect()),
+        ("all escapes", vec![b'\n'; size]),
+    ];
+
+    let mut group = c.benchmark_group("json_escape");
+    group.throughput(Throughput::Bytes(size as u64));
+    for (name, input) in &inputs {
+        group.bench_with_input(BenchmarkId::new("count scalar", name), input, |b, input| {
+            b.iter(|| count_escapable_bytes_scalar(input))
+        });
+        group.bench_with_input(BenchmarkId::new("count SWAR", name), input, |b, input| {
+            b.iter(|| count_escapable_bytes(input))
+        });
+        group.bench_with_input(BenchmarkId::new("escape scalar", name), input, |b, input| {
+            b.iter(|| escape_json_scalar(input))
+        });
+        group.bench_with_input(BenchmarkId::new("escape SWAR", name), input, |b, input| {
+            b.iter(|| escape_json(input))
+        });
+    }
+    group.finish();
+}
+
+/// The field and row counters on the fixtures of the differential test.
+fn csv(c: &mut Criterion) {
+    let parsers: [(&str, Counter); 4] = [
+        ("state machine", parse_csv_state_machine),
+        ("hybrid", parse_csv_hybrid),
+        ("if/else", parse_csv_if_else),
+        ("index", parse_csv_index),
+    ];
+
+    let mut group = c.benchmark_group("csv");
+    for (fixture, data) in fixtures(20_000) {
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        for (parser, count) in parsers {
+            group.bench_with_input(BenchmarkId::new(parser, fixture), &data[..], |b, data| {
+                b.iter(|| count(data))
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Pattern counts over a warm 14 MB file, and the read loop alone for the
+/// buffer sizes of `buffer_size_bench`.
+fn io(c: &mut Criterion) {
+    let data = &fixtures(300_000)[0].1;
+    fs::write(TEST_FILE, data).expect("Failed to write test file");
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+
+    let mut group = c.benchmark_group("io");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.sample_size(30);
+
+    group.bench_function("count from file", |b| {
+        b.iter(|| count_pattern_matches_from_file(TEST_FILE, b"Harvard").unwrap())
+    });
+    group.bench_function("count in memory", |b| {
+        b.iter(|| count_pattern_matches_in_memory(TEST_FILE, b"Harvard").unwrap())
+    });
+    group.bench_function("count prefetch", |b| {
+        b.iter(|| count_pattern_matches_prefetch(TEST_FILE, b"Harvard").unwrap())
+    });
+    group.bench_function(BenchmarkId::new("count parallel", threads), |b| {
+        b.iter(|| count_pattern_matches_parallel(TEST_FILE, b"Harvard", threads).unwrap())
+    });
+
+    for buffer_size in [4 << 10, 16 << 10, 64 << 10, 256 << 10, 1 << 20] {
+        let id = BenchmarkId::new("read loop", format!("{} KB", buffer_size >> 10));
+        group.bench_function(id, |b| {
+            b.iter(|| {
+                let mut file = File::open(TEST_FILE).unwrap();
+                let mut lines = 0;
+                for_each_buffer(&mut file, buffer_size, |buffer| {
+                    lines += memchr::memchr_iter(b'\n', buffer).count();
+                    ControlFlow::Continue(())
+                })
+                .unwrap();
+                lines
+            })
+        });
+    }
+    group.finish();
+
+    let _ = fs::remove_file(TEST_FILE);
+}
+
+criterion_group!(benches, line_feed, json_escape, csv, io);
+criterion_main!(benches);
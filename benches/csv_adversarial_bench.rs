@@ -1,116 +1,20 @@
-use std::time::Instant;
-use std::fs::{self, File};
-use std::io::Write;
+use scratchpad::bench::bench_with_timing;
+use scratchpad::csv_index::csv_stats;
+use scratchpad::datagen::{adversarial_csv, predictable_csv, random_pattern_csv, Size};
 use scratchpad::csv_state_machine::{parse_csv_state_machine, parse_csv_if_else};
 
-fn bench_with_timing(name: &str, f: impl Fn() -> (usize, usize), iterations: usize, input_size: usize) -> f64 {
-    // Warmup
-    for _ in 0..10 {
-        std::hint::black_box(f());
-    }
-
-    let start = Instant::now();
-    let mut total_bytes = 0;
-
-    for _ in 0..iterations {
-        let result = f();
-        total_bytes += input_size;
-        std::hint::black_box(result);
-    }
-
-    let elapsed = start.elapsed();
-    let elapsed_secs = elapsed.as_secs_f64();
-    let throughput_gb_s = (total_bytes as f64 / elapsed_secs) / 1_000_000_000.0;
-
+fn print_profile(data: &[u8]) {
+    let stats = csv_stats(data);
     println!(
-        "{:30} {:.2} ms total, {:.2} GB/s throughput",
-        format!("{}:", name),
-        elapsed_secs * 1000.0,
-        throughput_gb_s
+        "Profile: {} rows, {} fields, field len {}..{} (mean {:.1}), {:.0}% quoted, longest row {} B\n",
+        stats.rows,
+        stats.fields,
+        stats.min_field_len,
+        stats.max_field_len,
+        stats.mean_field_len(),
+        stats.quoted_ratio() * 100.0,
+        stats.longest_row
     );
-
-    throughput_gb_s
-}
-
-fn write_predictable_csv(file_path: &str, num_rows: usize) -> std::io::Result<()> {
-    let mut file = File::create(file_path)?;
-    writeln!(file, "Name,University,Year,GPA,Major")?;
-
-    for i in 0..num_rows {
-        writeln!(
-            file,
-            "Alice,\"Harvard University\",{},{:.2},\"Computer Science\"",
-            2020 + (i % 5),
-            3.0 + ((i % 10) as f64 / 10.0)
-        )?;
-    }
-
-    Ok(())
-}
-
-fn write_adversarial_csv(file_path: &str, num_rows: usize, seed: u64) -> std::io::Result<()> {
-    let mut file = File::create(file_path)?;
-    writeln!(file, "Name,University,Year,GPA,Major")?;
-
-    let mut rng = seed;
-    let mut next_random = || {
-        rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
-        rng
-    };
-
-    for _ in 0..num_rows {
-        let pattern = next_random() % 8;
-
-        match pattern {
-            0 => writeln!(file, "\"A,B\",C,2020,3.5,\"X,Y\"")?,
-            1 => writeln!(file, "\"Alice\"\"Bob\",\"Har\"\"vard\",2021,3.6,CS")?,
-            2 => writeln!(file, "A,B,C,D,E")?,
-            3 => writeln!(file, "\"A\",B,\"C\",D,\"E\"")?,
-            4 => writeln!(file, ",\"Harvard\",,3.7,")?,
-            5 => writeln!(file, "\"Line1\nLine2\",MIT,2022,3.8,\"Math\nPhysics\"")?,
-            6 => writeln!(file, "\"A,B\nC\",\"D\"\"E\",2023,3.9,\"F,G\nH\"")?,
-            7 => writeln!(file, "Normal,Harvard,2024,4.0,Engineering")?,
-            _ => unreachable!(),
-        }
-    }
-
-    Ok(())
-}
-
-fn write_random_pattern_csv(file_path: &str, num_rows: usize, seed: u64) -> std::io::Result<()> {
-    let mut file = File::create(file_path)?;
-    writeln!(file, "A,B,C,D,E")?;
-
-    let mut rng = seed;
-    let mut next_random = || {
-        rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
-        (rng >> 16) as u8
-    };
-
-    for _ in 0..num_rows {
-        for field_idx in 0..5 {
-            if field_idx > 0 {
-                write!(file, ",")?;
-            }
-
-            let r = next_random();
-
-            if r < 50 {
-                // Empty field
-            } else if r < 100 {
-                write!(file, "X{}", r)?;
-            } else if r < 150 {
-                write!(file, "\"A,{}\"", r)?;
-            } else if r < 200 {
-                write!(file, "\"A\"\"{}\"", r)?;
-            } else {
-                write!(file, "\"A\n{}\"", r)?;
-            }
-        }
-        writeln!(file)?;
-    }
-
-    Ok(())
 }
 
 fn main() {
@@ -118,54 +22,45 @@ fn main() {
     println!("KWIllets' approach: Minimize branches with table-driven DFA");
     println!("https://lemire.me/blog/2008/12/19/parsing-csv-files-is-cpu-bound-a-c-test-case-update-2/\n");
 
-    let iterations = 100;
-
     // Test 1: Predictable CSV
     println!("--- Test 1: Predictable CSV (10,000 rows) ---");
     println!("(Same structure every row - ideal for branch prediction)\n");
 
-    let predictable_file = "/tmp/test_predictable.csv";
-    write_predictable_csv(predictable_file, 10_000).expect("Failed to write file");
-    let predictable_data = fs::read(predictable_file).unwrap();
+    let predictable_data = predictable_csv(Size::Rows(10_000));
     let predictable_size = predictable_data.len();
+    print_profile(&predictable_data);
 
     let sm_throughput = bench_with_timing(
         "State Machine",
         || parse_csv_state_machine(&predictable_data),
-        iterations,
         predictable_size,
     );
 
     let ie_throughput = bench_with_timing(
         "If/Else",
         || parse_csv_if_else(&predictable_data),
-        iterations,
         predictable_size,
     );
 
     println!("If/Else advantage: {:.2}x faster\n", ie_throughput / sm_throughput);
-    let _ = fs::remove_file(predictable_file);
 
     // Test 2: Adversarial CSV with 8 random patterns
     println!("--- Test 2: Adversarial CSV (10,000 rows) ---");
     println!("(8 different patterns pseudo-randomly mixed)\n");
 
-    let adversarial_file = "/tmp/test_adversarial.csv";
-    write_adversarial_csv(adversarial_file, 10_000, 12345).expect("Failed to write file");
-    let adversarial_data = fs::read(adversarial_file).unwrap();
+    let adversarial_data = adversarial_csv(Size::Rows(10_000), 12345);
     let adversarial_size = adversarial_data.len();
+    print_profile(&adversarial_data);
 
     let sm_throughput = bench_with_timing(
         "State Machine",
         || parse_csv_state_machine(&adversarial_data),
-        iterations,
         adversarial_size,
     );
 
     let ie_throughput = bench_with_timing(
         "If/Else",
         || parse_csv_if_else(&adversarial_data),
-        iterations,
         adversarial_size,
     );
 
@@ -175,28 +70,24 @@ fn main() {
     } else {
         println!("If/Else still faster: {:.2}x\n", ie_throughput / sm_throughput);
     }
-    let _ = fs::remove_file(adversarial_file);
 
     // Test 3: Most adversarial - random per field
     println!("--- Test 3: Random Pattern CSV (10,000 rows) ---");
     println!("(Maximum unpredictability - random decision per field)\n");
 
-    let random_file = "/tmp/test_random.csv";
-    write_random_pattern_csv(random_file, 10_000, 54321).expect("Failed to write file");
-    let random_data = fs::read(random_file).unwrap();
+    let random_data = random_pattern_csv(Size::Rows(10_000), 54321);
     let random_size = random_data.len();
+    print_profile(&random_data);
 
     let sm_throughput = bench_with_timing(
         "State Machine",
         || parse_csv_state_machine(&random_data),
-        iterations,
         random_size,
     );
 
     let ie_throughput = bench_with_timing(
         "If/Else",
         || parse_csv_if_else(&random_data),
-        iterations,
         random_size,
     );
 
@@ -206,29 +97,25 @@ fn main() {
     } else {
         println!("If/Else still faster: {:.2}x\n", ie_throughput / sm_throughput);
     }
-    let _ = fs::remove_file(random_file);
 
     // Test 4: Large file
     println!("--- Test 4: Large File (100,000 rows) ---");
     println!("(Testing scalability with adversarial patterns)\n");
 
-    let large_file = "/tmp/test_large_adversarial.csv";
-    write_adversarial_csv(large_file, 100_000, 99999).expect("Failed to write file");
-    let large_data = fs::read(large_file).unwrap();
+    let large_data = adversarial_csv(Size::Rows(100_000), 99999);
     let large_size = large_data.len();
+    print_profile(&large_data);
     println!("File size: {:.2} MB\n", large_size as f64 / 1_000_000.0);
 
     let sm_throughput = bench_with_timing(
         "State Machine",
         || parse_csv_state_machine(&large_data),
-        20,
         large_size,
     );
 
     let ie_throughput = bench_with_timing(
         "If/Else",
         || parse_csv_if_else(&large_data),
-        20,
         large_size,
     );
 
@@ -238,7 +125,6 @@ fn main() {
     } else {
         println!("If/Else still faster: {:.2}x\n", ie_throughput / sm_throughput);
     }
-    let _ = fs::remove_file(large_file);
 
     println!("\n=== Summary ===\n");
     println!("📊 Results:");
@@ -0,0 +1,69 @@
+use scratchpad::bench::bench_with_timing;
+use scratchpad::csv_index::{parse_csv_index, parse_csv_parallel};
+use scratchpad::csv_reader::CsvReader;
+use scratchpad::csv_state_machine::{parse_csv_hybrid, parse_csv_if_else, parse_csv_state_machine};
+use scratchpad::datagen::fixtures;
+
+type Counter<'a> = &'a dyn Fn(&[u8]) -> (usize, usize);
+
+/// The usual way to count with the csv crate: one reused ByteRecord.
+fn csv_crate_counts(data: &[u8]) -> (usize, usize) {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(data);
+    let mut record = csv::ByteRecord::new();
+    let (mut fields, mut rows) = (0, 0);
+    while reader.read_byte_record(&mut record).unwrap() {
+        fields += record.len();
+        rows += 1;
+    }
+    (fields, rows)
+}
+
+fn csv_reader_counts(data: &[u8]) -> (usize, usize) {
+    let mut reader = CsvReader::new(data);
+    let (mut fields, mut rows) = (0, 0);
+    while let Some(record) = reader.next_record() {
+        fields += record.len();
+        rows += 1;
+    }
+    (fields, rows)
+}
+
+fn main() {
+    println!("=== Differential: this crate vs the csv crate ===\n");
+    println!("Identical fixtures (datagen::fixtures), counts checked before timing.");
+    println!("Ratios are relative to csv::Reader with a reused ByteRecord.\n");
+
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+
+    for (name, data) in fixtures(200_000) {
+        println!("--- {} ({:.2} MB) ---", name, data.len() as f64 / 1_000_000.0);
+
+        let expected = csv_crate_counts(&data);
+        let parsers: [(&str, Counter); 6] = [
+            ("State Machine", &parse_csv_state_machine),
+            ("Hybrid", &parse_csv_hybrid),
+            ("If/Else", &parse_csv_if_else),
+            ("Index", &parse_csv_index),
+            ("Index (parallel)", &|d| parse_csv_parallel(d, threads)),
+            ("CsvReader", &csv_reader_counts),
+        ];
+
+        let reference =
+            bench_with_timing("csv crate", || csv_crate_counts(&data), data.len());
+        for (parser, f) in parsers {
+            // The csv crate strips the BOM itself; CsvReader leaves it to the caller
+            let input = if parser == "CsvReader" {
+                scratchpad::csv_dialect::strip_bom(&data)
+            } else {
+                &data[..]
+            };
+            assert_eq!(f(input), expected, "{} disagrees with the csv crate on {}", parser, name);
+            let throughput = bench_with_timing(parser, || f(input), data.len());
+            println!("{:30} {:.2}x", "", throughput / reference);
+        }
+        println!();
+    }
+}
@@ -1,36 +1,10 @@
-use std::time::Instant;
 use std::fs::{self, File};
 use std::io::Write;
+use scratchpad::bench::bench_with_timing;
+use scratchpad::cpu_info::optimal_buffer_size;
 use scratchpad::csv_parse_buffer_size_impact::count_pattern_matches_from_file;
-
-fn bench_with_timing(name: &str, f: impl Fn() -> usize, iterations: usize, input_size: usize) -> f64 {
-    // Warmup
-    for _ in 0..10 {
-        std::hint::black_box(f());
-    }
-
-    let start = Instant::now();
-    let mut total_bytes = 0;
-
-    for _ in 0..iterations {
-        let result = f();
-        total_bytes += input_size;
-        std::hint::black_box(result);
-    }
-
-    let elapsed = start.elapsed();
-    let elapsed_secs = elapsed.as_secs_f64();
-    let throughput_gb_s = (total_bytes as f64 / elapsed_secs) / 1_000_000_000.0;
-
-    println!(
-        "{:30} {:.2} ms total, {:.2} GB/s throughput",
-        format!("{}:", name),
-        elapsed_secs * 1000.0,
-        throughput_gb_s
-    );
-
-    throughput_gb_s
-}
+use scratchpad::csv_reader::{parse_csv_sample, CsvReader};
+use scratchpad::needle;
 
 fn write_csv_to_file(file_path: &str, num_rows: usize) -> std::io::Result<()> {
     let mut file = File::create(file_path)?;
@@ -61,7 +35,7 @@ fn write_csv_to_file(file_path: &str, num_rows: usize) -> std::io::Result<()> {
 fn main() {
     println!("=== CSV Pattern Matching Benchmarks (Blog Post Method) ===\n");
     println!("Matches the blog post exactly:");
-    println!("  - 4KB fixed buffer");
+    println!("  - Fixed buffer ({} KB here, 4 KB in the post)", optimal_buffer_size() / 1024);
     println!("  - memchr (like Array.IndexOf) to find first byte");
     println!("  - Check if tail bytes match");
     println!("  - Handle buffer boundary with offset\n");
@@ -75,8 +49,6 @@ fn main() {
     println!("Test file created: {}", test_file);
     println!("File size: {:.2} MB\n", file_size as f64 / 1_000_000.0);
 
-    let iterations = 100;
-
     // Test 1: Small file
     println!("--- Small file (1,000 rows, ~50 KB) ---");
     let small_file = "/tmp/test_small.csv";
@@ -84,9 +56,8 @@ fn main() {
     let small_size = fs::metadata(small_file).unwrap().len();
 
     bench_with_timing(
-        "Disk (4KB buf + memchr)",
+        "Disk (buffered)",
         || count_pattern_matches_from_file(small_file, b"Harvard").unwrap(),
-        iterations * 10,
         small_size as usize,
     );
     println!();
@@ -100,9 +71,8 @@ fn main() {
     let medium_size = fs::metadata(medium_file).unwrap().len();
 
     bench_with_timing(
-        "Disk (4KB buf + memchr)",
+        "Disk (buffered)",
         || count_pattern_matches_from_file(medium_file, b"Harvard").unwrap(),
-        iterations * 2,
         medium_size as usize,
     );
     println!();
@@ -114,9 +84,27 @@ fn main() {
     println!("  (Blog post used 217,096 rows, 11 MB)\n");
 
     bench_with_timing(
-        "Disk (4KB buf + memchr)",
+        "Disk (buffered)",
         || count_pattern_matches_from_file(test_file, b"Harvard").unwrap(),
-        iterations,
+        file_size as usize,
+    );
+
+    // Same count, but only where the University column is Harvard
+    let data = fs::read(test_file).unwrap();
+    bench_with_timing(
+        "Reader (University == Harvard)",
+        || {
+            let mut reader = CsvReader::new(&data);
+            reader.headers();
+            reader
+                .filter_records(|r| r.get_named("University") == Some(b"Harvard"))
+                .count()
+        },
+        file_size as usize,
+    );
+    bench_with_timing(
+        "Reader (every 100th record)",
+        || parse_csv_sample(&data, 100).filter(|r| r.get(2) == Some(b"Harvard")).count(),
         file_size as usize,
     );
     println!();
@@ -126,6 +114,7 @@ fn main() {
     let patterns = vec![
         (b"H" as &[u8], "Single char"),
         (b"MIT" as &[u8], "3 chars"),
+        (b"Mathematics" as &[u8], "11 chars, common first byte"),
         (b"Harvard" as &[u8], "7 chars"),
         (b"Computer Science" as &[u8], "16 chars"),
     ];
@@ -134,11 +123,28 @@ fn main() {
         println!("  Pattern: {} ({})", desc, std::str::from_utf8(pattern).unwrap());
 
         bench_with_timing(
-            "    memchr",
+            "    Disk (buffered)",
             || count_pattern_matches_from_file(test_file, pattern).unwrap(),
-            iterations / 2,
             file_size as usize,
         );
+        // Every occurrence in memory, with each candidate search
+        for (name, find) in [
+            ("    memchr + memcmp (memory)", needle::find_memchr as fn(&[u8], &[u8]) -> _),
+            ("    first+last (memory)", needle::find),
+        ] {
+            bench_with_timing(
+                name,
+                || {
+                    let (mut count, mut i) = (0, 0);
+                    while let Some(pos) = find(&data[i..], pattern) {
+                        count += 1;
+                        i += pos + 1;
+                    }
+                    count
+                },
+                file_size as usize,
+            );
+        }
 
         println!();
     }
@@ -153,8 +159,9 @@ fn main() {
     println!("  NReco.Csv library:     0.33 GB/s");
     println!("  Sep library:           0.64 GB/s");
     println!("  Low-level byte search: 3.5 GB/s");
-    println!("\nOur Rust implementation (memchr-based, 4KB buffers):");
+    let buffer_kb = optimal_buffer_size() / 1024;
+    println!("\nOur Rust implementation (memchr-based, {} KB buffers):", buffer_kb);
     println!("  Uses memchr (Rust's optimized equivalent to Array.IndexOf)");
-    println!("  Reads from disk with 4KB fixed buffers");
+    println!("  Reads from disk with fixed buffers sized from the L1 data cache");
     println!("  Handles buffer boundaries with offset mechanism");
 }
@@ -1,36 +1,11 @@
-use std::time::Instant;
 use std::fs::{self, File};
 use std::io::Write;
-use scratchpad::csv_state_machine::{parse_csv_state_machine, parse_csv_if_else};
-
-fn bench_with_timing(name: &str, f: impl Fn() -> (usize, usize), iterations: usize, input_size: usize) -> f64 {
-    // Warmup
-    for _ in 0..10 {
-        std::hint::black_box(f());
-    }
-
-    let start = Instant::now();
-    let mut total_bytes = 0;
-
-    for _ in 0..iterations {
-        let result = f();
-        total_bytes += input_size;
-        std::hint::black_box(result);
-    }
-
-    let elapsed = start.elapsed();
-    let elapsed_secs = elapsed.as_secs_f64();
-    let throughput_gb_s = (total_bytes as f64 / elapsed_secs) / 1_000_000_000.0;
-
-    println!(
-        "{:30} {:.2} ms total, {:.2} GB/s throughput",
-        format!("{}:", name),
-        elapsed_secs * 1000.0,
-        throughput_gb_s
-    );
-
-    throughput_gb_s
-}
+use scratchpad::bench::bench_with_timing;
+use scratchpad::csv_index::{count_csv_rows, parse_csv_index, parse_csv_parallel};
+use scratchpad::csv_dialect::Dialect;
+use scratchpad::csv_state_machine::{
+    parse_csv_hybrid, parse_csv_if_else, parse_csv_state_machine, CsvStateMachine,
+};
 
 fn write_csv_to_file(file_path: &str, num_rows: usize) -> std::io::Result<()> {
     let mut file = File::create(file_path)?;
@@ -59,20 +34,18 @@ fn write_csv_to_file(file_path: &str, num_rows: usize) -> std::io::Result<()> {
 }
 
 fn main() {
-    println!("=== CSV Parsing Benchmarks: State Machine vs If/Else ===\n");
+    println!("=== CSV Parsing Benchmarks: State Machine vs If/Else vs Index ===\n");
     println!("Comparing KWIllets' table-driven DFA against simple if/else logic\n");
 
-    let iterations = 100;
-
     // Test different file sizes
     let sizes = vec![
-        (1_000, "1K rows (~50 KB)", 200),
-        (10_000, "10K rows (~500 KB)", 100),
-        (50_000, "50K rows (~2.5 MB)", 50),
-        (200_000, "200K rows (~10 MB)", 20),
+        (1_000, "1K rows (~50 KB)"),
+        (10_000, "10K rows (~500 KB)"),
+        (50_000, "50K rows (~2.5 MB)"),
+        (200_000, "200K rows (~10 MB)"),
     ];
 
-    for (num_rows, desc, iter) in sizes {
+    for (num_rows, desc) in sizes {
         println!("--- {} ---\n", desc);
         let test_file = format!("/tmp/test_csv_{}.csv", num_rows);
         write_csv_to_file(&test_file, num_rows).expect("Failed to write file");
@@ -82,18 +55,58 @@ fn main() {
         let sm_throughput = bench_with_timing(
             "State Machine",
             || parse_csv_state_machine(&data),
-            iter,
+            size,
+        );
+
+        let classified = CsvStateMachine::new(&Dialect::CSV).unwrap();
+        bench_with_timing(
+            "State Machine (u8 flat)",
+            || classified.count_flat(&data),
+            size,
+        );
+        let classified_throughput = bench_with_timing(
+            "State Machine (classified)",
+            || classified.count_classified(&data),
+            size,
+        );
+
+        let hybrid_throughput = bench_with_timing(
+            "Hybrid (memchr skips)",
+            || parse_csv_hybrid(&data),
             size,
         );
 
         let ie_throughput = bench_with_timing(
             "If/Else",
             || parse_csv_if_else(&data),
-            iter,
             size,
         );
 
-        println!("Winner: If/Else ({:.2}x faster)\n", ie_throughput / sm_throughput);
+        let idx_throughput = bench_with_timing(
+            "Two-pass Index",
+            || parse_csv_index(&data),
+            size,
+        );
+
+        let rows_throughput = bench_with_timing(
+            "Rows only (pass 1)",
+            || (0, count_csv_rows(&data)),
+            size,
+        );
+
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let par_throughput = bench_with_timing(
+            &format!("Index ({} threads)", threads),
+            || parse_csv_parallel(&data, threads),
+            size,
+        );
+
+        println!("Flat vs classified table: {:.2}x", sm_throughput / classified_throughput);
+        println!("Hybrid vs State Machine: {:.2}x", hybrid_throughput / sm_throughput);
+        println!("If/Else vs State Machine: {:.2}x", ie_throughput / sm_throughput);
+        println!("Index vs If/Else: {:.2}x", idx_throughput / ie_throughput);
+        println!("Rows only vs Index: {:.2}x", rows_throughput / idx_throughput);
+        println!("Parallel vs Index: {:.2}x\n", par_throughput / idx_throughput);
         let _ = fs::remove_file(&test_file);
     }
 
@@ -103,5 +116,9 @@ fn main() {
     println!("  - Modern CPUs have excellent branch predictors");
     println!("  - Predictable CSV structure helps branch prediction");
     println!("  - Table lookups have overhead");
+    println!("\nThe two-pass index (csv_index) skips the per-byte loop entirely:");
+    println!("  - 64 bytes classified per block with NEON/AVX2 compares");
+    println!("  - prefix_xor of the quote mask hides separators inside quotes");
+    println!("  - only the set bits (field ends) are visited in the second pass");
     println!("\nSee csv_adversarial_bench for unpredictable data patterns!");
 }
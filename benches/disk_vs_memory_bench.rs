@@ -1,7 +1,10 @@
-use std::time::Instant;
 use std::fs::{self, File};
 use std::io::Write;
-use scratchpad::csv_parse_buffer_size_impact::{count_pattern_matches_from_file, count_pattern_matches_in_memory};
+use scratchpad::bench::{measure_for, target_time};
+use scratchpad::cpu_info::optimal_buffer_size;
+use scratchpad::csv_parse_buffer_size_impact::{
+    count_pattern_matches_from_file, count_pattern_matches_in_memory, count_record_matches,
+};
 
 fn write_test_file(file_path: &str, num_rows: usize) -> std::io::Result<()> {
     let mut file = File::create(file_path)?;
@@ -16,22 +19,9 @@ fn write_test_file(file_path: &str, num_rows: usize) -> std::io::Result<()> {
     Ok(())
 }
 
-fn bench(name: &str, f: impl Fn() -> usize, iterations: usize, file_size: u64) -> (f64, f64) {
-    // Warmup
-    for _ in 0..10 {
-        std::hint::black_box(f());
-    }
-
-    let start = Instant::now();
-    for _ in 0..iterations {
-        let result = f();
-        std::hint::black_box(result);
-    }
-    let elapsed = start.elapsed().as_secs_f64();
-
-    let total_bytes = file_size as f64 * iterations as f64;
-    let throughput = total_bytes / elapsed / 1_000_000_000.0;
-    let time_per_op = (elapsed / iterations as f64) * 1000.0; // ms
+fn bench(name: &str, f: impl Fn() -> usize, file_size: u64) -> (f64, f64) {
+    let measurement = measure_for(f, target_time(), |_| file_size as usize);
+    let (throughput, time_per_op) = (measurement.throughput_gb_s(), measurement.ms_per_run());
 
     println!("{:30} {:>10.2} ms/op, {:>8.2} GB/s", name, time_per_op, throughput);
 
@@ -43,13 +33,13 @@ fn main() {
 
     // Test different file sizes
     let test_cases = vec![
-        ("Small (1K rows, ~50 KB)", 1_000, 1000),
-        ("Medium (10K rows, ~500 KB)", 10_000, 200),
-        ("Large (100K rows, ~5 MB)", 100_000, 100),
-        ("XLarge (200K rows, ~10 MB)", 200_000, 50),
+        ("Small (1K rows, ~50 KB)", 1_000),
+        ("Medium (10K rows, ~500 KB)", 10_000),
+        ("Large (100K rows, ~5 MB)", 100_000),
+        ("XLarge (200K rows, ~10 MB)", 200_000),
     ];
 
-    for (desc, num_rows, iterations) in test_cases {
+    for (desc, num_rows) in test_cases {
         let test_file = format!("/tmp/test_{}.csv", num_rows);
 
         println!("--- {} ---", desc);
@@ -58,16 +48,20 @@ fn main() {
         println!("  File size: {:.2} MB", file_size as f64 / 1_000_000.0);
 
         let (throughput_disk, time_disk) = bench(
-            "Disk (4KB buffered)",
+            "Disk (buffered)",
             || count_pattern_matches_from_file(&test_file, b"Harvard").unwrap(),
-            iterations,
             file_size,
         );
 
         let (throughput_mem, time_mem) = bench(
             "In-Memory (load all)",
             || count_pattern_matches_in_memory(&test_file, b"Harvard").unwrap(),
-            iterations,
+            file_size,
+        );
+
+        bench(
+            "In-Memory (quote-aware)",
+            || count_record_matches(&test_file, b"Harvard").unwrap(),
             file_size,
         );
 
@@ -81,11 +75,12 @@ fn main() {
     }
 
     println!("\n=== Analysis ===");
-    println!("\nDisk Buffering (4KB):");
-    println!("  ✓ Low memory footprint (4 KB buffer)");
+    let buffer_kb = optimal_buffer_size() / 1024;
+    println!("\nDisk Buffering ({}KB):", buffer_kb);
+    println!("  ✓ Low memory footprint ({} KB buffer)", buffer_kb);
     println!("  ✓ Can handle files larger than RAM");
     println!("  ✓ Streaming - starts processing immediately");
-    println!("  ✗ Syscall overhead (read() per 4KB chunk)");
+    println!("  ✗ Syscall overhead (read() per {}KB chunk)", buffer_kb);
     println!("  ✗ Buffer boundary handling complexity");
 
     println!("\nIn-Memory (load all):");
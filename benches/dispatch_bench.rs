@@ -0,0 +1,342 @@
+//! What runtime dispatch costs per call: each dispatching wrapper against
+//! the kernel it picks on this CPU, called directly, from empty inputs to
+//! 64 KB of NDJSON logs.
+//!
+//!   cargo bench --bench dispatch_bench
+//!   cargo bench --bench dispatch_bench -- --time 0.2 --json dispatch.jsonl
+//!
+//! | Wrapper                     | Checks the CPU     | Direct kernel (x86-64 / aarch64) |
+//! |-----------------------------|--------------------|----------------------------------|
+//! | `needle::find`              | on every call      | `find_avx2` / `find_neon`        |
+//! | `needle::Pattern::find`     | once, in `new`     | the same, through a fn pointer   |
+//! | `insert_line_feed_into`     | on aarch64 only    | `_scalar_into` / `_neon_unchecked_into` |
+//! | `csv_index::classify_block` | on every block     | `_avx2` / `_neon`                |
+//! | `bitmask::prefix_xor`       | on every word      | `prefix_xor_clmul` / `_pmull`    |
+//!
+//! The check is a load of the feature bits std caches and a branch; the call
+//! to the kernel stays a call, as a `target_feature` function does not inline
+//! into code built without the feature. Without the extension the direct
+//! kernel is the portable one the wrapper falls back to.
+//!
+//! The sized kernels are timed [`CALLS`] calls a run, so that a run on an
+//! empty input is not below the nanosecond of a sample. Each table gives the
+//! wrapper minus the direct kernel per call at every size; on an empty input
+//! that is the dispatch alone. The crossovers are the sizes below which the
+//! dispatch is most of a call, and more than a tenth of it. The per-block
+//! kernels are timed over every block (word) of 64 KB, in a loop like
+//! `parse_csv_index`'s, in turns with their direct kernel.
+//!
+//! On the x86-64 VM of `cpu_info` (AVX2 and PCLMULQDQ), over runs with
+//! `--time 0.2`:
+//!
+//! | Wrapper                 | Dispatch per call | Most of the call | Over a tenth |
+//! |-------------------------|-------------------|------------------|--------------|
+//! | `needle::find`          | -0.21 to 0.51 ns  | at no size       | below 8 B    |
+//! | `Pattern::find`         | -0.23 to 0.51 ns  | at no size       | below 8 B    |
+//! | `insert_line_feed_into` | 0.21 to 1.07 ns   | at no size       | below 256 B  |
+//! | `classify_block`        | 0.33 to 0.43 ns   | -                | 4-5%         |
+//! | `prefix_xor`            | -0.25 to -0.29 ns | -                | -            |
+//!
+//! There is no crossover to find here: an empty call of the direct kernel
+//! takes 2-3 ns, more than the dispatch. `insert_line_feed_into` has nothing
+//! to check on x86-64, so its row is the noise: the dispatch of `find` is
+//! below it. The one steady cost is in the per-block loop of
+//! `classify_block`, about 5%. `prefix_xor` is faster through the wrapper in
+//! every round, with the same call to the same four instructions: at a
+//! nanosecond a word, where the loop sits weighs more than the check.
+
+use std::hint::black_box;
+use std::time::Duration;
+
+use scratchpad::bench::{format_size, measure_for, report_labeled, sweep_with, target_time};
+use scratchpad::bench::{Curve, Labels, Measurement};
+use scratchpad::csv_index;
+use scratchpad::datagen::{ndjson, Size};
+use scratchpad::line_feed_every_k_bytes::{insert_line_feed_into, insert_line_feed_scalar_into};
+use scratchpad::needle::{self, Pattern};
+use scratchpad::{bitmask, cpu};
+
+/// Calls of a sized kernel per timed run.
+const CALLS: usize = 100;
+
+const SIZES: [usize; 11] = [
+    0,
+    8,
+    16,
+    32,
+    64,
+    128,
+    256,
+    1 << 10,
+    4 << 10,
+    16 << 10,
+    64 << 10,
+];
+
+/// Absent from the logs: every search scans the whole input.
+const NEEDLE: &[u8] = b"Harvard";
+
+/// Line length of the line feed kernels.
+const K: usize = 64;
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                              Sized Kernels
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// `call` on the first `size` bytes of `input`, at every size.
+fn curve<T>(name: &str, labels: &Labels, input: &[u8], mut call: impl FnMut(&[u8]) -> T) -> Curve {
+    sweep_with(name, labels, &SIZES, |size| {
+        let data = &input[..size];
+        let calls = || {
+            for _ in 0..CALLS {
+                black_box(call(black_box(data)));
+            }
+        };
+        measure_for(calls, target_time(), |_| CALLS * size)
+    })
+}
+
+/// Time of one call in the fastest run: the least disturbed on a shared
+/// machine, where the median moves by more than the dispatch.
+fn ns_per_call(measurement: &Measurement) -> f64 {
+    measurement.stats().min.as_secs_f64() * 1e9 / CALLS as f64
+}
+
+/// Where the dispatch is more than a `1 / parts` share of the call: below
+/// the first size at which the direct kernel takes over `parts - 1` times
+/// the dispatch.
+fn crossover(direct: &Curve, dispatch: f64, parts: f64) -> String {
+    let size = direct
+        .points
+        .iter()
+        .find(|(_, m)| ns_per_call(m) > (parts - 1.0) * dispatch);
+    match size {
+        Some((0, _)) => "at no size".to_string(),
+        Some((size, _)) => format!("below {}", format_size(*size)),
+        None => "at every size".to_string(),
+    }
+}
+
+/// The wrapper minus the direct kernel at every size, and the crossovers.
+fn overhead(name: &str, wrapper: &Curve, direct: &Curve) {
+    println!("{} over the direct kernel:", name);
+    println!("  {:>10} {:>12} {:>12} {:>12}", "Size", "Wrapper", "Direct", "Overhead");
+    for ((size, wrapped), (_, called)) in wrapper.points.iter().zip(&direct.points) {
+        let (wrapped, called) = (ns_per_call(wrapped), ns_per_call(called));
+        println!(
+            "  {:>10} {:>9.2} ns {:>9.2} ns {:>9.2} ns",
+            format_size(*size),
+            wrapped,
+            called,
+            wrapped - called
+        );
+    }
+
+    // On an empty input the kernel does next to nothing: the difference is
+    // the dispatch alone, the same at every size
+    let dispatch = ns_per_call(&wrapper.points[0].1) - ns_per_call(&direct.points[0].1);
+    println!(
+        "  dispatch {:.2} ns: most of the call {}, over a tenth of it {}",
+        dispatch,
+        crossover(direct, dispatch, 2.0),
+        crossover(direct, dispatch, 10.0)
+    );
+    println!();
+}
+
+fn find(input: &[u8]) {
+    println!("--- needle::find, {}-byte needle ---", NEEDLE.len());
+    let wrapper = curve("  needle::find", &Labels::new("find", "dispatch"), input, |h| {
+        needle::find(h, NEEDLE)
+    });
+    let pattern = Pattern::new(NEEDLE);
+    let picked =
+        curve("  Pattern::find", &Labels::new("find", "Pattern"), input, |h| pattern.find(h));
+
+    let swar = || {
+        curve("  find_swar", &Labels::new("find", "SWAR"), input, |h| {
+            needle::find_swar(h, NEEDLE)
+        })
+    };
+    // SAFETY (both): only called once the CPU is known to have the extension
+    #[cfg(target_arch = "x86_64")]
+    let direct = if cpu::has_avx2() {
+        curve("  find_avx2", &Labels::new("find", "AVX2"), input, |h| unsafe {
+            needle::find_avx2(h, NEEDLE)
+        })
+    } else {
+        swar()
+    };
+    #[cfg(target_arch = "aarch64")]
+    let direct = if cpu::has_neon() {
+        curve("  find_neon", &Labels::new("find", "NEON"), input, |h| unsafe {
+            needle::find_neon(h, NEEDLE)
+        })
+    } else {
+        swar()
+    };
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    let direct = swar();
+    println!();
+
+    overhead("needle::find", &wrapper, &direct);
+    overhead("Pattern::find", &picked, &direct);
+}
+
+fn line_feed(input: &[u8]) {
+    println!("--- insert_line_feed_into, K={} ---", K);
+    let mut output = Vec::with_capacity(2 * input.len());
+    let labels = |backend| Labels::new("insert_line_feed", backend).with_k(K);
+    let wrapper = curve("  insert_line_feed_into", &labels("dispatch"), input, |b| {
+        insert_line_feed_into(b, K, &mut output);
+        output.len()
+    });
+
+    let scalar = |output: &mut Vec<u8>| {
+        curve("  _scalar_into", &labels("scalar"), input, |b| {
+            insert_line_feed_scalar_into(b, K, output);
+            output.len()
+        })
+    };
+    #[cfg(target_arch = "aarch64")]
+    let direct = if cpu::has_neon() {
+        use scratchpad::line_feed_every_k_bytes::insert_line_feed_neon_unchecked_into;
+        // SAFETY: NEON is there
+        curve("  _neon_unchecked_into", &labels("NEON"), input, |b| unsafe {
+            insert_line_feed_neon_unchecked_into(b, K, &mut output);
+            output.len()
+        })
+    } else {
+        scalar(&mut output)
+    };
+    #[cfg(not(target_arch = "aarch64"))]
+    let direct = scalar(&mut output);
+    println!();
+
+    overhead("insert_line_feed_into", &wrapper, &direct);
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                            Per-Block Kernels
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Rounds of a per-item wrapper and its direct kernel, timed in turns.
+const ROUNDS: usize = 3;
+
+/// The fastest run of `call` over all of `items`, of `width` bytes each.
+fn fastest<I, T>(
+    (name, labels): (&str, &Labels),
+    items: &[I],
+    width: usize,
+    call: &mut impl FnMut(&I) -> T,
+) -> Duration {
+    let all = || {
+        for item in items {
+            black_box(call(black_box(item)));
+        }
+    };
+    let measurement = measure_for(all, target_time(), |_| items.len() * width);
+    report_labeled(name, labels, &measurement);
+    measurement.stats().min
+}
+
+/// Time per item of the wrapper over the direct kernel, in their fastest
+/// run of [`ROUNDS`] timed in turns: the speed of a shared machine drifts by
+/// more than the dispatch from one measurement to the next.
+fn per_item<I, A, B>(
+    (item, items, width): (&str, &[I], usize),
+    wrapper: (&str, &Labels),
+    mut wrapped: impl FnMut(&I) -> A,
+    direct: (&str, &Labels),
+    mut called: impl FnMut(&I) -> B,
+) {
+    let (mut wrapper_min, mut direct_min) = (Duration::MAX, Duration::MAX);
+    for _ in 0..ROUNDS {
+        wrapper_min = wrapper_min.min(fastest(wrapper, items, width, &mut wrapped));
+        direct_min = direct_min.min(fastest(direct, items, width, &mut called));
+    }
+
+    let ns = |min: Duration| min.as_secs_f64() * 1e9 / items.len() as f64;
+    let (wrapper_ns, direct_ns) = (ns(wrapper_min), ns(direct_min));
+    println!(
+        "{:30} {:.2} ns per {} through the wrapper, {:.2} ns direct: {:+.2} ns ({:.0}%)\n",
+        "",
+        wrapper_ns,
+        item,
+        direct_ns,
+        wrapper_ns - direct_ns,
+        100.0 * (wrapper_ns - direct_ns) / wrapper_ns
+    );
+}
+
+fn classify_block(blocks: &[[u8; 64]]) {
+    println!("--- csv_index::classify_block, {} blocks ---", blocks.len());
+    let labels = |backend| Labels::new("classify_block", backend);
+    let blocks = ("block", blocks, 64);
+    let wrapper = ("  classify_block", &labels("dispatch"));
+    let wrapped = |b: &[u8; 64]| csv_index::classify_block(b);
+
+    // SAFETY (both): only called once the CPU is known to have the extension
+    #[cfg(target_arch = "x86_64")]
+    if cpu::has_avx2() {
+        let direct = ("  classify_block_avx2", &labels("AVX2"));
+        return per_item(blocks, wrapper, wrapped, direct, |b| unsafe {
+            csv_index::classify_block_avx2(b)
+        });
+    }
+    #[cfg(target_arch = "aarch64")]
+    if cpu::has_neon() {
+        let direct = ("  classify_block_neon", &labels("NEON"));
+        return per_item(blocks, wrapper, wrapped, direct, |b| unsafe {
+            csv_index::classify_block_neon(b)
+        });
+    }
+
+    let direct = ("  classify_block_swar", &labels("SWAR"));
+    per_item(blocks, wrapper, wrapped, direct, csv_index::classify_block_swar)
+}
+
+fn prefix_xor(words: &[u64]) {
+    println!("--- bitmask::prefix_xor, {} words ---", words.len());
+    let labels = |backend| Labels::new("prefix_xor", backend);
+    let words = ("word", words, 8);
+    let wrapper = ("  prefix_xor", &labels("dispatch"));
+    let wrapped = |&w: &u64| bitmask::prefix_xor(w);
+
+    // SAFETY (both): only called once the CPU is known to have the extension
+    #[cfg(target_arch = "x86_64")]
+    if cpu::has_pclmulqdq() {
+        let direct = ("  prefix_xor_clmul", &labels("PCLMULQDQ"));
+        return per_item(words, wrapper, wrapped, direct, |&w| unsafe {
+            bitmask::prefix_xor_clmul(w)
+        });
+    }
+    #[cfg(target_arch = "aarch64")]
+    if cpu::has_pmull() {
+        let direct = ("  prefix_xor_pmull", &labels("PMULL"));
+        return per_item(words, wrapper, wrapped, direct, |&w| unsafe {
+            bitmask::prefix_xor_pmull(w)
+        });
+    }
+
+    let direct = ("  prefix_xor_shift", &labels("shift"));
+    per_item(words, wrapper, wrapped, direct, |&w| bitmask::prefix_xor_shift(w))
+}
+fn main() {
+    println!("=== Dispatching wrappers vs the kernel they pick ===\n");
+    let input = ndjson(Size::Bytes(64 << 10), 1);
+    find(&input);
+    line_feed(&input);
+
+    let input = &input[..64 << 10];
+    let blocks: Vec<[u8; 64]> = input
+        .chunks_exact(64)
+        .map(|block| block.try_into().unwrap())
+        .collect();
+    classify_block(&blocks);
+    let words: Vec<u64> = input
+        .chunks_exact(8)
+        .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+        .collect();
+    prefix_xor(&words);
+}
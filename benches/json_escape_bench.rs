@@ -1,75 +1,52 @@
-use std::time::Instant;
-use scratchpad::json_escape_SWAR::{has_json_escapable_byte, has_json_escapable_byte_scalar};
-
-fn bench_with_timing(name: &str, f: impl Fn() -> bool, iterations: usize, input_size: usize) -> f64 {
-    // Warmup
-    for _ in 0..10 {
-        std::hint::black_box(f());
-    }
-
-    let start = Instant::now();
-    let mut total_bytes = 0;
-
-    for _ in 0..iterations {
-        let result = f();
-        total_bytes += input_size;
-        std::hint::black_box(result);
-    }
-
-    let elapsed = start.elapsed();
-    let elapsed_secs = elapsed.as_secs_f64();
-    let throughput_gb_s = (total_bytes as f64 / elapsed_secs) / 1_000_000_000.0;
-
-    println!(
-        "{:30} {:.2} ms total, {:.2} GB/s throughput",
-        format!("{}:", name),
-        elapsed_secs * 1000.0,
-        throughput_gb_s
-    );
-
-    throughput_gb_s
-}
+use scratchpad::bench::{bench_labeled, geometric_sizes, sweep, Labels};
+use scratchpad::json_escape_SWAR::{
+    count_escapable_bytes, count_escapable_bytes_scalar, has_json_escapable_byte,
+    has_json_escapable_byte_scalar,
+};
 
 fn main() {
     println!("=== JSON Escape Detection Benchmarks (SWAR) ===\n");
 
+    // Every measurement except "early detection" counts escapable bytes, which
+    // must scan the whole buffer: early-exit detection would stop at the first
+    // hit and report the size of the buffer, not the bytes actually read.
+
     // Test 1: Clean ASCII (no escapable characters)
     println!("--- Clean ASCII (no escapable chars) ---");
     let clean_input: Vec<u8> = (32..127).cycle().take(1_000_000).collect();
-    let iterations = 1_000;
 
-    let scalar_clean = bench_with_timing(
+    let scalar_clean = bench_labeled(
         "Scalar (clean, 1 MB)",
-        || has_json_escapable_byte_scalar(&clean_input),
-        iterations,
+        &Labels::new("count_escapable_bytes", "scalar"),
+        || count_escapable_bytes_scalar(std::hint::black_box(&clean_input)),
         clean_input.len(),
     );
 
-    let swar_clean = bench_with_timing(
+    let swar_clean = bench_labeled(
         "SWAR (clean, 1 MB)",
-        || has_json_escapable_byte(&clean_input),
-        iterations,
+        &Labels::new("count_escapable_bytes", "SWAR"),
+        || count_escapable_bytes(std::hint::black_box(&clean_input)),
         clean_input.len(),
     );
 
     println!();
 
     // Test 2: With escapable characters (early exit scenario)
-    println!("--- With escapable chars (early detection) ---");
+    println!("--- With escapable chars (early detection, stops at byte 100) ---");
     let mut early_escape = vec![65u8; 1_000_000]; // All 'A'
     early_escape[100] = b'"'; // Add quote at position 100
 
-    let scalar_early = bench_with_timing(
+    let scalar_early = bench_labeled(
         "Scalar (early escape, 1 MB)",
+        &Labels::new("has_json_escapable_byte", "scalar"),
         || has_json_escapable_byte_scalar(&early_escape),
-        iterations,
         early_escape.len(),
     );
 
-    let swar_early = bench_with_timing(
+    let swar_early = bench_labeled(
         "SWAR (early escape, 1 MB)",
+        &Labels::new("has_json_escapable_byte", "SWAR"),
         || has_json_escapable_byte(&early_escape),
-        iterations,
         early_escape.len(),
     );
 
@@ -89,84 +66,62 @@ fn main() {
         mixed_input.push(byte);
     }
 
-    let scalar_mixed = bench_with_timing(
+    let scalar_mixed = bench_labeled(
         "Scalar (mixed, 1 MB)",
-        || has_json_escapable_byte_scalar(&mixed_input),
-        iterations,
+        &Labels::new("count_escapable_bytes", "scalar"),
+        || count_escapable_bytes_scalar(std::hint::black_box(&mixed_input)),
         mixed_input.len(),
     );
 
-    let swar_mixed = bench_with_timing(
+    let swar_mixed = bench_labeled(
         "SWAR (mixed, 1 MB)",
-        || has_json_escapable_byte(&mixed_input),
-        iterations,
+        &Labels::new("count_escapable_bytes", "SWAR"),
+        || count_escapable_bytes(std::hint::black_box(&mixed_input)),
         mixed_input.len(),
     );
 
     println!();
 
-    // Test 4: Different input sizes
-    println!("--- Different input sizes (clean ASCII) ---");
-    for size_kb in [1, 10, 100, 1000] {
-        let size_bytes = size_kb * 1024;
-        let input: Vec<u8> = (32..127).cycle().take(size_bytes).collect();
-        let iter_count = (1_000_000 / size_bytes).max(10);
-
-        println!("  {} KB:", size_kb);
-        let scalar_size = bench_with_timing(
-            "    Scalar",
-            || has_json_escapable_byte_scalar(&input),
-            iter_count,
-            input.len(),
-        );
-
-        let swar_size = bench_with_timing(
-            "    SWAR",
-            || has_json_escapable_byte(&input),
-            iter_count,
-            input.len(),
-        );
-
-        println!();
-    }
-
-    // Test 5: Very large input (10 MB)
-    println!("--- Very large input (10 MB, clean ASCII) ---");
-    let very_large_input: Vec<u8> = (32..127).cycle().take(10_000_000).collect();
-    let iterations_large = 100;
-
-    let scalar_large = bench_with_timing(
-        "Scalar (10 MB)",
-        || has_json_escapable_byte_scalar(&very_large_input),
-        iterations_large,
-        very_large_input.len(),
+    // Test 4: Input sizes from L1 to main memory
+    println!("--- Input sizes (clean ASCII, 1 KB to 1 GB) ---");
+    let sizes = geometric_sizes(1 << 10, 1 << 30, 1);
+    let clean = |size| (32..127).cycle().take(size).collect::<Vec<u8>>();
+
+    sweep(
+        "Scalar",
+        &Labels::new("count_escapable_bytes", "scalar"),
+        &sizes,
+        clean,
+        |input| count_escapable_bytes_scalar(std::hint::black_box(input)),
     );
+    println!();
 
-    let swar_large = bench_with_timing(
-        "SWAR (10 MB)",
-        || has_json_escapable_byte(&very_large_input),
-        iterations_large,
-        very_large_input.len(),
+    sweep(
+        "SWAR",
+        &Labels::new("count_escapable_bytes", "SWAR"),
+        &sizes,
+        clean,
+        |input| count_escapable_bytes(std::hint::black_box(input)),
     );
 
     println!();
 
-    // Test 6: Worst case - escapable char at the end
+    // Test 5: Worst case - escapable char at the end
     println!("--- Worst case (escapable at end) ---");
     let mut worst_case = vec![65u8; 1_000_000]; // All 'A'
     worst_case[999_999] = b'"'; // Quote at the very end
 
-    let scalar_worst = bench_with_timing(
+    let scalar_worst = bench_labeled(
         "Scalar (worst case, 1 MB)",
-        || has_json_escapable_byte_scalar(&worst_case),
-        iterations,
+        &Labels::new("count_escapable_bytes", "scalar"),
+        || count_escapable_bytes_scalar(std::hint::black_box(&worst_case)),
         worst_case.len(),
     );
 
-    let swar_worst = bench_with_timing(
+    let swar_worst = bench_labeled(
         "SWAR (worst case, 1 MB)",
-        || has_json_escapable_byte(&worst_case),
-        iterations,
+        &Labels::new("count_escapable_bytes", "SWAR"),
+        || count_escapable_bytes(std::hint::black_box(&worst_case)),
         worst_case.len(),
     );
 
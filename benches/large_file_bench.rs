@@ -1,7 +1,12 @@
 use std::time::Instant;
 use std::fs::{self, File};
 use std::io::Write;
-use scratchpad::csv_parse_buffer_size_impact::{count_pattern_matches_from_file, count_pattern_matches_in_memory};
+use scratchpad::cpu_info::optimal_buffer_size;
+use scratchpad::csv_parse_buffer_size_impact::{
+    count_pattern_matches_advised, count_pattern_matches_from_file,
+    count_pattern_matches_in_memory, count_pattern_matches_parallel,
+};
+use scratchpad::readahead::{evict_cached, ReadStrategy};
 
 fn write_test_file(file_path: &str, num_rows: usize) -> std::io::Result<()> {
     let mut file = File::create(file_path)?;
@@ -95,7 +100,7 @@ fn main() {
                  (file_size as f64 / total_memory as f64) * 100.0);
 
         let disk_result = bench(
-            "Disk (4KB buffered)",
+            "Disk (buffered)",
             || count_pattern_matches_from_file(test_file, b"Harvard"),
             file_size,
         );
@@ -106,6 +111,18 @@ fn main() {
             file_size,
         );
 
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        if let (Some((tp_disk, _)), Some((tp_parallel, _))) = (
+            disk_result,
+            bench(
+                &format!("Parallel ({} threads)", threads),
+                || count_pattern_matches_parallel(test_file, b"Harvard", threads),
+                file_size,
+            ),
+        ) {
+            println!("  → Parallel is {:.2}x the single-threaded disk scan", tp_parallel / tp_disk);
+        }
+
         match (disk_result, mem_result) {
             (Some((tp_disk, time_disk)), Some((tp_mem, time_mem))) => {
                 let speedup = tp_mem / tp_disk;
@@ -126,6 +143,24 @@ fn main() {
                 println!("  → Both approaches failed");
             }
         }
+
+        // Readahead hints only matter on a cold cache: drop the file from it
+        // before each advised scan (Linux)
+        if num_rows >= 20_000_000 {
+            let file = File::open(test_file).unwrap();
+            file.sync_all().unwrap();
+            let strategies = [ReadStrategy::Sequential, ReadStrategy::Random, ReadStrategy::WillNeed];
+            for strategy in strategies {
+                if evict_cached(&file).is_err() {
+                    break;
+                }
+                bench(
+                    &format!("Cold, {:?}", strategy),
+                    || count_pattern_matches_advised(test_file, b"Harvard", strategy),
+                    file_size,
+                );
+            }
+        }
         println!();
 
         // Clean up
@@ -133,8 +168,9 @@ fn main() {
     }
 
     println!("\n=== Analysis ===");
-    println!("\nBuffered Disk I/O (4KB):");
-    println!("  ✓ Constant memory footprint (~4 KB)");
+    let buffer_kb = optimal_buffer_size() / 1024;
+    println!("\nBuffered Disk I/O ({}KB):", buffer_kb);
+    println!("  ✓ Constant memory footprint (~{} KB)", buffer_kb);
     println!("  ✓ Can handle files of ANY size");
     println!("  ✓ Predictable performance regardless of file size");
     println!("  ✓ No risk of OOM (Out Of Memory) errors");
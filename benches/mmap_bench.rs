@@ -0,0 +1,117 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use scratchpad::bench::{measure_for, target_time};
+use scratchpad::csv_parse_buffer_size_impact::{
+    count_pattern_matches_from_file, count_pattern_matches_in_memory, count_pattern_matches_mmap,
+};
+use scratchpad::csv_state_machine::{
+    parse_csv_if_else, parse_csv_if_else_mmap, parse_csv_reader, parse_csv_state_machine,
+    parse_csv_state_machine_mmap,
+};
+
+fn write_test_file(file_path: &str, num_rows: usize) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(file_path)?);
+    writeln!(file, "Name,University,Year,GPA,Major")?;
+    let universities = ["MIT", "Harvard", "Stanford", "Yale", "Princeton"];
+    for i in 0..num_rows {
+        writeln!(
+            file,
+            "Person{},{},{},{:.2},\"Computer Science, {}\"",
+            i,
+            universities[i % universities.len()],
+            2020 + (i % 5),
+            3.0 + ((i % 10) as f64 / 10.0),
+            i % 7
+        )?;
+    }
+    file.flush()
+}
+
+fn bench<T>(name: &str, f: impl Fn() -> T, file_size: u64) -> f64 {
+    // The warmup runs also bring the file into the page cache
+    let measurement = measure_for(f, target_time(), |_| file_size as usize);
+    let (throughput, time_per_op) = (measurement.throughput_gb_s(), measurement.ms_per_run());
+    println!("{:34} {:>8.2} ms/op, {:>6.2} GB/s", name, time_per_op, throughput);
+
+    throughput
+}
+
+fn main() {
+    println!("=== mmap vs Buffered Read vs Full Read (warm page cache) ===\n");
+
+    let test_cases = [
+        ("Small (10K rows, ~0.6 MB)", 10_000),
+        ("Large (200K rows, ~12 MB)", 200_000),
+        ("XLarge (2M rows, ~120 MB)", 2_000_000),
+    ];
+
+    for (desc, num_rows) in test_cases {
+        let path = format!("/tmp/test_mmap_{}.csv", num_rows);
+        write_test_file(&path, num_rows).unwrap();
+        let size = fs::metadata(&path).unwrap().len();
+        println!("--- {} ---", desc);
+
+        println!("  Pattern match (\"Harvard\"):");
+        let buffered = bench(
+            "    Buffered (read)",
+            || count_pattern_matches_from_file(&path, b"Harvard").unwrap(),
+            size,
+        );
+        let full = bench(
+            "    Full read (fs::read)",
+            || count_pattern_matches_in_memory(&path, b"Harvard").unwrap(),
+            size,
+        );
+        let mapped = bench(
+            "    mmap",
+            || count_pattern_matches_mmap(&path, b"Harvard").unwrap(),
+            size,
+        );
+        println!(
+            "    mmap vs buffered: {:.2}x, vs full read: {:.2}x",
+            mapped / buffered,
+            mapped / full
+        );
+
+        println!("  State machine:");
+        let buffered = bench(
+            "    Buffered (stream parser)",
+            || parse_csv_reader(File::open(&path).unwrap()).unwrap(),
+            size,
+        );
+        let full = bench(
+            "    Full read (fs::read)",
+            || parse_csv_state_machine(&fs::read(&path).unwrap()),
+            size,
+        );
+        let mapped = bench(
+            "    mmap",
+            || parse_csv_state_machine_mmap(&path).unwrap(),
+            size,
+        );
+        println!(
+            "    mmap vs buffered: {:.2}x, vs full read: {:.2}x",
+            mapped / buffered,
+            mapped / full
+        );
+
+        println!("  If/Else:");
+        let full = bench(
+            "    Full read (fs::read)",
+            || parse_csv_if_else(&fs::read(&path).unwrap()),
+            size,
+        );
+        let mapped = bench("    mmap", || parse_csv_if_else_mmap(&path).unwrap(), size);
+        println!("    mmap vs full read: {:.2}x\n", mapped / full);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    println!("=== Analysis ===\n");
+    println!("Full read: every call allocates a fresh file-sized Vec, faults its");
+    println!("pages in and copies the file into it; the bigger the file, the more");
+    println!("that costs next to a parser that runs at GB/s.");
+    println!("Buffered: one small buffer stays in cache, but one read() per buffer.");
+    println!("mmap: no copy and no syscall per buffer, only page faults on the");
+    println!("page cache; for small files, setting up the map is the overhead.");
+}
@@ -0,0 +1,64 @@
+use scratchpad::bench::bench_with_timing;
+use scratchpad::number_spans::{find_number_spans, find_number_spans_scalar};
+
+fn generate_log(num_lines: usize) -> Vec<u8> {
+    let mut data = Vec::new();
+    for i in 0..num_lines {
+        data.extend_from_slice(
+            format!(
+                "2024-10-17T12:{:02}:{:02} INFO request id={} status=200 latency={}ms bytes={}\n",
+                (i / 60) % 60,
+                i % 60,
+                i,
+                (i * 37) % 1000,
+                (i * 7919) % 100_000
+            )
+            .as_bytes(),
+        );
+    }
+    data
+}
+
+fn main() {
+    println!("=== Number Span Extraction Benchmarks ===\n");
+
+    // Test 1: Log lines with several metrics each
+    println!("--- Log lines (100K lines) ---");
+    let log = generate_log(100_000);
+    println!("Input size: {:.2} MB\n", log.len() as f64 / 1_000_000.0);
+
+    let scalar = bench_with_timing(
+        "Scalar",
+        || find_number_spans_scalar(&log).len(),
+        log.len(),
+    );
+
+    let bitmask = bench_with_timing(
+        "Bitmask",
+        || find_number_spans(&log).count(),
+        log.len(),
+    );
+
+    println!("Speedup: {:.2}x\n", bitmask / scalar);
+
+    // Test 2: Sparse digits (mostly text)
+    println!("--- Sparse digits (1 MB, one number per 1000 bytes) ---");
+    let mut sparse: Vec<u8> = (b'a'..=b'z').cycle().take(1_000_000).collect();
+    for i in (0..sparse.len()).step_by(1000) {
+        sparse[i] = b'4';
+    }
+
+    let scalar = bench_with_timing(
+        "Scalar (sparse)",
+        || find_number_spans_scalar(&sparse).len(),
+        sparse.len(),
+    );
+
+    let bitmask = bench_with_timing(
+        "Bitmask (sparse)",
+        || find_number_spans(&sparse).count(),
+        sparse.len(),
+    );
+
+    println!("Speedup: {:.2}x\n", bitmask / scalar);
+}
@@ -0,0 +1,45 @@
+use scratchpad::bench::bench_with_timing;
+use scratchpad::relite::Regex;
+
+fn generate_log(num_lines: usize) -> Vec<u8> {
+    let levels = ["INFO", "INFO", "INFO", "DEBUG", "INFO", "WARN", "INFO", "INFO", "INFO", "ERROR"];
+    let mut data = Vec::new();
+    for i in 0..num_lines {
+        data.extend_from_slice(
+            format!(
+                "2024-10-17T12:{:02}:{:02} {} worker-{} handled request id={} in {}ms\n",
+                (i / 60) % 60,
+                i % 60,
+                levels[i % levels.len()],
+                i % 16,
+                i,
+                (i * 37) % 1000
+            )
+            .as_bytes(),
+        );
+    }
+    data
+}
+
+fn main() {
+    println!("=== Regex-lite Benchmarks (prefilter + NFA) ===\n");
+
+    let log = generate_log(200_000);
+    println!("Input size: {:.2} MB\n", log.len() as f64 / 1_000_000.0);
+
+    let patterns = [
+        ("Single literal", "ERROR"),
+        ("Literal alternation", "ERROR|WARN"),
+        ("Literal + class", r"WARN worker-\d+"),
+        ("Anchored", r"^2024-10-17T12:00"),
+        ("Class only", r"[#@]"),
+        ("No prefilter", r"x*"),
+    ];
+
+    for (desc, pattern) in patterns {
+        let re = Regex::new(pattern).unwrap();
+        println!("  Pattern: {} ({})", desc, pattern);
+        bench_with_timing("    relite", || re.count_matching_lines(&log), log.len());
+        println!();
+    }
+}
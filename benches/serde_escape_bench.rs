@@ -0,0 +1,58 @@
+use std::collections::BTreeMap;
+use scratchpad::bench::bench_with_timing;
+use scratchpad::json_escape_SWAR::escape_json;
+use scratchpad::serde_escape;
+
+/// Log-like records: mostly long clean messages, with an escape every `escape_every` records.
+fn generate_records(count: usize, escape_every: usize) -> Vec<BTreeMap<&'static str, String>> {
+    (0..count)
+        .map(|i| {
+            let mut record = BTreeMap::new();
+            record.insert("level", "INFO".to_string());
+            record.insert("service", format!("api-gateway-{}", i % 7));
+            let message = if escape_every > 0 && i % escape_every == 0 {
+                format!("request \"{}\" failed:\n\tpath=C:\\data\\{}", i, i * 31)
+            } else {
+                format!("request {} completed successfully after retrying upstream connection pool {}", i, i * 31)
+            };
+            record.insert("message", message);
+            record
+        })
+        .collect()
+}
+
+fn run(label: &str, records: &[BTreeMap<&'static str, String>]) {
+    println!("--- {} ---", label);
+    let size = serde_json::to_vec(records).unwrap().len();
+    let strings: Vec<&str> = records.iter().flat_map(|r| r.values().map(|v| v.as_str())).collect();
+    let string_bytes: usize = strings.iter().map(|s| s.len()).sum();
+
+    let default = bench_with_timing(
+        "serde_json (default)",
+        || serde_json::to_vec(records).unwrap().len(),
+        size,
+    );
+
+    let swar = bench_with_timing(
+        "serde_json + JsonStringWriter",
+        || serde_escape::to_vec(records).unwrap().len(),
+        size,
+    );
+
+    // Ceiling: escaping the strings alone, without serde_json's scan
+    bench_with_timing(
+        "escape_json (strings only)",
+        || strings.iter().map(|s| escape_json(s.as_bytes()).len()).sum::<usize>(),
+        string_bytes,
+    );
+
+    println!("JsonStringWriter vs default: {:.2}x\n", swar / default);
+}
+
+fn main() {
+    println!("=== serde_json String Escaping Benchmarks ===\n");
+
+    run("Clean strings (100K records)", &generate_records(100_000, 0));
+    run("1 in 10 records escaped (100K records)", &generate_records(100_000, 10));
+    run("Every record escaped (100K records)", &generate_records(100_000, 1));
+}
@@ -0,0 +1,893 @@
+//! The timing loop of the benches: warmup, timed runs, statistics, and the
+//! line they print.
+//!
+//! Every bench in `benches/` times a closure the same way: untimed runs to
+//! warm the caches (and the page cache, for file scans), then timed runs
+//! whose results go through `black_box` so the work is not optimized away.
+//! [`bench_with_timing`] does all of it and prints, on one line,
+//!
+//! ```text
+//!   memchr:                        2.85 GB/s, median 351.2 µs (min 340.8 µs,
+//!                                  max 412.0 µs, stddev 9.3 µs, 95% CI 350.1..352.0 µs)
+//! ```
+//!
+//! Each run is timed on its own (down to 10 µs, see below), so one slow run
+//! (a page fault, a preemption) shows in the max and the stddev instead of
+//! moving the throughput, which comes from the median. A sample costs two
+//! `Instant::now` calls, about 30 ns here: noise on anything slower than a
+//! few µs, a visible share of the 64 ns of an early exit. (The `0.00 ms total, inf GB/s` of old outputs
+//! were loops the optimizer removed; `black_box` on every result keeps them.)
+//!
+//! # Calibration
+//!
+//! The number of runs is not the bench's to pick: [`measure_for`] warms up
+//! for a tenth of a target time, doubling the runs, and takes the last of
+//! them as the time of a run; the timed runs then fill the target, 2 s by
+//! default or `--time SECONDS` after the `--` of `cargo bench`:
+//!
+//! ```text
+//!   cargo bench --bench json_escape_bench -- --time 0.5
+//! ```
+//!
+//! A fixed count of runs suits one input size: 100 runs of a 1 KB scan are
+//! 30 µs, timed in 300 ns pieces a tenth of which is the clock, and 100 runs
+//! of a 1 GB scan are half a minute. Now a run longer than the target runs
+//! [`MIN_SAMPLES`] times after one warmup, and runs shorter than
+//! [`MIN_SAMPLE_TIME`] are timed in batches, a sample being the average run
+//! of its batch, so that the 30 ns of the clock stay under 0.3%.
+//!
+//! Benches that print something else (ms per run, several columns) take the
+//! [`Measurement`] of [`measure_for`] and format it themselves; [`measure`]
+//! runs the counts it is given.
+//!
+//! [`sweep`] runs a kernel over inputs of [`geometric_sizes`], 1 KB in L1 to
+//! 1 GB from memory, and prints its throughput curve, one row per size;
+//! [`sweep_with`] does it for sizes that are not the input's (read buffers).
+//!
+//! # Records
+//!
+//! Run with `--json FILE` or `--csv FILE` after the `--` of `cargo bench`,
+//! every [`report`] also appends a record to FILE, for results from several
+//! machines to be put in one table instead of scraped from the terminal:
+//!
+//! ```text
+//!   cargo bench --bench line_feed_bench -- --json results.jsonl
+//!   cargo bench --bench json_escape_bench -- --csv results.csv
+//! ```
+//!
+//! A record holds the printed name, the kernel, backend and K of its
+//! [`Labels`] (the kernel is the name for unlabeled benches), the CPU it ran
+//! on ([`cpu::info`](crate::cpu::info) on one line), the input size of one
+//! run, the iterations, the [`Stats`] in ms and the throughput; see
+//! [`CSV_HEADER`]. JSON records are one object per line. A CSV file gets the
+//! header when it is empty, so runs append to the same file.
+//! `bench_report` turns them into tables and charts.
+//!
+//! # Energy
+//!
+//! With `--energy`, an [`EnergyProbe`] counts the energy of the timed runs
+//! (RAPL on Linux, `powermetrics` on macOS, both as root; see `energy`):
+//! the line ends in J/GB and the record gets `joules_per_gb`. Without a
+//! probe the bench runs as it would without the flag, after a note on
+//! stderr.
+
+use std::{
+    env,
+    fs::{File, OpenOptions},
+    hint::black_box,
+    io::Write,
+    path::PathBuf,
+    sync::{Mutex, Once, OnceLock},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    csv_write::write_record,
+    energy::{EnergyProbe, EnergyReading},
+    json_escape_SWAR::escape_json_into,
+};
+
+/// Time [`measure_for`] fills when `--time` does not say otherwise.
+pub const TARGET_TIME: Duration = Duration::from_secs(2);
+
+/// The fewest samples of [`measure_for`], for a median of the slowest runs.
+pub const MIN_SAMPLES: usize = 3;
+
+/// The shortest sample of [`measure_for`]: faster runs are timed in batches.
+pub const MIN_SAMPLE_TIME: Duration = Duration::from_micros(10);
+
+/// The timed runs of a benchmark.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Measurement {
+    /// Wall-clock time of each timed run, in order; of the average run of
+    /// each batch when `runs_per_sample` is more than one.
+    pub samples: Vec<Duration>,
+    /// Runs timed together for a sample.
+    pub runs_per_sample: usize,
+    /// Bytes processed by all the timed runs.
+    pub bytes: u64,
+    /// Energy of the CPU over all the timed runs in µJ, with `--energy`.
+    pub energy_uj: Option<u64>,
+}
+
+/// Summary of the run times of a [`Measurement`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    pub median: Duration,
+    pub mean: Duration,
+    /// Sample standard deviation (zero for fewer than two runs).
+    pub stddev: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    /// 95% confidence interval of the mean, `mean ± 1.96 stddev / √n`.
+    pub ci95: (Duration, Duration),
+}
+
+impl Measurement {
+    /// Timed runs, all samples together.
+    pub fn iterations(&self) -> usize {
+        self.samples.len() * self.runs_per_sample
+    }
+
+    /// Time of all the timed runs.
+    pub fn total(&self) -> Duration {
+        self.samples.iter().sum::<Duration>() * self.runs_per_sample as u32
+    }
+
+    pub fn stats(&self) -> Stats {
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        let n = sorted.len();
+        if n == 0 {
+            return Stats::default();
+        }
+
+        let median = (sorted[(n - 1) / 2] + sorted[n / 2]) / 2;
+        let mean = sorted.iter().sum::<Duration>().as_secs_f64() / n as f64;
+        let variance = sorted
+            .iter()
+            .map(|s| (s.as_secs_f64() - mean).powi(2))
+            .sum::<f64>()
+            / (n - 1).max(1) as f64;
+        let stddev = variance.sqrt();
+        let margin = 1.96 * stddev / (n as f64).sqrt();
+        Stats {
+            median,
+            mean: Duration::from_secs_f64(mean),
+            stddev: Duration::from_secs_f64(stddev),
+            min: sorted[0],
+            max: sorted[n - 1],
+            ci95: (
+                Duration::from_secs_f64((mean - margin).max(0.0)),
+                Duration::from_secs_f64(mean + margin),
+            ),
+        }
+    }
+
+    /// Bytes of an average run per second of the median run, in GB/s (10^9
+    /// bytes).
+    pub fn throughput_gb_s(&self) -> f64 {
+        let bytes_per_run = self.bytes as f64 / self.iterations().max(1) as f64;
+        bytes_per_run / self.stats().median.as_secs_f64() / 1_000_000_000.0
+    }
+
+    /// Time of the median run, in milliseconds.
+    pub fn ms_per_run(&self) -> f64 {
+        self.stats().median.as_secs_f64() * 1000.0
+    }
+
+    /// Joules per GB (10^9 bytes) processed, if the energy was measured.
+    pub fn joules_per_gb(&self) -> Option<f64> {
+        let energy_uj = self.energy_uj.filter(|_| self.bytes > 0)?;
+        Some(energy_uj as f64 * 1000.0 / self.bytes as f64)
+    }
+}
+
+/// Run `f` `warmup` times untimed, then `iterations` times timed, each run
+/// counting the bytes `bytes` reports for its result (the input size for a
+/// scan, the output length for an encoder).
+pub fn measure<T>(
+    mut f: impl FnMut() -> T,
+    warmup: usize,
+    iterations: usize,
+    mut bytes: impl FnMut(&T) -> usize,
+) -> Measurement {
+    for _ in 0..warmup {
+        black_box(f());
+    }
+
+    let mut samples = Vec::with_capacity(iterations);
+    let mut total_bytes = 0;
+    let energy = start_energy();
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let result = black_box(f());
+        samples.push(start.elapsed());
+        total_bytes += bytes(&result) as u64;
+    }
+
+    Measurement { samples, runs_per_sample: 1, bytes: total_bytes, energy_uj: stop_energy(energy) }
+}
+
+/// Run `f` for about `target` after warming it up, in as many samples as
+/// the time of a run allows (see [Calibration](self#calibration)), each run
+/// counting the bytes `bytes` reports for its result.
+///
+/// # Example
+/// ```
+/// use scratchpad::bench::{measure_for, MIN_SAMPLES};
+/// use std::time::Duration;
+///
+/// let data = vec![b'x'; 1 << 20];
+/// let find = || memchr::memchr(b'y', &data);
+/// let measurement = measure_for(find, Duration::from_millis(20), |_| data.len());
+/// assert!(measurement.samples.len() >= MIN_SAMPLES);
+/// ```
+pub fn measure_for<T>(
+    mut f: impl FnMut() -> T,
+    target: Duration,
+    bytes: impl FnMut(&T) -> usize,
+) -> Measurement {
+    // Doubling batches: the last is the longest, on the warmest caches
+    let warmup = Instant::now();
+    let mut batch = 1;
+    let run = loop {
+        let start = Instant::now();
+        for _ in 0..batch {
+            black_box(f());
+        }
+        let run = start.elapsed().div_f64(batch as f64);
+        if warmup.elapsed() >= target / 10 {
+            break run;
+        }
+        batch *= 2;
+    };
+
+    let runs = (target.as_secs_f64() / run.as_secs_f64()) as usize;
+    measure_batched(f, run, runs, MIN_SAMPLES, bytes)
+}
+
+/// About `runs` timed runs of `f`, each taking about `run`: batched into
+/// samples of at least [`MIN_SAMPLE_TIME`], `min_samples` or more of them.
+fn measure_batched<T>(
+    mut f: impl FnMut() -> T,
+    run: Duration,
+    runs: usize,
+    min_samples: usize,
+    mut bytes: impl FnMut(&T) -> usize,
+) -> Measurement {
+    let runs_per_sample = MIN_SAMPLE_TIME.as_nanos().div_ceil(run.as_nanos().max(1)) as usize;
+    let count = runs.div_ceil(runs_per_sample).max(min_samples);
+
+    let mut samples = Vec::with_capacity(count);
+    let mut total_bytes = 0;
+    let energy = start_energy();
+    for _ in 0..count {
+        let start = Instant::now();
+        for _ in 0..runs_per_sample {
+            let result = black_box(f());
+            total_bytes += bytes(&result) as u64;
+        }
+        samples.push(start.elapsed().div_f64(runs_per_sample as f64));
+    }
+
+    Measurement { samples, runs_per_sample, bytes: total_bytes, energy_uj: stop_energy(energy) }
+}
+
+/// The time each [`measure_for`] of a bench fills: `--time SECONDS` on the
+/// command line, [`TARGET_TIME`] without.
+pub fn target_time() -> Duration {
+    static TARGET: OnceLock<Duration> = OnceLock::new();
+    *TARGET.get_or_init(|| time_arg(env::args().skip(1)).unwrap_or(TARGET_TIME))
+}
+
+/// The last `--time SECONDS` of `args`.
+fn time_arg(args: impl IntoIterator<Item = String>) -> Option<Duration> {
+    let mut args = args.into_iter();
+    let mut found = None;
+    while let Some(arg) = args.next() {
+        if arg == "--time" {
+            let seconds = args.next().and_then(|s| s.parse::<f64>().ok());
+            let seconds = seconds.filter(|s| s.is_finite() && *s >= 0.0);
+            found =
+                Some(Duration::from_secs_f64(seconds.expect("--time takes a number of seconds")));
+        }
+    }
+    found
+}
+
+/// The probe `--energy` asks for, detected on the first call: `None`
+/// without the flag, or when there is none (said on stderr).
+fn energy_probe() -> Option<&'static EnergyProbe> {
+    static PROBE: OnceLock<Option<EnergyProbe>> = OnceLock::new();
+    PROBE
+        .get_or_init(|| {
+            if !env::args().skip(1).any(|arg| arg == "--energy") {
+                return None;
+            }
+            EnergyProbe::detect()
+                .map_err(|e| eprintln!("--energy: {}", e))
+                .ok()
+        })
+        .as_ref()
+}
+
+fn start_energy() -> Option<EnergyReading<'static>> {
+    let reading = energy_probe()?.start();
+    reading.map_err(|e| eprintln!("--energy: {}", e)).ok()
+}
+
+fn stop_energy(reading: Option<EnergyReading>) -> Option<u64> {
+    reading?
+        .stop()
+        .map_err(|e| eprintln!("--energy: {}", e))
+        .ok()
+}
+
+/// What a measurement is of, for the records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Labels<'a> {
+    /// The function measured (`insert_line_feed`); the printed name if empty.
+    pub kernel: &'a str,
+    /// Its implementation: `scalar`, `SWAR`, `NEON`...
+    pub backend: &'a str,
+    /// The K of kernels that take one (a line feed every K bytes).
+    pub k: Option<usize>,
+}
+
+impl<'a> Labels<'a> {
+    pub fn new(kernel: &'a str, backend: &'a str) -> Self {
+        Labels { kernel, backend, k: None }
+    }
+
+    pub fn with_k(self, k: usize) -> Self {
+        Labels { k: Some(k), ..self }
+    }
+}
+
+/// Format of the records file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// One JSON object per line.
+    Json,
+    /// A header line, then one row per measurement.
+    Csv,
+}
+
+/// The fields of a record, in order.
+pub const CSV_HEADER: [&str; 16] = [
+    "name",
+    "kernel",
+    "backend",
+    "cpu",
+    "input_size",
+    "k",
+    "iterations",
+    "median_ms",
+    "mean_ms",
+    "stddev_ms",
+    "min_ms",
+    "max_ms",
+    "ci95_low_ms",
+    "ci95_high_ms",
+    "throughput_gb_s",
+    "joules_per_gb",
+];
+
+/// Append the record of `measurement` to `out`, terminated by a newline. The
+/// input size is the bytes of one run.
+pub fn write_measurement(
+    format: RecordFormat,
+    name: &str,
+    labels: &Labels,
+    measurement: &Measurement,
+    out: &mut Vec<u8>,
+) {
+    // The indentation of the terminal output is not part of the name
+    let name = name.trim();
+    let kernel = if labels.kernel.is_empty() {
+        name
+    } else {
+        labels.kernel
+    };
+    let iterations = measurement.iterations();
+    let stats = measurement.stats();
+    // Down to the ns, the resolution of the samples
+    let ms = |d: Duration| format!("{:.6}", d.as_secs_f64() * 1000.0);
+
+    let cpu = crate::cpu::info().to_string();
+    let strings = [name, kernel, labels.backend, &cpu];
+    let numbers = [
+        Some((measurement.bytes / iterations.max(1) as u64).to_string()),
+        labels.k.map(|k| k.to_string()),
+        Some(iterations.to_string()),
+        Some(ms(stats.median)),
+        Some(ms(stats.mean)),
+        Some(ms(stats.stddev)),
+        Some(ms(stats.min)),
+        Some(ms(stats.max)),
+        Some(ms(stats.ci95.0)),
+        Some(ms(stats.ci95.1)),
+        Some(format!("{:.3}", measurement.throughput_gb_s())),
+        measurement.joules_per_gb().map(|j| format!("{:.3}", j)),
+    ];
+
+    match format {
+        RecordFormat::Csv => {
+            let numbers = numbers.iter().map(|n| n.as_deref().unwrap_or(""));
+            write_record(strings.into_iter().chain(numbers), out);
+        }
+        RecordFormat::Json => {
+            for (i, (field, value)) in CSV_HEADER.iter().zip(strings).enumerate() {
+                out.extend_from_slice(if i == 0 { b"{\"" } else { b",\"" });
+                out.extend_from_slice(field.as_bytes());
+                out.extend_from_slice(b"\":\"");
+                escape_json_into(value.as_bytes(), out);
+                out.push(b'"');
+            }
+            for (field, value) in CSV_HEADER[strings.len()..].iter().zip(numbers) {
+                let value = value.as_deref().unwrap_or("null");
+                write!(out, ",\"{}\":{}", field, value).unwrap();
+            }
+            out.extend_from_slice(b"}\n");
+        }
+    }
+}
+
+/// The records file named on the command line, opened on the first report.
+fn records() -> Option<&'static (RecordFormat, Mutex<File>)> {
+    static RECORDS: OnceLock<Option<(RecordFormat, Mutex<File>)>> = OnceLock::new();
+    RECORDS
+        .get_or_init(|| {
+            let (format, path) = records_arg(env::args().skip(1))?;
+            let open = OpenOptions::new().create(true).append(true).open(&path);
+            let mut file = open.unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+            if format == RecordFormat::Csv && file.metadata().is_ok_and(|m| m.len() == 0) {
+                let mut header = Vec::new();
+                write_record(CSV_HEADER, &mut header);
+                file.write_all(&header)
+                    .expect("Failed to write the CSV header");
+            }
+            Some((format, Mutex::new(file)))
+        })
+        .as_ref()
+}
+
+/// The last `--json FILE` or `--csv FILE` of `args`. Other arguments (the
+/// `--bench` cargo passes, filters) are left alone.
+fn records_arg(args: impl IntoIterator<Item = String>) -> Option<(RecordFormat, PathBuf)> {
+    let mut args = args.into_iter();
+    let mut found = None;
+    while let Some(arg) = args.next() {
+        let format = match arg.as_str() {
+            "--json" => RecordFormat::Json,
+            "--csv" => RecordFormat::Csv,
+            _ => continue,
+        };
+        let path = args
+            .next()
+            .unwrap_or_else(|| panic!("{} takes a file name", arg));
+        found = Some((format, PathBuf::from(path)));
+    }
+    found
+}
+
+/// Print the throughput and the [`Stats`] of `measurement` after `name`.
+pub fn report(name: &str, measurement: &Measurement) {
+    report_labeled(name, &Labels::default(), measurement);
+}
+
+/// [`report`], with the `labels` of its record.
+pub fn report_labeled(name: &str, labels: &Labels, measurement: &Measurement) {
+    print_cpu();
+    let stats = measurement.stats();
+    println!(
+        "{:30} {:.2} GB/s, median {} (min {}, max {}, stddev {}, 95% CI {}..{}){}",
+        format!("{}:", name),
+        measurement.throughput_gb_s(),
+        format_time(stats.median),
+        format_time(stats.min),
+        format_time(stats.max),
+        format_time(stats.stddev),
+        format_time(stats.ci95.0),
+        format_time(stats.ci95.1),
+        format_energy(measurement)
+    );
+
+    append_record(name, labels, measurement);
+}
+
+/// Print the CPU before the first result, for a terminal output pasted
+/// somewhere to say what it ran on.
+fn print_cpu() {
+    static PRINTED: Once = Once::new();
+    PRINTED.call_once(|| {
+        println!("CPU: {}", crate::cpu::info());
+        if let Some(probe) = energy_probe() {
+            println!("Energy: {}", probe);
+        }
+    });
+}
+
+/// Append the record of `measurement` to the records file, if there is one.
+fn append_record(name: &str, labels: &Labels, measurement: &Measurement) {
+    if let Some((format, file)) = records() {
+        let mut record = Vec::new();
+        write_measurement(*format, name, labels, measurement, &mut record);
+        let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+        file.write_all(&record).expect("Failed to write the record");
+    }
+}
+
+/// `, 4.21 J/GB` if the energy of `measurement` was measured.
+fn format_energy(measurement: &Measurement) -> String {
+    let joules = measurement.joules_per_gb();
+    joules.map_or(String::new(), |j| format!(", {:.2} J/GB", j))
+}
+
+/// `d` in the unit that gives it one to three digits before the point.
+fn format_time(d: Duration) -> String {
+    let ns = d.as_secs_f64() * 1e9;
+    match ns {
+        _ if ns < 1e3 => format!("{:.0} ns", ns),
+        _ if ns < 1e6 => format!("{:.1} µs", ns / 1e3),
+        _ if ns < 1e9 => format!("{:.2} ms", ns / 1e6),
+        _ => format!("{:.2} s", ns / 1e9),
+    }
+}
+
+/// Time `f` over `input_size` bytes for the [`target_time`], print the
+/// result and return the throughput in GB/s.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::bench::bench_with_timing;
+///
+/// let data = vec![b'x'; 1 << 20];
+/// bench_with_timing("memchr", || memchr::memchr(b'y', &data), data.len());
+/// ```
+pub fn bench_with_timing<T>(name: &str, f: impl FnMut() -> T, input_size: usize) -> f64 {
+    bench_labeled(name, &Labels::default(), f, input_size)
+}
+
+/// [`bench_with_timing`], with the `labels` of its record.
+pub fn bench_labeled<T>(
+    name: &str,
+    labels: &Labels,
+    f: impl FnMut() -> T,
+    input_size: usize,
+) -> f64 {
+    let measurement = measure_for(f, target_time(), |_| input_size);
+    report_labeled(name, labels, &measurement);
+    measurement.throughput_gb_s()
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                               Size Sweeps
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Bytes each size of [`sweep`] processes in its timed runs (but never fewer
+/// than [`MIN_SWEEP_RUNS`] runs): 1 KB runs 262,144 times, 1 GB 3 times.
+pub const SWEEP_BYTES: u64 = 256 << 20;
+
+/// The fewest timed runs of a size, for a median of the largest ones.
+pub const MIN_SWEEP_RUNS: usize = 3;
+
+/// Sizes from `min` to `max` (included), `steps_per_doubling` of them per
+/// factor of two, rounded to a cache line (64 bytes) past the powers of two.
+///
+/// # Example
+/// ```
+/// use scratchpad::bench::geometric_sizes;
+///
+/// assert_eq!(geometric_sizes(1 << 10, 1 << 30, 10).len(), 201);
+/// assert_eq!(geometric_sizes(1024, 16384, 1), [1024, 2048, 4096, 8192, 16384]);
+/// assert_eq!(geometric_sizes(4096, 16384, 2), [4096, 5824, 8192, 11584, 16384]);
+/// ```
+pub fn geometric_sizes(min: usize, max: usize, steps_per_doubling: usize) -> Vec<usize> {
+    let steps = steps_per_doubling.max(1);
+    let mut sizes = Vec::new();
+    for i in 0.. {
+        let power = min << (i / steps);
+        let size = match i % steps {
+            0 => power,
+            step => {
+                let scaled = power as f64 * 2f64.powf(step as f64 / steps as f64);
+                (scaled / 64.0).round() as usize * 64
+            }
+        };
+        if size > max || power == 0 {
+            break;
+        }
+        sizes.push(size);
+    }
+    sizes
+}
+
+/// Timed runs for a size of `bytes_per_run` bytes in a sweep.
+pub fn sweep_runs(bytes_per_run: u64) -> usize {
+    (SWEEP_BYTES / bytes_per_run.max(1)).max(MIN_SWEEP_RUNS as u64) as usize
+}
+
+/// Throughput against size: one measurement per size, in order.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Curve {
+    pub points: Vec<(usize, Measurement)>,
+}
+
+impl Curve {
+    /// The size with the highest throughput, and its measurement.
+    pub fn best(&self) -> Option<&(usize, Measurement)> {
+        self.points
+            .iter()
+            .max_by(|a, b| a.1.throughput_gb_s().total_cmp(&b.1.throughput_gb_s()))
+    }
+
+    pub fn at(&self, size: usize) -> Option<&Measurement> {
+        self.points.iter().find(|(s, _)| *s == size).map(|(_, m)| m)
+    }
+}
+
+/// Time `f` over inputs of each of `sizes` bytes, built by `input` before
+/// the timing, and print the throughput curve.
+///
+/// Each size runs [`sweep_runs`] times (rounded up to whole batches, see
+/// [Calibration](self#calibration)) after a tenth as many untimed runs.
+/// With `--json` or `--csv`, every size gets a record, its size in the name.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::bench::{geometric_sizes, sweep, Labels};
+///
+/// let sizes = geometric_sizes(1 << 10, 1 << 30, 1);
+/// let labels = Labels::new("memchr", "memchr crate");
+/// sweep("memchr", &labels, &sizes, |size| vec![b'x'; size], |data| memchr::memchr(b'y', data));
+/// ```
+pub fn sweep<I, T>(
+    name: &str,
+    labels: &Labels,
+    sizes: &[usize],
+    mut input: impl FnMut(usize) -> I,
+    mut f: impl FnMut(&I) -> T,
+) -> Curve {
+    sweep_with(name, labels, sizes, |size| {
+        let data = input(size);
+        let runs = sweep_runs(size as u64);
+        let warmup = runs.div_ceil(10);
+        let start = Instant::now();
+        for _ in 0..warmup {
+            black_box(f(&data));
+        }
+        let run = start.elapsed().div_f64(warmup as f64);
+        measure_batched(|| f(&data), run, runs, MIN_SWEEP_RUNS, |_| size)
+    })
+}
+
+/// [`sweep`] over anything a size can mean (a buffer, a batch, a thread
+/// count): `measure_at` times one size however it needs.
+pub fn sweep_with(
+    name: &str,
+    labels: &Labels,
+    sizes: &[usize],
+    mut measure_at: impl FnMut(usize) -> Measurement,
+) -> Curve {
+    print_cpu();
+    println!("{}:", name);
+    println!("  {:>10} {:>10} {:>10} {:>10} {:>10}", "Size", "GB/s", "Median", "Min", "Max");
+
+    let mut curve = Curve::default();
+    for &size in sizes {
+        let measurement = measure_at(size);
+        let stats = measurement.stats();
+        println!(
+            "  {:>10} {:>10.2} {:>10} {:>10} {:>10}{}",
+            format_size(size),
+            measurement.throughput_gb_s(),
+            format_time(stats.median),
+            format_time(stats.min),
+            format_time(stats.max),
+            format_energy(&measurement)
+        );
+        append_record(&format!("{} ({})", name.trim(), format_size(size)), labels, &measurement);
+        curve.points.push((size, measurement));
+    }
+    curve
+}
+
+/// `size` in B, KB, MB or GB (of 1024), with a decimal when it is not whole.
+pub fn format_size(size: usize) -> String {
+    let units = ["B", "KB", "MB", "GB"];
+    let unit = (0..units.len())
+        .rev()
+        .find(|&u| size >> (10 * u) > 0)
+        .unwrap_or(0);
+    let scaled = size as f64 / (1u64 << (10 * unit)) as f64;
+    if scaled.fract() == 0.0 {
+        format!("{} {}", scaled, units[unit])
+    } else {
+        format!("{:.1} {}", scaled, units[unit])
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_measure() {
+        let mut runs = 0;
+        let measurement = measure(
+            || {
+                runs += 1;
+                vec![0u8; runs]
+            },
+            3,
+            4,
+            |output| output.len(),
+        );
+        assert_eq!(runs, 7);
+        // The timed runs produced 4, 5, 6 and 7 bytes
+        assert_eq!((measurement.iterations(), measurement.bytes), (4, 22));
+        assert!(measurement.throughput_gb_s() > 0.0);
+
+        // No --energy among the arguments of the tests
+        assert_eq!(measurement.energy_uj, None);
+
+        let second = Measurement {
+            samples: vec![ms(500); 4],
+            runs_per_sample: 1,
+            bytes: 4_000,
+            energy_uj: Some(8_000),
+        };
+        assert_eq!(second.total(), ms(2000));
+        assert_eq!(second.ms_per_run(), 500.0);
+        assert_eq!(second.throughput_gb_s(), 2e-6);
+        // 8 mJ for 4 KB
+        assert_eq!(second.joules_per_gb(), Some(2000.0));
+        assert_eq!(format_energy(&second), ", 2000.00 J/GB");
+        let zero = Measurement { bytes: 0, ..second };
+        assert_eq!((zero.joules_per_gb(), format_energy(&zero)), (None, String::new()));
+    }
+
+    #[test]
+    fn test_measure_for() {
+        let data = vec![1u8; 1 << 10];
+        let target = Duration::from_millis(50);
+        let start = Instant::now();
+        let fast = measure_for(|| data[data.len() - 1], target, |_| data.len());
+        // Warmup, a tenth of the target, then about the target
+        assert!(start.elapsed() < target * 3);
+        assert!(fast.runs_per_sample > 1);
+        assert!(fast.samples.len() > 100);
+        assert_eq!(fast.bytes, fast.iterations() as u64 * 1024);
+
+        let slow = measure_for(|| std::thread::sleep(ms(30)), target, |_| 0);
+        assert_eq!((slow.samples.len(), slow.runs_per_sample), (MIN_SAMPLES, 1));
+        assert!(slow.stats().min >= ms(30));
+
+        let args = |args: &[&str]| time_arg(args.iter().map(|a| a.to_string()));
+        assert_eq!(args(&["--bench", "--time", "0.5"]), Some(ms(500)));
+        assert_eq!(args(&["--json", "out.jsonl"]), None);
+        assert_eq!(target_time(), TARGET_TIME);
+    }
+
+    #[test]
+    fn test_stats() {
+        let measurement = Measurement {
+            samples: [4, 1, 10, 3, 2].map(ms).to_vec(),
+            runs_per_sample: 1,
+            bytes: 0,
+            energy_uj: None,
+        };
+        let stats = measurement.stats();
+        // The slow run moves the mean, not the median
+        assert_eq!((stats.median, stats.mean), (ms(3), ms(4)));
+        assert_eq!((stats.min, stats.max), (ms(1), ms(10)));
+        // Variance (0 + 9 + 36 + 1 + 4) / 4 = 12.5 ms²
+        assert_eq!(stats.stddev.as_micros(), 3535);
+        let margin = 1.96 * 12.5f64.sqrt() / 5f64.sqrt();
+        assert_eq!(stats.ci95.0.as_micros(), ((4.0 - margin) * 1000.0) as u128);
+        assert_eq!(stats.ci95.1.as_micros(), ((4.0 + margin) * 1000.0) as u128);
+
+        let even = Measurement { samples: [1, 2, 4, 8].map(ms).to_vec(), ..measurement.clone() };
+        assert_eq!(even.stats().median, ms(3));
+        let one = Measurement { samples: vec![ms(7)], ..measurement };
+        assert_eq!(one.stats().stddev, Duration::ZERO);
+        assert_eq!(one.stats().ci95, (ms(7), ms(7)));
+
+        assert_eq!(format_time(Duration::from_nanos(141)), "141 ns");
+        assert_eq!(format_time(Duration::from_nanos(276_774)), "276.8 µs");
+        assert_eq!(format_time(ms(1061)), "1.06 s");
+    }
+
+    #[test]
+    fn test_sweep() {
+        assert_eq!(geometric_sizes(512, 2000, 1), [512, 1024]);
+        assert!(geometric_sizes(0, 10, 1).is_empty());
+        assert_eq!(sweep_runs(1 << 10), 262_144);
+        assert_eq!(sweep_runs(1 << 30), MIN_SWEEP_RUNS);
+
+        let mut built = Vec::new();
+        let curve = sweep(
+            "sum",
+            &Labels::default(),
+            &[1024, 4096],
+            |size| {
+                built.push(size);
+                vec![1u8; size]
+            },
+            |data| data[data.len() - 1],
+        );
+        assert_eq!(built, [1024, 4096]);
+        let sizes: Vec<usize> = curve.points.iter().map(|(size, _)| *size).collect();
+        assert_eq!(sizes, [1024, 4096]);
+        let at = curve.at(4096).unwrap();
+        // Batches of runs under 10 µs round the runs up
+        assert!(at.iterations() >= sweep_runs(4096) && at.runs_per_sample >= 1);
+        assert_eq!(at.bytes, at.iterations() as u64 * 4096);
+        assert!(curve.best().is_some() && curve.at(2048).is_none());
+
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(5824), "5.7 KB");
+        assert_eq!(format_size(1 << 30), "1 GB");
+    }
+
+    #[test]
+    fn test_records() {
+        let args = |args: &[&str]| records_arg(args.iter().map(|a| a.to_string()));
+        assert_eq!(args(&["--bench"]), None);
+        assert_eq!(
+            args(&["--bench", "--json", "out.jsonl"]),
+            Some((RecordFormat::Json, PathBuf::from("out.jsonl")))
+        );
+
+        let measurement = Measurement {
+            samples: vec![Duration::from_millis(500); 2],
+            runs_per_sample: 2,
+            bytes: 4_000,
+            energy_uj: None,
+        };
+        let labels = Labels::new("insert_line_feed", "NEON").with_k(64);
+        // "model, cores, ...": always quoted in CSV
+        let cpu = crate::cpu::info().to_string();
+        let mut out = Vec::new();
+        write_measurement(RecordFormat::Csv, "NEON (K=64)", &labels, &measurement, &mut out);
+        write_measurement(RecordFormat::Csv, "a \"b\"", &Labels::default(), &measurement, &mut out);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "NEON (K=64),insert_line_feed,NEON,\"{cpu}\",1000,64,4,500.000000,500.000000,\
+                 0.000000,500.000000,500.000000,500.000000,500.000000,0.000,\n\
+                 \"a \"\"b\"\"\",\"a \"\"b\"\"\",,\"{cpu}\",1000,,4,500.000000,500.000000,\
+                 0.000000,500.000000,500.000000,500.000000,500.000000,0.000,\n"
+            )
+        );
+
+        let mut out = Vec::new();
+        write_measurement(
+            RecordFormat::Json,
+            "    SWAR",
+            &Labels::default(),
+            &measurement,
+            &mut out,
+        );
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "{{\"name\":\"SWAR\",\"kernel\":\"SWAR\",\"backend\":\"\",\"cpu\":\"{cpu}\",\
+                 \"input_size\":1000,\"k\":null,\"iterations\":4,\"median_ms\":500.000000,\
+                 \"mean_ms\":500.000000,\"stddev_ms\":0.000000,\"min_ms\":500.000000,\
+                 \"max_ms\":500.000000,\"ci95_low_ms\":500.000000,\"ci95_high_ms\":500.000000,\
+                 \"throughput_gb_s\":0.000,\"joules_per_gb\":null}}\n"
+            )
+        );
+    }
+}
@@ -0,0 +1,600 @@
+//! Tables and charts from the records of the benches.
+//!
+//! Run with `--json FILE` or `--csv FILE`, the benches append a record per
+//! measurement (see `bench`). This turns a records file, or several (one per
+//! machine), into what the module docs used to get by hand: a Markdown table
+//! per kernel, its backends side by side, and an SVG bar chart of the same
+//! numbers. `bench_report` writes them to a directory:
+//!
+//! ```text
+//!   cargo bench --bench json_escape_bench -- --json m1.jsonl
+//!   cargo run --bin bench_report -- m1.jsonl x86.jsonl --out target/bench-report
+//! ```
+//!
+//! ```text
+//!   | Case        | scalar    | SWAR      | SWAR / scalar |
+//!   |-------------|----------:|----------:|--------------:|
+//!   | clean, 1 MB | 1.01 GB/s | 3.97 GB/s | 3.93x         |
+//! ```
+//!
+//! A row is a case: the record's name without its backend (`SWAR (clean,
+//! 1 MB)` and `Scalar (clean, 1 MB)` are both `clean, 1 MB`). With several
+//! files, each column is a backend on one of them, named after the file. A
+//! case measured twice keeps its last record.
+//!
+//! The CPU of each record (see `cpu`) goes above the tables, once per file
+//! and CPU: [`machines`].
+
+use std::{collections::HashMap, fmt, fmt::Write};
+
+use crate::{
+    bench::format_size, csv_reader::CsvReader, html_escape::escape_html, json_index::build_index,
+};
+
+/// One record of a bench run.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Row {
+    /// The file the record came from, when comparing several.
+    pub source: String,
+    pub name: String,
+    pub kernel: String,
+    pub backend: String,
+    /// The CPU the bench ran on, empty in records older than the field.
+    pub cpu: String,
+    /// Bytes of one run.
+    pub input_size: u64,
+    pub k: Option<u64>,
+    pub median_ms: f64,
+    pub throughput_gb_s: f64,
+}
+
+/// A line of a records file that is not a record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportError {
+    /// 1-based line (JSON) or record (CSV) number.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ReportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ReportError {}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Parsing
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// The records of a `--json` or `--csv` file, told apart by the first byte.
+///
+/// # Example
+/// ```
+/// use scratchpad::bench_report::parse_records;
+///
+/// let data = br#"{"name":"SWAR","backend":"SWAR","input_size":1024,"throughput_gb_s":3.02}"#;
+/// let rows = parse_records(data).unwrap();
+/// assert_eq!((rows[0].input_size, rows[0].throughput_gb_s), (1024, 3.02));
+/// ```
+pub fn parse_records(data: &[u8]) -> Result<Vec<Row>, ReportError> {
+    match data.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'{') => parse_json_lines(data),
+        Some(_) => parse_csv(data),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn parse_json_lines(data: &[u8]) -> Result<Vec<Row>, ReportError> {
+    let mut rows = Vec::new();
+    for (i, line) in data.split(|&b| b == b'\n').enumerate() {
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        let error = |message: &str| ReportError { line: i + 1, message: message.to_string() };
+        let fields = json_fields(line).ok_or_else(|| error("not a flat JSON object"))?;
+        rows.push(row(&fields).map_err(|e| error(&e))?);
+    }
+    Ok(rows)
+}
+
+fn parse_csv(data: &[u8]) -> Result<Vec<Row>, ReportError> {
+    let mut reader = CsvReader::new(data);
+    let Some(header) = reader.next_record().map(|r| csv_fields(&r)) else {
+        return Ok(Vec::new());
+    };
+
+    let mut rows = Vec::new();
+    let mut line = 1;
+    while let Some(record) = reader.next_record() {
+        line += 1;
+        let values = csv_fields(&record);
+        // The header again: files appended to one another
+        if values == header {
+            continue;
+        }
+        let fields: Vec<(String, Option<String>)> = header
+            .iter()
+            .zip(values)
+            .map(|(name, value)| (name.clone(), Some(value).filter(|v| !v.is_empty())))
+            .collect();
+        rows.push(row(&fields).map_err(|message| ReportError { line, message })?);
+    }
+    Ok(rows)
+}
+
+fn csv_fields(record: &crate::csv_reader::Record) -> Vec<String> {
+    (0..record.len())
+        .map(|i| {
+            let field = String::from_utf8_lossy(record.get(i).unwrap_or_default());
+            if record.is_quoted(i) {
+                field.replace("\"\"", "\"")
+            } else {
+                field.into_owned()
+            }
+        })
+        .collect()
+}
+
+/// A record from its fields, by name.
+fn row(fields: &[(String, Option<String>)]) -> Result<Row, String> {
+    let get = |name: &str| {
+        fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .and_then(|(_, value)| value.as_deref())
+    };
+    let number = |name: &str| -> Result<Option<f64>, String> {
+        get(name)
+            .map(|v| {
+                v.parse()
+                    .map_err(|_| format!("{}: not a number: {}", name, v))
+            })
+            .transpose()
+    };
+
+    let name = get("name").ok_or("no name")?.to_string();
+    Ok(Row {
+        source: String::new(),
+        kernel: get("kernel").unwrap_or(&name).to_string(),
+        backend: get("backend").unwrap_or_default().to_string(),
+        cpu: get("cpu").unwrap_or_default().to_string(),
+        input_size: number("input_size")?.unwrap_or(0.0) as u64,
+        k: number("k")?.map(|k| k as u64),
+        median_ms: number("median_ms")?.unwrap_or(0.0),
+        throughput_gb_s: number("throughput_gb_s")?.ok_or("no throughput_gb_s")?,
+        name,
+    })
+}
+
+/// The fields of a one-line JSON object without nested values, `None` for
+/// `null`; `None` if `line` is not one.
+fn json_fields(line: &[u8]) -> Option<Vec<(String, Option<String>)>> {
+    let index = build_index(line).ok()?;
+    let mut positions = index.positions.iter().map(|&p| p as usize);
+    if line[positions.next()?] != b'{' {
+        return None;
+    }
+
+    let mut fields = Vec::new();
+    loop {
+        let open = positions.next()?;
+        if line[open] == b'}' && fields.is_empty() {
+            return Some(fields);
+        }
+        let close = positions.next()?;
+        let colon = positions.next()?;
+        if line[open] != b'"' || line[colon] != b':' {
+            return None;
+        }
+        let key = unescape_json(&line[open + 1..close])?;
+
+        let mut next = positions.next()?;
+        let value = if line[next] == b'"' {
+            let end = positions.next()?;
+            let value = unescape_json(&line[next + 1..end])?;
+            next = positions.next()?;
+            Some(value)
+        } else {
+            let raw = std::str::from_utf8(&line[colon + 1..next]).ok()?.trim();
+            (raw != "null").then(|| raw.to_string())
+        };
+        fields.push((key, value));
+
+        match line[next] {
+            b',' => continue,
+            b'}' => return Some(fields),
+            _ => return None,
+        }
+    }
+}
+
+/// The content of a JSON string, escapes decoded.
+fn unescape_json(s: &[u8]) -> Option<String> {
+    let s = std::str::from_utf8(s).ok()?;
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        out.push(match chars.next()? {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'u' => {
+                let high = hex4(&mut chars)?;
+                // A character past U+FFFF: a surrogate pair, `\ud83d\ude00`
+                if (0xD800..0xDC00).contains(&high) && chars.as_str().starts_with("\\u") {
+                    chars.nth(1);
+                    let low = hex4(&mut chars)?.checked_sub(0xDC00)?;
+                    char::from_u32(0x10000 + ((high - 0xD800) << 10) + low)?
+                } else {
+                    char::from_u32(high).unwrap_or(char::REPLACEMENT_CHARACTER)
+                }
+            }
+            other => other,
+        });
+    }
+    Some(out)
+}
+
+/// The 4 hex digits of a `\u` escape.
+fn hex4(chars: &mut std::str::Chars) -> Option<u32> {
+    let digits = chars.as_str().get(..4)?;
+    chars.nth(3);
+    u32::from_str_radix(digits, 16).ok()
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tables
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// The records of one kernel, a case per row and a series (backend, or
+/// source and backend) per column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub kernel: String,
+    pub series: Vec<String>,
+    pub cases: Vec<String>,
+    /// Throughput in GB/s by (case, series).
+    pub cells: HashMap<(usize, usize), f64>,
+}
+
+impl Table {
+    pub fn get(&self, case: usize, series: usize) -> Option<f64> {
+        self.cells.get(&(case, series)).copied()
+    }
+
+    /// The highest throughput in the table.
+    fn max(&self) -> f64 {
+        self.cells.values().copied().fold(0.0, f64::max)
+    }
+}
+
+/// A table per kernel, kernels, cases and series in the order they first
+/// appear in `rows`.
+pub fn tables(rows: &[Row]) -> Vec<Table> {
+    let several_sources = rows.iter().any(|r| r.source != rows[0].source);
+    let mut tables: Vec<Table> = Vec::new();
+
+    for row in rows {
+        let table = match tables.iter().position(|t| t.kernel == row.kernel) {
+            Some(i) => &mut tables[i],
+            None => {
+                tables.push(Table {
+                    kernel: row.kernel.clone(),
+                    series: Vec::new(),
+                    cases: Vec::new(),
+                    cells: HashMap::new(),
+                });
+                tables.last_mut().unwrap()
+            }
+        };
+
+        let series = match (several_sources, row.backend.as_str()) {
+            (true, "") => row.source.clone(),
+            (true, backend) => format!("{} {}", row.source, backend),
+            (false, backend) => backend.to_string(),
+        };
+        let case = case_label(row);
+        let s = position_or_push(&mut table.series, series);
+        let c = position_or_push(&mut table.cases, case);
+        table.cells.insert((c, s), row.throughput_gb_s);
+    }
+    tables
+}
+
+fn position_or_push(items: &mut Vec<String>, item: String) -> usize {
+    items.iter().position(|i| *i == item).unwrap_or_else(|| {
+        items.push(item);
+        items.len() - 1
+    })
+}
+
+/// The name of `row` without its backend (ASCII case ignored) and the
+/// parentheses left around the rest, or its input size if nothing is left.
+fn case_label(row: &Row) -> String {
+    let name = row.name.as_str();
+    let backend = row.backend.to_ascii_lowercase();
+    let rest = match name.to_ascii_lowercase().find(&backend) {
+        Some(i) if !backend.is_empty() => format!("{}{}", &name[..i], &name[i + backend.len()..]),
+        _ => name.to_string(),
+    };
+    let rest = rest.trim();
+    let rest = rest
+        .strip_prefix('(')
+        .and_then(|r| r.strip_suffix(')'))
+        .unwrap_or(rest);
+    if rest.is_empty() {
+        format_size(row.input_size as usize)
+    } else {
+        rest.to_string()
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Output
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// A table as Markdown: a column per series and, after them, the speedup of
+/// every other series over the first.
+pub fn markdown(table: &Table) -> String {
+    let cell = |s: &str| s.replace('|', "\\|");
+    let mut out = String::new();
+    let mut header = vec!["Case".to_string()];
+    header.extend(table.series.iter().map(|s| cell(s)));
+    header.extend(
+        table.series[1..]
+            .iter()
+            .map(|s| format!("{} / {}", cell(s), cell(&table.series[0]))),
+    );
+    let _ = writeln!(out, "| {} |", header.join(" | "));
+    let align: Vec<&str> = header
+        .iter()
+        .enumerate()
+        .map(|(i, _)| if i == 0 { "---" } else { "--:" })
+        .collect();
+    let _ = writeln!(out, "|{}|", align.join("|"));
+
+    for (c, case) in table.cases.iter().enumerate() {
+        let mut line = vec![cell(case)];
+        for s in 0..table.series.len() {
+            line.push(
+                table
+                    .get(c, s)
+                    .map_or("–".to_string(), |v| format!("{:.2} GB/s", v)),
+            );
+        }
+        for s in 1..table.series.len() {
+            line.push(match (table.get(c, s), table.get(c, 0)) {
+                (Some(v), Some(base)) if base > 0.0 => format!("{:.2}x", v / base),
+                _ => "–".to_string(),
+            });
+        }
+        let _ = writeln!(out, "| {} |", line.join(" | "));
+    }
+    out
+}
+
+/// Colors of the series, in order (repeated past the last).
+const PALETTE: [&str; 6] = [
+    "#4e79a7", "#f28e2b", "#59a14f", "#e15759", "#76b7b2", "#b07aa1",
+];
+
+/// A table as a horizontal bar chart: a group of bars per case, one per
+/// series, each labeled with its throughput.
+pub fn svg(table: &Table) -> String {
+    const LABEL_WIDTH: usize = 180;
+    const CHART_WIDTH: usize = 420;
+    const BAR: usize = 14;
+    const GAP: usize = 10;
+    const LEGEND: usize = 24;
+
+    let group = BAR * table.series.len() + GAP;
+    let height = LEGEND + group * table.cases.len() + GAP;
+    let width = LABEL_WIDTH + CHART_WIDTH + 80;
+    let scale = CHART_WIDTH as f64 / table.max().max(f64::MIN_POSITIVE);
+
+    let mut out = String::from(r#"<svg xmlns="http://www.w3.org/2000/svg""#);
+    let _ = writeln!(
+        out,
+        r#" width="{}" height="{}" font-family="sans-serif" font-size="11">"#,
+        width, height
+    );
+    for (s, series) in table.series.iter().enumerate() {
+        let x = LABEL_WIDTH + s * 120;
+        let color = PALETTE[s % PALETTE.len()];
+        let _ =
+            writeln!(out, r#"  <rect x="{}" y="6" width="10" height="10" fill="{}"/>"#, x, color);
+        let _ = writeln!(out, r#"  <text x="{}" y="15">{}</text>"#, x + 14, xml(series));
+    }
+
+    for (c, case) in table.cases.iter().enumerate() {
+        let top = LEGEND + c * group;
+        let middle = top + BAR * table.series.len() / 2 + 4;
+        let _ = writeln!(
+            out,
+            r#"  <text x="{}" y="{}" text-anchor="end">{}</text>"#,
+            LABEL_WIDTH - 6,
+            middle,
+            xml(case)
+        );
+        for s in 0..table.series.len() {
+            let Some(value) = table.get(c, s) else {
+                continue;
+            };
+            let (y, bar) = (top + s * BAR, (value * scale).round() as usize);
+            let color = PALETTE[s % PALETTE.len()];
+            let _ = writeln!(
+                out,
+                r#"  <rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+                LABEL_WIDTH,
+                y,
+                bar,
+                BAR - 2,
+                color
+            );
+            let _ = writeln!(
+                out,
+                r#"  <text x="{}" y="{}">{:.2}</text>"#,
+                LABEL_WIDTH + bar + 4,
+                y + BAR - 4,
+                value
+            );
+        }
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+/// The CPUs of `rows` in order of appearance, after their source when
+/// there is one: `m1: Apple M1, 4P+4E, L1d 128 KB, ...`.
+pub fn machines(rows: &[Row]) -> Vec<String> {
+    let mut machines = Vec::new();
+    for row in rows.iter().filter(|row| !row.cpu.is_empty()) {
+        let machine = match row.source.as_str() {
+            "" => row.cpu.clone(),
+            source => format!("{}: {}", source, row.cpu),
+        };
+        position_or_push(&mut machines, machine);
+    }
+    machines
+}
+
+/// The tables as an HTML page, each followed by its chart, under the
+/// `machines` they ran on.
+pub fn html(tables: &[Table], machines: &[String]) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Benchmarks</title>\n\
+         <style>body{font-family:sans-serif} td,th{padding:2px 10px;text-align:right} \
+         td:first-child,th:first-child{text-align:left}</style>\n</head>\n<body>\n",
+    );
+    if !machines.is_empty() {
+        out.push_str("<h2>Hardware</h2>\n<ul>\n");
+        for machine in machines {
+            let _ = writeln!(out, "<li>{}</li>", xml(machine));
+        }
+        out.push_str("</ul>\n");
+    }
+    for table in tables {
+        let _ = writeln!(out, "<h2>{}</h2>\n<table>", xml(&table.kernel));
+        let mut header: Vec<String> = vec!["Case".into()];
+        header.extend(table.series.iter().map(|s| xml(s)));
+        let _ = writeln!(out, "<tr><th>{}</th></tr>", header.join("</th><th>"));
+        for (c, case) in table.cases.iter().enumerate() {
+            let mut line = vec![xml(case)];
+            for s in 0..table.series.len() {
+                line.push(
+                    table
+                        .get(c, s)
+                        .map_or("–".to_string(), |v| format!("{:.2} GB/s", v)),
+                );
+            }
+            let _ = writeln!(out, "<tr><td>{}</td></tr>", line.join("</td><td>"));
+        }
+        out.push_str("</table>\n");
+        out.push_str(&svg(table));
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn xml(s: &str) -> String {
+    String::from_utf8(escape_html(s.as_bytes())).expect("escaping keeps UTF-8")
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::{write_measurement, Labels, Measurement, RecordFormat};
+    use std::time::Duration;
+
+    #[test]
+    fn test_report() {
+        let measurement = |us| Measurement {
+            samples: vec![Duration::from_micros(us); 3],
+            runs_per_sample: 1,
+            bytes: 3 << 20,
+            energy_uj: None,
+        };
+        let records = [
+            ("Scalar (clean, 1 MB)", Labels::new("count", "scalar"), 1000),
+            ("SWAR (clean, 1 MB)", Labels::new("count", "SWAR"), 250),
+            ("Scalar (mixed | \"q\", 1 MB)", Labels::new("count", "scalar"), 1000),
+            ("NEON (K=64)", Labels::new("insert_line_feed", "NEON").with_k(64), 500),
+        ];
+        for format in [RecordFormat::Json, RecordFormat::Csv] {
+            let mut data = Vec::new();
+            if format == RecordFormat::Csv {
+                crate::csv_write::write_record(crate::bench::CSV_HEADER, &mut data);
+            }
+            for (name, labels, us) in &records {
+                write_measurement(format, name, labels, &measurement(*us), &mut data);
+            }
+            let rows = parse_records(&data).unwrap();
+            assert_eq!(rows.len(), 4);
+            assert_eq!(rows[2].name, "Scalar (mixed | \"q\", 1 MB)");
+            assert_eq!(
+                (rows[3].k, rows[3].input_size, rows[3].median_ms),
+                (Some(64), 1 << 20, 0.5)
+            );
+
+            let tables = tables(&rows);
+            assert_eq!(tables.len(), 2);
+            assert_eq!(tables[0].series, ["scalar", "SWAR"]);
+            assert_eq!(tables[0].cases, ["clean, 1 MB", "mixed | \"q\", 1 MB"]);
+            assert_eq!(tables[1].cases, ["K=64"]);
+            assert_eq!(
+                markdown(&tables[0]),
+                "| Case | scalar | SWAR | SWAR / scalar |\n\
+                 |---|--:|--:|--:|\n\
+                 | clean, 1 MB | 1.05 GB/s | 4.19 GB/s | 4.00x |\n\
+                 | mixed \\| \"q\", 1 MB | 1.05 GB/s | – | – |\n"
+            );
+            let chart = svg(&tables[0]);
+            assert_eq!(chart.matches("<rect").count(), 2 + 3);
+            assert!(chart.contains("mixed | &quot;q&quot;, 1 MB"));
+            let cpu = crate::cpu::info().to_string();
+            assert_eq!(rows[0].cpu, cpu);
+            assert_eq!(machines(&rows), std::slice::from_ref(&cpu));
+            let page = html(&tables, &machines(&rows));
+            assert!(page.contains("<h2>insert_line_feed</h2>"));
+            assert!(page.contains(&format!("<li>{}</li>", xml(&cpu))));
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let csv = b"name,backend,throughput_gb_s\nA,x,1.5\nname,backend,throughput_gb_s\nA,x,3\n";
+        let mut two_machines = parse_records(csv).unwrap();
+        assert_eq!(two_machines.len(), 2);
+        (two_machines[0].source, two_machines[1].source) = ("m1".into(), "x86".into());
+        assert!(machines(&two_machines).is_empty());
+        two_machines[1].cpu = "Xeon, 1 CPU".into();
+        assert_eq!(machines(&two_machines), ["x86: Xeon, 1 CPU"]);
+        let table = &tables(&two_machines)[0];
+        assert_eq!(
+            (table.series.as_slice(), table.get(0, 1)),
+            (&["m1 x".to_string(), "x86 x".to_string()][..], Some(3.0))
+        );
+
+        let err = parse_records(b"{\"name\":\"A\",\"throughput_gb_s\":1}\n{\"name\":[1]}\n");
+        assert_eq!(err.unwrap_err().to_string(), "line 2: not a flat JSON object");
+        let err = parse_records(b"name,throughput_gb_s\nA,1\nB,fast\n").unwrap_err();
+        assert_eq!((err.line, err.message.as_str()), (3, "throughput_gb_s: not a number: fast"));
+
+        let line = br#"{"a":"\u00e9\ud83d\ude00\"\\\n","b":null, "c" : 2.5}"#;
+        let fields = json_fields(line).unwrap();
+        assert_eq!(fields[0].1.as_deref(), Some("\u{e9}\u{1f600}\"\\\n"));
+        assert_eq!((fields[1].1.as_deref(), fields[2].1.as_deref()), (None, Some("2.5")));
+    }
+}
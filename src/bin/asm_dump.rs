@@ -0,0 +1,416 @@
+//! Dump and snapshot the generated assembly of the SIMD kernels.
+//!
+//! The comment blocks next to each kernel explain why it is fast; this tool
+//! checks that the code the compiler emits still matches. It builds the
+//! library with `--emit asm`, cuts out the selected functions, normalizes
+//! them (hashes, label numbers and debug directives removed) and compares
+//! them with the snapshots checked in under `asm/<target>/`:
+//!
+//!   cargo run --bin asm_dump -- --list                 kernels it knows
+//!   cargo run --bin asm_dump -- max_registers_neon     compare one kernel
+//!   cargo run --bin asm_dump                           compare all kernels
+//!   cargo run --bin asm_dump -- --bless                rewrite the snapshots
+//!   cargo run --bin asm_dump -- --target aarch64-unknown-linux-gnu
+//!
+//! A lost autovectorization or an extra bounds check then shows up as a
+//! snapshot diff in review instead of as a slower benchmark weeks later.
+//! Exits with status 1 if any kernel differs from its snapshot.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+
+/// Kernels tracked by default. Functions not compiled for the target (NEON
+/// on x86, ...) are skipped.
+const KERNELS: &[&str] = &[
+    "json_escape_SWAR::count_escapable_bytes_swar",
+    "json_escape_SWAR::count_escapable_bytes_neon",
+    "json_escape_SWAR::has_json_escapable_byte",
+    "json_escape_utf16::has_json_escapable_utf16_swar",
+    "json_escape_utf16::has_json_escapable_utf16_neon",
+    "byte_set::ByteSet::copy_until_swar",
+    "byte_set::ByteSet::copy_until_neon",
+    "wtf8::find_invalid_surrogate",
+    "utf8::valid_up_to",
+    "hll::max_registers_swar",
+    "hll::max_registers_neon",
+    "cms::add_counters_neon",
+    "sha256::compress_neon",
+    "line_feed_every_k_bytes::insert_line_feed_neon_unchecked_into",
+];
+
+const CRATE_NAME: &str = "scratchpad";
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                              Symbol Lookup
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Legacy mangled prefix of `scratchpad::<path>`, up to the hash.
+///
+///   hll::max_registers_neon  ->  _ZN10scratchpad3hll18max_registers_neon
+fn mangled_prefix(path: &str) -> String {
+    let mut mangled = format!("_ZN{}{}", CRATE_NAME.len(), CRATE_NAME);
+    for segment in path.split("::") {
+        mangled.push_str(&format!("{}{}", segment.len(), segment));
+    }
+    mangled
+}
+
+/// Whether `label` is the symbol of `path`: its prefix followed by a hash.
+fn is_symbol_of(label: &str, prefix: &str) -> bool {
+    label
+        .strip_prefix(prefix)
+        .and_then(|rest| rest.strip_prefix("17h"))
+        .is_some_and(|rest| rest.len() == 17 && rest.ends_with('E'))
+}
+
+/// The lines of function `path` in an assembly listing, if it was emitted.
+fn extract_function<'a>(asm: &'a str, path: &str) -> Option<Vec<&'a str>> {
+    let prefix = mangled_prefix(path);
+    let mut lines = asm.lines();
+
+    lines.by_ref().find(|line| {
+        line.strip_suffix(':')
+            .is_some_and(|label| is_symbol_of(label, &prefix))
+    })?;
+
+    Some(
+        lines
+            .take_while(|line| !line.starts_with(".Lfunc_end"))
+            .collect(),
+    )
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                              Normalization
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Remove the characters matching `part` after every occurrence of `marker`.
+fn strip_after(line: &str, marker: &str, part: impl Fn(char) -> bool) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(pos) = rest.find(marker) {
+        out.push_str(&rest[..pos + marker.len()]);
+        rest = rest[pos + marker.len()..].trim_start_matches(&part);
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Replace the digits after every occurrence of `marker` (label numbers).
+fn strip_numbers_after(line: &str, marker: &str) -> String {
+    strip_after(line, marker, |c| c.is_ascii_digit())
+}
+
+/// Remove the `17h<16 hex digits>` hash from mangled symbols.
+fn strip_symbol_hashes(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(pos) = rest.find("17h") {
+        let after = &rest[pos + 3..];
+        let is_hash = after.len() >= 17
+            && after.as_bytes()[16] == b'E'
+            && after.as_bytes()[..16].iter().all(u8::is_ascii_hexdigit);
+        if is_hash {
+            out.push_str(&rest[..pos]);
+            rest = &after[16..];
+        } else {
+            out.push_str(&rest[..pos + 3]);
+            rest = after;
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Make a function body independent of unrelated changes elsewhere in the
+/// crate: function-numbered labels, symbol hashes, anonymous constants
+/// (`.Lanon.<hash>.<n>`) and debug directives.
+fn normalize(lines: &[&str]) -> Vec<String> {
+    lines
+        .iter()
+        .filter(|line| {
+            let t = line.trim_start();
+            !(t.starts_with(".cfi_")
+                || t.starts_with(".loc")
+                || t.starts_with(".file")
+                || t.starts_with(".p2align")
+                || t.starts_with("//")
+                || t.starts_with('#')
+                || t.is_empty())
+        })
+        .map(|line| {
+            let line = strip_symbol_hashes(line);
+            let line = strip_numbers_after(&line, ".LBB");
+            let line = strip_numbers_after(&line, ".LCPI");
+            let line = strip_after(&line, ".Lanon", |c| c == '.' || c.is_ascii_hexdigit());
+            strip_numbers_after(&line, ".Ltmp")
+        })
+        .collect()
+}
+
+/// Instructions in a normalized body: everything but labels and directives.
+fn instruction_count(body: &[String]) -> usize {
+    body.iter()
+        .filter(|line| !line.ends_with(':') && !line.trim_start().starts_with('.'))
+        .count()
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                  Diff
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Line diff of two snapshots (LCS; kernels are a few hundred lines at most).
+fn diff(old: &[String], new: &[String]) -> Vec<String> {
+    let (n, m) = (old.len(), new.len());
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            out.push(format!("+{}", new[j]));
+            j += 1;
+        } else {
+            out.push(format!("-{}", old[i]));
+            i += 1;
+        }
+    }
+    out
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                  Build
+// ═══════════════════════════════════════════════════════════════════════════
+
+fn host_target() -> String {
+    let output = Command::new("rustc")
+        .arg("-vV")
+        .output()
+        .expect("failed to run rustc -vV");
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .expect("no host line in rustc -vV")
+        .to_string()
+}
+
+/// Build the library in release mode with `--emit asm` and read the listing.
+fn build_asm(manifest_dir: &Path, target: &str) -> String {
+    let target_dir = manifest_dir.join("target").join("asm_dump");
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+
+    let status = Command::new(cargo)
+        .current_dir(manifest_dir)
+        .args(["rustc", "--release", "--lib", "--target", target])
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .args(["--", "--emit", "asm"])
+        .status()
+        .expect("failed to run cargo");
+    if !status.success() {
+        eprintln!("asm_dump: build for {} failed", target);
+        process::exit(2);
+    }
+
+    let deps = target_dir.join(target).join("release").join("deps");
+    let newest = fs::read_dir(&deps)
+        .expect("no deps directory")
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|p| {
+            let name = p.file_name().unwrap().to_string_lossy();
+            name.starts_with(&format!("{}-", CRATE_NAME)) && name.ends_with(".s")
+        })
+        .max_by_key(|p| fs::metadata(p).and_then(|m| m.modified()).ok());
+
+    match newest {
+        Some(path) => fs::read_to_string(path).expect("failed to read assembly"),
+        None => {
+            eprintln!("asm_dump: no assembly found in {}", deps.display());
+            process::exit(2);
+        }
+    }
+}
+
+fn snapshot_path(manifest_dir: &Path, target: &str, kernel: &str) -> PathBuf {
+    manifest_dir
+        .join("asm")
+        .join(target)
+        .join(format!("{}.s", kernel.replace("::", "__")))
+}
+
+/// Full kernel paths for the names on the command line (a unique suffix
+/// such as `max_registers_neon` is enough).
+fn resolve(names: &[String]) -> Vec<String> {
+    if names.is_empty() {
+        return KERNELS.iter().map(|k| k.to_string()).collect();
+    }
+
+    names
+        .iter()
+        .map(|name| {
+            let matches: Vec<&&str> = KERNELS
+                .iter()
+                .filter(|k| *k == name || k.ends_with(&format!("::{}", name)))
+                .collect();
+            match matches.as_slice() {
+                [one] => one.to_string(),
+                // Not in the list: take it as a full path
+                [] => name.clone(),
+                _ => {
+                    eprintln!("asm_dump: {} is ambiguous: {:?}", name, matches);
+                    process::exit(2);
+                }
+            }
+        })
+        .collect()
+}
+
+fn main() {
+    let mut target = None;
+    let mut bless = false;
+    let mut names = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bless" => bless = true,
+            "--target" => target = args.next(),
+            "--list" => {
+                KERNELS.iter().for_each(|k| println!("{}", k));
+                return;
+            }
+            _ => names.push(arg),
+        }
+    }
+
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target = target.unwrap_or_else(host_target);
+    let asm = build_asm(manifest_dir, &target);
+
+    let mut changed = 0;
+    for kernel in resolve(&names) {
+        let Some(lines) = extract_function(&asm, &kernel) else {
+            println!("{:60} not emitted for {}", kernel, target);
+            continue;
+        };
+        let body = normalize(&lines);
+        let count = instruction_count(&body);
+        let path = snapshot_path(manifest_dir, &target, &kernel);
+
+        if bless {
+            fs::create_dir_all(path.parent().unwrap()).expect("failed to create snapshot dir");
+            fs::write(&path, body.join("\n") + "\n").expect("failed to write snapshot");
+            println!("{:60} {:5} instructions, snapshot written", kernel, count);
+            continue;
+        }
+
+        let Ok(snapshot) = fs::read_to_string(&path) else {
+            println!("{:60} {:5} instructions, no snapshot (run with --bless)", kernel, count);
+            continue;
+        };
+        let old: Vec<String> = snapshot.lines().map(str::to_string).collect();
+        if old == body {
+            println!("{:60} {:5} instructions, unchanged", kernel, count);
+        } else {
+            changed += 1;
+            println!("{:60} {:5} instructions, was {}:", kernel, count, instruction_count(&old));
+            for line in diff(&old, &body) {
+                println!("    {}", line);
+            }
+        }
+    }
+
+    if changed > 0 {
+        eprintln!("asm_dump: {} kernel(s) differ from their snapshots", changed);
+        process::exit(1);
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LISTING: &str = "\
+_ZN10scratchpad3hll18max_registers_neon17h7492d3f78fd512f5E:
+\t.cfi_startproc
+\tcmp\tx8, #16
+\tb.hs\t.LBB105_2
+.LBB105_2:
+\tbl\t_ZN4core5slice5index24slice_end_index_len_fail17h0123456789abcdefE
+\tret
+.Lfunc_end105:
+_ZN10scratchpad3hll18max_registers_swar17ha1ee1ce1a1a95538E:
+\tret
+";
+
+    #[test]
+    fn test_extract_and_normalize() {
+        assert_eq!(
+            mangled_prefix("hll::max_registers_neon"),
+            "_ZN10scratchpad3hll18max_registers_neon"
+        );
+
+        let lines = extract_function(LISTING, "hll::max_registers_neon").unwrap();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(
+            normalize(&lines),
+            vec![
+                "\tcmp\tx8, #16",
+                "\tb.hs\t.LBB_2",
+                ".LBB_2:",
+                "\tbl\t_ZN4core5slice5index24slice_end_index_len_failE",
+                "\tret",
+            ]
+        );
+        assert_eq!(instruction_count(&normalize(&lines)), 4);
+
+        // A prefix of another symbol is not a match
+        assert!(extract_function(LISTING, "hll::max_registers").is_none());
+    }
+
+    #[test]
+    fn test_normalize_anon_constants() {
+        // Hash and number of `.Lanon` labels change with unrelated code
+        let lines = [
+            "\tadrp\tx2, .Lanon.177c52a0e367ec7d1807e06052e2c846.83",
+            "\tadd\tx2, x2, :lo12:.Lanon.177c52a0e367ec7d1807e06052e2c846.83",
+            "\tldr\tq0, [x8, :lo12:.Lanon.0d4f9a.7+16]",
+        ];
+        assert_eq!(
+            normalize(&lines),
+            vec![
+                "\tadrp\tx2, .Lanon",
+                "\tadd\tx2, x2, :lo12:.Lanon",
+                "\tldr\tq0, [x8, :lo12:.Lanon+16]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let old: Vec<String> = ["a", "b", "c"].iter().map(|s| s.to_string()).collect();
+        let new: Vec<String> = ["a", "x", "c", "d"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(diff(&old, &new), vec!["+x", "-b", "+d"]);
+        assert!(diff(&old, &old).is_empty());
+    }
+}
@@ -0,0 +1,152 @@
+//! Markdown, HTML and SVG from the records the benches write with `--json`
+//! or `--csv` (see `bench_report`):
+//!
+//!   cargo run --bin bench_report -- records.jsonl                  into target/bench-report
+//!   cargo run --bin bench_report -- --out docs m1.jsonl x86.csv    a column per file and backend
+//!
+//! Writes `report.md` (a table per kernel), `report.html` (the tables and
+//! their charts) and a `<kernel>.svg` chart per kernel, under the CPUs the
+//! records came from. With several files, the columns are named after the
+//! files (`m1 SWAR`, `x86 SWAR`).
+//!
+//! Exits with status 0 on success, 2 on an error.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use scratchpad::bench_report::{html, machines, markdown, parse_records, svg, tables};
+
+const DEFAULT_OUT: &str = "target/bench-report";
+
+#[derive(Debug, PartialEq, Eq)]
+struct Args {
+    out: PathBuf,
+    files: Vec<String>,
+}
+
+fn usage() -> ! {
+    eprintln!("usage: bench_report [--out DIR] FILE...");
+    process::exit(2);
+}
+
+/// Parse the command line (without the program name); `None` if it is not
+/// a valid one.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Option<Args> {
+    let mut out = PathBuf::from(DEFAULT_OUT);
+    let mut files = Vec::new();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" | "-o" => out = PathBuf::from(args.next()?),
+            _ if arg.starts_with('-') && arg.len() > 1 => return None,
+            _ => files.push(arg),
+        }
+    }
+    if files.is_empty() {
+        return None;
+    }
+    Some(Args { out, files })
+}
+
+/// A file name for the chart of `kernel`: anything but letters, digits, `-`
+/// and `_` becomes `_`.
+fn chart_name(kernel: &str) -> String {
+    let name: String = kernel
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}.svg", name)
+}
+
+fn run(args: &Args) -> Result<(), String> {
+    let mut rows = Vec::new();
+    for file in &args.files {
+        let data = fs::read(file).map_err(|e| format!("{}: {}", file, e))?;
+        let mut records = parse_records(&data).map_err(|e| format!("{}: {}", file, e))?;
+        if args.files.len() > 1 {
+            let stem = Path::new(file)
+                .file_stem()
+                .map_or(file.clone(), |s| s.to_string_lossy().into());
+            records.iter_mut().for_each(|r| r.source = stem.clone());
+        }
+        rows.extend(records);
+    }
+    if rows.is_empty() {
+        return Err("no records".to_string());
+    }
+
+    let tables = tables(&rows);
+    let machines = machines(&rows);
+    let write = |name: &str, contents: &str| {
+        let path = args.out.join(name);
+        fs::write(&path, contents).map_err(|e| format!("{}: {}", path.display(), e))
+    };
+    fs::create_dir_all(&args.out).map_err(|e| format!("{}: {}", args.out.display(), e))?;
+
+    let mut report = String::new();
+    if !machines.is_empty() {
+        report += "## Hardware\n\n";
+        for machine in &machines {
+            report += &format!("- {}\n", machine);
+        }
+        report += "\n";
+    }
+    for table in &tables {
+        let chart = chart_name(&table.kernel);
+        report += &format!(
+            "## {}\n\n{}\n![{}]({})\n\n",
+            table.kernel,
+            markdown(table),
+            table.kernel,
+            chart
+        );
+        write(&chart, &svg(table))?;
+    }
+    write("report.md", &report)?;
+    write("report.html", &html(&tables, &machines))?;
+    println!("{} records, {} kernels: {}", rows.len(), tables.len(), args.out.display());
+    Ok(())
+}
+
+fn main() {
+    let args = parse_args(env::args().skip(1)).unwrap_or_else(|| usage());
+    if let Err(e) = run(&args) {
+        eprintln!("bench_report: {}", e);
+        process::exit(2);
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Option<Args> {
+        parse_args(line.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let parsed = args("m1.jsonl x86.csv").unwrap();
+        assert_eq!((parsed.out, parsed.files.len()), (PathBuf::from(DEFAULT_OUT), 2));
+        assert_eq!(args("--out docs a.jsonl").unwrap().out, PathBuf::from("docs"));
+        assert_eq!(args("a.jsonl --out"), None);
+        assert_eq!(args("--out docs"), None);
+        assert_eq!(args("--bogus a.jsonl"), None);
+        assert_eq!(chart_name("insert_line_feed"), "insert_line_feed.svg");
+        assert_eq!(chart_name("count / escape"), "count___escape.svg");
+    }
+}
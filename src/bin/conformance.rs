@@ -0,0 +1,105 @@
+//! Every kernel against its scalar reference, on every input length up to
+//! `--max-len` (see `conformance`):
+//!
+//!   cargo run --release --bin conformance                                  all kernels, 4 KB
+//!   cargo run --release --bin conformance -- --kernel find --seed 7        one kernel
+//!   cargo run --release --bin conformance -- --max-len 65536               longer inputs
+//!
+//! Prints the first divergence and exits with status 1, or a summary with
+//! status 0. Status 2 on a bad command line.
+
+use std::{env, process, time::Instant};
+
+use scratchpad::conformance::{run, Corpus, Kernel, KERNELS};
+
+#[derive(Debug, PartialEq, Eq)]
+struct Args {
+    corpus: Corpus,
+    /// The kernels to run; all of them if empty.
+    kernels: Vec<String>,
+}
+
+fn usage() -> ! {
+    eprintln!("usage: conformance [--max-len BYTES] [--seed N] [--kernel NAME]...");
+    eprintln!("kernels:");
+    for kernel in KERNELS {
+        eprintln!("  {}", kernel.name);
+    }
+    process::exit(2);
+}
+
+/// Parse the command line (without the program name); `None` if it is not
+/// a valid one.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Option<Args> {
+    let mut corpus = Corpus::default();
+    let mut kernels = Vec::new();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--max-len" => corpus.max_len = args.next()?.parse().ok()?,
+            "--seed" => corpus.seed = args.next()?.parse().ok()?,
+            "--kernel" => kernels.push(args.next()?),
+            _ => return None,
+        }
+    }
+    Some(Args { corpus, kernels })
+}
+
+/// The kernels named in `names` (all of them if there are none), or the
+/// first name that is not a kernel.
+fn select(names: &[String]) -> Result<Vec<Kernel>, &str> {
+    if names.is_empty() {
+        return Ok(KERNELS.to_vec());
+    }
+    names
+        .iter()
+        .map(|name| {
+            let kernel = KERNELS.iter().find(|k| k.name == name);
+            kernel.cloned().ok_or(name.as_str())
+        })
+        .collect()
+}
+
+fn main() {
+    let args = parse_args(env::args().skip(1)).unwrap_or_else(|| usage());
+    let kernels = select(&args.kernels).unwrap_or_else(|name| {
+        eprintln!("conformance: no kernel named {}", name);
+        usage()
+    });
+
+    let start = Instant::now();
+    match run(&args.corpus, &kernels) {
+        Ok(summary) => println!("{} ({:.1} s)", summary, start.elapsed().as_secs_f64()),
+        Err(divergence) => {
+            println!("{}", divergence);
+            process::exit(1);
+        }
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Option<Args> {
+        parse_args(line.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(args("").unwrap().corpus, Corpus::default());
+        let parsed = args("--max-len 100 --seed 7 --kernel find --kernel escape_json").unwrap();
+        assert_eq!(parsed.corpus, Corpus { max_len: 100, seed: 7 });
+        assert_eq!(select(&parsed.kernels).unwrap().len(), 2);
+        assert_eq!(select(&[]).unwrap().len(), KERNELS.len());
+        assert_eq!(select(&["nope".to_string()]).unwrap_err(), "nope");
+        assert_eq!(args("--max-len"), None);
+        assert_eq!(args("--seed x"), None);
+        assert_eq!(args("file"), None);
+    }
+}
@@ -0,0 +1,331 @@
+//! A small grep over the pattern matchers of `csv_parse_buffer_size_impact`.
+//!
+//! The benches time the search on generated CSV; this runs it on real files,
+//! with the output of `grep` for the three things the benches count:
+//!
+//!   cargo run --release --bin sgrep -- Harvard people.csv            matching lines
+//!   cargo run --release --bin sgrep -- --count Harvard *.csv         lines per file
+//!   cargo run --release --bin sgrep -- --column ERROR app.log        line:column:text
+//!   cargo run --release --bin sgrep -- --format ndjson ERROR *.log   JSON objects
+//!
+//! With `--format csv|ndjson|msgpack` the same results are rows of a
+//! [`Sink`], with the file name in a `file` column: `file,text` for the
+//! lines, `file,count` for the counts, `file,line,column,text` for the
+//! columns.
+//!
+//! Text output is prefixed with the file name when several files are given,
+//! as with grep. `--count` never holds more than a read buffer of the file;
+//! `--lines` holds a line at a time; `--column` reads the whole file once,
+//! and locates the matches in the bytes it prints from. A leading UTF-8 BOM
+//! is skipped and lines end at `\n` (`Dialect::CSV`).
+//!
+//! Exits with status 0 if a line matched, 1 if none did, 2 on an error.
+
+use std::{
+    env, fs,
+    io::{self, BufWriter, Write},
+    process,
+};
+
+use scratchpad::csv_dialect::Dialect;
+use scratchpad::csv_parse_buffer_size_impact::{
+    count_pattern_matches_from_file, find_pattern_matches_in_slice, for_each_matching_line,
+    MatchMode,
+};
+use scratchpad::sink::{OutputFormat, Sink, Value};
+
+/// What is printed for each file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    /// The matching lines.
+    Lines,
+    /// The number of matching lines.
+    Count,
+    /// Line number, column and text of the first match of each line.
+    Column,
+}
+
+impl Output {
+    /// The columns of its rows, when written to a [`Sink`].
+    fn columns(self) -> &'static [&'static str] {
+        match self {
+            Output::Lines => &["file", "text"],
+            Output::Count => &["file", "count"],
+            Output::Column => &["file", "line", "column", "text"],
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Args {
+    output: Output,
+    /// Rows in this format instead of grep's text.
+    format: Option<OutputFormat>,
+    pattern: String,
+    files: Vec<String>,
+}
+
+fn usage() -> ! {
+    eprintln!("usage: sgrep [--count | --lines | --column] [--format FORMAT] PATTERN FILE...");
+    eprintln!("formats: csv, ndjson, msgpack");
+    process::exit(2);
+}
+
+/// Parse the command line (without the program name); `None` if it is not
+/// a valid one.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Option<Args> {
+    let mut output = Output::Lines;
+    let mut format = None;
+    let mut positional = Vec::new();
+    let mut options_done = false;
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--count" | "-c" if !options_done => output = Output::Count,
+            "--lines" if !options_done => output = Output::Lines,
+            "--column" if !options_done => output = Output::Column,
+            "--format" if !options_done => format = Some(args.next()?.parse().ok()?),
+            "--" if !options_done => options_done = true,
+            _ if arg.starts_with('-') && arg.len() > 1 && !options_done => return None,
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let pattern = positional.next()?;
+    let files: Vec<String> = positional.collect();
+    if pattern.is_empty() || files.is_empty() {
+        return None;
+    }
+    Some(Args { output, format, pattern, files })
+}
+
+/// One result of a search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Hit<'a> {
+    /// The number of matching lines of the file.
+    Count(usize),
+    /// A matching line, without its terminator.
+    Line(&'a [u8]),
+    /// Line number and column (both from 1) of the first match of a line.
+    Column {
+        line: usize,
+        column: usize,
+        text: &'a [u8],
+    },
+}
+
+/// Search one file, passing each result to `emit`. Returns the number of
+/// matching lines.
+fn search(
+    path: &str,
+    pattern: &[u8],
+    output: Output,
+    mut emit: impl FnMut(Hit<'_>) -> io::Result<()>,
+) -> io::Result<usize> {
+    match output {
+        Output::Count => {
+            let count = count_pattern_matches_from_file(path, pattern)?;
+            emit(Hit::Count(count))?;
+            Ok(count)
+        }
+        Output::Lines => {
+            let mut count = 0;
+            // The callback cannot fail: keep the first write error for later
+            let mut written = Ok(());
+            for_each_matching_line(path, pattern, |line| {
+                count += 1;
+                if written.is_ok() {
+                    written = emit(Hit::Line(line));
+                }
+            })?;
+            written.map(|_| count)
+        }
+        Output::Column => {
+            // One read: the matches are located in the bytes that are printed
+            let data = fs::read(path)?;
+            let matches =
+                find_pattern_matches_in_slice(&data, pattern, &Dialect::CSV, MatchMode::Substring);
+            for m in &matches {
+                let start = m.offset - m.column;
+                let end = memchr::memchr(b'\n', &data[start..]).map_or(data.len(), |i| start + i);
+                let text = &data[start..end];
+                emit(Hit::Column { line: m.line, column: m.column + 1, text })?;
+            }
+            Ok(matches.len())
+        }
+    }
+}
+
+/// Where the results go.
+enum Printer<'w, W: Write> {
+    /// grep's lines, prefixed with the file name when `names`.
+    Text { out: W, names: bool },
+    /// Rows of [`Output::columns`].
+    Rows(Box<dyn Sink + 'w>),
+}
+
+impl<'w, W: Write + 'w> Printer<'w, W> {
+    fn new(output: Output, format: Option<OutputFormat>, out: W, names: bool) -> io::Result<Self> {
+        let Some(format) = format else {
+            return Ok(Printer::Text { out, names });
+        };
+        let mut sink = format.sink(out);
+        sink.begin(output.columns())?;
+        Ok(Printer::Rows(sink))
+    }
+
+    fn print(&mut self, path: &str, hit: Hit<'_>) -> io::Result<()> {
+        match self {
+            Printer::Text { out, names } => {
+                let prefix = if *names {
+                    format!("{}:", path)
+                } else {
+                    String::new()
+                };
+                match hit {
+                    Hit::Count(count) => writeln!(out, "{}{}", prefix, count),
+                    Hit::Line(line) => write_line(out, &prefix, line),
+                    Hit::Column { line, column, text } => {
+                        write_line(out, &format!("{}{}:{}:", prefix, line, column), text)
+                    }
+                }
+            }
+            Printer::Rows(sink) => {
+                let file = Value::from(path);
+                let int = |n: usize| Value::Int(n as i64);
+                match hit {
+                    Hit::Count(count) => sink.row(&[file, int(count)]),
+                    Hit::Line(line) => sink.row(&[file, Value::Str(without_cr(line))]),
+                    Hit::Column { line, column, text } => {
+                        sink.row(&[file, int(line), int(column), Value::Str(without_cr(text))])
+                    }
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match self {
+            Printer::Text { out, .. } => out.flush(),
+            Printer::Rows(sink) => sink.finish(),
+        }
+    }
+}
+
+/// `line` without the `\r` of a `\r\n` terminator.
+fn without_cr(line: &[u8]) -> &[u8] {
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// Write `prefix`, `line` (bytes as they are, not necessarily UTF-8) and a
+/// newline.
+fn write_line<W: Write>(out: &mut W, prefix: &str, line: &[u8]) -> io::Result<()> {
+    out.write_all(prefix.as_bytes())?;
+    out.write_all(without_cr(line))?;
+    out.write_all(b"\n")
+}
+
+fn main() {
+    let args = parse_args(env::args().skip(1)).unwrap_or_else(|| usage());
+    let out = BufWriter::new(io::stdout().lock());
+    let mut matched = false;
+    let mut failed = false;
+
+    let names = args.files.len() > 1;
+    let mut printer = Printer::new(args.output, args.format, out, names).unwrap_or_else(|e| {
+        eprintln!("sgrep: {}", e);
+        process::exit(2)
+    });
+    for path in &args.files {
+        match search(path, args.pattern.as_bytes(), args.output, |hit| printer.print(path, hit)) {
+            Ok(count) => matched |= count > 0,
+            // Output closed (`sgrep ... | head`): nothing left to do
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => process::exit(0),
+            Err(e) => {
+                eprintln!("sgrep: {}: {}", path, e);
+                failed = true;
+            }
+        }
+    }
+
+    if let Err(e) = printer.finish() {
+        if e.kind() != io::ErrorKind::BrokenPipe {
+            eprintln!("sgrep: {}", e);
+            failed = true;
+        }
+    }
+    process::exit(match (failed, matched) {
+        (true, _) => 2,
+        (false, true) => 0,
+        (false, false) => 1,
+    });
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Option<Args> {
+        parse_args(line.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let parsed = args("--count MIT a.csv b.csv").unwrap();
+        assert_eq!(parsed.output, Output::Count);
+        assert_eq!((parsed.pattern.as_str(), parsed.files.len()), ("MIT", 2));
+        assert_eq!(args("MIT a.csv --column").unwrap().output, Output::Column);
+        assert_eq!(args("-- --count a.csv").unwrap().pattern, "--count");
+        let formatted = args("--format ndjson --count MIT a.csv").unwrap();
+        assert_eq!(
+            (formatted.format, formatted.output),
+            (Some(OutputFormat::Ndjson), Output::Count)
+        );
+        assert_eq!(args("MIT a.csv").unwrap().format, None);
+        assert_eq!(args("--format xml MIT a.csv"), None);
+        assert_eq!(args("MIT a.csv --format"), None);
+        assert_eq!(args("MIT"), None);
+        assert_eq!(args("--bogus MIT a.csv"), None);
+    }
+
+    #[test]
+    fn test_search_outputs() {
+        let path = "/tmp/test_sgrep.csv";
+        fs::write(path, b"\xEF\xBB\xBFName,University\nAnn,Harvard\r\nBob,MIT\nCid,Harvard\n")
+            .unwrap();
+        let run = |output, format, names| {
+            let mut out = Vec::new();
+            let mut printer = Printer::new(output, format, &mut out, names).unwrap();
+            let count = search(path, b"Harvard", output, |hit| printer.print("f", hit)).unwrap();
+            printer.finish().unwrap();
+            drop(printer);
+            (count, out)
+        };
+
+        let text = |output, names| run(output, None, names);
+        assert_eq!(text(Output::Count, false), (2, b"2\n".to_vec()));
+        assert_eq!(text(Output::Lines, true), (2, b"f:Ann,Harvard\nf:Cid,Harvard\n".to_vec()));
+        let columns = b"2:5:Ann,Harvard\n4:5:Cid,Harvard\n";
+        assert_eq!(text(Output::Column, false), (2, columns.to_vec()));
+
+        // The same results as rows
+        let csv = run(Output::Column, Some(OutputFormat::Csv), false).1;
+        let rows = b"file,line,column,text\nf,2,5,\"Ann,Harvard\"\nf,4,5,\"Cid,Harvard\"\n";
+        assert_eq!(csv, rows);
+        let ndjson = run(Output::Count, Some(OutputFormat::Ndjson), false).1;
+        assert_eq!(ndjson, b"{\"file\":\"f\",\"count\":2}\n");
+        let msgpack = run(Output::Lines, Some(OutputFormat::MsgPack), false).1;
+        assert!(msgpack.starts_with(b"\x82\xA4file\xA1f\xA4text\xABAnn,Harvard"));
+
+        assert_eq!(search(path, b"Yale", Output::Column, |_| Ok(())).unwrap(), 0);
+        let missing = "/tmp/test_sgrep_missing.csv";
+        assert!(search(missing, b"MIT", Output::Lines, |_| Ok(())).is_err());
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,423 @@
+//! Soak test for the streaming readers: run them over generated CSV for hours
+//! and watch memory and throughput.
+//!
+//! The buffered readers carry a cut-off record (or pattern prefix) from one
+//! read to the next and grow their buffer when a record does not fit. A bug
+//! there rarely fails a unit test; it shows up as RSS creeping up or
+//! throughput sagging after a few hundred GB. This feeds an endless generated
+//! stream to each consumer, on its own thread:
+//!
+//! - `csv_reader::for_each_record_in_reader`: every record is checked against what the generator
+//!   wrote (field count, sequence number)
+//! - `count_pattern_matches_in_reader`: the match count is checked at the end
+//!
+//! Reads return a random number of bytes, so records and patterns get cut at
+//! every position, and now and then a record is longer than the reader's
+//! buffer. A line is printed per consumer every interval; at the end, RSS
+//! growth since the first interval and throughput drift between the first and
+//! last quarter of the run are checked:
+//!
+//!   cargo run --release --bin soak -- --duration 3h --interval 1m --seed 7
+//!
+//! Exits with status 1 if a check fails. RSS is read from `/proc/self/status`,
+//! so it is only checked on Linux.
+
+use std::{
+    env, fs,
+    io::{self, Read},
+    process, thread,
+    time::{Duration, Instant},
+};
+
+use scratchpad::{
+    csv_parse_buffer_size_impact::count_pattern_matches_in_reader,
+    csv_reader::for_each_record_in_reader,
+};
+
+/// RSS may grow this much over the first sample (buffers reaching their
+/// steady-state size) before it counts as a leak.
+const RSS_GROWTH_LIMIT: u64 = 16 << 20;
+/// The last quarter of the run must reach this fraction of the throughput of
+/// the first quarter.
+const THROUGHPUT_DRIFT: f64 = 0.8;
+
+const RECORDS_PER_BATCH: usize = 1000;
+const MAX_READ: usize = 64 << 10;
+const READER_BUFFER_SIZE: usize = 64 << 10;
+/// Longer than the reader's buffer, so that it has to grow.
+const LONG_FIELD: usize = 200 << 10;
+const PATTERN: &[u8] = b"ERROR";
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                Monitoring
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    mb_per_s: f64,
+    rss: Option<u64>,
+}
+
+/// Resident set size of this process, from `/proc/self/status`.
+fn rss_bytes() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb << 10)
+}
+
+/// Throughput and RSS of one consumer, sampled every `interval`.
+struct Monitor {
+    name: &'static str,
+    start: Instant,
+    interval: Duration,
+    next: Duration,
+    last_bytes: u64,
+    last_elapsed: Duration,
+    samples: Vec<Sample>,
+}
+
+impl Monitor {
+    fn new(name: &'static str, interval: Duration) -> Self {
+        Monitor {
+            name,
+            start: Instant::now(),
+            interval,
+            next: interval,
+            last_bytes: 0,
+            last_elapsed: Duration::ZERO,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Record a sample if the interval has passed; `bytes` is the total so far.
+    fn tick(&mut self, bytes: u64) {
+        let elapsed = self.start.elapsed();
+        if elapsed < self.next {
+            return;
+        }
+
+        let seconds = (elapsed - self.last_elapsed).as_secs_f64();
+        let sample =
+            Sample { mb_per_s: (bytes - self.last_bytes) as f64 / seconds / 1e6, rss: rss_bytes() };
+        println!(
+            "[{:>4}m{:02}s] {:8} {:8.1} MB/s  rss {}",
+            elapsed.as_secs() / 60,
+            elapsed.as_secs() % 60,
+            self.name,
+            sample.mb_per_s,
+            sample
+                .rss
+                .map_or("n/a".to_string(), |r| format!("{:.1} MB", r as f64 / 1e6)),
+        );
+
+        self.samples.push(sample);
+        self.last_bytes = bytes;
+        self.last_elapsed = elapsed;
+        self.next += self.interval;
+    }
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    sorted[sorted.len() / 2]
+}
+
+/// Compare the last quarter of the samples with the first (the first sample,
+/// taken while caches and buffers warm up, is left out).
+fn check_throughput(name: &str, samples: &[Sample]) -> Result<(), String> {
+    let rates: Vec<f64> = samples.iter().skip(1).map(|s| s.mb_per_s).collect();
+    let quarter = rates.len() / 4;
+    if quarter == 0 {
+        println!("{}: too few samples to check throughput drift", name);
+        return Ok(());
+    }
+
+    let first = median(&rates[..quarter]);
+    let last = median(&rates[rates.len() - quarter..]);
+    if last < first * THROUGHPUT_DRIFT {
+        return Err(format!("{}: throughput drifted from {:.1} to {:.1} MB/s", name, first, last));
+    }
+    Ok(())
+}
+
+fn check_rss(samples: &[Sample]) -> Result<(), String> {
+    let rss: Vec<u64> = samples.iter().filter_map(|s| s.rss).collect();
+    let (Some(&baseline), Some(&last)) = (rss.first(), rss.last()) else {
+        println!("rss: not available, not checked");
+        return Ok(());
+    };
+
+    if last > baseline + RSS_GROWTH_LIMIT {
+        return Err(format!(
+            "rss grew from {:.1} MB to {:.1} MB",
+            baseline as f64 / 1e6,
+            last as f64 / 1e6
+        ));
+    }
+    Ok(())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                Generator
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// A generated CSV stream that ends at `deadline` and knows what it holds.
+///
+/// Records are `seq,name,level,payload`. A name is sometimes quoted with a
+/// delimiter, a newline and an escaped quote inside; the level is `ERROR`
+/// (the only place the pattern can occur) or `INFO`.
+struct Feed {
+    rng: u64,
+    batch: Vec<u8>,
+    pos: usize,
+    records: u64,
+    matches: u64,
+    bytes: u64,
+    deadline: Instant,
+    monitor: Monitor,
+}
+
+impl Feed {
+    fn new(seed: u64, deadline: Instant, monitor: Monitor) -> Self {
+        Feed {
+            rng: seed,
+            batch: Vec::new(),
+            pos: 0,
+            records: 0,
+            matches: 0,
+            bytes: 0,
+            deadline,
+            monitor,
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_mul(1103515245).wrapping_add(12345);
+        self.rng >> 16
+    }
+
+    fn push_letters(&mut self, len: usize) {
+        for _ in 0..len {
+            let letter = b'a' + (self.next() % 26) as u8;
+            self.batch.push(letter);
+        }
+    }
+
+    fn refill(&mut self) {
+        self.batch.clear();
+        self.pos = 0;
+
+        for _ in 0..RECORDS_PER_BATCH {
+            self.batch
+                .extend_from_slice(self.records.to_string().as_bytes());
+            self.batch.push(b',');
+
+            if self.next().is_multiple_of(8) {
+                self.batch.extend_from_slice(b"\"last, \"\"first\"\"\n");
+                let len = (self.next() % 12) as usize;
+                self.push_letters(len);
+                self.batch.push(b'"');
+            } else {
+                let len = 1 + (self.next() % 12) as usize;
+                self.push_letters(len);
+            }
+
+            if self.next().is_multiple_of(10) {
+                self.batch.extend_from_slice(b",ERROR,");
+                self.matches += 1;
+            } else {
+                self.batch.extend_from_slice(b",INFO,");
+            }
+
+            let len = match self.next() % 4096 {
+                0 => LONG_FIELD,
+                r => (r % 200) as usize,
+            };
+            self.push_letters(len);
+            self.batch.push(b'\n');
+            self.records += 1;
+        }
+    }
+}
+
+impl Read for Feed {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.batch.len() {
+            // Only stop between records, so that the counts add up
+            if Instant::now() >= self.deadline {
+                return Ok(0);
+            }
+            self.refill();
+        }
+
+        let want = 1 + (self.next() as usize % MAX_READ);
+        let n = want.min(buf.len()).min(self.batch.len() - self.pos);
+        buf[..n].copy_from_slice(&self.batch[self.pos..self.pos + n]);
+        self.pos += n;
+        self.bytes += n as u64;
+
+        self.monitor.tick(self.bytes);
+        Ok(n)
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                Consumers
+// ═══════════════════════════════════════════════════════════════════════════
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Read every record and check it against the generator.
+fn soak_records(mut feed: Feed) -> Result<Vec<Sample>, String> {
+    let mut expected = 0u64;
+    let records = for_each_record_in_reader(&mut feed, READER_BUFFER_SIZE, |record| {
+        let seq = record
+            .get(0)
+            .and_then(|s| std::str::from_utf8(s).ok()?.parse().ok());
+        if record.len() != 4 || seq != Some(expected) {
+            let record = String::from_utf8_lossy(record.as_bytes());
+            return Err(invalid(format!("record {}: read {:?}", expected, record)));
+        }
+        expected += 1;
+        Ok(())
+    })
+    .map_err(|e| format!("records: {}", e))?;
+
+    if records as u64 != feed.records {
+        return Err(format!("records: read {}, generated {}", records, feed.records));
+    }
+    Ok(feed.monitor.samples)
+}
+
+/// Count pattern matches and check the total against the generator.
+fn soak_pattern(mut feed: Feed) -> Result<Vec<Sample>, String> {
+    let matches = count_pattern_matches_in_reader(&mut feed, PATTERN)
+        .map_err(|e| format!("pattern: {}", e))?;
+
+    if matches as u64 != feed.matches {
+        return Err(format!("pattern: counted {}, generated {}", matches, feed.matches));
+    }
+    Ok(feed.monitor.samples)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                  Main
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// `90`, `90s`, `15m` or `3h`.
+fn parse_duration(s: &str) -> Option<Duration> {
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let scale = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        _ => return None,
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .map(|n| Duration::from_secs(n * scale))
+}
+
+fn usage() -> ! {
+    eprintln!("usage: soak [--duration 1h] [--interval 1m] [--seed N]");
+    process::exit(2);
+}
+
+fn main() {
+    let mut duration = Duration::from_secs(3600);
+    let mut interval = Duration::from_secs(60);
+    let mut seed = 42;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--duration" => duration = parse_duration(&value).unwrap_or_else(|| usage()),
+            "--interval" => interval = parse_duration(&value).unwrap_or_else(|| usage()),
+            "--seed" => seed = value.parse().unwrap_or_else(|_| usage()),
+            _ => usage(),
+        }
+    }
+
+    println!("soak: {:?} with samples every {:?}, seed {}", duration, interval, seed);
+    let deadline = Instant::now() + duration;
+    let records = Feed::new(seed, deadline, Monitor::new("records", interval));
+    let pattern = Feed::new(seed, deadline, Monitor::new("pattern", interval));
+
+    let (records, pattern) = thread::scope(|s| {
+        let records = s.spawn(|| soak_records(records));
+        let pattern = s.spawn(|| soak_pattern(pattern));
+        (records.join().unwrap(), pattern.join().unwrap())
+    });
+
+    let checks = [
+        records.and_then(|samples| {
+            check_throughput("records", &samples)?;
+            check_rss(&samples)
+        }),
+        pattern.and_then(|samples| check_throughput("pattern", &samples)),
+    ];
+
+    let mut failed = false;
+    for error in checks.iter().filter_map(|c| c.as_ref().err()) {
+        eprintln!("soak: {}", error);
+        failed = true;
+    }
+    if failed {
+        process::exit(1);
+    }
+    println!("soak: ok");
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn short_feed() -> Feed {
+        let deadline = Instant::now() + Duration::from_millis(50);
+        Feed::new(7, deadline, Monitor::new("test", Duration::from_secs(3600)))
+    }
+
+    #[test]
+    fn test_consumers_match_feed() {
+        assert!(soak_records(short_feed()).is_ok());
+        assert!(soak_pattern(short_feed()).is_ok());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("15m"), Some(Duration::from_secs(900)));
+        assert_eq!(parse_duration("3h"), Some(Duration::from_secs(10800)));
+        assert_eq!(parse_duration("3d"), None);
+        assert_eq!(parse_duration("h"), None);
+    }
+
+    #[test]
+    fn test_checks() {
+        let sample = |mb_per_s, rss| Sample { mb_per_s, rss: Some(rss) };
+        let steady: Vec<Sample> = (0..20).map(|_| sample(500.0, 10 << 20)).collect();
+        assert!(check_throughput("x", &steady).is_ok());
+        assert!(check_rss(&steady).is_ok());
+
+        let mut degrading = steady.clone();
+        for (i, s) in degrading.iter_mut().enumerate() {
+            s.mb_per_s = 500.0 - 20.0 * i as f64;
+            s.rss = Some((10 + 2 * i as u64) << 20);
+        }
+        assert!(check_throughput("x", &degrading).is_err());
+        assert!(check_rss(&degrading).is_err());
+    }
+}
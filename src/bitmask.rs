@@ -0,0 +1,286 @@
+//! Shared helpers for turning 64-byte blocks into `u64` bitmasks.
+//!
+//! Bit i of a block mask corresponds to byte i of the block. Structural
+//! indexers (number spans, JSON, CSV) all work on these masks, so the
+//! per-architecture classification lives here once:
+//!
+//! - NEON:   compare 4 x 16 bytes, then fold into 64 bits with weighted pairwise adds
+//! - SWAR:   exact per-byte compare in a u64, then gather bit 7 of each byte with a multiply
+//!
+//! Quote masks become "inside a string" masks with [`prefix_xor`], a single
+//! carry-less multiply (PMULL / PCLMULQDQ) where the CPU has one.
+
+#[cfg(target_arch = "aarch64")]
+use std::arch::aarch64::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use crate::cpu;
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                  SWAR
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Collapse a per-byte 0x80 mask into 8 bits (byte i -> bit i).
+///
+/// `(mask >> 7)` leaves one bit at positions 0, 8, ..., 56; the multiply
+/// shifts each of them into the top byte at its own position.
+#[inline]
+pub fn movemask_swar(mask: u64) -> u64 {
+    ((mask >> 7).wrapping_mul(0x0102040810204080u64)) >> 56
+}
+
+/// Exact per-byte equality: bit 7 of each byte is set if that byte equals `byte`.
+///
+/// The classic `(x - 0x01..) & !x & 0x80..` "has zero byte" trick can flag a
+/// byte above a real match (borrow propagation), which is fine for early-exit
+/// detection but not for positional masks. Adding 0x7F to the low 7 bits
+/// never carries across bytes, so this version is exact.
+#[inline]
+pub fn eq_mask_swar(x: u64, byte: u8) -> u64 {
+    let t = x ^ (0x0101010101010101u64 * byte as u64);
+    let nonzero = ((t & 0x7F7F7F7F7F7F7F7Fu64) + 0x7F7F7F7F7F7F7F7Fu64) | t;
+    !nonzero & 0x8080808080808080u64
+}
+
+/// Compute the 64-bit mask of bytes equal to `byte` (SWAR version).
+pub fn eq_bitmask_64_swar(block: &[u8; 64], byte: u8) -> u64 {
+    let mut result = 0u64;
+
+    for (i, chunk) in block.chunks_exact(8).enumerate() {
+        let x = u64::from_le_bytes(chunk.try_into().unwrap());
+        result |= movemask_swar(eq_mask_swar(x, byte)) << (i * 8);
+    }
+
+    result
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                  NEON
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Turn four 0x00/0xFF comparison results into a 64-bit mask (byte i -> bit i).
+///
+/// NEON has no movemask, so we AND with per-lane bit weights and fold the
+/// registers together with pairwise adds (the simdjson approach).
+///
+/// # Safety
+/// Requires a CPU with NEON support (see [`cpu::has_neon`]).
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+pub unsafe fn movemask_64_neon(
+    m0: uint8x16_t,
+    m1: uint8x16_t,
+    m2: uint8x16_t,
+    m3: uint8x16_t,
+) -> u64 {
+    const WEIGHTS: [u8; 16] = [1, 2, 4, 8, 16, 32, 64, 128, 1, 2, 4, 8, 16, 32, 64, 128];
+    let weights = vld1q_u8(WEIGHTS.as_ptr());
+
+    let t0 = vandq_u8(m0, weights);
+    let t1 = vandq_u8(m1, weights);
+    let t2 = vandq_u8(m2, weights);
+    let t3 = vandq_u8(m3, weights);
+
+    let sum0 = vpaddq_u8(t0, t1);
+    let sum1 = vpaddq_u8(t2, t3);
+    let sum0 = vpaddq_u8(sum0, sum1);
+    let sum0 = vpaddq_u8(sum0, sum0);
+
+    vgetq_lane_u64(vreinterpretq_u64_u8(sum0), 0)
+}
+
+/// Compute the 64-bit mask of bytes equal to `byte` (NEON version).
+///
+/// # Safety
+/// Requires a CPU with NEON support (see [`cpu::has_neon`]).
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+pub unsafe fn eq_bitmask_64_neon(block: &[u8; 64], byte: u8) -> u64 {
+    let needle = vdupq_n_u8(byte);
+    let ptr = block.as_ptr();
+
+    let m0 = vceqq_u8(vld1q_u8(ptr), needle);
+    let m1 = vceqq_u8(vld1q_u8(ptr.add(16)), needle);
+    let m2 = vceqq_u8(vld1q_u8(ptr.add(32)), needle);
+    let m3 = vceqq_u8(vld1q_u8(ptr.add(48)), needle);
+
+    movemask_64_neon(m0, m1, m2, m3)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                               Dispatch
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Compute the 64-bit mask of bytes equal to `byte` (best available version).
+#[inline]
+pub fn eq_bitmask_64(block: &[u8; 64], byte: u8) -> u64 {
+    #[cfg(target_arch = "aarch64")]
+    if cpu::has_neon() {
+        return unsafe { eq_bitmask_64_neon(block, byte) };
+    }
+
+    eq_bitmask_64_swar(block, byte)
+}
+
+/// Copy the (possibly short) tail of a buffer into a zero-padded 64-byte block.
+#[inline]
+pub fn padded_block(tail: &[u8]) -> [u8; 64] {
+    let mut block = [0u8; 64];
+    let len = tail.len().min(64);
+    block[..len].copy_from_slice(&tail[..len]);
+    block
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                               Prefix XOR
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Bit i of prefix_xor(x) is the XOR of bits 0..=i of x. That is exactly the
+// low half of the carry-less product x * 0xFFFF_FFFF_FFFF_FFFF: column i of
+// the product sums (mod 2) x_j for every j <= i.
+//
+//   quotes:          ..1...1.1.11.1..
+//   prefix_xor:      ..1111..11..11..     inside = opening quote .. closing quote
+//
+// One PMULL / PCLMULQDQ (3-7 cycles) replaces the 6-step shift/XOR chain.
+
+/// Prefix XOR with 6 shift/XOR steps (portable version).
+///
+/// Applied to a mask of quote positions, this yields the "inside a string"
+/// mask (the opening quote included, the closing quote excluded).
+#[inline]
+pub fn prefix_xor_shift(x: u64) -> u64 {
+    let mut x = x;
+    x ^= x << 1;
+    x ^= x << 2;
+    x ^= x << 4;
+    x ^= x << 8;
+    x ^= x << 16;
+    x ^= x << 32;
+    x
+}
+
+/// Prefix XOR with one carry-less multiply (PMULL version).
+///
+/// # Safety
+/// Requires a CPU with the AES extension, which carries `PMULL` (check with
+/// [`has_pmull`](crate::cpu::has_pmull)).
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "aes")]
+#[inline]
+pub unsafe fn prefix_xor_pmull(x: u64) -> u64 {
+    vmull_p64(x, u64::MAX) as u64
+}
+
+/// Prefix XOR with one carry-less multiply (PCLMULQDQ version).
+///
+/// # Safety
+/// Requires a CPU with PCLMULQDQ support (check with
+/// [`has_pclmulqdq`](crate::cpu::has_pclmulqdq)).
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "pclmulqdq")]
+#[inline]
+pub unsafe fn prefix_xor_clmul(x: u64) -> u64 {
+    let product = _mm_clmulepi64_si128(_mm_set_epi64x(0, x as i64), _mm_set1_epi8(-1), 0);
+    _mm_cvtsi128_si64(product) as u64
+}
+
+/// Prefix XOR (best available version).
+///
+/// Applied to a mask of quote positions, this yields the "inside a string"
+/// mask (the opening quote included, the closing quote excluded).
+#[inline]
+pub fn prefix_xor(x: u64) -> u64 {
+    // Neither extension is in the baseline: detected at runtime (cached)
+    #[cfg(target_arch = "aarch64")]
+    if cpu::has_pmull() {
+        return unsafe { prefix_xor_pmull(x) };
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    if cpu::has_pclmulqdq() {
+        return unsafe { prefix_xor_clmul(x) };
+    }
+
+    prefix_xor_shift(x)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eq_bitmask_64_scalar(block: &[u8; 64], byte: u8) -> u64 {
+        block
+            .iter()
+            .enumerate()
+            .filter(|(_, &b)| b == byte)
+            .fold(0u64, |acc, (i, _)| acc | (1 << i))
+    }
+
+    #[test]
+    fn test_movemask_swar() {
+        assert_eq!(movemask_swar(0x8000000000000080), 0b1000_0001);
+        assert_eq!(movemask_swar(0), 0);
+        assert_eq!(movemask_swar(0x8080808080808080), 0xFF);
+    }
+
+    #[test]
+    fn test_eq_mask_swar_exact() {
+        // 0x22 right after another 0x22 and before 0x23: the borrow-based
+        // trick would flag the 0x23 too.
+        let x = u64::from_le_bytes([0x22, 0x22, 0x23, 0x00, 0xA2, 0x21, 0xFF, 0x22]);
+        assert_eq!(movemask_swar(eq_mask_swar(x, 0x22)), 0b1000_0011);
+        assert_eq!(movemask_swar(eq_mask_swar(x, 0x00)), 0b0000_1000);
+        assert_eq!(movemask_swar(eq_mask_swar(x, 0xFF)), 0b0100_0000);
+    }
+
+    #[test]
+    fn test_eq_bitmask_matches_scalar() {
+        let block: [u8; 64] = std::array::from_fn(|i| (i * 37 % 7) as u8 + b',');
+        for byte in [b',', b'-', b'.', b'/', b'0', b'1', b'2', 0u8, 255u8] {
+            assert_eq!(eq_bitmask_64_swar(&block, byte), eq_bitmask_64_scalar(&block, byte));
+            assert_eq!(eq_bitmask_64(&block, byte), eq_bitmask_64_scalar(&block, byte));
+        }
+    }
+
+    #[test]
+    fn test_padded_block() {
+        let block = padded_block(b"abc");
+        assert_eq!(&block[..3], b"abc");
+        assert!(block[3..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_prefix_xor() {
+        // Quotes at 1 and 5: inside = bits 1..=4
+        assert_eq!(prefix_xor(0b100010), 0b011110);
+        assert_eq!(prefix_xor(0), 0);
+        assert_eq!(prefix_xor(1), u64::MAX);
+        assert_eq!(prefix_xor(1 << 63), 1 << 63);
+    }
+
+    #[test]
+    fn test_prefix_xor_matches_shift() {
+        let mut rng = 7u64;
+        for _ in 0..1000 {
+            rng = rng.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let expected = prefix_xor_shift(rng);
+            assert_eq!(prefix_xor(rng), expected);
+
+            #[cfg(target_arch = "aarch64")]
+            if cpu::has_pmull() {
+                assert_eq!(unsafe { prefix_xor_pmull(rng) }, expected);
+            }
+
+            #[cfg(target_arch = "x86_64")]
+            if cpu::has_pclmulqdq() {
+                assert_eq!(unsafe { prefix_xor_clmul(rng) }, expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,458 @@
+//! Generic SWAR detector for a small set of bytes, plus clean-run escaping.
+//!
+//! The JSON detector in `json_escape_SWAR` hard-codes its three checks
+//! (`< 32`, `"`, `\`). Escapers for other formats (HTML, SQL, ...) need the
+//! same thing with different bytes, so the set is described by a [`ByteSet`]:
+//! - up to [`MAX_BYTES`] exact bytes
+//! - optionally every byte below a threshold (control characters)
+//!
+//! Escaping is then always the same loop: find the next byte in the set,
+//! copy the clean run before it in one `extend_from_slice`, emit the
+//! replacement, repeat. See [`escape_into`].
+
+#[cfg(target_arch = "aarch64")]
+use std::arch::aarch64::*;
+
+use crate::bitmask::movemask_swar;
+#[cfg(target_arch = "aarch64")]
+use crate::cpu;
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                ByteSet
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Maximum number of exact bytes in a [`ByteSet`].
+pub const MAX_BYTES: usize = 8;
+
+/// A set of bytes to detect: exact bytes, plus every byte below `below`.
+///
+/// Built with `const fn`s so presets can be constants.
+#[derive(Debug, Clone, Copy)]
+pub struct ByteSet {
+    bytes: [u8; MAX_BYTES],
+    len: usize,
+    below: u8,
+    table: [bool; 256],
+}
+
+/// Bytes that must be escaped inside a JSON string: `"`, `\` and controls.
+pub const JSON: ByteSet = ByteSet::new(b"\"\\").with_below(32);
+
+/// Bytes that must be escaped in HTML/XML text and attributes.
+pub const HTML: ByteSet = ByteSet::new(b"&<>\"'");
+
+/// Bytes that need care inside a SQL string literal: `'`, `\` and NUL.
+pub const SQL: ByteSet = ByteSet::new(b"'\\\0");
+
+/// Bytes that force a CSV field to be quoted (RFC 4180).
+pub const CSV: ByteSet = ByteSet::new(b",\"\r\n");
+
+impl ByteSet {
+    /// Create a set of exact bytes.
+    ///
+    /// # Panics
+    /// Panics if more than [`MAX_BYTES`] bytes are given.
+    pub const fn new(bytes: &[u8]) -> Self {
+        assert!(bytes.len() <= MAX_BYTES, "too many bytes for a ByteSet");
+
+        let mut set =
+            ByteSet { bytes: [0; MAX_BYTES], len: bytes.len(), below: 0, table: [false; 256] };
+        let mut i = 0;
+        while i < bytes.len() {
+            set.bytes[i] = bytes[i];
+            set.table[bytes[i] as usize] = true;
+            i += 1;
+        }
+        set
+    }
+
+    /// Also include every byte less than `n`.
+    ///
+    /// # Panics
+    /// Panics if `n > 128`: the SWAR compare only works on the low 7 bits.
+    pub const fn with_below(mut self, n: u8) -> Self {
+        assert!(n <= 128, "ByteSet threshold must be <= 128");
+
+        self.below = n;
+        let mut b = 0;
+        while b < n {
+            self.table[b as usize] = true;
+            b += 1;
+        }
+        self
+    }
+
+    /// Check if a single byte is in the set (scalar version).
+    #[inline]
+    pub fn contains(&self, byte: u8) -> bool {
+        self.table[byte as usize]
+    }
+
+    /// Compute the set mask of 8 bytes packed in a u64 (SWAR version).
+    ///
+    /// Returns a u64 with bit 7 of each byte set if that byte is in the set.
+    /// The mask is exact per byte, so `trailing_zeros() / 8` is the position
+    /// of the first match.
+    #[inline]
+    pub fn mask_swar(&self, x: u64) -> u64 {
+        const LOW7: u64 = 0x7F7F7F7F7F7F7F7Fu64;
+        const HIGH: u64 = 0x8080808080808080u64;
+        const ONES: u64 = 0x0101010101010101u64;
+
+        let mut mask = 0u64;
+
+        // byte < n  <=>  bit 7 clear and (low7 + (128 - n)) does not reach bit 7
+        if self.below > 0 {
+            let ge = (x & LOW7) + ONES * (128 - self.below as u64);
+            mask |= !(ge | x) & HIGH;
+        }
+
+        // Exact equality: zero bytes of x ^ b, without borrow propagation
+        for &b in &self.bytes[..self.len] {
+            let t = x ^ (ONES * b as u64);
+            mask |= !(((t & LOW7) + LOW7) | t) & HIGH;
+        }
+
+        mask
+    }
+
+    /// Find the index of the first byte of `buffer` in the set.
+    pub fn find_first(&self, buffer: &[u8]) -> Option<usize> {
+        let mut chunks = buffer.chunks_exact(8);
+
+        for (i, chunk) in chunks.by_ref().enumerate() {
+            let mask = self.mask_swar(u64::from_le_bytes(chunk.try_into().unwrap()));
+            if mask != 0 {
+                return Some(i * 8 + (mask.trailing_zeros() / 8) as usize);
+            }
+        }
+
+        // Handle remaining bytes (< 8) with the lookup table
+        let tail = chunks.remainder();
+        tail.iter()
+            .position(|&b| self.contains(b))
+            .map(|p| buffer.len() - tail.len() + p)
+    }
+
+    /// Find the index of the first byte of `buffer` in the set (scalar reference).
+    pub fn find_first_scalar(&self, buffer: &[u8]) -> Option<usize> {
+        buffer.iter().position(|&b| self.contains(b))
+    }
+
+    /// Check if any byte of `buffer` is in the set.
+    #[inline]
+    pub fn has_any(&self, buffer: &[u8]) -> bool {
+        self.find_first(buffer).is_some()
+    }
+
+    /// Compute the set mask of 16 bytes (NEON version): 0xFF lanes are in the set.
+    ///
+    /// # Safety
+    /// Requires a CPU with NEON support (see [`cpu::has_neon`]).
+    #[cfg(target_arch = "aarch64")]
+    #[target_feature(enable = "neon")]
+    #[inline]
+    pub unsafe fn mask_neon(&self, v: uint8x16_t) -> uint8x16_t {
+        // `below` is 0 when unused, and nothing is < 0
+        let mut m = vcltq_u8(v, vdupq_n_u8(self.below));
+        for &b in &self.bytes[..self.len] {
+            m = vorrq_u8(m, vceqq_u8(v, vdupq_n_u8(b)));
+        }
+        m
+    }
+
+    /// Compute the 64-bit mask of a block: bit i set if byte i is in the set.
+    #[inline]
+    pub fn bitmask_64(&self, block: &[u8; 64]) -> u64 {
+        let mut result = 0u64;
+
+        for (i, chunk) in block.chunks_exact(8).enumerate() {
+            let x = u64::from_le_bytes(chunk.try_into().unwrap());
+            result |= movemask_swar(self.mask_swar(x)) << (i * 8);
+        }
+
+        result
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                            Clean-Run Copy
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Scanning for the next special byte and then copying the clean run reads
+// the input twice. Here each block is loaded once, stored to the output
+// unconditionally and then checked:
+//
+//   src:   [a b c d e f g h i j k l m n o p][q r s & u v ...]
+//   store:  whole block                      whole block
+//   mask:   0                                bit 4 -> stop, keep 16 + 4 bytes
+//
+// Bytes stored past the stop point land in reserved spare capacity and are
+// cut off by the final `set_len`, so the hot loop has no partial stores.
+
+impl ByteSet {
+    /// Append the bytes of `src` up to the first byte in the set to `dst`.
+    ///
+    /// Returns the number of bytes copied: the index of the first byte in
+    /// the set, or `src.len()` if there is none.
+    ///
+    /// # Example
+    /// ```
+    /// use scratchpad::byte_set::HTML;
+    ///
+    /// let mut out = b"<p>".to_vec();
+    /// assert_eq!(HTML.copy_until(b"Tom & Jerry", &mut out), 4);
+    /// assert_eq!(out, b"<p>Tom ");
+    /// ```
+    #[inline]
+    pub fn copy_until(&self, src: &[u8], dst: &mut Vec<u8>) -> usize {
+        dst.reserve(src.len());
+
+        #[cfg(target_arch = "aarch64")]
+        if cpu::has_neon() {
+            return unsafe { self.copy_until_neon(src, dst) };
+        }
+
+        self.copy_until_swar(src, dst)
+    }
+
+    /// [`ByteSet::copy_until`] with 8-byte SWAR blocks.
+    pub fn copy_until_swar(&self, src: &[u8], dst: &mut Vec<u8>) -> usize {
+        dst.reserve(src.len());
+        let start = dst.len();
+        let out = unsafe { dst.as_mut_ptr().add(start) };
+        let mut i = 0;
+
+        while i + 8 <= src.len() {
+            let x = u64::from_le_bytes(src[i..i + 8].try_into().unwrap());
+            // SAFETY: `src.len()` bytes were reserved past `start`
+            unsafe { std::ptr::write_unaligned(out.add(i) as *mut u64, x.to_le()) };
+
+            let mask = self.mask_swar(x);
+            if mask != 0 {
+                i += (mask.trailing_zeros() / 8) as usize;
+                unsafe { dst.set_len(start + i) };
+                return i;
+            }
+            i += 8;
+        }
+
+        while i < src.len() && !self.contains(src[i]) {
+            unsafe { *out.add(i) = src[i] };
+            i += 1;
+        }
+        unsafe { dst.set_len(start + i) };
+        i
+    }
+
+    /// [`ByteSet::copy_until`] with 16-byte NEON loads and stores.
+    ///
+    /// # Safety
+    /// Requires a CPU with NEON support (see [`cpu::has_neon`]).
+    #[cfg(target_arch = "aarch64")]
+    #[target_feature(enable = "neon")]
+    pub unsafe fn copy_until_neon(&self, src: &[u8], dst: &mut Vec<u8>) -> usize {
+        dst.reserve(src.len());
+        let start = dst.len();
+        let out = dst.as_mut_ptr().add(start);
+        let mut i = 0;
+
+        while i + 16 <= src.len() {
+            let v = vld1q_u8(src.as_ptr().add(i));
+            vst1q_u8(out.add(i), v);
+
+            let m = self.mask_neon(v);
+            if vmaxvq_u8(m) != 0 {
+                // Narrow to 4 bits per lane to find the first set lane
+                let nibbles = vshrn_n_u16(vreinterpretq_u16_u8(m), 4);
+                let bits = vget_lane_u64(vreinterpret_u64_u8(nibbles), 0);
+                i += (bits.trailing_zeros() / 4) as usize;
+                dst.set_len(start + i);
+                return i;
+            }
+            i += 16;
+        }
+
+        while i < src.len() && !self.contains(src[i]) {
+            *out.add(i) = src[i];
+            i += 1;
+        }
+        dst.set_len(start + i);
+        i
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                          Clean-Run Escaping
+// ═══════════════════════════════════════════════════════════════════════════
+//
+//   Input:   Tom & "Jerry"
+//            ^^^^ clean run, copied in one go
+//                ^ in set: emit(b'&', out) writes "&amp;"
+//                 ^^ clean run
+//                   ^ in set ...
+//
+// Most real text is long clean runs with rare special bytes, so the cost is
+// dominated by [`ByteSet::copy_until`] rather than per-byte branching.
+
+/// Escape `input` into `out`, calling `emit` for every byte in `set`.
+///
+/// Bytes not in the set are copied unchanged, in runs.
+///
+/// # Example
+/// ```
+/// use scratchpad::byte_set::{escape_into, HTML};
+///
+/// let mut out = Vec::new();
+/// escape_into(b"a<b", &HTML, &mut out, |b, out| {
+///     out.extend_from_slice(format!("&#{};", b).as_bytes())
+/// });
+/// assert_eq!(out, b"a&#60;b");
+/// ```
+pub fn escape_into<F>(input: &[u8], set: &ByteSet, out: &mut Vec<u8>, mut emit: F)
+where
+    F: FnMut(u8, &mut Vec<u8>),
+{
+    let mut rest = input;
+
+    loop {
+        let copied = set.copy_until(rest, out);
+        let Some(&special) = rest.get(copied) else {
+            return;
+        };
+        emit(special, out);
+        rest = &rest[copied + 1..];
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contains() {
+        assert!(JSON.contains(b'"'));
+        assert!(JSON.contains(b'\\'));
+        assert!(JSON.contains(0));
+        assert!(JSON.contains(31));
+        assert!(!JSON.contains(32));
+        assert!(!JSON.contains(0xFF));
+
+        assert!(HTML.contains(b'&'));
+        assert!(HTML.contains(b'\''));
+        assert!(!HTML.contains(b'\n'));
+
+        assert!(SQL.contains(b'\''));
+        assert!(SQL.contains(b'\\'));
+        assert!(SQL.contains(0));
+        assert!(!SQL.contains(b'"'));
+
+        assert!(CSV.contains(b','));
+        assert!(CSV.contains(b'\r'));
+        assert!(!CSV.contains(b'\t'));
+    }
+
+    #[test]
+    fn test_mask_swar_exact() {
+        let x = u64::from_le_bytes([b'"', b'#', 0x1F, b' ', 0x9F, b'\\', 0xA2, b']']);
+        assert_eq!(movemask_swar(JSON.mask_swar(x)), 0b0010_0101);
+    }
+
+    #[test]
+    fn test_find_first_matches_scalar() {
+        let sets = [
+            JSON,
+            HTML,
+            SQL,
+            ByteSet::new(b""),
+            ByteSet::new(&[0, 0xFF]).with_below(128),
+        ];
+        let mut rng = 99u64;
+        let data: Vec<u8> = (0..400)
+            .map(|_| {
+                rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+                if (rng >> 16) & 15 == 0 {
+                    (rng >> 24) as u8
+                } else {
+                    b'a' + ((rng >> 24) % 26) as u8
+                }
+            })
+            .collect();
+
+        for set in &sets {
+            for start in 0..data.len() {
+                assert_eq!(
+                    set.find_first(&data[start..]),
+                    set.find_first_scalar(&data[start..]),
+                    "Mismatch for start={}",
+                    start
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_bitmask_64() {
+        let block: [u8; 64] = std::array::from_fn(|i| {
+            if i % 9 == 0 {
+                b'<'
+            } else {
+                b'a' + (i % 26) as u8
+            }
+        });
+        let expected = (0..64)
+            .filter(|i| i % 9 == 0)
+            .fold(0u64, |acc, i| acc | (1 << i));
+        assert_eq!(HTML.bitmask_64(&block), expected);
+        assert_eq!(JSON.bitmask_64(&block), 0);
+    }
+
+    #[test]
+    fn test_copy_until() {
+        let mut rng = 2024u64;
+        let data: Vec<u8> = (0..300)
+            .map(|_| {
+                rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+                if (rng >> 16) & 31 == 0 {
+                    (rng >> 24) as u8
+                } else {
+                    b'a' + ((rng >> 24) % 26) as u8
+                }
+            })
+            .collect();
+
+        for set in [JSON, HTML, SQL, CSV] {
+            for start in 0..data.len() {
+                let src = &data[start..];
+                let expected = set.find_first_scalar(src).unwrap_or(src.len());
+
+                let mut out = b"prefix".to_vec();
+                assert_eq!(set.copy_until(src, &mut out), expected, "Mismatch for start={}", start);
+                assert_eq!(&out[6..], &src[..expected]);
+                assert_eq!(&out[..6], b"prefix");
+
+                let mut out = Vec::new();
+                assert_eq!(set.copy_until_swar(src, &mut out), expected);
+                assert_eq!(out, &src[..expected]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_escape_into() {
+        let mut out = Vec::new();
+        escape_into(b"", &HTML, &mut out, |_, out| out.push(b'?'));
+        assert!(out.is_empty());
+
+        escape_into(b"<<a>>", &HTML, &mut out, |_, out| out.push(b'?'));
+        assert_eq!(out, b"??a??");
+
+        // Appends, never clears
+        escape_into(b" clean", &HTML, &mut out, |_, out| out.push(b'?'));
+        assert_eq!(out, b"??a?? clean");
+    }
+}
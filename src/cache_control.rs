@@ -0,0 +1,240 @@
+//! Empty the page cache before a cold read, on whatever the machine allows.
+//!
+//! A cold benchmark has to get the file out of the page cache between runs,
+//! or every run after the first measures memory. How depends on the OS and
+//! on the privileges of the bench:
+//!
+//! | [`CacheControl`] | Drops             | Needs                      |
+//! |------------------|-------------------|----------------------------|
+//! | `DropCaches`     | the whole cache   | Linux, root                |
+//! | `Purge`          | the whole cache   | macOS, root (`sudo purge`) |
+//! | `Fadvise`        | the file's pages  | Linux                      |
+//! | `DummyFile`      | whatever is older | disk space, time           |
+//!
+//! [`CacheControl::detect`] picks the first that works here, in that order:
+//! a bench run as root on Linux gets a cache as cold as after a reboot, one
+//! run as a user still gets the file itself evicted. `DummyFile` is the last
+//! resort (macOS without root, the BSDs): it reads a file the size of the
+//! physical memory, so that the kernel reclaims older pages, the file under
+//! test among them. That takes seconds per eviction and is best effort, as
+//! the kernel keeps pages it considers hot; `UncachedReader` reads around the
+//! cache instead, where the OS supports it.
+//!
+//! On a Linux VM with 6 GB of memory, evicting an 88 MB file read twice
+//! (pages counted with `mincore`):
+//!
+//! | Method       | Time       | Left in the cache |
+//! |--------------|------------|-------------------|
+//! | `Fadvise`    | 7 ms       | 0%                |
+//! | `DropCaches` | 1.9 s      | 0%                |
+//! | `DummyFile`  | 3.5-4.3 s  | 16-25%            |
+//!
+//! (8.1 s and 0% on the first `DummyFile` eviction, which writes the 6 GB.)
+//!
+//! Pages that are dirty cannot be dropped: every method writes the file out
+//! first (`fsync`, or `sync` for the whole cache).
+
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// Where Linux takes the order to drop its caches.
+const DROP_CACHES: &str = "/proc/sys/vm/drop_caches";
+
+/// Size of the dummy file when the physical memory is unknown.
+const DEFAULT_DUMMY_SIZE: u64 = 8 << 30;
+
+/// A way to evict a file from the page cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheControl {
+    /// `sync`, then `1` into `/proc/sys/vm/drop_caches`: the page cache of
+    /// every file (Linux, root).
+    DropCaches,
+    /// The `purge` command: the whole disk cache (macOS, root).
+    Purge,
+    /// `posix_fadvise(POSIX_FADV_DONTNEED)` on the file: only its pages, and
+    /// no privileges needed (Linux).
+    Fadvise,
+    /// Read the `size` bytes of the file at `path` (written on first use),
+    /// pushing older pages out of the cache (anywhere, best effort).
+    DummyFile { path: PathBuf, size: u64 },
+}
+
+impl CacheControl {
+    /// The most thorough method available to this process: `DropCaches` or
+    /// `Purge` as root, `Fadvise` on Linux, a [`dummy_file`](Self::dummy_file)
+    /// otherwise.
+    pub fn detect() -> Self {
+        if cfg!(target_os = "linux") && OpenOptions::new().write(true).open(DROP_CACHES).is_ok() {
+            CacheControl::DropCaches
+        } else if cfg!(target_os = "linux") {
+            CacheControl::Fadvise
+        } else if cfg!(target_os = "macos") && is_root() {
+            CacheControl::Purge
+        } else {
+            CacheControl::dummy_file()
+        }
+    }
+
+    /// A `DummyFile` in the temporary directory, as large as the physical
+    /// memory (8 GB if unknown).
+    pub fn dummy_file() -> Self {
+        CacheControl::DummyFile {
+            path: std::env::temp_dir().join("scratchpad_cache_dummy"),
+            size: physical_memory().unwrap_or(DEFAULT_DUMMY_SIZE),
+        }
+    }
+
+    /// Whether one eviction empties the cache for every file, not only the
+    /// one passed to [`evict`](Self::evict).
+    pub fn is_global(&self) -> bool {
+        !matches!(self, CacheControl::Fadvise)
+    }
+
+    /// Make the next read of `path` come from the disk.
+    pub fn evict(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        match self {
+            CacheControl::DropCaches => {
+                sync_all()?;
+                OpenOptions::new().write(true).open(DROP_CACHES)?.write_all(b"1")
+            }
+            CacheControl::Purge => {
+                let status = std::process::Command::new("purge").status()?;
+                if !status.success() {
+                    return Err(io::Error::other(format!("purge failed: {}", status)));
+                }
+                Ok(())
+            }
+            CacheControl::Fadvise => {
+                let file = File::open(path)?;
+                file.sync_all()?;
+                crate::readahead::evict_cached(&file)
+            }
+            CacheControl::DummyFile { path: dummy, size } => {
+                File::open(path)?.sync_all()?;
+                read_dummy(dummy, *size)
+            }
+        }
+    }
+}
+
+impl fmt::Display for CacheControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CacheControl::DropCaches => write!(f, "{}", DROP_CACHES),
+            CacheControl::Purge => write!(f, "purge"),
+            CacheControl::Fadvise => write!(f, "posix_fadvise(DONTNEED)"),
+            CacheControl::DummyFile { path, size } => {
+                write!(f, "reading {} ({} MB)", path.display(), size >> 20)
+            }
+        }
+    }
+}
+
+/// Read the `size`-byte file at `path`, after writing it if it is missing or
+/// shorter.
+fn read_dummy(path: &Path, size: u64) -> io::Result<()> {
+    const CHUNK: usize = 1 << 20;
+
+    let mut buffer = vec![0u8; CHUNK];
+    if std::fs::metadata(path).map_or(true, |m| m.len() < size) {
+        // Not zeros, which a file system may store as holes
+        for (i, byte) in buffer.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let mut file = File::create(path)?;
+        let mut written = 0;
+        while written < size {
+            let n = (size - written).min(CHUNK as u64) as usize;
+            file.write_all(&buffer[..n])?;
+            written += n as u64;
+        }
+        file.sync_all()?;
+    }
+
+    let mut file = File::open(path)?;
+    let mut remaining = size;
+    while remaining > 0 {
+        match file.read(&mut buffer)? {
+            0 => break,
+            n => remaining = remaining.saturating_sub(n as u64),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn sync_all() -> io::Result<()> {
+    // Flushes every file system; cannot fail
+    unsafe { libc::sync() };
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn sync_all() -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "sync needs Linux or macOS"))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub(crate) fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub(crate) fn is_root() -> bool {
+    false
+}
+
+/// Bytes of physical memory.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn physical_memory() -> Option<u64> {
+    let pages = unsafe { libc::sysconf(libc::_SC_PHYS_PAGES) };
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    (pages > 0 && page_size > 0).then(|| pages as u64 * page_size as u64)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn physical_memory() -> Option<u64> {
+    None
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_parse_buffer_size_impact::count_pattern_matches_from_file;
+
+    #[test]
+    fn test_evict() {
+        let path = "/tmp/test_cache_control.csv";
+        std::fs::write(path, b"Name,University\nAnn,Harvard\nBob,MIT\n").unwrap();
+
+        let dummy = PathBuf::from("/tmp/test_cache_control_dummy");
+        let _ = std::fs::remove_file(&dummy);
+        let small = CacheControl::DummyFile { path: dummy.clone(), size: 3 << 20 };
+        assert!(small.is_global());
+        small.evict(path).unwrap();
+        assert_eq!(std::fs::metadata(&dummy).unwrap().len(), 3 << 20);
+        assert_eq!(small.to_string(), "reading /tmp/test_cache_control_dummy (3 MB)");
+
+        #[cfg(target_os = "linux")]
+        {
+            assert_ne!(CacheControl::detect(), CacheControl::dummy_file());
+            CacheControl::Fadvise.evict(path).unwrap();
+            assert!(!CacheControl::Fadvise.is_global());
+        }
+        assert_eq!(count_pattern_matches_from_file(path, b"Harvard").unwrap(), 1);
+
+        let missing = CacheControl::Fadvise.evict("/tmp/test_cache_control_missing.csv");
+        assert_eq!(missing.unwrap_err().kind(), io::ErrorKind::NotFound);
+        let _ = std::fs::remove_file(path);
+        let _ = std::fs::remove_file(&dummy);
+    }
+}
@@ -0,0 +1,222 @@
+//! Content-defined chunking (CDC) with a gear rolling hash.
+//!
+//! Based on: https://www.usenix.org/conference/atc16/technical-sessions/presentation/xia (FastCDC)
+//!
+//! Fixed-size chunks are useless for change detection: inserting one byte at
+//! the start of a file shifts every later chunk. Content-defined chunking cuts
+//! where the *content* says so, so boundaries move with the data and an edit
+//! only changes the chunks around it.
+//!
+//! The gear hash is a rolling hash with one shift and one add per byte:
+//!
+//!   fp = (fp << 1) + GEAR[byte]
+//!
+//! After 64 steps a byte has been shifted out, so `fp` only depends on the
+//! last 64 bytes. A cut is made where the top `log2(avg_size)` bits of `fp`
+//! are all zero, which happens on average every `avg_size` bytes.
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                              Gear Table
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// 256 pseudo-random u64, one per byte value (SplitMix64, computed at compile time).
+const GEAR: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut state = 0x5EED_CDC0_5EED_CDC0u64;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                               Chunker
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Largest `max_size` accepted: 64 MiB, 256 times the default. Readers
+/// buffer a few chunks, so this bounds their memory.
+pub const MAX_CHUNK_SIZE: usize = 64 << 20;
+
+/// Chunk size limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkerParams {
+    /// No cut is considered before this many bytes.
+    pub min_size: usize,
+    /// Expected chunk size; must be a power of two.
+    pub avg_size: usize,
+    /// A cut is forced at this size.
+    pub max_size: usize,
+}
+
+impl Default for ChunkerParams {
+    fn default() -> Self {
+        ChunkerParams { min_size: 16 * 1024, avg_size: 64 * 1024, max_size: 256 * 1024 }
+    }
+}
+
+impl ChunkerParams {
+    /// Check that `avg_size` is a power of two and that
+    /// `0 < min_size <= max_size <= MAX_CHUNK_SIZE`.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if !self.avg_size.is_power_of_two() {
+            return Err("avg_size must be a power of two");
+        }
+        if !(0 < self.min_size && self.min_size <= self.max_size) {
+            return Err("invalid chunk size limits");
+        }
+        if self.max_size > MAX_CHUNK_SIZE {
+            return Err("max_size is above MAX_CHUNK_SIZE");
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn mask(&self) -> u64 {
+        if let Err(e) = self.validate() {
+            panic!("{}", e);
+        }
+
+        let bits = self.avg_size.trailing_zeros();
+        !(u64::MAX >> bits)
+    }
+}
+
+/// Find the end of the chunk starting at `data[0]`.
+///
+/// Returns `Some(len)` if a cut point was found (or `max_size` was reached),
+/// and None if `data` ends before either; the caller then needs more data,
+/// or at EOF takes all of `data` as the last chunk.
+#[inline]
+pub fn next_boundary(data: &[u8], params: &ChunkerParams) -> Option<usize> {
+    let mask = params.mask();
+    let end = data.len().min(params.max_size);
+
+    // Bytes before min_size can only affect fp through the last 64 of them
+    let mut fp = 0u64;
+    let warmup = params.min_size.saturating_sub(64).min(end);
+    for &b in &data[warmup..params.min_size.min(end)] {
+        fp = (fp << 1).wrapping_add(GEAR[b as usize]);
+    }
+
+    for (i, &b) in data.iter().enumerate().take(end).skip(params.min_size) {
+        fp = (fp << 1).wrapping_add(GEAR[b as usize]);
+        if fp & mask == 0 {
+            return Some(i + 1);
+        }
+    }
+
+    (end == params.max_size).then_some(end)
+}
+
+/// Split `data` into content-defined chunks, returning their lengths.
+///
+/// # Example
+/// ```
+/// use scratchpad::cdc::{chunk_lengths, ChunkerParams};
+///
+/// let data = vec![7u8; 1 << 20];
+/// let params = ChunkerParams::default();
+/// let lengths = chunk_lengths(&data, &params);
+/// assert_eq!(lengths.iter().sum::<usize>(), data.len());
+/// assert!(lengths.iter().all(|&len| len <= params.max_size));
+/// ```
+pub fn chunk_lengths(data: &[u8], params: &ChunkerParams) -> Vec<usize> {
+    let mut lengths = Vec::new();
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let len = next_boundary(&data[pos..], params).unwrap_or(data.len() - pos);
+        lengths.push(len);
+        pos += len;
+    }
+
+    lengths
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMALL: ChunkerParams = ChunkerParams { min_size: 256, avg_size: 1024, max_size: 4096 };
+
+    fn random_data(len: usize, seed: u64) -> Vec<u8> {
+        let mut rng = seed;
+        (0..len)
+            .map(|_| {
+                rng = rng
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (rng >> 56) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sizes_within_limits() {
+        let data = random_data(200_000, 1);
+        let lengths = chunk_lengths(&data, &SMALL);
+
+        assert_eq!(lengths.iter().sum::<usize>(), data.len());
+        let (last, rest) = lengths.split_last().unwrap();
+        assert!(rest
+            .iter()
+            .all(|&len| (SMALL.min_size..=SMALL.max_size).contains(&len)));
+        assert!(*last <= SMALL.max_size);
+
+        // Average within a factor of two of the target
+        let average = data.len() / lengths.len();
+        assert!(
+            (SMALL.avg_size / 2..SMALL.avg_size * 2).contains(&average),
+            "average {}",
+            average
+        );
+    }
+
+    #[test]
+    fn test_boundaries_resynchronize_after_insert() {
+        let data = random_data(100_000, 2);
+        let mut edited = data[..50_000].to_vec();
+        edited.extend_from_slice(b"inserted bytes");
+        edited.extend_from_slice(&data[50_000..]);
+
+        let ends = |d: &[u8]| -> Vec<usize> {
+            chunk_lengths(d, &SMALL)
+                .iter()
+                .scan(0, |pos, &len| {
+                    *pos += len;
+                    Some(*pos)
+                })
+                .collect()
+        };
+        let before = ends(&data);
+        let after: Vec<usize> = ends(&edited)
+            .iter()
+            .map(|&e| e.saturating_sub(14))
+            .collect();
+
+        // Almost every boundary after the edit is found again, shifted by the insert
+        let shared = before
+            .iter()
+            .filter(|&&e| e > 60_000 && after.contains(&e))
+            .count();
+        let total = before.iter().filter(|&&e| e > 60_000).count();
+        assert!(shared + 1 >= total, "only {} of {} boundaries kept", shared, total);
+    }
+
+    #[test]
+    fn test_empty_and_short() {
+        assert!(chunk_lengths(b"", &SMALL).is_empty());
+        assert_eq!(chunk_lengths(b"short", &SMALL), vec![5]);
+        assert_eq!(next_boundary(b"short", &SMALL), None);
+    }
+}
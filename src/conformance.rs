@@ -0,0 +1,750 @@
+//! Differential testing of the kernels: every SWAR, NEON and AVX2 version
+//! against its scalar reference, over generated inputs of every length.
+//!
+//! The unit tests of each kernel check a handful of fixed vectors. The bugs
+//! of a vectorized kernel hide in the cases those skip: a length one past a
+//! block, a tail of 7 bytes, an input that starts on an odd address, the K at
+//! which the shuffle path of `insert_line_feed` hands over to the copy path.
+//! [`run`] feeds every kernel of [`KERNELS`] each length from 0 to
+//! [`Corpus::max_len`], in each [`Distribution`], at a random offset from a
+//! 64-byte boundary, and with every K of the kernel. The bytes around the
+//! input are a special byte, so that a kernel which lets a read past either
+//! end into its result diverges.
+//!
+//! Inputs go from the shortest up and the first divergence ends the run, so
+//! the one reported is the shortest input that shows the bug:
+//!
+//! ```
+//! use scratchpad::conformance::{run, Corpus, KERNELS};
+//!
+//! let summary = run(&Corpus { max_len: 64, seed: 1 }, KERNELS).unwrap();
+//! assert_eq!(summary.inputs, 65 * 5);
+//! ```
+//!
+//!   cargo run --release --bin conformance                       4 KB, all kernels
+//!   cargo run --release --bin conformance -- --kernel escape_json --seed 7
+//!
+//! The 4 KB corpus is 20,485 inputs and 9.9 million calls for the 19
+//! kernels, 12 s on one x86_64 core with AVX2; the unit test runs it to 200
+//! bytes.
+//!
+//! Only the versions this CPU can run are compared: NEON on aarch64, AVX2 and
+//! PCLMULQDQ on x86_64 when detected. A read past the end whose result is
+//! thrown away (the NEON line feed loads 16 bytes where fewer are left) does
+//! not change any output, and goes unseen here.
+
+use std::{fmt, ops::RangeInclusive};
+
+use crate::{
+    bitmask::{self, padded_block},
+    byte_set::HTML,
+    cms, csv_index,
+    datagen::Rng,
+    hll, html_escape, json_escape_SWAR as json, json_escape_utf16 as utf16,
+    line_feed_every_k_bytes as line_feed, log_classify, needle, number_spans,
+    sql_escape::{self, SqlDialect},
+    wtf8,
+};
+
+/// Alignment of the buffer each input is placed in; the input starts at a
+/// random offset below it.
+const ALIGN: usize = 64;
+
+/// The bytes some kernel treats apart: escapes, CSV syntax, surrogate
+/// halves, digits, bytes with the high bit set.
+const SPECIALS: &[u8] = b"\"\\\n\r\t,'<>&\x00\x01\x1f\x7f\x80\xa0\xbf\xed\xff09";
+
+/// Plain bytes for the text between the specials.
+const TEXT: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ .-_:/";
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Corpus
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// How the bytes of an input are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Distribution {
+    /// Every byte value, equally likely.
+    Uniform,
+    /// Text, with one byte in 16 a special.
+    Text,
+    /// Specials only: a hit in every lane.
+    Specials,
+    /// Runs of 1 to 80 equal bytes, text or special, across block edges.
+    Runs,
+    /// Text with a single special, in the last byte: the tail has to find it.
+    LastByte,
+}
+
+/// Every distribution, in the order [`run`] goes through them.
+pub const DISTRIBUTIONS: [Distribution; 5] = [
+    Distribution::Uniform,
+    Distribution::Text,
+    Distribution::Specials,
+    Distribution::Runs,
+    Distribution::LastByte,
+];
+
+impl Distribution {
+    /// `len` bytes drawn from this distribution.
+    pub fn generate(self, len: usize, rng: &mut Rng) -> Vec<u8> {
+        match self {
+            Distribution::Uniform => (0..len).map(|_| rng.next_u32() as u8).collect(),
+            Distribution::Text => (0..len)
+                .map(|_| {
+                    if rng.below(16) == 0 {
+                        rng.pick(SPECIALS)
+                    } else {
+                        rng.pick(TEXT)
+                    }
+                })
+                .collect(),
+            Distribution::Specials => rng.bytes(SPECIALS, len),
+            Distribution::Runs => {
+                let mut out = Vec::with_capacity(len);
+                while out.len() < len {
+                    let byte = if rng.below(2) == 0 {
+                        rng.pick(SPECIALS)
+                    } else {
+                        rng.pick(TEXT)
+                    };
+                    let run = (1 + rng.below(80) as usize).min(len - out.len());
+                    out.resize(out.len() + run, byte);
+                }
+                out
+            }
+            Distribution::LastByte => {
+                let mut out = rng.bytes(TEXT, len);
+                if let Some(last) = out.last_mut() {
+                    *last = rng.pick(SPECIALS);
+                }
+                out
+            }
+        }
+    }
+}
+
+/// The inputs of a run: every length up to `max_len`, drawn from `seed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Corpus {
+    pub max_len: usize,
+    pub seed: u64,
+}
+
+impl Default for Corpus {
+    /// Every length to 4 KB: 64 NEON blocks, 128 AVX2 ones.
+    fn default() -> Self {
+        Corpus { max_len: 4096, seed: 0 }
+    }
+}
+
+/// `input` copied to `offset` bytes past a 64-byte boundary of `buffer`,
+/// between bytes set to `fill`. Returns the range of the copy.
+fn place(buffer: &mut Vec<u8>, input: &[u8], offset: usize, fill: u8) -> std::ops::Range<usize> {
+    buffer.clear();
+    buffer.resize(input.len() + 3 * ALIGN, fill);
+    // Past the first boundary, which is the start of an aligned allocation
+    let start = ALIGN + buffer[ALIGN..].as_ptr().align_offset(ALIGN) + offset;
+    buffer[start..start + input.len()].copy_from_slice(input);
+    start..start + input.len()
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Kernels
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// What a kernel returns, in a form every version's result can be compared
+/// in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Output {
+    Bytes(Vec<u8>),
+    Count(usize),
+    Position(Option<usize>),
+    Flag(bool),
+    /// Masks, counters, span bounds.
+    Words(Vec<u64>),
+    Error(String),
+}
+
+impl From<Vec<u8>> for Output {
+    fn from(bytes: Vec<u8>) -> Self {
+        Output::Bytes(bytes)
+    }
+}
+
+impl From<usize> for Output {
+    fn from(count: usize) -> Self {
+        Output::Count(count)
+    }
+}
+
+impl From<Option<usize>> for Output {
+    fn from(position: Option<usize>) -> Self {
+        Output::Position(position)
+    }
+}
+
+impl From<bool> for Output {
+    fn from(flag: bool) -> Self {
+        Output::Flag(flag)
+    }
+}
+
+impl From<Vec<u64>> for Output {
+    fn from(words: Vec<u64>) -> Self {
+        Output::Words(words)
+    }
+}
+
+impl<T: Into<Output>, E: fmt::Display> From<Result<T, E>> for Output {
+    fn from(result: Result<T, E>) -> Self {
+        result.map_or_else(|e| Output::Error(e.to_string()), Into::into)
+    }
+}
+
+/// Preview of `bytes` from `from`: at most 48 of them, escaped.
+fn preview(bytes: &[u8], from: usize) -> String {
+    let from = from.min(bytes.len());
+    let to = (from + 48).min(bytes.len());
+    format!(
+        "{}\"{}\"{} ({} bytes)",
+        if from > 0 { "..." } else { "" },
+        bytes[from..to].escape_ascii(),
+        if to < bytes.len() { "..." } else { "" },
+        bytes.len()
+    )
+}
+
+impl Output {
+    /// Index of the first byte or word where `self` and `other` differ, if
+    /// both are sequences.
+    fn first_difference(&self, other: &Output) -> Option<usize> {
+        fn position<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+            a.iter()
+                .zip(b)
+                .position(|(x, y)| x != y)
+                .unwrap_or(a.len().min(b.len()))
+        }
+        match (self, other) {
+            (Output::Bytes(a), Output::Bytes(b)) => Some(position(a, b)),
+            (Output::Words(a), Output::Words(b)) => Some(position(a, b)),
+            _ => None,
+        }
+    }
+
+    fn fmt_from(&self, f: &mut fmt::Formatter<'_>, from: usize) -> fmt::Result {
+        match self {
+            Output::Bytes(bytes) => write!(f, "{}", preview(bytes, from.saturating_sub(8))),
+            Output::Words(words) => {
+                let from = from.saturating_sub(2).min(words.len());
+                let to = (from + 6).min(words.len());
+                write!(f, "words {}..{} of {}:", from, to, words.len())?;
+                for word in &words[from..to] {
+                    write!(f, " {:#x}", word)?;
+                }
+                Ok(())
+            }
+            Output::Count(n) => write!(f, "{}", n),
+            Output::Position(p) => write!(f, "{:?}", p),
+            Output::Flag(b) => write!(f, "{}", b),
+            Output::Error(e) => write!(f, "error: {}", e),
+        }
+    }
+}
+
+/// The results of a kernel's versions on one input, the reference first.
+pub type Outputs = Vec<(&'static str, Output)>;
+
+/// A kernel and its versions.
+#[derive(Debug, Clone)]
+pub struct Kernel {
+    pub name: &'static str,
+    /// The K values to run each input with (`0..=0` for a kernel without a
+    /// K). The ones past the input length + 1 all behave the same and are
+    /// skipped.
+    pub ks: RangeInclusive<usize>,
+    /// Push the output of the reference, then of each version this CPU runs.
+    pub run: fn(input: &[u8], k: usize, outputs: &mut Outputs),
+}
+
+/// Every kernel with more than one version.
+pub const KERNELS: &[Kernel] = &[
+    Kernel { name: "insert_line_feed", ks: 0..=100, run: insert_line_feed },
+    Kernel { name: "count_escapable_bytes", ks: 0..=0, run: count_escapable_bytes },
+    Kernel { name: "has_json_escapable_byte", ks: 0..=0, run: has_json_escapable_byte },
+    Kernel { name: "escape_json", ks: 0..=0, run: escape_json },
+    Kernel { name: "find_first_html_escapable", ks: 0..=0, run: find_first_html_escapable },
+    Kernel { name: "copy_until", ks: 0..=0, run: copy_until },
+    Kernel { name: "escape_html", ks: 0..=0, run: escape_html },
+    Kernel { name: "escape_sql", ks: 0..=0, run: escape_sql },
+    Kernel { name: "eq_bitmask_64", ks: 0..=0, run: eq_bitmask_64 },
+    Kernel { name: "prefix_xor", ks: 0..=0, run: prefix_xor },
+    Kernel { name: "classify_block", ks: 0..=0, run: classify_block },
+    Kernel { name: "digit_bitmask_64", ks: 0..=0, run: digit_bitmask_64 },
+    Kernel { name: "find_number_spans", ks: 0..=0, run: find_number_spans },
+    Kernel { name: "find", ks: 0..=40, run: find },
+    Kernel { name: "has_json_escapable_utf16", ks: 0..=0, run: has_json_escapable_utf16 },
+    Kernel { name: "find_invalid_surrogate", ks: 0..=0, run: find_invalid_surrogate },
+    Kernel { name: "add_counters", ks: 0..=0, run: add_counters },
+    Kernel { name: "max_registers", ks: 0..=0, run: max_registers },
+    Kernel { name: "classify_lines", ks: 0..=0, run: classify_lines },
+];
+
+fn insert_line_feed(input: &[u8], k: usize, outputs: &mut Outputs) {
+    outputs.push(("scalar", line_feed::insert_line_feed_scalar(input, k).into()));
+    if let Ok(output) = line_feed::insert_line_feed_neon(input, k) {
+        outputs.push(("NEON", output.into()));
+    }
+    // Into a used buffer, which has to be cleared first
+    let mut output = b"stale".to_vec();
+    line_feed::insert_line_feed_into(input, k, &mut output);
+    outputs.push(("best", output.into()));
+}
+
+fn count_escapable_bytes(input: &[u8], _: usize, outputs: &mut Outputs) {
+    outputs.push(("scalar", json::count_escapable_bytes_scalar(input).into()));
+    outputs.push(("SWAR", json::count_escapable_bytes_swar(input).into()));
+    #[cfg(target_arch = "aarch64")]
+    if crate::cpu::has_neon() {
+        outputs.push(("NEON", unsafe { json::count_escapable_bytes_neon(input) }.into()));
+    }
+}
+
+fn has_json_escapable_byte(input: &[u8], _: usize, outputs: &mut Outputs) {
+    outputs.push(("scalar", json::has_json_escapable_byte_scalar(input).into()));
+    outputs.push(("SWAR", json::has_json_escapable_byte(input).into()));
+}
+
+fn escape_json(input: &[u8], _: usize, outputs: &mut Outputs) {
+    outputs.push(("scalar", json::escape_json_scalar(input).into()));
+    outputs.push(("SWAR", json::escape_json(input).into()));
+}
+
+fn find_first_html_escapable(input: &[u8], _: usize, outputs: &mut Outputs) {
+    outputs.push(("scalar", HTML.find_first_scalar(input).into()));
+    outputs.push(("best", html_escape::find_first_html_escapable(input).into()));
+}
+
+fn copy_until(input: &[u8], _: usize, outputs: &mut Outputs) {
+    // The copy, or an error if the count returned is not its length
+    let copied = |version: &dyn Fn(&[u8], &mut Vec<u8>) -> usize| {
+        let mut dst = b"<p>".to_vec();
+        let n = version(input, &mut dst);
+        match n == dst.len() - 3 {
+            true => Output::Bytes(dst),
+            false => Output::Error(format!("returned {} after copying {}", n, dst.len() - 3)),
+        }
+    };
+    let end = HTML.find_first_scalar(input).unwrap_or(input.len());
+    outputs.push(("scalar", [b"<p>", &input[..end]].concat().into()));
+    outputs.push(("SWAR", copied(&|src, dst| HTML.copy_until_swar(src, dst))));
+    #[cfg(target_arch = "aarch64")]
+    if crate::cpu::has_neon() {
+        outputs.push(("NEON", copied(&|src, dst| unsafe { HTML.copy_until_neon(src, dst) })));
+    }
+}
+
+fn escape_html(input: &[u8], _: usize, outputs: &mut Outputs) {
+    outputs.push(("scalar", html_escape::escape_html_scalar(input).into()));
+    outputs.push(("best", html_escape::escape_html(input).into()));
+}
+
+fn escape_sql(input: &[u8], _: usize, outputs: &mut Outputs) {
+    // Both dialects in one output: NUL is an error in the standard one
+    let both = |escape: fn(&[u8], SqlDialect) -> Result<Vec<u8>, sql_escape::NulByteError>| {
+        let standard = escape(input, SqlDialect::Standard);
+        escape(input, SqlDialect::MySql).map(|mut out| {
+            out.push(b'|');
+            out.extend_from_slice(&standard.unwrap_or_else(|e| e.to_string().into_bytes()));
+            out
+        })
+    };
+    outputs.push(("scalar", both(sql_escape::escape_sql_scalar).into()));
+    outputs.push(("best", both(sql_escape::escape_sql).into()));
+}
+
+/// `version` on each 64-byte block of `input`, the last one zero-padded.
+fn per_block(input: &[u8], mut version: impl FnMut(&[u8; 64], &mut Vec<u64>)) -> Output {
+    let mut words = Vec::new();
+    for chunk in input.chunks(64) {
+        version(&padded_block(chunk), &mut words);
+    }
+    Output::Words(words)
+}
+
+/// Bit i set where `block[i]` matches, byte by byte.
+fn mask_scalar(block: &[u8; 64], matches: impl Fn(u8) -> bool) -> u64 {
+    block
+        .iter()
+        .enumerate()
+        .fold(0, |mask, (i, &b)| mask | (matches(b) as u64) << i)
+}
+
+fn eq_bitmask_64(input: &[u8], _: usize, outputs: &mut Outputs) {
+    const BYTES: [u8; 5] = [b'"', b',', b'\n', 0x80, 0xFF];
+    let masks = |version: &dyn Fn(&[u8; 64], u8) -> u64| {
+        per_block(input, |block, words| words.extend(BYTES.map(|byte| version(block, byte))))
+    };
+    outputs.push(("scalar", masks(&|block, byte| mask_scalar(block, |b| b == byte))));
+    outputs.push(("SWAR", masks(&bitmask::eq_bitmask_64_swar)));
+    #[cfg(target_arch = "aarch64")]
+    if crate::cpu::has_neon() {
+        outputs.push((
+            "NEON",
+            masks(&|block, byte| unsafe { bitmask::eq_bitmask_64_neon(block, byte) }),
+        ));
+    }
+    outputs.push(("best", masks(&bitmask::eq_bitmask_64)));
+}
+
+fn prefix_xor(input: &[u8], _: usize, outputs: &mut Outputs) {
+    let words: Vec<u64> = input
+        .chunks(8)
+        .map(|chunk| {
+            let mut word = [0u8; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            u64::from_le_bytes(word)
+        })
+        .collect();
+    let prefixes =
+        |version: &dyn Fn(u64) -> u64| Output::Words(words.iter().map(|&x| version(x)).collect());
+    let scalar = |x: u64| {
+        let mut parity = 0;
+        (0..64).fold(0, |acc, i| {
+            parity ^= (x >> i) & 1;
+            acc | parity << i
+        })
+    };
+    outputs.push(("scalar", prefixes(&scalar)));
+    outputs.push(("shift", prefixes(&bitmask::prefix_xor_shift)));
+    #[cfg(target_arch = "x86_64")]
+    if crate::cpu::has_pclmulqdq() {
+        outputs.push(("PCLMULQDQ", prefixes(&|x| unsafe { bitmask::prefix_xor_clmul(x) })));
+    }
+    #[cfg(target_arch = "aarch64")]
+    if crate::cpu::has_pmull() {
+        outputs.push(("PMULL", prefixes(&|x| unsafe { bitmask::prefix_xor_pmull(x) })));
+    }
+    outputs.push(("best", prefixes(&bitmask::prefix_xor)));
+}
+
+fn classify_block(input: &[u8], _: usize, outputs: &mut Outputs) {
+    let masks = |version: &dyn Fn(&[u8; 64]) -> csv_index::BlockMasks| {
+        per_block(input, |block, words| {
+            let masks = version(block);
+            words.extend([masks.quotes, masks.delimiters, masks.terminators]);
+        })
+    };
+    outputs.push((
+        "scalar",
+        masks(&|block| csv_index::BlockMasks {
+            quotes: mask_scalar(block, |b| b == b'"'),
+            delimiters: mask_scalar(block, |b| b == b','),
+            terminators: mask_scalar(block, |b| b == b'\n'),
+        }),
+    ));
+    outputs.push(("SWAR", masks(&csv_index::classify_block_swar)));
+    #[cfg(target_arch = "x86_64")]
+    if crate::cpu::has_avx2() {
+        outputs.push(("AVX2", masks(&|block| unsafe { csv_index::classify_block_avx2(block) })));
+    }
+    #[cfg(target_arch = "aarch64")]
+    if crate::cpu::has_neon() {
+        outputs.push(("NEON", masks(&|block| unsafe { csv_index::classify_block_neon(block) })));
+    }
+}
+
+fn digit_bitmask_64(input: &[u8], _: usize, outputs: &mut Outputs) {
+    let masks = |version: &dyn Fn(&[u8; 64]) -> u64| {
+        per_block(input, |block, words| words.push(version(block)))
+    };
+    outputs.push(("scalar", masks(&|block| mask_scalar(block, number_spans::is_digit_scalar))));
+    outputs.push(("SWAR", masks(&number_spans::digit_bitmask_64_swar)));
+    #[cfg(target_arch = "aarch64")]
+    if crate::cpu::has_neon() {
+        outputs
+            .push(("NEON", masks(&|block| unsafe { number_spans::digit_bitmask_64_neon(block) })));
+    }
+}
+
+fn find_number_spans(input: &[u8], _: usize, outputs: &mut Outputs) {
+    let bounds = |spans: &mut dyn Iterator<Item = number_spans::Span>| {
+        Output::Words(
+            spans
+                .flat_map(|span| [span.start as u64, span.end as u64])
+                .collect(),
+        )
+    };
+    outputs
+        .push(("scalar", bounds(&mut number_spans::find_number_spans_scalar(input).into_iter())));
+    outputs.push(("best", bounds(&mut number_spans::find_number_spans(input))));
+}
+
+fn find(input: &[u8], k: usize, outputs: &mut Outputs) {
+    // A K-byte needle from the start, the middle or the end of the input, or
+    // the input and one more byte when it is shorter
+    let needle = match input.len().checked_sub(k) {
+        Some(room) => input[room * (k % 3) / 2..][..k].to_vec(),
+        None => [input, b"x"].concat(),
+    };
+    let naive = match needle.len() {
+        0 => Some(0),
+        n => input.windows(n).position(|window| window == needle),
+    };
+    outputs.push(("naive", naive.into()));
+    outputs.push(("SWAR", needle::find_swar(input, &needle).into()));
+    #[cfg(target_arch = "x86_64")]
+    if crate::cpu::has_avx2() {
+        outputs.push(("AVX2", unsafe { needle::find_avx2(input, &needle) }.into()));
+    }
+    #[cfg(target_arch = "aarch64")]
+    if crate::cpu::has_neon() {
+        outputs.push(("NEON", unsafe { needle::find_neon(input, &needle) }.into()));
+    }
+    outputs.push(("memchr", needle::find_memchr(input, &needle).into()));
+    outputs.push(("Horspool", needle::find_horspool(input, &needle).into()));
+    outputs.push(("Two-Way", needle::find_two_way(input, &needle).into()));
+}
+
+fn has_json_escapable_utf16(input: &[u8], _: usize, outputs: &mut Outputs) {
+    let units: Vec<u16> = input
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    outputs.push(("scalar", utf16::has_json_escapable_utf16_scalar(&units).into()));
+    outputs.push(("SWAR", utf16::has_json_escapable_utf16_swar(&units).into()));
+    #[cfg(target_arch = "aarch64")]
+    if crate::cpu::has_neon() {
+        outputs.push(("NEON", unsafe { utf16::has_json_escapable_utf16_neon(&units) }.into()));
+    }
+}
+
+fn find_invalid_surrogate(input: &[u8], _: usize, outputs: &mut Outputs) {
+    outputs.push(("scalar", wtf8::find_invalid_surrogate_scalar(input).into()));
+    outputs.push(("best", wtf8::find_invalid_surrogate(input).into()));
+}
+
+fn add_counters(input: &[u8], _: usize, outputs: &mut Outputs) {
+    // Counters from the input, added to themselves reversed: high bytes
+    // saturate
+    let src: Vec<u32> = input
+        .chunks_exact(4)
+        .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+        .collect();
+    let sum = |version: &dyn Fn(&mut [u32], &[u32])| {
+        let mut dst: Vec<u32> = src.iter().rev().copied().collect();
+        version(&mut dst, &src);
+        Output::Words(dst.into_iter().map(u64::from).collect())
+    };
+    outputs.push(("scalar", sum(&cms::add_counters_scalar)));
+    #[cfg(target_arch = "aarch64")]
+    if crate::cpu::has_neon() {
+        outputs.push(("NEON", sum(&|dst, src| unsafe { cms::add_counters_neon(dst, src) })));
+    }
+    outputs.push(("best", sum(&cms::add_counters)));
+}
+
+fn max_registers(input: &[u8], _: usize, outputs: &mut Outputs) {
+    // Registers hold ranks below 0x80, which the SWAR version relies on
+    let src: Vec<u8> = input.iter().map(|&b| b & 0x7F).collect();
+    let max = |version: &dyn Fn(&mut [u8], &[u8])| {
+        let mut dst: Vec<u8> = src.iter().rev().copied().collect();
+        version(&mut dst, &src);
+        Output::Bytes(dst)
+    };
+    outputs.push(("scalar", max(&hll::max_registers_scalar)));
+    outputs.push(("SWAR", max(&hll::max_registers_swar)));
+    #[cfg(target_arch = "aarch64")]
+    if crate::cpu::has_neon() {
+        outputs.push(("NEON", max(&|dst, src| unsafe { hll::max_registers_neon(dst, src) })));
+    }
+    outputs.push(("best", max(&hll::max_registers)));
+}
+
+fn classify_lines(input: &[u8], _: usize, outputs: &mut Outputs) {
+    let keyword_sets: &[&[&[u8]]] = &[&[b"\"\\", b"<>"], &[b"00", b"\xed\xa0"], &[b"&"]];
+    let counts = |counts: Vec<usize>| Output::Words(counts.into_iter().map(|n| n as u64).collect());
+    outputs.push(("scalar", counts(log_classify::classify_lines_scalar(input, keyword_sets))));
+    outputs.push(("best", counts(log_classify::classify_lines(input, keyword_sets))));
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                   Run
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// A version whose output differs from the reference's.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub kernel: &'static str,
+    pub reference: &'static str,
+    pub backend: &'static str,
+    /// `None` for a kernel without a K.
+    pub k: Option<usize>,
+    pub distribution: Distribution,
+    /// Offset of the input from a 64-byte boundary.
+    pub offset: usize,
+    pub input: Vec<u8>,
+    pub expected: Output,
+    pub actual: Output,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} differs from {}", self.kernel, self.backend, self.reference)?;
+        write!(
+            f,
+            " on {} bytes ({:?}, offset {}",
+            self.input.len(),
+            self.distribution,
+            self.offset
+        )?;
+        if let Some(k) = self.k {
+            write!(f, ", K={}", k)?;
+        }
+        writeln!(f, ")")?;
+        writeln!(f, "  input:    {}", preview(&self.input, 0))?;
+
+        let from = self.expected.first_difference(&self.actual).unwrap_or(0);
+        if from > 0 {
+            writeln!(f, "  first difference at {}", from)?;
+        }
+        write!(f, "  expected: ")?;
+        self.expected.fmt_from(f, from)?;
+        write!(f, "\n  got:      ")?;
+        self.actual.fmt_from(f, from)
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+/// What a run without divergence went through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    pub kernels: usize,
+    pub inputs: usize,
+    /// Kernel calls, each version counted.
+    pub calls: usize,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} kernels, {} inputs, {} calls: no divergence",
+            self.kernels, self.inputs, self.calls
+        )
+    }
+}
+
+/// Run `kernels` over `corpus`, and stop at the first divergence.
+pub fn run(corpus: &Corpus, kernels: &[Kernel]) -> Result<Summary, Box<Divergence>> {
+    let mut rng = Rng::new(corpus.seed);
+    let mut summary = Summary { kernels: kernels.len(), inputs: 0, calls: 0 };
+    let mut buffer = Vec::new();
+    let mut outputs = Outputs::new();
+
+    for len in 0..=corpus.max_len {
+        for distribution in DISTRIBUTIONS {
+            let generated = distribution.generate(len, &mut rng);
+            let offset = rng.below(ALIGN as u32) as usize;
+            let range = place(&mut buffer, &generated, offset, rng.pick(SPECIALS));
+            let input = &buffer[range];
+            summary.inputs += 1;
+
+            for kernel in kernels {
+                let ks = *kernel.ks.start()..=(*kernel.ks.end()).min(len + 1);
+                for k in ks {
+                    outputs.clear();
+                    (kernel.run)(input, k, &mut outputs);
+                    summary.calls += outputs.len();
+
+                    let (reference, expected) = &outputs[0];
+                    if let Some((backend, actual)) =
+                        outputs[1..].iter().find(|(_, o)| o != expected)
+                    {
+                        return Err(Box::new(Divergence {
+                            kernel: kernel.name,
+                            reference,
+                            backend,
+                            k: (kernel.ks != (0..=0)).then_some(k),
+                            distribution,
+                            offset,
+                            input: input.to_vec(),
+                            expected: expected.clone(),
+                            actual: actual.clone(),
+                        }));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distributions() {
+        let mut rng = Rng::new(3);
+        for distribution in DISTRIBUTIONS {
+            assert!(distribution.generate(0, &mut rng).is_empty());
+            assert_eq!(distribution.generate(1000, &mut rng).len(), 1000);
+        }
+        let last = Distribution::LastByte.generate(100, &mut rng);
+        assert!(last[..99].iter().all(|b| TEXT.contains(b)) && SPECIALS.contains(&last[99]));
+
+        let mut buffer = Vec::new();
+        for offset in [0, 1, 63] {
+            let range = place(&mut buffer, b"abc", offset, b'"');
+            assert_eq!(buffer[range.clone()], *b"abc");
+            assert_eq!(
+                buffer[range.start..].as_ptr().align_offset(ALIGN),
+                (ALIGN - offset) % ALIGN
+            );
+            assert_eq!((buffer[range.start - 1], buffer[range.end]), (b'"', b'"'));
+        }
+    }
+
+    #[test]
+    fn test_kernels_conform() {
+        // Past three AVX2 blocks and a few of the line feed K values
+        let corpus = Corpus { max_len: 200, seed: 42 };
+        match run(&corpus, KERNELS) {
+            Ok(summary) => assert_eq!(summary.inputs, 201 * DISTRIBUTIONS.len()),
+            Err(divergence) => panic!("{}", divergence),
+        }
+    }
+
+    #[test]
+    fn test_divergence_reported() {
+        // An escaper that misses the quote in the last 7 bytes, as a SWAR
+        // loop without its tail would
+        fn broken(input: &[u8], _: usize, outputs: &mut Outputs) {
+            let body = input.len() - input.len() % 8;
+            let mut out = json::escape_json(&input[..body]);
+            out.extend_from_slice(&input[body..]);
+            outputs.push(("scalar", json::escape_json_scalar(input).into()));
+            outputs.push(("broken", out.into()));
+        }
+        let kernels = [Kernel { name: "escape_json", ks: 0..=0, run: broken }];
+
+        let divergence = run(&Corpus { max_len: 100, seed: 1 }, &kernels).unwrap_err();
+        assert_eq!(divergence.backend, "broken");
+        assert!((1..8).contains(&divergence.input.len()));
+        assert_eq!(divergence.k, None);
+        let message = divergence.to_string();
+        assert!(
+            message.starts_with("escape_json: broken differs from scalar on "),
+            "{}",
+            message
+        );
+        assert!(message.contains("expected: "), "{}", message);
+    }
+}
@@ -0,0 +1,537 @@
+//! The CPU we run on: the SIMD extensions the kernels are written for, its
+//! cores and its caches.
+//!
+//! The dispatchers ask [`has_neon`], [`has_avx2`], [`has_pclmulqdq`],
+//! [`has_pmull`] and [`has_sha2`]. NEON is part of the aarch64 baseline on
+//! every mainstream target, so on those [`has_neon`] folds to `true` at
+//! compile time and the dispatchers cost nothing. Targets built without it
+//! (`aarch64-unknown-none-softfloat`, custom `-C target-feature=-neon`
+//! builds) check the CPU once at runtime (the result is cached by std)
+//! instead of executing NEON unconditionally; so do the others, which no
+//! baseline includes.
+//!
+//! [`info`] gathers all of it in a [`CpuInfo`], detected once: the model,
+//! the performance and efficiency cores of a hybrid CPU, the cache sizes
+//! (which [`cpu_info`](crate::cpu_info) sizes the read buffers from), and
+//! the extensions. The benches print it before their first result and
+//! put it in every record, so that results from several machines say which
+//! one they came from:
+//!
+//! ```text
+//!   Intel(R) Xeon(R) Processor, 1 CPU, L1d 48 KB, L2 2 MB, L3 300 MB, AVX2 PCLMULQDQ
+//!   Apple M1, 4P+4E, L1d 128 KB, L2 12 MB, NEON PMULL SHA2
+//! ```
+//!
+//! Core kinds come from `/sys/devices/cpu_core` and `cpu_atom` (Intel hybrid)
+//! or the `cpu_capacity` of each CPU (Arm big.LITTLE) on Linux, and from
+//! `hw.perflevel*` on macOS. They count logical CPUs, as the threads of a
+//! bench would. Cache sizes come from `/sys/devices/system/cpu/cpu0/cache`
+//! on Linux and `sysctl hw.perflevel0.*` (falling back to `hw.*`) on macOS;
+//! elsewhere they are unknown.
+
+use std::{fmt, sync::OnceLock};
+
+
+/// Whether NEON can be used on this CPU.
+#[inline]
+pub fn has_neon() -> bool {
+    #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+    {
+        true
+    }
+
+    #[cfg(all(target_arch = "aarch64", not(target_feature = "neon")))]
+    {
+        std::arch::is_aarch64_feature_detected!("neon")
+    }
+
+    #[cfg(not(target_arch = "aarch64"))]
+    {
+        false
+    }
+}
+
+/// A SIMD entry point was called on a CPU (or architecture) without the
+/// extension it needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnsupportedTarget {
+    /// The missing extension, as named by `target_feature` ("neon", ...).
+    pub feature: &'static str,
+}
+
+impl fmt::Display for UnsupportedTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is not available on this CPU", self.feature)
+    }
+}
+
+impl std::error::Error for UnsupportedTarget {}
+
+/// `Ok(())` if NEON is available, [`UnsupportedTarget`] otherwise.
+pub fn require_neon() -> Result<(), UnsupportedTarget> {
+    if has_neon() {
+        Ok(())
+    } else {
+        Err(UnsupportedTarget { feature: "neon" })
+    }
+}
+
+/// Whether AVX2 can be used on this CPU.
+#[inline]
+pub fn has_avx2() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        is_x86_feature_detected!("avx2")
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        false
+    }
+}
+
+/// Whether PCLMULQDQ (carry-less multiply) can be used on this CPU.
+#[inline]
+pub fn has_pclmulqdq() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        is_x86_feature_detected!("pclmulqdq")
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        false
+    }
+}
+
+/// Whether the 64-bit PMULL can be used on this CPU: part of the `aes`
+/// extension, which Rust detects as one.
+#[inline]
+pub fn has_pmull() -> bool {
+    #[cfg(target_arch = "aarch64")]
+    {
+        std::arch::is_aarch64_feature_detected!("aes")
+    }
+
+    #[cfg(not(target_arch = "aarch64"))]
+    {
+        false
+    }
+}
+
+/// Whether the SHA-256 instructions can be used on this CPU.
+#[inline]
+pub fn has_sha2() -> bool {
+    #[cfg(target_arch = "aarch64")]
+    {
+        std::arch::is_aarch64_feature_detected!("sha2")
+    }
+
+    #[cfg(not(target_arch = "aarch64"))]
+    {
+        false
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 CpuInfo
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// The extensions a kernel of this crate can use on this CPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Features {
+    pub neon: bool,
+    pub pmull: bool,
+    pub sha2: bool,
+    pub avx2: bool,
+    pub pclmulqdq: bool,
+}
+
+impl Features {
+    pub fn detect() -> Self {
+        Features {
+            neon: has_neon(),
+            pmull: has_pmull(),
+            sha2: has_sha2(),
+            avx2: has_avx2(),
+            pclmulqdq: has_pclmulqdq(),
+        }
+    }
+
+    /// The names of the extensions present, `NEON PMULL SHA2`.
+    pub fn names(&self) -> Vec<&'static str> {
+        let all = [
+            (self.neon, "NEON"),
+            (self.pmull, "PMULL"),
+            (self.sha2, "SHA2"),
+            (self.avx2, "AVX2"),
+            (self.pclmulqdq, "PCLMULQDQ"),
+        ];
+        all.into_iter()
+            .filter(|(present, _)| *present)
+            .map(|(_, name)| name)
+            .collect()
+    }
+}
+
+/// Logical CPUs of each kind; all of them are performance CPUs on a CPU
+/// that is not hybrid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cores {
+    pub performance: usize,
+    pub efficiency: usize,
+}
+
+impl Cores {
+    pub fn total(&self) -> usize {
+        self.performance + self.efficiency
+    }
+
+    pub fn is_hybrid(&self) -> bool {
+        self.performance > 0 && self.efficiency > 0
+    }
+}
+
+impl fmt::Display for Cores {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.total() {
+            _ if self.is_hybrid() => write!(f, "{}P+{}E", self.performance, self.efficiency),
+            1 => write!(f, "1 CPU"),
+            n => write!(f, "{} CPUs", n),
+        }
+    }
+}
+
+/// Cache sizes in bytes, `None` where they could not be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheSizes {
+    /// L1 data cache of one core (of a performance core on hybrid CPUs).
+    pub l1d: Option<usize>,
+    /// L2 cache of one core, or of the cluster sharing it.
+    pub l2: Option<usize>,
+    /// Last-level cache shared by the cores; Apple silicon has none.
+    pub l3: Option<usize>,
+}
+
+/// What the kernels and the benches need to know of the CPU.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuInfo {
+    /// The name of the processor, or the architecture when it has none.
+    pub model: String,
+    pub cores: Cores,
+    pub caches: CacheSizes,
+    pub features: Features,
+}
+
+impl fmt::Display for CpuInfo {
+    /// One line: the model, the cores, the caches known, the extensions.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, {}", self.model, self.cores)?;
+        let caches = [
+            ("L1d", self.caches.l1d),
+            ("L2", self.caches.l2),
+            ("L3", self.caches.l3),
+        ];
+        for (name, size) in caches {
+            if let Some(size) = size {
+                write!(f, ", {} {}", name, format_cache_size(size))?;
+            }
+        }
+        let features = self.features.names();
+        if !features.is_empty() {
+            write!(f, ", {}", features.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A cache size in MB when it is a whole number of them, else in KB.
+fn format_cache_size(size: usize) -> String {
+    if size >= 1 << 20 && size.is_multiple_of(1 << 20) {
+        format!("{} MB", size >> 20)
+    } else if size.is_multiple_of(1 << 10) {
+        format!("{} KB", size >> 10)
+    } else {
+        format!("{} B", size)
+    }
+}
+
+/// This CPU (detected on the first call).
+pub fn info() -> &'static CpuInfo {
+    static INFO: OnceLock<CpuInfo> = OnceLock::new();
+    INFO.get_or_init(|| CpuInfo {
+        model: model().unwrap_or_else(|| std::env::consts::ARCH.to_string()),
+        cores: cores(),
+        caches: caches(),
+        features: Features::detect(),
+    })
+}
+
+// ───────────────────────────────────────────────────────────────────────────
+//                                Detection
+// ───────────────────────────────────────────────────────────────────────────
+
+/// Every logical CPU as a performance one: what is left when the kinds are
+/// unknown.
+fn uniform_cores() -> Cores {
+    let n = std::thread::available_parallelism().map_or(1, |n| n.get());
+    Cores { performance: n, efficiency: 0 }
+}
+
+/// The `model name` of `/proc/cpuinfo` (x86; Arm kernels have none).
+#[cfg(target_os = "linux")]
+fn model() -> Option<String> {
+    let cpuinfo = std::fs::read_to_string("/proc/cpuinfo").ok()?;
+    let line = cpuinfo
+        .lines()
+        .find(|line| line.starts_with("model name"))?;
+    let name = line
+        .split_once(':')?
+        .1
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!name.is_empty()).then_some(name)
+}
+
+/// Intel hybrid CPUs list their kinds in `/sys/devices/cpu_core` and
+/// `cpu_atom`; Arm kernels give each CPU a `cpu_capacity`, the highest for
+/// the performance cores.
+#[cfg(target_os = "linux")]
+fn cores() -> Cores {
+    let read = |path: &str| std::fs::read_to_string(path).ok();
+    let list = |path: &str| read(path).and_then(|text| parse_cpu_list(&text));
+
+    if let (Some(performance), Some(efficiency)) =
+        (list("/sys/devices/cpu_core/cpus"), list("/sys/devices/cpu_atom/cpus"))
+    {
+        return Cores { performance, efficiency };
+    }
+
+    let online = read("/sys/devices/system/cpu/online").and_then(|text| cpu_list(&text));
+    let capacities: Option<Vec<u32>> = online.as_ref().and_then(|cpus| {
+        cpus.iter()
+            .map(|cpu| {
+                let path = format!("/sys/devices/system/cpu/cpu{}/cpu_capacity", cpu);
+                read(&path)?.trim().parse().ok()
+            })
+            .collect()
+    });
+    match (capacities, online) {
+        (Some(capacities), _) if !capacities.is_empty() => {
+            let max = capacities.iter().copied().max().unwrap_or(0);
+            let performance = capacities.iter().filter(|&&c| c == max).count();
+            Cores { performance, efficiency: capacities.len() - performance }
+        }
+        (_, Some(online)) if !online.is_empty() => {
+            Cores { performance: online.len(), efficiency: 0 }
+        }
+        _ => uniform_cores(),
+    }
+}
+
+/// The CPUs of a sysfs CPU list, `0-3,8-11`.
+#[cfg(any(target_os = "linux", test))]
+fn cpu_list(text: &str) -> Option<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for range in text.trim().split(',').filter(|r| !r.is_empty()) {
+        let (first, last) = range.split_once('-').unwrap_or((range, range));
+        let (first, last): (usize, usize) = (first.parse().ok()?, last.parse().ok()?);
+        cpus.extend(first..=last);
+    }
+    Some(cpus)
+}
+
+/// The number of CPUs of a sysfs CPU list; `None` if there are none.
+#[cfg(any(target_os = "linux", test))]
+fn parse_cpu_list(text: &str) -> Option<usize> {
+    cpu_list(text).map(|cpus| cpus.len()).filter(|&n| n > 0)
+}
+
+/// Read the caches of cpu0 from sysfs: one `index*` directory per cache,
+/// with its `level`, `type` and `size`.
+#[cfg(target_os = "linux")]
+fn caches() -> CacheSizes {
+    let mut sizes = CacheSizes::default();
+    let Ok(entries) = std::fs::read_dir("/sys/devices/system/cpu/cpu0/cache") else {
+        return sizes;
+    };
+
+    for entry in entries.flatten() {
+        let dir = entry.path();
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).ok();
+        let (Some(level), Some(kind), Some(size)) = (read("level"), read("type"), read("size"))
+        else {
+            continue;
+        };
+        let size = parse_size(&size);
+        match (level.trim(), kind.trim()) {
+            ("1", "Data") => sizes.l1d = size,
+            ("2", "Data" | "Unified") => sizes.l2 = size,
+            ("3", "Unified") => sizes.l3 = size,
+            _ => {}
+        }
+    }
+    sizes
+}
+
+/// Parse a sysfs cache size: `48K`, `2048K`, `32M`.
+#[cfg(any(target_os = "linux", test))]
+fn parse_size(text: &str) -> Option<usize> {
+    let text = text.trim();
+    let (digits, unit) = match text.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+        Some((i, _)) => text.split_at(i),
+        None => (text, ""),
+    };
+    let multiplier = match unit {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => return None,
+    };
+    digits.parse::<usize>().ok()?.checked_mul(multiplier)
+}
+
+/// `machdep.cpu.brand_string`: `Apple M1`, `Intel(R) Core(TM) i9-9980HK ...`.
+#[cfg(target_os = "macos")]
+fn model() -> Option<String> {
+    let name = b"machdep.cpu.brand_string\0";
+    let mut buffer = [0u8; 256];
+    let mut len = buffer.len();
+    let result = unsafe {
+        libc::sysctlbyname(
+            name.as_ptr() as *const libc::c_char,
+            buffer.as_mut_ptr() as *mut libc::c_void,
+            &mut len,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if result != 0 {
+        return None;
+    }
+    let bytes = &buffer[..len.min(buffer.len())];
+    let text = String::from_utf8_lossy(bytes.split(|&b| b == 0).next()?);
+    Some(text.trim().to_string()).filter(|name| !name.is_empty())
+}
+
+/// `hw.perflevel0` is the fastest kind of core on Apple silicon, and Intel
+/// Macs have no perflevels.
+#[cfg(target_os = "macos")]
+fn cores() -> Cores {
+    match sysctl_size("hw.perflevel0.logicalcpu\0") {
+        Some(performance) => Cores {
+            performance,
+            efficiency: sysctl_size("hw.perflevel1.logicalcpu\0").unwrap_or(0),
+        },
+        None => uniform_cores(),
+    }
+}
+
+/// Ask the kernel: the sizes of the performance cores on Apple silicon
+/// (`hw.perflevel0`), the plain `hw.*` values on Intel Macs.
+#[cfg(target_os = "macos")]
+fn caches() -> CacheSizes {
+    let read = |names: [&str; 2]| names.into_iter().find_map(sysctl_size);
+    CacheSizes {
+        l1d: read(["hw.perflevel0.l1dcachesize\0", "hw.l1dcachesize\0"]),
+        l2: read(["hw.perflevel0.l2cachesize\0", "hw.l2cachesize\0"]),
+        l3: sysctl_size("hw.l3cachesize\0"),
+    }
+}
+
+/// A numeric sysctl (`name` NUL-terminated), `None` if missing or zero.
+#[cfg(target_os = "macos")]
+fn sysctl_size(name: &str) -> Option<usize> {
+    let mut value = 0u64;
+    let mut len = std::mem::size_of::<u64>();
+    let result = unsafe {
+        libc::sysctlbyname(
+            name.as_ptr() as *const libc::c_char,
+            &mut value as *mut u64 as *mut libc::c_void,
+            &mut len,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    // 32-bit values fill the low bytes (little-endian)
+    if result != 0 || value == 0 || (len != 4 && len != 8) {
+        return None;
+    }
+    usize::try_from(value).ok()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn model() -> Option<String> {
+    None
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn cores() -> Cores {
+    uniform_cores()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn caches() -> CacheSizes {
+    CacheSizes::default()
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_require_neon_matches_arch() {
+        assert_eq!(require_neon().is_ok(), has_neon());
+
+        #[cfg(not(target_arch = "aarch64"))]
+        assert_eq!(require_neon().unwrap_err().to_string(), "neon is not available on this CPU");
+    }
+
+    #[test]
+    fn test_info() {
+        let info = info();
+        assert_eq!(info.features, Features::detect());
+        assert!(info.cores.total() >= 1);
+        assert_eq!(info.caches, caches());
+        assert!(info.to_string().starts_with(&format!("{}, ", info.model)));
+        #[cfg(target_arch = "x86_64")]
+        assert!(!info.features.neon && !info.features.sha2);
+
+        let info = CpuInfo {
+            model: "Apple M1".to_string(),
+            cores: Cores { performance: 4, efficiency: 4 },
+            caches: CacheSizes { l1d: Some(128 << 10), l2: Some(12 << 20), l3: None },
+            features: Features { neon: true, pmull: true, sha2: true, ..Features::default() },
+        };
+        assert_eq!(info.to_string(), "Apple M1, 4P+4E, L1d 128 KB, L2 12 MB, NEON PMULL SHA2");
+        assert_eq!(format_cache_size(1280 << 10), "1280 KB");
+        let one = Cores { performance: 1, efficiency: 0 };
+        assert_eq!((one.to_string(), one.is_hybrid()), ("1 CPU".to_string(), false));
+    }
+
+    #[test]
+    fn test_cpu_list() {
+        assert_eq!(parse_cpu_list("0-15\n"), Some(16));
+        assert_eq!(parse_cpu_list("0-3,8-11"), Some(8));
+        assert_eq!(parse_cpu_list("0"), Some(1));
+        assert_eq!(parse_cpu_list("\n"), None);
+        assert_eq!(parse_cpu_list("0-x"), None);
+        assert_eq!(cpu_list("2,4-5"), Some(vec![2, 4, 5]));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("48K\n"), Some(48 * 1024));
+        assert_eq!(parse_size("2048K"), Some(2 << 20));
+        assert_eq!(parse_size("32M"), Some(32 << 20));
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("12Q"), None);
+        assert_eq!(parse_size(""), None);
+    }
+}
@@ -0,0 +1,68 @@
+//! The read buffer size the caches of the CPU we run on suggest.
+//!
+//! `cache_aware_bench` shows where buffered scans are fastest: once the
+//! buffer no longer fits in the L1 data cache, it is evicted between the copy
+//! out of the page cache and the scan. Three runs on a Linux VM with a 48 KB
+//! L1d (the VM is noisy, about ±15% from run to run):
+//!
+//! | Buffer | 4 KB           | 8-64 KB        | 96-192 KB      |
+//! |--------|----------------|----------------|----------------|
+//! | Scan   | 0.80-1.06 GB/s | 0.78-1.47 GB/s | 0.75-1.09 GB/s |
+//!
+//! 8-64 KB beat 4 KB (a `read()` per page) in almost every run, and 96-192
+//! KB fell back to about 4 KB speed in two runs out of three.
+//! [`optimal_buffer_size`] takes half the L1d (16 KB here, 64 KB on an Apple
+//! M-series P-core), which leaves the other half to everything else.
+//!
+//! The sizes are those of [`cpu::info`], detected once with the rest of the
+//! CPU. Where they are unknown the buffer stays at 4 KB.
+
+use crate::cpu::{self, CacheSizes};
+
+/// Buffer size when the L1 data cache size is unknown: one page, as in the
+/// blog post.
+pub const DEFAULT_BUFFER_SIZE: usize = 4096;
+
+/// Bounds of [`optimal_buffer_size`].
+const MIN_BUFFER_SIZE: usize = 4096;
+const MAX_BUFFER_SIZE: usize = 256 * 1024;
+
+/// Read buffer size for the buffered scanners: half the L1 data cache,
+/// rounded down to a power of two, between 4 KB and 256 KB.
+pub fn optimal_buffer_size() -> usize {
+    buffer_size_for(cpu::info().caches)
+}
+
+fn buffer_size_for(caches: CacheSizes) -> usize {
+    match caches.l1d {
+        Some(l1d) if l1d >= 2 => {
+            let half = l1d / 2;
+            (1 << half.ilog2()).clamp(MIN_BUFFER_SIZE, MAX_BUFFER_SIZE)
+        }
+        _ => DEFAULT_BUFFER_SIZE,
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffer_size_for() {
+        let with_l1d = |l1d| buffer_size_for(CacheSizes { l1d, l2: None, l3: None });
+        assert_eq!(with_l1d(None), DEFAULT_BUFFER_SIZE);
+        assert_eq!(with_l1d(Some(48 * 1024)), 16 * 1024);
+        assert_eq!(with_l1d(Some(64 * 1024)), 32 * 1024);
+        assert_eq!(with_l1d(Some(128 * 1024)), 64 * 1024);
+        assert_eq!(with_l1d(Some(1024)), MIN_BUFFER_SIZE);
+        assert_eq!(with_l1d(Some(0)), DEFAULT_BUFFER_SIZE);
+        assert_eq!(with_l1d(Some(16 << 20)), MAX_BUFFER_SIZE);
+
+        let size = optimal_buffer_size();
+        assert!(size.is_power_of_two() && (MIN_BUFFER_SIZE..=MAX_BUFFER_SIZE).contains(&size));
+    }
+}
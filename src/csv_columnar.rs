@@ -0,0 +1,352 @@
+//! Columnar CSV output in the Arrow layout (behind the `arrow` feature).
+//!
+//! Dataframe libraries store a text column as two buffers: the values of all
+//! rows back to back, and `rows + 1` i32 offsets into them (value `i` is
+//! `values[offsets[i]..offsets[i + 1]]`). [`parse_columns`] fills those
+//! buffers straight from the DFA tokenizer in `csv_state_machine`, one
+//! [`Column`] per CSV column, so parsed CSV reaches arrow-rs (and polars,
+//! through it) without a `Vec<Vec<String>>` in between:
+//!
+//! ```text
+//!   input:    name,city$Ann,"Oslo, NO"$Bob,Rome$      ($ = \n, header row)
+//!   name:     values  AnnBob          offsets  0 3 6
+//!   city:     values  Oslo, NORome    offsets  0 8 12
+//! ```
+//!
+//! Values are unescaped on the way in: enclosing quotes dropped, `""`
+//! collapsed (or backslash escapes resolved, depending on the dialect). A
+//! row shorter than the widest one gets empty values for its missing
+//! columns, so every column has one value per row.
+//!
+//! [`Column::into_binary_array`] and [`Column::into_string_array`] hand the
+//! buffers over to arrow-rs without copying them.
+
+use std::fmt;
+
+use arrow_array::{BinaryArray, StringArray};
+use arrow_buffer::{Buffer, OffsetBuffer, ScalarBuffer};
+use arrow_schema::ArrowError;
+
+use crate::{
+    csv_dialect::{Dialect, DialectError},
+    csv_state_machine::{CsvStateMachine, FieldSpan},
+};
+
+/// Why the columns could not be built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnarError {
+    /// The dialect cannot be parsed (see [`Dialect::validate`]).
+    Dialect(DialectError),
+    /// The values of a column add up to more than `i32::MAX` bytes, which
+    /// i32 offsets cannot address.
+    ColumnTooLarge { column: usize },
+}
+
+impl fmt::Display for ColumnarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColumnarError::Dialect(e) => write!(f, "invalid dialect: {}", e),
+            ColumnarError::ColumnTooLarge { column } => {
+                write!(f, "column {} holds more than 2 GB of values", column)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ColumnarError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ColumnarError::Dialect(e) => Some(e),
+            ColumnarError::ColumnTooLarge { .. } => None,
+        }
+    }
+}
+
+impl From<DialectError> for ColumnarError {
+    fn from(e: DialectError) -> Self {
+        ColumnarError::Dialect(e)
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Column
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// One column as an Arrow variable-size binary layout: i32 offsets plus
+/// contiguous value bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    offsets: Vec<i32>,
+    values: Vec<u8>,
+}
+
+impl Column {
+    fn new() -> Self {
+        Column { offsets: vec![0], values: Vec::new() }
+    }
+
+    /// Number of values (rows).
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Value `i`, or None if out of bounds.
+    #[inline]
+    pub fn get(&self, i: usize) -> Option<&[u8]> {
+        let start = *self.offsets.get(i)? as usize;
+        let end = *self.offsets.get(i + 1)? as usize;
+        Some(&self.values[start..end])
+    }
+
+    /// The `len() + 1` offsets, starting at 0.
+    pub fn offsets(&self) -> &[i32] {
+        &self.offsets
+    }
+
+    /// All values, back to back.
+    pub fn values(&self) -> &[u8] {
+        &self.values
+    }
+
+    /// The offset and value buffers.
+    pub fn into_parts(self) -> (Vec<i32>, Vec<u8>) {
+        (self.offsets, self.values)
+    }
+
+    /// Convert to an arrow-rs `BinaryArray`, reusing both buffers.
+    pub fn into_binary_array(self) -> BinaryArray {
+        let offsets = OffsetBuffer::new(ScalarBuffer::from(self.offsets));
+        BinaryArray::new(offsets, Buffer::from_vec(self.values), None)
+    }
+
+    /// Convert to an arrow-rs `StringArray`, reusing both buffers.
+    ///
+    /// Fails if a value is not valid UTF-8.
+    pub fn into_string_array(self) -> Result<StringArray, ArrowError> {
+        let offsets = OffsetBuffer::new(ScalarBuffer::from(self.offsets));
+        StringArray::try_new(offsets, Buffer::from_vec(self.values), None)
+    }
+
+    /// Append empty values until the column has `rows` values.
+    #[inline]
+    fn pad_to(&mut self, rows: usize) {
+        let end = *self.offsets.last().unwrap();
+        self.offsets.resize(rows + 1, end);
+    }
+
+    /// End the value written since the last offset.
+    #[inline]
+    fn finish_value(&mut self, column: usize) -> Result<(), ColumnarError> {
+        let end = i32::try_from(self.values.len())
+            .map_err(|_| ColumnarError::ColumnTooLarge { column })?;
+        self.offsets.push(end);
+        Ok(())
+    }
+}
+
+/// Append the content of a field with its escapes resolved.
+#[inline]
+fn push_unescaped(raw: &[u8], quoted: bool, dialect: &Dialect, out: &mut Vec<u8>) {
+    // Doubled quotes only occur inside quotes; a backslash escapes anywhere
+    let special = match dialect.escape_byte() {
+        Some(escape) => escape,
+        None if quoted => dialect.quote,
+        None => {
+            out.extend_from_slice(raw);
+            return;
+        }
+    };
+
+    let mut rest = raw;
+    while let Some(p) = memchr::memchr(special, rest) {
+        // Either way the byte after it is kept: `\x` is x, `""` is `"`
+        out.extend_from_slice(&rest[..p]);
+        match rest.get(p + 1) {
+            Some(&b) => out.push(b),
+            None => {
+                // A lone escape byte at the very end of the input is data
+                out.push(special);
+                return;
+            }
+        }
+        rest = &rest[p + 2..];
+    }
+    out.extend_from_slice(rest);
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Parsing
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Parsed CSV in columns, from [`parse_columns`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Columns {
+    /// The header row, unescaped (empty without a header).
+    pub names: Vec<Vec<u8>>,
+    /// One column per field of the widest row (or header).
+    pub columns: Vec<Column>,
+    /// Number of data rows: the length of every column.
+    pub rows: usize,
+}
+
+/// Parse [`Dialect::CSV`] into columns, taking the first row as column names
+/// if `header` is set.
+///
+/// # Example
+/// ```
+/// use scratchpad::csv_columnar::parse_columns;
+///
+/// let parsed = parse_columns(b"name,city\nAnn,\"Oslo, NO\"\nBob,Rome\n", true).unwrap();
+/// assert_eq!(parsed.names, vec![b"name".to_vec(), b"city".to_vec()]);
+/// assert_eq!(parsed.rows, 2);
+///
+/// let city = &parsed.columns[1];
+/// assert_eq!(city.values(), b"Oslo, NORome");
+/// assert_eq!(city.offsets(), &[0, 8, 12]);
+///
+/// let array = parsed.columns[0].clone().into_string_array().unwrap();
+/// assert_eq!(array.value(1), "Bob");
+/// ```
+pub fn parse_columns(data: &[u8], header: bool) -> Result<Columns, ColumnarError> {
+    parse_columns_with(data, &Dialect::CSV, header)
+}
+
+/// [`parse_columns`] for any dialect.
+pub fn parse_columns_with(
+    data: &[u8],
+    dialect: &Dialect,
+    header: bool,
+) -> Result<Columns, ColumnarError> {
+    let machine = CsvStateMachine::new(dialect)?;
+    let mut names = Vec::new();
+    let mut columns: Vec<Column> = Vec::new();
+    let mut rows = 0;
+    let mut result = Ok(());
+
+    machine.for_each_span(data, |span: FieldSpan| {
+        if result.is_err() {
+            return;
+        }
+        let raw = &data[span.range];
+        if header && span.row == 0 {
+            let mut name = Vec::new();
+            push_unescaped(raw, span.quoted, dialect, &mut name);
+            names.push(name);
+            return;
+        }
+
+        let row = span.row - header as usize;
+        rows = row + 1;
+        if span.column >= columns.len() {
+            columns.resize_with(span.column + 1, Column::new);
+        }
+        let column = &mut columns[span.column];
+        column.pad_to(row);
+        push_unescaped(raw, span.quoted, dialect, &mut column.values);
+        result = column.finish_value(span.column);
+    });
+    result?;
+
+    if columns.len() < names.len() {
+        columns.resize_with(names.len(), Column::new);
+    }
+    for column in &mut columns {
+        column.pad_to(rows);
+    }
+
+    Ok(Columns { names, columns, rows })
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::Array;
+
+    use super::*;
+    use crate::csv_dialect::Escape;
+    use crate::csv_index::extract_column;
+
+    fn values(column: &Column) -> Vec<&[u8]> {
+        (0..column.len()).map(|i| column.get(i).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_ragged_rows() {
+        let parsed = parse_columns(b"a,b\n1\n2,\"x\"\"y\",extra\n,\n", true).unwrap();
+        assert_eq!(parsed.rows, 3);
+        assert_eq!(parsed.columns.len(), 3);
+        assert_eq!(values(&parsed.columns[0]), vec![&b"1"[..], b"2", b""]);
+        assert_eq!(values(&parsed.columns[1]), vec![&b""[..], b"x\"y", b""]);
+        assert_eq!(values(&parsed.columns[2]), vec![&b""[..], b"extra", b""]);
+        assert_eq!(parsed.columns[2].offsets(), &[0, 0, 5, 5]);
+
+        let header_only = parse_columns(b"a,b\n", true).unwrap();
+        assert_eq!((header_only.rows, header_only.columns.len()), (0, 2));
+        assert!(header_only.columns[0].is_empty());
+
+        let empty = parse_columns(b"", false).unwrap();
+        assert_eq!((empty.rows, empty.columns.len()), (0, 0));
+    }
+
+    #[test]
+    fn test_matches_extract_column() {
+        let mut data = Vec::new();
+        let mut rng = 17u64;
+        for row in 0..300 {
+            for column in 0..4 {
+                rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+                if column > 0 {
+                    data.push(b',');
+                }
+                match (rng >> 16) % 4 {
+                    0 => data.extend_from_slice(b"\"q,\nx\""),
+                    1 => {}
+                    _ => data.extend_from_slice(format!("v{}", row).as_bytes()),
+                }
+            }
+            data.push(b'\n');
+        }
+
+        // No doubled quotes, so the raw values are the unescaped ones
+        let parsed = parse_columns(&data, false).unwrap();
+        assert_eq!(parsed.rows, 300);
+        for (i, column) in parsed.columns.iter().enumerate() {
+            assert_eq!(values(column), extract_column(&data, i), "Mismatch for column={}", i);
+        }
+    }
+
+    #[test]
+    fn test_dialects() {
+        let mysql = Dialect { delimiter: b'\t', escape: Escape::Backslash, ..Dialect::CSV };
+        let parsed = parse_columns_with(b"a\\\tb\t\"c\\\"d\"\tend\\", &mysql, false).unwrap();
+        let row: Vec<&[u8]> = parsed.columns.iter().map(|c| c.get(0).unwrap()).collect();
+        assert_eq!(row, vec![&b"a\tb"[..], b"c\"d", b"end\\"]);
+
+        let bad = Dialect { quote: b',', ..Dialect::CSV };
+        assert!(matches!(parse_columns_with(b"", &bad, false), Err(ColumnarError::Dialect(_))));
+    }
+
+    #[test]
+    fn test_into_arrow() {
+        let parsed = parse_columns(b"\"\xC3\xA9t\xC3\xA9\",x\n,\xFF\n", false).unwrap();
+        let mut columns = parsed.columns.into_iter();
+
+        let strings = columns.next().unwrap().into_string_array().unwrap();
+        assert_eq!(strings.len(), 2);
+        assert_eq!((strings.value(0), strings.value(1)), ("\u{e9}t\u{e9}", ""));
+
+        let column = columns.next().unwrap();
+        assert!(column.clone().into_string_array().is_err());
+        let binary = column.into_binary_array();
+        assert_eq!((binary.value(0), binary.value(1)), (&b"x"[..], &b"\xFF"[..]));
+    }
+}
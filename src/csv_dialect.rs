@@ -0,0 +1,185 @@
+//! CSV dialects: which bytes delimit fields and records, and how quotes work.
+//!
+//! RFC 4180 is only one member of a large family. Exports from databases and
+//! spreadsheets commonly differ in:
+//!
+//! - the delimiter: `,`, `;` (European locales), `\t` (TSV), `|`
+//! - the quote character: `"` or `'`
+//! - how a quote is written inside a quoted field: doubled (`""`, RFC 4180) or preceded by a
+//!   backslash (`\"`, MySQL / PostgreSQL text format)
+//! - the record terminator: `\n`, or `\r` for old Mac exports
+//! - a UTF-8 byte order mark (`EF BB BF`) before the first byte, which Excel
+//!   writes on every "CSV UTF-8" export
+//!
+//! A [`Dialect`] bundles these choices; parsers take one at construction.
+
+use std::fmt;
+
+/// How a quote character is written inside a quoted field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Escape {
+    /// The quote is doubled: `"say ""hi"""` (RFC 4180).
+    Doubled,
+    /// The quote (or any other byte) is preceded by `\`: `"say \"hi\""`.
+    /// A backslash also escapes delimiters and terminators outside quotes.
+    Backslash,
+}
+
+/// The bytes that give a CSV file its structure.
+///
+/// # Example
+/// ```
+/// use scratchpad::csv_dialect::{Dialect, Escape};
+///
+/// let mysql = Dialect { delimiter: b'\t', escape: Escape::Backslash, ..Dialect::CSV };
+/// assert!(mysql.validate().is_ok());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dialect {
+    pub delimiter: u8,
+    pub quote: u8,
+    pub escape: Escape,
+    pub terminator: u8,
+    /// Skip a UTF-8 BOM at the very start of the input, so it does not end
+    /// up in the first field.
+    pub skip_bom: bool,
+}
+
+impl Dialect {
+    /// RFC 4180: `,` `"` doubled quotes, `\n` (a `\r` before it is data),
+    /// leading BOM skipped.
+    pub const CSV: Dialect = Dialect {
+        delimiter: b',',
+        quote: b'"',
+        escape: Escape::Doubled,
+        terminator: b'\n',
+        skip_bom: true,
+    };
+
+    /// Tab-separated values, otherwise like [`Dialect::CSV`].
+    pub const TSV: Dialect = Dialect { delimiter: b'\t', ..Dialect::CSV };
+
+    /// The escape byte, if the dialect has one.
+    #[inline]
+    pub const fn escape_byte(&self) -> Option<u8> {
+        match self.escape {
+            Escape::Doubled => None,
+            Escape::Backslash => Some(b'\\'),
+        }
+    }
+
+    /// `data` without its leading BOM, if the dialect skips it.
+    #[inline]
+    pub fn strip_bom<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        if self.skip_bom {
+            strip_bom(data)
+        } else {
+            data
+        }
+    }
+
+    /// Check that the structural bytes are distinct and not NUL.
+    ///
+    /// NUL is reserved: the state machine parsers use it as the end-of-input
+    /// sentinel. A `const fn`, so tables can be built for a dialect at
+    /// compile time.
+    pub const fn validate(&self) -> Result<(), DialectError> {
+        let (bytes, len) = match self.escape_byte() {
+            Some(escape) => ([self.delimiter, self.quote, self.terminator, escape], 4),
+            None => ([self.delimiter, self.quote, self.terminator, 0], 3),
+        };
+
+        let mut i = 0;
+        while i < len {
+            if bytes[i] == 0 {
+                return Err(DialectError::Nul);
+            }
+            i += 1;
+        }
+
+        let mut i = 0;
+        while i < len {
+            let mut j = i + 1;
+            while j < len {
+                if bytes[j] == bytes[i] {
+                    return Err(DialectError::Duplicate(bytes[i]));
+                }
+                j += 1;
+            }
+            i += 1;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Dialect::CSV
+    }
+}
+
+/// The UTF-8 encoding of U+FEFF, used as a byte order mark.
+pub const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// `data` without a leading UTF-8 BOM.
+///
+/// # Example
+/// ```
+/// use scratchpad::csv_dialect::strip_bom;
+///
+/// assert_eq!(strip_bom(b"\xEF\xBB\xBFname,age\n"), b"name,age\n");
+/// assert_eq!(strip_bom(b"name,age\n"), b"name,age\n");
+/// ```
+#[inline]
+pub fn strip_bom(data: &[u8]) -> &[u8] {
+    data.strip_prefix(UTF8_BOM).unwrap_or(data)
+}
+
+/// Why a [`Dialect`] cannot be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DialectError {
+    /// One of the structural bytes is NUL.
+    Nul,
+    /// The same byte is used for two roles.
+    Duplicate(u8),
+}
+
+impl fmt::Display for DialectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DialectError::Nul => write!(f, "dialect uses NUL, which is reserved as the sentinel"),
+            DialectError::Duplicate(b) => {
+                write!(f, "dialect uses {:?} for two roles", char::from(*b))
+            }
+        }
+    }
+}
+
+impl std::error::Error for DialectError {}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert!(Dialect::CSV.validate().is_ok());
+        assert!(Dialect::TSV.validate().is_ok());
+
+        let semicolon = Dialect { delimiter: b';', quote: b'\'', ..Dialect::CSV };
+        assert!(semicolon.validate().is_ok());
+
+        let clash = Dialect { delimiter: b'"', ..Dialect::CSV };
+        assert_eq!(clash.validate(), Err(DialectError::Duplicate(b'"')));
+
+        let clash = Dialect { quote: b'\\', escape: Escape::Backslash, ..Dialect::CSV };
+        assert_eq!(clash.validate(), Err(DialectError::Duplicate(b'\\')));
+
+        let nul = Dialect { terminator: 0, ..Dialect::CSV };
+        assert_eq!(nul.validate(), Err(DialectError::Nul));
+    }
+}
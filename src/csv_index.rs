@@ -0,0 +1,1265 @@
+//! simdcsv-style structural index of a CSV document, built in two passes.
+//!
+//! Based on: https://github.com/geofflangdale/simdcsv
+//!
+//! The DFA and if/else counters in `csv_state_machine` look at one byte at a
+//! time. Here bytes are only classified, 64 at a time, and the parsing works
+//! on bitmasks:
+//!
+//! 1. **Classify**: for each 64-byte block, masks of the quotes, delimiters and terminators (NEON
+//!    compares folded into 64 bits, AVX2 `_mm256_movemask_epi8`, SWAR elsewhere).
+//!    `prefix_xor(quotes)` (one PMULL / PCLMULQDQ carry-less multiply) is the "inside quotes"
+//!    mask, so separators inside quoted fields are dropped without any per-byte state.
+//! 2. **Walk**: the set bits left over are the field ends; the ones that are terminators also end a
+//!    row.
+//!
+//! ```text
+//!   input:       a,"b,c"$"x""y",z$      ($ = \n)
+//!   quotes:      ..1...1.1.11.1...
+//!   in_quotes:   ..1111..11.11....      prefix_xor(quotes)
+//!   separators:  .1.....1......1.1      (delimiters | terminators) & !in_quotes
+//! ```
+//!
+//! A doubled `""` leaves the quoted field and enters it again, so escaped
+//! quotes need no special case. Quotes toggle wherever they appear, as in
+//! `parse_csv_if_else`: on well-formed input the counts equal the other
+//! parsers', but a stray quote inside an unquoted field opens a quoted region
+//! here while the DFA keeps it as data. A quoted field still open at the end
+//! of the input is dropped, as the DFA does.
+//!
+//! Uses [`Dialect::CSV`](crate::csv_dialect::Dialect::CSV): `,` `"` `\n`,
+//! leading BOM skipped.
+//!
+//! [`extract_column`] fuses the two passes to pull single columns out without
+//! tokenizing the others, and [`csv_stats`] to profile a file's fields and
+//! rows. [`count_csv_rows`] and [`index_rows`] stop after pass 1 when only
+//! the rows are needed, and [`unquote_field`] decodes the raw fields the
+//! index spans. [`RecordCursor`] finds the record around a position on
+//! demand, for scanners that jump ahead with memchr.
+//! [`parse_csv_parallel`] splits the counting over threads: quotes are the
+//! only state that crosses a chunk boundary, and it is a single bit.
+
+#[cfg(target_arch = "aarch64")]
+use std::arch::aarch64::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+use std::{fmt, io, ops::Range, thread};
+
+#[cfg(target_arch = "aarch64")]
+use crate::bitmask::movemask_64_neon;
+use crate::{
+    bitmask::{eq_bitmask_64_swar, eq_mask_swar, padded_block, prefix_xor},
+    cpu,
+    csv_dialect::strip_bom,
+    sink::{Sink, Value},
+};
+
+const QUOTE: u8 = b'"';
+const DELIMITER: u8 = b',';
+const TERMINATOR: u8 = b'\n';
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                          Pass 1: Classification
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// The structural bytes of one 64-byte block (bit i for byte i).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockMasks {
+    pub quotes: u64,
+    pub delimiters: u64,
+    pub terminators: u64,
+}
+
+/// Classify a block (SWAR version).
+pub fn classify_block_swar(block: &[u8; 64]) -> BlockMasks {
+    BlockMasks {
+        quotes: eq_bitmask_64_swar(block, QUOTE),
+        delimiters: eq_bitmask_64_swar(block, DELIMITER),
+        terminators: eq_bitmask_64_swar(block, TERMINATOR),
+    }
+}
+
+/// Classify a block (NEON version): 4 loads, then 3 compares per register.
+///
+/// # Safety
+/// Requires a CPU with NEON support (see [`cpu::has_neon`]).
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+pub unsafe fn classify_block_neon(block: &[u8; 64]) -> BlockMasks {
+    let ptr = block.as_ptr();
+    let v0 = vld1q_u8(ptr);
+    let v1 = vld1q_u8(ptr.add(16));
+    let v2 = vld1q_u8(ptr.add(32));
+    let v3 = vld1q_u8(ptr.add(48));
+
+    let mask = |byte: u8| {
+        let needle = vdupq_n_u8(byte);
+        movemask_64_neon(
+            vceqq_u8(v0, needle),
+            vceqq_u8(v1, needle),
+            vceqq_u8(v2, needle),
+            vceqq_u8(v3, needle),
+        )
+    };
+
+    BlockMasks { quotes: mask(QUOTE), delimiters: mask(DELIMITER), terminators: mask(TERMINATOR) }
+}
+
+/// Bytes of `lo:hi` equal to `byte`, as a 64-bit mask.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn eq_mask_avx2(lo: __m256i, hi: __m256i, byte: u8) -> u64 {
+    let needle = _mm256_set1_epi8(byte as i8);
+    let lo = _mm256_movemask_epi8(_mm256_cmpeq_epi8(lo, needle)) as u32 as u64;
+    let hi = _mm256_movemask_epi8(_mm256_cmpeq_epi8(hi, needle)) as u32 as u64;
+    lo | (hi << 32)
+}
+
+/// Classify a block (AVX2 version): 2 loads, then 3 compares per register.
+///
+/// # Safety
+/// Requires a CPU with AVX2 support (check with [`has_avx2`](crate::cpu::has_avx2)).
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+pub unsafe fn classify_block_avx2(block: &[u8; 64]) -> BlockMasks {
+    let ptr = block.as_ptr() as *const __m256i;
+    let lo = _mm256_loadu_si256(ptr);
+    let hi = _mm256_loadu_si256(ptr.add(1));
+
+    BlockMasks {
+        quotes: eq_mask_avx2(lo, hi, QUOTE),
+        delimiters: eq_mask_avx2(lo, hi, DELIMITER),
+        terminators: eq_mask_avx2(lo, hi, TERMINATOR),
+    }
+}
+
+/// Classify a block (best available version).
+#[inline]
+pub fn classify_block(block: &[u8; 64]) -> BlockMasks {
+    #[cfg(target_arch = "aarch64")]
+    if cpu::has_neon() {
+        return unsafe { classify_block_neon(block) };
+    }
+
+    // AVX2 is not part of the x86_64 baseline: detected at runtime (cached)
+    #[cfg(target_arch = "x86_64")]
+    if cpu::has_avx2() {
+        return unsafe { classify_block_avx2(block) };
+    }
+
+    classify_block_swar(block)
+}
+
+/// Separators outside quotes in one block.
+#[derive(Debug, Clone, Copy)]
+struct Separators {
+    /// Delimiters and terminators: field ends.
+    fields: u64,
+    /// Terminators: row ends.
+    rows: u64,
+}
+
+/// Pass 1: classify every block and mask out what is inside quotes.
+///
+/// Also returns whether the input ends inside a quoted field.
+fn find_separators(data: &[u8]) -> (Vec<Separators>, bool) {
+    let mut separators = Vec::with_capacity(data.len().div_ceil(64));
+    let mut prev_in_quotes = 0u64;
+
+    for chunk in data.chunks(64) {
+        let block = if chunk.len() == 64 {
+            chunk.try_into().unwrap()
+        } else {
+            padded_block(chunk)
+        };
+
+        let masks = classify_block(&block);
+        let in_quotes = prefix_xor(masks.quotes) ^ prev_in_quotes;
+        prev_in_quotes = ((in_quotes as i64) >> 63) as u64;
+
+        separators.push(Separators {
+            fields: (masks.delimiters | masks.terminators) & !in_quotes,
+            rows: masks.terminators & !in_quotes,
+        });
+    }
+
+    (separators, prev_in_quotes != 0)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                          Pass 2: Boundaries
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Why the index could not be built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsvIndexError {
+    /// Positions are stored as u32; inputs must be smaller than 4 GB.
+    InputTooLarge,
+}
+
+impl fmt::Display for CsvIndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsvIndexError::InputTooLarge => write!(f, "input larger than 4 GB"),
+        }
+    }
+}
+
+impl std::error::Error for CsvIndexError {}
+
+/// Field and row boundaries of a CSV document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvIndex {
+    /// End offset of every field: its delimiter or terminator, or the input
+    /// length for an unterminated last field. Ascending.
+    pub field_ends: Vec<u32>,
+    /// For every row, the number of fields up to and including it: row `r`
+    /// holds fields `row_ends[r - 1]..row_ends[r]`.
+    pub row_ends: Vec<u32>,
+    /// Start of the first field (after a skipped BOM).
+    start: usize,
+}
+
+impl CsvIndex {
+    pub fn field_count(&self) -> usize {
+        self.field_ends.len()
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.row_ends.len()
+    }
+
+    /// Byte range of field `i` in the input, quotes included.
+    pub fn field(&self, i: usize) -> Range<usize> {
+        let start = match i {
+            0 => self.start,
+            _ => self.field_ends[i - 1] as usize + 1,
+        };
+        start..self.field_ends[i] as usize
+    }
+
+    /// The field indices of row `r`, for [`CsvIndex::field`].
+    pub fn row(&self, r: usize) -> Range<usize> {
+        let start = match r {
+            0 => 0,
+            _ => self.row_ends[r - 1] as usize,
+        };
+        start..self.row_ends[r] as usize
+    }
+}
+
+/// Build the field and row index of `data`.
+///
+/// # Example
+/// ```
+/// use scratchpad::csv_index::build_index;
+///
+/// let data = b"a,\"b,c\"\n\"x\"\"y\",z\n";
+/// let index = build_index(data).unwrap();
+/// assert_eq!(index.field_ends, vec![1, 7, 14, 16]);
+/// assert_eq!(index.row_ends, vec![2, 4]);
+///
+/// let fields: Vec<&[u8]> = index.row(1).map(|i| &data[index.field(i)]).collect();
+/// assert_eq!(fields, vec![&b"\"x\"\"y\""[..], b"z"]);
+/// ```
+pub fn build_index(data: &[u8]) -> Result<CsvIndex, CsvIndexError> {
+    if data.len() > u32::MAX as usize {
+        return Err(CsvIndexError::InputTooLarge);
+    }
+
+    let start = data.len() - strip_bom(data).len();
+    let (separators, unclosed) = find_separators(&data[start..]);
+
+    let mut field_ends = Vec::new();
+    let mut row_ends = Vec::new();
+
+    for (b, block) in separators.iter().enumerate() {
+        let base = (start + b * 64) as u32;
+        let mut bits = block.fields;
+        while bits != 0 {
+            let bit = bits.trailing_zeros();
+            field_ends.push(base + bit);
+            if (block.rows >> bit) & 1 != 0 {
+                row_ends.push(field_ends.len() as u32);
+            }
+            bits &= bits - 1;
+        }
+    }
+
+    // An unterminated last row still ends with a field (empty after a final
+    // delimiter), unless that is an unclosed quoted field
+    let last_row_end = row_ends.last().map(|&n| field_ends[n as usize - 1] as usize);
+    let terminated = last_row_end.is_some_and(|end| end + 1 == data.len());
+    if start < data.len() && !terminated && !unclosed {
+        field_ends.push(data.len() as u32);
+        row_ends.push(field_ends.len() as u32);
+    }
+
+    Ok(CsvIndex { field_ends, row_ends, start })
+}
+
+/// Count fields and rows with the two-pass index, like
+/// `csv_state_machine::parse_csv_if_else`.
+///
+/// # Panics
+/// If `data` is 4 GB or larger.
+pub fn parse_csv_index(data: &[u8]) -> (usize, usize) {
+    let index = build_index(data).expect("input larger than 4 GB");
+    (index.field_count(), index.row_count())
+}
+
+/// Count rows only: the terminators outside quotes, plus an unterminated
+/// last row (even one that is a lone empty field after a delimiter), as in
+/// [`build_index`].
+///
+/// Pass 1 without pass 2: nothing is stored and no field end is visited,
+/// a popcount per block does the counting. When only the row count is
+/// needed, this replaces a full parse: about 3 GB/s with AVX2, 2-3.5x
+/// [`parse_csv_index`] and 9x the DFA (`csv_state_machine_bench`).
+///
+/// # Example
+/// ```
+/// use scratchpad::csv_index::count_csv_rows;
+///
+/// assert_eq!(count_csv_rows(b"id,text\n1,\"multi\nline\"\n2,last"), 3);
+/// ```
+pub fn count_csv_rows(data: &[u8]) -> usize {
+    let body = strip_bom(data);
+    let mut rows = 0;
+    let mut prev_in_quotes = 0u64;
+    let mut terminators = 0u64;
+
+    for chunk in body.chunks(64) {
+        let block = if chunk.len() == 64 {
+            chunk.try_into().unwrap()
+        } else {
+            padded_block(chunk)
+        };
+
+        let masks = classify_block(&block);
+        let in_quotes = prefix_xor(masks.quotes) ^ prev_in_quotes;
+        prev_in_quotes = ((in_quotes as i64) >> 63) as u64;
+
+        terminators = masks.terminators & !in_quotes;
+        rows += terminators.count_ones() as usize;
+    }
+
+    // Anything after the last terminator is a row, unless it ends inside an
+    // unclosed quoted field
+    if !body.is_empty() && prev_in_quotes == 0 {
+        let last = (body.len() - 1) & 63;
+        rows += ((terminators >> last) & 1 == 0) as usize;
+    }
+
+    rows
+}
+
+/// The byte offset of every row start, quote-aware: the start of the input
+/// (after a BOM) and the byte after every terminator outside quotes, unless
+/// it is the end of the input.
+///
+/// Row `r` spans `offsets[r]..offsets[r + 1]` (its terminator included), the
+/// last one runs to the end of `data`. There is one offset per row of
+/// [`count_csv_rows`], with one exception: input that ends inside an unclosed
+/// quoted field has an offset for that broken record, which is not counted
+/// as a row but whose bytes still need a row to belong to.
+///
+/// Every offset is a place where a parser can start with no state, so the
+/// index also gives clean chunk boundaries for splitting a file: the row at
+/// or after a byte position is a binary search away.
+///
+/// # Example
+/// ```
+/// use scratchpad::csv_index::index_rows;
+///
+/// let csv = b"id,text\n1,\"two\nlines\"\n2,x\n";
+/// let offsets = index_rows(csv);
+/// assert_eq!(offsets, vec![0, 8, 22]);
+/// assert_eq!(&csv[offsets[1]..offsets[2]], b"1,\"two\nlines\"\n");
+///
+/// // Split in two at the first row boundary past the middle
+/// let middle = offsets.partition_point(|&offset| offset < csv.len() / 2);
+/// assert_eq!(offsets[middle], 22);
+/// ```
+pub fn index_rows(data: &[u8]) -> Vec<usize> {
+    let start = data.len() - strip_bom(data).len();
+    if start == data.len() {
+        return Vec::new();
+    }
+
+    let mut offsets = vec![start];
+    let mut prev_in_quotes = 0u64;
+
+    for (b, chunk) in data[start..].chunks(64).enumerate() {
+        let block = if chunk.len() == 64 {
+            chunk.try_into().unwrap()
+        } else {
+            padded_block(chunk)
+        };
+
+        let masks = classify_block(&block);
+        let in_quotes = prefix_xor(masks.quotes) ^ prev_in_quotes;
+        prev_in_quotes = ((in_quotes as i64) >> 63) as u64;
+
+        let mut rows = masks.terminators & !in_quotes;
+        let base = start + b * 64;
+        while rows != 0 {
+            offsets.push(base + rows.trailing_zeros() as usize + 1);
+            rows &= rows - 1;
+        }
+    }
+
+    // A final terminator starts no row
+    if offsets.last() == Some(&data.len()) {
+        offsets.pop();
+    }
+    offsets
+}
+
+/// Quote-aware record boundaries of `data`, classified lazily: a query only
+/// classifies the blocks up to the position asked about, and each block
+/// once over all queries.
+///
+/// For scanners that jump ahead with memchr and need to know which record
+/// a hit is in, without indexing the whole input first. Positions must be
+/// asked about in non-decreasing order. `data` is taken as is: strip a BOM
+/// before.
+///
+/// # Example
+/// ```
+/// use scratchpad::csv_index::RecordCursor;
+///
+/// let csv = b"id,text\n1,\"two\nlines\"\n2,x\n";
+/// let mut records = RecordCursor::new(csv);
+/// // The "lines" on the third line of the file belong to the second record
+/// assert_eq!(records.record_start(15), 8);
+/// assert_eq!(records.record_end(15), 21);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RecordCursor<'a> {
+    data: &'a [u8],
+    /// Start of the next block to classify
+    next_block: usize,
+    /// Terminators outside quotes in the last classified block
+    rows: u64,
+    prev_in_quotes: u64,
+    /// Start of the record holding the start of the last classified block
+    block_record_start: usize,
+}
+
+impl<'a> RecordCursor<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, next_block: 0, rows: 0, prev_in_quotes: 0, block_record_start: 0 }
+    }
+
+    /// Start of the record holding the byte at `pos`.
+    pub fn record_start(&mut self, pos: usize) -> usize {
+        self.classify_up_to(pos);
+        let before = self.rows & low_bits((pos + 64).saturating_sub(self.next_block));
+        match before {
+            0 => self.block_record_start,
+            _ => self.next_block - before.leading_zeros() as usize,
+        }
+    }
+
+    /// Position of the terminator ending the record holding the byte at
+    /// `pos`, or the end of `data` for an unterminated last record.
+    pub fn record_end(&mut self, pos: usize) -> usize {
+        self.classify_up_to(pos);
+        let mut after = self.rows & !low_bits((pos + 64).saturating_sub(self.next_block));
+        while after == 0 {
+            if !self.classify_next() {
+                return self.data.len();
+            }
+            after = self.rows;
+        }
+        self.next_block - 64 + after.trailing_zeros() as usize
+    }
+
+    /// Classify blocks until the one holding `pos` (or the last one).
+    fn classify_up_to(&mut self, pos: usize) {
+        while pos >= self.next_block && self.classify_next() {}
+    }
+
+    fn classify_next(&mut self) -> bool {
+        if self.next_block >= self.data.len() {
+            return false;
+        }
+        if self.rows != 0 {
+            self.block_record_start = self.next_block - self.rows.leading_zeros() as usize;
+        }
+
+        let chunk = &self.data[self.next_block..self.data.len().min(self.next_block + 64)];
+        let block = if chunk.len() == 64 {
+            chunk.try_into().unwrap()
+        } else {
+            padded_block(chunk)
+        };
+
+        let masks = classify_block(&block);
+        let in_quotes = prefix_xor(masks.quotes) ^ self.prev_in_quotes;
+        self.prev_in_quotes = ((in_quotes as i64) >> 63) as u64;
+        self.rows = masks.terminators & !in_quotes;
+        self.next_block += 64;
+        true
+    }
+}
+
+/// The `n` low bits set (all of them from 64 on).
+#[inline]
+fn low_bits(n: usize) -> u64 {
+    if n >= 64 {
+        u64::MAX
+    } else {
+        (1 << n) - 1
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                            Column Extraction
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Extraction needs no index: the two passes are fused block by block, and
+// the fields of other columns are skipped by clearing their bits. Nothing is
+// decoded except the fields asked for.
+
+/// Call `f(end, row_end)` for the end of every field of `data[start..]`, in
+/// order, with the same boundaries as [`build_index`].
+fn for_each_field_end<F: FnMut(usize, bool)>(data: &[u8], start: usize, mut f: F) {
+    let mut prev_in_quotes = 0u64;
+    let mut last_row_end = None;
+
+    for (b, chunk) in data[start..].chunks(64).enumerate() {
+        let block = if chunk.len() == 64 {
+            chunk.try_into().unwrap()
+        } else {
+            padded_block(chunk)
+        };
+
+        let masks = classify_block(&block);
+        let in_quotes = prefix_xor(masks.quotes) ^ prev_in_quotes;
+        prev_in_quotes = ((in_quotes as i64) >> 63) as u64;
+
+        let rows = masks.terminators & !in_quotes;
+        let mut fields = (masks.delimiters | masks.terminators) & !in_quotes;
+        let base = start + b * 64;
+        while fields != 0 {
+            let bit = fields.trailing_zeros();
+            f(base + bit as usize, (rows >> bit) & 1 != 0);
+            fields &= fields - 1;
+        }
+        if rows != 0 {
+            last_row_end = Some(base + 63 - rows.leading_zeros() as usize);
+        }
+    }
+
+    let terminated = last_row_end.is_some_and(|end| end + 1 == data.len());
+    if start < data.len() && !terminated && prev_in_quotes == 0 {
+        f(data.len(), true);
+    }
+}
+
+/// `field` without its enclosing quotes, if it has them. Doubled quotes
+/// inside are left as is.
+#[inline]
+fn unquote(field: &[u8]) -> &[u8] {
+    match field {
+        [QUOTE, inner @ .., QUOTE] => inner,
+        _ => field,
+    }
+}
+
+/// The values of column `column` (0-based), one per row, like
+/// [`extract_columns`] with a single column.
+///
+/// # Example
+/// ```
+/// use scratchpad::csv_index::extract_column;
+///
+/// let csv = b"name,city\nAnn,\"Paris, FR\"\nBob\nCid,Oslo\n";
+/// let cities = extract_column(csv, 1);
+/// assert_eq!(cities, vec![&b"city"[..], b"Paris, FR", b"", b"Oslo"]);
+/// ```
+pub fn extract_column(data: &[u8], column: usize) -> Vec<&[u8]> {
+    extract_columns(data, &[column]).pop().unwrap()
+}
+
+/// The values of several columns: `result[i]` holds column `columns[i]`, one
+/// value per row (the header included).
+///
+/// Values are slices of `data` with the enclosing quotes removed; doubled
+/// quotes inside are not unescaped (see [`unquote_field`]). A row too short for a column gives an
+/// empty value, so every column has one value per row.
+pub fn extract_columns<'a>(data: &'a [u8], columns: &[usize]) -> Vec<Vec<&'a [u8]>> {
+    let mut values: Vec<Vec<&[u8]>> = vec![Vec::new(); columns.len()];
+    let Some(&last_wanted) = columns.iter().max() else {
+        return values;
+    };
+
+    let start = data.len() - strip_bom(data).len();
+    let mut field_start = start;
+    let mut column = 0;
+    let mut row = 0;
+
+    for_each_field_end(data, start, |end, row_end| {
+        if column <= last_wanted {
+            for (out, &wanted) in values.iter_mut().zip(columns) {
+                if wanted == column {
+                    out.push(unquote(&data[field_start..end]));
+                }
+            }
+        }
+        field_start = end + 1;
+        column += 1;
+
+        if row_end {
+            row += 1;
+            for out in values.iter_mut().filter(|out| out.len() < row) {
+                out.push(&[]);
+            }
+            column = 0;
+        }
+    });
+
+    // A last row cut short by an unclosed quote still has its first fields
+    if column > 0 {
+        for out in values.iter_mut().filter(|out| out.len() <= row) {
+            out.push(&[]);
+        }
+    }
+
+    values
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                               Unquoting
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Position of the first `"` in `data`, 8 bytes at a time (SWAR).
+#[inline]
+fn find_quote_swar(data: &[u8]) -> Option<usize> {
+    let mut words = data.chunks_exact(8);
+    let mut offset = 0;
+    for word in words.by_ref() {
+        let mask = eq_mask_swar(u64::from_le_bytes(word.try_into().unwrap()), QUOTE);
+        if mask != 0 {
+            return Some(offset + (mask.trailing_zeros() / 8) as usize);
+        }
+        offset += 8;
+    }
+    words.remainder().iter().position(|&b| b == QUOTE).map(|p| offset + p)
+}
+
+/// Append the value of a raw field (as [`CsvIndex::field`] spans it) to `out`.
+///
+/// A field starting with `"` loses its enclosing quotes and has every `""`
+/// collapsed to `"`; the clean runs in between are found 8 bytes at a time
+/// and copied in bulk. Anything else is copied as is, since a `"` inside an
+/// unquoted field is data. Bytes after the closing quote are kept, as the
+/// DFA reads them, and a field left open at the end of the input runs to its
+/// end.
+///
+/// # Example
+/// ```
+/// use scratchpad::csv_index::{build_index, unquote_field};
+///
+/// let csv = b"id,quote\n1,\"say \"\"hi\"\", Ann\"\n";
+/// let index = build_index(csv).unwrap();
+///
+/// let mut value = Vec::new();
+/// unquote_field(&csv[index.field(3)], &mut value);
+/// assert_eq!(value, b"say \"hi\", Ann");
+/// ```
+pub fn unquote_field(raw: &[u8], out: &mut Vec<u8>) {
+    let Some((&QUOTE, mut rest)) = raw.split_first() else {
+        out.extend_from_slice(raw);
+        return;
+    };
+
+    while let Some(p) = find_quote_swar(rest) {
+        out.extend_from_slice(&rest[..p]);
+        if rest.get(p + 1) != Some(&QUOTE) {
+            // The closing quote
+            out.extend_from_slice(&rest[p + 1..]);
+            return;
+        }
+        out.push(QUOTE);
+        rest = &rest[p + 2..];
+    }
+    out.extend_from_slice(rest);
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                               Statistics
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Shape of a CSV document's fields and rows, from [`csv_stats`].
+///
+/// Lengths are in bytes as the fields appear in the input: enclosing quotes
+/// and doubled quotes included, terminators excluded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CsvStats {
+    pub rows: usize,
+    pub fields: usize,
+    /// Shortest field (0 if there are none).
+    pub min_field_len: usize,
+    pub max_field_len: usize,
+    /// Total length of all fields.
+    pub field_bytes: usize,
+    /// Fields starting with a quote.
+    pub quoted_fields: usize,
+    /// Longest row, delimiters included.
+    pub longest_row: usize,
+}
+
+impl CsvStats {
+    /// Mean field length (0 if there are no fields).
+    pub fn mean_field_len(&self) -> f64 {
+        if self.fields == 0 {
+            return 0.0;
+        }
+        self.field_bytes as f64 / self.fields as f64
+    }
+
+    /// Fraction of the fields that are quoted (0 if there are no fields).
+    pub fn quoted_ratio(&self) -> f64 {
+        if self.fields == 0 {
+            return 0.0;
+        }
+        self.quoted_fields as f64 / self.fields as f64
+    }
+
+    /// Write the statistics to `sink` as a single row: a column per field,
+    /// then the mean field length and the quoted ratio.
+    ///
+    /// # Example
+    /// ```
+    /// use scratchpad::csv_index::csv_stats;
+    /// use scratchpad::sink::CsvSink;
+    ///
+    /// let mut sink = CsvSink::new(Vec::new());
+    /// csv_stats(b"id,name\n1,\"Smith, J\"\n").write_to(&mut sink).unwrap();
+    /// let csv = String::from_utf8(sink.into_inner()).unwrap();
+    /// assert!(csv.starts_with("rows,fields,min_field_len,"));
+    /// assert!(csv.ends_with("\n2,4,1,10,17,1,12,4.25,0.25\n"));
+    /// ```
+    pub fn write_to(&self, sink: &mut impl Sink) -> io::Result<()> {
+        let int = |n: usize| Value::Int(n as i64);
+        sink.begin(&[
+            "rows",
+            "fields",
+            "min_field_len",
+            "max_field_len",
+            "field_bytes",
+            "quoted_fields",
+            "longest_row",
+            "mean_field_len",
+            "quoted_ratio",
+        ])?;
+        sink.row(&[
+            int(self.rows),
+            int(self.fields),
+            int(self.min_field_len),
+            int(self.max_field_len),
+            int(self.field_bytes),
+            int(self.quoted_fields),
+            int(self.longest_row),
+            Value::Float(self.mean_field_len()),
+            Value::Float(self.quoted_ratio()),
+        ])?;
+        sink.finish()
+    }
+}
+
+/// Profile the fields and rows of `data` in one pass over the structural
+/// masks, with the same boundaries as [`build_index`].
+///
+/// # Example
+/// ```
+/// use scratchpad::csv_index::csv_stats;
+///
+/// let stats = csv_stats(b"id,name\n1,\"Smith, J\"\n22,Ann\n");
+/// assert_eq!((stats.rows, stats.fields), (3, 6));
+/// assert_eq!((stats.min_field_len, stats.max_field_len), (1, 10));
+/// assert_eq!(stats.longest_row, 12);
+/// assert_eq!(stats.quoted_fields, 1);
+/// ```
+pub fn csv_stats(data: &[u8]) -> CsvStats {
+    let start = data.len() - strip_bom(data).len();
+    let mut stats = CsvStats { min_field_len: usize::MAX, ..CsvStats::default() };
+    let mut field_start = start;
+    let mut row_start = start;
+
+    for_each_field_end(data, start, |end, row_end| {
+        let len = end - field_start;
+        stats.fields += 1;
+        stats.field_bytes += len;
+        stats.min_field_len = stats.min_field_len.min(len);
+        stats.max_field_len = stats.max_field_len.max(len);
+        stats.quoted_fields += (data.get(field_start) == Some(&QUOTE)) as usize;
+        field_start = end + 1;
+
+        if row_end {
+            stats.rows += 1;
+            stats.longest_row = stats.longest_row.max(end - row_start);
+            row_start = end + 1;
+        }
+    });
+
+    if stats.fields == 0 {
+        stats.min_field_len = 0;
+    }
+    stats
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Parallel
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// A chunk cannot know whether it starts inside a quoted field without
+// scanning everything before it. It does not need to: it is counted under
+// both assumptions at once, since starting inside quotes just complements
+// its in_quotes mask. The quote parity of the chunks before it then picks
+// the right count.
+//
+//   chunks:        [ a,"b, ][ c",d\n  ][ e,f\n ]
+//   quotes odd:       yes      yes       no
+//   starts inside:    no       yes       no      (XOR of the parities before)
+
+/// Counts of one chunk, for both quote states at its start.
+#[derive(Debug, Clone, Copy, Default)]
+struct ChunkCounts {
+    /// Whether the chunk holds an odd number of quotes.
+    quotes_odd: bool,
+    /// Indexed by whether the chunk starts inside a quoted field.
+    fields: [usize; 2],
+    rows: [usize; 2],
+    last_terminator: [Option<usize>; 2],
+}
+
+/// Count the separators of `chunk` (at `base` in the input) under both
+/// assumptions.
+fn count_chunk(chunk: &[u8], base: usize) -> ChunkCounts {
+    let mut counts = ChunkCounts::default();
+    let mut prev_in_quotes = 0u64;
+
+    for (b, block) in chunk.chunks(64).enumerate() {
+        let block = if block.len() == 64 {
+            block.try_into().unwrap()
+        } else {
+            padded_block(block)
+        };
+
+        let masks = classify_block(&block);
+        let in_quotes = prefix_xor(masks.quotes) ^ prev_in_quotes;
+        prev_in_quotes = ((in_quotes as i64) >> 63) as u64;
+
+        for (inside, outside_quotes) in [!in_quotes, in_quotes].into_iter().enumerate() {
+            let fields = (masks.delimiters | masks.terminators) & outside_quotes;
+            let rows = masks.terminators & outside_quotes;
+            counts.fields[inside] += fields.count_ones() as usize;
+            counts.rows[inside] += rows.count_ones() as usize;
+            if rows != 0 {
+                let last = 63 - rows.leading_zeros() as usize;
+                counts.last_terminator[inside] = Some(base + b * 64 + last);
+            }
+        }
+    }
+
+    counts.quotes_odd = prev_in_quotes != 0;
+    counts
+}
+
+/// Count fields and rows on up to `threads` threads, like [`parse_csv_index`].
+///
+/// Unlike [`build_index`], there is no 4 GB limit: nothing is stored per
+/// field.
+///
+/// # Example
+/// ```
+/// use scratchpad::csv_index::{parse_csv_index, parse_csv_parallel};
+///
+/// let csv = b"id,text\n1,\"a,\nb\"\n".repeat(1000);
+/// assert_eq!(parse_csv_parallel(&csv, 4), (4000, 2000));
+/// assert_eq!(parse_csv_parallel(&csv, 4), parse_csv_index(&csv));
+/// ```
+pub fn parse_csv_parallel(data: &[u8], threads: usize) -> (usize, usize) {
+    let start = data.len() - strip_bom(data).len();
+    let body = &data[start..];
+
+    // Whole blocks per chunk, so every chunk classifies aligned blocks
+    let chunk_size = body.len().div_ceil(threads.max(1)).next_multiple_of(64).max(64);
+
+    let counts: Vec<ChunkCounts> = thread::scope(|s| {
+        let workers: Vec<_> = body
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(i, chunk)| s.spawn(move || count_chunk(chunk, start + i * chunk_size)))
+            .collect();
+        workers.into_iter().map(|w| w.join().unwrap()).collect()
+    });
+
+    // Reconcile in order: each chunk's start state is the parity so far
+    let mut inside = false;
+    let (mut fields, mut rows) = (0, 0);
+    let mut last_terminator = None;
+    for chunk in &counts {
+        let i = inside as usize;
+        fields += chunk.fields[i];
+        rows += chunk.rows[i];
+        last_terminator = chunk.last_terminator[i].or(last_terminator);
+        inside ^= chunk.quotes_odd;
+    }
+
+    // An unterminated last row, as in `build_index`
+    let terminated = last_terminator.is_some_and(|end| end + 1 == data.len());
+    if start < data.len() && !terminated && !inside {
+        fields += 1;
+        rows += 1;
+    }
+
+    (fields, rows)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_state_machine::{parse_csv_if_else, parse_csv_state_machine, tokenize};
+
+    fn classify_block_scalar(block: &[u8; 64]) -> BlockMasks {
+        let mut masks = BlockMasks::default();
+        for (i, &b) in block.iter().enumerate() {
+            masks.quotes |= ((b == QUOTE) as u64) << i;
+            masks.delimiters |= ((b == DELIMITER) as u64) << i;
+            masks.terminators |= ((b == TERMINATOR) as u64) << i;
+        }
+        masks
+    }
+
+    /// Well-formed CSV with quoted fields (delimiters, newlines and doubled
+    /// quotes inside) straddling block boundaries, after a header row.
+    fn random_csv(rows: usize, seed: u64) -> Vec<u8> {
+        let mut rng = seed;
+        let mut next = || {
+            rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+            rng >> 16
+        };
+
+        let mut data = b"id,name,notes\n".to_vec();
+        for _ in 0..rows {
+            for column in 0..1 + next() % 5 {
+                if column > 0 {
+                    data.push(b',');
+                }
+                match next() % 4 {
+                    0 => data.extend_from_slice(b"\"q,\"\"u\"\"\not\""),
+                    1 => {}
+                    _ => data.extend(std::iter::repeat_n(b'x', (next() % 40) as usize)),
+                }
+            }
+            data.push(b'\n');
+        }
+        data
+    }
+
+    #[test]
+    fn test_classify_matches_scalar() {
+        let alphabet = b"\",\nab\r";
+        let mut rng = 3u64;
+        for _ in 0..100 {
+            let block: [u8; 64] = std::array::from_fn(|_| {
+                rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+                alphabet[((rng >> 16) % alphabet.len() as u64) as usize]
+            });
+            let expected = classify_block_scalar(&block);
+            assert_eq!(classify_block_swar(&block), expected);
+            assert_eq!(classify_block(&block), expected);
+        }
+    }
+
+    #[test]
+    fn test_counts_match_parsers() {
+        let inputs: [&[u8]; 7] = [
+            b"a,b,c\n1,2,3\n",
+            b"\"hello\",\"world\"\n\"foo\",\"bar\"\n",
+            b"\"hello\nworld\",test\n",
+            b"\"hello\"\"world\",test\n",
+            b"a,,c\n,,\n",
+            b"a,b,c",
+            b"",
+        ];
+        for csv in inputs {
+            assert_eq!(parse_csv_index(csv), parse_csv_state_machine(csv), "{:?}", csv);
+            assert_eq!(parse_csv_index(csv), parse_csv_if_else(csv), "{:?}", csv);
+        }
+
+        // A blank line is a row of one empty field; a final delimiter ends an
+        // empty last field
+        for csv in [&b"\n\n"[..], b"a,", b"a,b\n,", b",a\n", b"\"x\","] {
+            assert_eq!(parse_csv_index(csv), parse_csv_state_machine(csv), "{:?}", csv);
+            assert_eq!(parse_csv_index(csv), parse_csv_if_else(csv), "{:?}", csv);
+            assert_eq!(parse_csv_parallel(csv, 2), parse_csv_state_machine(csv), "{:?}", csv);
+            assert_eq!(csv_stats(csv).rows, count_csv_rows(csv), "{:?}", csv);
+        }
+
+        for seed in 0..10 {
+            let csv = random_csv(200, seed);
+            assert_eq!(parse_csv_index(&csv), parse_csv_state_machine(&csv));
+            assert_eq!(parse_csv_index(&csv), parse_csv_if_else(&csv));
+        }
+    }
+
+    #[test]
+    fn test_fields_match_tokenize() {
+        let csv = random_csv(300, 11);
+        let index = build_index(&csv).unwrap();
+        let spans = tokenize(&csv);
+        assert_eq!(index.field_count(), spans.len());
+
+        for (i, span) in spans.iter().enumerate() {
+            // The index keeps the quotes
+            let quote = span.quoted as usize;
+            assert_eq!(index.field(i), span.range.start - quote..span.range.end + quote);
+        }
+        for r in 0..index.row_count() {
+            assert!(index.row(r).all(|i| spans[i].row == r));
+        }
+    }
+
+    #[test]
+    fn test_extract_columns_match_tokenize() {
+        let csv = random_csv(300, 5);
+        let spans = tokenize(&csv);
+        let rows = spans.last().unwrap().row + 1;
+
+        let columns = [0, 2, 4, 2, 9];
+        let values = extract_columns(&csv, &columns);
+        for (out, &column) in values.iter().zip(&columns) {
+            assert_eq!(out.len(), rows);
+            let mut expected = vec![&b""[..]; rows];
+            for span in spans.iter().filter(|s| s.column == column) {
+                expected[span.row] = &csv[span.range.clone()];
+            }
+            assert_eq!(*out, expected, "column {}", column);
+        }
+
+        assert_eq!(extract_column(&csv, 2), values[1]);
+        assert!(extract_columns(&csv, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_extract_column_edges() {
+        // BOM, unterminated last row, unclosed quote dropped
+        assert_eq!(extract_column(b"\xEF\xBB\xBFa,b\n1,2", 0), vec![&b"a"[..], b"1"]);
+        assert_eq!(extract_column(b"a,b\n1,\"open", 0), vec![&b"a"[..], b"1"]);
+        assert_eq!(extract_column(b"a,b\n1,\"open", 1), vec![&b"b"[..], b""]);
+        assert_eq!(extract_column(b"", 0), Vec::<&[u8]>::new());
+    }
+
+    #[test]
+    fn test_stats_match_index() {
+        let mut inputs = vec![random_csv(300, 11), b"\xEF\xBB\xBFa,\"b\"\n\n\"open,x".to_vec()];
+        inputs.extend([&b""[..], b"\n", b"a", b"\"\",\"x\"\"\"\n"].map(|s| s.to_vec()));
+
+        for data in &inputs {
+            let index = build_index(data).unwrap();
+            let lens: Vec<usize> = (0..index.field_count()).map(|i| index.field(i).len()).collect();
+            let quoted = (0..index.field_count())
+                .filter(|&i| data[index.field(i)].first() == Some(&QUOTE))
+                .count();
+            let longest_row = (0..index.row_count())
+                .map(|r| {
+                    let fields = index.row(r);
+                    index.field_ends[fields.end - 1] as usize - index.field(fields.start).start
+                })
+                .max()
+                .unwrap_or(0);
+
+            let stats = csv_stats(data);
+            assert_eq!((stats.fields, stats.rows), (index.field_count(), index.row_count()));
+            assert_eq!(stats.min_field_len, lens.iter().copied().min().unwrap_or(0));
+            assert_eq!(stats.max_field_len, lens.iter().copied().max().unwrap_or(0));
+            assert_eq!(stats.field_bytes, lens.iter().sum::<usize>());
+            assert_eq!(stats.quoted_fields, quoted);
+            assert_eq!(stats.longest_row, longest_row, "{:?}", String::from_utf8_lossy(data));
+        }
+
+        assert_eq!(csv_stats(b""), CsvStats::default());
+        assert_eq!(csv_stats(b"").mean_field_len(), 0.0);
+        let stats = csv_stats(b"a,\"b\"\nccc,dd\n");
+        assert_eq!((stats.mean_field_len(), stats.quoted_ratio()), (2.25, 0.25));
+    }
+
+    #[test]
+    fn test_count_rows() {
+        let inputs: [&[u8]; 9] = [
+            b"",
+            b"a,b,c",
+            b"a,b,c\n",
+            b"a\n,",
+            b"\n\n",
+            b"\"multi\nline\",x\n\"y\"",
+            b"\"unclosed\nx",
+            b"\xEF\xBB\xBF",
+            b"\xEF\xBB\xBFa\nb",
+        ];
+        for csv in inputs {
+            assert_eq!(count_csv_rows(csv), parse_csv_index(csv).1, "{:?}", csv);
+        }
+
+        for seed in 0..10 {
+            let csv = random_csv(200, seed);
+            for len in [csv.len(), csv.len() - 1, csv.len() / 2, 64, 63, 65] {
+                let prefix = &csv[..len];
+                assert_eq!(count_csv_rows(prefix), parse_csv_index(prefix).1, "{}", len);
+            }
+        }
+    }
+
+    #[test]
+    fn test_index_rows() {
+        assert_eq!(index_rows(b""), Vec::<usize>::new());
+        assert_eq!(index_rows(b"\xEF\xBB\xBF"), Vec::<usize>::new());
+        assert_eq!(index_rows(b"\xEF\xBB\xBFa\nb"), vec![3, 5]);
+        assert_eq!(index_rows(b"\n\n"), vec![0, 1]);
+        assert_eq!(index_rows(b"\"a\nb\"\n\"c\""), vec![0, 6]);
+
+        for seed in 0..10 {
+            let csv = random_csv(200, seed);
+            let offsets = index_rows(&csv);
+            assert_eq!(offsets.len(), count_csv_rows(&csv));
+            assert_eq!(index_rows(&csv[..csv.len() - 1]), offsets);
+
+            // Each row's first field starts at its offset
+            let index = build_index(&csv).unwrap();
+            for (r, &offset) in offsets.iter().enumerate() {
+                let first = index.row(r).next().unwrap();
+                assert_eq!(index.field(first).start, offset, "row {}", r);
+            }
+        }
+    }
+
+    #[test]
+    fn test_index_rows_len_matches_count() {
+        let inputs: [&[u8]; 9] =
+            [b"", b"\xEF\xBB\xBF", b"a", b"a,", b",", b"\n", b"a,b\n,", b"\n\n", b"\"a\nb\","];
+        for csv in inputs {
+            assert_eq!(index_rows(csv).len(), count_csv_rows(csv), "{:?}", csv);
+        }
+
+        // Every prefix: only an unclosed quoted field at the end has an
+        // offset but no row
+        let csv = random_csv(20, 7);
+        for end in 0..=csv.len() {
+            let prefix = &csv[..end];
+            let unclosed = prefix.iter().filter(|&&b| b == b'"').count() % 2 == 1;
+            assert_eq!(
+                index_rows(prefix).len(),
+                count_csv_rows(prefix) + unclosed as usize,
+                "{:?}",
+                String::from_utf8_lossy(prefix)
+            );
+        }
+    }
+
+    #[test]
+    fn test_record_cursor() {
+        for seed in 0..10 {
+            let csv = random_csv(200, seed);
+            let offsets = index_rows(&csv);
+            let mut records = RecordCursor::new(&csv);
+            // Every third byte, in order: the record around it is the one
+            // index_rows puts it in
+            for pos in (0..csv.len()).step_by(3) {
+                let r = offsets.partition_point(|&offset| offset <= pos) - 1;
+                let last_end = csv.len() - csv.ends_with(b"\n") as usize;
+                let end = offsets.get(r + 1).map_or(last_end, |next| next - 1);
+                assert_eq!(records.record_start(pos), offsets[r], "seed {} pos {}", seed, pos);
+                assert_eq!(records.record_end(pos), end, "seed {} pos {}", seed, pos);
+            }
+        }
+
+        let mut records = RecordCursor::new(b"");
+        assert_eq!((records.record_start(0), records.record_end(0)), (0, 0));
+        // A record longer than a block, quoted terminators included
+        let mut long = b"a\n\"".to_vec();
+        long.extend(std::iter::repeat_n(b'\n', 200));
+        long.extend_from_slice(b"\"\nz");
+        let mut records = RecordCursor::new(&long);
+        assert_eq!(records.record_start(1), 0);
+        assert_eq!(records.record_end(100), 204);
+        assert_eq!(records.record_start(204), 2);
+        assert_eq!((records.record_start(205), records.record_end(205)), (205, 206));
+    }
+
+    #[test]
+    fn test_unquote_field() {
+        let cases: [(&[u8], &[u8]); 9] = [
+            (b"", b""),
+            (b"plain", b"plain"),
+            (b"a\"\"b", b"a\"\"b"),
+            (b"\"\"", b""),
+            (b"\"\"\"\"", b"\""),
+            (b"\"a,\nb\"", b"a,\nb"),
+            (b"\"0123456789 \"\"quoted\"\" abcdef\"", b"0123456789 \"quoted\" abcdef"),
+            (b"\"closed\"junk", b"closedjunk"),
+            (b"\"unclosed\"\"", b"unclosed\""),
+        ];
+        for (raw, expected) in cases {
+            let mut out = b"prefix".to_vec();
+            unquote_field(raw, &mut out);
+            assert_eq!(&out[6..], expected, "Mismatch for {:?}", raw);
+        }
+
+        // Round trip through the writer, quotes at every offset of a word
+        for len in 0..40 {
+            for at in 0..len {
+                let mut field = vec![b'x'; len];
+                field[at] = b'"';
+                let mut raw = Vec::new();
+                crate::csv_write::write_field(&field, &mut raw);
+                let mut out = Vec::new();
+                unquote_field(&raw, &mut out);
+                assert_eq!(out, field, "{}, {}", len, at);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parallel_matches_index() {
+        let mut inputs = vec![
+            b"a,b\n\"unterminated,x\n".to_vec(),
+            b"\xEF\xBB\xBFname,age\nAnn,30".to_vec(),
+            b"a,b,c".to_vec(),
+            Vec::new(),
+        ];
+        inputs.extend((0..5).map(|seed| random_csv(300, seed)));
+
+        for csv in &inputs {
+            for threads in [0, 1, 2, 3, 7, 16, 1000] {
+                assert_eq!(parse_csv_parallel(csv, threads), parse_csv_index(csv), "{}", threads);
+            }
+        }
+    }
+
+    #[test]
+    fn test_unclosed_quote_and_bom() {
+        // Dropped, as by the DFA
+        assert_eq!(parse_csv_index(b"a,b\n\"unterminated,x\n"), (2, 1));
+        assert_eq!(
+            parse_csv_index(b"a,b\n\"unterminated,x\n"),
+            parse_csv_state_machine(b"a,b\n\"unterminated,x\n")
+        );
+
+        let csv = b"\xEF\xBB\xBFname,age\nAnn,30";
+        let index = build_index(csv).unwrap();
+        assert_eq!(&csv[index.field(0)], b"name");
+        assert_eq!(&csv[index.field(3)], b"30");
+        assert_eq!(parse_csv_index(csv), (4, 2));
+    }
+}
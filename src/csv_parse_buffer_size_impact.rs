@@ -3,144 +3,1562 @@
 //! Based on: https://lemire.me/blog/2024/10/17/how-fast-can-you-parse-a-csv-file-in-c/
 //!
 //! This implementation matches the blog post's approach:
-//! - Read CSV files from disk using 4KB fixed buffers
+//! - Read CSV files from disk using 4KB fixed buffers (now sized from the L1
+//!   data cache by [`optimal_buffer_size`]: 16 KB with a 48 KB L1d)
 //! - Use memchr (like Array.IndexOf) to find the first byte of the pattern
 //! - Check if remaining bytes match
 //! - Handle patterns spanning buffer boundaries
 //!
 //! Key insight: Using memchr to jump to candidates is 12x faster than parsing CSV fields.
+//! The candidates now come from the first+last byte SIMD filter of
+//! [`needle`](crate::needle), which skips the positions where only the first
+//! byte matches: memchr stops at every `M` when looking for `MIT`.
+//! `needle_bench` measures both against Boyer-Moore-Horspool and Two-Way:
+//! memchr + compare loses to Horspool only on long needles and tiny
+//! alphabets, and the filter beats both on every input tried.
+//!
+//! The buffered search takes any `Read` source
+//! ([`count_pattern_matches_in_reader`]): stdin, pipes and sockets count the
+//! same way as files.
+//! [`count_pattern_matches_parallel`] splits a file at line starts and scans
+//! the parts on separate threads.
+//! With the `uring` feature on Linux, `count_pattern_matches_uring` keeps
+//! several reads in flight through io_uring while scanning.
+//! [`count_pattern_matches_uncached`] reads around the page cache, for cold
+//! benchmarks and one-off scans of huge files.
+//! [`count_pattern_matches_advised`] first hints the access pattern to the
+//! kernel with a [`ReadStrategy`].
+//! [`count_pattern_matches_prefetch`] reads the next chunk on another thread
+//! while the current one is searched.
+//! [`count_pattern_matches_progress`] reports how many bytes are done after
+//! every read, for progress bars on long scans.
+//! The `count_compiled_matches_` functions take a [`Pattern`] prepared once,
+//! for the same needle searched in many files or chunks, as
+//! [`scan`](crate::scan) does over file lists and directory trees. Read
+//! buffers are borrowed from [`ScratchPool::global`] and returned after each
+//! call.
+//!
+//! With the `mmap` feature, `count_pattern_matches_mmap` runs the in-memory
+//! search over a memory-mapped file instead of a copy of it. With the `tokio`
+//! feature, `count_pattern_matches_async` reads through `tokio::fs` without
+//! blocking the runtime.
+//!
+//! [`find_pattern_matches`] runs the same search but returns the line number,
+//! file offset and column of each matching line instead of counting them.
+//! [`for_each_matching_line`] streams the matching lines themselves to a
+//! closure, reassembled when a buffer boundary cuts them;
+//! [`collect_matching_lines`] returns them, all or the first few.
+//! [`count_pattern_occurrences`] counts every match instead of every
+//! matching line, for frequency analysis. [`count_pattern_matches_limited`]
+//! stops after a given number of matching lines and reports how much of the
+//! input it searched: whether a 2.5 GB file mentions Harvard at all takes
+//! one hit, not a full scan. [`count_values_in_column`] counts the lines per
+//! value of a column instead: the rows of each university, not just
+//! Harvard's.
+//!
+//! By default a pattern matches anywhere in a line, so `MIT` also counts the
+//! `SMITH` rows; the `_mode` variants take [`MatchMode::WholeField`] to only
+//! accept matches bounded by the dialect's delimiter or a line boundary, and
+//! [`count_pattern_matches_anchored`] takes [`MatchAnchor::LineStart`] to
+//! only count the lines starting with the pattern.
+//!
+//! Lines are not records when quoted fields span several of them:
+//! [`count_record_matches`] tracks quote parity with the SIMD quote mask of
+//! `csv_index` and counts each matching CSV record once.
+//!
+//! A UTF-8 BOM at the start of the file (Excel exports) is skipped by default;
+//! the `_with` variants take a [`Dialect`] to keep it or to count lines ending
+//! in another terminator. Only the terminator matters for substring counting, so
+//! [`Dialect::TSV`] exports and plain log files go through the same path as
+//! CSV, and `\r`-terminated files just need `terminator: b'\r'`.
 //!
 //! WARNING: This prioritizes speed over correctness. Does NOT handle:
 //! - Quoted fields with embedded newlines
 //! - Escaped quotes
 //! - Multi-byte encodings
 
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{self, Read};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::{ControlFlow, Range};
+use std::thread;
+
+use crate::cpu_info::optimal_buffer_size;
+use crate::csv_dialect::{Dialect, UTF8_BOM};
+use crate::csv_index::RecordCursor;
+use crate::needle::Pattern;
+use crate::readahead::ReadStrategy;
+use crate::scratch::ScratchPool;
+use crate::sink::{Sink, Value};
+use crate::sparse::for_each_data_segment;
+
+/// Read size of [`count_pattern_matches_async`]: large enough that the hop
+/// to tokio's blocking pool on every read stays small next to the scan.
+#[cfg(feature = "tokio")]
+pub const ASYNC_BUFFER_SIZE: usize = 256 * 1024;
+
+/// Count lines containing a pattern by reading from disk with buffers of
+/// [`optimal_buffer_size`] (4 KB in the blog post).
+///
+/// Matches the blog post's C# implementation:
+/// ```csharp
+/// i = Array.IndexOf(buffer, (byte)harvardBytes[0], i, ...);
+/// if (region.SequenceEqual(tailbytes)) { ... }
+/// ```
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::csv_parse_buffer_size_impact::count_pattern_matches_from_file;
+///
+/// let count = count_pattern_matches_from_file("researchers.csv", b"Harvard")
+///     .expect("Failed to read file");
+/// println!("Found {} matching lines", count);
+/// ```
+pub fn count_pattern_matches_from_file(
+    file_path: &str,
+    pattern: &[u8],
+) -> io::Result<usize> {
+    count_pattern_matches_from_file_with(file_path, pattern, &Dialect::CSV)
+}
+
+/// [`count_pattern_matches_from_file`] with the BOM handling and line
+/// terminator of `dialect` (its other fields are not used).
+pub fn count_pattern_matches_from_file_with(
+    file_path: &str,
+    pattern: &[u8],
+    dialect: &Dialect,
+) -> io::Result<usize> {
+    count_compiled_matches_from_file(file_path, &Pattern::new(pattern), dialect)
+}
+
+/// [`count_pattern_matches_from_file_with`] for a [`Pattern`] prepared once:
+/// scanning many files for the same needle skips preparing it for each.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::csv_dialect::Dialect;
+/// use scratchpad::csv_parse_buffer_size_impact::count_compiled_matches_from_file;
+/// use scratchpad::needle::Pattern;
+///
+/// let pattern = Pattern::new(b"Harvard");
+/// let mut total = 0;
+/// for day in 1..=31 {
+///     let path = format!("logs/2024-01-{:02}.csv", day);
+///     total += count_compiled_matches_from_file(&path, &pattern, &Dialect::CSV)
+///         .expect("Failed to read file");
+/// }
+/// ```
+pub fn count_compiled_matches_from_file(
+    file_path: &str,
+    pattern: &Pattern,
+    dialect: &Dialect,
+) -> io::Result<usize> {
+    count_compiled_matches_in_reader(File::open(file_path)?, pattern, dialect)
+}
+
+/// Count lines containing a pattern read from any source: stdin, a pipe, a
+/// socket, a decompressor, a generated stream.
+///
+/// The search behind [`count_pattern_matches_from_file`], which only opens
+/// the file. Reads in buffers of [`optimal_buffer_size`]; pass the reader by
+/// value or as `&mut`.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::csv_parse_buffer_size_impact::count_pattern_matches_in_reader;
+///
+/// // zcat access.log.gz | example
+/// let count = count_pattern_matches_in_reader(std::io::stdin().lock(), b"ERROR")
+///     .expect("Failed to read stdin");
+/// println!("Found {} matching lines", count);
+/// ```
+pub fn count_pattern_matches_in_reader<R: Read>(reader: R, pattern: &[u8]) -> io::Result<usize> {
+    count_pattern_matches_in_reader_with(reader, pattern, &Dialect::CSV)
+}
+
+/// [`count_pattern_matches_in_reader`] with the BOM handling and line
+/// terminator of `dialect`.
+pub fn count_pattern_matches_in_reader_with<R: Read>(
+    reader: R,
+    pattern: &[u8],
+    dialect: &Dialect,
+) -> io::Result<usize> {
+    count_compiled_matches_in_reader(reader, &Pattern::new(pattern), dialect)
+}
+
+/// [`count_pattern_matches_in_reader_with`] for a [`Pattern`] prepared once.
+pub fn count_compiled_matches_in_reader<R: Read>(
+    mut reader: R,
+    pattern: &Pattern,
+    dialect: &Dialect,
+) -> io::Result<usize> {
+    if pattern.is_empty() {
+        return Ok(0);
+    }
+
+    count_in_reader(&mut reader, pattern, dialect, dialect.skip_bom)
+}
+
+/// A count that may have stopped early, from the `_limited` functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LimitedCount {
+    /// Matching lines found: all of them, or the limit if it was reached.
+    pub count: usize,
+    /// Bytes of the input searched, a leading BOM included: up to the end of
+    /// the match that reached the limit, else the whole input.
+    pub bytes_examined: u64,
+}
+
+/// Count lines containing a pattern, stopping at the `limit`-th: with
+/// `Some(1)`, "does this file mention Harvard at all" returns at the first
+/// hit instead of reading the rest of the file. `None` counts them all.
+///
+/// The result also says how far the search went; reads happen a buffer at a
+/// time, so up to [`optimal_buffer_size`] bytes more may have been read.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::csv_parse_buffer_size_impact::count_pattern_matches_limited;
+///
+/// let found = count_pattern_matches_limited("huge.csv", b"Harvard", Some(1))
+///     .expect("Failed to read file");
+/// if found.count > 0 {
+///     println!("Harvard by byte {}", found.bytes_examined);
+/// }
+/// ```
+pub fn count_pattern_matches_limited(
+    file_path: &str,
+    pattern: &[u8],
+    limit: Option<usize>,
+) -> io::Result<LimitedCount> {
+    count_pattern_matches_limited_with(file_path, pattern, &Dialect::CSV, limit)
+}
+
+/// [`count_pattern_matches_limited`] with the BOM handling and line
+/// terminator of `dialect`.
+pub fn count_pattern_matches_limited_with(
+    file_path: &str,
+    pattern: &[u8],
+    dialect: &Dialect,
+    limit: Option<usize>,
+) -> io::Result<LimitedCount> {
+    let file = File::open(file_path)?;
+    count_compiled_matches_in_reader_limited(file, &Pattern::new(pattern), dialect, limit)
+}
+
+/// [`count_pattern_matches_limited_with`] over any reader, for a [`Pattern`]
+/// prepared once.
+pub fn count_compiled_matches_in_reader_limited<R: Read>(
+    mut reader: R,
+    pattern: &Pattern,
+    dialect: &Dialect,
+    limit: Option<usize>,
+) -> io::Result<LimitedCount> {
+    let limit = limit.unwrap_or(usize::MAX);
+    if pattern.is_empty() || limit == 0 {
+        return Ok(LimitedCount::default());
+    }
+
+    count_in_reader_limited(&mut reader, pattern, dialect, dialect.skip_bom, limit)
+}
+
+/// Count lines containing a pattern with `threads` threads, each scanning its
+/// own part of the file with the buffered memchr search.
+///
+/// The file is cut into `threads` byte ranges of about the same size, each
+/// boundary moved forward to the next line start so that every line belongs
+/// to exactly one range. The boundaries are found with a short read around
+/// each cut; then every thread opens the file, seeks to its range and counts
+/// it as [`count_pattern_matches_from_file`] does, and the counts are summed.
+///
+/// Pays off once the file is in the page cache or on storage with more
+/// bandwidth than one core can scan (NVMe). With a single thread it only adds
+/// a thread spawn and a seek: `large_file_bench` on one core measures 0.8-1.0x
+/// the plain scan (1.13 vs 1.44 GB/s on the 2.5 GB file). A line longer than
+/// a range merges the ranges it covers, so there may be fewer threads than
+/// asked for.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::csv_parse_buffer_size_impact::count_pattern_matches_parallel;
+///
+/// let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+/// let count = count_pattern_matches_parallel("researchers.csv", b"Harvard", threads)
+///     .expect("Failed to read file");
+/// ```
+pub fn count_pattern_matches_parallel(
+    file_path: &str,
+    pattern: &[u8],
+    threads: usize,
+) -> io::Result<usize> {
+    count_pattern_matches_parallel_with(file_path, pattern, threads, &Dialect::CSV)
+}
+
+/// [`count_pattern_matches_parallel`] with the BOM handling and line
+/// terminator of `dialect`.
+pub fn count_pattern_matches_parallel_with(
+    file_path: &str,
+    pattern: &[u8],
+    threads: usize,
+    dialect: &Dialect,
+) -> io::Result<usize> {
+    if pattern.is_empty() {
+        return Ok(0);
+    }
+
+    let pattern = &Pattern::new(pattern);
+    let mut file = File::open(file_path)?;
+    let len = file.metadata()?.len();
+    let threads = threads.max(1) as u64;
+
+    let mut bounds = vec![0];
+    for k in 1..threads {
+        let cut = len * k / threads;
+        let start = next_line_start(&mut file, cut, dialect.terminator)?;
+        if start > *bounds.last().unwrap() && start < len {
+            bounds.push(start);
+        }
+    }
+    bounds.push(len);
+
+    thread::scope(|s| {
+        let workers: Vec<_> = bounds
+            .windows(2)
+            .map(|range| {
+                let (start, end) = (range[0], range[1]);
+                s.spawn(move || {
+                    let mut file = File::open(file_path)?;
+                    file.seek(SeekFrom::Start(start))?;
+                    // Only the first range can start with the file's BOM
+                    let skip_bom = start == 0 && dialect.skip_bom;
+                    count_in_reader(&mut file.take(end - start), pattern, dialect, skip_bom)
+                })
+            })
+            .collect();
+        workers.into_iter().map(|w| w.join().unwrap()).sum()
+    })
+}
+
+/// Position of the first line start at or after `pos`: `pos` itself if the
+/// byte before it ends a line, else the byte after the next terminator (or
+/// the end of the file).
+fn next_line_start(file: &mut File, pos: u64, terminator: u8) -> io::Result<u64> {
+    if pos == 0 {
+        return Ok(0);
+    }
+
+    let mut buffer = [0u8; 256];
+    let mut offset = pos - 1;
+    file.seek(SeekFrom::Start(offset))?;
+    loop {
+        let n = match file.read(&mut buffer) {
+            Ok(0) => return Ok(offset),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if let Some(i) = memchr::memchr(terminator, &buffer[..n]) {
+            return Ok(offset + i as u64 + 1);
+        }
+        offset += n as u64;
+    }
+}
+
+/// Count lines containing a pattern, skipping the holes of a sparse file.
+///
+/// Pre-allocated files (log files created with `fallocate`/`truncate`) can be
+/// mostly holes. Reading a hole returns zeros without touching the disk, but
+/// the scanner still has to chew through them. Here only the data ranges
+/// reported by `SEEK_DATA`/`SEEK_HOLE` are read; each one is scanned
+/// independently, so a match never spans a hole.
+///
+/// Returns the match count and the skipped hole ranges.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::csv_parse_buffer_size_impact::count_pattern_matches_from_sparse_file;
+///
+/// let (count, holes) = count_pattern_matches_from_sparse_file("prealloc.log", b"ERROR")
+///     .expect("Failed to read file");
+/// let skipped: u64 = holes.iter().map(|h| h.end - h.start).sum();
+/// println!("Found {} matching lines, skipped {} bytes of holes", count, skipped);
+/// ```
+pub fn count_pattern_matches_from_sparse_file(
+    file_path: &str,
+    pattern: &[u8],
+) -> io::Result<(usize, Vec<Range<u64>>)> {
+    count_pattern_matches_from_sparse_file_with(file_path, pattern, &Dialect::CSV)
+}
+
+/// [`count_pattern_matches_from_sparse_file`] with the BOM handling and line
+/// terminator of `dialect` (its other fields are not used).
+pub fn count_pattern_matches_from_sparse_file_with(
+    file_path: &str,
+    pattern: &[u8],
+    dialect: &Dialect,
+) -> io::Result<(usize, Vec<Range<u64>>)> {
+    let pattern = Pattern::new(pattern);
+    let mut file = File::open(file_path)?;
+    let mut line_count = 0;
+    let mut first_segment = true;
+
+    let holes = for_each_data_segment(&mut file, |segment| {
+        if !pattern.is_empty() {
+            // Only the first segment can start with the file's BOM
+            let skip_bom = first_segment && dialect.skip_bom;
+            line_count += count_in_reader(segment, &pattern, dialect, skip_bom)?;
+        }
+        first_segment = false;
+        Ok(())
+    })?;
+
+    Ok((line_count, holes))
+}
+
+/// Count the lines of `reader` containing `pattern` (not empty).
+fn count_in_reader<R: Read>(
+    reader: &mut R,
+    pattern: &Pattern,
+    dialect: &Dialect,
+    skip_bom: bool,
+) -> io::Result<usize> {
+    Ok(count_in_reader_limited(reader, pattern, dialect, skip_bom, usize::MAX)?.count)
+}
+
+/// [`count_in_reader`], stopping at the match of the `limit`-th line (not 0).
+fn count_in_reader_limited<R: Read>(
+    reader: &mut R,
+    pattern: &Pattern,
+    dialect: &Dialect,
+    skip_bom: bool,
+    limit: usize,
+) -> io::Result<LimitedCount> {
+    let mut buffer = ScratchPool::global().take(optimal_buffer_size());
+    let mut scan = LineScan::new(pattern, dialect, limit);
+    let mut offset = 0;
+    // Position of `buffer[0]` in the input
+    let mut base = 0;
+    // Bytes already in the buffer that have not been scanned yet
+    let mut unscanned = false;
+
+    if skip_bom {
+        let bom_len;
+        (offset, bom_len) = read_past_bom(reader, &mut buffer)?;
+        base = bom_len as u64;
+        unscanned = offset > 0;
+    }
+
+    loop {
+        // Stop at EOF even if a partial pattern is still carried over
+        let n = reader.read(&mut buffer[offset..])?;
+        if n == 0 && !unscanned {
+            break;
+        }
+        let bytes_read = n + offset;
+        unscanned = false;
+
+        match scan.scan(&mut buffer, bytes_read) {
+            ControlFlow::Continue(carried) => offset = carried,
+            ControlFlow::Break(end) => {
+                let bytes_examined = base + end as u64;
+                return Ok(LimitedCount { count: scan.line_count, bytes_examined });
+            }
+        }
+        base += (bytes_read - offset) as u64;
+    }
+
+    Ok(LimitedCount { count: scan.line_count, bytes_examined: base + offset as u64 })
+}
+
+/// The state the buffered search carries from one buffer to the next,
+/// whatever fills the buffers: a `Read` here, a tokio file in
+/// `count_pattern_matches_async`.
+struct LineScan<'a> {
+    pattern: &'a Pattern,
+    terminator: u8,
+    /// Stop at the match of this many lines
+    limit: usize,
+    line_count: usize,
+    /// A counted line runs past the end of the buffer
+    in_counted_line: bool,
+}
+
+impl<'a> LineScan<'a> {
+    fn new(pattern: &'a Pattern, dialect: &Dialect, limit: usize) -> Self {
+        let terminator = dialect.terminator;
+        Self { pattern, terminator, limit, line_count: 0, in_counted_line: false }
+    }
+
+    /// Count the matching lines of `buffer[..filled]`, then move a pattern
+    /// start cut by its end to the front. Continues with the number of bytes
+    /// moved (where the next read goes), or breaks with the end of the match
+    /// that reached the limit.
+    fn scan(&mut self, buffer: &mut [u8], filled: usize) -> ControlFlow<usize, usize> {
+        let (pattern, terminator) = (self.pattern, self.terminator);
+
+        // Search for pattern in current buffer
+        let mut i = 0;
+        if self.in_counted_line {
+            (i, self.in_counted_line) = skip_line(&buffer[..filled], 0, terminator);
+        }
+        // Candidates filtered on the first and last byte, then compared
+        // (a short read can hold less than one pattern)
+        while i < filled {
+            match pattern.find(&buffer[i..filled]) {
+                None => break,
+                Some(pos) => {
+                    self.line_count += 1;
+                    if self.line_count == self.limit {
+                        return ControlFlow::Break(i + pos + pattern.len());
+                    }
+
+                    // Skip to end of line to avoid double-counting
+                    (i, self.in_counted_line) = skip_line(&buffer[..filled], i + pos, terminator);
+                }
+            }
+        }
+
+        // Handle pattern spanning buffer boundary (not inside a counted line)
+        for i in filled.saturating_sub(pattern.len() - 1).max(i)..filled {
+            if pattern.as_bytes().starts_with(&buffer[i..filled]) {
+                buffer.copy_within(i..filled, 0);
+                return ControlFlow::Continue(filled - i);
+            }
+        }
+        ControlFlow::Continue(0)
+    }
+}
+
+/// Position after the terminator ending the line at `from`, and whether the
+/// line runs past the end of `buffer` instead.
+#[inline]
+fn skip_line(buffer: &[u8], from: usize, terminator: u8) -> (usize, bool) {
+    match memchr::memchr(terminator, &buffer[from..]) {
+        Some(pos) => (from + pos + 1, false),
+        None => (buffer.len(), true),
+    }
+}
+
+/// Read the first bytes of `reader` into `buffer`, dropping a leading BOM.
+///
+/// Reads until there are enough bytes to tell (or EOF) and returns how many
+/// bytes are left at the start of `buffer`, and how many were dropped.
+fn read_past_bom<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<(usize, usize)> {
+    let mut filled = 0;
+    while filled < UTF8_BOM.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(drop_bom(buffer, filled))
+}
+
+/// Drop a BOM at the start of `buffer[..filled]`: the bytes left, and the
+/// bytes dropped.
+fn drop_bom(buffer: &mut [u8], filled: usize) -> (usize, usize) {
+    if buffer[..filled].starts_with(UTF8_BOM) {
+        buffer.copy_within(UTF8_BOM.len()..filled, 0);
+        return (filled - UTF8_BOM.len(), UTF8_BOM.len());
+    }
+    (filled, 0)
+}
+
+/// Feed `reader` to `f` one filled buffer at a time, until EOF or `f` breaks.
+///
+/// The buffered-read loop shared by scanners that do not need to carry bytes
+/// over buffer boundaries (single-byte searches). `buffer_size` is the knob
+/// measured by the buffer size benches: 4 KB as in the blog post, 64 KB to
+/// fill L1.
+pub fn for_each_buffer<R, F>(reader: &mut R, buffer_size: usize, mut f: F) -> io::Result<()>
+where
+    R: Read,
+    F: FnMut(&[u8]) -> ControlFlow<()>,
+{
+    let mut buffer = ScratchPool::global().take(buffer_size);
+
+    loop {
+        let bytes_read = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if f(&buffer[..bytes_read]).is_break() {
+            return Ok(());
+        }
+    }
+}
+
+/// Hand every line containing a pattern to `f`, without its terminator.
+///
+/// Reads in the buffers of [`count_pattern_matches_from_file`], but a
+/// matching line cut by a buffer boundary is reassembled before `f` sees it:
+/// the incomplete last line of a buffer is moved to the front for the next
+/// read, and the buffer doubles when a single line does not fit. Lines are
+/// only searched once complete, so `f` gets each matching line once, in file
+/// order, and can extract or aggregate fields without a second pass.
+///
+/// Lines of a `\r\n` file keep their `\r`.
+///
+/// # Example
+/// ```no_run
+/// use std::collections::HashMap;
+/// use scratchpad::csv_parse_buffer_size_impact::for_each_matching_line;
+///
+/// let mut per_year: HashMap<Vec<u8>, usize> = HashMap::new();
+/// for_each_matching_line("researchers.csv", b"Harvard", |line| {
+///     let year = line.split(|&b| b == b',').nth(2).unwrap_or_default();
+///     *per_year.entry(year.to_vec()).or_default() += 1;
+/// })
+/// .expect("Failed to read file");
+/// ```
+pub fn for_each_matching_line<F: FnMut(&[u8])>(
+    file_path: &str,
+    pattern: &[u8],
+    f: F,
+) -> io::Result<()> {
+    for_each_matching_line_with(file_path, pattern, &Dialect::CSV, f)
+}
+
+/// [`for_each_matching_line`] with the BOM handling and line terminator of
+/// `dialect`.
+pub fn for_each_matching_line_with<F: FnMut(&[u8])>(
+    file_path: &str,
+    pattern: &[u8],
+    dialect: &Dialect,
+    mut f: F,
+) -> io::Result<()> {
+    try_for_each_matching_line(file_path, pattern, dialect, |line| {
+        f(line);
+        ControlFlow::Continue(())
+    })
+}
+
+/// Collect the lines containing a pattern, without their terminators.
+///
+/// Each line is complete, however long it is and wherever the buffer
+/// boundaries fall: this is [`for_each_matching_line`] with the lines
+/// copied out. The count-only search keeps no more than a pattern's worth of
+/// bytes from one buffer to the next, so it cannot give the lines back.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::csv_parse_buffer_size_impact::collect_matching_lines;
+///
+/// for line in collect_matching_lines("app.log", b"ERROR").expect("Failed to read file") {
+///     println!("{}", String::from_utf8_lossy(&line));
+/// }
+/// ```
+pub fn collect_matching_lines(file_path: &str, pattern: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+    collect_matching_lines_with(file_path, pattern, &Dialect::CSV, None)
+}
+
+/// [`collect_matching_lines`] with the BOM handling and line terminator of
+/// `dialect`, stopping after `limit` lines (`None` collects them all).
+///
+/// With a limit, reading stops at the buffer holding the last line wanted:
+/// the first ten errors of a huge log cost a few buffers. For a bound on
+/// memory rather than on the number of lines, hand the lines to
+/// [`for_each_matching_line_with`] instead, which holds one at a time.
+pub fn collect_matching_lines_with(
+    file_path: &str,
+    pattern: &[u8],
+    dialect: &Dialect,
+    limit: Option<usize>,
+) -> io::Result<Vec<Vec<u8>>> {
+    let limit = limit.unwrap_or(usize::MAX);
+    let mut lines = Vec::new();
+    if limit == 0 {
+        return Ok(lines);
+    }
+
+    try_for_each_matching_line(file_path, pattern, dialect, |line| {
+        lines.push(line.to_vec());
+        if lines.len() == limit {
+            return ControlFlow::Break(());
+        }
+        ControlFlow::Continue(())
+    })?;
+    Ok(lines)
+}
+
+/// [`for_each_matching_line_with`], until `f` breaks.
+fn try_for_each_matching_line(
+    file_path: &str,
+    pattern: &[u8],
+    dialect: &Dialect,
+    mut f: impl FnMut(&[u8]) -> ControlFlow<()>,
+) -> io::Result<()> {
+    if pattern.is_empty() {
+        return Ok(());
+    }
+
+    let pattern = Pattern::new(pattern);
+    let terminator = dialect.terminator;
+    try_for_each_complete_lines(file_path, dialect, |lines| {
+        try_for_each_match(lines, &pattern, dialect, MatchMode::Substring, |i| {
+            let start = memchr::memrchr(terminator, &lines[..i]).map_or(0, |pos| pos + 1);
+            let end = memchr::memchr(terminator, &lines[i..]).map_or(lines.len(), |pos| i + pos);
+            f(&lines[start..end])
+        })
+    })
+}
+
+/// Hand the file to `f` in runs of complete lines, terminators included
+/// (except after the last line of the file if it has none), until EOF or
+/// `f` breaks.
+///
+/// The incomplete last line of each buffer is moved to the front for the next
+/// read, and the buffer doubles when a single line does not fit.
+fn try_for_each_complete_lines(
+    file_path: &str,
+    dialect: &Dialect,
+    mut f: impl FnMut(&[u8]) -> ControlFlow<()>,
+) -> io::Result<()> {
+    let mut file = File::open(file_path)?;
+    let mut buffer = ScratchPool::global().take(optimal_buffer_size());
+    let terminator = dialect.terminator;
+    // Bytes at the start of the buffer, an incomplete line after the first read
+    let mut filled = if dialect.skip_bom { read_past_bom(&mut file, &mut buffer)?.0 } else { 0 };
+
+    loop {
+        if filled == buffer.len() {
+            let len = buffer.len();
+            buffer.resize(2 * len, 0);
+        }
+        let n = match file.read(&mut buffer[filled..]) {
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        // End of the complete lines; the carried bytes hold no terminator,
+        // and at EOF the last line needs none
+        let complete = if n == 0 {
+            filled
+        } else {
+            match memchr::memrchr(terminator, &buffer[filled..filled + n]) {
+                Some(pos) => filled + pos + 1,
+                None => {
+                    filled += n;
+                    continue;
+                }
+            }
+        };
+        filled += n;
+
+        if f(&buffer[..complete]).is_break() || n == 0 {
+            return Ok(());
+        }
+        buffer.copy_within(complete..filled, 0);
+        filled -= complete;
+    }
+}
+
+/// Count lines containing a pattern by loading entire file into memory first.
+///
+/// This is the simpler approach: read everything, then search.
+/// Trades memory for simplicity.
+pub fn count_pattern_matches_in_memory(
+    file_path: &str,
+    pattern: &[u8],
+) -> io::Result<usize> {
+    count_pattern_matches_in_memory_with(file_path, pattern, &Dialect::CSV)
+}
+
+/// [`count_pattern_matches_in_memory`] with the BOM handling and line
+/// terminator of `dialect` (its other fields are not used).
+pub fn count_pattern_matches_in_memory_with(
+    file_path: &str,
+    pattern: &[u8],
+    dialect: &Dialect,
+) -> io::Result<usize> {
+    count_pattern_matches_in_memory_mode(file_path, pattern, dialect, MatchMode::Substring)
+}
+
+/// [`count_pattern_matches_in_memory_with`], counting only the lines where
+/// `pattern` appears as `mode` requires.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::csv_dialect::Dialect;
+/// use scratchpad::csv_parse_buffer_size_impact::{
+///     count_pattern_matches_in_memory_mode, MatchMode,
+/// };
+///
+/// // Rows with an "MIT" field, not the ones naming Smith
+/// let count = count_pattern_matches_in_memory_mode(
+///     "researchers.csv",
+///     b"MIT",
+///     &Dialect::CSV,
+///     MatchMode::WholeField,
+/// )
+/// .expect("Failed to read file");
+/// ```
+pub fn count_pattern_matches_in_memory_mode(
+    file_path: &str,
+    pattern: &[u8],
+    dialect: &Dialect,
+    mode: MatchMode,
+) -> io::Result<usize> {
+    if pattern.is_empty() {
+        return Ok(0);
+    }
+
+    // Load entire file into memory
+    let file = std::fs::read(file_path)?;
+    Ok(count_in_slice(&file, &Pattern::new(pattern), dialect, mode))
+}
+
+/// Count the lines of `data`, a whole file or a chunk of one already in
+/// memory, containing a [`Pattern`] prepared once.
+///
+/// The search of [`count_pattern_matches_in_memory_with`]: a leading BOM is
+/// skipped if `dialect` says so, and a chunk should start at a line start.
+pub fn count_compiled_matches_in_slice(data: &[u8], pattern: &Pattern, dialect: &Dialect) -> usize {
+    if pattern.is_empty() {
+        return 0;
+    }
+    count_in_slice(data, pattern, dialect, MatchMode::Substring)
+}
+
+/// [`count_compiled_matches_in_slice`], stopping at the `limit`-th matching
+/// line as [`count_pattern_matches_limited`] does.
+pub fn count_compiled_matches_in_slice_limited(
+    data: &[u8],
+    pattern: &Pattern,
+    dialect: &Dialect,
+    limit: Option<usize>,
+) -> LimitedCount {
+    let limit = limit.unwrap_or(usize::MAX);
+    if pattern.is_empty() || limit == 0 {
+        return LimitedCount::default();
+    }
+
+    let lines = dialect.strip_bom(data);
+    let bom_len = data.len() - lines.len();
+    let mut count = 0;
+    let mut examined = data.len();
+    let _ = try_for_each_match(lines, pattern, dialect, MatchMode::Substring, |i| {
+        count += 1;
+        if count < limit {
+            return ControlFlow::Continue(());
+        }
+        examined = bom_len + i + pattern.len();
+        ControlFlow::Break(())
+    });
+    LimitedCount { count, bytes_examined: examined as u64 }
+}
+
+/// [`count_pattern_matches_in_memory`] over a memory-mapped file: the same
+/// search, without copying the file first (feature `mmap`).
+#[cfg(feature = "mmap")]
+pub fn count_pattern_matches_mmap(file_path: &str, pattern: &[u8]) -> io::Result<usize> {
+    count_pattern_matches_mmap_with(file_path, pattern, &Dialect::CSV)
+}
+
+/// [`count_pattern_matches_mmap`] with the BOM handling and line terminator
+/// of `dialect`.
+#[cfg(feature = "mmap")]
+pub fn count_pattern_matches_mmap_with(
+    file_path: &str,
+    pattern: &[u8],
+    dialect: &Dialect,
+) -> io::Result<usize> {
+    if pattern.is_empty() {
+        return Ok(0);
+    }
+    let pattern = Pattern::new(pattern);
+    crate::mmap::with_mapped_file(file_path, |data| {
+        count_in_slice(data, &pattern, dialect, MatchMode::Substring)
+    })
+}
+
+/// [`count_pattern_matches_from_file`] with the reads issued through
+/// io_uring, [`DEPTH`](crate::uring::DEPTH) reads of 256 KB in flight while
+/// the current chunk is scanned (feature `uring`, Linux only).
+///
+/// Fails if io_uring is unavailable; fall back to
+/// [`count_pattern_matches_from_file`] then.
+#[cfg(all(feature = "uring", target_os = "linux"))]
+pub fn count_pattern_matches_uring(file_path: &str, pattern: &[u8]) -> io::Result<usize> {
+    count_pattern_matches_uring_with(file_path, pattern, &Dialect::CSV)
+}
+
+/// [`count_pattern_matches_uring`] with the BOM handling and line terminator
+/// of `dialect`.
+#[cfg(all(feature = "uring", target_os = "linux"))]
+pub fn count_pattern_matches_uring_with(
+    file_path: &str,
+    pattern: &[u8],
+    dialect: &Dialect,
+) -> io::Result<usize> {
+    let reader = crate::uring::UringReader::open(file_path)?;
+    count_pattern_matches_in_reader_with(reader, pattern, dialect)
+}
+
+/// [`count_pattern_matches_from_file`] with the page cache bypassed
+/// (`O_DIRECT` on Linux, `F_NOCACHE` on macOS): every byte comes from the
+/// disk, and scanning a huge archive does not evict the rest of the cache.
+///
+/// Fails where uncached reads are unavailable (other targets, tmpfs); see
+/// [`uncached`](crate::uncached).
+pub fn count_pattern_matches_uncached(file_path: &str, pattern: &[u8]) -> io::Result<usize> {
+    count_pattern_matches_uncached_with(file_path, pattern, &Dialect::CSV)
+}
+
+/// [`count_pattern_matches_uncached`] with the BOM handling and line
+/// terminator of `dialect`.
+pub fn count_pattern_matches_uncached_with(
+    file_path: &str,
+    pattern: &[u8],
+    dialect: &Dialect,
+) -> io::Result<usize> {
+    let reader = crate::uncached::UncachedReader::open(file_path)?;
+    count_pattern_matches_in_reader_with(reader, pattern, dialect)
+}
+
+/// [`count_pattern_matches_from_file`] after advising the kernel how the
+/// file will be read (see [`readahead`](crate::readahead)): the same count,
+/// with the readahead of `strategy`.
+pub fn count_pattern_matches_advised(
+    file_path: &str,
+    pattern: &[u8],
+    strategy: ReadStrategy,
+) -> io::Result<usize> {
+    let file = File::open(file_path)?;
+    strategy.advise_file(&file)?;
+    count_pattern_matches_in_reader(file, pattern)
+}
+
+/// [`count_pattern_matches_from_file`] with the file read ahead on a
+/// background thread, two 256 KB buffers deep (see
+/// [`prefetch`](crate::prefetch)): the next chunk is read while the current
+/// one is searched.
+pub fn count_pattern_matches_prefetch(file_path: &str, pattern: &[u8]) -> io::Result<usize> {
+    let reader = crate::prefetch::PrefetchReader::new(File::open(file_path)?);
+    count_pattern_matches_in_reader(reader, pattern)
+}
+
+/// [`count_pattern_matches_from_file`] calling `on_progress(bytes_done,
+/// bytes_total)` after every buffer read (see [`progress`](crate::progress)):
+/// the same count, with something to show during a multi-GB scan.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::csv_parse_buffer_size_impact::count_pattern_matches_progress;
+///
+/// let mut shown = 0;
+/// let count = count_pattern_matches_progress("huge.csv", b"Harvard", |done, total| {
+///     let percent = done * 100 / total.max(1);
+///     if percent > shown {
+///         shown = percent;
+///         eprint!("\r{}%", percent);
+///     }
+/// })
+/// .expect("Failed to read file");
+/// ```
+pub fn count_pattern_matches_progress<F: FnMut(u64, u64)>(
+    file_path: &str,
+    pattern: &[u8],
+    on_progress: F,
+) -> io::Result<usize> {
+    count_pattern_matches_progress_with(file_path, pattern, &Dialect::CSV, on_progress)
+}
+
+/// [`count_pattern_matches_progress`] with the BOM handling and line
+/// terminator of `dialect`.
+pub fn count_pattern_matches_progress_with<F: FnMut(u64, u64)>(
+    file_path: &str,
+    pattern: &[u8],
+    dialect: &Dialect,
+    on_progress: F,
+) -> io::Result<usize> {
+    let file = File::open(file_path)?;
+    let len = file.metadata()?.len();
+    let reader = crate::progress::ProgressReader::new(file, len, on_progress);
+    count_pattern_matches_in_reader_with(reader, pattern, dialect)
+}
+
+/// Count lines containing a pattern without blocking an async runtime
+/// (feature `tokio`).
+///
+/// The search of [`count_pattern_matches_from_file`], with the reads going
+/// through `tokio::fs::File`: the task yields while a read is in flight, so
+/// a service can scan a large file on the runtime that serves its requests.
+/// Each tokio file read is a hop to the blocking thread pool, so the reads
+/// are [`ASYNC_BUFFER_SIZE`] rather than L1-sized: on a warm 67 MB file this
+/// takes 44 ms against 40 ms for the blocking search, where 16 KB reads took
+/// 84 ms.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::csv_parse_buffer_size_impact::count_pattern_matches_async;
+///
+/// async fn harvard_rows() -> std::io::Result<usize> {
+///     count_pattern_matches_async("researchers.csv", b"Harvard").await
+/// }
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn count_pattern_matches_async(file_path: &str, pattern: &[u8]) -> io::Result<usize> {
+    count_pattern_matches_async_with(file_path, pattern, &Dialect::CSV).await
+}
+
+/// [`count_pattern_matches_async`] with the BOM handling and line terminator
+/// of `dialect`.
+#[cfg(feature = "tokio")]
+pub async fn count_pattern_matches_async_with(
+    file_path: &str,
+    pattern: &[u8],
+    dialect: &Dialect,
+) -> io::Result<usize> {
+    use tokio::io::AsyncReadExt;
+
+    if pattern.is_empty() {
+        return Ok(0);
+    }
+
+    let pattern = Pattern::new(pattern);
+    let mut file = tokio::fs::File::open(file_path).await?;
+    let mut buffer = ScratchPool::global().take(ASYNC_BUFFER_SIZE);
+    let mut scan = LineScan::new(&pattern, dialect, usize::MAX);
+    let mut offset = 0;
+    // Bytes already in the buffer that have not been scanned yet
+    let mut unscanned = false;
+
+    if dialect.skip_bom {
+        while offset < UTF8_BOM.len() {
+            match file.read(&mut buffer[offset..]).await? {
+                0 => break,
+                n => offset += n,
+            }
+        }
+        offset = drop_bom(&mut buffer, offset).0;
+        unscanned = offset > 0;
+    }
+
+    loop {
+        let n = file.read(&mut buffer[offset..]).await?;
+        if n == 0 && !unscanned {
+            return Ok(scan.line_count);
+        }
+        let bytes_read = n + offset;
+        unscanned = false;
+
+        // No limit: the scan never breaks
+        if let ControlFlow::Continue(carried) = scan.scan(&mut buffer, bytes_read) {
+            offset = carried;
+        }
+    }
+}
+
+/// Count every occurrence of a pattern, not the lines containing one: a line
+/// naming Harvard three times counts 3.
+///
+/// The line counters stop at the first match of each line, which suits
+/// `grep -c` but not frequency analysis. Occurrences do not overlap, as with
+/// `str::matches`: `aa` is found twice in `aaaa`. Lines play no part, so a
+/// pattern containing the terminator is counted like any other.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::csv_parse_buffer_size_impact::count_pattern_occurrences;
+///
+/// let mentions = count_pattern_occurrences("citations.csv", b"Harvard")
+///     .expect("Failed to read file");
+/// ```
+pub fn count_pattern_occurrences(file_path: &str, pattern: &[u8]) -> io::Result<usize> {
+    count_pattern_occurrences_with(file_path, pattern, &Dialect::CSV)
+}
+
+/// [`count_pattern_occurrences`] with the BOM handling of `dialect` (its
+/// other fields are not used).
+pub fn count_pattern_occurrences_with(
+    file_path: &str,
+    pattern: &[u8],
+    dialect: &Dialect,
+) -> io::Result<usize> {
+    count_pattern_occurrences_in_reader(File::open(file_path)?, pattern, dialect)
+}
+
+/// [`count_pattern_occurrences_with`] over any reader.
+pub fn count_pattern_occurrences_in_reader<R: Read>(
+    mut reader: R,
+    pattern: &[u8],
+    dialect: &Dialect,
+) -> io::Result<usize> {
+    if pattern.is_empty() {
+        return Ok(0);
+    }
+
+    let pattern = Pattern::new(pattern);
+    // Room for the carried bytes and at least as much new input
+    let size = optimal_buffer_size().max(2 * pattern.len());
+    let mut buffer = ScratchPool::global().take(size);
+    let mut filled =
+        if dialect.skip_bom { read_past_bom(&mut reader, &mut buffer)?.0 } else { 0 };
+    let mut count = 0;
+    let mut eof = false;
+
+    loop {
+        while !eof && filled < buffer.len() {
+            match reader.read(&mut buffer[filled..]) {
+                Ok(0) => eof = true,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        // Resume after each match: occurrences do not overlap
+        let mut i = 0;
+        while let Some(pos) = pattern.find(&buffer[i..filled]) {
+            count += 1;
+            i += pos + pattern.len();
+        }
+        if eof {
+            return Ok(count);
+        }
+
+        // Carry the bytes a match cut by the end of the buffer can start in,
+        // none of them inside a counted match
+        let keep = i.max(filled.saturating_sub(pattern.len() - 1));
+        buffer.copy_within(keep..filled, 0);
+        filled -= keep;
+    }
+}
+
+/// Count the lines per value of a column: "how many rows per university"
+/// rather than "how many rows mention Harvard".
+///
+/// Each line is cut at the dialect's delimiter with memchr, skipping the
+/// fields before `column` (0-based) without looking at their bytes, and the
+/// field is looked up by its bytes in a hash table. A value is copied once,
+/// the first time it is seen, so memory grows with the distinct values, not
+/// the file. On a warm 88 MB file of 3 million rows, grouping 3 universities
+/// takes 115-160 ms where counting the Harvard lines takes 33-46 ms; about
+/// half of it is the table lookups, and hashing with `hash64` instead of
+/// SipHash measured the same. Lines with fewer fields, empty ones included, are not counted;
+/// the header line is, under its column name. A `\r` before a `\n`
+/// terminator is dropped, so the last column of a `\r\n` file groups like
+/// the others. Quotes are not parsed (see the module warning): a quoted field
+/// is counted with its quotes, and one containing the delimiter is cut.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::csv_parse_buffer_size_impact::count_values_in_column;
+///
+/// let per_university = count_values_in_column("researchers.csv", 1)
+///     .expect("Failed to read file");
+/// println!("Harvard: {:?}", per_university.get(&b"Harvard"[..]));
+/// ```
+pub fn count_values_in_column(
+    file_path: &str,
+    column: usize,
+) -> io::Result<HashMap<Vec<u8>, usize>> {
+    count_values_in_column_with(file_path, column, &Dialect::CSV)
+}
+
+/// [`count_values_in_column`] with the delimiter, BOM handling and line
+/// terminator of `dialect`.
+pub fn count_values_in_column_with(
+    file_path: &str,
+    column: usize,
+    dialect: &Dialect,
+) -> io::Result<HashMap<Vec<u8>, usize>> {
+    let mut counts = HashMap::new();
+    let (delimiter, terminator) = (dialect.delimiter, dialect.terminator);
+
+    try_for_each_complete_lines(file_path, dialect, |lines| {
+        let mut start = 0;
+        while start < lines.len() {
+            let end = memchr::memchr(terminator, &lines[start..]);
+            let end = end.map_or(lines.len(), |pos| start + pos);
+            let mut line = &lines[start..end];
+            start = end + 1;
+            if terminator == b'\n' {
+                line = line.strip_suffix(b"\r").unwrap_or(line);
+            }
+            if line.is_empty() {
+                continue;
+            }
+
+            let Some(value) = nth_field(line, column, delimiter) else {
+                continue;
+            };
+            match counts.get_mut(value) {
+                Some(count) => *count += 1,
+                None => {
+                    counts.insert(value.to_vec(), 1);
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    })?;
+
+    Ok(counts)
+}
+
+/// [`count_values_in_column`] written to `sink` as `value,count` rows, the
+/// most frequent value first (ties in byte order), for a tool that reads the
+/// counts rather than a `HashMap`. Returns the number of rows.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::csv_parse_buffer_size_impact::write_values_in_column;
+/// use scratchpad::sink::NdjsonSink;
+///
+/// // {"value":"Harvard","count":2500} and so on, one line per university
+/// let mut sink = NdjsonSink::new(std::io::stdout().lock());
+/// write_values_in_column("researchers.csv", 1, &mut sink).expect("Failed to read file");
+/// ```
+pub fn write_values_in_column(
+    file_path: &str,
+    column: usize,
+    sink: &mut impl Sink,
+) -> io::Result<usize> {
+    write_values_in_column_with(file_path, column, &Dialect::CSV, sink)
+}
+
+/// [`write_values_in_column`] with the delimiter, BOM handling and line
+/// terminator of `dialect`.
+pub fn write_values_in_column_with(
+    file_path: &str,
+    column: usize,
+    dialect: &Dialect,
+    sink: &mut impl Sink,
+) -> io::Result<usize> {
+    let mut counts: Vec<_> = count_values_in_column_with(file_path, column, dialect)?
+        .into_iter()
+        .collect();
+    counts.sort_unstable_by(|(a, m), (b, n)| n.cmp(m).then_with(|| a.cmp(b)));
+
+    sink.begin(&["value", "count"])?;
+    for (value, count) in &counts {
+        sink.row(&[Value::Str(value), Value::Int(*count as i64)])?;
+    }
+    sink.finish()?;
+    Ok(counts.len())
+}
 
-const BUFFER_SIZE: usize = 4096;
+/// The `column`-th field of `line` (0-based), `None` if it has fewer fields.
+#[inline]
+fn nth_field(line: &[u8], column: usize, delimiter: u8) -> Option<&[u8]> {
+    let mut start = 0;
+    for _ in 0..column {
+        start += memchr::memchr(delimiter, &line[start..])? + 1;
+    }
+    let end = memchr::memchr(delimiter, &line[start..]).map_or(line.len(), |pos| start + pos);
+    Some(&line[start..end])
+}
 
-/// Count lines containing a pattern by reading from disk with 4KB buffering.
+/// Count lines containing a pattern where `anchor` allows it:
+/// [`MatchAnchor::LineStart`] only counts the lines starting with it.
 ///
-/// Matches the blog post's C# implementation:
-/// ```csharp
-/// i = Array.IndexOf(buffer, (byte)harvardBytes[0], i, ...);
-/// if (region.SequenceEqual(tailbytes)) { ... }
-/// ```
+/// When the pattern is known to be the first column (an ID, a date, a log
+/// level), the anchored search only compares the bytes after each
+/// terminator, found with memchr, and never looks for candidates inside the
+/// lines. Its cost is one memchr step per line whatever the pattern: 36-50
+/// ms on a warm 88 MB file of 3 million rows. The unanchored search takes
+/// 76-90 ms for `2021`, found in the middle of every row, but 19-25 ms for
+/// a first column value that occurs once, where the first+last byte filter
+/// skips most of the file.
 ///
 /// # Example
 /// ```no_run
-/// use scratchpad::csv_parse_buffer_size_impact::count_pattern_matches_from_file;
+/// use scratchpad::csv_parse_buffer_size_impact::{
+///     count_pattern_matches_anchored, MatchAnchor,
+/// };
 ///
-/// let count = count_pattern_matches_from_file("researchers.csv", b"Harvard")
+/// let errors = count_pattern_matches_anchored("app.log", b"ERROR", MatchAnchor::LineStart)
 ///     .expect("Failed to read file");
-/// println!("Found {} matching lines", count);
 /// ```
-pub fn count_pattern_matches_from_file(
+pub fn count_pattern_matches_anchored(
+    file_path: &str,
+    pattern: &[u8],
+    anchor: MatchAnchor,
+) -> io::Result<usize> {
+    count_pattern_matches_anchored_with(file_path, pattern, &Dialect::CSV, anchor)
+}
+
+/// [`count_pattern_matches_anchored`] with the BOM handling and line
+/// terminator of `dialect`.
+pub fn count_pattern_matches_anchored_with(
     file_path: &str,
     pattern: &[u8],
+    dialect: &Dialect,
+    anchor: MatchAnchor,
 ) -> io::Result<usize> {
+    if anchor == MatchAnchor::Anywhere {
+        return count_pattern_matches_from_file_with(file_path, pattern, dialect);
+    }
     if pattern.is_empty() {
         return Ok(0);
     }
 
-    let mut file = File::open(file_path)?;
-    let mut buffer = vec![0u8; BUFFER_SIZE];
+    let terminator = dialect.terminator;
     let mut line_count = 0;
-    let mut offset = 0;
-
-    let first_byte = pattern[0];
-    let tail_bytes = &pattern[1..];
-
-    loop {
-        let bytes_read = file.read(&mut buffer[offset..])? + offset;
-        if bytes_read == 0 {
-            break;
+    // Every run of complete lines starts at a line start
+    try_for_each_complete_lines(file_path, dialect, |lines| {
+        line_count += usize::from(lines.starts_with(pattern));
+        for pos in memchr::memchr_iter(terminator, lines) {
+            line_count += usize::from(lines[pos + 1..].starts_with(pattern));
         }
-        offset = 0;
+        ControlFlow::Continue(())
+    })?;
+    Ok(line_count)
+}
 
-        // Search for pattern in current buffer
-        let mut i = 0;
-        while i <= bytes_read.saturating_sub(pattern.len()) {
-            // Find first byte using memchr (like Array.IndexOf)
-            match memchr::memchr(first_byte, &buffer[i..bytes_read - pattern.len() + 1]) {
-                None => break,
-                Some(pos) => {
-                    i += pos;
-
-                    // Check if tail bytes match (like region.SequenceEqual)
-                    if &buffer[i + 1..i + pattern.len()] == tail_bytes {
-                        line_count += 1;
-
-                        // Skip to end of line to avoid double-counting
-                        while i < bytes_read && buffer[i] != b'\n' {
-                            i += 1;
-                        }
-                        i += 1;
-                    } else {
-                        i += 1;
-                    }
-                }
-            }
-        }
+/// How a pattern has to appear in a line for the line to match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchMode {
+    /// Anywhere in the line, inside a field or across fields.
+    #[default]
+    Substring,
+    /// As a whole field: preceded by a delimiter or the start of the line and
+    /// followed by a delimiter or the end of the line (a terminator, the `\r`
+    /// of a `\r\n`, the end of the file). `MIT` matches `Bob,MIT,2021` but
+    /// not `SMITH,2021` or `Bob,MITx`.
+    ///
+    /// Quotes are not bounds: `"MIT"` is not matched by `MIT`.
+    WholeField,
+}
 
-        // Handle pattern spanning buffer boundary
-        for i in bytes_read.saturating_sub(pattern.len() - 1)..bytes_read {
-            if pattern.starts_with(&buffer[i..bytes_read]) {
-                let region_len = bytes_read - i;
-                buffer.copy_within(i..bytes_read, 0);
-                offset = region_len;
-                break;
+impl MatchMode {
+    /// Whether the `len`-byte match at `data[i..]` is bounded as `self` requires.
+    #[inline]
+    fn accepts(self, data: &[u8], i: usize, len: usize, dialect: &Dialect) -> bool {
+        match self {
+            MatchMode::Substring => true,
+            MatchMode::WholeField => {
+                let is_bound =
+                    |b: u8| b == dialect.delimiter || b == dialect.terminator || b == b'\r';
+                (i == 0 || is_bound(data[i - 1])) && data.get(i + len).is_none_or(|&b| is_bound(b))
             }
         }
     }
+}
 
-    Ok(line_count)
+/// Where in a line a pattern has to start for the line to match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchAnchor {
+    /// At any position.
+    #[default]
+    Anywhere,
+    /// At the start of the line (after a leading BOM in the first line, if
+    /// the dialect skips it).
+    LineStart,
 }
 
-/// Count lines containing a pattern by loading entire file into memory first.
+/// Where a matching line was found by [`find_pattern_matches`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchLoc {
+    /// Line number, starting at 1 (as printed by `grep -n`).
+    pub line: usize,
+    /// Byte offset of the match in the file, a leading BOM included.
+    pub offset: usize,
+    /// Byte offset of the match within its line, starting at 0.
+    pub column: usize,
+}
+
+/// Locate the lines containing a pattern, for when the count is not enough
+/// (jumping to a log entry, printing context around it).
 ///
-/// This is the simpler approach: read everything, then search.
-/// Trades memory for simplicity.
-pub fn count_pattern_matches_in_memory(
+/// Reports the first match of every matching line, so the result has as many
+/// entries as [`count_pattern_matches_in_memory`] counts. Line numbers are
+/// tracked by counting terminators between matches, with the same memchr
+/// the search uses, so non-matching lines are never visited one by one.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::csv_parse_buffer_size_impact::find_pattern_matches;
+///
+/// for m in find_pattern_matches("app.log", b"ERROR").expect("Failed to read file") {
+///     println!("{}:{}: byte {}", m.line, m.column, m.offset);
+/// }
+/// ```
+pub fn find_pattern_matches(file_path: &str, pattern: &[u8]) -> io::Result<Vec<MatchLoc>> {
+    find_pattern_matches_with(file_path, pattern, &Dialect::CSV)
+}
+
+/// [`find_pattern_matches`] with the BOM handling and line terminator of
+/// `dialect`.
+pub fn find_pattern_matches_with(
+    file_path: &str,
+    pattern: &[u8],
+    dialect: &Dialect,
+) -> io::Result<Vec<MatchLoc>> {
+    find_pattern_matches_mode(file_path, pattern, dialect, MatchMode::Substring)
+}
+
+/// [`find_pattern_matches_with`], locating only the matches that `mode`
+/// accepts.
+pub fn find_pattern_matches_mode(
+    file_path: &str,
+    pattern: &[u8],
+    dialect: &Dialect,
+    mode: MatchMode,
+) -> io::Result<Vec<MatchLoc>> {
+    if pattern.is_empty() {
+        return Ok(Vec::new());
+    }
+    let file = std::fs::read(file_path)?;
+    Ok(find_pattern_matches_in_slice(&file, pattern, dialect, mode))
+}
+
+/// [`find_pattern_matches_mode`] on a file already in memory: for a caller
+/// that needs the bytes of the matching lines too, without reading it twice.
+pub fn find_pattern_matches_in_slice(
+    file: &[u8],
+    pattern: &[u8],
+    dialect: &Dialect,
+    mode: MatchMode,
+) -> Vec<MatchLoc> {
+    if pattern.is_empty() {
+        return Vec::new();
+    }
+
+    let data = dialect.strip_bom(file);
+    let bom_len = file.len() - data.len();
+    let terminator = dialect.terminator;
+
+    let mut matches = Vec::new();
+    let mut line = 1;
+    let mut line_start = 0;
+    // Terminators before this position are already counted
+    let mut counted = 0;
+    for_each_match(data, &Pattern::new(pattern), dialect, mode, |i| {
+        let skipped = &data[counted..i];
+        line += memchr::memchr_iter(terminator, skipped).count();
+        if let Some(pos) = memchr::memrchr(terminator, skipped) {
+            line_start = counted + pos + 1;
+        }
+        counted = i;
+        matches.push(MatchLoc { line, offset: bom_len + i, column: i - line_start });
+    });
+
+    matches
+}
+
+/// Count the CSV records containing a pattern, quote-aware.
+///
+/// The line-based counters take every terminator as the end of a record. A
+/// quoted field spanning lines breaks that: a match in its second line is
+/// counted as a line of its own, and a record with two matches on different
+/// lines is counted twice. Here a match counts its whole record once, with
+/// the record boundaries (terminators outside quotes) of `csv_index`'s
+/// classification: 64 bytes at a time through [`RecordCursor`], only as far
+/// as the last match.
+///
+/// Uses [`Dialect::CSV`], leading BOM skipped. The classification replaces
+/// the memchr to the end of each matching line, so on the
+/// `disk_vs_memory_bench` file (a match on every row) it runs as fast as
+/// [`count_pattern_matches_in_memory`]: 2.2 GB/s on a warm 10 MB file.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::csv_parse_buffer_size_impact::count_record_matches;
+///
+/// let count = count_record_matches("notes.csv", b"Harvard").expect("Failed to read file");
+/// ```
+pub fn count_record_matches(file_path: &str, pattern: &[u8]) -> io::Result<usize> {
+    count_record_matches_mode(file_path, pattern, MatchMode::Substring)
+}
+
+/// [`count_record_matches`], counting only the records where `pattern`
+/// appears as `mode` requires.
+pub fn count_record_matches_mode(
     file_path: &str,
     pattern: &[u8],
+    mode: MatchMode,
 ) -> io::Result<usize> {
     if pattern.is_empty() {
         return Ok(0);
     }
 
-    // Load entire file into memory
-    let data = std::fs::read(file_path)?;
+    let file = std::fs::read(file_path)?;
+    let data = Dialect::CSV.strip_bom(&file);
+    let pattern = Pattern::new(pattern);
+    let mut records = RecordCursor::new(data);
+    let mut record_count = 0;
+    let mut i = 0;
+
+    while i < data.len() {
+        match pattern.find(&data[i..]) {
+            None => break,
+            Some(pos) => {
+                i += pos;
+
+                if mode.accepts(data, i, pattern.len(), &Dialect::CSV) {
+                    record_count += 1;
+
+                    // Skip to end of record, past quoted terminators
+                    i = records.record_end(i) + 1;
+                } else {
+                    i += 1;
+                }
+            }
+        }
+    }
+
+    Ok(record_count)
+}
 
-    let first_byte = pattern[0];
-    let tail_bytes = &pattern[1..];
+/// Count the lines of a whole file in memory containing `pattern` (not empty).
+fn count_in_slice(file: &[u8], pattern: &Pattern, dialect: &Dialect, mode: MatchMode) -> usize {
     let mut line_count = 0;
+    for_each_match(dialect.strip_bom(file), pattern, dialect, mode, |_| line_count += 1);
+    line_count
+}
+
+/// Call `f` with the position of the first match of `pattern` (not empty)
+/// accepted by `mode` on every line of `data` that has one.
+fn for_each_match(
+    data: &[u8],
+    pattern: &Pattern,
+    dialect: &Dialect,
+    mode: MatchMode,
+    mut f: impl FnMut(usize),
+) {
+    let _ = try_for_each_match(data, pattern, dialect, mode, |i| {
+        f(i);
+        ControlFlow::Continue(())
+    });
+}
+
+/// [`for_each_match`], until `f` breaks.
+fn try_for_each_match(
+    data: &[u8],
+    pattern: &Pattern,
+    dialect: &Dialect,
+    mode: MatchMode,
+    mut f: impl FnMut(usize) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    let terminator = dialect.terminator;
     let mut i = 0;
 
     // Search through the data
-    while i <= data.len().saturating_sub(pattern.len()) {
-        match memchr::memchr(first_byte, &data[i..]) {
+    while i < data.len() {
+        match pattern.find(&data[i..]) {
             None => break,
             Some(pos) => {
                 i += pos;
 
-                if i + pattern.len() <= data.len() && &data[i + 1..i + pattern.len()] == tail_bytes {
-                    line_count += 1;
+                if mode.accepts(data, i, pattern.len(), dialect) {
+                    f(i)?;
 
                     // Skip to end of line
-                    while i < data.len() && data[i] != b'\n' {
-                        i += 1;
-                    }
-                    i += 1;
+                    i = skip_line(data, i, terminator).0;
                 } else {
                     i += 1;
                 }
             }
         }
     }
-
-    Ok(line_count)
+    ControlFlow::Continue(())
 }
 
 #[cfg(test)]
@@ -152,6 +1570,18 @@ mod tests {
         File::create(path)?.write_all(content)
     }
 
+    /// A reader that hands out at most 3 bytes per read, less than a pattern.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.0.len().min(buf.len()).min(3);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
     #[test]
     fn test_basic() {
         let file = "/tmp/test_csv_basic.csv";
@@ -179,13 +1609,11 @@ mod tests {
     #[test]
     fn test_buffer_boundary() {
         let file = "/tmp/test_csv_boundary.csv";
-        let mut content = Vec::new();
-
-        // Fill buffer close to 4KB
-        for _ in 0..800 {
-            content.extend_from_slice(b"Name,MIT,2020\n");
-        }
-        content.extend_from_slice(b"Bob,Harvard,2021\n");
+        // "Harvard" straddles the end of the first buffer
+        let buffer_size = optimal_buffer_size();
+        let mut content = b"Name,MIT,2020\n".repeat((buffer_size - 20) / 14);
+        content.resize(buffer_size - 3, b'x');
+        content.extend_from_slice(b"Harvard,2021\n");
 
         create_test_file(file, &content).unwrap();
         let count = count_pattern_matches_from_file(file, b"Harvard").unwrap();
@@ -194,6 +1622,120 @@ mod tests {
         let _ = std::fs::remove_file(file);
     }
 
+    #[test]
+    fn test_pattern_prefix_at_eof() {
+        let file = "/tmp/test_csv_prefix_eof.csv";
+        create_test_file(file, b"Alice,Harvard,2020\nBob,Harv").unwrap();
+
+        assert_eq!(count_pattern_matches_from_file(file, b"Harvard").unwrap(), 1);
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[test]
+    fn test_short_reads() {
+        let data = b"Alice,Harvard,2020\nBob,MIT,2021\nCarol,Harvard,2022\n";
+        let mut reader = Trickle(data);
+        let count = count_pattern_matches_in_reader(&mut reader, b"Harvard");
+        assert_eq!(count.unwrap(), 2);
+        let count = count_pattern_matches_in_reader(&data[..], b"Harvard");
+        assert_eq!(count.unwrap(), 2);
+    }
+
+    #[test]
+    fn test_bom() {
+        let file = "/tmp/test_csv_bom.csv";
+        // The pattern is anchored on the first bytes of the file
+        create_test_file(file, b"\xEF\xBB\xBFName,University\nName,MIT\n").unwrap();
+
+        let pattern = b"\xBFName";
+        assert_eq!(count_pattern_matches_from_file(file, pattern).unwrap(), 0);
+        assert_eq!(count_pattern_matches_in_memory(file, pattern).unwrap(), 0);
+
+        let keep = Dialect { skip_bom: false, ..Dialect::CSV };
+        assert_eq!(count_pattern_matches_from_file_with(file, pattern, &keep).unwrap(), 1);
+        assert_eq!(count_pattern_matches_in_memory_with(file, pattern, &keep).unwrap(), 1);
+
+        assert_eq!(count_pattern_matches_from_file(file, b"Name").unwrap(), 2);
+        assert_eq!(count_pattern_matches_in_memory(file, b"Name").unwrap(), 2);
+        let _ = std::fs::remove_file(file);
+
+        // Short files and BOM-only files
+        for content in [&b"\xEF\xBB\xBF"[..], b"\xEF\xBB", b"N", b"\xEF\xBB\xBFN"] {
+            create_test_file(file, content).unwrap();
+            let expected = content.ends_with(b"N") as usize;
+            assert_eq!(count_pattern_matches_from_file(file, b"N").unwrap(), expected);
+            assert_eq!(count_pattern_matches_in_memory(file, b"N").unwrap(), expected);
+        }
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[test]
+    fn test_terminators() {
+        let file = "/tmp/test_csv_terminators.csv";
+        // A TSV export, a log with a matched line longer than the buffer
+        // (counted once), and a file with \r line endings
+        let mut log = b"ERROR ".to_vec();
+        log.resize(3 * optimal_buffer_size(), b'x');
+        log.extend_from_slice(b" ERROR again\nINFO ok\nERROR\n");
+        let mac = Dialect { terminator: b'\r', ..Dialect::CSV };
+        let cases: [(&[u8], &[u8], Dialect, usize); 3] = [
+            (b"Name\tUni\nAlice\tHarvard\nBob\tMIT\n", b"Harvard", Dialect::TSV, 1),
+            (&log, b"ERROR", Dialect::CSV, 2),
+            (b"a,Harvard\rb,Harvard\rc,MIT", b"Harvard", mac, 2),
+        ];
+
+        for (content, pattern, dialect, expected) in cases {
+            create_test_file(file, content).unwrap();
+            let from_file = count_pattern_matches_from_file_with(file, pattern, &dialect);
+            let in_memory = count_pattern_matches_in_memory_with(file, pattern, &dialect);
+            let sparse = count_pattern_matches_from_sparse_file_with(file, pattern, &dialect);
+            assert_eq!(from_file.unwrap(), expected);
+            assert_eq!(in_memory.unwrap(), expected);
+            assert_eq!(sparse.unwrap().0, expected);
+        }
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[test]
+    fn test_sparse_file() {
+        let file = "/tmp/test_csv_sparse.csv";
+        let mut f = File::create(file).unwrap();
+        f.write_all(b"Alice,Harvard,2020\n").unwrap();
+        f.set_len(8 << 20).unwrap();
+        f.seek(SeekFrom::Start(4 << 20)).unwrap();
+        f.write_all(b"Bob,Harvard,2021\nCarol,MIT,2022\n").unwrap();
+        drop(f);
+
+        let (count, holes) = count_pattern_matches_from_sparse_file(file, b"Harvard").unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(count, count_pattern_matches_from_file(file, b"Harvard").unwrap());
+        assert!(holes.iter().all(|h| h.start < h.end && h.end <= 8 << 20));
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[test]
+    fn test_for_each_buffer() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| i as u8).collect();
+
+        let mut seen = Vec::new();
+        for_each_buffer(&mut &data[..], 4096, |buf| {
+            assert!(buf.len() <= 4096);
+            seen.extend_from_slice(buf);
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        assert_eq!(seen, data);
+
+        // Breaking stops the reads
+        let mut calls = 0;
+        for_each_buffer(&mut &data[..], 1000, |_| {
+            calls += 1;
+            ControlFlow::Break(())
+        })
+        .unwrap();
+        assert_eq!(calls, 1);
+    }
+
     #[test]
     fn test_multiple_matches_same_line() {
         let file = "/tmp/test_csv_multi.csv";
@@ -205,4 +1747,453 @@ mod tests {
         assert_eq!(count, 1); // Should count line once, not twice
         let _ = std::fs::remove_file(file);
     }
+
+    #[test]
+    fn test_find_pattern_matches() {
+        let file = "/tmp/test_csv_find.csv";
+        let content = b"\xEF\xBB\xBFName,Uni\nAlice,Harvard\n\nBob,MIT\nHarvard,Harvard U\nHarv";
+        create_test_file(file, content).unwrap();
+
+        let matches = find_pattern_matches(file, b"Harvard").unwrap();
+        assert_eq!(
+            matches,
+            [
+                MatchLoc { line: 2, offset: 18, column: 6 },
+                MatchLoc { line: 5, offset: 35, column: 0 },
+            ]
+        );
+        for m in &matches {
+            assert_eq!(&content[m.offset..m.offset + 7], b"Harvard");
+        }
+        assert_eq!(matches.len(), count_pattern_matches_in_memory(file, b"Harvard").unwrap());
+        let in_slice =
+            find_pattern_matches_in_slice(content, b"Harvard", &Dialect::CSV, MatchMode::Substring);
+        assert_eq!(in_slice, matches);
+
+        // A match on the first line is not shifted by the BOM
+        let first = find_pattern_matches(file, b"Name").unwrap();
+        assert_eq!(first, [MatchLoc { line: 1, offset: 3, column: 0 }]);
+
+        let mac = Dialect { terminator: b'\r', ..Dialect::CSV };
+        create_test_file(file, b"a,MIT\rb,Harvard").unwrap();
+        let matches = find_pattern_matches_with(file, b"Harvard", &mac).unwrap();
+        assert_eq!(matches, [MatchLoc { line: 2, offset: 8, column: 2 }]);
+        assert!(find_pattern_matches(file, b"").unwrap().is_empty());
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[test]
+    fn test_for_each_matching_line() {
+        let file = "/tmp/test_csv_matching_lines.csv";
+        // Matching lines across buffer boundaries, one longer than the
+        // buffer, and an unterminated last line
+        let mut content = b"\xEF\xBB\xBFName,Uni\n".to_vec();
+        let mut expected = Vec::new();
+        for i in 0..2000 {
+            let line = format!("P{},{},{}", i, if i % 7 == 0 { "Harvard" } else { "MIT" }, i);
+            if i % 7 == 0 {
+                expected.push(line.clone().into_bytes());
+            }
+            content.extend_from_slice(line.as_bytes());
+            content.push(b'\n');
+        }
+        let mut long = b"Long,Harvard,".to_vec();
+        long.resize(3 * optimal_buffer_size(), b'x');
+        content.extend_from_slice(&long);
+        content.extend_from_slice(b"\nLast,Harvard");
+        expected.push(long);
+        expected.push(b"Last,Harvard".to_vec());
+        create_test_file(file, &content).unwrap();
+
+        let mut lines = Vec::new();
+        for_each_matching_line(file, b"Harvard", |line| lines.push(line.to_vec())).unwrap();
+        assert_eq!(lines, expected);
+        assert_eq!(lines.len(), count_pattern_matches_from_file(file, b"Harvard").unwrap());
+
+        // The BOM is not part of the first line unless kept
+        let mut first = Vec::new();
+        for_each_matching_line(file, b"Name", |line| first.push(line.to_vec())).unwrap();
+        assert_eq!(first, [b"Name,Uni".to_vec()]);
+        let keep = Dialect { skip_bom: false, ..Dialect::CSV };
+        first.clear();
+        for_each_matching_line_with(file, b"Name", &keep, |line| first.push(line.to_vec()))
+            .unwrap();
+        assert_eq!(first, [b"\xEF\xBB\xBFName,Uni".to_vec()]);
+
+        let mac = Dialect { terminator: b'\r', ..Dialect::CSV };
+        create_test_file(file, b"a,Harvard\rb,MIT\rc,Harvard\r").unwrap();
+        lines.clear();
+        for_each_matching_line_with(file, b"Harvard", &mac, |line| lines.push(line.to_vec()))
+            .unwrap();
+        assert_eq!(lines, [b"a,Harvard".to_vec(), b"c,Harvard".to_vec()]);
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[test]
+    fn test_whole_field() {
+        let file = "/tmp/test_csv_whole_field.csv";
+        let content = b"\xEF\xBB\xBFMIT,Uni\r\nSMITH,MITx\r\nAnn,MIT,SMITH\r\nBob,\"MIT\"\r\nMIT";
+        create_test_file(file, content).unwrap();
+
+        let (csv, field) = (&Dialect::CSV, MatchMode::WholeField);
+        assert_eq!(count_pattern_matches_in_memory(file, b"MIT").unwrap(), 5);
+        assert_eq!(count_pattern_matches_in_memory_mode(file, b"MIT", csv, field).unwrap(), 3);
+        // The first candidate of a line is rejected, a later one accepted
+        let lines: Vec<_> = find_pattern_matches_mode(file, b"MIT", csv, field)
+            .unwrap()
+            .iter()
+            .map(|m| (m.line, m.column))
+            .collect();
+        assert_eq!(lines, [(1, 0), (3, 4), (5, 0)]);
+
+        create_test_file(file, b"a\tMIT\nb\tMIT,x\n").unwrap();
+        let tsv = count_pattern_matches_in_memory_mode(file, b"MIT", &Dialect::TSV, field);
+        assert_eq!(tsv.unwrap(), 1);
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[test]
+    fn test_record_matches() {
+        let file = "/tmp/test_csv_records.csv";
+        // A quoted note spans three lines and mentions Harvard twice; another
+        // quoted field ends in a delimiter-bounded MIT
+        let content = b"\xEF\xBB\xBFid,note\n1,\"Harvard\nthen Harvard\nagain\"\n\
+                        2,MIT\n3,\"x\nMIT\",MIT\n4,SMITH";
+        create_test_file(file, content).unwrap();
+
+        assert_eq!(count_pattern_matches_in_memory(file, b"Harvard").unwrap(), 2);
+        assert_eq!(count_record_matches(file, b"Harvard").unwrap(), 1);
+        assert_eq!(count_record_matches(file, b"MIT").unwrap(), 3);
+        let field = count_record_matches_mode(file, b"MIT", MatchMode::WholeField);
+        assert_eq!(field.unwrap(), 2);
+        assert_eq!(count_record_matches(file, b"").unwrap(), 0);
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[test]
+    fn test_parallel() {
+        let file = "/tmp/test_csv_parallel.csv";
+        let mut content = b"\xEF\xBB\xBFName,University\n".to_vec();
+        for i in 0..5000 {
+            let university = ["MIT", "Harvard", "Harvard University, Harvard"][i % 3];
+            content.extend_from_slice(format!("Person{},{}\n", i, university).as_bytes());
+        }
+        // A line longer than a whole range, and no final terminator
+        content.extend(std::iter::repeat_n(b'x', content.len() / 2));
+        content.extend_from_slice(b"Harvard");
+        create_test_file(file, &content).unwrap();
+
+        let expected = count_pattern_matches_from_file(file, b"Harvard").unwrap();
+        assert_eq!(expected, 3334);
+        for threads in [0, 1, 2, 3, 7, 64, 1000] {
+            let count = count_pattern_matches_parallel(file, b"Harvard", threads).unwrap();
+            assert_eq!(count, expected, "{} threads", threads);
+        }
+        // A match in the BOM bytes only counts when the BOM is kept
+        let keep = Dialect { skip_bom: false, ..Dialect::CSV };
+        let bom = count_pattern_matches_parallel_with(file, b"\xBFName", 4, &keep);
+        assert_eq!(bom.unwrap(), 1);
+        assert_eq!(count_pattern_matches_parallel(file, b"\xBFName", 4).unwrap(), 0);
+
+        create_test_file(file, b"").unwrap();
+        assert_eq!(count_pattern_matches_parallel(file, b"Harvard", 4).unwrap(), 0);
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[test]
+    fn test_compiled_pattern() {
+        let pattern = Pattern::new(b"Harvard");
+        let files = [
+            ("/tmp/test_csv_compiled_1.csv", &b"\xEF\xBB\xBFName,Uni\nAnn,Harvard\nBob,MIT\n"[..]),
+            ("/tmp/test_csv_compiled_2.csv", b"Cid,Harvard\nDee,Harvard\n"),
+        ];
+        for (path, content) in files {
+            create_test_file(path, content).unwrap();
+            assert_eq!(
+                count_compiled_matches_from_file(path, &pattern, &Dialect::CSV).unwrap(),
+                count_pattern_matches_from_file(path, b"Harvard").unwrap()
+            );
+            assert_eq!(
+                count_compiled_matches_in_slice(content, &pattern, &Dialect::CSV),
+                count_pattern_matches_in_memory(path, b"Harvard").unwrap()
+            );
+            let _ = std::fs::remove_file(path);
+        }
+
+        // Chunks of one buffer, cut at line starts
+        let data = b"Ann,Harvard\nBob,MIT\nCid,Harvard\n";
+        let chunks = [&data[..12], &data[12..20], &data[20..]];
+        let count: usize = chunks
+            .iter()
+            .map(|chunk| count_compiled_matches_in_slice(chunk, &pattern, &Dialect::CSV))
+            .sum();
+        assert_eq!(count, 2);
+        let reader = count_compiled_matches_in_reader(&data[..], &Pattern::new(b""), &Dialect::CSV);
+        assert_eq!(reader.unwrap(), 0);
+    }
+
+    #[test]
+    fn test_occurrences() {
+        let file = "/tmp/test_csv_occurrences.csv";
+        create_test_file(file, b"\xEF\xBB\xBFAnn,Harvard,Harvard\nBob,MIT\nHarvard\n").unwrap();
+        assert_eq!(count_pattern_occurrences(file, b"Harvard").unwrap(), 3);
+        assert_eq!(count_pattern_matches_from_file(file, b"Harvard").unwrap(), 2);
+        // Across lines, and in the BOM only when it is kept
+        assert_eq!(count_pattern_occurrences(file, b"MIT\nHar").unwrap(), 1);
+        assert_eq!(count_pattern_occurrences(file, b"\xBFAnn").unwrap(), 0);
+        let keep = Dialect { skip_bom: false, ..Dialect::CSV };
+        assert_eq!(count_pattern_occurrences_with(file, b"\xBFAnn", &keep).unwrap(), 1);
+        let _ = std::fs::remove_file(file);
+
+        // Not overlapping
+        let count = |data: &[u8], pattern: &[u8]| {
+            count_pattern_occurrences_in_reader(data, pattern, &Dialect::CSV).unwrap()
+        };
+        assert_eq!(count(b"aaaa", b"aa"), 2);
+        assert_eq!(count(b"aaaaa", b"aa"), 2);
+        assert_eq!(count(b"abab", b""), 0);
+
+        // Matches cut by buffer boundaries and by 3-byte reads
+        let text: String = (0..20_000).map(|i| ["Harvard", "Har", "vard,", "aa"][i % 4]).collect();
+        for pattern in ["Harvard", "aa", "rdHar", "aHarvardH"] {
+            let expected = text.matches(pattern).count();
+            assert_eq!(count(text.as_bytes(), pattern.as_bytes()), expected, "{}", pattern);
+            let trickled = count_pattern_occurrences_in_reader(
+                Trickle(text.as_bytes()),
+                pattern.as_bytes(),
+                &Dialect::CSV,
+            );
+            assert_eq!(trickled.unwrap(), expected, "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn test_limit() {
+        let mut content = b"\xEF\xBB\xBFName,University\n".to_vec();
+        for i in 0..50_000 {
+            let university = if i % 997 == 0 { "Harvard,Harvard" } else { "MIT" };
+            content.extend_from_slice(format!("Person{},{}\n", i, university).as_bytes());
+        }
+        // End of the first Harvard of each matching line
+        let ends: Vec<u64> = content
+            .split_inclusive(|&b| b == b'\n')
+            .scan(0, |start, line| {
+                let end = line.windows(7).position(|w| w == b"Harvard");
+                let end = end.map(|pos| *start + pos + 7);
+                *start += line.len();
+                Some(end)
+            })
+            .flatten()
+            .map(|end| end as u64)
+            .collect();
+        let total = content.len() as u64;
+        let file = "/tmp/test_csv_limit.csv";
+        create_test_file(file, &content).unwrap();
+
+        let pattern = Pattern::new(b"Harvard");
+        for limit in [Some(1), Some(2), Some(30), Some(ends.len()), Some(1000), None] {
+            let expected = match limit {
+                Some(k) if k <= ends.len() => {
+                    LimitedCount { count: k, bytes_examined: ends[k - 1] }
+                }
+                _ => LimitedCount { count: ends.len(), bytes_examined: total },
+            };
+            assert_eq!(count_pattern_matches_limited(file, b"Harvard", limit).unwrap(), expected);
+            let dialect = &Dialect::CSV;
+            let trickled = Trickle(&content);
+            let counted =
+                count_compiled_matches_in_reader_limited(trickled, &pattern, dialect, limit);
+            assert_eq!(counted.unwrap(), expected);
+            let counted =
+                count_compiled_matches_in_slice_limited(&content, &pattern, dialect, limit);
+            assert_eq!(counted, expected);
+        }
+
+        let nothing = LimitedCount::default();
+        assert_eq!(count_pattern_matches_limited(file, b"Harvard", Some(0)).unwrap(), nothing);
+        assert_eq!(count_pattern_matches_limited(file, b"", None).unwrap(), nothing);
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[test]
+    fn test_collect_matching_lines() {
+        let file = "/tmp/test_csv_collect_lines.csv";
+        // Lines longer than any buffer, with the match near either end, and
+        // short ones cut at every offset
+        let mut content = b"\xEF\xBB\xBFName,Uni\n".to_vec();
+        let mut expected = Vec::new();
+        for i in 0..3000 {
+            let mut line = format!("P{},", i).into_bytes();
+            if i % 500 == 3 {
+                line.resize(5 * optimal_buffer_size() + i, b'x');
+            }
+            if i % 3 == 0 {
+                line.extend_from_slice(b"Harvard");
+            }
+            if i % 500 == 4 {
+                line.extend(std::iter::repeat_n(b'y', 70_000));
+            }
+            if i % 3 == 0 {
+                expected.push(line.clone());
+            }
+            content.extend_from_slice(&line);
+            content.push(b'\n');
+        }
+        create_test_file(file, &content).unwrap();
+
+        let lines = collect_matching_lines(file, b"Harvard").unwrap();
+        assert_eq!(lines.len(), expected.len());
+        assert!(lines == expected);
+
+        let first = collect_matching_lines_with(file, b"Harvard", &Dialect::CSV, Some(5)).unwrap();
+        assert!(first == expected[..5]);
+        let all = collect_matching_lines_with(file, b"Harvard", &Dialect::CSV, Some(10_000));
+        assert_eq!(all.unwrap().len(), expected.len());
+        assert!(collect_matching_lines_with(file, b"Harvard", &Dialect::CSV, Some(0))
+            .unwrap()
+            .is_empty());
+        assert!(collect_matching_lines(file, b"Yale").unwrap().is_empty());
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[test]
+    fn test_values_in_column() {
+        let file = "/tmp/test_csv_values_in_column.csv";
+        let mut content = b"\xEF\xBB\xBFName,University,Year\n".to_vec();
+        for i in 0..10_000 {
+            let university = ["MIT", "Harvard", "Yale", ""][i % 4];
+            let end = if i % 7 == 0 { "\r\n" } else { "\n" };
+            let line = format!("P{},{},{}{}", i, university, 2020 + i % 3, end);
+            content.extend_from_slice(line.as_bytes());
+        }
+        // Too short for column 2, an empty line, a last line without terminator
+        content.extend_from_slice(b"Solo,MIT\n\nLast,Yale,2020");
+        create_test_file(file, &content).unwrap();
+        let get = |counts: &HashMap<Vec<u8>, usize>, value: &str| {
+            counts.get(value.as_bytes()).copied()
+        };
+
+        let universities = count_values_in_column(file, 1).unwrap();
+        assert_eq!(universities.len(), 5);
+        assert_eq!(get(&universities, "Harvard"), Some(2500));
+        assert_eq!(get(&universities, "MIT"), Some(2501));
+        assert_eq!(get(&universities, "Yale"), Some(2501));
+        assert_eq!(get(&universities, ""), Some(2500));
+        assert_eq!(get(&universities, "University"), Some(1));
+
+        // Last column: `\r` dropped; the BOM is kept on request
+        let years = count_values_in_column(file, 2).unwrap();
+        assert_eq!(years.values().sum::<usize>(), 10_002);
+        assert_eq!((get(&years, "2020"), get(&years, "2020\r")), (Some(3335), None));
+        let keep = Dialect { skip_bom: false, ..Dialect::CSV };
+        let names = count_values_in_column_with(file, 0, &keep).unwrap();
+        assert_eq!(get(&names, "\u{feff}Name"), Some(1));
+        assert!(count_values_in_column(file, 3).unwrap().is_empty());
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[test]
+    fn test_write_values_in_column() {
+        use crate::sink::{CsvSink, MsgPackSink, NdjsonSink};
+
+        let file = "/tmp/test_csv_write_values.csv";
+        let content = b"Name,University\nAnn,Harvard\r\nBob,MIT\nCid,\"A, B\"\nDee,Harvard\n";
+        create_test_file(file, content).unwrap();
+        // Counts descending, ties in byte order; the quoted field is cut
+        let rows: [(&[u8], u8); 4] =
+            [(b"Harvard", 2), (b"\"A", 1), (b"MIT", 1), (b"University", 1)];
+
+        let mut csv = CsvSink::new(Vec::new());
+        assert_eq!(write_values_in_column(file, 1, &mut csv).unwrap(), 4);
+        assert_eq!(
+            String::from_utf8(csv.into_inner()).unwrap(),
+            "value,count\nHarvard,2\n\"\"\"A\",1\nMIT,1\nUniversity,1\n"
+        );
+
+        let mut ndjson = NdjsonSink::new(Vec::new());
+        write_values_in_column(file, 1, &mut ndjson).unwrap();
+        let lines: Vec<String> = rows
+            .iter()
+            .map(|(value, count)| {
+                let value = String::from_utf8_lossy(value).replace('"', "\\\"");
+                format!("{{\"value\":\"{}\",\"count\":{}}}\n", value, count)
+            })
+            .collect();
+        assert_eq!(String::from_utf8(ndjson.into_inner()).unwrap(), lines.concat());
+
+        let mut msgpack = MsgPackSink::new(Vec::new());
+        write_values_in_column(file, 1, &mut msgpack).unwrap();
+        let mut expected = Vec::new();
+        for (value, count) in rows {
+            expected.extend_from_slice(b"\x82\xA5value");
+            expected.push(0xA0 | value.len() as u8);
+            expected.extend_from_slice(value);
+            expected.extend_from_slice(b"\xA5count");
+            expected.push(count);
+        }
+        assert_eq!(msgpack.into_inner(), expected);
+
+        // Through the sink of a format chosen at runtime
+        let mut boxed = crate::sink::OutputFormat::Csv.sink(Vec::new());
+        assert_eq!(write_values_in_column(file, 3, &mut boxed).unwrap(), 0);
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[test]
+    fn test_anchored() {
+        let file = "/tmp/test_csv_anchored.csv";
+        // Starts of lines at every offset of the buffers, and the pattern
+        // in the middle of the others
+        let mut content = b"\xEF\xBB\xBFERROR,first\n".to_vec();
+        for i in 0..20_000 {
+            let line = match i % 3 {
+                0 => format!("ERROR,{}\n", i),
+                1 => format!("INFO,ERROR {}\n", i),
+                _ => format!("ERR{}\n", i),
+            };
+            content.extend_from_slice(line.as_bytes());
+        }
+        content.extend_from_slice(b"ERROR");
+        create_test_file(file, &content).unwrap();
+
+        let anchored = count_pattern_matches_anchored(file, b"ERROR", MatchAnchor::LineStart);
+        assert_eq!(anchored.unwrap(), 6669);
+        let anywhere = count_pattern_matches_anchored(file, b"ERROR", MatchAnchor::Anywhere);
+        assert_eq!(anywhere.unwrap(), count_pattern_matches_from_file(file, b"ERROR").unwrap());
+
+        let keep = Dialect { skip_bom: false, ..Dialect::CSV };
+        let line_start = MatchAnchor::LineStart;
+        let count = count_pattern_matches_anchored_with(file, b"ERROR", &keep, line_start);
+        assert_eq!(count.unwrap(), 6668);
+        let anchored = count_pattern_matches_anchored(file, b"", MatchAnchor::LineStart);
+        assert_eq!(anchored.unwrap(), 0);
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_async() {
+        let file = "/tmp/test_csv_async.csv";
+        let mut content = b"\xEF\xBB\xBFHarvard,University\n".to_vec();
+        for i in 0..100_000 {
+            let university = ["MIT", "Harvard", "Yale"][i % 3];
+            content.extend_from_slice(format!("Person{},{}\n", i, university).as_bytes());
+        }
+        create_test_file(file, &content).unwrap();
+
+        // Spawnable on a multi-threaded runtime
+        fn assert_send<T: Send>(future: T) -> T {
+            future
+        }
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let count = runtime.block_on(assert_send(count_pattern_matches_async(file, b"Harvard")));
+        assert_eq!(count.unwrap(), count_pattern_matches_from_file(file, b"Harvard").unwrap());
+
+        let keep = Dialect { skip_bom: false, ..Dialect::CSV };
+        let count = runtime.block_on(count_pattern_matches_async_with(file, b"\xBFHarvard", &keep));
+        assert_eq!(count.unwrap(), 1);
+        let missing = runtime.block_on(count_pattern_matches_async("/tmp/test_csv_missing", b"x"));
+        assert_eq!(missing.unwrap_err().kind(), io::ErrorKind::NotFound);
+        let _ = std::fs::remove_file(file);
+    }
 }
@@ -0,0 +1,842 @@
+//! Zero-copy CSV record reader with map/filter adapters.
+//!
+//! The counters in `csv_state_machine` only report how many fields and rows
+//! there are. [`CsvReader`] hands out the records themselves: each
+//! [`Record`] is a list of byte ranges into the input, so reading a record
+//! allocates nothing and a field is only copied if the caller asks for it.
+//!
+//! Field boundaries are found with `memchr2(',', '\n')` (and `memchr('"')`
+//! inside quoted fields), so the bodies of long fields are skipped rather
+//! than stepped through byte by byte.
+//!
+//! Rules (RFC 4180, lenient like the counters):
+//! - a field starting with `"` is quoted; `,` and `\n` inside it are data and `""` is an escaped
+//!   quote (left doubled in the raw field)
+//! - a `"` inside an unquoted field is data
+//! - bytes between a closing quote and the next delimiter are ignored
+//! - a `\r` before the `\n` terminator is dropped
+//!
+//! [`CsvReader::next_record`] lends each record from the reader's field
+//! buffer; [`CsvReader::records`] is a plain `Iterator` whose records only
+//! borrow the input, reusing one span buffer while records are dropped in turn.
+//!
+//! [`CsvReader::headers`] reads the first row as column names; records read
+//! after it look fields up by name with [`Record::get_named`].
+//!
+//! ETL-style transforms are expressed with [`CsvReader::filter_records`] and
+//! [`CsvReader::map_records`]: the predicate and the mapping see the borrowed
+//! record, so rejected records never cost more than finding their fields.
+//!
+//! [`parse_csv_sample`] (or [`CsvReader::sample`]) parses only every nth
+//! record and jumps over the others, for previews and schema inference.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read},
+    ops::Range,
+    rc::Rc,
+};
+
+use crate::scratch::ScratchPool;
+
+const FILE_BUFFER_SIZE: usize = 1 << 20;
+
+/// Column names (raw, as [`Record::get`] returns them) to column indices.
+pub type Headers<'a> = HashMap<&'a [u8], usize>;
+
+/// Byte range of one field's content in the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Field {
+    start: usize,
+    end: usize,
+    quoted: bool,
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Record
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Where a record's field spans live.
+#[derive(Debug, Clone)]
+enum Spans<'r> {
+    /// The reader's own buffer (lending API).
+    Borrowed(&'r [Field]),
+    /// A buffer shared with [`Records`], reused once the record is dropped.
+    Shared(Rc<Vec<Field>>),
+}
+
+/// One CSV record, borrowed from the input and a field buffer.
+#[derive(Debug, Clone)]
+pub struct Record<'r> {
+    data: &'r [u8],
+    range: Range<usize>,
+    fields: Spans<'r>,
+    headers: Option<Rc<Headers<'r>>>,
+}
+
+impl<'r> Record<'r> {
+    #[inline]
+    fn fields(&self) -> &[Field] {
+        match &self.fields {
+            Spans::Borrowed(fields) => fields,
+            Spans::Shared(fields) => fields,
+        }
+    }
+
+    /// Number of fields.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.fields().len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.fields().is_empty()
+    }
+
+    /// Raw content of field `i`: without surrounding quotes, `""` not collapsed.
+    #[inline]
+    pub fn get(&self, i: usize) -> Option<&'r [u8]> {
+        let data = self.data;
+        self.fields().get(i).map(|f| &data[f.start..f.end])
+    }
+
+    /// Raw content of the field in column `name`.
+    ///
+    /// None if the reader's headers were never read (see
+    /// [`CsvReader::headers`]), there is no such column, or the record is
+    /// shorter than the header row.
+    #[inline]
+    pub fn get_named(&self, name: impl AsRef<[u8]>) -> Option<&'r [u8]> {
+        let i = *self.headers.as_ref()?.get(name.as_ref())?;
+        self.get(i)
+    }
+
+    /// Field `i` as it appears in the input, enclosing quotes included.
+    #[inline]
+    pub fn get_raw(&self, i: usize) -> Option<&'r [u8]> {
+        let data = self.data;
+        self.fields().get(i).map(|f| {
+            let q = f.quoted as usize;
+            // An unclosed quoted field runs to the end of the input
+            &data[f.start - q..(f.end + q).min(data.len())]
+        })
+    }
+
+    /// Whether field `i` was quoted in the input.
+    #[inline]
+    pub fn is_quoted(&self, i: usize) -> bool {
+        self.fields().get(i).is_some_and(|f| f.quoted)
+    }
+
+    /// Iterate over the raw contents of all fields.
+    pub fn iter(&self) -> impl Iterator<Item = &'r [u8]> + '_ {
+        let data = self.data;
+        self.fields().iter().map(move |f| &data[f.start..f.end])
+    }
+
+    /// The record as it appears in the input, without its terminator.
+    #[inline]
+    pub fn as_bytes(&self) -> &'r [u8] {
+        &self.data[self.range.clone()]
+    }
+
+    /// Byte offset of the record in the input.
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.range.start
+    }
+
+    /// Copy all fields out of the input.
+    pub fn to_vec(&self) -> Vec<Vec<u8>> {
+        self.iter().map(|f| f.to_vec()).collect()
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Reader
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Reads CSV records from an in-memory buffer.
+///
+/// # Example
+/// ```
+/// use scratchpad::csv_reader::CsvReader;
+///
+/// let mut reader = CsvReader::new(b"name,city\n\"Smith, J\",Boston\n");
+/// reader.next_record(); // header
+/// let record = reader.next_record().unwrap();
+/// assert_eq!(record.get(0), Some(&b"Smith, J"[..]));
+/// assert_eq!(record.get(1), Some(&b"Boston"[..]));
+/// ```
+pub struct CsvReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    fields: Vec<Field>,
+    /// Whether the last record ended with `\n` (rather than the end of data).
+    terminated: bool,
+    headers: Option<Rc<Headers<'a>>>,
+}
+
+impl<'a> CsvReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        CsvReader { data, pos: 0, fields: Vec::new(), terminated: false, headers: None }
+    }
+
+    /// Column names parsed from the first row, or None for empty input.
+    ///
+    /// Called before any record is read, this consumes the header row, so
+    /// the records that follow are data only. Either way, every record read
+    /// afterwards resolves [`Record::get_named`] against these names. If a
+    /// name appears twice, the first column wins.
+    ///
+    /// # Example
+    /// ```
+    /// use scratchpad::csv_reader::CsvReader;
+    ///
+    /// let data = b"Name,University\nAlice,Harvard\nBob,MIT\nCarol,Harvard\n";
+    /// let mut reader = CsvReader::new(data);
+    /// assert_eq!(reader.headers().unwrap()[&b"University"[..]], 1);
+    /// let harvard = reader
+    ///     .filter_records(|r| r.get_named("University") == Some(b"Harvard"))
+    ///     .count();
+    /// assert_eq!(harvard, 2);
+    /// ```
+    pub fn headers(&mut self) -> Option<&Headers<'a>> {
+        if self.headers.is_none() {
+            let mut first = CsvReader::new(self.data);
+            first.advance()?;
+            let mut headers = Headers::with_capacity(first.fields.len());
+            for (i, f) in first.fields.iter().enumerate() {
+                headers.entry(&self.data[f.start..f.end]).or_insert(i);
+            }
+            if self.pos == 0 {
+                self.pos = first.pos;
+                self.terminated = first.terminated;
+            }
+            self.headers = Some(Rc::new(headers));
+        }
+        self.headers.as_deref()
+    }
+
+    #[inline]
+    fn record(&self, range: Range<usize>) -> Record<'_> {
+        let fields = Spans::Borrowed(&self.fields);
+        Record { data: self.data, range, fields, headers: self.headers.clone() }
+    }
+
+    /// Read the next record, or None at the end of the input.
+    ///
+    /// The record borrows the reader, so it must be dropped before the next
+    /// call; its fields are reused buffers, not allocations.
+    pub fn next_record(&mut self) -> Option<Record<'_>> {
+        let range = self.advance()?;
+        Some(self.record(range))
+    }
+
+    /// Find the fields of the next record and move past it.
+    fn advance(&mut self) -> Option<Range<usize>> {
+        let data = self.data;
+        if self.pos >= data.len() {
+            return None;
+        }
+
+        self.fields.clear();
+        let record_start = self.pos;
+        let mut pos = self.pos;
+
+        loop {
+            let field_start = pos;
+            let (field, next) = if data.get(pos) == Some(&b'"') {
+                let close = closing_quote(data, pos + 1);
+                let content = Field { start: pos + 1, end: close, quoted: true };
+                let after = (close + 1).min(data.len());
+                let next = memchr::memchr2(b',', b'\n', &data[after..]).map(|p| after + p);
+                (content, next)
+            } else {
+                // Unquoted: a quote is data, so only look for the delimiters
+                let next = memchr::memchr2(b',', b'\n', &data[pos..]).map(|p| pos + p);
+                let end = next.unwrap_or(data.len());
+                (Field { start: field_start, end, quoted: false }, next)
+            };
+
+            match next {
+                Some(p) if data[p] == b',' => {
+                    self.fields.push(field);
+                    pos = p + 1;
+                }
+                Some(p) => {
+                    self.fields.push(trim_cr(data, field));
+                    self.pos = p + 1;
+                    self.terminated = true;
+                    let end = if p > record_start && data[p - 1] == b'\r' {
+                        p - 1
+                    } else {
+                        p
+                    };
+                    return Some(record_start..end);
+                }
+                None => {
+                    self.fields.push(trim_cr(data, field));
+                    self.pos = data.len();
+                    self.terminated = false;
+                    return Some(record_start..data.len());
+                }
+            }
+        }
+    }
+
+    /// Move past the next record without finding its fields, by the same
+    /// rules as `advance`. False at the end of the input.
+    fn skip(&mut self) -> bool {
+        let data = self.data;
+        if self.pos >= data.len() {
+            return false;
+        }
+
+        let record_start = self.pos;
+        let mut pos = self.pos;
+        while let Some(p) = memchr::memchr2(b'\n', b'"', &data[pos..]) {
+            let p = pos + p;
+            if data[p] == b'\n' {
+                self.pos = p + 1;
+                self.terminated = true;
+                return true;
+            }
+            // Only a quote opening a field starts a quoted field
+            pos = if p == record_start || data[p - 1] == b',' {
+                (closing_quote(data, p + 1) + 1).min(data.len())
+            } else {
+                p + 1
+            };
+        }
+
+        self.pos = data.len();
+        self.terminated = false;
+        true
+    }
+
+    /// Iterate over the records as owned values borrowing only the input.
+    ///
+    /// Unlike [`CsvReader::next_record`], the records can outlive the call
+    /// to `next`, and [`Record::get`] returns slices of the input itself.
+    /// The field spans sit in one buffer shared with the iterator: while
+    /// each record is dropped before the next is read (the usual `for`
+    /// loop), that buffer is reused and reading allocates nothing. Keeping
+    /// a record alive makes the next one start a fresh buffer.
+    ///
+    /// # Example
+    /// ```
+    /// use scratchpad::csv_reader::CsvReader;
+    ///
+    /// let data = b"name,city\nAlice,Oslo\nBob,Rome\n";
+    /// let cities: Vec<&[u8]> = CsvReader::new(data)
+    ///     .records()
+    ///     .skip(1)
+    ///     .map(|r| r.get(1).unwrap())
+    ///     .collect();
+    /// assert_eq!(cities, vec![&b"Oslo"[..], b"Rome"]);
+    /// ```
+    pub fn records(self) -> Records<'a> {
+        Records { reader: self, spans: Rc::new(Vec::new()) }
+    }
+
+    /// Keep only the records for which `predicate` returns true.
+    ///
+    /// The resulting iterator yields each accepted record as it appears in
+    /// the input (a slice, no copy). Use [`FilterRecords::map_records`] to
+    /// transform the accepted records instead.
+    ///
+    /// # Example
+    /// ```
+    /// use scratchpad::csv_reader::CsvReader;
+    ///
+    /// let data = b"Alice,Harvard\nBob,MIT\nCarol,Harvard\n";
+    /// let harvard: Vec<&[u8]> = CsvReader::new(data)
+    ///     .filter_records(|r| r.get(1) == Some(b"Harvard"))
+    ///     .collect();
+    /// assert_eq!(harvard, vec![&b"Alice,Harvard"[..], b"Carol,Harvard"]);
+    /// ```
+    pub fn filter_records<P>(self, predicate: P) -> FilterRecords<'a, P>
+    where
+        P: FnMut(&Record<'_>) -> bool,
+    {
+        FilterRecords { reader: self, predicate }
+    }
+
+    /// Transform every record with `f`.
+    ///
+    /// # Example
+    /// ```
+    /// use scratchpad::csv_reader::CsvReader;
+    ///
+    /// let names: Vec<String> = CsvReader::new(b"Alice,1\nBob,2\n")
+    ///     .map_records(|r| String::from_utf8_lossy(r.get(0).unwrap()).into_owned())
+    ///     .collect();
+    /// assert_eq!(names, vec!["Alice", "Bob"]);
+    /// ```
+    pub fn map_records<T, F>(self, f: F) -> MapRecords<'a, fn(&Record<'_>) -> bool, F>
+    where
+        F: FnMut(&Record<'_>) -> T,
+    {
+        MapRecords { inner: self.filter_records(accept_all as fn(&Record<'_>) -> bool), f }
+    }
+
+    /// Parse only every `every_n`th record (the next one, then every
+    /// `every_n` after it), skipping the ones in between (0 is taken as 1).
+    ///
+    /// A skipped record costs one `memchr2('\n', '"')` jump unless it has
+    /// quoted fields, which are jumped over with `memchr('"')`: no field is
+    /// found. On the 200,000 row file of `csv_parse_bench`, every 100th
+    /// record is read at 2.4 GB/s, against 0.4 GB/s for all of them.
+    pub fn sample(self, every_n: usize) -> Sample<'a> {
+        Sample { records: self.records(), every_n: every_n.max(1), started: false }
+    }
+}
+
+fn accept_all(_: &Record<'_>) -> bool {
+    true
+}
+
+/// Every `every_n`th record of `data`, starting with the first, for schema
+/// inference or previews of huge inputs (see [`CsvReader::sample`]).
+///
+/// # Example
+/// ```
+/// use scratchpad::csv_reader::parse_csv_sample;
+///
+/// let data = b"id,note\n1,a\n2,\"multi\nline\"\n3,b\n4,c\n";
+/// let ids: Vec<&[u8]> = parse_csv_sample(data, 2).map(|r| r.get(0).unwrap()).collect();
+/// assert_eq!(ids, vec![&b"id"[..], b"2", b"4"]);
+/// ```
+pub fn parse_csv_sample(data: &[u8], every_n: usize) -> Sample<'_> {
+    CsvReader::new(data).sample(every_n)
+}
+
+/// Call `f` on every record of a file, reading it in 1 MB buffers.
+///
+/// A record cut off at the end of a buffer (including a quoted field still
+/// open there) is carried over and parsed again with the next read; the
+/// buffer grows if a single record does not fit. Returns the number of
+/// records.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::csv_reader::for_each_record;
+///
+/// let mut harvard = 0;
+/// for_each_record("researchers.csv", |r| {
+///     harvard += (r.get(2) == Some(b"Harvard")) as usize;
+///     Ok(())
+/// })
+/// .unwrap();
+/// ```
+pub fn for_each_record<F>(file_path: &str, f: F) -> io::Result<usize>
+where
+    F: FnMut(&Record<'_>) -> io::Result<()>,
+{
+    let mut file = File::open(file_path)?;
+    for_each_record_in_reader(&mut file, FILE_BUFFER_SIZE, f)
+}
+
+/// [`for_each_record`] over any reader, starting with `buffer_size` byte
+/// buffers.
+pub fn for_each_record_in_reader<R, F>(
+    reader: &mut R,
+    buffer_size: usize,
+    mut f: F,
+) -> io::Result<usize>
+where
+    R: Read,
+    F: FnMut(&Record<'_>) -> io::Result<()>,
+{
+    let mut buffer = ScratchPool::global().take(buffer_size.max(1));
+    let mut fields = Vec::new();
+    let mut carry = 0;
+    let mut records = 0;
+
+    loop {
+        if carry == buffer.len() {
+            let len = buffer.len();
+            buffer.resize(len * 2, 0);
+        }
+
+        let bytes_read = match reader.read(&mut buffer[carry..]) {
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let eof = bytes_read == 0;
+        let filled = carry + bytes_read;
+
+        let mut csv = CsvReader::new(&buffer[..filled]);
+        csv.fields = fields;
+        let mut consumed = filled;
+        while let Some(range) = csv.advance() {
+            if !csv.terminated && !eof {
+                // Incomplete: parse it again once more data is in
+                consumed = range.start;
+                break;
+            }
+            f(&csv.record(range))?;
+            records += 1;
+        }
+        fields = csv.fields;
+
+        if eof {
+            return Ok(records);
+        }
+        buffer.copy_within(consumed..filled, 0);
+        carry = filled - consumed;
+    }
+}
+
+/// Position of the quote closing a quoted field whose content starts at `pos`.
+///
+/// Doubled quotes are skipped. Returns `data.len()` if the field is unclosed.
+#[inline]
+fn closing_quote(data: &[u8], mut pos: usize) -> usize {
+    while let Some(p) = memchr::memchr(b'"', &data[pos..]) {
+        let q = pos + p;
+        if data.get(q + 1) != Some(&b'"') {
+            return q;
+        }
+        pos = q + 2;
+    }
+    data.len()
+}
+
+/// Drop a `\r` at the end of an unquoted field ending a `\r\n` record.
+#[inline]
+fn trim_cr(data: &[u8], mut field: Field) -> Field {
+    if !field.quoted && field.end > field.start && data[field.end - 1] == b'\r' {
+        field.end -= 1;
+    }
+    field
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                Adapters
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Iterator over records. Created by [`CsvReader::records`].
+pub struct Records<'a> {
+    reader: CsvReader<'a>,
+    spans: Rc<Vec<Field>>,
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Record<'a>;
+
+    fn next(&mut self) -> Option<Record<'a>> {
+        let range = self.reader.advance()?;
+        if Rc::get_mut(&mut self.spans).is_none() {
+            // The previous record is still alive: leave its spans to it
+            self.spans = Rc::new(Vec::new());
+        }
+        // Trade buffers with the reader: it gets the recycled one back
+        let spans = Rc::get_mut(&mut self.spans).unwrap();
+        std::mem::swap(spans, &mut self.reader.fields);
+
+        let fields = Spans::Shared(Rc::clone(&self.spans));
+        let headers = self.reader.headers.clone();
+        Some(Record { data: self.reader.data, range, fields, headers })
+    }
+}
+
+/// Every nth record. Created by [`CsvReader::sample`] or [`parse_csv_sample`].
+pub struct Sample<'a> {
+    records: Records<'a>,
+    every_n: usize,
+    started: bool,
+}
+
+impl<'a> Iterator for Sample<'a> {
+    type Item = Record<'a>;
+
+    fn next(&mut self) -> Option<Record<'a>> {
+        if self.started {
+            for _ in 1..self.every_n {
+                if !self.records.reader.skip() {
+                    return None;
+                }
+            }
+        }
+        self.started = true;
+        self.records.next()
+    }
+}
+
+/// Records accepted by a predicate. Created by [`CsvReader::filter_records`].
+pub struct FilterRecords<'a, P> {
+    reader: CsvReader<'a>,
+    predicate: P,
+}
+
+impl<'a, P> FilterRecords<'a, P>
+where
+    P: FnMut(&Record<'_>) -> bool,
+{
+    /// Read the next accepted record.
+    pub fn next_record(&mut self) -> Option<Record<'_>> {
+        loop {
+            let range = self.reader.advance()?;
+            let record = self.reader.record(range);
+            if (self.predicate)(&record) {
+                // Re-borrow: the predicate's borrow has ended
+                let range = record.range;
+                return Some(self.reader.record(range));
+            }
+        }
+    }
+
+    /// Transform the accepted records with `f`; rejected ones are never mapped.
+    pub fn map_records<T, F>(self, f: F) -> MapRecords<'a, P, F>
+    where
+        F: FnMut(&Record<'_>) -> T,
+    {
+        MapRecords { inner: self, f }
+    }
+}
+
+impl<'a, P> Iterator for FilterRecords<'a, P>
+where
+    P: FnMut(&Record<'_>) -> bool,
+{
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let data = self.reader.data;
+        self.next_record().map(|r| &data[r.range])
+    }
+}
+
+/// Mapped records. Created by [`CsvReader::map_records`] or
+/// [`FilterRecords::map_records`].
+pub struct MapRecords<'a, P, F> {
+    inner: FilterRecords<'a, P>,
+    f: F,
+}
+
+impl<P, F, T> Iterator for MapRecords<'_, P, F>
+where
+    P: FnMut(&Record<'_>) -> bool,
+    F: FnMut(&Record<'_>) -> T,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let record = self.inner.next_record()?;
+        Some((self.f)(&record))
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_state_machine::parse_csv_if_else;
+
+    fn read_all(data: &[u8]) -> Vec<Vec<Vec<u8>>> {
+        CsvReader::new(data).map_records(|r| r.to_vec()).collect()
+    }
+
+    #[test]
+    fn test_simple() {
+        assert_eq!(
+            read_all(b"a,b,c\n1,2,3\n"),
+            vec![
+                vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()],
+                vec![b"1".to_vec(), b"2".to_vec(), b"3".to_vec()]
+            ]
+        );
+        assert!(read_all(b"").is_empty());
+        assert_eq!(read_all(b"x"), vec![vec![b"x".to_vec()]]);
+        assert_eq!(read_all(b"a,,\n"), vec![vec![b"a".to_vec(), vec![], vec![]]]);
+    }
+
+    #[test]
+    fn test_quoted() {
+        let data = b"\"hello, world\",\"line\nbreak\",\"say \"\"hi\"\"\"\n";
+        let mut reader = CsvReader::new(data);
+        let record = reader.next_record().unwrap();
+        assert_eq!(record.len(), 3);
+        assert_eq!(record.get(0), Some(&b"hello, world"[..]));
+        assert_eq!(record.get(1), Some(&b"line\nbreak"[..]));
+        assert_eq!(record.get(2), Some(&b"say \"\"hi\"\""[..]));
+        assert!(record.is_quoted(0));
+        assert_eq!(record.as_bytes(), &data[..data.len() - 1]);
+        assert!(reader.next_record().is_none());
+    }
+
+    #[test]
+    fn test_lenient_cases() {
+        // Quote inside an unquoted field, junk after a closing quote, unclosed quote
+        assert_eq!(read_all(b"ab\"c,d\n"), vec![vec![b"ab\"c".to_vec(), b"d".to_vec()]]);
+        assert_eq!(read_all(b"\"ab\"xy,d\n"), vec![vec![b"ab".to_vec(), b"d".to_vec()]]);
+        assert_eq!(read_all(b"\"ab,d\n"), vec![vec![b"ab,d\n".to_vec()]]);
+    }
+
+    #[test]
+    fn test_crlf() {
+        let data = b"a,b\r\n\"x\",y\r\n";
+        let mut reader = CsvReader::new(data);
+        assert_eq!(reader.next_record().unwrap().as_bytes(), b"a,b");
+        let record = reader.next_record().unwrap();
+        assert_eq!(record.get(0), Some(&b"x"[..]));
+        assert_eq!(record.get(1), Some(&b"y"[..]));
+        assert_eq!(record.get_raw(0), Some(&b"\"x\""[..]));
+        assert_eq!(record.get_raw(1), Some(&b"y"[..]));
+        assert_eq!(record.offset(), 5);
+    }
+
+    #[test]
+    fn test_filter_then_map() {
+        let data = b"name,uni\nAlice,Harvard\nBob,MIT\nCarol,Harvard\n";
+        let mut mapped = 0;
+        let names: Vec<Vec<u8>> = CsvReader::new(data)
+            .filter_records(|r| r.get(1) == Some(b"Harvard"))
+            .map_records(|r| {
+                mapped += 1;
+                r.get(0).unwrap().to_vec()
+            })
+            .collect();
+        assert_eq!(names, vec![b"Alice".to_vec(), b"Carol".to_vec()]);
+        assert_eq!(mapped, 2);
+    }
+
+    #[test]
+    fn test_for_each_record_across_buffers() {
+        let data = b"id,text\n1,\"multi\nline, with \"\"quotes\"\"\"\n2,plain\r\n3,\"\"\n4,last";
+        let expected = read_all(data);
+
+        for buffer_size in [1, 2, 3, 7, 16, 1 << 20] {
+            let mut records = Vec::new();
+            let count = for_each_record_in_reader(&mut &data[..], buffer_size, |r| {
+                records.push(r.to_vec());
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(count, 5);
+            assert_eq!(records, expected, "Mismatch for buffer_size={}", buffer_size);
+        }
+    }
+
+    #[test]
+    fn test_counts_match_if_else() {
+        let mut data = b"h1,h2\n".to_vec();
+        let mut rng = 5u64;
+        for _ in 0..500 {
+            let fields = 1 + ((rng >> 16) % 5) as usize;
+            for f in 0..fields {
+                rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+                if f > 0 {
+                    data.push(b',');
+                }
+                match (rng >> 16) % 4 {
+                    0 => data.extend_from_slice(b"\"q,\"\"x\"\"\ny\""),
+                    1 => {}
+                    _ => data.extend_from_slice(format!("v{}", rng % 1000).as_bytes()),
+                }
+            }
+            data.push(b'\n');
+        }
+
+        let (fields, rows) = parse_csv_if_else(&data);
+        let mut reader = CsvReader::new(&data);
+        let (mut my_fields, mut my_rows) = (0, 0);
+        while let Some(record) = reader.next_record() {
+            my_fields += record.len();
+            my_rows += 1;
+        }
+        assert_eq!((my_fields, my_rows), (fields, rows));
+    }
+
+    #[test]
+    fn test_records_iterator() {
+        let data = b"a,\"b,c\"\n1,2,3\r\nx\n";
+        let records: Vec<Record<'_>> = CsvReader::new(data).records().collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].to_vec(), vec![b"a".to_vec(), b"b,c".to_vec()]);
+        assert_eq!(records[1].to_vec(), vec![b"1".to_vec(), b"2".to_vec(), b"3".to_vec()]);
+        assert_eq!(records[2].get(0), Some(&b"x"[..]));
+
+        // Fields outlive their record
+        let mut reader = CsvReader::new(data).records();
+        let first = reader.next().unwrap().get(1).unwrap();
+        let second = reader.next().unwrap().get(2).unwrap();
+        assert_eq!((first, second), (&b"b,c"[..], &b"3"[..]));
+        assert!(reader.next().is_some() && reader.next().is_none());
+
+        // Matches the lending API
+        let lending: Vec<Vec<Vec<u8>>> = read_all(data);
+        let owned: Vec<Vec<Vec<u8>>> = CsvReader::new(data).records().map(|r| r.to_vec()).collect();
+        assert_eq!(lending, owned);
+    }
+
+    #[test]
+    fn test_headers() {
+        let data = b"Name,\"University\",Name\nAlice,Harvard\nBob\nCarol,Harvard,x\n";
+        let mut reader = CsvReader::new(data);
+        let headers = reader.headers().unwrap();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[&b"Name"[..]], 0);
+        assert_eq!(headers[&b"University"[..]], 1);
+
+        // The header row is consumed; short rows have no value for the column
+        let first = reader.next_record().unwrap();
+        assert_eq!(first.get_named("Name"), Some(&b"Alice"[..]));
+        assert_eq!(first.get_named(b"University"), Some(&b"Harvard"[..]));
+        assert_eq!(first.get_named("GPA"), None);
+        let universities: Vec<_> =
+            reader.records().map(|r| r.get_named("University")).collect();
+        assert_eq!(universities, vec![None, Some(&b"Harvard"[..])]);
+
+        // Read late, the headers do not move the reader
+        let mut reader = CsvReader::new(data);
+        assert_eq!(reader.next_record().unwrap().get_named("Name"), None);
+        reader.headers();
+        assert_eq!(reader.next_record().unwrap().get_named("Name"), Some(&b"Alice"[..]));
+
+        assert!(CsvReader::new(b"").headers().is_none());
+    }
+
+    #[test]
+    fn test_sample_matches_step_by() {
+        let rows: [&[u8]; 9] = [
+            b"plain,row\n",
+            b"\"quoted\nnewline\",x\n",
+            b"a\"quote,in data\n",
+            b"\"x\"junk\"y,z\n",
+            b"\"doubled \"\"\n\"\"\"\n",
+            b"crlf,row\r\n",
+            b"1,\"2\",\"\n3\"\n",
+            b"\n",
+            b"last,x\n",
+        ];
+        let mut data = Vec::new();
+        for i in 0..100 {
+            data.extend_from_slice(rows[i * 7 % rows.len()]);
+        }
+
+        for input in [&data[..], &data[..data.len() - 1], b"\"unclosed,\nx", b""] {
+            let all: Vec<_> = CsvReader::new(input).map_records(|r| r.to_vec()).collect();
+            for every_n in [0, 1, 2, 3, 7, 1000] {
+                let sampled: Vec<_> =
+                    parse_csv_sample(input, every_n).map(|r| r.to_vec()).collect();
+                let expected: Vec<_> = all.iter().step_by(every_n.max(1)).cloned().collect();
+                assert_eq!(sampled, expected, "every {}", every_n);
+            }
+        }
+
+        // After the header row
+        let mut reader = CsvReader::new(b"name,n\na,1\nb,2\nc,3\n");
+        reader.headers();
+        let sampled: Vec<_> = reader.sample(2).map(|r| r.get_named("name").unwrap()).collect();
+        assert_eq!(sampled, vec![&b"a"[..], b"c"]);
+    }
+}
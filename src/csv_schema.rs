@@ -0,0 +1,368 @@
+//! Header-aware schema inference and diffing for raw CSV files.
+//!
+//! A data-contract check usually only needs to know whether a file still
+//! looks like it did yesterday: same columns, same types, roughly the same
+//! amount of missing data. That can be answered from the header and a sample
+//! of rows, long before the whole file is processed.
+//!
+//! [`infer_schema`] reads the first [`SAMPLE_BYTES`] of a file, drops a
+//! record cut off by the sample boundary, and infers per column:
+//!
+//!   type:   Empty < Integer < Float < Text      (Boolean only widens to Text)
+//!   stats:  empty count, min/max of numeric values, max field length
+//!
+//! [`schema_diff`] matches the columns of two files by header name and
+//! reports added, removed and moved columns, type changes, and columns whose
+//! share of empty values moved by more than [`EMPTY_RATE_TOLERANCE`].
+
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::{self, Read},
+};
+
+use crate::{csv_reader::CsvReader, json_number::is_valid_json_number};
+
+/// How much of each file [`schema_diff`] samples.
+pub const SAMPLE_BYTES: usize = 4 << 20;
+
+/// Largest change in the share of empty values not reported as a difference.
+pub const EMPTY_RATE_TOLERANCE: f64 = 0.05;
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                              Type Inference
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Inferred type of a column: the narrowest type all sampled values fit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    /// Only empty values were seen.
+    Empty,
+    Boolean,
+    Integer,
+    Float,
+    Text,
+}
+
+impl ColumnType {
+    /// Type of a single non-empty value.
+    pub fn of(value: &[u8]) -> ColumnType {
+        let digits = value.strip_prefix(b"-").unwrap_or(value);
+        if !digits.is_empty() && digits.iter().all(u8::is_ascii_digit) {
+            ColumnType::Integer
+        } else if is_valid_json_number(value) {
+            ColumnType::Float
+        } else if value.eq_ignore_ascii_case(b"true") || value.eq_ignore_ascii_case(b"false") {
+            ColumnType::Boolean
+        } else {
+            ColumnType::Text
+        }
+    }
+
+    /// The narrowest type that holds values of both `self` and `other`.
+    pub fn widen(self, other: ColumnType) -> ColumnType {
+        use ColumnType::*;
+        match (self, other) {
+            (Empty, t) | (t, Empty) => t,
+            (a, b) if a == b => a,
+            (Integer, Float) | (Float, Integer) => Float,
+            _ => Text,
+        }
+    }
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColumnType::Empty => "empty",
+            ColumnType::Boolean => "boolean",
+            ColumnType::Integer => "integer",
+            ColumnType::Float => "float",
+            ColumnType::Text => "text",
+        };
+        f.write_str(name)
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Schema
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// What the sample says about one column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnSchema {
+    pub name: String,
+    pub ty: ColumnType,
+    /// Sampled rows where the field is empty or missing.
+    pub empty: usize,
+    /// Smallest and largest numeric value, if any value parsed as a number.
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Longest raw field, in bytes.
+    pub max_len: usize,
+}
+
+impl ColumnSchema {
+    fn new(name: &[u8]) -> Self {
+        ColumnSchema {
+            name: String::from_utf8_lossy(name).into_owned(),
+            ty: ColumnType::Empty,
+            empty: 0,
+            min: None,
+            max: None,
+            max_len: 0,
+        }
+    }
+
+    fn observe(&mut self, value: &[u8]) {
+        if value.is_empty() {
+            self.empty += 1;
+            return;
+        }
+
+        let ty = ColumnType::of(value);
+        self.ty = self.ty.widen(ty);
+        self.max_len = self.max_len.max(value.len());
+
+        if matches!(ty, ColumnType::Integer | ColumnType::Float) {
+            // Validated as a number above, so it is ASCII
+            if let Ok(x) = std::str::from_utf8(value).unwrap().parse::<f64>() {
+                self.min = Some(self.min.map_or(x, |m| m.min(x)));
+                self.max = Some(self.max.map_or(x, |m| m.max(x)));
+            }
+        }
+    }
+}
+
+/// Columns of a CSV file, inferred from its header and a sample of rows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    pub columns: Vec<ColumnSchema>,
+    /// Data rows sampled (header excluded).
+    pub rows: usize,
+}
+
+impl Schema {
+    /// Share of sampled rows where column `i` is empty (0.0 with no rows).
+    pub fn empty_rate(&self, i: usize) -> f64 {
+        if self.rows == 0 {
+            0.0
+        } else {
+            self.columns[i].empty as f64 / self.rows as f64
+        }
+    }
+}
+
+/// Infer the schema of CSV data whose first record is the header.
+///
+/// # Example
+/// ```
+/// use scratchpad::csv_schema::{infer_schema_from_bytes, ColumnType};
+///
+/// let schema = infer_schema_from_bytes(b"id,price,note\n1,9.5,\n2,12,cheap\n");
+/// assert_eq!(schema.rows, 2);
+/// assert_eq!(schema.columns[0].ty, ColumnType::Integer);
+/// assert_eq!(schema.columns[1].ty, ColumnType::Float);
+/// assert_eq!(schema.columns[2].empty, 1);
+/// ```
+pub fn infer_schema_from_bytes(data: &[u8]) -> Schema {
+    let mut reader = CsvReader::new(data);
+    let mut columns: Vec<ColumnSchema> = match reader.next_record() {
+        Some(header) => header.iter().map(ColumnSchema::new).collect(),
+        None => Vec::new(),
+    };
+
+    let mut rows = 0;
+    while let Some(record) = reader.next_record() {
+        rows += 1;
+        for (i, column) in columns.iter_mut().enumerate() {
+            column.observe(record.get(i).unwrap_or_default());
+        }
+    }
+
+    Schema { columns, rows }
+}
+
+/// Infer the schema of a CSV file from its header and first [`SAMPLE_BYTES`].
+pub fn infer_schema(file_path: &str) -> io::Result<Schema> {
+    let mut sample = Vec::new();
+    File::open(file_path)?
+        .take(SAMPLE_BYTES as u64)
+        .read_to_end(&mut sample)?;
+    Ok(infer_schema_from_bytes(complete_records(&sample, SAMPLE_BYTES)))
+}
+
+/// Drop the record cut off at the end of a sample that filled `limit` bytes.
+///
+/// A shorter sample is the whole file, so its last record is complete even
+/// without a terminator.
+fn complete_records(sample: &[u8], limit: usize) -> &[u8] {
+    if sample.len() < limit {
+        return sample;
+    }
+    match memchr::memrchr(b'\n', sample) {
+        Some(p) => &sample[..p + 1],
+        None => sample,
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                  Diff
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Differences between two schemas, with columns matched by name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SchemaDiff {
+    /// Only in the second file.
+    pub added: Vec<String>,
+    /// Only in the first file.
+    pub removed: Vec<String>,
+    /// In both, at a different position: (name, index in a, index in b).
+    pub moved: Vec<(String, usize, usize)>,
+    /// In both, with a different inferred type. Columns that are all empty
+    /// on one side are not reported.
+    pub type_changes: Vec<(String, ColumnType, ColumnType)>,
+    /// In both, with empty rates further apart than [`EMPTY_RATE_TOLERANCE`].
+    pub empty_rate_changes: Vec<(String, f64, f64)>,
+}
+
+impl SchemaDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.moved.is_empty()
+            && self.type_changes.is_empty()
+            && self.empty_rate_changes.is_empty()
+    }
+}
+
+/// Compare two inferred schemas.
+pub fn diff_schemas(a: &Schema, b: &Schema) -> SchemaDiff {
+    let index_a: HashMap<&str, usize> = a
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (c.name.as_str(), i))
+        .collect();
+    let index_b: HashMap<&str, usize> = b
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (c.name.as_str(), i))
+        .collect();
+
+    let mut diff = SchemaDiff::default();
+
+    for column in &a.columns {
+        if !index_b.contains_key(column.name.as_str()) {
+            diff.removed.push(column.name.clone());
+        }
+    }
+
+    for (j, column) in b.columns.iter().enumerate() {
+        let Some(&i) = index_a.get(column.name.as_str()) else {
+            diff.added.push(column.name.clone());
+            continue;
+        };
+
+        if i != j {
+            diff.moved.push((column.name.clone(), i, j));
+        }
+
+        let (ty_a, ty_b) = (a.columns[i].ty, column.ty);
+        if ty_a != ty_b && ty_a != ColumnType::Empty && ty_b != ColumnType::Empty {
+            diff.type_changes.push((column.name.clone(), ty_a, ty_b));
+        }
+
+        let (rate_a, rate_b) = (a.empty_rate(i), b.empty_rate(j));
+        if (rate_a - rate_b).abs() > EMPTY_RATE_TOLERANCE {
+            diff.empty_rate_changes
+                .push((column.name.clone(), rate_a, rate_b));
+        }
+    }
+
+    diff
+}
+
+/// Compare the schemas of two CSV files, inferred from samples.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::csv_schema::schema_diff;
+///
+/// let diff = schema_diff("yesterday.csv", "today.csv").unwrap();
+/// for (name, before, after) in &diff.type_changes {
+///     println!("{}: {} -> {}", name, before, after);
+/// }
+/// ```
+pub fn schema_diff(a: &str, b: &str) -> io::Result<SchemaDiff> {
+    Ok(diff_schemas(&infer_schema(a)?, &infer_schema(b)?))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_type() {
+        assert_eq!(ColumnType::of(b"42"), ColumnType::Integer);
+        assert_eq!(ColumnType::of(b"-7"), ColumnType::Integer);
+        assert_eq!(ColumnType::of(b"3.5e2"), ColumnType::Float);
+        assert_eq!(ColumnType::of(b"TRUE"), ColumnType::Boolean);
+        assert_eq!(ColumnType::of(b"-"), ColumnType::Text);
+        assert_eq!(ColumnType::of(b"12ab"), ColumnType::Text);
+
+        use ColumnType::*;
+        assert_eq!(Empty.widen(Integer), Integer);
+        assert_eq!(Integer.widen(Float), Float);
+        assert_eq!(Boolean.widen(Integer), Text);
+        assert_eq!(Float.widen(Text), Text);
+    }
+
+    #[test]
+    fn test_infer_schema() {
+        let schema = infer_schema_from_bytes(b"n,s\n1,a\n2.5,\n-3,b\n4,last");
+        assert_eq!(schema.rows, 4);
+        assert_eq!(schema.columns[0].ty, ColumnType::Float);
+        assert_eq!(schema.columns[0].min, Some(-3.0));
+        assert_eq!(schema.columns[0].max, Some(4.0));
+        assert_eq!(schema.columns[1].ty, ColumnType::Text);
+        assert_eq!(schema.columns[1].empty, 1);
+        assert_eq!(schema.columns[1].max_len, 4);
+    }
+
+    #[test]
+    fn test_complete_records() {
+        assert_eq!(complete_records(b"a\nb\ncut", 7), b"a\nb\n");
+        assert_eq!(complete_records(b"a\nb\nlast", 100), b"a\nb\nlast");
+        assert_eq!(complete_records(b"a\nb\n", 4), b"a\nb\n");
+    }
+
+    #[test]
+    fn test_schema_diff() {
+        let path_a = "/tmp/test_schema_diff_a.csv";
+        let path_b = "/tmp/test_schema_diff_b.csv";
+        std::fs::write(path_a, b"id,name,age,zip\n1,Ann,30,123\n2,Bob,41,456\n").unwrap();
+        std::fs::write(path_b, b"name,id,age,email\nAnn,1,,a@x\nBob,2,unknown,b@x\n").unwrap();
+
+        let diff = schema_diff(path_a, path_b).unwrap();
+        assert_eq!(diff.added, vec!["email"]);
+        assert_eq!(diff.removed, vec!["zip"]);
+        assert_eq!(diff.moved, vec![("name".to_string(), 1, 0), ("id".to_string(), 0, 1)]);
+        assert_eq!(
+            diff.type_changes,
+            vec![("age".to_string(), ColumnType::Integer, ColumnType::Text)]
+        );
+        assert_eq!(diff.empty_rate_changes, vec![("age".to_string(), 0.0, 0.5)]);
+
+        assert!(schema_diff(path_a, path_a).unwrap().is_empty());
+
+        std::fs::remove_file(path_a).ok();
+        std::fs::remove_file(path_b).ok();
+    }
+}
@@ -0,0 +1,363 @@
+//! Streaming CSV-to-CSV transforms: rewrite a file record by record.
+//!
+//! Every transform reads the input with [`for_each_record`] (1 MB buffers,
+//! records never split) and writes each output record with one write call
+//! into a buffered writer, so memory stays flat on multi-GB files.
+//!
+//! Fields that are not rewritten are passed through: a field that was quoted
+//! in the input is written back with its raw content (already escaped)
+//! between quotes, and an unquoted one is copied as is unless the CSV byte-set
+//! detector from [`crate::byte_set`] finds something that needs quoting.
+
+use std::io::{self, BufWriter, IoSlice, Write};
+
+use crate::{
+    byte_set::CSV,
+    csv_reader::{for_each_record, Record},
+    csv_write::write_field,
+    hash::hash64,
+};
+
+/// Append field `i` of `record` unchanged (see the module docs).
+#[inline]
+pub(crate) fn push_raw_field(record: &Record<'_>, i: usize, out: &mut Vec<u8>) {
+    let raw = record.get(i).unwrap_or_default();
+    if record.is_quoted(i) {
+        out.push(b'"');
+        out.extend_from_slice(raw);
+        out.push(b'"');
+    } else if CSV.has_any(raw) {
+        // A stray quote (or a lone \r) in an unquoted field: quote it properly
+        write_field(raw, out);
+    } else {
+        out.extend_from_slice(raw);
+    }
+}
+
+/// Append all fields of `record` unchanged, followed by `\n`.
+fn push_raw_record(record: &Record<'_>, out: &mut Vec<u8>) {
+    for i in 0..record.len() {
+        if i > 0 {
+            out.push(b',');
+        }
+        push_raw_field(record, i, out);
+    }
+    out.push(b'\n');
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Masking
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Replace the values of `columns` (0-based) with `mask_value` in every record.
+///
+/// The header (first record) is copied unchanged. Columns past the end of a
+/// short record are ignored. Returns the number of records written.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::csv_transform::mask_columns;
+///
+/// let mut out = std::fs::File::create("people_masked.csv").unwrap();
+/// // Hide the email (1) and phone (3) columns
+/// mask_columns("people.csv", &[1, 3], b"***", &mut out).unwrap();
+/// ```
+pub fn mask_columns<W: Write>(
+    file_path: &str,
+    columns: &[usize],
+    mask_value: &[u8],
+    out: &mut W,
+) -> io::Result<usize> {
+    let max_column = columns.iter().max().map_or(0, |&c| c + 1);
+    let mut masked = vec![false; max_column];
+    for &c in columns {
+        masked[c] = true;
+    }
+
+    let mut mask = Vec::new();
+    write_field(mask_value, &mut mask);
+
+    let mut writer = BufWriter::new(out);
+    let mut line = Vec::new();
+    let mut is_header = true;
+
+    let records = for_each_record(file_path, |record| {
+        line.clear();
+        for i in 0..record.len() {
+            if i > 0 {
+                line.push(b',');
+            }
+            if !is_header && masked.get(i).copied().unwrap_or(false) {
+                line.extend_from_slice(&mask);
+            } else {
+                push_raw_field(record, i, &mut line);
+            }
+        }
+        line.push(b'\n');
+        is_header = false;
+        writer.write_all(&line)
+    })?;
+
+    writer.flush()?;
+    Ok(records)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                Projection
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Projection never changes a field, so nothing is copied into a line buffer:
+// each output record is a list of slices pointing into the read buffer
+//
+//   [field c0] [","] [field c1] [","] ... [field cn] ["\n"]
+//
+// handed to the writer in one vectored write.
+
+/// Write all of `slices` with vectored writes.
+fn write_all_vectored<W: Write>(writer: &mut W, mut slices: &mut [IoSlice<'_>]) -> io::Result<()> {
+    while !slices.is_empty() {
+        match writer.write_vectored(slices) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => IoSlice::advance_slices(&mut slices, n),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Write only `columns` (0-based, in the given order) of every record.
+///
+/// Columns may be repeated or reordered; the header is projected like any
+/// other record. A column past the end of a short record is written empty.
+/// Fields are copied byte for byte from the input (quotes included), so the
+/// output is exactly as well-formed as the input. Returns the number of
+/// records written.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::csv_transform::project_to_file;
+///
+/// let mut out = std::fs::File::create("narrow.csv").unwrap();
+/// // Keep columns 12, 0 and 7 of a 300-column file, in that order
+/// project_to_file("wide.csv", &[12, 0, 7], &mut out).unwrap();
+/// ```
+pub fn project_to_file<W: Write>(
+    file_path: &str,
+    columns_in_order: &[usize],
+    out: &mut W,
+) -> io::Result<usize> {
+    let mut writer = BufWriter::new(out);
+
+    let records = for_each_record(file_path, |record| {
+        let mut slices = Vec::with_capacity(2 * columns_in_order.len() + 1);
+        for (k, &c) in columns_in_order.iter().enumerate() {
+            if k > 0 {
+                slices.push(IoSlice::new(b","));
+            }
+            slices.push(IoSlice::new(record.get_raw(c).unwrap_or_default()));
+        }
+        slices.push(IoSlice::new(b"\n"));
+        write_all_vectored(&mut writer, &mut slices)
+    })?;
+
+    writer.flush()?;
+    Ok(records)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                Splitting
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Each record goes to one side based on the hash of its key, not on a random
+// draw, so the same key lands in the same partition on every run and on every
+// machine (and all rows sharing a key stay together):
+//
+//   hash64(key) < ratio * 2^64   ->  out_a
+//   otherwise                    ->  out_b
+
+/// Seed for the split hash; changing it reshuffles every split.
+const SPLIT_SEED: u64 = 0;
+
+/// Number of records written to each side by [`split_dataset`] (header excluded).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SplitCounts {
+    pub a: usize,
+    pub b: usize,
+}
+
+/// Split a CSV file in two by hashing `key_column` (0-based) of each record.
+///
+/// About `ratio` of the records (`0.0..=1.0`) go to `out_a` and the rest to
+/// `out_b`. The header is written to both outputs. Records too short to have
+/// the key column are hashed as if the key were empty.
+///
+/// The split only depends on the key bytes, so it is stable across runs:
+/// rerunning on a file with appended rows keeps every old row on its side.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::csv_transform::split_dataset;
+/// use std::fs::File;
+///
+/// let mut train = File::create("train.csv").unwrap();
+/// let mut test = File::create("test.csv").unwrap();
+/// // 80/20 split, keyed on the user id in column 0
+/// let counts = split_dataset("events.csv", 0.8, 0, &mut train, &mut test).unwrap();
+/// println!("{} train, {} test", counts.a, counts.b);
+/// ```
+pub fn split_dataset<A: Write, B: Write>(
+    file_path: &str,
+    ratio: f64,
+    key_column: usize,
+    out_a: &mut A,
+    out_b: &mut B,
+) -> io::Result<SplitCounts> {
+    if !(0.0..=1.0).contains(&ratio) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("split ratio {} is not in 0.0..=1.0", ratio),
+        ));
+    }
+    // `as` saturates, so 1.0 maps to u64::MAX and a hash of exactly
+    // u64::MAX is the only one that could land on the wrong side
+    let threshold = (ratio * 2f64.powi(64)) as u64;
+
+    let mut writer_a = BufWriter::new(out_a);
+    let mut writer_b = BufWriter::new(out_b);
+    let mut counts = SplitCounts { a: 0, b: 0 };
+    let mut line = Vec::new();
+    let mut is_header = true;
+
+    for_each_record(file_path, |record| {
+        line.clear();
+        push_raw_record(record, &mut line);
+
+        if is_header {
+            is_header = false;
+            writer_a.write_all(&line)?;
+            return writer_b.write_all(&line);
+        }
+
+        let key = record.get(key_column).unwrap_or_default();
+        if hash64(key, SPLIT_SEED) < threshold {
+            counts.a += 1;
+            writer_a.write_all(&line)
+        } else {
+            counts.b += 1;
+            writer_b.write_all(&line)
+        }
+    })?;
+
+    writer_a.flush()?;
+    writer_b.flush()?;
+    Ok(counts)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mask_columns() {
+        let path = "/tmp/test_mask_columns.csv";
+        std::fs::write(
+            path,
+            b"name,email,city\r\nAlice,a@x.com,\"Paris, FR\"\nBob,\"b\"\"@x.com\",Rome\nCarol\n",
+        )
+        .unwrap();
+
+        let mut out = Vec::new();
+        assert_eq!(mask_columns(path, &[1, 5], b"***", &mut out).unwrap(), 4);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "name,email,city\nAlice,***,\"Paris, FR\"\nBob,***,Rome\nCarol\n"
+        );
+
+        // A mask value that itself needs quoting
+        let mut out = Vec::new();
+        mask_columns(path, &[0], b"x,y", &mut out).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .contains("\n\"x,y\",\"b\"\"@x.com\",Rome\n"));
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_untouched_fields_round_trip() {
+        let path = "/tmp/test_mask_roundtrip.csv";
+        let data = b"a,\"b,\"\"c\"\"\",\"multi\nline\",d\"e\n";
+        std::fs::write(path, data).unwrap();
+
+        let mut out = Vec::new();
+        mask_columns(path, &[], b"", &mut out).unwrap();
+        assert_eq!(out, b"a,\"b,\"\"c\"\"\",\"multi\nline\",\"d\"\"e\"\n");
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_split_dataset() {
+        let path = "/tmp/test_split_dataset.csv";
+        let mut data = b"id,value\n".to_vec();
+        for i in 0..2000 {
+            data.extend_from_slice(format!("user{},{}\n", i % 500, i).as_bytes());
+        }
+        std::fs::write(path, &data).unwrap();
+
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        let counts = split_dataset(path, 0.8, 0, &mut a, &mut b).unwrap();
+        assert_eq!(counts.a + counts.b, 2000);
+        assert!((1400..1800).contains(&counts.a), "Unbalanced split: {:?}", counts);
+        assert!(a.starts_with(b"id,value\n") && b.starts_with(b"id,value\n"));
+
+        // Every key lands on exactly one side
+        let keys = |out: &[u8]| -> std::collections::HashSet<Vec<u8>> {
+            out.split(|&c| c == b'\n')
+                .skip(1)
+                .filter(|l| !l.is_empty())
+                .map(|l| l.split(|&c| c == b',').next().unwrap().to_vec())
+                .collect()
+        };
+        assert!(keys(&a).is_disjoint(&keys(&b)));
+
+        // Stable across runs
+        let (mut a2, mut b2) = (Vec::new(), Vec::new());
+        split_dataset(path, 0.8, 0, &mut a2, &mut b2).unwrap();
+        assert_eq!((a, b), (a2, b2));
+
+        // Edge ratios
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        assert_eq!(
+            split_dataset(path, 0.0, 0, &mut a, &mut b).unwrap(),
+            SplitCounts { a: 0, b: 2000 }
+        );
+        assert_eq!(
+            split_dataset(path, 1.0, 0, &mut a, &mut b).unwrap(),
+            SplitCounts { a: 2000, b: 0 }
+        );
+        assert!(split_dataset(path, 1.5, 0, &mut a, &mut b).is_err());
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_project_to_file() {
+        let path = "/tmp/test_project_to_file.csv";
+        std::fs::write(path, b"id,name,city\r\n1,\"Smith, J\",Oslo\n2,\"a\"\"b\"\n3").unwrap();
+
+        let mut out = Vec::new();
+        assert_eq!(project_to_file(path, &[2, 0, 1, 0], &mut out).unwrap(), 4);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "city,id,name,id\nOslo,1,\"Smith, J\",1\n,2,\"a\"\"b\",2\n,3,,3\n"
+        );
+
+        std::fs::remove_file(path).ok();
+    }
+}
@@ -0,0 +1,272 @@
+//! Write RFC 4180 CSV: fields quoted only when needed, quotes doubled.
+//!
+//! A field must be quoted if it contains `,` `"` `\r` or `\n` (the [`CSV`]
+//! preset of [`crate::byte_set`]); inside the quotes, every `"` is doubled.
+//! Almost every field in real output is clean, so the writer is built around
+//! that case:
+//!
+//! ```text
+//!   field:  Smith, "J"
+//!           ^^^^^ copied by ByteSet::copy_until (NEON/SWAR), stops at ','
+//!   out:    Smith           -> open quote inserted before the copied run
+//!   out:    "Smith          -> rest escaped with escape_into, only '"' special
+//!   out:    "Smith, ""J"""
+//! ```
+//!
+//! A clean field is loaded and stored once, with no separate "does this need
+//! quoting" scan. A dirty one pays for moving the run before its first
+//! special byte by one, then the quote-doubling loop copies clean runs
+//! between quotes in bulk like the other escapers.
+//!
+//! A record made of a single empty field is written as `""`, so it does not
+//! turn into an empty line that readers skip.
+
+use std::io::{self, BufWriter, Write};
+
+use crate::byte_set::{escape_into, ByteSet, CSV};
+
+/// The only byte that changes inside a quoted field.
+const QUOTE: ByteSet = ByteSet::new(b"\"");
+
+// ───────────────────────────────────────────────────────────────────────────
+//                         Scalar Reference
+// ───────────────────────────────────────────────────────────────────────────
+
+/// Append `field`, quoted if needed, byte by byte (scalar reference).
+pub fn write_field_scalar(field: &[u8], out: &mut Vec<u8>) {
+    if !field.iter().any(|&b| CSV.contains(b)) {
+        out.extend_from_slice(field);
+        return;
+    }
+    out.push(b'"');
+    for &b in field {
+        if b == b'"' {
+            out.push(b'"');
+        }
+        out.push(b);
+    }
+    out.push(b'"');
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Fields
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Check if `field` has to be quoted.
+#[inline]
+pub fn needs_quoting(field: &[u8]) -> bool {
+    CSV.has_any(field)
+}
+
+/// Append `field` to `out`, quoted and with doubled quotes if needed.
+///
+/// # Example
+/// ```
+/// use scratchpad::csv_write::write_field;
+///
+/// let mut out = Vec::new();
+/// write_field(b"Boston", &mut out);
+/// out.push(b',');
+/// write_field(b"Smith, \"J\"", &mut out);
+/// assert_eq!(out, b"Boston,\"Smith, \"\"J\"\"\"");
+/// ```
+#[inline]
+pub fn write_field(field: &[u8], out: &mut Vec<u8>) {
+    let start = out.len();
+    let clean = CSV.copy_until(field, out);
+    if clean == field.len() {
+        return;
+    }
+
+    out.insert(start, b'"');
+    escape_into(&field[clean..], &QUOTE, out, |_, out| out.extend_from_slice(b"\"\""));
+    out.push(b'"');
+}
+
+/// Append one record: `fields` separated by `,`, followed by `\n`.
+///
+/// # Example
+/// ```
+/// use scratchpad::csv_write::write_record;
+///
+/// let mut out = Vec::new();
+/// write_record(["id", "note"], &mut out);
+/// write_record(["1", "line\nbreak"], &mut out);
+/// assert_eq!(out, b"id,note\n1,\"line\nbreak\"\n");
+/// ```
+pub fn write_record<I>(fields: I, out: &mut Vec<u8>)
+where
+    I: IntoIterator,
+    I::Item: AsRef<[u8]>,
+{
+    let start = out.len();
+    let mut count = 0;
+    for field in fields {
+        if count > 0 {
+            out.push(b',');
+        }
+        write_field(field.as_ref(), out);
+        count += 1;
+    }
+    end_record(start, count, out);
+}
+
+/// Terminate a record of `count` fields that was written from `out[start]`.
+///
+/// A record holding one empty field is written as `""`, as an empty line
+/// would read back as no record at all.
+pub(crate) fn end_record(start: usize, count: usize, out: &mut Vec<u8>) {
+    if count == 1 && out.len() == start {
+        out.extend_from_slice(b"\"\"");
+    }
+    out.push(b'\n');
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Writer
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Writes CSV records to an [`io::Write`].
+///
+/// Each record is encoded into a reused buffer and handed to a
+/// [`BufWriter`], so small records are batched into large writes. Like
+/// `BufWriter`, dropping the writer flushes it and ignores errors; call
+/// [`CsvWriter::flush`] or [`CsvWriter::into_inner`] to see them.
+///
+/// # Example
+/// ```
+/// use scratchpad::csv_write::CsvWriter;
+///
+/// let mut writer = CsvWriter::new(Vec::new());
+/// writer.write_record(["name", "city"]).unwrap();
+/// writer.write_record([&b"Smith, J"[..], b"Boston"]).unwrap();
+/// assert_eq!(writer.into_inner().unwrap(), b"name,city\n\"Smith, J\",Boston\n");
+/// ```
+pub struct CsvWriter<W: Write> {
+    writer: BufWriter<W>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> CsvWriter<W> {
+    pub fn new(writer: W) -> Self {
+        CsvWriter { writer: BufWriter::new(writer), buffer: Vec::new() }
+    }
+
+    /// Write one record (see [`write_record`]).
+    pub fn write_record<I>(&mut self, fields: I) -> io::Result<()>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        self.buffer.clear();
+        write_record(fields, &mut self.buffer);
+        self.writer.write_all(&self.buffer)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Flush and return the underlying writer.
+    pub fn into_inner(self) -> io::Result<W> {
+        self.writer.into_inner().map_err(|e| e.into_error())
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_reader::CsvReader;
+
+    /// Collapse `""` to `"`, as the reader leaves it doubled.
+    fn collapse_quotes(field: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(field.len());
+        let mut i = 0;
+        while i < field.len() {
+            out.push(field[i]);
+            i += if field[i..].starts_with(b"\"\"") {
+                2
+            } else {
+                1
+            };
+        }
+        out
+    }
+
+    #[test]
+    fn test_write_field() {
+        let cases: [(&[u8], &[u8]); 7] = [
+            (b"", b""),
+            (b"plain", b"plain"),
+            (b",", b"\",\""),
+            (b"\"", b"\"\"\"\""),
+            (b"a\r\nb", b"\"a\r\nb\""),
+            (b"say \"hi\"", b"\"say \"\"hi\"\"\""),
+            (b"0123456789abcdef0123\"x", b"\"0123456789abcdef0123\"\"x\""),
+        ];
+        for (field, expected) in cases {
+            let mut out = b"prefix".to_vec();
+            write_field(field, &mut out);
+            assert_eq!(&out[6..], expected, "Mismatch for {:?}", field);
+            assert_eq!(&out[..6], b"prefix");
+            assert_eq!(needs_quoting(field), field != expected);
+        }
+    }
+
+    #[test]
+    fn test_matches_scalar() {
+        let alphabet = b"\",\r\nab \xC3\xA9";
+        let mut rng = 777u64;
+        let data: Vec<u8> = (0..1000)
+            .map(|_| {
+                rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+                if (rng >> 16) & 7 == 0 {
+                    alphabet[((rng >> 20) % alphabet.len() as u64) as usize]
+                } else {
+                    b'a' + ((rng >> 20) % 26) as u8
+                }
+            })
+            .collect();
+
+        for start in 0..100 {
+            for len in [0, 1, 7, 15, 16, 17, 40, 200] {
+                let field = &data[start..start + len];
+                let (mut fast, mut scalar) = (Vec::new(), Vec::new());
+                write_field(field, &mut fast);
+                write_field_scalar(field, &mut scalar);
+                assert_eq!(fast, scalar, "Mismatch for start={}, len={}", start, len);
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let records: Vec<Vec<&[u8]>> = vec![
+            vec![b"id", b"text"],
+            vec![b"1", b"multi\nline, with \"quotes\""],
+            vec![b""],
+            vec![b"", b""],
+            vec![b"3", b"trailing\r"],
+        ];
+
+        let mut writer = CsvWriter::new(Vec::new());
+        for record in &records {
+            writer.write_record(record).unwrap();
+        }
+        let out = writer.into_inner().unwrap();
+        assert!(out.starts_with(b"id,text\n1,\"multi\nline, with \"\"quotes\"\"\"\n\"\"\n,\n"));
+
+        let read: Vec<Vec<Vec<u8>>> = CsvReader::new(&out)
+            .map_records(|r| r.iter().map(collapse_quotes).collect())
+            .collect();
+        let expected: Vec<Vec<Vec<u8>>> = records
+            .iter()
+            .map(|r| r.iter().map(|f| f.to_vec()).collect())
+            .collect();
+        assert_eq!(read, expected);
+    }
+}
@@ -0,0 +1,315 @@
+//! Energy of the timed runs of a bench, for joules per GB next to GB/s.
+//!
+//! Run with `--energy` after the `--` of `cargo bench`, every measurement of
+//! `bench` also reads an [`EnergyProbe`] around its timed runs; the line it
+//! prints and its record get the energy per GB (10^9 bytes) processed:
+//!
+//! ```text
+//!   sudo cargo bench --bench json_escape_bench -- --energy
+//!   SWAR (clean, 1 MB):            3.14 GB/s, median 318.8 µs (...), 4.21 J/GB
+//! ```
+//!
+//! | [`EnergyProbe`] | Reads                                          | Needs       |
+//! |-----------------|------------------------------------------------|-------------|
+//! | `Rapl`          | `energy_uj` of each package, at start and stop | Linux, root |
+//! | `Powermetrics`  | `CPU Power` every 100 ms, times the sample     | macOS, root |
+//!
+//! Both need root: `powermetrics` refuses to run without it, and Linux has
+//! kept RAPL counters from users since they were shown to leak what the CPU
+//! computes (CVE-2020-8694). Both measure the whole CPU, not the bench: idle
+//! cores and other processes count too, so run on a quiet machine. RAPL
+//! counts to the µJ. `powermetrics` reports per sample, and only the samples
+//! that end between start and stop count, so up to 100 ms is lost at each
+//! end: up to a tenth of the default 2 s of `--time`.
+//!
+//! On Apple silicon the efficiency cores do the same work for less energy
+//! and more time, and which one wins depends on the kernel. A bench run
+//! under `taskpolicy -c background` stays on them, as one under `taskset`
+//! with the `cpu_atom` CPUs does on an Intel hybrid (see `cpu`).
+
+use std::{
+    fmt, fs,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    thread::JoinHandle,
+};
+
+/// Where Linux lists its RAPL domains.
+const POWERCAP: &str = "/sys/class/powercap";
+
+/// Sampling interval of `powermetrics`, in ms.
+const POWERMETRICS_INTERVAL_MS: u32 = 100;
+
+/// A way to read the energy the CPU uses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnergyProbe {
+    /// The `energy_uj` counter of each CPU package (Linux, root).
+    Rapl(Vec<RaplDomain>),
+    /// `powermetrics --samplers cpu_power` in the background (macOS, root).
+    Powermetrics,
+}
+
+/// A RAPL package: `intel-rapl:0` in `/sys/class/powercap`, on AMD too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RaplDomain {
+    /// `package-0`, ...
+    pub name: String,
+    energy_uj: PathBuf,
+    /// The counter wraps around to zero past this.
+    max_energy_range_uj: u64,
+}
+
+impl EnergyProbe {
+    /// The probe of this OS, or why there is none (`PermissionDenied` when
+    /// it needs root).
+    pub fn detect() -> io::Result<Self> {
+        if cfg!(target_os = "linux") {
+            Ok(EnergyProbe::Rapl(rapl_domains(Path::new(POWERCAP))?))
+        } else if cfg!(target_os = "macos") && crate::cache_control::is_root() {
+            Ok(EnergyProbe::Powermetrics)
+        } else if cfg!(target_os = "macos") {
+            Err(io::Error::new(io::ErrorKind::PermissionDenied, "powermetrics needs root"))
+        } else {
+            Err(io::Error::new(io::ErrorKind::Unsupported, "energy needs Linux or macOS"))
+        }
+    }
+
+    /// Start counting.
+    pub fn start(&self) -> io::Result<EnergyReading<'_>> {
+        match self {
+            EnergyProbe::Rapl(domains) => {
+                let start = domains
+                    .iter()
+                    .map(|d| read_uj(&d.energy_uj))
+                    .collect::<Result<_, _>>()?;
+                Ok(EnergyReading(Reading::Rapl { domains, start }))
+            }
+            EnergyProbe::Powermetrics => {
+                let interval = POWERMETRICS_INTERVAL_MS.to_string();
+                let mut child = Command::new("powermetrics")
+                    .args(["--samplers", "cpu_power", "-i", &interval])
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::null())
+                    .spawn()?;
+                let stdout = child.stdout.take().expect("stdout is piped");
+                let sampler =
+                    std::thread::spawn(move || powermetrics_energy(BufReader::new(stdout)));
+                Ok(EnergyReading(Reading::Powermetrics { child, sampler }))
+            }
+        }
+    }
+}
+
+impl fmt::Display for EnergyProbe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EnergyProbe::Rapl(domains) => {
+                let names: Vec<&str> = domains.iter().map(|d| d.name.as_str()).collect();
+                write!(f, "RAPL ({})", names.join(", "))
+            }
+            EnergyProbe::Powermetrics => {
+                write!(f, "powermetrics (CPU Power every {} ms)", POWERMETRICS_INTERVAL_MS)
+            }
+        }
+    }
+}
+
+/// A count in progress, from [`EnergyProbe::start`].
+#[derive(Debug)]
+pub struct EnergyReading<'a>(Reading<'a>);
+
+#[derive(Debug)]
+enum Reading<'a> {
+    /// The counters at the start.
+    Rapl {
+        domains: &'a [RaplDomain],
+        start: Vec<u64>,
+    },
+    /// `powermetrics`, and the thread adding up its samples.
+    Powermetrics {
+        child: Child,
+        sampler: JoinHandle<(u64, usize)>,
+    },
+}
+
+impl EnergyReading<'_> {
+    /// The energy since the start, in µJ.
+    pub fn stop(self) -> io::Result<u64> {
+        match self.0 {
+            Reading::Rapl { domains, start } => {
+                let mut total = 0;
+                for (domain, start) in domains.iter().zip(start) {
+                    let end = read_uj(&domain.energy_uj)?;
+                    total += counter_delta(start, end, domain.max_energy_range_uj);
+                }
+                Ok(total)
+            }
+            Reading::Powermetrics { mut child, sampler } => {
+                // Its stdout closes with it, which ends the sampler
+                let _ = child.kill();
+                let status = child.wait()?;
+                let (energy, samples) = sampler.join().expect("the sampler does not panic");
+                if samples == 0 {
+                    return Err(io::Error::other(format!("powermetrics: no sample ({})", status)));
+                }
+                Ok(energy)
+            }
+        }
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                  RAPL
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// The packages under `root`, read once to know they can be: the
+/// `intel-rapl:N` named `package-N`. Not their `intel-rapl:N:M` subdomains
+/// (the cores and uncore are in the package, DRAM is not the CPU), nor the
+/// `psys` domain of laptops, which counts the packages again.
+fn rapl_domains(root: &Path) -> io::Result<Vec<RaplDomain>> {
+    let mut domains = Vec::new();
+    let entries = fs::read_dir(root)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", root.display(), e)))?;
+    for entry in entries {
+        let dir = entry?.path();
+        let is_domain = dir
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("intel-rapl:"))
+            .is_some_and(|index| !index.contains(':'));
+        if !is_domain {
+            continue;
+        }
+        let name = fs::read_to_string(dir.join("name"))?.trim().to_string();
+        if !name.starts_with("package") {
+            continue;
+        }
+        let energy_uj = dir.join("energy_uj");
+        read_uj(&energy_uj)?;
+        let max_energy_range_uj = read_uj(&dir.join("max_energy_range_uj"))?;
+        domains.push(RaplDomain { name, energy_uj, max_energy_range_uj });
+    }
+    if domains.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no RAPL package in {}", root.display()),
+        ));
+    }
+    domains.sort_by(|a, b| a.energy_uj.cmp(&b.energy_uj));
+    Ok(domains)
+}
+
+fn read_uj(path: &Path) -> io::Result<u64> {
+    let text = fs::read_to_string(path)?;
+    text.trim().parse().map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("{}: {:?}", path.display(), text))
+    })
+}
+
+/// `end - start` of a counter that wraps around past `max`.
+fn counter_delta(start: u64, end: u64, max: u64) -> u64 {
+    if end >= start {
+        end - start
+    } else {
+        max - start + end + 1
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                               powermetrics
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// The energy in µJ of the samples of a `powermetrics` text output, and
+/// their count: each `CPU Power: N mW` times the `(T ms elapsed)` of its
+/// sample header.
+fn powermetrics_energy(output: impl BufRead) -> (u64, usize) {
+    let (mut energy, mut samples) = (0.0, 0);
+    let mut elapsed_ms = None;
+    for line in output.lines().map_while(Result::ok) {
+        if line.starts_with("*** Sampled system activity") {
+            elapsed_ms = line
+                .rsplit_once('(')
+                .and_then(|(_, rest)| rest.split_once("ms elapsed"))
+                .and_then(|(ms, _)| ms.trim().parse::<f64>().ok());
+        } else if let Some(power) = line.strip_prefix("CPU Power:") {
+            let mw = power
+                .trim()
+                .strip_suffix("mW")
+                .and_then(|mw| mw.trim().parse::<f64>().ok());
+            if let (Some(mw), Some(ms)) = (mw, elapsed_ms.take()) {
+                // mW × ms = µJ
+                energy += mw * ms;
+                samples += 1;
+            }
+        }
+    }
+    (energy as u64, samples)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rapl() {
+        let root = std::env::temp_dir().join("test_energy_powercap");
+        let _ = fs::remove_dir_all(&root);
+        for (dir, name, uj) in [
+            ("intel-rapl:0", "package-0", "1000"),
+            ("intel-rapl:0:0", "core", "500"),
+            ("intel-rapl:1", "package-1", "262143999990"),
+            ("intel-rapl:2", "psys", "7000"),
+        ] {
+            let dir = root.join(dir);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("name"), format!("{}\n", name)).unwrap();
+            fs::write(dir.join("energy_uj"), format!("{}\n", uj)).unwrap();
+            fs::write(dir.join("max_energy_range_uj"), "262143999999\n").unwrap();
+        }
+
+        let probe = EnergyProbe::Rapl(rapl_domains(&root).unwrap());
+        assert_eq!(probe.to_string(), "RAPL (package-0, package-1)");
+        let reading = probe.start().unwrap();
+        // package-1 wraps around: 9 µJ to the max, 1 to zero, 20 after
+        fs::write(root.join("intel-rapl:0/energy_uj"), "4000").unwrap();
+        fs::write(root.join("intel-rapl:1/energy_uj"), "20").unwrap();
+        assert_eq!(reading.stop().unwrap(), 3000 + 30);
+
+        fs::write(root.join("intel-rapl:1/energy_uj"), "").unwrap();
+        assert_eq!(rapl_domains(&root).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        fs::remove_dir_all(root.join("intel-rapl:0")).unwrap();
+        fs::remove_dir_all(root.join("intel-rapl:1")).unwrap();
+        assert_eq!(rapl_domains(&root).unwrap_err().kind(), io::ErrorKind::NotFound);
+        fs::remove_dir_all(&root).unwrap();
+        let missing = rapl_domains(&root).unwrap_err();
+        assert!(missing.to_string().starts_with(&root.display().to_string()));
+    }
+
+    #[test]
+    fn test_powermetrics_energy() {
+        let output = "Machine model: Mac14,2\n\
+            *** Sampled system activity (Wed Oct 16 10:00:00 2024 +0200) (102.50ms elapsed) ***\n\
+            \n\
+            **** Processor usage ****\n\
+            E-Cluster HW active frequency: 1020 MHz\n\
+            CPU Power: 2000 mW\n\
+            GPU Power: 12 mW\n\
+            *** Sampled system activity (Wed Oct 16 10:00:00 2024 +0200) (99.00ms elapsed) ***\n\
+            CPU Power: 500 mW\n\
+            *** Sampled system activity (Wed Oct 16 10:00:00 2024 +0200) (100.00ms elapsed) ***\n";
+        assert_eq!(powermetrics_energy(output.as_bytes()), (205_000 + 49_500, 2));
+        assert_eq!(powermetrics_energy(&b"powermetrics must be run as root\n"[..]), (0, 0));
+    }
+
+    #[test]
+    fn test_counter_delta() {
+        assert_eq!(counter_delta(5, 8, 10), 3);
+        assert_eq!(counter_delta(8, 2, 10), 5);
+        assert_eq!(counter_delta(7, 7, 10), 0);
+    }
+}
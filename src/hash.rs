@@ -0,0 +1,163 @@
+//! Fast 64-bit non-cryptographic hashing of byte buffers.
+//!
+//! Used to fingerprint chunks (content-defined chunking, incremental
+//! indexes), where the only requirement is that different contents almost
+//! never collide and that hashing is much faster than reading the data.
+//!
+//! The inner loop consumes 32 bytes per iteration in four independent lanes,
+//! so the multiplies pipeline instead of forming one long dependency chain:
+//!
+//!   lane i:   acc_i = rotl(acc_i + word * P2, 31) * P1     (xxHash64 round)
+//!
+//! The lanes are merged and the tail (< 32 bytes) folded in 8 bytes at a time,
+//! then the result goes through a final avalanche so every input bit affects
+//! every output bit.
+
+const P1: u64 = 0x9E3779B185EBCA87;
+const P2: u64 = 0xC2B2AE3D27D4EB4F;
+const P3: u64 = 0x165667B19E3779F9;
+const P4: u64 = 0x85EBCA77C2B2AE63;
+const P5: u64 = 0x27D4EB2F165667C5;
+
+#[inline]
+fn read_u64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes[..8].try_into().unwrap())
+}
+
+#[inline]
+fn round(acc: u64, word: u64) -> u64 {
+    acc.wrapping_add(word.wrapping_mul(P2))
+        .rotate_left(31)
+        .wrapping_mul(P1)
+}
+
+#[inline]
+fn merge(acc: u64, lane: u64) -> u64 {
+    (acc ^ round(0, lane)).wrapping_mul(P1).wrapping_add(P4)
+}
+
+/// Final avalanche (murmur3 `fmix64`).
+#[inline]
+fn avalanche(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xFF51AFD7ED558CCD);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xC4CEB9FE1A85EC53);
+    h ^ (h >> 33)
+}
+
+/// Hash `data` with a seed into 64 bits.
+///
+/// # Example
+/// ```
+/// use scratchpad::hash::hash64;
+///
+/// assert_eq!(hash64(b"hello", 0), hash64(b"hello", 0));
+/// assert_ne!(hash64(b"hello", 0), hash64(b"hellp", 0));
+/// assert_ne!(hash64(b"hello", 0), hash64(b"hello", 1));
+/// ```
+pub fn hash64(data: &[u8], seed: u64) -> u64 {
+    let mut h;
+    let mut rest = data;
+
+    if data.len() >= 32 {
+        let mut acc = [
+            seed.wrapping_add(P1).wrapping_add(P2),
+            seed.wrapping_add(P2),
+            seed,
+            seed.wrapping_sub(P1),
+        ];
+
+        let mut stripes = data.chunks_exact(32);
+        for stripe in stripes.by_ref() {
+            acc[0] = round(acc[0], read_u64(&stripe[0..]));
+            acc[1] = round(acc[1], read_u64(&stripe[8..]));
+            acc[2] = round(acc[2], read_u64(&stripe[16..]));
+            acc[3] = round(acc[3], read_u64(&stripe[24..]));
+        }
+        rest = stripes.remainder();
+
+        h = acc[0]
+            .rotate_left(1)
+            .wrapping_add(acc[1].rotate_left(7))
+            .wrapping_add(acc[2].rotate_left(12))
+            .wrapping_add(acc[3].rotate_left(18));
+        for lane in acc {
+            h = merge(h, lane);
+        }
+    } else {
+        h = seed.wrapping_add(P5);
+    }
+
+    h = h.wrapping_add(data.len() as u64);
+
+    let mut words = rest.chunks_exact(8);
+    for word in words.by_ref() {
+        h = (h ^ round(0, read_u64(word)))
+            .rotate_left(27)
+            .wrapping_mul(P1)
+            .wrapping_add(P4);
+    }
+    for &b in words.remainder() {
+        h = (h ^ (b as u64).wrapping_mul(P5))
+            .rotate_left(11)
+            .wrapping_mul(P1);
+    }
+
+    avalanche(h ^ P3)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deterministic_and_length_sensitive() {
+        assert_eq!(hash64(b"", 0), hash64(b"", 0));
+        assert_ne!(hash64(b"", 0), hash64(b"\0", 0));
+        assert_ne!(hash64(b"\0", 0), hash64(b"\0\0", 0));
+    }
+
+    #[test]
+    fn test_single_bit_flips_change_hash() {
+        let data: Vec<u8> = (0..100u8).collect();
+        let base = hash64(&data, 7);
+        for i in 0..data.len() {
+            for bit in 0..8 {
+                let mut flipped = data.clone();
+                flipped[i] ^= 1 << bit;
+                assert_ne!(hash64(&flipped, 7), base, "Collision at byte {} bit {}", i, bit);
+            }
+        }
+    }
+
+    #[test]
+    fn test_no_collisions_on_small_inputs() {
+        let mut seen = std::collections::HashSet::new();
+        assert!(seen.insert(hash64(b"", 0)));
+        for len in 1..40usize {
+            for fill in 0..=255u8 {
+                assert!(seen.insert(hash64(&vec![fill; len], 0)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_avalanche_roughly_balanced() {
+        // Flipping one input bit should flip about half of the output bits
+        let data = [0u8; 64];
+        let base = hash64(&data, 0);
+        let mut total = 0;
+        for i in 0..64 {
+            let mut flipped = data;
+            flipped[i] = 1;
+            total += (hash64(&flipped, 0) ^ base).count_ones();
+        }
+        let average = total as f64 / 64.0;
+        assert!((24.0..40.0).contains(&average), "average flipped bits {}", average);
+    }
+}
@@ -0,0 +1,152 @@
+//! Detect and escape HTML/XML special characters.
+//!
+//! The five characters with a meaning in markup are replaced by entities:
+//!
+//! | Byte | Entity   |
+//! |------|----------|
+//! | `&`  | `&amp;`  |
+//! | `<`  | `&lt;`   |
+//! | `>`  | `&gt;`   |
+//! | `"`  | `&quot;` |
+//! | `'`  | `&#39;`  |
+//!
+//! `&#39;` rather than `&apos;` because the latter is not defined in HTML 4.
+//! Detection and the clean-run copy loop come from [`crate::byte_set`], the
+//! same machinery as the JSON escaper.
+
+use crate::byte_set::{escape_into, HTML};
+
+// ───────────────────────────────────────────────────────────────────────────
+//                         Scalar Reference
+// ───────────────────────────────────────────────────────────────────────────
+
+/// Check if a single byte needs HTML escaping (scalar version).
+#[inline]
+pub fn needs_html_escape_scalar(byte: u8) -> bool {
+    matches!(byte, b'&' | b'<' | b'>' | b'"' | b'\'')
+}
+
+/// Return the entity for a byte, or None if it does not need escaping.
+#[inline]
+fn html_entity(byte: u8) -> Option<&'static [u8]> {
+    match byte {
+        b'&' => Some(b"&amp;"),
+        b'<' => Some(b"&lt;"),
+        b'>' => Some(b"&gt;"),
+        b'"' => Some(b"&quot;"),
+        b'\'' => Some(b"&#39;"),
+        _ => None,
+    }
+}
+
+/// Escape `input` byte by byte (scalar reference).
+pub fn escape_html_scalar(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    for &b in input {
+        match html_entity(b) {
+            Some(entity) => out.extend_from_slice(entity),
+            None => out.push(b),
+        }
+    }
+    out
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                          Detection and Escaping
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Check if any byte in a buffer needs HTML escaping (SWAR version).
+pub fn has_html_escapable_byte(buffer: &[u8]) -> bool {
+    HTML.has_any(buffer)
+}
+
+/// Find the index of the first byte that needs HTML escaping (SWAR version).
+pub fn find_first_html_escapable(buffer: &[u8]) -> Option<usize> {
+    HTML.find_first(buffer)
+}
+
+/// Escape `input` for HTML/XML text or attribute values, appending to `out`.
+pub fn escape_html_into(input: &[u8], out: &mut Vec<u8>) {
+    escape_into(input, &HTML, out, |b, out| out.extend_from_slice(html_entity(b).unwrap()));
+}
+
+/// Escape `input` for HTML/XML text or attribute values.
+///
+/// # Example
+/// ```
+/// use scratchpad::html_escape::escape_html;
+///
+/// assert_eq!(escape_html(b"<a href=\"x\">Tom & Jerry</a>"),
+///            b"&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&lt;/a&gt;");
+/// ```
+pub fn escape_html(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len() + input.len() / 8);
+    escape_html_into(input, &mut out);
+    out
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scalar_detection() {
+        for b in [b'&', b'<', b'>', b'"', b'\''] {
+            assert!(needs_html_escape_scalar(b));
+        }
+        for b in [b' ', b'a', b'\n', b';', b'=', 0u8, 0xFF] {
+            assert!(!needs_html_escape_scalar(b));
+        }
+    }
+
+    #[test]
+    fn test_detection() {
+        assert!(!has_html_escapable_byte(b""));
+        assert!(!has_html_escapable_byte(b"plain text without markup"));
+        assert!(has_html_escapable_byte(b"1 < 2"));
+        assert_eq!(find_first_html_escapable(b"it's"), Some(2));
+        assert_eq!(find_first_html_escapable(b"0123456789abcdef&"), Some(16));
+    }
+
+    #[test]
+    fn test_escape_all_entities() {
+        assert_eq!(escape_html(b"&<>\"'"), b"&amp;&lt;&gt;&quot;&#39;");
+        assert_eq!(escape_html(b"&amp;"), b"&amp;amp;");
+        assert_eq!(escape_html(b""), b"");
+        assert_eq!(escape_html("caf\u{e9} <b>".as_bytes()), "caf\u{e9} &lt;b&gt;".as_bytes());
+    }
+
+    #[test]
+    fn test_escape_into_appends() {
+        let mut out = b"<p>".to_vec();
+        escape_html_into(b"a<b", &mut out);
+        assert_eq!(out, b"<p>a&lt;b");
+    }
+
+    #[test]
+    fn test_matches_scalar() {
+        // Differential test: random bytes with a high density of special characters
+        let alphabet = b"&<>\"'ab c\n\x00\xC3\xA9";
+        let mut rng = 31337u64;
+        let data: Vec<u8> = (0..2000)
+            .map(|_| {
+                rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+                alphabet[((rng >> 16) % alphabet.len() as u64) as usize]
+            })
+            .collect();
+
+        for len in 0..200 {
+            let input = &data[..len];
+            assert_eq!(escape_html(input), escape_html_scalar(input), "Mismatch for len={}", len);
+            assert_eq!(
+                find_first_html_escapable(input),
+                input.iter().position(|&b| needs_html_escape_scalar(b))
+            );
+        }
+        assert_eq!(escape_html(&data), escape_html_scalar(&data));
+    }
+}
@@ -27,43 +27,56 @@
 // All checks happen in parallel using bitwise operations!
 
 //=== JSON Escape Detection Benchmarks (SWAR) ===
+// (x86_64, stddev and confidence interval cut)
 //
 // --- Clean ASCII (no escapable chars) ---
-// Scalar (clean, 1 MB):          0.00 ms total, 444444.44 GB/s throughput
-// SWAR (clean, 1 MB):            0.00 ms total, 307692.31 GB/s throughput
+// Scalar (clean, 1 MB):          1.01 GB/s, median 988.5 µs (min 864.3 µs, max 11.04 ms
+// SWAR (clean, 1 MB):            3.97 GB/s, median 251.7 µs (min 209.0 µs, max 2.40 ms
 //
-// --- With escapable chars (early detection) ---
-// Scalar (early escape, 1 MB):   0.06 ms total, 15564.20 GB/s throughput
-// SWAR (early escape, 1 MB):     0.03 ms total, 29375.48 GB/s throughput
+// --- With escapable chars (early detection, stops at byte 100) ---
+// Scalar (early escape, 1 MB):   5714.29 GB/s, median 175 ns (min 103 ns, max 280 ns
+// SWAR (early escape, 1 MB):     15625.00 GB/s, median 64 ns (min 59 ns, max 128 ns
 //
 // --- Mixed content (quotes, backslashes, newlines) ---
-// Scalar (mixed, 1 MB):          0.01 ms total, 132590.82 GB/s throughput
-// SWAR (mixed, 1 MB):            0.01 ms total, 175162.02 GB/s throughput
+// Scalar (mixed, 1 MB):          1.00 GB/s, median 1.00 ms (min 838.4 µs, max 4.14 ms
+// SWAR (mixed, 1 MB):            3.86 GB/s, median 258.8 µs (min 232.7 µs, max 663.3 µs
 //
-// --- Different input sizes (clean ASCII) ---
-//   1 KB:
-//     Scalar:                    0.00 ms total, 444.19 GB/s throughput
-//     SWAR:                      0.00 ms total, 307.52 GB/s throughput
+// --- Input sizes (clean ASCII, 1 KB to 1 GB) ---    (every fourth size kept)
+// Scalar:
+//         Size       GB/s     Median        Min        Max
+//         1 KB       0.89     1.2 µs     870 ns   473.9 µs
+//        16 KB       0.92    17.8 µs    14.1 µs    1.64 ms
+//       256 KB       0.95   276.5 µs   213.7 µs    3.46 ms
+//         4 MB       0.94    4.45 ms    3.83 ms    8.52 ms
+//        64 MB       1.11   60.29 ms   58.91 ms   67.00 ms
+//         1 GB       0.96     1.12 s     1.09 s     1.21 s
 //
-//   10 KB:
-//     Scalar:                    0.00 ms total, 3973.12 GB/s throughput
-//     SWAR:                      0.00 ms total, 2982.82 GB/s throughput
-//
-//   100 KB:
-//     Scalar:                    0.00 ms total, inf GB/s throughput
-//     SWAR:                      0.00 ms total, 24975.61 GB/s throughput
-//
-//   1000 KB:
-//     Scalar:                    0.00 ms total, 243809.52 GB/s throughput
-//     SWAR:                      0.00 ms total, 243809.52 GB/s throughput
-//
-// --- Very large input (10 MB, clean ASCII) ---
-// Scalar (10 MB):                0.00 ms total, 3436426.12 GB/s throughput
-// SWAR (10 MB):                  0.00 ms total, 3003003.00 GB/s throughput
+// SWAR:
+//         Size       GB/s     Median        Min        Max
+//         1 KB       3.02     339 ns     258 ns    1.22 ms
+//        16 KB       3.53     4.6 µs     3.7 µs   465.9 µs
+//       256 KB       3.56    73.6 µs    61.2 µs   142.7 µs
+//         4 MB       3.66    1.15 ms    1.10 ms    1.39 ms
+//        64 MB       3.17   21.19 ms   20.64 ms   21.95 ms
+//         1 GB       3.19  336.24 ms  300.15 ms  340.03 ms
 //
 // --- Worst case (escapable at end) ---
-// Scalar (worst case, 1 MB):     629.03 ms total, 1.59 GB/s throughput
-// SWAR (worst case, 1 MB):       336.55 ms total, 2.97 GB/s throughput
+// Scalar (worst case, 1 MB):     1.02 GB/s, median 983.6 µs (min 756.1 µs, max 3.58 ms
+// SWAR (worst case, 1 MB):       4.52 GB/s, median 221.1 µs (min 212.7 µs, max 2.71 ms
+//
+// The early-escape rows count the whole 1 MB but read 100 bytes: they time
+// the early exit, not a throughput.
+
+#[cfg(target_arch = "aarch64")]
+use std::arch::aarch64::*;
+use std::io::{self, Read};
+use std::ops::{ControlFlow, Range};
+
+use crate::bitmask::padded_block;
+use crate::byte_set::{escape_into, JSON};
+#[cfg(target_arch = "aarch64")]
+use crate::cpu;
+use crate::csv_parse_buffer_size_impact::for_each_buffer;
 
 // ───────────────────────────────────────────────────────────────────────────
 //                         Scalar Reference
@@ -283,6 +296,339 @@ pub fn find_first_escapable(buffer: &[u8]) -> Option<usize> {
     None
 }
 
+// ═══════════════════════════════════════════════════════════════════════════
+//                      Counting: Always Scan Everything
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// The detectors above stop at the first hit, so their throughput depends on
+// where the first escapable byte is (a quote at byte 10 makes a 1 MB buffer
+// look like 100 TB/s). Counting has to look at every byte, which makes it
+// the honest bandwidth measurement, and the count is useful in itself: it
+// bounds the output size of the escaper.
+//
+//   SWAR:  exact per-byte mask (`ByteSet::mask_swar`), popcount per u64
+//   NEON:  compare 16 bytes to 0xFF/0x00 lanes, subtract them from a u8
+//          accumulator (adds 1 per hit), widen-and-sum every 255 vectors
+
+/// Count the bytes that need JSON escaping, byte by byte (scalar reference).
+pub fn count_escapable_bytes_scalar(buffer: &[u8]) -> usize {
+    buffer.iter().filter(|&&b| needs_json_escape_scalar(b)).count()
+}
+
+/// Count the bytes that need JSON escaping (SWAR version).
+pub fn count_escapable_bytes_swar(buffer: &[u8]) -> usize {
+    let mut chunks = buffer.chunks_exact(8);
+    let mut count = 0usize;
+
+    for chunk in chunks.by_ref() {
+        let x = u64::from_le_bytes(chunk.try_into().unwrap());
+        count += JSON.mask_swar(x).count_ones() as usize;
+    }
+
+    count + count_escapable_bytes_scalar(chunks.remainder())
+}
+
+/// Count the bytes that need JSON escaping (NEON version).
+///
+/// # Safety
+/// Requires a CPU with NEON support (see [`cpu::has_neon`]).
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+pub unsafe fn count_escapable_bytes_neon(buffer: &[u8]) -> usize {
+    let below = vdupq_n_u8(32);
+    let quote = vdupq_n_u8(b'"');
+    let backslash = vdupq_n_u8(b'\\');
+
+    let mut chunks = buffer.chunks_exact(16);
+    let mut count = 0usize;
+    let mut acc = vdupq_n_u8(0);
+    let mut pending = 0;
+
+    for chunk in chunks.by_ref() {
+        let v = vld1q_u8(chunk.as_ptr());
+        let m = vorrq_u8(vcltq_u8(v, below), vorrq_u8(vceqq_u8(v, quote), vceqq_u8(v, backslash)));
+        // A hit is 0xFF, i.e. -1: subtracting it counts one
+        acc = vsubq_u8(acc, m);
+
+        pending += 1;
+        if pending == 255 {
+            count += vaddlvq_u8(acc) as usize;
+            acc = vdupq_n_u8(0);
+            pending = 0;
+        }
+    }
+
+    count + vaddlvq_u8(acc) as usize + count_escapable_bytes_scalar(chunks.remainder())
+}
+
+/// Count the bytes that need JSON escaping, scanning the whole buffer.
+///
+/// # Example
+/// ```
+/// use scratchpad::json_escape_SWAR::count_escapable_bytes;
+///
+/// assert_eq!(count_escapable_bytes(b"say \"hi\"\n"), 3);
+/// ```
+#[inline]
+pub fn count_escapable_bytes(buffer: &[u8]) -> usize {
+    #[cfg(target_arch = "aarch64")]
+    if cpu::has_neon() {
+        return unsafe { count_escapable_bytes_neon(buffer) };
+    }
+
+    count_escapable_bytes_swar(buffer)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                               Escaping
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Detection tells us *whether* a string needs escaping; the escaper also has
+// to rewrite it. Clean runs between escapable bytes are copied in bulk by
+// `byte_set::escape_into`, which is shared with the HTML escaper.
+
+/// Append the JSON escape sequence for one escapable byte.
+#[inline]
+fn emit_json_escape(byte: u8, out: &mut Vec<u8>) {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+
+    match byte {
+        b'"' => out.extend_from_slice(b"\\\""),
+        b'\\' => out.extend_from_slice(b"\\\\"),
+        b'\n' => out.extend_from_slice(b"\\n"),
+        b'\r' => out.extend_from_slice(b"\\r"),
+        b'\t' => out.extend_from_slice(b"\\t"),
+        0x08 => out.extend_from_slice(b"\\b"),
+        0x0C => out.extend_from_slice(b"\\f"),
+        _ => out.extend_from_slice(&[
+            b'\\',
+            b'u',
+            b'0',
+            b'0',
+            HEX[(byte >> 4) as usize],
+            HEX[(byte & 0xF) as usize],
+        ]),
+    }
+}
+
+/// Append the bytes of `src` up to the first escapable byte to `dst`.
+///
+/// Returns how many bytes were copied (`src.len()` if all are clean). This is
+/// the copy loop of [`escape_json_into`]; see [`ByteSet::copy_until`].
+///
+/// [`ByteSet::copy_until`]: crate::byte_set::ByteSet::copy_until
+///
+/// # Example
+/// ```
+/// use scratchpad::json_escape_SWAR::copy_until_escapable;
+///
+/// let mut out = Vec::new();
+/// assert_eq!(copy_until_escapable(b"abc\"def", &mut out), 3);
+/// assert_eq!(out, b"abc");
+/// ```
+#[inline]
+pub fn copy_until_escapable(src: &[u8], dst: &mut Vec<u8>) -> usize {
+    JSON.copy_until(src, dst)
+}
+
+/// Escape `input` for use inside a JSON string, appending to `out`.
+///
+/// Non-ASCII bytes are copied unchanged (UTF-8 passes through).
+pub fn escape_json_into(input: &[u8], out: &mut Vec<u8>) {
+    escape_into(input, &JSON, out, emit_json_escape);
+}
+
+/// Escape `input` for use inside a JSON string.
+///
+/// # Example
+/// ```
+/// use scratchpad::json_escape_SWAR::escape_json;
+///
+/// assert_eq!(escape_json(b"say \"hi\"\n"), b"say \\\"hi\\\"\\n");
+/// ```
+pub fn escape_json(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len() + input.len() / 8);
+    escape_json_into(input, &mut out);
+    out
+}
+
+/// Escape `input` for use inside a JSON string, byte by byte (scalar reference).
+pub fn escape_json_scalar(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    for &b in input {
+        if needs_json_escape_scalar(b) {
+            emit_json_escape(b, &mut out);
+        } else {
+            out.push(b);
+        }
+    }
+    out
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                          Clean-Run Span Iterator
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// For serializers that want their own emission strategy (writev of clean runs,
+// custom escape tables, ...), expose the scan itself:
+//
+//   Input:   ab"cd\n
+//   Spans:   Clean(0..2) NeedsEscape('"', 2) Clean(3..5) NeedsEscape('\n', 5)
+//
+// Each 64-byte block becomes one u64 of escapable positions; clean runs are the
+// gaps between set bits, so a long clean run costs one mask per 64 bytes.
+
+/// One item of [`escape_spans`]: a run of clean bytes, or one byte to escape.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Span {
+    /// Bytes that can be copied unchanged.
+    Clean(Range<usize>),
+    /// A byte that needs escaping, and its index.
+    NeedsEscape(u8, usize),
+}
+
+/// Iterator over clean runs and escapable bytes. Created by [`escape_spans`].
+pub struct EscapeSpans<'a> {
+    input: &'a [u8],
+    block_start: usize,
+    mask: u64,
+    pos: usize,
+}
+
+impl EscapeSpans<'_> {
+    /// Load the escapable-byte mask of the block starting at `block_start`.
+    fn load_block(&mut self) {
+        let remaining = &self.input[self.block_start..];
+        self.mask = if remaining.len() >= 64 {
+            JSON.bitmask_64(remaining[..64].try_into().unwrap())
+        } else {
+            // Padding is zeros, which are escapable: mask them out
+            JSON.bitmask_64(&padded_block(remaining)) & ((1u64 << remaining.len()) - 1)
+        };
+    }
+
+    /// Index of the next escapable byte at or after `pos`, or the input length.
+    fn next_escapable(&mut self) -> usize {
+        loop {
+            if self.mask != 0 {
+                return self.block_start + self.mask.trailing_zeros() as usize;
+            }
+            if self.block_start + 64 >= self.input.len() {
+                return self.input.len();
+            }
+            self.block_start += 64;
+            self.load_block();
+        }
+    }
+}
+
+impl Iterator for EscapeSpans<'_> {
+    type Item = Span;
+
+    fn next(&mut self) -> Option<Span> {
+        if self.pos >= self.input.len() {
+            return None;
+        }
+
+        let next = self.next_escapable();
+        if next > self.pos {
+            let clean = self.pos..next;
+            self.pos = next;
+            return Some(Span::Clean(clean));
+        }
+
+        self.mask &= self.mask - 1;
+        self.pos += 1;
+        Some(Span::NeedsEscape(self.input[next], next))
+    }
+}
+
+/// Split `input` into clean runs and bytes that need JSON escaping.
+///
+/// # Example
+/// ```
+/// use scratchpad::json_escape_SWAR::{escape_spans, Span};
+///
+/// let spans: Vec<Span> = escape_spans(b"ab\"cd\n").collect();
+/// assert_eq!(
+///     spans,
+///     vec![
+///         Span::Clean(0..2),
+///         Span::NeedsEscape(b'"', 2),
+///         Span::Clean(3..5),
+///         Span::NeedsEscape(b'\n', 5),
+///     ]
+/// );
+/// ```
+pub fn escape_spans(input: &[u8]) -> EscapeSpans<'_> {
+    let mut spans = EscapeSpans { input, block_start: 0, mask: 0, pos: 0 };
+    if !input.is_empty() {
+        spans.load_block();
+    }
+    spans
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                      Streaming Detection over a Reader
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Escapable bytes are single bytes, so nothing has to be carried between
+// buffers: each buffer is scanned independently and the reader is dropped at
+// the first hit. Memory stays at one buffer for files of any size.
+
+/// Read buffer for the streaming functions: 64 KB, the L1-sized sweet spot
+/// from `buffer_size_bench`.
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Find the offset of the first byte in `reader` that needs JSON escaping.
+///
+/// Returns None if the whole stream is clean.
+///
+/// # Example
+/// ```
+/// use scratchpad::json_escape_SWAR::find_first_json_escapable_in_reader;
+///
+/// let mut input: &[u8] = b"clean text, then a \"quote\"";
+/// assert_eq!(find_first_json_escapable_in_reader(&mut input).unwrap(), Some(19));
+/// ```
+pub fn find_first_json_escapable_in_reader<R: Read>(reader: &mut R) -> io::Result<Option<u64>> {
+    find_first_json_escapable_in_reader_with_buffer(reader, READ_BUFFER_SIZE)
+}
+
+/// Check if any byte in `reader` needs JSON escaping, without loading it all.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::json_escape_SWAR::has_json_escapable_in_reader;
+///
+/// let mut file = std::fs::File::open("export.ndjson").unwrap();
+/// println!("needs escaping: {}", has_json_escapable_in_reader(&mut file).unwrap());
+/// ```
+pub fn has_json_escapable_in_reader<R: Read>(reader: &mut R) -> io::Result<bool> {
+    Ok(find_first_json_escapable_in_reader(reader)?.is_some())
+}
+
+fn find_first_json_escapable_in_reader_with_buffer<R: Read>(
+    reader: &mut R,
+    buffer_size: usize,
+) -> io::Result<Option<u64>> {
+    let mut offset = 0u64;
+    let mut found = None;
+
+    for_each_buffer(reader, buffer_size, |buffer| match JSON.find_first(buffer) {
+        Some(pos) => {
+            found = Some(offset + pos as u64);
+            ControlFlow::Break(())
+        }
+        None => {
+            offset += buffer.len() as u64;
+            ControlFlow::Continue(())
+        }
+    })?;
+
+    Ok(found)
+}
+
 // ═══════════════════════════════════════════════════════════════════════════
 //                                 Tests
 // ═══════════════════════════════════════════════════════════════════════════
@@ -446,6 +792,139 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_escape_json() {
+        assert_eq!(escape_json(b""), b"");
+        assert_eq!(escape_json(b"plain"), b"plain");
+        assert_eq!(escape_json(b"a\"b\\c"), b"a\\\"b\\\\c");
+        assert_eq!(escape_json(b"\n\r\t\x08\x0C"), b"\\n\\r\\t\\b\\f");
+        assert_eq!(escape_json(b"\x00\x1F"), b"\\u0000\\u001f");
+        assert_eq!(escape_json("caf\u{e9}".as_bytes()), "caf\u{e9}".as_bytes());
+    }
+
+    #[test]
+    fn test_escape_json_matches_scalar() {
+        let mut rng = 2025u64;
+        let data: Vec<u8> = (0..1000)
+            .map(|_| {
+                rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+                (rng >> 24) as u8
+            })
+            .collect();
+
+        for len in 0..100 {
+            assert_eq!(escape_json(&data[..len]), escape_json_scalar(&data[..len]));
+        }
+        assert_eq!(escape_json(&data), escape_json_scalar(&data));
+    }
+
+    #[test]
+    fn test_escape_spans_rebuild_escape() {
+        // Driving the emission from the spans must give the same output as escape_json
+        let mut rng = 77u64;
+        let data: Vec<u8> = (0..500)
+            .map(|_| {
+                rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+                if (rng >> 16) & 7 == 0 { b"\"\\\n\x01"[((rng >> 20) & 3) as usize] } else { b'a' }
+            })
+            .collect();
+
+        for len in 0..data.len() {
+            let input = &data[..len];
+            let mut out = Vec::new();
+            let mut covered = 0;
+            for span in escape_spans(input) {
+                match span {
+                    Span::Clean(range) => {
+                        assert!(!range.is_empty() && range.start == covered);
+                        covered = range.end;
+                        out.extend_from_slice(&input[range]);
+                    }
+                    Span::NeedsEscape(byte, index) => {
+                        assert_eq!(index, covered);
+                        covered += 1;
+                        out.extend_from_slice(&escape_json(&[byte]));
+                    }
+                }
+            }
+            assert_eq!(covered, len);
+            assert_eq!(out, escape_json_scalar(input), "Mismatch for len={}", len);
+        }
+    }
+
+    #[test]
+    fn test_escape_spans_edges() {
+        assert_eq!(escape_spans(b"").count(), 0);
+        assert_eq!(escape_spans(&[b'x'; 64]).collect::<Vec<_>>(), vec![Span::Clean(0..64)]);
+
+        let mut data = vec![b'x'; 130];
+        data[63] = b'"';
+        data[64] = b'"';
+        data[129] = 0;
+        let spans: Vec<Span> = escape_spans(&data).collect();
+        assert_eq!(
+            spans,
+            vec![
+                Span::Clean(0..63),
+                Span::NeedsEscape(b'"', 63),
+                Span::NeedsEscape(b'"', 64),
+                Span::Clean(65..129),
+                Span::NeedsEscape(0, 129),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reader_detection() {
+        let mut data = vec![b'a'; 200_000];
+        assert!(!has_json_escapable_in_reader(&mut &data[..]).unwrap());
+
+        for pos in [0usize, 7, 4095, 4096, 65_535, 65_536, 199_999] {
+            data[pos] = b'\\';
+            for buffer_size in [1, 4096, READ_BUFFER_SIZE] {
+                assert_eq!(
+                    find_first_json_escapable_in_reader_with_buffer(&mut &data[..], buffer_size)
+                        .unwrap(),
+                    Some(pos as u64),
+                    "Mismatch for pos={} buffer_size={}",
+                    pos,
+                    buffer_size
+                );
+            }
+            data[pos] = b'a';
+        }
+
+        let path = "/tmp/test_json_escape_reader.txt";
+        data[150_000] = b'\n';
+        std::fs::write(path, &data).unwrap();
+        let mut file = std::fs::File::open(path).unwrap();
+        assert!(has_json_escapable_in_reader(&mut file).unwrap());
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_count_escapable_bytes() {
+        assert_eq!(count_escapable_bytes(b""), 0);
+        assert_eq!(count_escapable_bytes(b"clean"), 0);
+
+        // Non-ASCII bytes must not be counted; runs long enough to flush the NEON accumulator
+        let alphabet = b"\"\\\n\x00\x1Fa ~\x7F\x80\xA2\xDC\xFF";
+        let mut rng = 77u64;
+        let data: Vec<u8> = (0..10_000)
+            .map(|_| {
+                rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+                alphabet[((rng >> 16) % alphabet.len() as u64) as usize]
+            })
+            .collect();
+
+        for len in (0..data.len()).step_by(97) {
+            let expected = count_escapable_bytes_scalar(&data[..len]);
+            assert_eq!(count_escapable_bytes_swar(&data[..len]), expected);
+            assert_eq!(count_escapable_bytes(&data[..len]), expected);
+        }
+        assert_eq!(count_escapable_bytes(&vec![b'"'; 16 * 300 + 5]), 16 * 300 + 5);
+    }
+
     #[test]
     fn test_edge_cases() {
         // Byte 32 (space) should NOT need escaping
@@ -0,0 +1,201 @@
+//! JSON escape detection on UTF-16 code units.
+//!
+//! Strings coming from Windows APIs or JavaScript engines are UTF-16. Checking
+//! whether they need escaping before transcoding lets the clean ones (almost
+//! all of them) take a fast path. The escapable set is the same as for UTF-8:
+//! code units below 0x20, `"` (0x22) and `\` (0x5C). Surrogates and all other
+//! non-ASCII code units pass through unchanged.
+//!
+//! - SWAR: 4 code units per u64, with 16-bit lanes
+//! - NEON: 8 code units per `uint16x8_t`
+
+#[cfg(target_arch = "aarch64")]
+use std::arch::aarch64::*;
+
+#[cfg(target_arch = "aarch64")]
+use crate::cpu;
+
+// ───────────────────────────────────────────────────────────────────────────
+//                         Scalar Reference
+// ───────────────────────────────────────────────────────────────────────────
+
+/// Check if a single code unit needs JSON escaping (scalar version).
+#[inline]
+pub fn needs_json_escape_utf16_scalar(unit: u16) -> bool {
+    unit < 0x20 || unit == 0x22 || unit == 0x5C
+}
+
+/// Check if any code unit needs JSON escaping (scalar reference).
+pub fn has_json_escapable_utf16_scalar(units: &[u16]) -> bool {
+    units.iter().any(|&u| needs_json_escape_utf16_scalar(u))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                        SWAR: 4 Code Units in 64 bits
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// The byte tricks from `byte_set::ByteSet::mask_swar`, with 16-bit lanes:
+//
+//   lane < 0x20:   bit 15 clear and (low15 + 0x7FE0) does not reach bit 15
+//   lane == c:     t = lane ^ c is zero: ((t & low15) + low15) | t has bit 15 clear
+//
+// `low15 + 0x7FFF` is at most 0xFFFE, so no carry crosses into the next lane
+// and the result is exact per lane.
+
+const LOW15: u64 = 0x7FFF7FFF7FFF7FFFu64;
+const HIGH16: u64 = 0x8000800080008000u64;
+const ONES16: u64 = 0x0001000100010001u64;
+
+/// Compute the escapable mask of 4 code units packed in a u64 (SWAR version).
+///
+/// Returns a u64 with bit 15 of each 16-bit lane set if that code unit needs
+/// escaping.
+#[inline]
+pub fn json_escapable_mask_utf16_swar(x: u64) -> u64 {
+    let lt32 = !(((x & LOW15) + ONES16 * (0x8000 - 0x20)) | x) & HIGH16;
+
+    let t = x ^ (ONES16 * 0x22);
+    let eq34 = !(((t & LOW15) + LOW15) | t) & HIGH16;
+
+    let t = x ^ (ONES16 * 0x5C);
+    let eq92 = !(((t & LOW15) + LOW15) | t) & HIGH16;
+
+    lt32 | eq34 | eq92
+}
+
+/// Check if any code unit needs JSON escaping (SWAR version).
+pub fn has_json_escapable_utf16_swar(units: &[u16]) -> bool {
+    let mut chunks = units.chunks_exact(4);
+
+    for chunk in chunks.by_ref() {
+        let x = chunk[0] as u64
+            | (chunk[1] as u64) << 16
+            | (chunk[2] as u64) << 32
+            | (chunk[3] as u64) << 48;
+        if json_escapable_mask_utf16_swar(x) != 0 {
+            return true;
+        }
+    }
+
+    // Handle remaining code units (< 4) with scalar
+    has_json_escapable_utf16_scalar(chunks.remainder())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                        NEON: 8 Code Units per Vector
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Check if any code unit needs JSON escaping (NEON version).
+///
+/// # Safety
+/// Requires a CPU with NEON support (see [`cpu::has_neon`]).
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+pub unsafe fn has_json_escapable_utf16_neon(units: &[u16]) -> bool {
+    let below = vdupq_n_u16(0x20);
+    let quote = vdupq_n_u16(0x22);
+    let backslash = vdupq_n_u16(0x5C);
+
+    let mut chunks = units.chunks_exact(8);
+
+    for chunk in chunks.by_ref() {
+        let v = vld1q_u16(chunk.as_ptr());
+        let m =
+            vorrq_u16(vcltq_u16(v, below), vorrq_u16(vceqq_u16(v, quote), vceqq_u16(v, backslash)));
+        if vmaxvq_u16(m) != 0 {
+            return true;
+        }
+    }
+
+    has_json_escapable_utf16_scalar(chunks.remainder())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                               Dispatch
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Check if any code unit of a UTF-16 string needs JSON escaping.
+///
+/// # Example
+/// ```
+/// use scratchpad::json_escape_utf16::has_json_escapable_utf16;
+///
+/// let clean: Vec<u16> = "caf\u{e9} \u{1F600}".encode_utf16().collect();
+/// let quoted: Vec<u16> = "say \"hi\"".encode_utf16().collect();
+/// assert!(!has_json_escapable_utf16(&clean));
+/// assert!(has_json_escapable_utf16(&quoted));
+/// ```
+#[inline]
+pub fn has_json_escapable_utf16(units: &[u16]) -> bool {
+    #[cfg(target_arch = "aarch64")]
+    if cpu::has_neon() {
+        return unsafe { has_json_escapable_utf16_neon(units) };
+    }
+
+    has_json_escapable_utf16_swar(units)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scalar() {
+        for u in [0u16, 0x0A, 0x1F, 0x22, 0x5C] {
+            assert!(needs_json_escape_utf16_scalar(u));
+        }
+        for u in [0x20u16, 0x21, 0x5B, 0x7F, 0x0122, 0x5C00, 0xD83D, 0xFFFF] {
+            assert!(!needs_json_escape_utf16_scalar(u));
+        }
+    }
+
+    #[test]
+    fn test_swar_lanes_exact() {
+        // High bytes equal to escapable values must not match
+        let x = 0x2200u64 | (0x001Fu64 << 16) | (0x5C5Cu64 << 32) | (0x005Cu64 << 48);
+        assert_eq!(json_escapable_mask_utf16_swar(x), 0x8000_0000_8000_0000);
+    }
+
+    #[test]
+    fn test_matches_scalar() {
+        let interesting = [
+            0x00u16, 0x1F, 0x20, 0x22, 0x5C, 0x5D, 0x2200, 0x8022, 0xFFFF, 0xD83D,
+        ];
+        let mut rng = 4242u64;
+        let data: Vec<u16> = (0..500)
+            .map(|_| {
+                rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+                if (rng >> 16) & 7 == 0 {
+                    interesting[((rng >> 20) % interesting.len() as u64) as usize]
+                } else {
+                    0x41 + ((rng >> 24) % 26) as u16
+                }
+            })
+            .collect();
+
+        for start in 0..64 {
+            for len in 0..40 {
+                let units = &data[start..start + len];
+                let expected = has_json_escapable_utf16_scalar(units);
+                assert_eq!(has_json_escapable_utf16_swar(units), expected);
+                assert_eq!(has_json_escapable_utf16(units), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_position_in_every_lane() {
+        let mut units = vec![0x41u16; 37];
+        assert!(!has_json_escapable_utf16(&units));
+        for i in 0..units.len() {
+            units[i] = 0x22;
+            assert!(has_json_escapable_utf16_swar(&units), "Missed at {}", i);
+            assert!(has_json_escapable_utf16(&units), "Missed at {}", i);
+            units[i] = 0x41;
+        }
+    }
+}
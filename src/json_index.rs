@@ -0,0 +1,329 @@
+//! simdjson-style stage 1: a structural index of a JSON document.
+//!
+//! Based on: https://arxiv.org/abs/1902.08318 (Langdale & Lemire, "Parsing Gigabytes of JSON per Second")
+//!
+//! Stage 1 finds every structural character `{ } [ ] : ,` outside of strings,
+//! plus every string boundary (unescaped `"`), without parsing anything.
+//! The result is a compact index:
+//! - one u64 bitmask per 64-byte block
+//! - the extracted positions as u32 offsets
+//!
+//! Even without a stage 2 parser this is useful: splitting a top-level array
+//! into elements, counting records, or jumping to a key only needs the index.
+
+use std::fmt;
+
+use crate::bitmask::{eq_bitmask_64, padded_block, prefix_xor};
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                         Per-Block Classification
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// For each 64-byte block:
+//
+//   Input:      {"a":"x\"y",  "b":[1,2]}
+//   quotes:      .1.1.1..1...1.1.......     (unescaped only)
+//   in_string:   .111.1111...11........     prefix_xor(quotes)
+//   structural: 1...1.......,...1.[.,.]}    outside strings
+//
+// State carried between blocks:
+//   prev_escaped     block ended in an odd run of backslashes
+//   prev_in_string   block ended inside a string (all ones / all zeros)
+
+const EVEN_BITS: u64 = 0x5555_5555_5555_5555;
+
+/// Mark the characters escaped by a backslash (simdjson's `find_escaped`).
+///
+/// A character is escaped if it follows an odd-length run of backslashes.
+/// Runs are measured by adding the run starts to the backslash mask: the carry
+/// ripples through each run and lands just after it, on an even or odd bit.
+#[inline]
+fn find_escaped(backslash: u64, prev_escaped: &mut u64) -> u64 {
+    // A backslash escaped by the previous block does not start a new escape
+    let backslash = backslash & !*prev_escaped;
+    let follows_escape = (backslash << 1) | *prev_escaped;
+
+    let odd_sequence_starts = backslash & !EVEN_BITS & !follows_escape;
+    let (sequences_starting_on_even_bits, overflow) =
+        odd_sequence_starts.overflowing_add(backslash);
+    *prev_escaped = overflow as u64;
+
+    let invert_mask = sequences_starting_on_even_bits << 1;
+    (EVEN_BITS ^ invert_mask) & follows_escape
+}
+
+#[inline]
+fn structural_bitmask_64(block: &[u8; 64]) -> u64 {
+    eq_bitmask_64(block, b'{')
+        | eq_bitmask_64(block, b'}')
+        | eq_bitmask_64(block, b'[')
+        | eq_bitmask_64(block, b']')
+        | eq_bitmask_64(block, b':')
+        | eq_bitmask_64(block, b',')
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Index
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Why stage 1 rejected the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonIndexError {
+    /// The document ends inside a string.
+    UnclosedString,
+    /// Positions are stored as u32; inputs must be smaller than 4 GB.
+    InputTooLarge,
+}
+
+impl fmt::Display for JsonIndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonIndexError::UnclosedString => write!(f, "unclosed string"),
+            JsonIndexError::InputTooLarge => write!(f, "input larger than 4 GB"),
+        }
+    }
+}
+
+impl std::error::Error for JsonIndexError {}
+
+/// Structural index of a JSON document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonIndex {
+    /// One mask per 64-byte block: bit i set if byte `64 * block + i` is structural.
+    pub blocks: Vec<u64>,
+    /// Offsets of all structural characters and string boundaries, ascending.
+    pub positions: Vec<u32>,
+}
+
+/// Build the structural index of `data`.
+///
+/// # Example
+/// ```
+/// use scratchpad::json_index::build_index;
+///
+/// let index = build_index(br#"{"a":[1,"x,y"]}"#).unwrap();
+/// assert_eq!(index.positions, vec![0, 1, 3, 4, 5, 7, 8, 12, 13, 14]);
+/// ```
+pub fn build_index(data: &[u8]) -> Result<JsonIndex, JsonIndexError> {
+    if data.len() > u32::MAX as usize {
+        return Err(JsonIndexError::InputTooLarge);
+    }
+
+    let num_blocks = data.len().div_ceil(64);
+    let mut blocks = Vec::with_capacity(num_blocks);
+    let mut positions = Vec::new();
+
+    let mut prev_escaped = 0u64;
+    let mut prev_in_string = 0u64;
+
+    for (b, chunk) in data.chunks(64).enumerate() {
+        let block = if chunk.len() == 64 {
+            chunk.try_into().unwrap()
+        } else {
+            padded_block(chunk)
+        };
+
+        let backslash = eq_bitmask_64(&block, b'\\');
+        let escaped = find_escaped(backslash, &mut prev_escaped);
+        let quotes = eq_bitmask_64(&block, b'"') & !escaped;
+
+        let in_string = prefix_xor(quotes) ^ prev_in_string;
+        prev_in_string = ((in_string as i64) >> 63) as u64;
+
+        let structural = (structural_bitmask_64(&block) & !in_string) | quotes;
+        blocks.push(structural);
+
+        // Extract set bits as positions
+        let base = (b * 64) as u32;
+        let mut bits = structural;
+        while bits != 0 {
+            positions.push(base + bits.trailing_zeros());
+            bits &= bits - 1;
+        }
+    }
+
+    if prev_in_string != 0 {
+        return Err(JsonIndexError::UnclosedString);
+    }
+
+    Ok(JsonIndex { blocks, positions })
+}
+
+/// Build the list of structural positions byte by byte (scalar reference).
+pub fn structural_positions_scalar(data: &[u8]) -> Result<Vec<u32>, JsonIndexError> {
+    let mut positions = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, &b) in data.iter().enumerate() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match b {
+            b'\\' => escaped = true,
+            b'"' => {
+                in_string = !in_string;
+                positions.push(i as u32);
+            }
+            b'{' | b'}' | b'[' | b']' | b':' | b',' if !in_string => positions.push(i as u32),
+            _ => {}
+        }
+    }
+
+    if in_string {
+        return Err(JsonIndexError::UnclosedString);
+    }
+    Ok(positions)
+}
+
+/// Byte ranges of the elements of a top-level JSON array, using only the index.
+///
+/// Returns an empty list if the document is not an array. Element ranges are
+/// not trimmed of surrounding whitespace.
+///
+/// # Example
+/// ```
+/// use scratchpad::json_index::{build_index, top_level_elements};
+///
+/// let data = br#"[{"a":1},[2,3],"s"]"#;
+/// let index = build_index(data).unwrap();
+/// let elements: Vec<&[u8]> =
+///     top_level_elements(data, &index).into_iter().map(|r| &data[r]).collect();
+/// assert_eq!(elements, vec![&br#"{"a":1}"#[..], b"[2,3]", br#""s""#]);
+/// ```
+pub fn top_level_elements(data: &[u8], index: &JsonIndex) -> Vec<std::ops::Range<usize>> {
+    let mut elements = Vec::new();
+    let mut positions = index.positions.iter().map(|&p| p as usize);
+
+    let mut element_start = match positions.next() {
+        Some(p) if data[p] == b'[' => p + 1,
+        _ => return elements,
+    };
+    let mut depth = 0usize;
+
+    // Structurals inside strings are already masked out; quotes are skipped
+    for p in positions {
+        let b = data[p];
+        match b {
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' if depth > 0 => depth -= 1,
+            b',' | b']' if depth == 0 => {
+                if data[element_start..p]
+                    .iter()
+                    .any(|c| !c.is_ascii_whitespace())
+                {
+                    elements.push(element_start..p);
+                }
+                element_start = p + 1;
+                if b == b']' {
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    elements
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simple_object() {
+        let data = br#"{"key": "value", "n": [1, 2]}"#;
+        let index = build_index(data).unwrap();
+        assert_eq!(index.positions, structural_positions_scalar(data).unwrap());
+        assert_eq!(index.blocks.len(), 1);
+    }
+
+    #[test]
+    fn test_structurals_inside_strings_ignored() {
+        let data = br#"{"a,b:{[]}": 1}"#;
+        let index = build_index(data).unwrap();
+        let chars: Vec<u8> = index.positions.iter().map(|&p| data[p as usize]).collect();
+        assert_eq!(chars, b"{\"\":}".to_vec());
+    }
+
+    #[test]
+    fn test_escaped_quotes() {
+        let data = br#"["a\"b", "c\\", "d\\\"e"]"#;
+        let index = build_index(data).unwrap();
+        assert_eq!(index.positions, structural_positions_scalar(data).unwrap());
+        assert_eq!(index.positions.len(), 2 + 2 * 3 + 2);
+    }
+
+    #[test]
+    fn test_unclosed_string() {
+        assert_eq!(build_index(br#"{"a": "b"#), Err(JsonIndexError::UnclosedString));
+        assert_eq!(build_index(br#"["\"]"#), Err(JsonIndexError::UnclosedString));
+    }
+
+    #[test]
+    fn test_empty() {
+        let index = build_index(b"").unwrap();
+        assert!(index.blocks.is_empty());
+        assert!(index.positions.is_empty());
+    }
+
+    #[test]
+    fn test_matches_scalar_across_blocks() {
+        // Strings and backslash runs straddling 64-byte boundaries
+        let mut data = Vec::new();
+        let mut rng = 7u64;
+        data.push(b'[');
+        for i in 0..500 {
+            rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+            let backslashes = "\\\\".repeat(((rng >> 16) % 4) as usize);
+            let quote = if (rng >> 20) & 3 == 0 { "\\\"" } else { "" };
+            data.extend_from_slice(
+                format!("{{\"k{}\":\"v{}{}x,[]\",\"n\":{}}},", i, backslashes, quote, rng % 100)
+                    .as_bytes(),
+            );
+        }
+        data.extend_from_slice(b"0]");
+
+        let index = build_index(&data).unwrap();
+        assert_eq!(index.positions, structural_positions_scalar(&data).unwrap());
+
+        for len in (0..data.len()).step_by(37) {
+            assert_eq!(
+                build_index(&data[..len]).map(|i| i.positions),
+                structural_positions_scalar(&data[..len]),
+                "Mismatch for len={}",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn test_blocks_match_positions() {
+        let data: Vec<u8> = br#"{"a":1},"#.iter().cycle().take(200).copied().collect();
+        let index = build_index(&data).unwrap();
+        let from_blocks: usize = index.blocks.iter().map(|b| b.count_ones() as usize).sum();
+        assert_eq!(from_blocks, index.positions.len());
+    }
+
+    #[test]
+    fn test_top_level_elements() {
+        let data = br#"[ 1 , {"a": [1, 2]}, "x,]" , [] ]"#;
+        let index = build_index(data).unwrap();
+        let elements: Vec<&[u8]> = top_level_elements(data, &index)
+            .into_iter()
+            .map(|r| &data[r])
+            .collect();
+        assert_eq!(elements, vec![&b" 1 "[..], br#" {"a": [1, 2]}"#, br#" "x,]" "#, b" [] "]);
+
+        let data = b"[]";
+        assert!(top_level_elements(data, &build_index(data).unwrap()).is_empty());
+
+        let data = br#"{"a": 1}"#;
+        assert!(top_level_elements(data, &build_index(data).unwrap()).is_empty());
+    }
+}
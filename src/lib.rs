@@ -2,3 +2,59 @@ pub mod line_feed_every_k_bytes;
 pub mod json_escape_SWAR;
 pub mod csv_parse_buffer_size_impact;
 pub mod csv_state_machine;
+pub mod number_spans;
+pub mod relite;
+pub mod log_classify;
+pub mod bitmask;
+pub mod json_index;
+pub mod byte_set;
+pub mod html_escape;
+pub mod timestamp;
+pub mod lines;
+pub mod time_buckets;
+pub mod reverse_scan;
+pub mod sql_escape;
+pub mod sparse;
+pub mod hash;
+pub mod cdc;
+pub mod line_index;
+#[cfg(feature = "serde")]
+pub mod serde_escape;
+pub mod sha256;
+pub mod merkle;
+pub mod manifest;
+pub mod sink;
+pub mod json_escape_utf16;
+pub mod wtf8;
+pub mod utf8;
+pub mod msgpack;
+pub mod json_number;
+pub mod csv_reader;
+pub mod csv_write;
+#[cfg(feature = "arrow")]
+pub mod csv_columnar;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod csv_transform;
+pub mod csv_schema;
+pub mod hll;
+pub mod csv_dialect;
+pub mod cms;
+pub mod csv_index;
+pub mod cpu;
+#[cfg(all(feature = "uring", target_os = "linux"))]
+pub mod uring;
+pub mod uncached;
+pub mod readahead;
+pub mod prefetch;
+pub mod needle;
+pub mod cpu_info;
+pub mod scratch;
+pub mod scan;
+pub mod progress;
+pub mod bench;
+pub mod cache_control;
+pub mod bench_report;
+pub mod datagen;
+pub mod conformance;
+pub mod energy;
@@ -1,5 +1,8 @@
+#[cfg(target_arch = "aarch64")]
 use std::arch::aarch64::*;
 
+use crate::cpu::{self, UnsupportedTarget};
+
 // ═══════════════════════════════════════════════════════════════════════════
 //                        NEON SIMD Line Feed Insertion
 // ═══════════════════════════════════════════════════════════════════════════
@@ -12,7 +15,12 @@ use std::arch::aarch64::*;
 // Architecture:
 //   insert_line_feed_scalar()         Simple reference implementation
 //   insert_line_feed32_neon_impl()    Core SIMD kernel (32→33 bytes)
-//   insert_line_feed_neon()           Main driver for arbitrary buffers
+//   insert_line_feed_neon_unchecked() Main driver for arbitrary buffers
+//   insert_line_feed_neon()           Driver behind a runtime NEON check
+//   insert_line_feed()                NEON if available, scalar otherwise
+//
+// The `_into` variants write into a caller's Vec (cleared first) instead of
+// allocating one, for buffers reused from a ScratchPool.
 //
 // Core technique: Mark insertion points with 255 in shuffle masks, then blend
 // with linefeeds using vbslq_u8. For insertions in the lower 16 bytes, use
@@ -89,6 +97,9 @@ pub static SHUFFLE_MASKS_NEON: [[u8; 16]; 16] = [
 //   n ≥ 16    Insert in upper register
 //   n < 16    Insert in lower, shift upper (requires vextq_u8)
 
+/// # Safety
+/// Requires a CPU with NEON support (see [`cpu::has_neon`]).
+#[cfg(target_arch = "aarch64")]
 #[target_feature(enable = "neon")]
 pub unsafe fn insert_line_feed32_neon_impl(input: &[u8; 32], n: usize) -> [u8; 33] {
     let mut output = [0u8; 33];
@@ -204,13 +215,23 @@ pub unsafe fn insert_line_feed32_neon_impl(input: &[u8; 32], n: usize) -> [u8; 3
 // ═══════════════════════════════════════════════════════════════════════════
 
 pub fn insert_line_feed_scalar(buffer: &[u8], k: usize) -> Vec<u8> {
+    let mut output = Vec::new();
+    insert_line_feed_scalar_into(buffer, k, &mut output);
+    output
+}
+
+/// [`insert_line_feed_scalar`] into `output`, cleared first: its allocation
+/// is reused (a [`ScratchPool`](crate::scratch::ScratchPool) buffer, or the
+/// output of the previous call).
+pub fn insert_line_feed_scalar_into(buffer: &[u8], k: usize, output: &mut Vec<u8>) {
+    output.clear();
     if k == 0 {
-        return buffer.to_vec();
+        output.extend_from_slice(buffer);
+        return;
     }
 
     let num_line_feeds = buffer.len() / k;
-    let output_len = buffer.len() + num_line_feeds;
-    let mut output = Vec::with_capacity(output_len);
+    output.reserve(buffer.len() + num_line_feeds);
 
     let mut input_pos = 0;
 
@@ -221,8 +242,6 @@ pub fn insert_line_feed_scalar(buffer: &[u8], k: usize) -> Vec<u8> {
     }
 
     output.extend_from_slice(&buffer[input_pos..]);
-
-    output
 }
 
 // ═══════════════════════════════════════════════════════════════════════════
@@ -233,14 +252,33 @@ pub fn insert_line_feed_scalar(buffer: &[u8], k: usize) -> Vec<u8> {
 //   k ≤ 32:  Use shuffle-based SIMD kernel
 //   k > 32:  Bulk SIMD copy (32 bytes/iteration) + append '\n'
 
-pub fn insert_line_feed_neon(buffer: &[u8], k: usize) -> Vec<u8> {
+/// # Safety
+/// Requires a CPU with NEON support (see [`cpu::has_neon`]).
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+pub unsafe fn insert_line_feed_neon_unchecked(buffer: &[u8], k: usize) -> Vec<u8> {
+    let mut output = Vec::new();
+    insert_line_feed_neon_unchecked_into(buffer, k, &mut output);
+    output
+}
+
+/// [`insert_line_feed_neon_unchecked`] into `output`, cleared first.
+///
+/// # Safety
+/// Requires a CPU with NEON support (see [`cpu::has_neon`]).
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+pub unsafe fn insert_line_feed_neon_unchecked_into(buffer: &[u8], k: usize, output: &mut Vec<u8>) {
+    output.clear();
     if k == 0 {
-        return buffer.to_vec();
+        output.extend_from_slice(buffer);
+        return;
     }
 
     let num_line_feeds = buffer.len() / k;
     let output_len = buffer.len() + num_line_feeds;
-    let mut output = Vec::with_capacity(output_len);
+    // The 32-byte stores of the last chunk can run up to 31 bytes past it
+    output.reserve(output_len + 32);
 
     let mut input_pos = 0;
 
@@ -361,10 +399,61 @@ pub fn insert_line_feed_neon(buffer: &[u8], k: usize) -> Vec<u8> {
 
     // Copy leftover bytes (incomplete final chunk, no '\n')
     output.extend_from_slice(&buffer[input_pos..]);
+}
 
+// ═══════════════════════════════════════════════════════════════════════════
+//                               Dispatch
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Cross-compiled binaries and emulators can run on CPUs without NEON: the
+// checked entry points never execute the kernel there.
+
+/// Insert '\n' every `k` bytes with NEON, or [`UnsupportedTarget`] if this
+/// CPU has no NEON.
+#[cfg_attr(not(target_arch = "aarch64"), allow(unused_variables))]
+pub fn insert_line_feed_neon(buffer: &[u8], k: usize) -> Result<Vec<u8>, UnsupportedTarget> {
+    cpu::require_neon()?;
+
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        Ok(insert_line_feed_neon_unchecked(buffer, k))
+    }
+
+    // require_neon always fails off aarch64
+    #[cfg(not(target_arch = "aarch64"))]
+    unreachable!()
+}
+
+/// Insert '\n' every `k` bytes (best available version).
+pub fn insert_line_feed(buffer: &[u8], k: usize) -> Vec<u8> {
+    let mut output = Vec::new();
+    insert_line_feed_into(buffer, k, &mut output);
     output
 }
 
+/// [`insert_line_feed`] into `output`, cleared first: no allocation once it
+/// is large enough.
+///
+/// ```
+/// use scratchpad::line_feed_every_k_bytes::insert_line_feed_into;
+/// use scratchpad::scratch::ScratchPool;
+///
+/// let pool = ScratchPool::new();
+/// for request in [&b"ABCDEFGHIJ"[..], b"KLMNOP"] {
+///     let mut output = pool.take_empty(2 * request.len());
+///     insert_line_feed_into(request, 3, &mut output);
+///     assert_eq!(output[3], b'\n');
+/// } // back in the pool for the next request
+/// ```
+pub fn insert_line_feed_into(buffer: &[u8], k: usize, output: &mut Vec<u8>) {
+    #[cfg(target_arch = "aarch64")]
+    if cpu::has_neon() {
+        return unsafe { insert_line_feed_neon_unchecked_into(buffer, k, output) };
+    }
+
+    insert_line_feed_scalar_into(buffer, k, output)
+}
+
 // ═══════════════════════════════════════════════════════════════════════════
 //                                 Tests
 // ═══════════════════════════════════════════════════════════════════════════
@@ -408,12 +497,40 @@ mod tests {
         assert_eq!(result, b"");
     }
 
+    #[test]
+    fn test_dispatch_matches_scalar() {
+        let input: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+        for k in [0, 1, 16, 33, 72] {
+            assert_eq!(insert_line_feed(&input, k), insert_line_feed_scalar(&input, k));
+        }
+    }
+
+    #[test]
+    fn test_into_reuses_output() {
+        let input: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+        let mut output = b"stale".to_vec();
+        for k in [0, 3, 33] {
+            insert_line_feed_into(&input, k, &mut output);
+            assert_eq!(output, insert_line_feed_scalar(&input, k));
+        }
+        let ptr = output.as_ptr();
+        insert_line_feed_into(&input[..10], 3, &mut output);
+        assert_eq!(output, b"\0\x01\x02\n\x03\x04\x05\n\x06\x07\x08\n\x09");
+        assert_eq!(output.as_ptr(), ptr);
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "aarch64"))]
+    fn test_neon_unsupported() {
+        assert_eq!(insert_line_feed_neon(b"ABCDEF", 3), Err(UnsupportedTarget { feature: "neon" }));
+    }
+
     #[test]
     #[cfg(target_arch = "aarch64")]
     fn test_neon_matches_scalar_small() {
         let input = b"ABCDEFGHIJ";
         let scalar = insert_line_feed_scalar(input, 3);
-        let neon = insert_line_feed_neon(input, 3);
+        let neon = insert_line_feed_neon(input, 3).unwrap();
         assert_eq!(scalar, neon, "NEON and scalar results should match for small input");
     }
 
@@ -425,7 +542,7 @@ mod tests {
 
         for k in [1, 5, 10, 15, 16, 20, 31, 32, 50, 64, 72, 100, 128] {
             let scalar = insert_line_feed_scalar(&input, k);
-            let neon = insert_line_feed_neon(&input, k);
+            let neon = insert_line_feed_neon(&input, k).unwrap();
             assert_eq!(scalar, neon, "NEON and scalar results should match for k={}", k);
         }
     }
@@ -479,7 +596,7 @@ mod tests {
     #[cfg(target_arch = "aarch64")]
     fn test_neon_zero_k() {
         let input = b"ABCDEF";
-        let result = insert_line_feed_neon(input, 0);
+        let result = insert_line_feed_neon(input, 0).unwrap();
         assert_eq!(result, b"ABCDEF");
     }
 
@@ -487,7 +604,7 @@ mod tests {
     #[cfg(target_arch = "aarch64")]
     fn test_neon_empty() {
         let input = b"";
-        let result = insert_line_feed_neon(input, 3);
+        let result = insert_line_feed_neon(input, 3).unwrap();
         assert_eq!(result, b"");
     }
 }
@@ -0,0 +1,409 @@
+//! Incremental line index of huge files, maintained with content-defined chunks.
+//!
+//! A line index (byte offset of every line start) makes "jump to line N" and
+//! parallel splitting instant, but rebuilding it after every append or edit
+//! of a multi-GB file is a full pass. Here the index is stored per chunk,
+//! where chunks come from the gear-hash chunker in [`crate::cdc`]:
+//!
+//!   chunk:  offset | len | hash64 | line starts (relative to the chunk)
+//!
+//! [`reindex`] re-chunks the file and, for every chunk whose (hash, len) is
+//! already in the old index, reuses the stored line starts instead of
+//! scanning the chunk again. Since content-defined boundaries move with the
+//! data, an insert in the middle only invalidates the chunks around it.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+};
+
+use crate::{
+    cdc::{next_boundary, ChunkerParams},
+    hash::hash64,
+};
+
+const HASH_SEED: u64 = 0x11AE_1DE7;
+const MAGIC: &[u8; 8] = b"LINEIDX1";
+
+/// One content-defined chunk and the lines starting in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    /// Byte offset of the chunk in the file.
+    pub offset: u64,
+    pub len: u32,
+    pub hash: u64,
+    /// Offsets (relative to the chunk) of the bytes following each `\n`.
+    pub line_starts: Vec<u32>,
+}
+
+/// Line index of a file, stored per chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    pub params: ChunkerParams,
+    pub chunks: Vec<Chunk>,
+}
+
+/// How much of the old index [`reindex_with_stats`] could reuse.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReindexStats {
+    pub reused_chunks: usize,
+    pub rescanned_chunks: usize,
+    pub rescanned_bytes: u64,
+}
+
+impl LineIndex {
+    /// Number of lines in the file (a last line without `\n` counts).
+    pub fn line_count(&self) -> u64 {
+        let newlines: u64 = self.chunks.iter().map(|c| c.line_starts.len() as u64).sum();
+        let file_len = self.chunks.last().map_or(0, |c| c.offset + c.len as u64);
+
+        // Every `\n` starts a line, except one at the very end of the file
+        let ends_with_newline = self
+            .chunks
+            .last()
+            .and_then(|c| c.line_starts.last().map(|&s| s == c.len))
+            .unwrap_or(false);
+        match file_len {
+            0 => 0,
+            _ => newlines + 1 - ends_with_newline as u64,
+        }
+    }
+
+    /// Byte offset of the start of line `n` (0-based), if the file has that many lines.
+    pub fn line_offset(&self, n: u64) -> Option<u64> {
+        if n == 0 {
+            return (self.line_count() > 0).then_some(0);
+        }
+
+        let mut remaining = n - 1;
+        for chunk in &self.chunks {
+            let count = chunk.line_starts.len() as u64;
+            if remaining < count {
+                let offset = chunk.offset + chunk.line_starts[remaining as usize] as u64;
+                return (n < self.line_count()).then_some(offset);
+            }
+            remaining -= count;
+        }
+        None
+    }
+
+    /// Write the index in a compact little-endian binary format.
+    pub fn save(&self, file_path: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(file_path)?);
+        out.write_all(MAGIC)?;
+        for v in [
+            self.params.min_size,
+            self.params.avg_size,
+            self.params.max_size,
+        ] {
+            out.write_all(&(v as u64).to_le_bytes())?;
+        }
+        out.write_all(&(self.chunks.len() as u64).to_le_bytes())?;
+
+        for chunk in &self.chunks {
+            out.write_all(&chunk.offset.to_le_bytes())?;
+            out.write_all(&chunk.len.to_le_bytes())?;
+            out.write_all(&chunk.hash.to_le_bytes())?;
+            out.write_all(&(chunk.line_starts.len() as u32).to_le_bytes())?;
+            for start in &chunk.line_starts {
+                out.write_all(&start.to_le_bytes())?;
+            }
+        }
+        out.flush()
+    }
+
+    /// Read an index written by [`LineIndex::save`].
+    ///
+    /// Fails with `InvalidData` if the file is not an index or its chunker
+    /// parameters are invalid (see [`ChunkerParams::validate`]).
+    pub fn load(file_path: &str) -> io::Result<Self> {
+        let mut input = BufReader::new(File::open(file_path)?);
+
+        let mut magic = [0u8; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a line index file"));
+        }
+
+        fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+            let mut b = [0u8; 8];
+            r.read_exact(&mut b)?;
+            Ok(u64::from_le_bytes(b))
+        }
+        fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+            let mut b = [0u8; 4];
+            r.read_exact(&mut b)?;
+            Ok(u32::from_le_bytes(b))
+        }
+
+        let params = ChunkerParams {
+            min_size: read_u64(&mut input)? as usize,
+            avg_size: read_u64(&mut input)? as usize,
+            max_size: read_u64(&mut input)? as usize,
+        };
+        // Checked now: `reindex` would panic on them, or allocate max_size * 4
+        params
+            .validate()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let num_chunks = read_u64(&mut input)?;
+
+        let mut chunks = Vec::new();
+        for _ in 0..num_chunks {
+            let offset = read_u64(&mut input)?;
+            let len = read_u32(&mut input)?;
+            let hash = read_u64(&mut input)?;
+            let count = read_u32(&mut input)?;
+            let line_starts = (0..count)
+                .map(|_| read_u32(&mut input))
+                .collect::<io::Result<_>>()?;
+            chunks.push(Chunk { offset, len, hash, line_starts });
+        }
+
+        Ok(LineIndex { params, chunks })
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                           Building the Index
+// ═══════════════════════════════════════════════════════════════════════════
+
+fn scan_line_starts(data: &[u8]) -> Vec<u32> {
+    memchr::memchr_iter(b'\n', data)
+        .map(|p| p as u32 + 1)
+        .collect()
+}
+
+/// Stream the file through the chunker, calling `f(offset, chunk_bytes)` per chunk.
+fn for_each_chunk<F>(file_path: &str, params: &ChunkerParams, mut f: F) -> io::Result<()>
+where
+    F: FnMut(u64, &[u8]),
+{
+    let mut file = File::open(file_path)?;
+    let mut buffer = vec![0u8; params.max_size * 4];
+    let mut filled = 0;
+    let mut offset = 0u64;
+    let mut eof = false;
+
+    loop {
+        // Top up the buffer so a full max_size window is available
+        while !eof && filled < buffer.len() {
+            let n = file.read(&mut buffer[filled..])?;
+            eof = n == 0;
+            filled += n;
+        }
+
+        let mut pos = 0;
+        while pos < filled {
+            let len = match next_boundary(&buffer[pos..filled], params) {
+                Some(len) => len,
+                None if eof => filled - pos,
+                None => break,
+            };
+            f(offset, &buffer[pos..pos + len]);
+            offset += len as u64;
+            pos += len;
+        }
+
+        buffer.copy_within(pos..filled, 0);
+        filled -= pos;
+        if eof && filled == 0 {
+            return Ok(());
+        }
+    }
+}
+
+/// Build the line index of a file from scratch.
+pub fn build_index(file_path: &str, params: ChunkerParams) -> io::Result<LineIndex> {
+    let mut chunks = Vec::new();
+    for_each_chunk(file_path, &params, |offset, data| {
+        chunks.push(Chunk {
+            offset,
+            len: data.len() as u32,
+            hash: hash64(data, HASH_SEED),
+            line_starts: scan_line_starts(data),
+        });
+    })?;
+    Ok(LineIndex { params, chunks })
+}
+
+/// Rebuild the line index of a changed file, reusing unchanged chunks of `old_index`.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::cdc::ChunkerParams;
+/// use scratchpad::line_index::{build_index, reindex};
+///
+/// let index = build_index("big.log", ChunkerParams::default()).unwrap();
+/// // ... the file is appended to or edited ...
+/// let index = reindex("big.log", &index).unwrap();
+/// println!("{} lines", index.line_count());
+/// ```
+pub fn reindex(file_path: &str, old_index: &LineIndex) -> io::Result<LineIndex> {
+    reindex_with_stats(file_path, old_index).map(|(index, _)| index)
+}
+
+/// Like [`reindex`], also reporting how many chunks were reused.
+pub fn reindex_with_stats(
+    file_path: &str,
+    old_index: &LineIndex,
+) -> io::Result<(LineIndex, ReindexStats)> {
+    let known: HashMap<(u64, u32), &[u32]> = old_index
+        .chunks
+        .iter()
+        .map(|c| ((c.hash, c.len), c.line_starts.as_slice()))
+        .collect();
+
+    let mut stats = ReindexStats::default();
+    let mut chunks = Vec::new();
+
+    for_each_chunk(file_path, &old_index.params, |offset, data| {
+        let hash = hash64(data, HASH_SEED);
+        let len = data.len() as u32;
+
+        let line_starts = match known.get(&(hash, len)) {
+            Some(starts) => {
+                stats.reused_chunks += 1;
+                starts.to_vec()
+            }
+            None => {
+                stats.rescanned_chunks += 1;
+                stats.rescanned_bytes += len as u64;
+                scan_line_starts(data)
+            }
+        };
+        chunks.push(Chunk { offset, len, hash, line_starts });
+    })?;
+
+    Ok((LineIndex { params: old_index.params, chunks }, stats))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMALL: ChunkerParams = ChunkerParams { min_size: 512, avg_size: 2048, max_size: 8192 };
+
+    fn generate_log(lines: usize, seed: u64) -> Vec<u8> {
+        let mut rng = seed;
+        let mut data = Vec::new();
+        for i in 0..lines {
+            rng = rng
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            data.extend_from_slice(
+                format!("{} event={:x} {}\n", i, rng, "x".repeat((rng >> 58) as usize)).as_bytes(),
+            );
+        }
+        data
+    }
+
+    fn line_offsets(data: &[u8]) -> Vec<u64> {
+        crate::lines::split_lines(data)
+            .scan(0u64, |pos, line| {
+                let start = *pos;
+                *pos += line.len() as u64 + 1;
+                Some(start)
+            })
+            .collect()
+    }
+
+    fn check_index(index: &LineIndex, data: &[u8]) {
+        let expected = line_offsets(data);
+        assert_eq!(index.line_count(), expected.len() as u64);
+        for (n, &offset) in expected.iter().enumerate() {
+            assert_eq!(index.line_offset(n as u64), Some(offset), "Mismatch for line {}", n);
+        }
+        assert_eq!(index.line_offset(expected.len() as u64), None);
+    }
+
+    #[test]
+    fn test_build_index() {
+        let path = "/tmp/test_line_index_build.log";
+        let mut data = generate_log(3000, 1);
+        data.extend_from_slice(b"last line without newline");
+        std::fs::write(path, &data).unwrap();
+
+        let index = build_index(path, SMALL).unwrap();
+        assert!(index.chunks.len() > 10);
+        check_index(&index, &data);
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_reindex_after_insert_reuses_most_chunks() {
+        let path = "/tmp/test_line_index_reindex.log";
+        let data = generate_log(5000, 2);
+        std::fs::write(path, &data).unwrap();
+        let old = build_index(path, SMALL).unwrap();
+
+        // Insert a few lines in the middle
+        let mid = data.len() / 2 + memchr::memchr(b'\n', &data[data.len() / 2..]).unwrap() + 1;
+        let mut edited = data[..mid].to_vec();
+        edited.extend_from_slice(b"inserted\nlines\n");
+        edited.extend_from_slice(&data[mid..]);
+        std::fs::write(path, &edited).unwrap();
+
+        let (new, stats) = reindex_with_stats(path, &old).unwrap();
+        check_index(&new, &edited);
+        assert_eq!(new, build_index(path, SMALL).unwrap());
+        assert!(stats.reused_chunks > 10 * stats.rescanned_chunks, "{:?}", stats);
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_save_load_roundtrip() {
+        let path = "/tmp/test_line_index_save.log";
+        let index_path = "/tmp/test_line_index_save.idx";
+        std::fs::write(path, generate_log(1000, 3)).unwrap();
+
+        let index = build_index(path, SMALL).unwrap();
+        index.save(index_path).unwrap();
+        assert_eq!(LineIndex::load(index_path).unwrap(), index);
+
+        std::fs::write(index_path, b"garbage").unwrap();
+        assert!(LineIndex::load(index_path).is_err());
+
+        std::fs::remove_file(path).ok();
+        std::fs::remove_file(index_path).ok();
+    }
+
+    #[test]
+    fn test_load_rejects_invalid_params() {
+        let path = "/tmp/test_line_index_params.log";
+        let index_path = "/tmp/test_line_index_params.idx";
+        std::fs::write(path, generate_log(100, 4)).unwrap();
+        build_index(path, SMALL).unwrap().save(index_path).unwrap();
+        let saved = std::fs::read(index_path).unwrap();
+
+        // Header: magic, then min_size, avg_size and max_size as u64
+        for (field, value) in [(1, 3u64), (0, 0), (0, 1 << 20), (2, 1 << 40)] {
+            let mut corrupted = saved.clone();
+            let at = 8 + field * 8;
+            corrupted[at..at + 8].copy_from_slice(&value.to_le_bytes());
+            std::fs::write(index_path, &corrupted).unwrap();
+
+            let err = LineIndex::load(index_path).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "field {} = {}", field, value);
+        }
+
+        std::fs::remove_file(path).ok();
+        std::fs::remove_file(index_path).ok();
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let path = "/tmp/test_line_index_empty.log";
+        std::fs::write(path, b"").unwrap();
+        let index = build_index(path, SMALL).unwrap();
+        assert_eq!(index.line_count(), 0);
+        assert_eq!(index.line_offset(0), None);
+        std::fs::remove_file(path).ok();
+    }
+}
@@ -0,0 +1,73 @@
+//! Split a buffer into lines with memchr.
+//!
+//! The line-oriented modules (log classification, time bucketing, ...) all
+//! need the same loop; this keeps the edge cases in one place:
+//! - the `\n` terminator is not part of the line
+//! - a final line without `\n` is still yielded
+//! - a trailing `\n` does not produce an extra empty line
+
+/// Iterator over the lines of a buffer. Created by [`split_lines`].
+pub struct Lines<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = &'a [u8];
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a [u8]> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+
+        let rest = &self.data[self.pos..];
+        match memchr::memchr(b'\n', rest) {
+            Some(end) => {
+                self.pos += end + 1;
+                Some(&rest[..end])
+            }
+            None => {
+                self.pos = self.data.len();
+                Some(rest)
+            }
+        }
+    }
+}
+
+/// Split `data` into lines (without their `\n`).
+///
+/// # Example
+/// ```
+/// use scratchpad::lines::split_lines;
+///
+/// let lines: Vec<&[u8]> = split_lines(b"a\n\nbc\n").collect();
+/// assert_eq!(lines, vec![&b"a"[..], b"", b"bc"]);
+/// ```
+pub fn split_lines(data: &[u8]) -> Lines<'_> {
+    Lines { data, pos: 0 }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_lines() {
+        assert_eq!(split_lines(b"").count(), 0);
+        assert_eq!(split_lines(b"\n").collect::<Vec<_>>(), vec![&b""[..]]);
+        assert_eq!(split_lines(b"x").collect::<Vec<_>>(), vec![&b"x"[..]]);
+        assert_eq!(split_lines(b"a\nb").collect::<Vec<_>>(), vec![&b"a"[..], b"b"]);
+    }
+
+    #[test]
+    fn test_matches_std_split() {
+        let data = b"one\ntwo\n\nthree\r\nfour";
+        let expected: Vec<&[u8]> = data.split(|&b| b == b'\n').collect();
+        assert_eq!(split_lines(data).collect::<Vec<_>>(), expected);
+    }
+}
@@ -0,0 +1,151 @@
+//! Classify log lines by severity keywords in a single pass.
+//!
+//! The usual dashboard recipe is one `grep -c` per class (ERROR, WARN, INFO),
+//! i.e. one full pass over the data per class. Here all keywords of all
+//! classes go into one [`LiteralSet`], the buffer is scanned once, and each hit
+//! is attributed to the line it falls on.
+//!
+//! Classes are in priority order: a line is counted for the first class (by
+//! index) that has a hit on it, so "ERROR: retrying after WARN" is an ERROR
+//! line. Lines without any hit are not counted.
+
+use crate::relite::LiteralSet;
+
+/// Count lines per class, where each class is a set of keywords.
+///
+/// Returns one count per entry of `keyword_sets`.
+///
+/// # Example
+/// ```
+/// use scratchpad::log_classify::classify_lines;
+///
+/// let log = b"ERROR disk full\nWARN slow\nINFO ok\nWARN: ERROR retry\nDEBUG x\n";
+/// let errors: &[&[u8]] = &[b"ERROR", b"FATAL"];
+/// let warnings: &[&[u8]] = &[b"WARN"];
+/// let infos: &[&[u8]] = &[b"INFO"];
+///
+/// assert_eq!(classify_lines(log, &[errors, warnings, infos]), vec![2, 1, 1]);
+/// ```
+pub fn classify_lines(data: &[u8], keyword_sets: &[&[&[u8]]]) -> Vec<usize> {
+    let mut counts = vec![0usize; keyword_sets.len()];
+
+    // Flatten all keywords into one matcher, remembering each keyword's class
+    let mut keywords: Vec<&[u8]> = Vec::new();
+    let mut class_of: Vec<usize> = Vec::new();
+    for (class, set) in keyword_sets.iter().enumerate() {
+        for &keyword in set.iter() {
+            keywords.push(keyword);
+            class_of.push(class);
+        }
+    }
+    let matcher = LiteralSet::new(&keywords);
+
+    let mut i = 0;
+    // End of the line currently being classified, and its best class so far
+    let mut line_end = 0;
+    let mut line_class: Option<usize> = None;
+
+    while let Some((pos, idx)) = matcher.find_at(data, i) {
+        if line_class.is_some() && pos > line_end {
+            // Hit is on a later line: commit the previous one
+            counts[line_class.take().unwrap()] += 1;
+        }
+        if line_class.is_none() {
+            line_end = memchr::memchr(b'\n', &data[pos..]).map_or(data.len(), |p| pos + p);
+        }
+
+        let class = class_of[idx];
+        line_class = Some(line_class.map_or(class, |c| c.min(class)));
+
+        // Nothing can outrank class 0: skip the rest of the line
+        i = if class == 0 { line_end } else { pos + 1 };
+        if i >= data.len() {
+            break;
+        }
+    }
+
+    if let Some(class) = line_class {
+        counts[class] += 1;
+    }
+
+    counts
+}
+
+/// Count lines per class with one substring search per class and line (scalar reference).
+pub fn classify_lines_scalar(data: &[u8], keyword_sets: &[&[&[u8]]]) -> Vec<usize> {
+    let mut counts = vec![0usize; keyword_sets.len()];
+
+    for line in data.split(|&b| b == b'\n') {
+        let hit = keyword_sets.iter().position(|set| {
+            set.iter()
+                .any(|kw| !kw.is_empty() && line.windows(kw.len()).any(|w| w == *kw))
+        });
+        if let Some(class) = hit {
+            counts[class] += 1;
+        }
+    }
+
+    counts
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ERRORS: &[&[u8]] = &[b"ERROR", b"FATAL"];
+    const WARNINGS: &[&[u8]] = &[b"WARN"];
+    const INFOS: &[&[u8]] = &[b"INFO"];
+
+    #[test]
+    fn test_basic() {
+        let log = b"INFO start\nERROR boom\nWARN hmm\nINFO ok\nFATAL bye\n";
+        assert_eq!(classify_lines(log, &[ERRORS, WARNINGS, INFOS]), vec![2, 1, 2]);
+    }
+
+    #[test]
+    fn test_priority_within_line() {
+        // The WARN hit comes first on the line, but ERROR outranks it
+        let log = b"WARN then ERROR\nINFO and WARN\n";
+        assert_eq!(classify_lines(log, &[ERRORS, WARNINGS, INFOS]), vec![1, 1, 0]);
+    }
+
+    #[test]
+    fn test_multiple_hits_counted_once() {
+        let log = b"ERROR ERROR ERROR\nERROR\n";
+        assert_eq!(classify_lines(log, &[ERRORS, WARNINGS]), vec![2, 0]);
+    }
+
+    #[test]
+    fn test_no_trailing_newline_and_empty() {
+        assert_eq!(classify_lines(b"x\nWARN", &[ERRORS, WARNINGS]), vec![0, 1]);
+        assert_eq!(classify_lines(b"", &[ERRORS, WARNINGS]), vec![0, 0]);
+        assert_eq!(classify_lines(b"WARN", &[]), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_matches_scalar() {
+        let levels = [
+            "INFO",
+            "WARN",
+            "ERROR",
+            "DEBUG",
+            "FATAL",
+            "INFO WARN",
+            "WARN ERROR",
+        ];
+        let mut log = Vec::new();
+        let mut rng = 42u64;
+        for i in 0..2000 {
+            rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+            let level = levels[((rng >> 16) % levels.len() as u64) as usize];
+            log.extend_from_slice(format!("t={} {} message {}\n", i, level, rng % 97).as_bytes());
+        }
+
+        let sets = [ERRORS, WARNINGS, INFOS];
+        assert_eq!(classify_lines(&log, &sets), classify_lines_scalar(&log, &sets));
+    }
+}
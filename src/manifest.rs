@@ -0,0 +1,307 @@
+//! Checksum manifest of a directory tree, hashed in parallel.
+//!
+//! A manifest lists every regular file under a root with its size and
+//! SHA-256, one line per file, sorted by path:
+//!
+//!   <sha256 hex>  <size>  <relative/path>
+//!
+//! The output only depends on the file contents and names (no timestamps, no
+//! walk order), so two machines holding the same data produce byte-identical
+//! manifests and [`diff_manifests`] reports exactly what changed.
+//!
+//! Files are hashed by a fixed number of worker threads, each streaming its
+//! current file through one buffer: memory stays at `threads * BUFFER_SIZE`
+//! no matter how large the files are.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use crate::sha256::{to_hex, Sha256};
+
+const BUFFER_SIZE: usize = 1 << 20;
+
+/// (index in the sorted file list, size, hash)
+type HashedFile = (usize, u64, [u8; 32]);
+
+/// One file of a manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Path relative to the root, with `/` separators.
+    pub path: String,
+    pub size: u64,
+    pub hash: [u8; 32],
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                              Directory Walk
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Collect all regular files below `dir`, as (relative path, full path).
+///
+/// Symlinks are not followed, so a link cycle cannot make the walk infinite.
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+
+        if file_type.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if file_type.is_file() {
+            let relative = path.strip_prefix(root).unwrap();
+            let name = relative
+                .iter()
+                .map(|c| c.to_str())
+                .collect::<Option<Vec<_>>>()
+                .filter(|parts| parts.iter().all(|p| !p.contains('\n')))
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("unsupported file name: {:?}", relative),
+                    )
+                })?
+                .join("/");
+            files.push((name, path));
+        }
+    }
+    Ok(())
+}
+
+fn hash_file(path: &Path, buffer: &mut [u8]) -> io::Result<(u64, [u8; 32])> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut size = 0u64;
+
+    loop {
+        let bytes_read = match file.read(buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.update(&buffer[..bytes_read]);
+        size += bytes_read as u64;
+    }
+
+    Ok((size, hasher.finalize()))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                   API
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Hash every regular file under `root`, sorted by relative path.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::manifest::{manifest, write_manifest};
+///
+/// let entries = manifest("/data/export").unwrap();
+/// write_manifest(&entries, "/tmp/export.manifest").unwrap();
+/// ```
+pub fn manifest(root: &str) -> io::Result<Vec<ManifestEntry>> {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    manifest_with_threads(root, threads)
+}
+
+fn manifest_with_threads(root: &str, threads: usize) -> io::Result<Vec<ManifestEntry>> {
+    let root = Path::new(root);
+    let mut files = Vec::new();
+    collect_files(root, root, &mut files)?;
+    files.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+    // Workers pull the next file index; results are put back in order after
+    let next = AtomicUsize::new(0);
+    let results: Vec<io::Result<Vec<HashedFile>>> = thread::scope(|s| {
+        let workers: Vec<_> = (0..threads.clamp(1, files.len().max(1)))
+            .map(|_| {
+                s.spawn(|| {
+                    let mut buffer = vec![0u8; BUFFER_SIZE];
+                    let mut done = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some((_, path)) = files.get(i) else {
+                            return Ok(done);
+                        };
+                        let (size, hash) = hash_file(path, &mut buffer)?;
+                        done.push((i, size, hash));
+                    }
+                })
+            })
+            .collect();
+        workers.into_iter().map(|w| w.join().unwrap()).collect()
+    });
+
+    let mut hashed = vec![(0u64, [0u8; 32]); files.len()];
+    for result in results {
+        for (i, size, hash) in result? {
+            hashed[i] = (size, hash);
+        }
+    }
+
+    Ok(files
+        .into_iter()
+        .zip(hashed)
+        .map(|((path, _), (size, hash))| ManifestEntry { path, size, hash })
+        .collect())
+}
+
+/// Write a manifest in the `<hex>  <size>  <path>` line format.
+pub fn write_manifest(entries: &[ManifestEntry], file_path: &str) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(file_path)?);
+    for entry in entries {
+        writeln!(out, "{}  {}  {}", to_hex(&entry.hash), entry.size, entry.path)?;
+    }
+    out.flush()
+}
+
+/// Read a manifest written by [`write_manifest`].
+pub fn read_manifest(file_path: &str) -> io::Result<Vec<ManifestEntry>> {
+    let invalid = |line: usize| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("invalid manifest line {}", line + 1))
+    };
+
+    let mut entries = Vec::new();
+    for (n, line) in BufReader::new(File::open(file_path)?).lines().enumerate() {
+        let line = line?;
+        let mut fields = line.splitn(3, "  ");
+        let (Some(hex), Some(size), Some(path)) = (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(invalid(n));
+        };
+
+        let mut hash = [0u8; 32];
+        if hex.len() != 64 {
+            return Err(invalid(n));
+        }
+        for (byte, pair) in hash.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+            let pair = std::str::from_utf8(pair).map_err(|_| invalid(n))?;
+            *byte = u8::from_str_radix(pair, 16).map_err(|_| invalid(n))?;
+        }
+
+        entries.push(ManifestEntry {
+            path: path.to_string(),
+            size: size.parse().map_err(|_| invalid(n))?,
+            hash,
+        });
+    }
+    Ok(entries)
+}
+
+/// Paths that differ between two manifests, each list sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManifestDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Present in both, with a different size or hash.
+    pub changed: Vec<String>,
+}
+
+impl ManifestDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compare an old and a new manifest.
+pub fn diff_manifests(old: &[ManifestEntry], new: &[ManifestEntry]) -> ManifestDiff {
+    let old: BTreeMap<&str, &ManifestEntry> = old.iter().map(|e| (e.path.as_str(), e)).collect();
+    let new: BTreeMap<&str, &ManifestEntry> = new.iter().map(|e| (e.path.as_str(), e)).collect();
+
+    let mut diff = ManifestDiff::default();
+    for (path, entry) in &new {
+        match old.get(path) {
+            None => diff.added.push(path.to_string()),
+            Some(before) if before != entry => diff.changed.push(path.to_string()),
+            Some(_) => {}
+        }
+    }
+    diff.removed = old
+        .keys()
+        .filter(|path| !new.contains_key(*path))
+        .map(|path| path.to_string())
+        .collect();
+    diff
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sha256::sha256;
+
+    fn make_tree(root: &str) {
+        fs::remove_dir_all(root).ok();
+        fs::create_dir_all(format!("{}/b/nested", root)).unwrap();
+        fs::create_dir_all(format!("{}/empty_dir", root)).unwrap();
+        fs::write(format!("{}/a.txt", root), b"hello\n").unwrap();
+        fs::write(format!("{}/b/nested/data.bin", root), vec![7u8; 3 * BUFFER_SIZE + 5]).unwrap();
+        fs::write(format!("{}/b/empty", root), b"").unwrap();
+    }
+
+    #[test]
+    fn test_manifest_entries() {
+        let root = "/tmp/test_manifest_tree";
+        make_tree(root);
+
+        let entries = manifest(root).unwrap();
+        let paths: Vec<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["a.txt", "b/empty", "b/nested/data.bin"]);
+        assert_eq!(entries[0].size, 6);
+        assert_eq!(entries[0].hash, sha256(b"hello\n"));
+        assert_eq!(entries[1].hash, sha256(b""));
+        assert_eq!(entries[2].hash, sha256(&vec![7u8; 3 * BUFFER_SIZE + 5]));
+
+        // Same result whatever the number of workers
+        assert_eq!(manifest_with_threads(root, 1).unwrap(), entries);
+        assert_eq!(manifest_with_threads(root, 16).unwrap(), entries);
+
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_roundtrip_and_diff() {
+        let root = "/tmp/test_manifest_diff";
+        let path = "/tmp/test_manifest_diff.manifest";
+        make_tree(root);
+
+        let before = manifest(root).unwrap();
+        write_manifest(&before, path).unwrap();
+        assert_eq!(read_manifest(path).unwrap(), before);
+
+        fs::write(format!("{}/a.txt", root), b"hello!\n").unwrap();
+        fs::remove_file(format!("{}/b/empty", root)).unwrap();
+        fs::write(format!("{}/b/new file", root), b"x").unwrap();
+
+        let after = manifest(root).unwrap();
+        assert_eq!(
+            diff_manifests(&read_manifest(path).unwrap(), &after),
+            ManifestDiff {
+                added: vec!["b/new file".to_string()],
+                removed: vec!["b/empty".to_string()],
+                changed: vec!["a.txt".to_string()],
+            }
+        );
+        assert!(diff_manifests(&after, &after).is_empty());
+
+        fs::remove_dir_all(root).ok();
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        let path = "/tmp/test_manifest_invalid.manifest";
+        fs::write(path, b"abc  12  x\n").unwrap();
+        assert_eq!(read_manifest(path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        fs::remove_file(path).ok();
+    }
+}
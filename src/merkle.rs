@@ -0,0 +1,258 @@
+//! Merkle root of a file's fixed-size chunks, with parallel leaf hashing.
+//!
+//! Based on: RFC 6962 section 2.1, https://www.rfc-editor.org/rfc/rfc6962#section-2.1
+//!
+//! Comparing two copies of a dataset on different machines should not mean
+//! shipping the data. Both sides compute a Merkle root over the file's
+//! chunks; equal roots mean equal contents, and unequal subtrees point at the
+//! chunks that differ.
+//!
+//!   leaf = SHA-256(0x00 || chunk)
+//!   node = SHA-256(0x01 || left || right)
+//!
+//! The prefixes keep a leaf from being confused with an inner node. For a
+//! number of leaves that is not a power of two the tree is the RFC 6962 one:
+//! the left subtree is the largest power of two, so nothing is duplicated.
+//!
+//! The file is read sequentially in batches of chunks; the leaves of a batch
+//! are hashed on all cores while the tree is folded in order on a stack.
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    thread,
+};
+
+use crate::sha256::{sha256, Sha256};
+
+/// Hash of one leaf (a chunk of data).
+pub fn leaf_hash(chunk: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(&[0x00]);
+    hasher.update(chunk);
+    hasher.finalize()
+}
+
+/// Hash of an inner node from its two children.
+pub fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(&[0x01]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize()
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                          Streaming Tree Builder
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Leaves arrive left to right. The stack holds the roots of complete subtrees
+// with strictly decreasing sizes, like the bits of a binary counter:
+//
+//   after 6 leaves:  [ root(0..4), root(4..6) ]
+//
+// Pushing a leaf merges equal-sized neighbours. At the end the remaining
+// roots are folded right to left, which gives exactly the RFC 6962 shape.
+
+/// Builds a Merkle root from leaf hashes in O(log n) memory.
+#[derive(Debug, Clone, Default)]
+pub struct MerkleBuilder {
+    /// (number of leaves below, subtree root)
+    stack: Vec<(u64, [u8; 32])>,
+}
+
+impl MerkleBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append the hash of the next leaf (see [`leaf_hash`]).
+    pub fn push_leaf(&mut self, hash: [u8; 32]) {
+        let mut node = (1u64, hash);
+        while let Some(&(size, left)) = self.stack.last() {
+            if size != node.0 {
+                break;
+            }
+            self.stack.pop();
+            node = (size * 2, node_hash(&left, &node.1));
+        }
+        self.stack.push(node);
+    }
+
+    /// Root of all pushed leaves; the hash of the empty string if there are none.
+    pub fn finish(mut self) -> [u8; 32] {
+        let Some((_, mut root)) = self.stack.pop() else {
+            return sha256(b"");
+        };
+        while let Some((_, left)) = self.stack.pop() {
+            root = node_hash(&left, &root);
+        }
+        root
+    }
+}
+
+/// Merkle root of in-memory chunks, built recursively (reference version).
+pub fn merkle_root_of_chunks(chunks: &[&[u8]]) -> [u8; 32] {
+    fn subtree(chunks: &[&[u8]]) -> [u8; 32] {
+        if chunks.len() == 1 {
+            return leaf_hash(chunks[0]);
+        }
+        // Largest power of two strictly less than the number of leaves
+        let split = 1 << (usize::BITS - 1 - (chunks.len() - 1).leading_zeros());
+        node_hash(&subtree(&chunks[..split]), &subtree(&chunks[split..]))
+    }
+
+    if chunks.is_empty() {
+        return sha256(b"");
+    }
+    subtree(chunks)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 File API
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Compute the Merkle root of a file split into `chunk_size`-byte leaves.
+///
+/// The last leaf may be shorter. An empty file has no leaves.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::merkle::merkle_root;
+/// use scratchpad::sha256::to_hex;
+///
+/// let root = merkle_root("dataset.csv", 1 << 20).unwrap();
+/// println!("{}", to_hex(&root));
+/// ```
+pub fn merkle_root(file_path: &str, chunk_size: usize) -> io::Result<[u8; 32]> {
+    assert!(chunk_size > 0, "chunk_size must be positive");
+
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    // A few chunks per thread per batch keeps all cores busy between reads
+    let batch_chunks = threads * 4;
+
+    let mut file = File::open(file_path)?;
+    let mut buffer = vec![0u8; chunk_size * batch_chunks];
+    let mut builder = MerkleBuilder::new();
+
+    loop {
+        let filled = read_full(&mut file, &mut buffer)?;
+        if filled == 0 {
+            break;
+        }
+
+        let chunks: Vec<&[u8]> = buffer[..filled].chunks(chunk_size).collect();
+        for hash in hash_leaves_parallel(&chunks, threads) {
+            builder.push_leaf(hash);
+        }
+
+        if filled < buffer.len() {
+            break;
+        }
+    }
+
+    Ok(builder.finish())
+}
+
+/// Fill `buf` as far as possible; returns less than `buf.len()` only at EOF.
+fn read_full(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Hash leaves on up to `threads` threads, returning the hashes in order.
+fn hash_leaves_parallel(chunks: &[&[u8]], threads: usize) -> Vec<[u8; 32]> {
+    if threads <= 1 || chunks.len() <= 1 {
+        return chunks.iter().map(|c| leaf_hash(c)).collect();
+    }
+
+    let mut hashes = vec![[0u8; 32]; chunks.len()];
+    let per_thread = chunks.len().div_ceil(threads);
+
+    thread::scope(|s| {
+        for (out, inp) in hashes.chunks_mut(per_thread).zip(chunks.chunks(per_thread)) {
+            s.spawn(move || {
+                for (h, c) in out.iter_mut().zip(inp) {
+                    *h = leaf_hash(c);
+                }
+            });
+        }
+    });
+
+    hashes
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sha256::to_hex;
+
+    #[test]
+    fn test_small_trees_by_hand() {
+        let (a, b, c) = (leaf_hash(b"a"), leaf_hash(b"b"), leaf_hash(b"c"));
+        assert_eq!(merkle_root_of_chunks(&[b"a"]), a);
+        assert_eq!(merkle_root_of_chunks(&[b"a", b"b"]), node_hash(&a, &b));
+        // Three leaves: ((a, b), c), nothing duplicated
+        assert_eq!(merkle_root_of_chunks(&[b"a", b"b", b"c"]), node_hash(&node_hash(&a, &b), &c));
+        assert_eq!(
+            to_hex(&merkle_root_of_chunks(&[])),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn test_builder_matches_recursive() {
+        let leaves: Vec<Vec<u8>> = (0..40u32).map(|i| i.to_le_bytes().to_vec()).collect();
+        for n in 0..leaves.len() {
+            let chunks: Vec<&[u8]> = leaves[..n].iter().map(|l| l.as_slice()).collect();
+            let mut builder = MerkleBuilder::new();
+            for c in &chunks {
+                builder.push_leaf(leaf_hash(c));
+            }
+            assert_eq!(builder.finish(), merkle_root_of_chunks(&chunks), "Mismatch for n={}", n);
+        }
+    }
+
+    #[test]
+    fn test_file_root() {
+        let path = "/tmp/test_merkle_root.bin";
+        let data: Vec<u8> = (0..1_000_003u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 24) as u8)
+            .collect();
+        std::fs::write(path, &data).unwrap();
+
+        for chunk_size in [1000, 4096, 65536, 2_000_000] {
+            let chunks: Vec<&[u8]> = data.chunks(chunk_size).collect();
+            assert_eq!(merkle_root(path, chunk_size).unwrap(), merkle_root_of_chunks(&chunks));
+        }
+
+        // Any change changes the root
+        let mut changed = data.clone();
+        changed[777_777] ^= 1;
+        std::fs::write(path, &changed).unwrap();
+        let chunks: Vec<&[u8]> = data.chunks(4096).collect();
+        assert_ne!(merkle_root(path, 4096).unwrap(), merkle_root_of_chunks(&chunks));
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let path = "/tmp/test_merkle_empty.bin";
+        std::fs::write(path, b"").unwrap();
+        assert_eq!(merkle_root(path, 1024).unwrap(), sha256(b""));
+        std::fs::remove_file(path).ok();
+    }
+}
@@ -0,0 +1,470 @@
+//! Minimal MessagePack encoder and decoder.
+//!
+//! Based on: https://github.com/msgpack/msgpack/blob/master/spec.md
+//!
+//! Supported types: nil, bool, int (signed and unsigned), float 32/64, str,
+//! bin, array, map. Extension types and timestamps are not.
+//!
+//! Every variable-width type (ints, str/bin/array/map lengths) picks the
+//! smallest encoding. Instead of a chain of range comparisons, the width is
+//! looked up from the number of significant bits:
+//!
+//!   bits = 64 - leading_zeros(n)        (for signed: of n ^ (n >> 63), plus a sign bit)
+//!   WIDTH[bits] -> 1, 2, 4 or 8 bytes
+//!
+//! which compiles to a `lzcnt`/`clz` and one load.
+
+use std::fmt;
+
+use crate::utf8;
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                            Width Selection
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Index (0..4) of the smallest of 1, 2, 4, 8 bytes holding `bits` bits.
+const WIDTH_INDEX: [u8; 65] = {
+    let mut table = [0u8; 65];
+    let mut bits = 0;
+    while bits <= 64 {
+        table[bits] = match bits {
+            0..=8 => 0,
+            9..=16 => 1,
+            17..=32 => 2,
+            _ => 3,
+        };
+        bits += 1;
+    }
+    table
+};
+
+#[inline]
+fn unsigned_width_index(n: u64) -> usize {
+    WIDTH_INDEX[(64 - n.leading_zeros()) as usize] as usize
+}
+
+#[inline]
+fn signed_width_index(n: i64) -> usize {
+    // Magnitude bits plus one sign bit
+    let magnitude = (n ^ (n >> 63)) as u64;
+    WIDTH_INDEX[(64 - magnitude.leading_zeros()) as usize + 1] as usize
+}
+
+/// Append the low `1 << width_index` bytes of `n`, big-endian.
+#[inline]
+fn push_be(n: u64, width_index: usize, out: &mut Vec<u8>) {
+    let bytes = n.to_be_bytes();
+    out.extend_from_slice(&bytes[8 - (1 << width_index)..]);
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Encoder
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[inline]
+pub fn encode_nil(out: &mut Vec<u8>) {
+    out.push(0xC0);
+}
+
+#[inline]
+pub fn encode_bool(b: bool, out: &mut Vec<u8>) {
+    out.push(0xC2 | b as u8);
+}
+
+/// Append a signed integer in its smallest signed encoding.
+#[inline]
+pub fn encode_int(n: i64, out: &mut Vec<u8>) {
+    if (-32..=127).contains(&n) {
+        // positive / negative fixint
+        out.push(n as u8);
+        return;
+    }
+    let w = signed_width_index(n);
+    out.push(0xD0 + w as u8);
+    push_be(n as u64, w, out);
+}
+
+/// Append an unsigned integer in its smallest unsigned encoding.
+#[inline]
+pub fn encode_uint(n: u64, out: &mut Vec<u8>) {
+    if n <= 127 {
+        out.push(n as u8);
+        return;
+    }
+    let w = unsigned_width_index(n);
+    out.push(0xCC + w as u8);
+    push_be(n, w, out);
+}
+
+#[inline]
+pub fn encode_f32(x: f32, out: &mut Vec<u8>) {
+    out.push(0xCA);
+    out.extend_from_slice(&x.to_be_bytes());
+}
+
+#[inline]
+pub fn encode_f64(x: f64, out: &mut Vec<u8>) {
+    out.push(0xCB);
+    out.extend_from_slice(&x.to_be_bytes());
+}
+
+/// Append a length header: the fixed form (`fix_marker | len`) if `len` fits,
+/// else the 8/16/32-bit form starting at `first_marker`. Arrays and maps
+/// have no 8-bit form (`has_8` false).
+#[inline]
+fn encode_len(
+    len: usize,
+    fix: Option<(u8, usize)>,
+    first_marker: u8,
+    has_8: bool,
+    out: &mut Vec<u8>,
+) {
+    assert!(len <= u32::MAX as usize, "MessagePack lengths are limited to 2^32 - 1");
+
+    if let Some((fix_marker, fix_max)) = fix {
+        if len <= fix_max {
+            out.push(fix_marker | len as u8);
+            return;
+        }
+    }
+
+    // 8-bit form only for str/bin; arrays and maps start at 16 bits
+    let w = unsigned_width_index(len as u64).max(!has_8 as usize);
+    out.push(first_marker + w as u8 - !has_8 as u8);
+    push_be(len as u64, w, out);
+}
+
+/// Append a str header and the UTF-8 bytes.
+#[inline]
+pub fn encode_str(s: &str, out: &mut Vec<u8>) {
+    encode_len(s.len(), Some((0xA0, 31)), 0xD9, true, out);
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// Append a bin header and the bytes.
+#[inline]
+pub fn encode_bin(b: &[u8], out: &mut Vec<u8>) {
+    encode_len(b.len(), None, 0xC4, true, out);
+    out.extend_from_slice(b);
+}
+
+/// Append `bytes` as str if they are valid UTF-8, as bin otherwise.
+///
+/// Validated with [`utf8::as_str`], which checks ASCII 64 bytes at a time.
+#[inline]
+pub fn encode_text(bytes: &[u8], out: &mut Vec<u8>) {
+    match utf8::as_str(bytes) {
+        Some(s) => encode_str(s, out),
+        None => encode_bin(bytes, out),
+    }
+}
+
+/// Append an array header; the `len` elements follow.
+#[inline]
+pub fn encode_array_len(len: usize, out: &mut Vec<u8>) {
+    encode_len(len, Some((0x90, 15)), 0xDC, false, out);
+}
+
+/// Append a map header; the `len` key/value pairs follow.
+#[inline]
+pub fn encode_map_len(len: usize, out: &mut Vec<u8>) {
+    encode_len(len, Some((0x80, 15)), 0xDE, false, out);
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Decoder
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// A decoded value, borrowing strings and binaries from the input.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MsgPackValue<'a> {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Str(&'a str),
+    Bin(&'a [u8]),
+    Array(Vec<MsgPackValue<'a>>),
+    Map(Vec<(MsgPackValue<'a>, MsgPackValue<'a>)>),
+}
+
+/// Why decoding failed, with the offset of the offending marker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input ends in the middle of a value.
+    UnexpectedEof,
+    /// A str value is not valid UTF-8.
+    InvalidUtf8(usize),
+    /// Extension types and the reserved 0xC1 marker are not supported.
+    Unsupported(usize),
+    /// Arrays and maps nested deeper than [`MAX_DEPTH`].
+    TooDeep(usize),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEof => write!(f, "unexpected end of input"),
+            DecodeError::InvalidUtf8(at) => write!(f, "invalid UTF-8 in str at offset {}", at),
+            DecodeError::Unsupported(at) => write!(f, "unsupported type at offset {}", at),
+            DecodeError::TooDeep(at) => write!(f, "nesting too deep at offset {}", at),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Maximum nesting of arrays and maps accepted by the decoder.
+pub const MAX_DEPTH: usize = 256;
+
+struct Decoder<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], DecodeError> {
+        let end = self.pos.checked_add(n).ok_or(DecodeError::UnexpectedEof)?;
+        let bytes = self
+            .input
+            .get(self.pos..end)
+            .ok_or(DecodeError::UnexpectedEof)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    /// Read a big-endian unsigned integer of `1 << width_index` bytes.
+    fn be(&mut self, width_index: usize) -> Result<u64, DecodeError> {
+        let bytes = self.take(1 << width_index)?;
+        Ok(bytes.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64))
+    }
+
+    fn value(&mut self, depth: usize) -> Result<MsgPackValue<'a>, DecodeError> {
+        let at = self.pos;
+        let marker = self.take(1)?[0];
+
+        let value = match marker {
+            0x00..=0x7F => MsgPackValue::Int(marker as i64),
+            0xE0..=0xFF => MsgPackValue::Int(marker as i8 as i64),
+            0xC0 => MsgPackValue::Nil,
+            0xC2 | 0xC3 => MsgPackValue::Bool(marker == 0xC3),
+            0xCC..=0xCF => MsgPackValue::UInt(self.be((marker - 0xCC) as usize)?),
+            0xD0..=0xD3 => {
+                let w = (marker - 0xD0) as usize;
+                let shift = 64 - 8 * (1 << w);
+                // Sign-extend from the encoded width
+                MsgPackValue::Int(((self.be(w)? << shift) as i64) >> shift)
+            }
+            0xCA => MsgPackValue::Float(f32::from_bits(self.be(2)? as u32) as f64),
+            0xCB => MsgPackValue::Float(f64::from_bits(self.be(3)?)),
+            0xA0..=0xBF => self.str((marker & 0x1F) as usize, at)?,
+            0xD9..=0xDB => {
+                let len = self.be((marker - 0xD9) as usize)? as usize;
+                self.str(len, at)?
+            }
+            0xC4..=0xC6 => {
+                let len = self.be((marker - 0xC4) as usize)? as usize;
+                MsgPackValue::Bin(self.take(len)?)
+            }
+            0x90..=0x9F => self.array((marker & 0x0F) as usize, depth, at)?,
+            0xDC | 0xDD => {
+                let len = self.be((marker - 0xDC + 1) as usize)? as usize;
+                self.array(len, depth, at)?
+            }
+            0x80..=0x8F => self.map((marker & 0x0F) as usize, depth, at)?,
+            0xDE | 0xDF => {
+                let len = self.be((marker - 0xDE + 1) as usize)? as usize;
+                self.map(len, depth, at)?
+            }
+            _ => return Err(DecodeError::Unsupported(at)),
+        };
+        Ok(value)
+    }
+
+    fn str(&mut self, len: usize, at: usize) -> Result<MsgPackValue<'a>, DecodeError> {
+        let bytes = self.take(len)?;
+        std::str::from_utf8(bytes)
+            .map(MsgPackValue::Str)
+            .map_err(|_| DecodeError::InvalidUtf8(at))
+    }
+
+    fn array(
+        &mut self,
+        len: usize,
+        depth: usize,
+        at: usize,
+    ) -> Result<MsgPackValue<'a>, DecodeError> {
+        if depth >= MAX_DEPTH {
+            return Err(DecodeError::TooDeep(at));
+        }
+        // Every element takes at least one byte: never trust the length for capacity
+        let mut items = Vec::with_capacity(len.min(self.input.len() - self.pos));
+        for _ in 0..len {
+            items.push(self.value(depth + 1)?);
+        }
+        Ok(MsgPackValue::Array(items))
+    }
+
+    fn map(
+        &mut self,
+        len: usize,
+        depth: usize,
+        at: usize,
+    ) -> Result<MsgPackValue<'a>, DecodeError> {
+        if depth >= MAX_DEPTH {
+            return Err(DecodeError::TooDeep(at));
+        }
+        let mut pairs = Vec::with_capacity(len.min((self.input.len() - self.pos) / 2));
+        for _ in 0..len {
+            let key = self.value(depth + 1)?;
+            let value = self.value(depth + 1)?;
+            pairs.push((key, value));
+        }
+        Ok(MsgPackValue::Map(pairs))
+    }
+}
+
+/// Decode the first value of `input`, returning it and the number of bytes used.
+///
+/// # Example
+/// ```
+/// use scratchpad::msgpack::{decode, encode_int, encode_map_len, encode_str, MsgPackValue};
+///
+/// let mut buf = Vec::new();
+/// encode_map_len(1, &mut buf);
+/// encode_str("n", &mut buf);
+/// encode_int(-300, &mut buf);
+///
+/// let (value, used) = decode(&buf).unwrap();
+/// assert_eq!(value, MsgPackValue::Map(vec![(MsgPackValue::Str("n"), MsgPackValue::Int(-300))]));
+/// assert_eq!(used, buf.len());
+/// ```
+pub fn decode(input: &[u8]) -> Result<(MsgPackValue<'_>, usize), DecodeError> {
+    let mut decoder = Decoder { input, pos: 0 };
+    let value = decoder.value(0)?;
+    Ok((value, decoder.pos))
+}
+
+/// Decode a stream of concatenated values (as written by a sink).
+pub fn decode_all(input: &[u8]) -> Result<Vec<MsgPackValue<'_>>, DecodeError> {
+    let mut decoder = Decoder { input, pos: 0 };
+    let mut values = Vec::new();
+    while decoder.pos < input.len() {
+        values.push(decoder.value(0)?);
+    }
+    Ok(values)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoded(f: impl FnOnce(&mut Vec<u8>)) -> Vec<u8> {
+        let mut out = Vec::new();
+        f(&mut out);
+        out
+    }
+
+    #[test]
+    fn test_int_sizes() {
+        assert_eq!(encoded(|o| encode_int(0, o)), [0x00]);
+        assert_eq!(encoded(|o| encode_int(127, o)), [0x7F]);
+        assert_eq!(encoded(|o| encode_int(-32, o)), [0xE0]);
+        assert_eq!(encoded(|o| encode_int(-33, o)), [0xD0, 0xDF]);
+        assert_eq!(encoded(|o| encode_int(-128, o)), [0xD0, 0x80]);
+        assert_eq!(encoded(|o| encode_int(128, o)), [0xD1, 0x00, 0x80]);
+        assert_eq!(encoded(|o| encode_int(-129, o)), [0xD1, 0xFF, 0x7F]);
+        assert_eq!(encoded(|o| encode_int(1 << 20, o)), [0xD2, 0x00, 0x10, 0x00, 0x00]);
+        assert_eq!(encoded(|o| encode_int(i64::MIN, o))[0], 0xD3);
+
+        assert_eq!(encoded(|o| encode_uint(255, o)), [0xCC, 0xFF]);
+        assert_eq!(encoded(|o| encode_uint(256, o)), [0xCD, 0x01, 0x00]);
+        assert_eq!(encoded(|o| encode_uint(u64::MAX, o)).len(), 9);
+    }
+
+    #[test]
+    fn test_length_headers() {
+        assert_eq!(encoded(|o| encode_str("abc", o))[0], 0xA3);
+        assert_eq!(&encoded(|o| encode_str(&"x".repeat(40), o))[..2], [0xD9, 40]);
+        assert_eq!(&encoded(|o| encode_str(&"x".repeat(300), o))[..3], [0xDA, 0x01, 0x2C]);
+        assert_eq!(&encoded(|o| encode_bin(b"ab", o))[..2], [0xC4, 2]);
+        assert_eq!(encoded(|o| encode_array_len(15, o)), [0x9F]);
+        assert_eq!(encoded(|o| encode_array_len(16, o)), [0xDC, 0x00, 0x10]);
+        assert_eq!(encoded(|o| encode_map_len(3, o)), [0x83]);
+        assert_eq!(encoded(|o| encode_map_len(70_000, o)), [0xDF, 0x00, 0x01, 0x11, 0x70]);
+        assert_eq!(encoded(|o| encode_text(b"\xFF", o)), [0xC4, 1, 0xFF]);
+        assert_eq!(encoded(|o| encode_text("é".as_bytes(), o)), [0xA2, 0xC3, 0xA9]);
+        assert_eq!(encoded(|o| encode_text(b"\xED\xA0\x80", o))[0], 0xC4);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let ints = [
+            0i64,
+            1,
+            -1,
+            127,
+            128,
+            -32,
+            -33,
+            255,
+            256,
+            -32768,
+            65535,
+            1 << 40,
+            i64::MIN,
+            i64::MAX,
+        ];
+        let mut buf = Vec::new();
+        encode_array_len(ints.len() + 6, &mut buf);
+        for &n in &ints {
+            encode_int(n, &mut buf);
+        }
+        encode_uint(u64::MAX, &mut buf);
+        encode_nil(&mut buf);
+        encode_bool(true, &mut buf);
+        encode_f64(-2.5, &mut buf);
+        encode_f32(0.5, &mut buf);
+        encode_map_len(1, &mut buf);
+        encode_str(&"k".repeat(70_000), &mut buf);
+        encode_bin(&[1, 2, 3], &mut buf);
+
+        let (value, used) = decode(&buf).unwrap();
+        assert_eq!(used, buf.len());
+
+        let long_key = "k".repeat(70_000);
+        let mut expected: Vec<MsgPackValue> = ints.iter().map(|&n| MsgPackValue::Int(n)).collect();
+        expected.extend([
+            MsgPackValue::UInt(u64::MAX),
+            MsgPackValue::Nil,
+            MsgPackValue::Bool(true),
+            MsgPackValue::Float(-2.5),
+            MsgPackValue::Float(0.5),
+            MsgPackValue::Map(vec![(MsgPackValue::Str(&long_key), MsgPackValue::Bin(&[1, 2, 3]))]),
+        ]);
+        assert_eq!(value, MsgPackValue::Array(expected));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(decode(b""), Err(DecodeError::UnexpectedEof));
+        assert_eq!(decode(&[0xA3, b'a']), Err(DecodeError::UnexpectedEof));
+        assert_eq!(decode(&[0xDD, 0xFF, 0xFF, 0xFF, 0xFF]), Err(DecodeError::UnexpectedEof));
+        assert_eq!(decode(&[0x91, 0xC1]), Err(DecodeError::Unsupported(1)));
+        assert_eq!(decode(&[0xA1, 0xFF]), Err(DecodeError::InvalidUtf8(0)));
+        assert_eq!(decode(&[0x91; MAX_DEPTH + 1]), Err(DecodeError::TooDeep(MAX_DEPTH)));
+    }
+
+    #[test]
+    fn test_decode_all() {
+        let mut buf = Vec::new();
+        encode_int(1, &mut buf);
+        encode_str("two", &mut buf);
+        assert_eq!(decode_all(&buf).unwrap(), vec![MsgPackValue::Int(1), MsgPackValue::Str("two")]);
+    }
+}
@@ -0,0 +1,618 @@
+//! Substring search with a first+last byte candidate filter.
+//!
+//! Based on: http://0x80.pl/articles/simd-strfind.html
+//!
+//! The pattern counters used to find candidates with memchr on the first
+//! byte of the pattern, then compare the rest. That is fast for `Harvard` in
+//! a file of names, and slow when the first byte is common: every `M` of
+//! `MIT`, `Mallory` and `Mathematics` stops the memchr for a pattern starting
+//! with `M`. Two bytes that far apart rarely both match by accident, so
+//! [`find`] compares the first byte at position i and the last byte at
+//! i + len - 1 for a whole block of positions at once:
+//!
+//! ```text
+//!   pattern:        MIT           (first 'M', last 'T' two bytes later)
+//!   haystack:       Mallory,MIT,Mathematics
+//!   h[i]   == 'M':  1.......1...1..........
+//!   h[i+2] == 'T':  ........1..............
+//!   both:           ........1..............   one candidate left, at i = 8
+//! ```
+//!
+//! Only the candidates left in the mask get a full compare.
+//!
+//! - AVX2: 32 positions per step, `_mm256_movemask_epi8`
+//! - NEON: 16 positions per step, narrowed to a 4-bit-per-byte mask
+//! - SWAR: 8 positions per step with [`eq_mask_swar`]
+//!
+//! One-byte patterns have nothing to filter on and go straight to memchr.
+//! [`find_memchr`] keeps the memchr + compare search for comparison.
+//!
+//! [`find`] checks the CPU on every call. A [`Pattern`] owns its bytes and
+//! the kernel picked for them once, for the same needle searched in
+//! thousands of files or chunks.
+//!
+//! `csv_parse_bench`, every occurrence in a 7 MB CSV in memory (AVX2):
+//!
+//! | Pattern            | memchr + memcmp | First+last filter |
+//! |--------------------|-----------------|-------------------|
+//! | `MIT`              | 4.64 GB/s       | 9.50 GB/s         |
+//! | `Mathematics`      | 4.67 GB/s       | 5.67 GB/s         |
+//! | `Computer Science` | 3.82 GB/s       | 5.63 GB/s         |
+//! | `Harvard`          | 10.58 GB/s      | 8.53 GB/s         |
+//!
+//! When the first byte is rare (`H` in this file), memchr already skips
+//! most of the input and wins.
+//!
+//! [`Horspool`] and [`TwoWay`], the classic skipping searches, are here to
+//! measure the filter against; `needle_bench` runs all of them over
+//! alphabets of 4 to 256 letters and needles of 3 to 64 bytes. A 16-byte
+//! needle, every occurrence in 8 MB (AVX2, `memchr::memmem` for reference):
+//!
+//! | Haystack     | memchr + memcmp | First+last | Horspool  | Two-Way   | memmem     |
+//! |--------------|-----------------|------------|-----------|-----------|------------|
+//! | DNA (ACGT)   | 0.34 GB/s       | 1.22 GB/s  | 0.57 GB/s | 0.19 GB/s | 1.80 GB/s  |
+//! | English      | 0.90 GB/s       | 5.64 GB/s  | 1.35 GB/s | 0.61 GB/s | 14.33 GB/s |
+//! | CSV rows     | 2.25 GB/s       | 6.88 GB/s  | 2.00 GB/s | 0.88 GB/s | 16.05 GB/s |
+//! | Random bytes | 6.51 GB/s       | 13.40 GB/s | 2.49 GB/s | 0.80 GB/s | 17.21 GB/s |
+//!
+//! Horspool beats memchr + memcmp only with few letters or long needles (64
+//! bytes of CSV: 3.50 against 2.29 GB/s). Two-Way's linear worst case costs
+//! it everywhere else: it is the slowest in every row. The filter beats both
+//! in every run; `memchr::memmem`, which picks its candidates on the needle's
+//! rarest bytes, is faster still on text.
+
+#[cfg(target_arch = "aarch64")]
+use std::arch::aarch64::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+use std::fmt;
+
+use crate::bitmask::eq_mask_swar;
+use crate::cpu;
+
+/// Position of the first occurrence of `needle` in `haystack` (best available
+/// version). An empty needle is found at 0.
+#[inline]
+pub fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    #[cfg(target_arch = "aarch64")]
+    if cpu::has_neon() {
+        return unsafe { find_neon(haystack, needle) };
+    }
+
+    // AVX2 is not part of the x86_64 baseline: detected at runtime (cached)
+    #[cfg(target_arch = "x86_64")]
+    if cpu::has_avx2() {
+        return unsafe { find_avx2(haystack, needle) };
+    }
+
+    find_swar(haystack, needle)
+}
+
+/// A search kernel: safe to call once the CPU has its extension.
+type Kernel = unsafe fn(&[u8], &[u8]) -> Option<usize>;
+
+/// A needle prepared for repeated searches: the bytes, and the kernel chosen
+/// for them and for this CPU when the pattern is built.
+///
+/// # Example
+/// ```
+/// use scratchpad::needle::Pattern;
+///
+/// let pattern = Pattern::new(b"Harvard");
+/// assert_eq!(pattern.find(b"Ann,Harvard"), Some(4));
+/// assert_eq!(pattern.find(b"Bob,MIT"), None);
+/// ```
+#[derive(Clone)]
+pub struct Pattern {
+    bytes: Box<[u8]>,
+    kernel: Kernel,
+}
+
+impl Pattern {
+    /// Prepare `bytes` for searching. An empty pattern is found at 0.
+    pub fn new(bytes: &[u8]) -> Self {
+        Self { bytes: bytes.into(), kernel: Self::pick_kernel(bytes.len()) }
+    }
+
+    fn pick_kernel(len: usize) -> Kernel {
+        if len < 2 {
+            return |haystack, needle| find_short(haystack, needle);
+        }
+
+        #[cfg(target_arch = "aarch64")]
+        if cpu::has_neon() {
+            return find_neon;
+        }
+
+        #[cfg(target_arch = "x86_64")]
+        if cpu::has_avx2() {
+            return find_avx2;
+        }
+
+        find_swar
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Position of the first occurrence of the pattern in `haystack`.
+    #[inline]
+    pub fn find(&self, haystack: &[u8]) -> Option<usize> {
+        // SAFETY: `pick_kernel` only returns kernels this CPU supports
+        unsafe { (self.kernel)(haystack, &self.bytes) }
+    }
+}
+
+impl fmt::Debug for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Pattern").field(&String::from_utf8_lossy(&self.bytes)).finish()
+    }
+}
+
+/// Position of the first occurrence of `needle` in `haystack`: memchr on the
+/// first byte, then compare the rest (the search the filter replaces).
+pub fn find_memchr(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    let Some((&first_byte, tail_bytes)) = needle.split_first() else {
+        return Some(0);
+    };
+    let mut i = 0;
+
+    while i + needle.len() <= haystack.len() {
+        i += memchr::memchr(first_byte, &haystack[i..haystack.len() - needle.len() + 1])?;
+        if &haystack[i + 1..i + needle.len()] == tail_bytes {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// The needles the filter does not apply to: empty, one byte, or longer
+/// than the haystack.
+#[inline]
+fn find_short(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    match needle {
+        [] => Some(0),
+        _ if needle.len() > haystack.len() => None,
+        [byte] => memchr::memchr(*byte, haystack),
+        _ => unreachable!("needle of {} bytes is not short", needle.len()),
+    }
+}
+
+/// Whether the bytes between the first and the last of `needle` match at
+/// `i` (the filter already compared those two).
+#[inline]
+fn middle_matches(haystack: &[u8], i: usize, needle: &[u8]) -> bool {
+    let last = needle.len() - 1;
+    haystack[i + 1..i + last] == needle[1..last]
+}
+
+/// Check the candidates from `start` on one at a time (the tail after the
+/// last whole block).
+#[inline]
+fn find_scalar(haystack: &[u8], needle: &[u8], start: usize) -> Option<usize> {
+    let last = needle.len() - 1;
+    (start..haystack.len() - last).find(|&i| {
+        haystack[i] == needle[0]
+            && haystack[i + last] == needle[last]
+            && middle_matches(haystack, i, needle)
+    })
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                  SWAR
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Position of the first occurrence of `needle` in `haystack` (SWAR version).
+pub fn find_swar(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.len() < 2 || needle.len() > haystack.len() {
+        return find_short(haystack, needle);
+    }
+
+    let last = needle.len() - 1;
+    // Positions a match can start at
+    let end = haystack.len() - last;
+    let mut i = 0;
+
+    while i + 8 <= end {
+        let firsts = u64::from_le_bytes(haystack[i..i + 8].try_into().unwrap());
+        let lasts = u64::from_le_bytes(haystack[i + last..i + last + 8].try_into().unwrap());
+        let mut mask = eq_mask_swar(firsts, needle[0]) & eq_mask_swar(lasts, needle[last]);
+
+        while mask != 0 {
+            let candidate = i + mask.trailing_zeros() as usize / 8;
+            if middle_matches(haystack, candidate, needle) {
+                return Some(candidate);
+            }
+            mask &= mask - 1;
+        }
+        i += 8;
+    }
+
+    find_scalar(haystack, needle, i)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                  AVX2
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Position of the first occurrence of `needle` in `haystack` (AVX2 version).
+///
+/// # Safety
+/// Requires a CPU with AVX2 support (check with [`has_avx2`](crate::cpu::has_avx2)).
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+pub unsafe fn find_avx2(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.len() < 2 || needle.len() > haystack.len() {
+        return find_short(haystack, needle);
+    }
+
+    let last = needle.len() - 1;
+    let end = haystack.len() - last;
+    let first_byte = _mm256_set1_epi8(needle[0] as i8);
+    let last_byte = _mm256_set1_epi8(needle[last] as i8);
+    let ptr = haystack.as_ptr();
+    let mut i = 0;
+
+    while i + 32 <= end {
+        let firsts = _mm256_loadu_si256(ptr.add(i) as *const __m256i);
+        let lasts = _mm256_loadu_si256(ptr.add(i + last) as *const __m256i);
+        let both = _mm256_and_si256(
+            _mm256_cmpeq_epi8(firsts, first_byte),
+            _mm256_cmpeq_epi8(lasts, last_byte),
+        );
+        let mut mask = _mm256_movemask_epi8(both) as u32;
+
+        while mask != 0 {
+            let candidate = i + mask.trailing_zeros() as usize;
+            if middle_matches(haystack, candidate, needle) {
+                return Some(candidate);
+            }
+            mask &= mask - 1;
+        }
+        i += 32;
+    }
+
+    find_scalar(haystack, needle, i)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                  NEON
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Position of the first occurrence of `needle` in `haystack` (NEON version).
+///
+/// NEON has no movemask: the 0x00/0xFF compare result is narrowed to 4 bits
+/// per byte (`shrn`), and one bit of each nibble is kept.
+///
+/// # Safety
+/// Requires a CPU with NEON support (see [`cpu::has_neon`]).
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+pub unsafe fn find_neon(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.len() < 2 || needle.len() > haystack.len() {
+        return find_short(haystack, needle);
+    }
+
+    let last = needle.len() - 1;
+    let end = haystack.len() - last;
+    let first_byte = vdupq_n_u8(needle[0]);
+    let last_byte = vdupq_n_u8(needle[last]);
+    let ptr = haystack.as_ptr();
+    let mut i = 0;
+
+    while i + 16 <= end {
+        let firsts = vld1q_u8(ptr.add(i));
+        let lasts = vld1q_u8(ptr.add(i + last));
+        let both = vandq_u8(vceqq_u8(firsts, first_byte), vceqq_u8(lasts, last_byte));
+        let nibbles = vshrn_n_u16(vreinterpretq_u16_u8(both), 4);
+        let mut mask = vget_lane_u64(vreinterpret_u64_u8(nibbles), 0) & 0x8888_8888_8888_8888;
+
+        while mask != 0 {
+            let candidate = i + mask.trailing_zeros() as usize / 4;
+            if middle_matches(haystack, candidate, needle) {
+                return Some(candidate);
+            }
+            mask &= mask - 1;
+        }
+        i += 16;
+    }
+
+    find_scalar(haystack, needle, i)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                          Horspool and Two-Way
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Boyer-Moore-Horspool: compare at the end of the window and, on a miss,
+/// shift by how far the window's last byte is from the end of the needle.
+///
+/// Shifts approach the needle length when the alphabet is large and the
+/// needle long; with four letters (DNA) the last byte is almost always close
+/// to the end and the shifts stay short. The shift table is built once.
+#[derive(Clone)]
+pub struct Horspool {
+    needle: Box<[u8]>,
+    /// Shift for each value of the window's last byte
+    shift: Box<[usize; 256]>,
+}
+
+impl Horspool {
+    pub fn new(needle: &[u8]) -> Self {
+        let mut shift = Box::new([needle.len(); 256]);
+        if let Some((_, init)) = needle.split_last() {
+            for (i, &b) in init.iter().enumerate() {
+                shift[b as usize] = init.len() - i;
+            }
+        }
+        Self { needle: needle.into(), shift }
+    }
+
+    /// Position of the first occurrence in `haystack`; an empty needle is
+    /// found at 0.
+    pub fn find(&self, haystack: &[u8]) -> Option<usize> {
+        let Some((&last_byte, init)) = self.needle.split_last() else {
+            return Some(0);
+        };
+        let last = init.len();
+        let mut i = 0;
+
+        while i + last < haystack.len() {
+            let b = haystack[i + last];
+            if b == last_byte && &haystack[i..i + last] == init {
+                return Some(i);
+            }
+            i += self.shift[b as usize];
+        }
+        None
+    }
+}
+
+/// Position of the first occurrence of `needle` in `haystack` with
+/// [`Horspool`] (the table is built for this call).
+pub fn find_horspool(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    Horspool::new(needle).find(haystack)
+}
+
+/// Two-Way (Crochemore-Perrin): linear time in the worst case with constant
+/// extra space, the algorithm behind glibc's `memmem` and the standard
+/// library's `str::find`.
+///
+/// The needle is cut at a critical position into `left` and `right`. Each
+/// window compares `right` forwards, then `left` backwards; a mismatch in
+/// `right` shifts past the mismatched byte, a mismatch in `left` shifts by
+/// the period of the needle. For a periodic needle (`abab`) the part of the
+/// window known to match after a period shift is not compared again, which
+/// is what rules out the quadratic cases of the naive search and of
+/// Horspool (`aaa...ab` in `aaa...a`).
+#[derive(Clone)]
+pub struct TwoWay {
+    needle: Box<[u8]>,
+    /// Start of the right half
+    critical: usize,
+    /// Shift after a mismatch in the left half
+    period: usize,
+    /// Whether `period` is the exact period of the needle (the matched
+    /// prefix of a shifted window is remembered)
+    periodic: bool,
+}
+
+impl TwoWay {
+    pub fn new(needle: &[u8]) -> Self {
+        // The later of the two maximal suffixes, for the two byte orders, is
+        // a critical factorization
+        let (critical_lt, period_lt) = maximal_suffix(needle, false);
+        let (critical_gt, period_gt) = maximal_suffix(needle, true);
+        let (critical, period) = if critical_lt > critical_gt {
+            (critical_lt, period_lt)
+        } else {
+            (critical_gt, period_gt)
+        };
+
+        let periodic = needle.get(period..period + critical) == Some(&needle[..critical]);
+        let period = if periodic { period } else { critical.max(needle.len() - critical) + 1 };
+        Self { needle: needle.into(), critical, period, periodic }
+    }
+
+    /// Position of the first occurrence in `haystack`; an empty needle is
+    /// found at 0.
+    pub fn find(&self, haystack: &[u8]) -> Option<usize> {
+        let needle = &*self.needle;
+        let n = needle.len();
+        let mut pos = 0;
+        // Bytes of the window's start known to match (periodic needles only)
+        let mut memory = 0;
+
+        while pos + n <= haystack.len() {
+            let window = &haystack[pos..pos + n];
+
+            let mut i = self.critical.max(memory);
+            while i < n && needle[i] == window[i] {
+                i += 1;
+            }
+            if i < n {
+                pos += i - self.critical + 1;
+                memory = 0;
+                continue;
+            }
+
+            let mut j = self.critical;
+            while j > memory && needle[j - 1] == window[j - 1] {
+                j -= 1;
+            }
+            // Everything before `memory` matched before the shift
+            if j <= memory {
+                return Some(pos);
+            }
+            pos += self.period;
+            if self.periodic {
+                memory = n - self.period;
+            }
+        }
+        None
+    }
+}
+
+/// Position of the first occurrence of `needle` in `haystack` with
+/// [`TwoWay`] (the factorization is computed for this call).
+pub fn find_two_way(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    TwoWay::new(needle).find(haystack)
+}
+
+/// Start and period of the lexicographically largest suffix of `x`, under
+/// the reversed byte order if `reversed`.
+fn maximal_suffix(x: &[u8], reversed: bool) -> (usize, usize) {
+    let (mut left, mut right, mut offset, mut period) = (0, 1, 0, 1);
+
+    while let Some(&a) = x.get(right + offset) {
+        let b = x[left + offset];
+        if (a < b) != reversed && a != b {
+            // Smaller: the suffix at `left` stays the largest, its period grows
+            right += offset + 1;
+            offset = 0;
+            period = right - left;
+        } else if a == b {
+            if offset + 1 == period {
+                right += offset + 1;
+                offset = 0;
+            } else {
+                offset += 1;
+            }
+        } else {
+            // Larger: the suffix at `right` is the new candidate
+            left = right;
+            right += 1;
+            offset = 0;
+            period = 1;
+        }
+    }
+    (left, period)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find_naive(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        (0..=haystack.len().checked_sub(needle.len())?)
+            .find(|&i| &haystack[i..i + needle.len()] == needle)
+    }
+
+    type Finder = fn(&[u8], &[u8]) -> Option<usize>;
+
+    fn finders() -> Vec<(&'static str, Finder)> {
+        let mut finders: Vec<(&'static str, Finder)> =
+            vec![("find", find), ("memchr", find_memchr), ("swar", find_swar)];
+        finders.push(("horspool", find_horspool));
+        finders.push(("two-way", find_two_way));
+        #[cfg(target_arch = "x86_64")]
+        if cpu::has_avx2() {
+            finders.push(("avx2", |h, n| unsafe { find_avx2(h, n) }));
+        }
+        #[cfg(target_arch = "aarch64")]
+        finders.push(("neon", |h, n| unsafe { find_neon(h, n) }));
+        finders
+    }
+
+    #[test]
+    fn test_find_matches_naive() {
+        // Few distinct bytes: many candidates pass the filter
+        let haystack: Vec<u8> = (0..300u32).map(|i| b"MIT,Ma"[(i * i % 7 % 6) as usize]).collect();
+        let needles: [&[u8]; 9] =
+            [b"", b"M", b"MI", b"MIT", b"M,M", b"aM", b"MIT,Ma", b"MIT,MIT,", b"xyz"];
+
+        for (name, finder) in finders() {
+            for needle in needles {
+                // Every start and end, so matches land in the blocks and the tails
+                for start in 0..40 {
+                    for end in (haystack.len() - 40..=haystack.len()).step_by(3) {
+                        let slice = &haystack[start..end];
+                        assert_eq!(
+                            finder(slice, needle),
+                            find_naive(slice, needle),
+                            "{} {:?} in [{}..{}]",
+                            name,
+                            std::str::from_utf8(needle).unwrap(),
+                            start,
+                            end
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_pattern() {
+        let haystack = b"Mallory,MIT,Mathematics,MIT";
+        for needle in [&b""[..], b"M", b"MIT", b"Mathematics", b"Harvard"] {
+            let pattern = Pattern::new(needle);
+            assert_eq!(pattern.as_bytes(), needle);
+            assert_eq!(pattern.find(haystack), find_naive(haystack, needle));
+            // Reused on other haystacks
+            assert_eq!(pattern.find(&haystack[10..]), find_naive(&haystack[10..], needle));
+        }
+        assert_eq!(format!("{:?}", Pattern::new(b"MIT")), r#"Pattern("MIT")"#);
+    }
+
+    #[test]
+    fn test_find_edges() {
+        for (name, finder) in finders() {
+            assert_eq!(finder(b"", b""), Some(0), "{}", name);
+            assert_eq!(finder(b"", b"a"), None, "{}", name);
+            assert_eq!(finder(b"ab", b"abc"), None, "{}", name);
+            assert_eq!(finder(b"abc", b"abc"), Some(0), "{}", name);
+
+            // Last possible position, after several whole blocks
+            let mut haystack = vec![b'H'; 100];
+            haystack.extend_from_slice(b"Harvard");
+            assert_eq!(finder(&haystack, b"Harvard"), Some(100), "{}", name);
+            // First and last bytes match everywhere, the middle nowhere
+            let haystack = vec![b'd'; 100];
+            assert_eq!(finder(&haystack, b"dxd"), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_horspool_two_way() {
+        // Two letters: periodic needles, and near misses at every position
+        let haystack: Vec<u8> =
+            (0..2000u32).map(|i| if (i * i + i / 7) % 5 < 3 { b'a' } else { b'b' }).collect();
+        for len in 1..=12 {
+            for start in (0..200).step_by(13) {
+                let needle = &haystack[start..start + len];
+                let (horspool, two_way) = (Horspool::new(needle), TwoWay::new(needle));
+                for from in [0, 1, 500, 1990] {
+                    let expected = find_naive(&haystack[from..], needle);
+                    assert_eq!(horspool.find(&haystack[from..]), expected, "{:?}", needle);
+                    assert_eq!(two_way.find(&haystack[from..]), expected, "{:?}", needle);
+                }
+            }
+        }
+
+        // The quadratic case of the naive search
+        let mut haystack = vec![b'a'; 10_000];
+        let needle = [&[b'a'; 100][..], b"b"].concat();
+        assert_eq!(find_two_way(&haystack, &needle), None);
+        haystack.push(b'b');
+        assert_eq!(find_two_way(&haystack, &needle), Some(10_000 - 100));
+        for needle in [&b"abab"[..], b"aab", b"abaab", b"ba"] {
+            let factored = TwoWay::new(needle);
+            assert!(factored.critical < needle.len() && factored.period <= needle.len());
+        }
+    }
+}
@@ -0,0 +1,316 @@
+//! Locate runs of ASCII digits ("number spans") using digit-class bitmasks.
+//!
+//! Based on: https://lemire.me/blog/2018/09/30/quickly-identifying-a-sequence-of-digits-in-a-string-of-characters
+//!
+//! Log-metric extraction ("find all integers on each line") usually reaches for a
+//! regex engine. Here we classify 64 bytes at a time into a `u64` digit mask and
+//! extract the runs of set bits with shifts and `trailing_zeros`, so no per-byte
+//! state machine is involved.
+
+#[cfg(target_arch = "aarch64")]
+use std::arch::aarch64::*;
+
+#[cfg(target_arch = "aarch64")]
+use crate::bitmask::movemask_64_neon;
+use crate::bitmask::{movemask_swar, padded_block};
+#[cfg(target_arch = "aarch64")]
+use crate::cpu;
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                         Digit-Class Bitmasks
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Each 64-byte block becomes one u64: bit i is set if byte i is in '0'..='9'.
+//
+//   Block:  [a][=][1][2][ ][x][7][ ] ...
+//   Mask:    0  0  1  1  0  0  1  0  ...
+//
+// Run boundaries then fall out of two shifts:
+//
+//   starts = mask & !(mask << 1 | carry)    first digit of each run
+//   ends   = !mask & (mask << 1 | carry)    first non-digit after each run
+//
+// `carry` is the top bit of the previous block, so runs crossing a block
+// boundary are not split in two.
+
+/// Half-open byte range `[start, end)` of a run of ASCII digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// Check if a byte is an ASCII digit (scalar version).
+#[inline]
+pub fn is_digit_scalar(byte: u8) -> bool {
+    byte.is_ascii_digit()
+}
+
+/// Compute the digit mask of 8 bytes packed in a u64 (SWAR version).
+///
+/// Returns a u64 with bit 7 of each byte set if that byte is an ASCII digit.
+/// Unlike the early-exit detectors, this is exact per byte, so it can be
+/// turned into a positional bitmask.
+#[inline]
+pub fn digit_mask_swar(x: u64) -> u64 {
+    // XOR with '0' maps digits to 0..=9; everything else lands elsewhere.
+    let shifted = x ^ 0x3030303030303030u64;
+
+    // Adding 118 (0x76) to the low 7 bits sets bit 7 iff the value is >= 10.
+    // 127 + 118 = 245, so no carry ever crosses into the next byte.
+    let low7 = shifted & 0x7F7F7F7F7F7F7F7Fu64;
+    let ge10 = low7 + 0x7676767676767676u64;
+
+    // Digit iff bit 7 was clear originally and the value is < 10.
+    !(ge10 | shifted) & 0x8080808080808080u64
+}
+
+/// Compute the 64-bit digit mask of a 64-byte block (SWAR version).
+pub fn digit_bitmask_64_swar(block: &[u8; 64]) -> u64 {
+    let mut result = 0u64;
+
+    for (i, chunk) in block.chunks_exact(8).enumerate() {
+        let x = u64::from_le_bytes(chunk.try_into().unwrap());
+        result |= movemask_swar(digit_mask_swar(x)) << (i * 8);
+    }
+
+    result
+}
+
+/// Compute the 64-bit digit mask of a 64-byte block (NEON version).
+///
+/// Uses the wrapping subtract + unsigned compare trick: `(b - '0') < 10`.
+///
+/// # Safety
+/// Requires a CPU with NEON support (see [`cpu::has_neon`]).
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+pub unsafe fn digit_bitmask_64_neon(block: &[u8; 64]) -> u64 {
+    let zero = vdupq_n_u8(b'0');
+    let ten = vdupq_n_u8(10);
+    let ptr = block.as_ptr();
+
+    let m0 = vcltq_u8(vsubq_u8(vld1q_u8(ptr), zero), ten);
+    let m1 = vcltq_u8(vsubq_u8(vld1q_u8(ptr.add(16)), zero), ten);
+    let m2 = vcltq_u8(vsubq_u8(vld1q_u8(ptr.add(32)), zero), ten);
+    let m3 = vcltq_u8(vsubq_u8(vld1q_u8(ptr.add(48)), zero), ten);
+
+    movemask_64_neon(m0, m1, m2, m3)
+}
+
+#[inline]
+fn digit_bitmask_64(block: &[u8; 64]) -> u64 {
+    #[cfg(target_arch = "aarch64")]
+    if cpu::has_neon() {
+        return unsafe { digit_bitmask_64_neon(block) };
+    }
+
+    digit_bitmask_64_swar(block)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                          Bit-Run Extraction
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Iterator over the digit runs of a buffer. Created by [`find_number_spans`].
+pub struct NumberSpans<'a> {
+    data: &'a [u8],
+    block_start: usize,
+    starts: u64,
+    ends: u64,
+    carry: u64,
+    open: Option<usize>,
+}
+
+impl<'a> NumberSpans<'a> {
+    fn load_block(&mut self) {
+        let remaining = &self.data[self.block_start..];
+
+        // Pad the final partial block with zeros (never digits), which also
+        // closes any run that reaches the end of the buffer.
+        let mask = if remaining.len() >= 64 {
+            digit_bitmask_64(remaining[..64].try_into().unwrap())
+        } else {
+            digit_bitmask_64(&padded_block(remaining))
+        };
+
+        let prev = (mask << 1) | self.carry;
+        self.starts = mask & !prev;
+        self.ends = !mask & prev;
+        self.carry = mask >> 63;
+    }
+}
+
+impl Iterator for NumberSpans<'_> {
+    type Item = Span;
+
+    fn next(&mut self) -> Option<Span> {
+        loop {
+            if self.open.is_none() && self.starts != 0 {
+                let bit = self.starts.trailing_zeros() as usize;
+                self.starts &= self.starts - 1;
+                self.open = Some(self.block_start + bit);
+            }
+
+            if self.open.is_some() && self.ends != 0 {
+                let bit = self.ends.trailing_zeros() as usize;
+                self.ends &= self.ends - 1;
+                let start = self.open.take().unwrap();
+                return Some(Span { start, end: self.block_start + bit });
+            }
+
+            // Current block exhausted: move to the next one
+            if self.block_start + 64 >= self.data.len() {
+                // A full final block whose run touches the last byte has no
+                // padding to close it.
+                self.block_start = self.data.len();
+                return self
+                    .open
+                    .take()
+                    .map(|start| Span { start, end: self.data.len() });
+            }
+
+            self.block_start += 64;
+            self.load_block();
+        }
+    }
+}
+
+/// Find all runs of ASCII digits in `data`.
+///
+/// # Example
+/// ```
+/// use scratchpad::number_spans::{find_number_spans, Span};
+///
+/// let spans: Vec<Span> = find_number_spans(b"took 125ms, rows=42").collect();
+/// assert_eq!(spans, vec![Span { start: 5, end: 8 }, Span { start: 17, end: 19 }]);
+/// ```
+pub fn find_number_spans(data: &[u8]) -> NumberSpans<'_> {
+    let mut spans = NumberSpans { data, block_start: 0, starts: 0, ends: 0, carry: 0, open: None };
+    if !data.is_empty() {
+        spans.load_block();
+    }
+    spans
+}
+
+/// Find all runs of ASCII digits in `data` (scalar reference).
+pub fn find_number_spans_scalar(data: &[u8]) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut i = 0;
+
+    while i < data.len() {
+        if is_digit_scalar(data[i]) {
+            let start = i;
+            while i < data.len() && is_digit_scalar(data[i]) {
+                i += 1;
+            }
+            spans.push(Span { start, end: i });
+        } else {
+            i += 1;
+        }
+    }
+
+    spans
+}
+
+/// Parse a run of ASCII digits, saturating at `u64::MAX` instead of overflowing.
+///
+/// Metric values in logs are occasionally garbage (e.g. 40-digit ids), and a
+/// saturated value is more useful to a dashboard than a parse error.
+pub fn parse_u64_saturating(digits: &[u8]) -> u64 {
+    digits
+        .iter()
+        .fold(0u64, |acc, &d| acc.saturating_mul(10).saturating_add((d - b'0') as u64))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digit_mask_swar() {
+        let x = u64::from_le_bytes([b'0', b'9', b'/', b':', b'a', b'5', 0xB0, b' ']);
+        let mask = digit_mask_swar(x);
+        assert_eq!(movemask_swar(mask), 0b0010_0011);
+    }
+
+    #[test]
+    fn test_basic_spans() {
+        let data = b"GET /api/v2/items?id=1234 200 17ms";
+        let spans: Vec<Span> = find_number_spans(data).collect();
+        let values: Vec<&[u8]> = spans.iter().map(|s| &data[s.start..s.end]).collect();
+        assert_eq!(values, vec![&b"2"[..], b"1234", b"200", b"17"]);
+    }
+
+    #[test]
+    fn test_no_digits() {
+        assert_eq!(find_number_spans(b"").count(), 0);
+        assert_eq!(find_number_spans(b"no numbers here").count(), 0);
+    }
+
+    #[test]
+    fn test_all_digits() {
+        let data = vec![b'7'; 200];
+        let spans: Vec<Span> = find_number_spans(&data).collect();
+        assert_eq!(spans, vec![Span { start: 0, end: 200 }]);
+    }
+
+    #[test]
+    fn test_run_across_block_boundary() {
+        let mut data = vec![b'x'; 128];
+        data[60..70].copy_from_slice(b"0123456789");
+        data[127] = b'5';
+        let spans: Vec<Span> = find_number_spans(&data).collect();
+        assert_eq!(spans, vec![Span { start: 60, end: 70 }, Span { start: 127, end: 128 }]);
+    }
+
+    #[test]
+    fn test_matches_scalar() {
+        // Pseudo-random mix of digits and separators, all lengths 0..300
+        let mut rng = 12345u64;
+        let data: Vec<u8> = (0..300)
+            .map(|_| {
+                rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+                b"0123456789 ,x:"[((rng >> 16) % 14) as usize]
+            })
+            .collect();
+
+        for len in 0..data.len() {
+            let spans: Vec<Span> = find_number_spans(&data[..len]).collect();
+            assert_eq!(spans, find_number_spans_scalar(&data[..len]), "Mismatch for len={}", len);
+        }
+    }
+
+    #[test]
+    #[cfg(target_arch = "aarch64")]
+    fn test_neon_matches_swar() {
+        let block: [u8; 64] = std::array::from_fn(|i| (i * 7) as u8);
+        unsafe {
+            assert_eq!(digit_bitmask_64_neon(&block), digit_bitmask_64_swar(&block));
+        }
+    }
+
+    #[test]
+    fn test_parse_u64_saturating() {
+        assert_eq!(parse_u64_saturating(b"0"), 0);
+        assert_eq!(parse_u64_saturating(b"18446744073709551615"), u64::MAX);
+        assert_eq!(parse_u64_saturating(b"99999999999999999999999"), u64::MAX);
+        assert_eq!(parse_u64_saturating(b"1234"), 1234);
+    }
+}
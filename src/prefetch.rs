@@ -0,0 +1,198 @@
+//! Double-buffered reads: a background thread reads chunk N+1 while the
+//! caller scans chunk N.
+//!
+//! The buffered scanners block in `read()` and scan afterwards, so I/O and
+//! compute take turns. [`PrefetchReader`] moves the reads to a thread of
+//! their own, with two buffers passed back and forth over channels:
+//!
+//! ```text
+//!   reader thread:  [fill A][fill B]   [fill A]   [fill B]
+//!                        \      \    ^     \    ^
+//!   caller:               [scan A]  [scan B]   [scan A] ...
+//!                                \__/       \__/   (empty buffers go back)
+//! ```
+//!
+//! It implements [`Read`], so the pattern counter and the record readers run
+//! on top of it unchanged; chunks reach the caller's buffer with a memcpy.
+//! Wrapping [`UncachedReader`](crate::uncached::UncachedReader) overlaps the
+//! disk reads of a cold scan with the search.
+//!
+//! `cold_disk_bench`, on a single-core Linux VM:
+//!
+//! | 10 MB file                    | Direct    | Prefetch thread |
+//! |-------------------------------|-----------|-----------------|
+//! | cold (`UncachedReader`)       | 1.00 GB/s | 1.38 GB/s       |
+//! | hot (4 KB `read()` / 256 KB)  | 1.32 GB/s | 1.49 GB/s       |
+//!
+//! Even with one core the cold scan gains: waiting for the disk takes no
+//! CPU, so the search runs during the wait. Spawning the thread costs about
+//! 0.03 ms, more than scanning a 50 KB file.
+
+use std::{
+    io::{self, Read},
+    sync::mpsc::{self, Receiver, SyncSender},
+    thread,
+};
+
+/// Default chunk size of [`PrefetchReader::new`].
+pub const CHUNK_SIZE: usize = 256 * 1024;
+
+/// Number of buffers passed between the threads.
+const BUFFERS: usize = 2;
+
+/// A reader whose reads happen ahead of time on a background thread.
+pub struct PrefetchReader {
+    /// Chunks read by the thread (a 0-length chunk is the end of input)
+    filled: Receiver<io::Result<(Vec<u8>, usize)>>,
+    /// Consumed buffers, returned to the thread
+    empty: SyncSender<Vec<u8>>,
+    /// The chunk being consumed: buffer, bytes in it, bytes consumed
+    current: Option<(Vec<u8>, usize, usize)>,
+    done: bool,
+}
+
+impl PrefetchReader {
+    /// Start reading `reader` ahead in chunks of [`CHUNK_SIZE`].
+    pub fn new<R: Read + Send + 'static>(reader: R) -> Self {
+        Self::with_chunk_size(reader, CHUNK_SIZE)
+    }
+
+    /// Start reading `reader` ahead in chunks of `chunk_size` bytes.
+    pub fn with_chunk_size<R: Read + Send + 'static>(mut reader: R, chunk_size: usize) -> Self {
+        let (filled_tx, filled) = mpsc::sync_channel(BUFFERS);
+        let (empty, empty_rx) = mpsc::sync_channel::<Vec<u8>>(BUFFERS);
+        for _ in 0..BUFFERS {
+            empty.send(vec![0u8; chunk_size.max(1)]).unwrap();
+        }
+
+        // Ends at the end of input, on an error, or when the reader is dropped
+        // (both channels disconnect)
+        thread::spawn(move || {
+            while let Ok(mut buffer) = empty_rx.recv() {
+                let result = fill(&mut reader, &mut buffer);
+                let last = !matches!(result, Ok(n) if n > 0);
+                if filled_tx.send(result.map(|n| (buffer, n))).is_err() || last {
+                    return;
+                }
+            }
+        });
+
+        Self { filled, empty, current: None, done: false }
+    }
+}
+
+/// Read into `buffer` until it is full or the input ends.
+fn fill<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+impl Read for PrefetchReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some((buffer, filled, consumed)) = &mut self.current {
+                if *consumed < *filled {
+                    let n = buf.len().min(*filled - *consumed);
+                    buf[..n].copy_from_slice(&buffer[*consumed..*consumed + n]);
+                    *consumed += n;
+                    return Ok(n);
+                }
+                // Consumed: hand the buffer back for the chunk after next
+                let (buffer, _, _) = self.current.take().unwrap();
+                let _ = self.empty.send(buffer);
+            }
+            if self.done {
+                return Ok(0);
+            }
+
+            match self.filled.recv() {
+                Ok(Ok((_, 0))) | Err(_) => self.done = true,
+                Ok(Ok((buffer, n))) => self.current = Some((buffer, n, 0)),
+                Ok(Err(e)) => {
+                    self.done = true;
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_parse_buffer_size_impact::{
+        count_pattern_matches_from_file, count_pattern_matches_prefetch,
+    };
+
+    #[test]
+    fn test_prefetch_reads_everything() {
+        let data: Vec<u8> = (0..1_000_000u32).map(|i| (i * 7) as u8).collect();
+        for chunk_size in [7, 4096, 300_000, 2_000_000] {
+            let input = io::Cursor::new(data.clone());
+            let mut reader = PrefetchReader::with_chunk_size(input, chunk_size);
+            let mut read = Vec::new();
+            reader.read_to_end(&mut read).unwrap();
+            assert!(read == data, "chunk size {}", chunk_size);
+            assert_eq!(reader.read(&mut [0u8; 4]).unwrap(), 0);
+        }
+
+        // Dropped before the end
+        let mut reader = PrefetchReader::with_chunk_size(io::Cursor::new(data), 1000);
+        reader.read_exact(&mut [0u8; 10]).unwrap();
+        drop(reader);
+
+        let mut empty = PrefetchReader::new(io::empty());
+        assert_eq!(empty.read(&mut [0u8; 4]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_prefetch_errors() {
+        struct Failing(usize);
+        impl Read for Failing {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.0 == 0 {
+                    return Err(io::Error::other("disk on fire"));
+                }
+                let n = buf.len().min(self.0);
+                buf[..n].fill(b'x');
+                self.0 -= n;
+                Ok(n)
+            }
+        }
+
+        let mut reader = PrefetchReader::with_chunk_size(Failing(100), 64);
+        let mut read = Vec::new();
+        let err = reader.read_to_end(&mut read).unwrap_err();
+        assert_eq!(err.to_string(), "disk on fire");
+        assert_eq!(read.len(), 64);
+    }
+
+    #[test]
+    fn test_prefetch_pattern_count() {
+        let path = "/tmp/test_prefetch.csv";
+        let mut data = b"\xEF\xBB\xBFName,University\n".to_vec();
+        for i in 0..100_000 {
+            let university = ["MIT", "Harvard", "Yale"][i % 3];
+            data.extend_from_slice(format!("Person{},{}\n", i, university).as_bytes());
+        }
+        std::fs::write(path, &data).unwrap();
+
+        assert_eq!(
+            count_pattern_matches_prefetch(path, b"Harvard").unwrap(),
+            count_pattern_matches_from_file(path, b"Harvard").unwrap()
+        );
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,163 @@
+//! Access pattern hints for the kernel: `posix_fadvise` on files, `madvise`
+//! on maps.
+//!
+//! The page cache guesses how a file will be read and sizes its readahead
+//! from that guess. A scanner knows better: a pattern search reads the file
+//! once, front to back, and a lookup through `index_rows` offsets jumps
+//! around. [`ReadStrategy`] tells the kernel before the first read:
+//!
+//! | Strategy     | File (`posix_fadvise`)  | Map (`madvise`)      |
+//! |--------------|-------------------------|----------------------|
+//! | `Sequential` | larger readahead window | `MADV_SEQUENTIAL`    |
+//! | `Random`     | readahead off           | `MADV_RANDOM`        |
+//! | `WillNeed`   | start reading it all    | `MADV_WILLNEED`      |
+//!
+//! On macOS files get `F_RDAHEAD` (on for `Sequential`, off for `Random`) and
+//! `F_RDADVISE` for `WillNeed`; elsewhere the file hints do nothing. They are
+//! hints either way: the data read is the same.
+//!
+//! Hints only matter when the file is not cached yet. `large_file_bench`
+//! drops its 1 GB and 2.5 GB files from the cache with [`evict_cached`]
+//! (Linux) before every advised scan (single runs, Linux VM):
+//!
+//! | Cold scan  | Sequential | Random    | WillNeed  |
+//! |------------|------------|-----------|-----------|
+//! | 1 GB       | 1.33 GB/s  | 0.13 GB/s | 0.61 GB/s |
+//! | 2.5 GB     | 1.13 GB/s  | 0.13 GB/s | 1.26 GB/s |
+//!
+//! Turning readahead off costs 10x on a scan. `WillNeed` queues the whole
+//! file at once and varies the most from run to run; `Sequential` (the
+//! default, and what `mmap::map_file` uses) is the safe choice for a scan.
+
+use std::{fs::File, io};
+
+/// How a file is about to be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReadStrategy {
+    /// Once, front to back: read ahead aggressively.
+    #[default]
+    Sequential,
+    /// Scattered reads: do not read ahead.
+    Random,
+    /// All of it, soon: start reading the whole file now.
+    WillNeed,
+}
+
+impl ReadStrategy {
+    /// Advise the kernel about the whole of `file`.
+    #[cfg(target_os = "linux")]
+    pub fn advise_file(self, file: &File) -> io::Result<()> {
+        let advice = match self {
+            ReadStrategy::Sequential => libc::POSIX_FADV_SEQUENTIAL,
+            ReadStrategy::Random => libc::POSIX_FADV_RANDOM,
+            ReadStrategy::WillNeed => libc::POSIX_FADV_WILLNEED,
+        };
+        fadvise(file, advice)
+    }
+
+    /// Advise the kernel about the whole of `file`.
+    #[cfg(target_os = "macos")]
+    pub fn advise_file(self, file: &File) -> io::Result<()> {
+        use std::os::unix::io::AsRawFd;
+
+        let fd = file.as_raw_fd();
+        let result = match self {
+            ReadStrategy::Sequential => unsafe { libc::fcntl(fd, libc::F_RDAHEAD, 1) },
+            ReadStrategy::Random => unsafe { libc::fcntl(fd, libc::F_RDAHEAD, 0) },
+            ReadStrategy::WillNeed => {
+                let len = file.metadata()?.len().min(i32::MAX as u64);
+                let advice = libc::radvisory { ra_offset: 0, ra_count: len as libc::c_int };
+                unsafe { libc::fcntl(fd, libc::F_RDADVISE, &advice) }
+            }
+        };
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Advise the kernel about the whole of `file`.
+    ///
+    /// No file hints on this target: does nothing.
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    pub fn advise_file(self, file: &File) -> io::Result<()> {
+        let _ = file;
+        Ok(())
+    }
+
+    /// Advise the kernel about the whole of `map` (feature `mmap`).
+    #[cfg(all(feature = "mmap", unix))]
+    pub fn advise_map(self, map: &memmap2::Mmap) -> io::Result<()> {
+        use memmap2::Advice;
+
+        map.advise(match self {
+            ReadStrategy::Sequential => Advice::Sequential,
+            ReadStrategy::Random => Advice::Random,
+            ReadStrategy::WillNeed => Advice::WillNeed,
+        })
+    }
+}
+
+/// Drop the cached pages of `file`, so that the next read comes from the
+/// disk: a cold read for benchmarks, without root or `drop_caches`.
+///
+/// Only clean pages are dropped; write the file out first (`sync_all`) if it
+/// was just written. Linux only: elsewhere this fails with
+/// [`io::ErrorKind::Unsupported`].
+#[cfg(target_os = "linux")]
+pub fn evict_cached(file: &File) -> io::Result<()> {
+    fadvise(file, libc::POSIX_FADV_DONTNEED)
+}
+
+/// Drop the cached pages of `file`.
+///
+/// Linux only: here this fails with [`io::ErrorKind::Unsupported`].
+#[cfg(not(target_os = "linux"))]
+pub fn evict_cached(file: &File) -> io::Result<()> {
+    let _ = file;
+    Err(io::Error::new(io::ErrorKind::Unsupported, "evicting a file from the cache needs Linux"))
+}
+
+#[cfg(target_os = "linux")]
+fn fadvise(file: &File, advice: libc::c_int) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    // Returns the error number instead of setting errno
+    match unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, advice) } {
+        0 => Ok(()),
+        errno => Err(io::Error::from_raw_os_error(errno)),
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_parse_buffer_size_impact::{
+        count_pattern_matches_advised, count_pattern_matches_from_file,
+    };
+
+    #[test]
+    fn test_strategies_keep_results() {
+        let path = "/tmp/test_readahead.csv";
+        std::fs::write(path, b"Name,University\nAnn,Harvard\nBob,MIT\nCid,Harvard\n").unwrap();
+        let file = File::open(path).unwrap();
+
+        let expected = count_pattern_matches_from_file(path, b"Harvard").unwrap();
+        for strategy in [ReadStrategy::Sequential, ReadStrategy::Random, ReadStrategy::WillNeed] {
+            strategy.advise_file(&file).unwrap();
+            let count = count_pattern_matches_advised(path, b"Harvard", strategy);
+            assert_eq!(count.unwrap(), expected);
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            evict_cached(&file).unwrap();
+            assert_eq!(count_pattern_matches_from_file(path, b"Harvard").unwrap(), expected);
+        }
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,1022 @@
+//! Regex-lite: a deliberately limited, line-oriented matcher over SIMD prefilters.
+//!
+//! Supported syntax:
+//! - Literals and escapes: `abc`, `\.`, `\n`, `\t`
+//! - Alternation and groups: `ERROR|WARN`, `(GET|POST) /api`
+//! - Character classes: `[a-z0-9_]`, `[^,]`, `.`, `\d \w \s` (and `\D \W \S`)
+//! - Repetition: `?`, `*`, `+`
+//! - Anchors: `^` and `$` (line start / line end)
+//!
+//! Matching is grep-style: a match never spans a `'\n'`, so `.` and negated
+//! classes do not match newlines.
+//!
+//! The pattern is compiled twice:
+//! 1. A prefilter that finds candidate positions at memchr speed:
+//!    - one required literal:   `memchr::memmem`
+//!    - several literals:       first-byte scan (memchr2/3, or a nibble lookup "Teddy-lite"), then
+//!      `starts_with`
+//!    - no literal:             first-byte class bitmap
+//! 2. A small Thompson NFA that verifies only the lines containing a candidate.
+//!
+//! Most lines of a log never reach the NFA, which is where the speed comes from.
+
+#[cfg(target_arch = "aarch64")]
+use std::arch::aarch64::*;
+use std::fmt;
+
+use memchr::memmem;
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                              Byte Classes
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// A set of bytes stored as a 256-bit bitmap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteClass([u64; 4]);
+
+impl ByteClass {
+    pub const fn empty() -> Self {
+        ByteClass([0; 4])
+    }
+
+    pub fn single(byte: u8) -> Self {
+        let mut class = Self::empty();
+        class.insert(byte);
+        class
+    }
+
+    #[inline]
+    pub fn insert(&mut self, byte: u8) {
+        self.0[(byte >> 6) as usize] |= 1u64 << (byte & 63);
+    }
+
+    pub fn insert_range(&mut self, lo: u8, hi: u8) {
+        for b in lo..=hi {
+            self.insert(b);
+        }
+    }
+
+    #[inline]
+    pub fn remove(&mut self, byte: u8) {
+        self.0[(byte >> 6) as usize] &= !(1u64 << (byte & 63));
+    }
+
+    #[inline]
+    pub fn contains(&self, byte: u8) -> bool {
+        self.0[(byte >> 6) as usize] & (1u64 << (byte & 63)) != 0
+    }
+
+    pub fn negate(&mut self) {
+        for word in &mut self.0 {
+            *word = !*word;
+        }
+    }
+
+    pub fn union(&mut self, other: &ByteClass) {
+        for (a, b) in self.0.iter_mut().zip(other.0.iter()) {
+            *a |= b;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&w| w == 0)
+    }
+
+    /// Return the byte if this class contains exactly one.
+    pub fn as_single(&self) -> Option<u8> {
+        let count: u32 = self.0.iter().map(|w| w.count_ones()).sum();
+        if count != 1 {
+            return None;
+        }
+        (0..=255u8).find(|&b| self.contains(b))
+    }
+
+    fn digit() -> Self {
+        let mut class = Self::empty();
+        class.insert_range(b'0', b'9');
+        class
+    }
+
+    fn word() -> Self {
+        let mut class = Self::digit();
+        class.insert_range(b'a', b'z');
+        class.insert_range(b'A', b'Z');
+        class.insert(b'_');
+        class
+    }
+
+    fn space() -> Self {
+        let mut class = Self::empty();
+        for &b in b" \t\r\x0B\x0C" {
+            class.insert(b);
+        }
+        class
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Parser
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Why a pattern failed to compile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    UnexpectedEnd,
+    UnmatchedParen,
+    NothingToRepeat,
+    UnclosedClass,
+    InvalidRange,
+    EmptyClass,
+}
+
+/// A pattern compilation error, with the byte offset where it was detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub offset: usize,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self.kind {
+            ErrorKind::UnexpectedEnd => "unexpected end of pattern",
+            ErrorKind::UnmatchedParen => "unmatched parenthesis",
+            ErrorKind::NothingToRepeat => "repetition operator without operand",
+            ErrorKind::UnclosedClass => "unclosed character class",
+            ErrorKind::InvalidRange => "invalid class range",
+            ErrorKind::EmptyClass => "character class matches nothing",
+        };
+        write!(f, "{} at offset {}", msg, self.offset)
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Repeat {
+    ZeroOrOne,
+    ZeroOrMore,
+    OneOrMore,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Class(ByteClass),
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    Repeat(Box<Node>, Repeat),
+    LineStart,
+    LineEnd,
+}
+
+struct Parser<'p> {
+    pattern: &'p [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, kind: ErrorKind) -> Error {
+        Error { kind, offset: self.pos }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.pattern.get(self.pos).copied()
+    }
+
+    fn parse_alt(&mut self) -> Result<Node, Error> {
+        let mut alts = vec![self.parse_concat()?];
+        while self.peek() == Some(b'|') {
+            self.pos += 1;
+            alts.push(self.parse_concat()?);
+        }
+        Ok(if alts.len() == 1 {
+            alts.pop().unwrap()
+        } else {
+            Node::Alt(alts)
+        })
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, Error> {
+        let mut items = Vec::new();
+        while let Some(b) = self.peek() {
+            if b == b'|' || b == b')' {
+                break;
+            }
+            items.push(self.parse_repeat()?);
+        }
+        Ok(Node::Concat(items))
+    }
+
+    fn parse_repeat(&mut self) -> Result<Node, Error> {
+        let atom = self.parse_atom()?;
+        let repeat = match self.peek() {
+            Some(b'?') => Repeat::ZeroOrOne,
+            Some(b'*') => Repeat::ZeroOrMore,
+            Some(b'+') => Repeat::OneOrMore,
+            _ => return Ok(atom),
+        };
+        if matches!(atom, Node::LineStart | Node::LineEnd) {
+            return Err(self.error(ErrorKind::NothingToRepeat));
+        }
+        self.pos += 1;
+        if matches!(self.peek(), Some(b'?' | b'*' | b'+')) {
+            return Err(self.error(ErrorKind::NothingToRepeat));
+        }
+        Ok(Node::Repeat(Box::new(atom), repeat))
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, Error> {
+        let b = self.peek().ok_or(self.error(ErrorKind::UnexpectedEnd))?;
+        self.pos += 1;
+
+        match b {
+            b'(' => {
+                let inner = self.parse_alt()?;
+                if self.peek() != Some(b')') {
+                    return Err(self.error(ErrorKind::UnmatchedParen));
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            b'^' => Ok(Node::LineStart),
+            b'$' => Ok(Node::LineEnd),
+            b'.' => {
+                let mut class = ByteClass::empty();
+                class.negate();
+                Ok(Node::Class(class))
+            }
+            b'[' => self.parse_class(),
+            b'\\' => self.parse_escape().map(Node::Class),
+            b'?' | b'*' | b'+' => {
+                self.pos -= 1;
+                Err(self.error(ErrorKind::NothingToRepeat))
+            }
+            _ => Ok(Node::Class(ByteClass::single(b))),
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<ByteClass, Error> {
+        let b = self.peek().ok_or(self.error(ErrorKind::UnexpectedEnd))?;
+        self.pos += 1;
+
+        let (mut class, negated) = match b {
+            b'd' => (ByteClass::digit(), false),
+            b'D' => (ByteClass::digit(), true),
+            b'w' => (ByteClass::word(), false),
+            b'W' => (ByteClass::word(), true),
+            b's' => (ByteClass::space(), false),
+            b'S' => (ByteClass::space(), true),
+            b'n' => (ByteClass::single(b'\n'), false),
+            b't' => (ByteClass::single(b'\t'), false),
+            b'r' => (ByteClass::single(b'\r'), false),
+            _ => (ByteClass::single(b), false),
+        };
+        if negated {
+            class.negate();
+        }
+        Ok(class)
+    }
+
+    fn parse_class(&mut self) -> Result<Node, Error> {
+        let start = self.pos - 1;
+        let mut class = ByteClass::empty();
+        let negated = self.peek() == Some(b'^');
+        if negated {
+            self.pos += 1;
+        }
+
+        let mut first = true;
+        loop {
+            let b = self
+                .peek()
+                .ok_or(Error { kind: ErrorKind::UnclosedClass, offset: start })?;
+            if b == b']' && !first {
+                self.pos += 1;
+                break;
+            }
+            first = false;
+
+            let lo = self.parse_class_item()?;
+            let lo = match lo {
+                Ok(byte) => byte,
+                Err(set) => {
+                    class.union(&set);
+                    continue;
+                }
+            };
+
+            // Range: a-z (a trailing '-' before ']' is a literal)
+            if self.peek() == Some(b'-') && self.pattern.get(self.pos + 1) != Some(&b']') {
+                self.pos += 1;
+                match self.parse_class_item()? {
+                    Ok(hi) if hi >= lo => class.insert_range(lo, hi),
+                    _ => return Err(self.error(ErrorKind::InvalidRange)),
+                }
+            } else {
+                class.insert(lo);
+            }
+        }
+
+        if negated {
+            class.negate();
+        }
+        if class.is_empty() {
+            return Err(Error { kind: ErrorKind::EmptyClass, offset: start });
+        }
+        Ok(Node::Class(class))
+    }
+
+    /// One class member: `Ok(byte)` for a single byte, `Err(set)` for `\d`-style sets.
+    fn parse_class_item(&mut self) -> Result<Result<u8, ByteClass>, Error> {
+        let b = self.peek().ok_or(self.error(ErrorKind::UnclosedClass))?;
+        self.pos += 1;
+        if b != b'\\' {
+            return Ok(Ok(b));
+        }
+        let class = self.parse_escape()?;
+        Ok(match class.as_single() {
+            Some(byte) => Ok(byte),
+            None => Err(class),
+        })
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                         Thompson NFA Compilation
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Classic Thompson construction:
+//
+//   a|b     Split(L1, L2)  L1: a  Jmp(L3)  L2: b  L3:
+//   a*      L1: Split(L2, L3)  L2: a  Jmp(L1)  L3:
+//   a+      L1: a  Split(L1, L2)  L2:
+//   a?      Split(L1, L2)  L1: a  L2:
+
+#[derive(Debug, Clone)]
+enum Inst {
+    Class(ByteClass),
+    Split(usize, usize),
+    Jmp(usize),
+    LineStart,
+    LineEnd,
+    Match,
+}
+
+fn compile(node: &Node, prog: &mut Vec<Inst>) {
+    match node {
+        Node::Class(class) => prog.push(Inst::Class(*class)),
+        Node::Concat(items) => {
+            for item in items {
+                compile(item, prog);
+            }
+        }
+        Node::Alt(alts) => {
+            let mut jumps = Vec::new();
+            for (i, alt) in alts.iter().enumerate() {
+                if i + 1 < alts.len() {
+                    let split = prog.len();
+                    prog.push(Inst::Split(split + 1, 0));
+                    compile(alt, prog);
+                    jumps.push(prog.len());
+                    prog.push(Inst::Jmp(0));
+                    let next = prog.len();
+                    prog[split] = Inst::Split(split + 1, next);
+                } else {
+                    compile(alt, prog);
+                }
+            }
+            let end = prog.len();
+            for j in jumps {
+                prog[j] = Inst::Jmp(end);
+            }
+        }
+        Node::Repeat(inner, Repeat::ZeroOrOne) => {
+            let split = prog.len();
+            prog.push(Inst::Split(split + 1, 0));
+            compile(inner, prog);
+            let end = prog.len();
+            prog[split] = Inst::Split(split + 1, end);
+        }
+        Node::Repeat(inner, Repeat::ZeroOrMore) => {
+            let split = prog.len();
+            prog.push(Inst::Split(split + 1, 0));
+            compile(inner, prog);
+            prog.push(Inst::Jmp(split));
+            let end = prog.len();
+            prog[split] = Inst::Split(split + 1, end);
+        }
+        Node::Repeat(inner, Repeat::OneOrMore) => {
+            let start = prog.len();
+            compile(inner, prog);
+            let split = prog.len();
+            prog.push(Inst::Split(start, split + 1));
+        }
+        Node::LineStart => prog.push(Inst::LineStart),
+        Node::LineEnd => prog.push(Inst::LineEnd),
+    }
+}
+
+/// Strip '\n' from every class: matches never span lines.
+fn strip_newlines(node: &mut Node) {
+    match node {
+        Node::Class(class) => class.remove(b'\n'),
+        Node::Concat(items) | Node::Alt(items) => items.iter_mut().for_each(strip_newlines),
+        Node::Repeat(inner, _) => strip_newlines(inner),
+        Node::LineStart | Node::LineEnd => {}
+    }
+}
+
+/// Longest run of single-byte, non-repeated atoms in one top-level alternative.
+fn required_literal(node: &Node) -> Option<Vec<u8>> {
+    let items = match node {
+        Node::Concat(items) => items.as_slice(),
+        Node::Class(_) => std::slice::from_ref(node),
+        _ => return None,
+    };
+
+    let mut best: Vec<u8> = Vec::new();
+    let mut current: Vec<u8> = Vec::new();
+    for item in items {
+        match item {
+            Node::Class(class) if class.as_single().is_some() => {
+                current.push(class.as_single().unwrap());
+                continue;
+            }
+            // Anchors are zero-width: they don't break a literal run
+            Node::LineStart | Node::LineEnd => continue,
+            _ => {}
+        }
+        if current.len() > best.len() {
+            best = std::mem::take(&mut current);
+        }
+        current.clear();
+    }
+    if current.len() > best.len() {
+        best = current;
+    }
+
+    if best.is_empty() {
+        None
+    } else {
+        Some(best)
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                     First-Byte Set (nibble lookup scan)
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// A byte set is encoded as two 16-entry tables indexed by nibble, in the
+// style of Hyperscan's "shufti" / Teddy:
+//
+//   bucket(b)  = 1 << (high_nibble(b) & 7)
+//   hi[h]     |= bucket    for each b in set with high nibble h
+//   lo[l]     |= bucket    for each b in set with low nibble l
+//
+//   candidate(b) = lo[b & 0xF] & hi[b >> 4] != 0
+//
+// vqtbl1q_u8 performs both lookups on 16 bytes at once. High nibbles h and
+// h+8 share a bucket, so non-ASCII bytes may produce false positives; every
+// candidate is re-checked against the exact bitmap.
+
+#[derive(Debug, Clone)]
+struct FirstByteSet {
+    class: ByteClass,
+    /// Up to three bytes: handed to memchr2/memchr3 directly.
+    few: Vec<u8>,
+    #[cfg_attr(not(target_arch = "aarch64"), allow(dead_code))]
+    lo: [u8; 16],
+    #[cfg_attr(not(target_arch = "aarch64"), allow(dead_code))]
+    hi: [u8; 16],
+}
+
+impl FirstByteSet {
+    fn new(class: ByteClass) -> Self {
+        let mut lo = [0u8; 16];
+        let mut hi = [0u8; 16];
+        for b in 0..=255u8 {
+            if class.contains(b) {
+                let bucket = 1u8 << ((b >> 4) & 7);
+                hi[(b >> 4) as usize] |= bucket;
+                lo[(b & 0xF) as usize] |= bucket;
+            }
+        }
+        let few: Vec<u8> = (0..=255u8).filter(|&b| class.contains(b)).take(4).collect();
+        let few = if few.len() <= 3 { few } else { Vec::new() };
+        FirstByteSet { class, few, lo, hi }
+    }
+
+    fn find(&self, haystack: &[u8], at: usize) -> Option<usize> {
+        let found = match *self.few.as_slice() {
+            [a] => Some(memchr::memchr(a, &haystack[at..])),
+            [a, b] => Some(memchr::memchr2(a, b, &haystack[at..])),
+            [a, b, c] => Some(memchr::memchr3(a, b, c, &haystack[at..])),
+            _ => None,
+        };
+        if let Some(found) = found {
+            return found.map(|pos| at + pos);
+        }
+
+        #[cfg_attr(not(target_arch = "aarch64"), allow(unused_mut))]
+        let mut i = at;
+
+        #[cfg(target_arch = "aarch64")]
+        if crate::cpu::has_neon() {
+            unsafe {
+                let lo = vld1q_u8(self.lo.as_ptr());
+                let hi = vld1q_u8(self.hi.as_ptr());
+                let nibble = vdupq_n_u8(0x0F);
+
+                while i + 16 <= haystack.len() {
+                    let v = vld1q_u8(haystack.as_ptr().add(i));
+                    let lo_hit = vqtbl1q_u8(lo, vandq_u8(v, nibble));
+                    let hi_hit = vqtbl1q_u8(hi, vshrq_n_u8(v, 4));
+                    let hit = vandq_u8(lo_hit, hi_hit);
+
+                    if vmaxvq_u8(hit) != 0 {
+                        if let Some(pos) = haystack[i..i + 16]
+                            .iter()
+                            .position(|&b| self.class.contains(b))
+                        {
+                            return Some(i + pos);
+                        }
+                    }
+                    i += 16;
+                }
+            }
+        }
+
+        haystack[i..]
+            .iter()
+            .position(|&b| self.class.contains(b))
+            .map(|pos| i + pos)
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                     Multi-Literal Matcher (Teddy-lite)
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// A set of literals searched for simultaneously.
+///
+/// Candidates come from a scan over the literals' first bytes; each candidate
+/// is confirmed with `starts_with`. This is the multi-pattern matcher used by
+/// the regex prefilter and by [`crate::log_classify`].
+#[derive(Debug, Clone)]
+pub struct LiteralSet {
+    literals: Vec<Vec<u8>>,
+    first: FirstByteSet,
+}
+
+impl LiteralSet {
+    /// Build a set from `literals`. Empty literals never match.
+    pub fn new<L: AsRef<[u8]>>(literals: &[L]) -> LiteralSet {
+        let literals: Vec<Vec<u8>> = literals.iter().map(|l| l.as_ref().to_vec()).collect();
+        let mut first = ByteClass::empty();
+        for lit in literals.iter().filter(|l| !l.is_empty()) {
+            first.insert(lit[0]);
+        }
+        LiteralSet { literals, first: FirstByteSet::new(first) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.literals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.literals.is_empty()
+    }
+
+    /// Leftmost occurrence of any literal at or after `at`, as
+    /// `(position, literal index)`. On ties, the literal listed first wins.
+    ///
+    /// # Example
+    /// ```
+    /// use scratchpad::relite::LiteralSet;
+    ///
+    /// let set = LiteralSet::new(&["WARN", "ERROR"]);
+    /// assert_eq!(set.find_at(b"12:00 ERROR x WARN", 0), Some((6, 1)));
+    /// ```
+    pub fn find_at(&self, haystack: &[u8], at: usize) -> Option<(usize, usize)> {
+        let mut i = at;
+        while let Some(pos) = self.first.find(haystack, i) {
+            let rest = &haystack[pos..];
+            if let Some(idx) = self
+                .literals
+                .iter()
+                .position(|lit| !lit.is_empty() && rest.starts_with(lit))
+            {
+                return Some((pos, idx));
+            }
+            i = pos + 1;
+        }
+        None
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                               Prefilter
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[derive(Debug, Clone)]
+enum Prefilter {
+    /// Pattern can match the empty string: every line is a candidate.
+    None,
+    /// A single literal that every match contains.
+    Memmem(memmem::Finder<'static>),
+    /// One required literal per alternative; scan for their first bytes.
+    Literals(LiteralSet),
+    /// No literal, but the set of possible first bytes is known.
+    FirstByte(FirstByteSet),
+}
+
+impl Prefilter {
+    fn build(ast: &Node, prog: &[Inst]) -> Prefilter {
+        let alts = match ast {
+            Node::Alt(alts) => alts.as_slice(),
+            _ => std::slice::from_ref(ast),
+        };
+
+        let literals: Option<Vec<Vec<u8>>> = alts.iter().map(required_literal).collect();
+        if let Some(mut literals) = literals {
+            literals.sort();
+            literals.dedup();
+            if literals.len() == 1 {
+                return Prefilter::Memmem(memmem::Finder::new(&literals[0]).into_owned());
+            }
+            return Prefilter::Literals(LiteralSet::new(&literals));
+        }
+
+        match first_bytes(prog) {
+            Some(class) => Prefilter::FirstByte(FirstByteSet::new(class)),
+            None => Prefilter::None,
+        }
+    }
+
+    /// Position of the next candidate at or after `at`.
+    fn find(&self, haystack: &[u8], at: usize) -> Option<usize> {
+        match self {
+            Prefilter::None => (at <= haystack.len()).then_some(at),
+            Prefilter::Memmem(finder) => finder.find(&haystack[at..]).map(|pos| at + pos),
+            Prefilter::Literals(set) => set.find_at(haystack, at).map(|(pos, _)| pos),
+            Prefilter::FirstByte(set) => set.find(haystack, at),
+        }
+    }
+}
+
+/// Union of classes reachable from the start without consuming input, or
+/// None if the pattern can match the empty string.
+fn first_bytes(prog: &[Inst]) -> Option<ByteClass> {
+    let mut class = ByteClass::empty();
+    let mut seen = vec![false; prog.len()];
+    let mut stack = vec![0];
+
+    while let Some(pc) = stack.pop() {
+        if std::mem::replace(&mut seen[pc], true) {
+            continue;
+        }
+        match &prog[pc] {
+            Inst::Class(c) => class.union(c),
+            Inst::Split(a, b) => {
+                stack.push(*a);
+                stack.push(*b);
+            }
+            Inst::Jmp(a) => stack.push(*a),
+            Inst::LineStart | Inst::LineEnd => stack.push(pc + 1),
+            Inst::Match => return None,
+        }
+    }
+
+    Some(class)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                          NFA Verifier (Pike VM)
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Byte range `[start, end)` of a match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A compiled regex-lite pattern.
+#[derive(Debug, Clone)]
+pub struct Regex {
+    prog: Vec<Inst>,
+    prefilter: Prefilter,
+}
+
+struct Threads {
+    list: Vec<(usize, usize)>, // (pc, start)
+    stamp: Vec<usize>,         // position + 1 at which pc was last added
+    stack: Vec<usize>,         // scratch for epsilon closure
+}
+
+impl Regex {
+    /// Compile a pattern.
+    ///
+    /// # Example
+    /// ```
+    /// use scratchpad::relite::Regex;
+    ///
+    /// let re = Regex::new(r"(ERROR|WARN) code=\d+").unwrap();
+    /// assert!(re.is_match(b"12:00 WARN code=42 disk"));
+    /// assert!(!re.is_match(b"12:00 INFO code=42"));
+    /// ```
+    pub fn new(pattern: &str) -> Result<Regex, Error> {
+        let mut parser = Parser { pattern: pattern.as_bytes(), pos: 0 };
+        let mut ast = parser.parse_alt()?;
+        if parser.pos < parser.pattern.len() {
+            return Err(parser.error(ErrorKind::UnmatchedParen));
+        }
+        strip_newlines(&mut ast);
+
+        let mut prog = Vec::new();
+        compile(&ast, &mut prog);
+        prog.push(Inst::Match);
+
+        let prefilter = Prefilter::build(&ast, &prog);
+        Ok(Regex { prog, prefilter })
+    }
+
+    /// Check whether the pattern matches anywhere in `haystack`.
+    pub fn is_match(&self, haystack: &[u8]) -> bool {
+        self.find(haystack).is_some()
+    }
+
+    /// Find the leftmost-longest match in `haystack`.
+    pub fn find(&self, haystack: &[u8]) -> Option<Match> {
+        self.find_at(haystack, 0)
+    }
+
+    /// Find the leftmost-longest match starting on the line containing `at` or later.
+    ///
+    /// As in grep, a final `\n` ends the last line: the empty text after it
+    /// is not another line, so `^$` does not match there.
+    pub fn find_at(&self, haystack: &[u8], at: usize) -> Option<Match> {
+        let mut line_start = memchr::memrchr(b'\n', &haystack[..at]).map_or(0, |p| p + 1);
+        let mut threads = self.new_threads();
+
+        loop {
+            if line_start > 0 && line_start == haystack.len() {
+                return None;
+            }
+            let candidate = self.prefilter.find(haystack, line_start)?;
+
+            // Verify the whole line containing the candidate
+            let start = memchr::memrchr(b'\n', &haystack[line_start..candidate])
+                .map_or(line_start, |p| line_start + p + 1);
+            let end = memchr::memchr(b'\n', &haystack[candidate..])
+                .map_or(haystack.len(), |p| candidate + p);
+
+            if let Some(m) = self.find_in_line(&haystack[start..end], &mut threads) {
+                return Some(Match { start: start + m.start, end: start + m.end });
+            }
+            if end >= haystack.len() {
+                return None;
+            }
+            line_start = end + 1;
+        }
+    }
+
+    /// Count the lines of `data` that contain at least one match.
+    pub fn count_matching_lines(&self, data: &[u8]) -> usize {
+        let mut count = 0;
+        let mut at = 0;
+
+        while at < data.len() {
+            match self.find_at(data, at) {
+                None => break,
+                Some(m) => {
+                    count += 1;
+                    match memchr::memchr(b'\n', &data[m.end..]) {
+                        None => break,
+                        Some(pos) => at = m.end + pos + 1,
+                    }
+                }
+            }
+        }
+
+        count
+    }
+
+    fn new_threads(&self) -> [Threads; 2] {
+        [
+            Threads { list: Vec::new(), stamp: vec![0; self.prog.len()], stack: Vec::new() },
+            Threads { list: Vec::new(), stamp: vec![0; self.prog.len()], stack: Vec::new() },
+        ]
+    }
+
+    /// Follow epsilon transitions from `pc` and add the resulting threads.
+    fn add_thread(&self, threads: &mut Threads, pc: usize, start: usize, pos: usize, line: &[u8]) {
+        let stack = &mut threads.stack;
+        stack.push(pc);
+        while let Some(pc) = stack.pop() {
+            if threads.stamp[pc] == pos + 1 {
+                continue;
+            }
+            threads.stamp[pc] = pos + 1;
+
+            match self.prog[pc] {
+                Inst::Class(_) | Inst::Match => threads.list.push((pc, start)),
+                // Push in reverse so the first branch is explored first
+                Inst::Split(a, b) => {
+                    stack.push(b);
+                    stack.push(a);
+                }
+                Inst::Jmp(a) => stack.push(a),
+                Inst::LineStart => {
+                    if pos == 0 {
+                        stack.push(pc + 1);
+                    }
+                }
+                Inst::LineEnd => {
+                    if pos == line.len() {
+                        stack.push(pc + 1);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Leftmost-longest match within a single line (no '\n' inside).
+    fn find_in_line(&self, line: &[u8], threads: &mut [Threads; 2]) -> Option<Match> {
+        let [clist, nlist] = threads;
+        clist.list.clear();
+        clist.stamp.fill(0);
+        nlist.stamp.fill(0);
+
+        let mut best: Option<Match> = None;
+
+        for pos in 0..=line.len() {
+            // Start a new thread here unless a match already started earlier
+            if best.is_none() {
+                self.add_thread(clist, 0, pos, pos, line);
+            }
+            if clist.list.is_empty() {
+                if best.is_some() {
+                    break;
+                }
+                continue;
+            }
+
+            nlist.list.clear();
+            for i in 0..clist.list.len() {
+                let (pc, start) = clist.list[i];
+                if best.is_some_and(|m| start > m.start) {
+                    continue;
+                }
+                match &self.prog[pc] {
+                    Inst::Match => {
+                        if best.is_none_or(|m| start < m.start || pos > m.end) {
+                            best = Some(Match { start, end: pos });
+                        }
+                    }
+                    Inst::Class(class) => {
+                        if pos < line.len() && class.contains(line[pos]) {
+                            self.add_thread(nlist, pc + 1, start, pos + 1, line);
+                        }
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            std::mem::swap(clist, nlist);
+        }
+
+        best
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(pattern: &str, haystack: &[u8]) -> Option<(usize, usize)> {
+        Regex::new(pattern)
+            .unwrap()
+            .find(haystack)
+            .map(|m| (m.start, m.end))
+    }
+
+    #[test]
+    fn test_literal() {
+        assert_eq!(find("Harvard", b"Bob,Harvard,2021"), Some((4, 11)));
+        assert_eq!(find("Harvard", b"Bob,MIT,2021"), None);
+    }
+
+    #[test]
+    fn test_alternation() {
+        assert_eq!(find("ERROR|WARN", b"INFO ok\nWARN disk\n"), Some((8, 12)));
+        assert_eq!(find("ERROR|WARN|INFO", b"INFO ok"), Some((0, 4)));
+        assert_eq!(find("(GET|POST) /api", b"x POST /api/v1"), Some((2, 11)));
+    }
+
+    #[test]
+    fn test_classes() {
+        assert_eq!(find(r"id=\d+", b"user id=1234 ok"), Some((5, 12)));
+        assert_eq!(find("[a-c]+x", b"zzabcabx"), Some((2, 8)));
+        assert_eq!(find("[^,]+", b",,abc,"), Some((2, 5)));
+        assert_eq!(find(r"\w+@\w+", b"mail: bob@example now"), Some((6, 17)));
+        assert_eq!(find("[-a]+", b"x-a-y"), Some((1, 4)));
+    }
+
+    #[test]
+    fn test_repetition() {
+        assert_eq!(find("colou?r", b"the color red"), Some((4, 9)));
+        assert_eq!(find("colou?r", b"the colour red"), Some((4, 10)));
+        assert_eq!(find("ab*c", b"ac abbbc"), Some((0, 2)));
+        assert_eq!(find("a.*z", b"xa12z34z"), Some((1, 8)));
+    }
+
+    #[test]
+    fn test_anchors() {
+        assert_eq!(find("^WARN", b"x WARN\nWARN y"), Some((7, 11)));
+        assert_eq!(find("done$", b"done x\nall done\n"), Some((11, 15)));
+        assert_eq!(find("^$", b"a\n\nb"), Some((2, 2)));
+        assert_eq!(find("^abc$", b"abcd\nabc"), Some((5, 8)));
+    }
+
+    #[test]
+    fn test_anchored_empty_after_final_newline() {
+        // The text after a final '\n' is not a line, as in grep
+        assert_eq!(find("^$", b"a\n"), None);
+        assert_eq!(find("^$", b"a\n\n"), Some((2, 2)));
+        assert_eq!(find("^", b"a\n"), Some((0, 0)));
+        let at_end = Regex::new("^$").unwrap().find_at(b"a\n", 2);
+        assert_eq!(at_end, None);
+
+        let count = |pattern, data| Regex::new(pattern).unwrap().count_matching_lines(data);
+        assert_eq!(count("^$", b"a\n"), 0);
+        assert_eq!(count("^$", b"a\n\nb\n"), 1);
+        assert_eq!(count("^", b"a\nb\n"), 2);
+        assert_eq!(count("$", b"a\nb"), 2);
+        assert_eq!(count("x*", b"a\n\n"), 2);
+    }
+
+    #[test]
+    fn test_no_match_across_lines() {
+        assert_eq!(find("a.*b", b"a\nb"), None);
+        assert_eq!(find(r"a\s*b", b"a \n b"), None);
+        assert_eq!(find("a[^x]b", b"a\nb"), None);
+    }
+
+    #[test]
+    fn test_leftmost_longest() {
+        assert_eq!(find("a|ab", b"xab"), Some((1, 3)));
+        assert_eq!(find(r"\d+", b"x 123 45"), Some((2, 5)));
+    }
+
+    #[test]
+    fn test_count_matching_lines() {
+        let re = Regex::new("Harvard").unwrap();
+        let data = b"Name,University\nAlice,Harvard\nBob,MIT\nHarvard,Harvard University\n";
+        assert_eq!(re.count_matching_lines(data), 2);
+
+        let re = Regex::new(r"^\w+,MIT$").unwrap();
+        assert_eq!(re.count_matching_lines(data), 1);
+    }
+
+    #[test]
+    fn test_prefilter_selection() {
+        assert!(matches!(Regex::new("abc").unwrap().prefilter, Prefilter::Memmem(_)));
+        assert!(matches!(Regex::new("abc|xyz").unwrap().prefilter, Prefilter::Literals(..)));
+        assert!(matches!(Regex::new(r"\d+").unwrap().prefilter, Prefilter::FirstByte(_)));
+        assert!(matches!(Regex::new("a*").unwrap().prefilter, Prefilter::None));
+    }
+
+    #[test]
+    fn test_first_byte_set_long_input() {
+        let re = Regex::new("[xyz]q|zz").unwrap();
+        let mut data = vec![b'a'; 1000];
+        data[700] = b'z';
+        data[701] = b'z';
+        assert_eq!(re.find(&data), Some(Match { start: 700, end: 702 }));
+    }
+
+    #[test]
+    fn test_literal_set() {
+        let set = LiteralSet::new(&["abc", "", "bcd", "ab"]);
+        assert_eq!(set.find_at(b"xxabcd", 0), Some((2, 0)));
+        assert_eq!(set.find_at(b"xxabcd", 3), Some((3, 2)));
+        assert_eq!(set.find_at(b"xxabx", 0), Some((2, 3)));
+        assert_eq!(set.find_at(b"xxxx", 0), None);
+        assert_eq!(LiteralSet::new(&[""]).find_at(b"abc", 0), None);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Regex::new("(abc").unwrap_err().kind, ErrorKind::UnmatchedParen);
+        assert_eq!(Regex::new("abc)").unwrap_err().kind, ErrorKind::UnmatchedParen);
+        assert_eq!(Regex::new("*a").unwrap_err().kind, ErrorKind::NothingToRepeat);
+        assert_eq!(Regex::new("a**").unwrap_err().kind, ErrorKind::NothingToRepeat);
+        assert_eq!(Regex::new("[abc").unwrap_err().kind, ErrorKind::UnclosedClass);
+        assert_eq!(Regex::new("[z-a]").unwrap_err().kind, ErrorKind::InvalidRange);
+        assert_eq!(Regex::new("a\\").unwrap_err().kind, ErrorKind::UnexpectedEnd);
+    }
+}
@@ -0,0 +1,234 @@
+//! Tail-style queries that read a file backwards.
+//!
+//! "When did this last fail?" and "show me the last 100 lines" only care about
+//! the end of the file, yet a forward scan reads all of it. Here the file is
+//! read in buffer-sized chunks from the end towards the start, and each chunk
+//! is searched with memchr's reverse scanners (`memrchr`, `memmem::FinderRev`),
+//! so the cost is proportional to the distance from the end, not the file size.
+//!
+//!   file:   [ chunk 3 ][ chunk 2 ][ chunk 1 ][ chunk 0 ]   <- read first
+//!                                        ^^^ overlap of pattern.len() - 1 bytes
+//!
+//! Consecutive chunks overlap by `pattern.len() - 1` bytes so a match that
+//! straddles a chunk boundary is still seen in one piece.
+
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+use memchr::memmem::FinderRev;
+
+use crate::lines::split_lines;
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Read `buf.len()` bytes at `offset`.
+fn read_at(file: &mut File, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(buf)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                              Last Match
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Find the byte offset of the last occurrence of `pattern` in a file.
+///
+/// Returns None if the pattern does not occur (or is empty).
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::reverse_scan::last_match_offset;
+///
+/// if let Some(offset) = last_match_offset("app.log", b"ERROR").unwrap() {
+///     println!("Last error at byte {}", offset);
+/// }
+/// ```
+pub fn last_match_offset(file_path: &str, pattern: &[u8]) -> io::Result<Option<u64>> {
+    last_match_offset_with_buffer(file_path, pattern, BUFFER_SIZE)
+}
+
+fn last_match_offset_with_buffer(
+    file_path: &str,
+    pattern: &[u8],
+    buffer_size: usize,
+) -> io::Result<Option<u64>> {
+    if pattern.is_empty() {
+        return Ok(None);
+    }
+
+    let mut file = File::open(file_path)?;
+    let file_len = file.metadata()?.len();
+    let finder = FinderRev::new(pattern);
+
+    let overlap = (pattern.len() - 1) as u64;
+    let step = buffer_size.max(pattern.len()) as u64;
+    let mut buffer = vec![0u8; (step + overlap) as usize];
+
+    // Window [start, end) where end includes the overlap into the later chunk
+    let mut chunk_end = file_len;
+    while chunk_end > 0 {
+        let start = chunk_end.saturating_sub(step);
+        let end = (chunk_end + overlap).min(file_len);
+        let window = &mut buffer[..(end - start) as usize];
+        read_at(&mut file, start, window)?;
+
+        if let Some(pos) = finder.rfind(window) {
+            return Ok(Some(start + pos as u64));
+        }
+
+        chunk_end = start;
+    }
+
+    Ok(None)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                              Last N Lines
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Read the last `n` lines of a file (without their `\n`), in file order.
+///
+/// A trailing `\n` at the end of the file does not count as an extra empty
+/// line, matching `tail -n`.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::reverse_scan::read_last_n_lines;
+///
+/// for line in read_last_n_lines("app.log", 10).unwrap() {
+///     println!("{}", String::from_utf8_lossy(&line));
+/// }
+/// ```
+pub fn read_last_n_lines(file_path: &str, n: usize) -> io::Result<Vec<Vec<u8>>> {
+    read_last_n_lines_with_buffer(file_path, n, BUFFER_SIZE)
+}
+
+fn read_last_n_lines_with_buffer(
+    file_path: &str,
+    n: usize,
+    buffer_size: usize,
+) -> io::Result<Vec<Vec<u8>>> {
+    let mut file = File::open(file_path)?;
+    let file_len = file.metadata()?.len();
+    if n == 0 || file_len == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut buffer = vec![0u8; buffer_size.max(1)];
+
+    // Ignore the final terminator: it ends the last line rather than starting a new one
+    let mut last = [0u8; 1];
+    read_at(&mut file, file_len - 1, &mut last)?;
+    let scan_end = if last[0] == b'\n' {
+        file_len - 1
+    } else {
+        file_len
+    };
+
+    // Walk backwards until n newlines are seen; the last one found starts the tail
+    let mut tail_start = 0;
+    let mut newlines = 0;
+    let mut chunk_end = scan_end;
+    'scan: while chunk_end > 0 {
+        let start = chunk_end.saturating_sub(buffer.len() as u64);
+        let chunk = &mut buffer[..(chunk_end - start) as usize];
+        read_at(&mut file, start, chunk)?;
+
+        for pos in memchr::memrchr_iter(b'\n', chunk) {
+            newlines += 1;
+            if newlines == n {
+                tail_start = start + pos as u64 + 1;
+                break 'scan;
+            }
+        }
+
+        chunk_end = start;
+    }
+
+    let mut tail = vec![0u8; (file_len - tail_start) as usize];
+    read_at(&mut file, tail_start, &mut tail)?;
+
+    Ok(split_lines(&tail).map(|line| line.to_vec()).collect())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_temp(name: &str, data: &[u8]) -> String {
+        let path = format!("/tmp/test_reverse_scan_{}", name);
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_last_match_offset() {
+        let path = write_temp("last_match", b"ERROR one\nok\nERROR two\nok\n");
+        assert_eq!(last_match_offset(&path, b"ERROR").unwrap(), Some(13));
+        assert_eq!(last_match_offset(&path, b"missing").unwrap(), None);
+        assert_eq!(last_match_offset(&path, b"").unwrap(), None);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_last_match_across_chunk_boundaries() {
+        let mut data = vec![b'.'; 1000];
+        data[500..507].copy_from_slice(b"NEEDLE!");
+        let path = write_temp("boundaries", &data);
+
+        // Every chunk size, including ones that split the match
+        for buffer_size in 1..64 {
+            assert_eq!(
+                last_match_offset_with_buffer(&path, b"NEEDLE!", buffer_size).unwrap(),
+                Some(500),
+                "Mismatch for buffer_size={}",
+                buffer_size
+            );
+        }
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_last_n_lines() {
+        let path = write_temp("last_n", b"a\nb\n\nc\nd\n");
+        let lines = |n| read_last_n_lines(&path, n).unwrap();
+        assert_eq!(lines(0), Vec::<Vec<u8>>::new());
+        assert_eq!(lines(2), vec![b"c".to_vec(), b"d".to_vec()]);
+        assert_eq!(lines(3), vec![b"".to_vec(), b"c".to_vec(), b"d".to_vec()]);
+        assert_eq!(lines(100).len(), 5);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_last_n_lines_matches_forward_split() {
+        let mut data = Vec::new();
+        for i in 0..500 {
+            data.extend_from_slice(format!("line {} {}\n", i, "x".repeat(i % 13)).as_bytes());
+        }
+        data.extend_from_slice(b"no trailing newline");
+        let path = write_temp("forward", &data);
+
+        let all: Vec<Vec<u8>> = split_lines(&data).map(|l| l.to_vec()).collect();
+        for n in [1, 2, 7, 100, 501, 1000] {
+            for buffer_size in [1, 3, 16, 4096] {
+                let expected = all[all.len().saturating_sub(n)..].to_vec();
+                assert_eq!(read_last_n_lines_with_buffer(&path, n, buffer_size).unwrap(), expected);
+            }
+        }
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let path = write_temp("empty", b"");
+        assert!(read_last_n_lines(&path, 3).unwrap().is_empty());
+        assert_eq!(last_match_offset(&path, b"x").unwrap(), None);
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,293 @@
+//! Pattern counts over many files: explicit paths and directory trees.
+//!
+//! [`scan_paths`] counts the matching lines of every file it is given, and of
+//! the files inside the directories it is given, like `grep -c` over a file
+//! list. The [`Pattern`] is prepared once for the whole scan, and each file
+//! borrows its read buffer from [`ScratchPool::global`](crate::scratch::ScratchPool::global):
+//! after the first file, a scan of thousands of small files allocates only
+//! their paths.
+//!
+//! [`ScanOptions`] turns on recursion into subdirectories and filters the
+//! files found in directories by name with a glob (`*.csv`, `2024-??.tsv`,
+//! `[!.]*`). Files named in the path list are always scanned. Directories are
+//! listed in name order, so the result does not depend on the file system;
+//! symlinks inside them are not followed, as in `manifest`.
+
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    csv_dialect::Dialect, csv_parse_buffer_size_impact::count_compiled_matches_in_reader,
+    needle::Pattern,
+};
+
+/// Which files [`scan_paths_with`] finds in directories, and how it reads them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanOptions {
+    /// Descend into subdirectories (otherwise only the files directly inside
+    /// a directory are scanned).
+    pub recursive: bool,
+    /// Only scan the files found in directories whose name matches this glob:
+    /// `*` is any run of characters, `?` one character, `[a-z]` and `[!0-9]`
+    /// a class.
+    pub glob: Option<String>,
+    pub dialect: Dialect,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions { recursive: false, glob: None, dialect: Dialect::CSV }
+    }
+}
+
+/// Count the lines containing `pattern` in each of `paths` and in the files
+/// directly inside the directories among them, as (file, count) in path
+/// order.
+///
+/// # Example
+/// ```no_run
+/// use scratchpad::scan::scan_paths;
+///
+/// for (path, count) in scan_paths(&["a.csv", "exports/"], b"Harvard").expect("scan failed") {
+///     println!("{}: {}", path.display(), count);
+/// }
+/// ```
+pub fn scan_paths<P: AsRef<Path>>(
+    paths: &[P],
+    pattern: &[u8],
+) -> io::Result<Vec<(PathBuf, usize)>> {
+    scan_paths_with(paths, pattern, &ScanOptions::default())
+}
+
+/// [`scan_paths`] with the recursion, name filter and dialect of `options`.
+///
+/// Stops at the first file or directory that cannot be read; the error names
+/// its path.
+pub fn scan_paths_with<P: AsRef<Path>>(
+    paths: &[P],
+    pattern: &[u8],
+    options: &ScanOptions,
+) -> io::Result<Vec<(PathBuf, usize)>> {
+    let mut files = Vec::new();
+    for path in paths {
+        let path = path.as_ref();
+        if fs::metadata(path).map_err(|e| with_path(e, path))?.is_dir() {
+            collect_files(path, options, &mut files)?;
+        } else {
+            files.push(path.to_path_buf());
+        }
+    }
+
+    let pattern = Pattern::new(pattern);
+    files
+        .into_iter()
+        .map(|path| {
+            let file = File::open(&path).map_err(|e| with_path(e, &path))?;
+            let count = count_compiled_matches_in_reader(file, &pattern, &options.dialect)
+                .map_err(|e| with_path(e, &path))?;
+            Ok((path, count))
+        })
+        .collect()
+}
+
+fn with_path(e: io::Error, path: &Path) -> io::Error {
+    io::Error::new(e.kind(), format!("{}: {}", path.display(), e))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                              Directory Walk
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Append the files in `dir` that `options` selects, in name order,
+/// subdirectories included if recursive.
+fn collect_files(dir: &Path, options: &ScanOptions, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .and_then(|entries| entries.collect::<io::Result<Vec<_>>>())
+        .map_err(|e| with_path(e, dir))?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        // Not followed: a link cycle cannot make the walk infinite
+        let file_type = entry.file_type().map_err(|e| with_path(e, &entry.path()))?;
+        if file_type.is_dir() && options.recursive {
+            collect_files(&entry.path(), options, files)?;
+        } else if file_type.is_file() {
+            let name = entry.file_name();
+            let selected = options
+                .glob
+                .as_deref()
+                .is_none_or(|glob| glob_matches(glob, &name.to_string_lossy()));
+            if selected {
+                files.push(entry.path());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether `name` matches `glob` as a whole: `*` matches any run of
+/// characters, `?` one character, `[...]` one character of a class (`[abc]`,
+/// `[a-z]`, negated with `!` or `^`). Anything else matches itself, as does a
+/// `[` without its `]`.
+///
+/// # Example
+/// ```
+/// use scratchpad::scan::glob_matches;
+///
+/// assert!(glob_matches("*.csv", "people.csv"));
+/// assert!(glob_matches("2024-0[1-6].tsv", "2024-03.tsv"));
+/// assert!(!glob_matches("*.csv", "people.csv.gz"));
+/// ```
+pub fn glob_matches(glob: &str, name: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut g, mut n) = (0, 0);
+    // The glob after the last `*` seen, and the name position it resumes at
+    let mut backtrack = None;
+
+    while n < name.len() {
+        if glob.get(g) == Some(&'*') {
+            g += 1;
+            backtrack = Some((g, n));
+            continue;
+        }
+        if let Some(len) = match_one(&glob[g..], name[n]) {
+            g += len;
+            n += 1;
+            continue;
+        }
+        // Mismatch: the last `*` takes one more character, or nothing matches
+        let Some((after_star, resume)) = backtrack else {
+            return false;
+        };
+        g = after_star;
+        n = resume + 1;
+        backtrack = Some((after_star, n));
+    }
+    glob[g..].iter().all(|&c| c == '*')
+}
+
+/// If the glob token at the start of `glob` (not a `*`) matches `c`, its
+/// length.
+fn match_one(glob: &[char], c: char) -> Option<usize> {
+    match glob.first()? {
+        '?' => Some(1),
+        '[' => match match_class(glob, c) {
+            Some((true, len)) => Some(len),
+            Some((false, _)) => None,
+            None => (c == '[').then_some(1),
+        },
+        &literal => (literal == c).then_some(1),
+    }
+}
+
+/// Match `c` against the class at the start of `glob` (`[...]`): whether it
+/// matched and the length of the class, `None` if the class has no `]`.
+fn match_class(glob: &[char], c: char) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negated = matches!(glob.get(i), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+    // A `]` right after the opening bracket is a member, not the end
+    while let Some(&member) = glob.get(i) {
+        if member == ']' && !first {
+            return Some((matched != negated, i + 1));
+        }
+        first = false;
+        match (glob.get(i + 1), glob.get(i + 2)) {
+            (Some('-'), Some(&end)) if end != ']' => {
+                matched |= (member..=end).contains(&c);
+                i += 3;
+            }
+            _ => {
+                matched |= member == c;
+                i += 1;
+            }
+        }
+    }
+    None
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("*", "anything"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("*.csv", ".csv"));
+        assert!(glob_matches("data_??.csv", "data_07.csv"));
+        assert!(!glob_matches("data_??.csv", "data_7.csv"));
+        assert!(glob_matches("*a*b*c", "xxaxxbxxbxc"));
+        assert!(!glob_matches("*a*b*c", "xxaxxbxxbx"));
+        assert!(glob_matches("[!.]*", "visible"));
+        assert!(!glob_matches("[!.]*", ".hidden"));
+        assert!(glob_matches("[]x]", "]") && glob_matches("[a-]", "-"));
+        assert!(glob_matches("[x", "[x"));
+        assert!(glob_matches("é?.csv", "éa.csv"));
+    }
+
+    #[test]
+    fn test_scan_paths() {
+        let root = "/tmp/test_scan_tree";
+        fs::remove_dir_all(root).ok();
+        fs::create_dir_all(format!("{}/nested/deeper", root)).unwrap();
+        let files = [
+            ("b.csv", "Ann,Harvard\nBob,MIT\n"),
+            ("a.csv", "\u{feff}Harvard\nHarvard,Harvard\n"),
+            ("notes.txt", "Harvard\n"),
+            ("nested/c.csv", "Cid,Harvard\n"),
+            ("nested/deeper/d.csv", "Harvard\nHarvard\nHarvard\n"),
+        ];
+        for (name, content) in files {
+            fs::write(format!("{}/{}", root, name), content).unwrap();
+        }
+        let counts = |result: Vec<(PathBuf, usize)>| -> Vec<(String, usize)> {
+            let relative = |p: &Path| p.strip_prefix(root).unwrap().display().to_string();
+            result
+                .iter()
+                .map(|(p, count)| (relative(p), *count))
+                .collect()
+        };
+        let pair = |name: &str, count| (name.to_string(), count);
+
+        // Directory contents in name order, not recursing
+        assert_eq!(
+            counts(scan_paths(&[root], b"Harvard").unwrap()),
+            [pair("a.csv", 2), pair("b.csv", 1), pair("notes.txt", 1)]
+        );
+
+        let options =
+            ScanOptions { recursive: true, glob: Some("*.csv".into()), ..Default::default() };
+        let explicit = format!("{}/notes.txt", root);
+        let result = scan_paths_with(&[explicit.as_str(), root], b"Harvard", &options).unwrap();
+        assert_eq!(
+            counts(result),
+            [
+                pair("notes.txt", 1),
+                pair("a.csv", 2),
+                pair("b.csv", 1),
+                pair("nested/c.csv", 1),
+                pair("nested/deeper/d.csv", 3),
+            ]
+        );
+
+        let missing = format!("{}/missing.csv", root);
+        let err = scan_paths(&[missing.as_str()], b"Harvard").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().starts_with(&missing));
+        fs::remove_dir_all(root).ok();
+    }
+}
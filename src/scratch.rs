@@ -0,0 +1,186 @@
+//! Reusable scratch buffers for the file scanners.
+//!
+//! Every buffered scan allocates its read buffer (16 KB for the pattern
+//! counters, 1 MB for the record reader) and frees it when done. Once per
+//! file that is noise; in a service answering many small requests it is an
+//! allocation, page faults on first touch, and a free per request. A
+//! [`ScratchPool`] keeps the buffers of finished calls for the next ones:
+//!
+//! ```text
+//!   call 1:  take ──> [buffer A] ──> scan ──> drop: A back in the pool
+//!   call 2:  take ──> [buffer A] ──> scan ──> ...          (no allocation)
+//! ```
+//!
+//! The scanners borrow from [`ScratchPool::global`], shared by all threads
+//! (one lock per call, not per read). Callers with their own lifetime rules
+//! (a pool per worker, dropped with it) create a pool of their own and pass
+//! its buffers to the `_into` functions, such as
+//! `line_feed_every_k_bytes::insert_line_feed_into`.
+//!
+//! Buffers come back with whatever the previous user left in them: a read
+//! buffer is overwritten before it is looked at, anything else should clear
+//! it first. At most [`MAX_POOLED`] buffers are kept, and none larger than
+//! [`MAX_POOLED_CAPACITY`] (a line-reassembling scan can grow its buffer to
+//! the longest line of a file).
+
+use std::{
+    mem,
+    ops::{Deref, DerefMut},
+    sync::Mutex,
+};
+
+/// Idle buffers kept by a pool; more are freed when they come back.
+pub const MAX_POOLED: usize = 16;
+
+/// Largest buffer a pool keeps: a grown buffer is freed rather than pinned.
+pub const MAX_POOLED_CAPACITY: usize = 16 << 20;
+
+/// A stack of idle buffers, handed out by [`take`](ScratchPool::take) and
+/// returned when the [`ScratchBuffer`] is dropped.
+#[derive(Debug, Default)]
+pub struct ScratchPool {
+    idle: Mutex<Vec<Vec<u8>>>,
+}
+
+impl ScratchPool {
+    pub const fn new() -> Self {
+        Self { idle: Mutex::new(Vec::new()) }
+    }
+
+    /// The pool the crate's file scanners borrow from.
+    pub fn global() -> &'static ScratchPool {
+        static GLOBAL: ScratchPool = ScratchPool::new();
+        &GLOBAL
+    }
+
+    /// A buffer of `len` bytes, the most recently returned one if any (the
+    /// likeliest to still be in cache). Bytes a previous user wrote are left
+    /// as they were; only growth is zero-filled.
+    pub fn take(&self, len: usize) -> ScratchBuffer<'_> {
+        let mut buffer = self.pop().unwrap_or_default();
+        buffer.resize(len, 0);
+        ScratchBuffer { buffer, pool: self }
+    }
+
+    /// An empty buffer with room for at least `capacity` bytes, for output
+    /// appended with `extend_from_slice` and friends.
+    pub fn take_empty(&self, capacity: usize) -> ScratchBuffer<'_> {
+        let mut buffer = self.pop().unwrap_or_default();
+        buffer.clear();
+        buffer.reserve(capacity);
+        ScratchBuffer { buffer, pool: self }
+    }
+
+    /// Number of idle buffers in the pool.
+    pub fn idle(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn pop(&self) -> Option<Vec<u8>> {
+        self.lock().pop()
+    }
+
+    fn put(&self, buffer: Vec<u8>) {
+        if buffer.capacity() == 0 || buffer.capacity() > MAX_POOLED_CAPACITY {
+            return;
+        }
+        let mut idle = self.lock();
+        if idle.len() < MAX_POOLED {
+            idle.push(buffer);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Vec<u8>>> {
+        // A panic while holding the lock leaves a valid list of buffers
+        self.idle.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A buffer borrowed from a [`ScratchPool`]: a `Vec<u8>` that goes back to
+/// the pool when dropped.
+#[derive(Debug)]
+pub struct ScratchBuffer<'a> {
+    buffer: Vec<u8>,
+    pool: &'a ScratchPool,
+}
+
+impl ScratchBuffer<'_> {
+    /// Keep the buffer instead of returning it to the pool.
+    pub fn into_vec(mut self) -> Vec<u8> {
+        mem::take(&mut self.buffer)
+    }
+}
+
+impl Deref for ScratchBuffer<'_> {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.buffer
+    }
+}
+
+impl DerefMut for ScratchBuffer<'_> {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+}
+
+impl Drop for ScratchBuffer<'_> {
+    fn drop(&mut self) {
+        self.pool.put(mem::take(&mut self.buffer));
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffers_are_reused() {
+        let pool = ScratchPool::new();
+        let mut buffer = pool.take(64 * 1024);
+        assert_eq!(buffer.len(), 64 * 1024);
+        buffer[0] = 42;
+        let ptr = buffer.as_ptr();
+        drop(buffer);
+        assert_eq!(pool.idle(), 1);
+
+        // Same allocation, old contents kept, resized to the request
+        let buffer = pool.take(1024);
+        assert_eq!((buffer.as_ptr(), buffer.len(), buffer[0]), (ptr, 1024, 42));
+        assert_eq!(pool.idle(), 0);
+        let second = pool.take_empty(100);
+        assert!(second.is_empty() && second.capacity() >= 100);
+        drop((buffer, second));
+        assert_eq!(pool.idle(), 2);
+
+        // Kept out of the pool: grown too large, or taken over
+        pool.take(MAX_POOLED_CAPACITY + 1);
+        pool.take(10).into_vec();
+        assert_eq!(pool.idle(), 0);
+    }
+
+    #[test]
+    fn test_pool_is_bounded() {
+        let pool = ScratchPool::new();
+        let buffers: Vec<_> = (0..MAX_POOLED + 5).map(|_| pool.take(16)).collect();
+        drop(buffers);
+        assert_eq!(pool.idle(), MAX_POOLED);
+
+        // Shared by threads
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..100 {
+                        pool.take(4096)[0] = 1;
+                    }
+                });
+            }
+        });
+        assert!(pool.idle() <= MAX_POOLED);
+    }
+}
@@ -0,0 +1,169 @@
+//! serde_json integration for the SWAR JSON escaper (behind the `serde` feature).
+//!
+//! [`JsonStringWriter`] is a `serde_json::ser::Formatter` whose
+//! `write_string_fragment` goes through [`escape_json_into`], so existing
+//! serde users can A/B the escaper without changing their types:
+//!
+//! ```
+//! use scratchpad::serde_escape::to_vec;
+//!
+//! let json = to_vec(&vec!["plain", "with \"quotes\""]).unwrap();
+//! assert_eq!(json, br#"["plain","with \"quotes\""]"#);
+//! ```
+//!
+//! Note on what is measured: serde_json scans every string itself and hands
+//! the formatter the clean runs between escapable bytes (`write_string_fragment`)
+//! and the escapes (`write_char_escape`) separately. The fragments therefore
+//! pass through the SWAR clean-run path unchanged; the A/B compares the
+//! per-fragment write path, not serde_json's own scan loop.
+//!
+//! Measured with `cargo bench --features serde --bench serde_escape_bench`
+//! (x86-64), the writer runs at 0.7x-0.8x of the default formatter: clean
+//! fragments are scanned a second time. A real speedup needs the escaper to
+//! see whole strings, which serde_json's `Formatter` does not allow.
+
+use std::io;
+
+use serde::Serialize;
+use serde_json::ser::{CompactFormatter, Formatter};
+
+use crate::json_escape_SWAR::escape_json_into;
+
+/// A serde_json formatter that escapes string fragments with the SWAR escaper.
+///
+/// Wraps another formatter (compact by default) for everything but strings,
+/// so `JsonStringWriter::new(PrettyFormatter::new())` works as expected.
+#[derive(Debug, Clone, Default)]
+pub struct JsonStringWriter<F = CompactFormatter> {
+    inner: F,
+    scratch: Vec<u8>,
+}
+
+impl<F: Formatter> JsonStringWriter<F> {
+    pub fn new(inner: F) -> Self {
+        JsonStringWriter { inner, scratch: Vec::new() }
+    }
+}
+
+impl<F: Formatter> Formatter for JsonStringWriter<F> {
+    #[inline]
+    fn write_string_fragment<W: ?Sized + io::Write>(
+        &mut self,
+        writer: &mut W,
+        fragment: &str,
+    ) -> io::Result<()> {
+        self.scratch.clear();
+        escape_json_into(fragment.as_bytes(), &mut self.scratch);
+        writer.write_all(&self.scratch)
+    }
+
+    // Layout is delegated so pretty printing keeps working
+
+    fn begin_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.begin_array(writer)
+    }
+
+    fn end_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_array(writer)
+    }
+
+    fn begin_array_value<W: ?Sized + io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.inner.begin_array_value(writer, first)
+    }
+
+    fn end_array_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_array_value(writer)
+    }
+
+    fn begin_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.begin_object(writer)
+    }
+
+    fn end_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_object(writer)
+    }
+
+    fn begin_object_key<W: ?Sized + io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.inner.begin_object_key(writer, first)
+    }
+
+    fn end_object_key<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_object_key(writer)
+    }
+
+    fn begin_object_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.begin_object_value(writer)
+    }
+
+    fn end_object_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_object_value(writer)
+    }
+}
+
+/// Serialize `value` as compact JSON into `writer`, using [`JsonStringWriter`].
+pub fn to_writer<W: io::Write, T: ?Sized + Serialize>(
+    writer: W,
+    value: &T,
+) -> serde_json::Result<()> {
+    let mut ser =
+        serde_json::Serializer::with_formatter(writer, JsonStringWriter::new(CompactFormatter));
+    value.serialize(&mut ser)
+}
+
+/// Serialize `value` as compact JSON, using [`JsonStringWriter`].
+pub fn to_vec<T: ?Sized + Serialize>(value: &T) -> serde_json::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(128);
+    to_writer(&mut out, value)?;
+    Ok(out)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde_json::ser::PrettyFormatter;
+
+    use super::*;
+
+    #[test]
+    fn test_matches_serde_json() {
+        let mut map = BTreeMap::new();
+        map.insert("plain", "value".to_string());
+        map.insert("quote\"key", "line1\nline2\t\"q\" \\ \u{1} caf\u{e9}".to_string());
+        map.insert("empty", String::new());
+        let value = (map, vec![1.5, -2.0], Some("x"), None::<&str>, true);
+
+        assert_eq!(to_vec(&value).unwrap(), serde_json::to_vec(&value).unwrap());
+    }
+
+    #[test]
+    fn test_pretty_delegation() {
+        let value = vec![("a", 1), ("b\"", 2)];
+
+        let mut ours = Vec::new();
+        let formatter = JsonStringWriter::new(PrettyFormatter::new());
+        value
+            .serialize(&mut serde_json::Serializer::with_formatter(&mut ours, formatter))
+            .unwrap();
+
+        assert_eq!(ours, serde_json::to_vec_pretty(&value).unwrap());
+    }
+
+    #[test]
+    fn test_all_control_characters() {
+        let s: String = (0u8..128).map(|b| b as char).collect();
+        assert_eq!(to_vec(&s).unwrap(), serde_json::to_vec(&s).unwrap());
+    }
+}
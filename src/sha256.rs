@@ -0,0 +1,286 @@
+//! SHA-256 with a scalar compression function and an ARMv8 crypto-extension one.
+//!
+//! Based on: FIPS 180-4, https://csrc.nist.gov/pubs/fips/180-4/upd1/final
+//!
+//! The block function dominates: 64 rounds of adds, rotates and boolean mixes
+//! per 64-byte block. ARMv8 has dedicated instructions for it:
+//! - `SHA256H` / `SHA256H2`: four rounds on the two halves of the state
+//! - `SHA256SU0` / `SHA256SU1`: four words of the message schedule
+//!
+//! The crypto extension is optional on aarch64, so it is detected at runtime
+//! and the scalar version is used otherwise.
+
+#[cfg(target_arch = "aarch64")]
+use std::arch::aarch64::*;
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const H0: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                          Compression Functions
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Process 64-byte blocks into `state` (scalar version).
+pub fn compress_scalar(state: &mut [u32; 8], blocks: &[u8]) {
+    for block in blocks.chunks_exact(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes(word.try_into().unwrap());
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (s, v) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *s = s.wrapping_add(v);
+        }
+    }
+}
+
+/// Process 64-byte blocks into `state` (ARMv8 crypto extension version).
+///
+/// The state lives in two registers (ABCD, EFGH); each iteration adds the
+/// round constants to four message words and runs four rounds. The first 12
+/// iterations also extend the message schedule by four words.
+///
+/// # Safety
+/// Requires a CPU with the `sha2` feature (check with
+/// [`has_sha2`](crate::cpu::has_sha2)).
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon,sha2")]
+pub unsafe fn compress_neon(state: &mut [u32; 8], blocks: &[u8]) {
+    let mut abcd = vld1q_u32(state.as_ptr());
+    let mut efgh = vld1q_u32(state.as_ptr().add(4));
+
+    for block in blocks.chunks_exact(64) {
+        let abcd_orig = abcd;
+        let efgh_orig = efgh;
+
+        // Big-endian words: byte-reverse each 32-bit lane
+        let p = block.as_ptr();
+        let mut msg = [
+            vreinterpretq_u32_u8(vrev32q_u8(vld1q_u8(p))),
+            vreinterpretq_u32_u8(vrev32q_u8(vld1q_u8(p.add(16)))),
+            vreinterpretq_u32_u8(vrev32q_u8(vld1q_u8(p.add(32)))),
+            vreinterpretq_u32_u8(vrev32q_u8(vld1q_u8(p.add(48)))),
+        ];
+
+        for i in 0..16 {
+            let wk = vaddq_u32(msg[i % 4], vld1q_u32(K.as_ptr().add(4 * i)));
+            if i < 12 {
+                let next = vsha256su0q_u32(msg[i % 4], msg[(i + 1) % 4]);
+                msg[i % 4] = vsha256su1q_u32(next, msg[(i + 2) % 4], msg[(i + 3) % 4]);
+            }
+
+            let abcd_prev = abcd;
+            abcd = vsha256hq_u32(abcd, efgh, wk);
+            efgh = vsha256h2q_u32(efgh, abcd_prev, wk);
+        }
+
+        abcd = vaddq_u32(abcd, abcd_orig);
+        efgh = vaddq_u32(efgh, efgh_orig);
+    }
+
+    vst1q_u32(state.as_mut_ptr(), abcd);
+    vst1q_u32(state.as_mut_ptr().add(4), efgh);
+}
+
+/// Process 64-byte blocks into `state` (best available version).
+#[inline]
+fn compress(state: &mut [u32; 8], blocks: &[u8]) {
+    #[cfg(target_arch = "aarch64")]
+    if crate::cpu::has_sha2() {
+        unsafe { compress_neon(state, blocks) };
+        return;
+    }
+
+    compress_scalar(state, blocks)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                             Streaming Hasher
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Incremental SHA-256.
+///
+/// # Example
+/// ```
+/// use scratchpad::sha256::Sha256;
+///
+/// let mut hasher = Sha256::new();
+/// hasher.update(b"ab");
+/// hasher.update(b"c");
+/// assert_eq!(hasher.finalize()[..4], [0xba, 0x78, 0x16, 0xbf]);
+/// ```
+#[derive(Clone)]
+pub struct Sha256 {
+    state: [u32; 8],
+    buffer: [u8; 64],
+    buffered: usize,
+    total_len: u64,
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sha256 {
+    pub fn new() -> Self {
+        Sha256 { state: H0, buffer: [0; 64], buffered: 0, total_len: 0 }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+
+        // Complete a partially filled block first
+        if self.buffered > 0 {
+            let take = (64 - self.buffered).min(data.len());
+            self.buffer[self.buffered..self.buffered + take].copy_from_slice(&data[..take]);
+            self.buffered += take;
+            data = &data[take..];
+            if self.buffered < 64 {
+                return;
+            }
+            compress(&mut self.state, &self.buffer);
+            self.buffered = 0;
+        }
+
+        // Whole blocks straight from the input
+        let whole = data.len() - data.len() % 64;
+        compress(&mut self.state, &data[..whole]);
+
+        let rest = &data[whole..];
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffered = rest.len();
+    }
+
+    pub fn finalize(mut self) -> [u8; 32] {
+        // Padding: 0x80, zeros, then the bit length as a big-endian u64
+        let bit_len = self.total_len.wrapping_mul(8);
+        let mut padding = [0u8; 72];
+        padding[0] = 0x80;
+        let pad_len = if self.buffered < 56 {
+            56 - self.buffered
+        } else {
+            120 - self.buffered
+        };
+        self.update(&padding[..pad_len]);
+        padding[..8].copy_from_slice(&bit_len.to_be_bytes());
+        self.update(&padding[..8]);
+
+        let mut out = [0u8; 32];
+        for (chunk, word) in out.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        out
+    }
+}
+
+/// Hash `data` in one call.
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Format a digest as lowercase hex.
+pub fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_vectors() {
+        assert_eq!(
+            to_hex(&sha256(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            to_hex(&sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            to_hex(&sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            to_hex(&sha256(&vec![b'a'; 1_000_000])),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+    }
+
+    #[test]
+    fn test_incremental_matches_one_shot() {
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 31 % 251) as u8).collect();
+        let expected = sha256(&data);
+        for split in [0, 1, 55, 56, 63, 64, 65, 127, 500, 1000] {
+            let mut hasher = Sha256::new();
+            hasher.update(&data[..split]);
+            hasher.update(&data[split..]);
+            assert_eq!(hasher.finalize(), expected, "Mismatch for split={}", split);
+        }
+    }
+
+    #[test]
+    #[cfg(target_arch = "aarch64")]
+    fn test_neon_matches_scalar() {
+        if !crate::cpu::has_sha2() {
+            return;
+        }
+        let data: Vec<u8> = (0..64 * 10).map(|i| (i * 7) as u8).collect();
+        let mut scalar = H0;
+        let mut neon = H0;
+        compress_scalar(&mut scalar, &data);
+        unsafe { compress_neon(&mut neon, &data) };
+        assert_eq!(scalar, neon);
+    }
+}
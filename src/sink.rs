@@ -0,0 +1,473 @@
+//! Output sinks: write tabular results as CSV, NDJSON or MessagePack.
+//!
+//! Query-style functions produce rows of a few typed values. Instead of each
+//! one formatting text, they write to a [`Sink`], and the caller picks the
+//! encoding:
+//! - [`CsvSink`]: RFC 4180, fields quoted only when needed
+//! - [`NdjsonSink`]: one JSON object per line, strings escaped with the SWAR escaper from
+//!   [`crate::json_escape_SWAR`]
+//! - [`MsgPackSink`]: one MessagePack map per row (see [`crate::msgpack`]), for tools that want
+//!   typed values without parsing text
+//!
+//! Each row is encoded into a reused buffer and written with one `write_all`,
+//! so wrapping the writer in a `BufWriter` is only needed for tiny rows.
+//!
+//! Written to by the value counts of a column
+//! ([`write_values_in_column`](crate::csv_parse_buffer_size_impact::write_values_in_column)),
+//! the statistics of a CSV document ([`CsvStats::write_to`](crate::csv_index::CsvStats::write_to))
+//! and `sgrep --format`.
+
+use std::{
+    fmt,
+    io::{self, Write},
+    str::FromStr,
+};
+
+use crate::{
+    csv_write::{end_record, write_field, write_record},
+    json_escape_SWAR::escape_json_into,
+    msgpack::{
+        encode_bool, encode_f64, encode_int, encode_map_len, encode_nil, encode_str, encode_text,
+    },
+    utf8,
+};
+
+/// One value of an output row.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value<'a> {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    /// Text; usually UTF-8. Invalid UTF-8 is written unchanged to CSV, as
+    /// U+FFFD to NDJSON and as bin to MessagePack.
+    Str(&'a [u8]),
+}
+
+impl<'a> From<&'a str> for Value<'a> {
+    fn from(s: &'a str) -> Self {
+        Value::Str(s.as_bytes())
+    }
+}
+
+impl<'a> From<&'a [u8]> for Value<'a> {
+    fn from(s: &'a [u8]) -> Self {
+        Value::Str(s)
+    }
+}
+
+impl From<i64> for Value<'_> {
+    fn from(n: i64) -> Self {
+        Value::Int(n)
+    }
+}
+
+impl From<f64> for Value<'_> {
+    fn from(x: f64) -> Self {
+        Value::Float(x)
+    }
+}
+
+impl From<bool> for Value<'_> {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+/// Destination for rows of values.
+///
+/// Call [`Sink::begin`] once with the column names, then [`Sink::row`] for
+/// each row (with one value per column), then [`Sink::finish`].
+pub trait Sink {
+    fn begin(&mut self, columns: &[&str]) -> io::Result<()>;
+    fn row(&mut self, values: &[Value<'_>]) -> io::Result<()>;
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// A boxed sink, as [`OutputFormat::sink`] returns, can be passed where a
+/// `&mut impl Sink` is expected.
+impl<S: Sink + ?Sized> Sink for Box<S> {
+    fn begin(&mut self, columns: &[&str]) -> io::Result<()> {
+        (**self).begin(columns)
+    }
+
+    fn row(&mut self, values: &[Value<'_>]) -> io::Result<()> {
+        (**self).row(values)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        (**self).finish()
+    }
+}
+
+fn check_width(columns: usize, values: usize) -> io::Result<()> {
+    if columns != values {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("row has {} values for {} columns", values, columns),
+        ));
+    }
+    Ok(())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                   CSV
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Writes a header line and one CSV record per row.
+///
+/// Null is an empty field; booleans are `true`/`false`.
+pub struct CsvSink<W: Write> {
+    writer: W,
+    columns: usize,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> CsvSink<W> {
+    pub fn new(writer: W) -> Self {
+        CsvSink { writer, columns: 0, buffer: Vec::new() }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Sink for CsvSink<W> {
+    fn begin(&mut self, columns: &[&str]) -> io::Result<()> {
+        self.columns = columns.len();
+        self.buffer.clear();
+        write_record(columns, &mut self.buffer);
+        self.writer.write_all(&self.buffer)
+    }
+
+    fn row(&mut self, values: &[Value<'_>]) -> io::Result<()> {
+        check_width(self.columns, values.len())?;
+        self.buffer.clear();
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                self.buffer.push(b',');
+            }
+            match *value {
+                Value::Null => {}
+                Value::Bool(b) => write!(self.buffer, "{}", b)?,
+                Value::Int(n) => write!(self.buffer, "{}", n)?,
+                Value::Float(x) => write!(self.buffer, "{}", x)?,
+                Value::Str(s) => write_field(s, &mut self.buffer),
+            }
+        }
+        end_record(0, values.len(), &mut self.buffer);
+        self.writer.write_all(&self.buffer)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                  NDJSON
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Writes one JSON object per row: `{"column":value,...}\n`.
+///
+/// Non-finite floats have no JSON representation and are written as `null`.
+/// JSON text must be UTF-8, so invalid sequences in strings are replaced
+/// with U+FFFD.
+pub struct NdjsonSink<W: Write> {
+    writer: W,
+    /// Column names, pre-escaped as `"name":`.
+    keys: Vec<Vec<u8>>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> NdjsonSink<W> {
+    pub fn new(writer: W) -> Self {
+        NdjsonSink { writer, keys: Vec::new(), buffer: Vec::new() }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Sink for NdjsonSink<W> {
+    fn begin(&mut self, columns: &[&str]) -> io::Result<()> {
+        self.keys = columns
+            .iter()
+            .map(|name| {
+                let mut key = vec![b'"'];
+                escape_json_into(name.as_bytes(), &mut key);
+                key.extend_from_slice(b"\":");
+                key
+            })
+            .collect();
+        Ok(())
+    }
+
+    fn row(&mut self, values: &[Value<'_>]) -> io::Result<()> {
+        check_width(self.keys.len(), values.len())?;
+        self.buffer.clear();
+        self.buffer.push(b'{');
+        for (i, (key, value)) in self.keys.iter().zip(values).enumerate() {
+            if i > 0 {
+                self.buffer.push(b',');
+            }
+            self.buffer.extend_from_slice(key);
+            match *value {
+                Value::Null => self.buffer.extend_from_slice(b"null"),
+                Value::Bool(b) => write!(self.buffer, "{}", b)?,
+                Value::Int(n) => write!(self.buffer, "{}", n)?,
+                Value::Float(x) if x.is_finite() => write!(self.buffer, "{:?}", x)?,
+                Value::Float(_) => self.buffer.extend_from_slice(b"null"),
+                Value::Str(s) => {
+                    self.buffer.push(b'"');
+                    if utf8::valid_up_to(s) == s.len() {
+                        escape_json_into(s, &mut self.buffer);
+                    } else {
+                        let text = String::from_utf8_lossy(s);
+                        escape_json_into(text.as_bytes(), &mut self.buffer);
+                    }
+                    self.buffer.push(b'"');
+                }
+            }
+        }
+        self.buffer.extend_from_slice(b"}\n");
+        self.writer.write_all(&self.buffer)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                               MessagePack
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Encoding is done by [`crate::msgpack`]. Strings that are not valid UTF-8
+// are written as bin rather than str.
+
+/// Writes one MessagePack map per row, keyed by column name.
+pub struct MsgPackSink<W: Write> {
+    writer: W,
+    /// Column names, pre-encoded as MessagePack strings.
+    keys: Vec<Vec<u8>>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> MsgPackSink<W> {
+    pub fn new(writer: W) -> Self {
+        MsgPackSink { writer, keys: Vec::new(), buffer: Vec::new() }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Sink for MsgPackSink<W> {
+    fn begin(&mut self, columns: &[&str]) -> io::Result<()> {
+        self.keys = columns
+            .iter()
+            .map(|name| {
+                let mut key = Vec::new();
+                encode_str(name, &mut key);
+                key
+            })
+            .collect();
+        Ok(())
+    }
+
+    fn row(&mut self, values: &[Value<'_>]) -> io::Result<()> {
+        check_width(self.keys.len(), values.len())?;
+        self.buffer.clear();
+        encode_map_len(values.len(), &mut self.buffer);
+        for (key, value) in self.keys.iter().zip(values) {
+            self.buffer.extend_from_slice(key);
+            match *value {
+                Value::Null => encode_nil(&mut self.buffer),
+                Value::Bool(b) => encode_bool(b, &mut self.buffer),
+                Value::Int(n) => encode_int(n, &mut self.buffer),
+                Value::Float(x) => encode_f64(x, &mut self.buffer),
+                Value::Str(s) => encode_text(s, &mut self.buffer),
+            }
+        }
+        self.writer.write_all(&self.buffer)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                              Format Selection
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Output encoding, as chosen on a command line (`csv`, `ndjson`, `msgpack`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Csv,
+    Ndjson,
+    MsgPack,
+}
+
+/// The output format name is not one of `csv`, `ndjson`, `msgpack`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownFormatError(pub String);
+
+impl fmt::Display for UnknownFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown output format {:?} (expected csv, ndjson or msgpack)", self.0)
+    }
+}
+
+impl std::error::Error for UnknownFormatError {}
+
+impl FromStr for OutputFormat {
+    type Err = UnknownFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(OutputFormat::Csv),
+            "ndjson" | "jsonl" => Ok(OutputFormat::Ndjson),
+            "msgpack" => Ok(OutputFormat::MsgPack),
+            _ => Err(UnknownFormatError(s.to_string())),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Create a sink writing this format to `writer`.
+    ///
+    /// # Example
+    /// ```
+    /// use scratchpad::sink::{OutputFormat, Value};
+    ///
+    /// let mut out = Vec::new();
+    /// let mut sink = "ndjson".parse::<OutputFormat>().unwrap().sink(&mut out);
+    /// sink.begin(&["bucket", "count"]).unwrap();
+    /// sink.row(&[Value::Int(1705314600), Value::Int(42)]).unwrap();
+    /// sink.finish().unwrap();
+    /// drop(sink);
+    /// assert_eq!(out, b"{\"bucket\":1705314600,\"count\":42}\n");
+    /// ```
+    pub fn sink<'w, W: Write + 'w>(self, writer: W) -> Box<dyn Sink + 'w> {
+        match self {
+            OutputFormat::Csv => Box::new(CsvSink::new(writer)),
+            OutputFormat::Ndjson => Box::new(NdjsonSink::new(writer)),
+            OutputFormat::MsgPack => Box::new(MsgPackSink::new(writer)),
+        }
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_rows(sink: &mut dyn Sink) {
+        sink.begin(&["name", "n", "x", "ok", "missing"]).unwrap();
+        sink.row(&[
+            "plain".into(),
+            1.into(),
+            0.5.into(),
+            true.into(),
+            Value::Null,
+        ])
+        .unwrap();
+        sink.row(&[
+            "a,\"b\"\nc".into(),
+            (-300).into(),
+            f64::NAN.into(),
+            false.into(),
+            Value::Null,
+        ])
+        .unwrap();
+        sink.finish().unwrap();
+    }
+
+    #[test]
+    fn test_csv() {
+        let mut sink = CsvSink::new(Vec::new());
+        write_rows(&mut sink);
+        assert_eq!(
+            String::from_utf8(sink.into_inner()).unwrap(),
+            "name,n,x,ok,missing\nplain,1,0.5,true,\n\"a,\"\"b\"\"\nc\",-300,NaN,false,\n"
+        );
+    }
+
+    #[test]
+    fn test_ndjson() {
+        let mut sink = NdjsonSink::new(Vec::new());
+        write_rows(&mut sink);
+        assert_eq!(
+            String::from_utf8(sink.into_inner()).unwrap(),
+            "{\"name\":\"plain\",\"n\":1,\"x\":0.5,\"ok\":true,\"missing\":null}\n\
+             {\"name\":\"a,\\\"b\\\"\\nc\",\"n\":-300,\"x\":null,\"ok\":false,\"missing\":null}\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_single_empty_field() {
+        let mut sink = CsvSink::new(Vec::new());
+        sink.begin(&[""]).unwrap();
+        sink.row(&[Value::Null]).unwrap();
+        sink.row(&["".into()]).unwrap();
+        sink.row(&["x".into()]).unwrap();
+        assert_eq!(sink.into_inner(), b"\"\"\n\"\"\n\"\"\nx\n");
+    }
+
+    #[test]
+    fn test_ndjson_invalid_utf8() {
+        let mut sink = NdjsonSink::new(Vec::new());
+        sink.begin(&["s"]).unwrap();
+        sink.row(&[Value::Str(b"caf\xE9 \"ok\"")]).unwrap();
+        sink.row(&[Value::Str(b"\xED\xA0\x80")]).unwrap();
+        assert_eq!(
+            String::from_utf8(sink.into_inner()).unwrap(),
+            "{\"s\":\"caf\u{FFFD} \\\"ok\\\"\"}\n{\"s\":\"\u{FFFD}\u{FFFD}\u{FFFD}\"}\n"
+        );
+    }
+
+    #[test]
+    fn test_msgpack() {
+        let mut sink = MsgPackSink::new(Vec::new());
+        sink.begin(&["s", "i", "f", "b", "z"]).unwrap();
+        sink.row(&[
+            "hi".into(),
+            (-300).into(),
+            1.5.into(),
+            true.into(),
+            Value::Null,
+        ])
+        .unwrap();
+
+        let mut expected = vec![0x85];
+        expected.extend_from_slice(&[0xA1, b's', 0xA2, b'h', b'i']);
+        expected.extend_from_slice(&[0xA1, b'i', 0xD1, 0xFE, 0xD4]);
+        expected.extend_from_slice(&[0xA1, b'f', 0xCB, 0x3F, 0xF8, 0, 0, 0, 0, 0, 0]);
+        expected.extend_from_slice(&[0xA1, b'b', 0xC3]);
+        expected.extend_from_slice(&[0xA1, b'z', 0xC0]);
+        assert_eq!(sink.into_inner(), expected);
+    }
+
+    #[test]
+    fn test_row_width_checked() {
+        let mut sink = OutputFormat::Csv.sink(Vec::new());
+        sink.begin(&["a", "b"]).unwrap();
+        assert_eq!(sink.row(&[Value::Null]).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_format_from_str() {
+        assert_eq!("msgpack".parse::<OutputFormat>(), Ok(OutputFormat::MsgPack));
+        assert_eq!("jsonl".parse::<OutputFormat>(), Ok(OutputFormat::Ndjson));
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+}
@@ -0,0 +1,191 @@
+//! Find the data ranges of sparse files with `SEEK_DATA` / `SEEK_HOLE`.
+//!
+//! A sparse file has ranges ("holes") that were never written: they read as
+//! zeros but occupy no disk blocks. Pre-allocated log files are often mostly
+//! holes, and a streaming scanner that reads them byte by byte spends minutes
+//! on zeros. Linux can report where the data is:
+//!
+//!   lseek(fd, off, SEEK_DATA)  -> start of the next data range at or after off
+//!   lseek(fd, off, SEEK_HOLE)  -> start of the next hole at or after off
+//!
+//!   file:   [ data ][     hole      ][ data ][  hole  ]
+//!           ^0     ^SEEK_HOLE       ^SEEK_DATA
+//!
+//! Filesystems without hole tracking report the whole file as one data range,
+//! and so does every non-Linux target, so callers never need a fallback path.
+
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    ops::Range,
+};
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                              Data Ranges
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(target_os = "linux")]
+fn seek_data_or_hole(file: &File, offset: u64, whence: libc::c_int) -> io::Result<Option<u64>> {
+    use std::os::unix::io::AsRawFd;
+
+    let pos = unsafe { libc::lseek(file.as_raw_fd(), offset as libc::off_t, whence) };
+    if pos >= 0 {
+        return Ok(Some(pos as u64));
+    }
+
+    // ENXIO: no data (or hole) at or after offset
+    let err = io::Error::last_os_error();
+    match err.raw_os_error() {
+        Some(libc::ENXIO) => Ok(None),
+        _ => Err(err),
+    }
+}
+
+/// List the ranges of a file that contain data, in ascending order.
+///
+/// Everything between them is a hole. On targets or filesystems without hole
+/// reporting, the whole file is returned as a single range.
+#[cfg(target_os = "linux")]
+pub fn data_ranges(file: &File) -> io::Result<Vec<Range<u64>>> {
+    let len = file.metadata()?.len();
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+
+    while offset < len {
+        let start = match seek_data_or_hole(file, offset, libc::SEEK_DATA) {
+            Ok(Some(start)) => start,
+            Ok(None) => break,
+            // EINVAL: the filesystem does not support SEEK_DATA
+            Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {
+                return Ok(vec![Range { start: 0, end: len }])
+            }
+            Err(e) => return Err(e),
+        };
+        // There is always a virtual hole at EOF
+        let end = seek_data_or_hole(file, start, libc::SEEK_HOLE)?
+            .unwrap_or(len)
+            .min(len);
+
+        if start < end {
+            ranges.push(start..end);
+        }
+        offset = end.max(start + 1);
+    }
+
+    Ok(ranges)
+}
+
+/// List the ranges of a file that contain data, in ascending order.
+///
+/// Hole reporting is Linux-only: here the whole file is one data range.
+#[cfg(not(target_os = "linux"))]
+pub fn data_ranges(file: &File) -> io::Result<Vec<Range<u64>>> {
+    let len = file.metadata()?.len();
+    Ok(if len > 0 {
+        vec![Range { start: 0, end: len }]
+    } else {
+        Vec::new()
+    })
+}
+
+/// Complement of `data` within `0..len`: the holes.
+pub fn hole_ranges(data: &[Range<u64>], len: u64) -> Vec<Range<u64>> {
+    let mut holes = Vec::new();
+    let mut pos = 0;
+
+    for range in data {
+        if range.start > pos {
+            holes.push(pos..range.start);
+        }
+        pos = pos.max(range.end);
+    }
+    if pos < len {
+        holes.push(pos..len);
+    }
+
+    holes
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                           Segment Streaming
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Call `f` with a reader over each data range of `file`, skipping holes.
+///
+/// Each reader yields exactly the bytes of one data range and then EOF, so
+/// streaming scanners can be reused unchanged. Returns the skipped holes.
+pub fn for_each_data_segment<F>(file: &mut File, mut f: F) -> io::Result<Vec<Range<u64>>>
+where
+    F: FnMut(&mut io::Take<&mut File>) -> io::Result<()>,
+{
+    let len = file.metadata()?.len();
+    let data = data_ranges(file)?;
+
+    for range in &data {
+        file.seek(SeekFrom::Start(range.start))?;
+        f(&mut file.by_ref().take(range.end - range.start))?;
+    }
+
+    Ok(hole_ranges(&data, len))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn test_hole_ranges() {
+        assert_eq!(hole_ranges(&[], 10), vec![Range { start: 0, end: 10 }]);
+        assert_eq!(hole_ranges(&[Range { start: 0, end: 10 }], 10), Vec::<Range<u64>>::new());
+        assert_eq!(hole_ranges(&[2..4, 6..8], 10), vec![0..2, 4..6, 8..10]);
+    }
+
+    #[test]
+    fn test_sparse_file_segments() {
+        let path = "/tmp/test_sparse_segments.bin";
+        let mut f = File::create(path).unwrap();
+        f.write_all(b"head").unwrap();
+        f.set_len(16 << 20).unwrap();
+        f.seek(SeekFrom::Start(8 << 20)).unwrap();
+        f.write_all(b"middle").unwrap();
+        drop(f);
+
+        let mut file = File::open(path).unwrap();
+        let mut contents = Vec::new();
+        let holes = for_each_data_segment(&mut file, |segment| {
+            segment.read_to_end(&mut contents).map(|_| ())
+        })
+        .unwrap();
+
+        // Data and holes tile the file exactly, whether or not the
+        // filesystem reports holes
+        let data = data_ranges(&file).unwrap();
+        let covered: u64 = data.iter().chain(&holes).map(|r| r.end - r.start).sum();
+        assert_eq!(covered, 16 << 20);
+        assert_eq!(contents.len() as u64, data.iter().map(|r| r.end - r.start).sum::<u64>());
+
+        // The written bytes are never skipped
+        assert!(contents.windows(4).any(|w| w == b"head"));
+        assert!(contents.windows(6).any(|w| w == b"middle"));
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let path = "/tmp/test_sparse_empty.bin";
+        File::create(path).unwrap();
+        let mut file = File::open(path).unwrap();
+        assert!(data_ranges(&file).unwrap().is_empty());
+        assert!(for_each_data_segment(&mut file, |_| Ok(()))
+            .unwrap()
+            .is_empty());
+        std::fs::remove_file(path).ok();
+    }
+}
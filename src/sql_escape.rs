@@ -0,0 +1,179 @@
+//! Detect and escape bytes inside SQL string literals.
+//!
+//! Bulk-generating `INSERT ... VALUES ('...')` statements from CSV spends most
+//! of its time escaping field values, and almost all values are clean. The
+//! detection and clean-run copy loop come from [`crate::byte_set`] (the
+//! [`SQL`] preset: `'`, `\` and NUL), like the JSON and HTML escapers.
+//!
+//! What an escape looks like depends on the server:
+//!
+//! | Byte | Standard (PostgreSQL, SQLite, ...) | MySQL (default mode) |
+//! |------|------------------------------------|----------------------|
+//! | `'`  | `''`                               | `''`                 |
+//! | `\`  | unchanged                          | `\\`                 |
+//! | NUL  | error: not representable           | `\0`                 |
+
+use std::fmt;
+
+use crate::byte_set::{escape_into, SQL};
+
+/// Which string literal rules to escape for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SqlDialect {
+    /// ANSI SQL: only the quote is special, by doubling.
+    #[default]
+    Standard,
+    /// MySQL without `NO_BACKSLASH_ESCAPES`: backslash is an escape character.
+    MySql,
+}
+
+/// The input contains a NUL byte, which a standard SQL literal cannot hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NulByteError {
+    /// Offset of the first NUL byte in the input.
+    pub offset: usize,
+}
+
+impl fmt::Display for NulByteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "NUL byte at offset {} cannot be escaped in a standard SQL literal",
+            self.offset
+        )
+    }
+}
+
+impl std::error::Error for NulByteError {}
+
+// ───────────────────────────────────────────────────────────────────────────
+//                         Scalar Reference
+// ───────────────────────────────────────────────────────────────────────────
+
+/// Check if a single byte needs attention in a SQL literal (scalar version).
+#[inline]
+pub fn needs_sql_escape_scalar(byte: u8) -> bool {
+    byte == b'\'' || byte == b'\\' || byte == 0
+}
+
+/// Escape `input` byte by byte (scalar reference).
+pub fn escape_sql_scalar(input: &[u8], dialect: SqlDialect) -> Result<Vec<u8>, NulByteError> {
+    let mut out = Vec::with_capacity(input.len());
+    for (i, &b) in input.iter().enumerate() {
+        match (b, dialect) {
+            (b'\'', _) => out.extend_from_slice(b"''"),
+            (b'\\', SqlDialect::MySql) => out.extend_from_slice(b"\\\\"),
+            (0, SqlDialect::MySql) => out.extend_from_slice(b"\\0"),
+            (0, SqlDialect::Standard) => return Err(NulByteError { offset: i }),
+            _ => out.push(b),
+        }
+    }
+    Ok(out)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                          Detection and Escaping
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Check if any byte in a buffer needs attention in a SQL literal (SWAR version).
+pub fn has_sql_escapable_byte(buffer: &[u8]) -> bool {
+    SQL.has_any(buffer)
+}
+
+/// Escape `input` for use inside a single-quoted SQL literal, appending to `out`.
+///
+/// On error, `out` may contain a partial result.
+pub fn escape_sql_into(
+    input: &[u8],
+    dialect: SqlDialect,
+    out: &mut Vec<u8>,
+) -> Result<(), NulByteError> {
+    if dialect == SqlDialect::Standard {
+        if let Some(offset) = memchr::memchr(0, input) {
+            return Err(NulByteError { offset });
+        }
+    }
+
+    escape_into(input, &SQL, out, |b, out| match (b, dialect) {
+        (b'\'', _) => out.extend_from_slice(b"''"),
+        (b'\\', SqlDialect::MySql) => out.extend_from_slice(b"\\\\"),
+        (0, _) => out.extend_from_slice(b"\\0"),
+        _ => out.push(b),
+    });
+    Ok(())
+}
+
+/// Escape `input` for use inside a single-quoted SQL literal.
+///
+/// # Example
+/// ```
+/// use scratchpad::sql_escape::{escape_sql, SqlDialect};
+///
+/// assert_eq!(escape_sql(b"O'Brien", SqlDialect::Standard).unwrap(), b"O''Brien");
+/// assert_eq!(escape_sql(b"C:\\tmp", SqlDialect::MySql).unwrap(), b"C:\\\\tmp");
+/// assert!(escape_sql(b"a\0b", SqlDialect::Standard).is_err());
+/// ```
+pub fn escape_sql(input: &[u8], dialect: SqlDialect) -> Result<Vec<u8>, NulByteError> {
+    let mut out = Vec::with_capacity(input.len() + input.len() / 16);
+    escape_sql_into(input, dialect, &mut out)?;
+    Ok(out)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detection() {
+        assert!(!has_sql_escapable_byte(b"plain value, with \"double\" quotes"));
+        assert!(has_sql_escapable_byte(b"it's"));
+        assert!(has_sql_escapable_byte(b"back\\slash"));
+        assert!(has_sql_escapable_byte(b"nul\0"));
+    }
+
+    #[test]
+    fn test_standard() {
+        assert_eq!(escape_sql(b"", SqlDialect::Standard).unwrap(), b"");
+        assert_eq!(escape_sql(b"''", SqlDialect::Standard).unwrap(), b"''''");
+        assert_eq!(escape_sql(b"a\\b", SqlDialect::Standard).unwrap(), b"a\\b");
+        assert_eq!(
+            escape_sql(b"0123456789\0", SqlDialect::Standard),
+            Err(NulByteError { offset: 10 })
+        );
+    }
+
+    #[test]
+    fn test_mysql() {
+        assert_eq!(escape_sql(b"it's \\ \0", SqlDialect::MySql).unwrap(), b"it''s \\\\ \\0");
+    }
+
+    #[test]
+    fn test_matches_scalar() {
+        let alphabet = b"'\\\0abc ,\"\n\xC3\xA9";
+        let mut rng = 4242u64;
+        let data: Vec<u8> = (0..2000)
+            .map(|_| {
+                rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+                alphabet[((rng >> 16) % alphabet.len() as u64) as usize]
+            })
+            .collect();
+
+        for dialect in [SqlDialect::Standard, SqlDialect::MySql] {
+            for len in 0..200 {
+                let input = &data[..len];
+                assert_eq!(
+                    escape_sql(input, dialect),
+                    escape_sql_scalar(input, dialect),
+                    "Mismatch for len={}, {:?}",
+                    len,
+                    dialect
+                );
+            }
+            assert_eq!(escape_sql(&data, dialect), escape_sql_scalar(&data, dialect));
+        }
+    }
+}
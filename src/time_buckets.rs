@@ -0,0 +1,233 @@
+//! Histogram of log lines per time window, keyed by the timestamp on each line.
+//!
+//! "How many lines per minute?" is the first question asked of any incident
+//! log. The usual answer (`cut | sort | uniq -c`) sorts the whole file; here
+//! each line's timestamp is parsed with the SWAR parser from
+//! [`crate::timestamp`] and counted into its bucket during a single pass.
+//!
+//! Logs are almost always in time order, so consecutive lines nearly always
+//! hit the same bucket. The current bucket is kept in a register-friendly
+//! (bucket, count) pair and only flushed to the map when the bucket changes.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, Read},
+};
+
+use crate::{lines::split_lines, sparse::for_each_data_segment, timestamp::TsFormat};
+
+const BUFFER_SIZE: usize = 1 << 20;
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                              Accumulator
+// ═══════════════════════════════════════════════════════════════════════════
+
+struct Histogram {
+    format: TsFormat,
+    bucket_secs: i64,
+    buckets: BTreeMap<i64, usize>,
+    current: Option<(i64, usize)>,
+}
+
+impl Histogram {
+    fn new(format: TsFormat, bucket_secs: u64) -> Self {
+        assert!(bucket_secs > 0, "bucket_secs must be positive");
+        Histogram {
+            format,
+            bucket_secs: bucket_secs as i64,
+            buckets: BTreeMap::new(),
+            current: None,
+        }
+    }
+
+    #[inline]
+    fn add_line(&mut self, line: &[u8]) {
+        let Some(ts) = self.format.parse_prefix(line) else {
+            return;
+        };
+        let bucket = ts.div_euclid(self.bucket_secs) * self.bucket_secs;
+
+        match &mut self.current {
+            Some((b, count)) if *b == bucket => *count += 1,
+            _ => {
+                self.flush();
+                self.current = Some((bucket, 1));
+            }
+        }
+    }
+
+    /// Add every line of `reader`, treating its EOF as the end of a line.
+    fn add_reader<R: Read>(&mut self, reader: &mut R, buffer: &mut Vec<u8>) -> io::Result<()> {
+        let mut carry = 0;
+
+        loop {
+            if carry == buffer.len() {
+                // A single line longer than the buffer: grow it
+                buffer.resize(buffer.len() * 2, 0);
+            }
+
+            let bytes_read = reader.read(&mut buffer[carry..])?;
+            if bytes_read == 0 {
+                break;
+            }
+            let filled = carry + bytes_read;
+
+            // Only complete lines are processed; the partial last one is carried over
+            let complete = match memchr::memrchr(b'\n', &buffer[..filled]) {
+                Some(pos) => pos + 1,
+                None => 0,
+            };
+            for line in split_lines(&buffer[..complete]) {
+                self.add_line(line);
+            }
+
+            buffer.copy_within(complete..filled, 0);
+            carry = filled - complete;
+        }
+
+        if carry > 0 {
+            self.add_line(&buffer[..carry]);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) {
+        if let Some((bucket, count)) = self.current.take() {
+            *self.buckets.entry(bucket).or_insert(0) += count;
+        }
+    }
+
+    fn finish(mut self) -> Vec<(i64, usize)> {
+        self.flush();
+        self.buckets.into_iter().collect()
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                  API
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Count lines per `bucket_secs`-wide time window.
+///
+/// Returns `(bucket_start, count)` pairs sorted by bucket, where
+/// `bucket_start` is in Unix seconds. Lines that do not start with a valid
+/// timestamp in `ts_format` are skipped; empty buckets are not reported.
+///
+/// # Panics
+/// Panics if `bucket_secs` is 0.
+///
+/// # Example
+/// ```
+/// use scratchpad::time_buckets::bucket_by_time;
+/// use scratchpad::timestamp::TsFormat;
+///
+/// let log = b"2024-01-15T10:30:05 GET /\n2024-01-15T10:30:59 GET /a\n2024-01-15T10:31:00 GET /b\n";
+/// assert_eq!(
+///     bucket_by_time(log, TsFormat::Iso8601, 60),
+///     vec![(1705314600, 2), (1705314660, 1)]
+/// );
+/// ```
+pub fn bucket_by_time(data: &[u8], ts_format: TsFormat, bucket_secs: u64) -> Vec<(i64, usize)> {
+    let mut histogram = Histogram::new(ts_format, bucket_secs);
+    for line in split_lines(data) {
+        histogram.add_line(line);
+    }
+    histogram.finish()
+}
+
+/// Count lines per time window, streaming the file in 1 MB buffers.
+///
+/// Same result as [`bucket_by_time`] on the whole file, in constant memory
+/// (apart from the buckets and the longest line). Holes in sparse files are
+/// skipped without being read.
+pub fn bucket_by_time_from_file(
+    file_path: &str,
+    ts_format: TsFormat,
+    bucket_secs: u64,
+) -> io::Result<Vec<(i64, usize)>> {
+    let mut histogram = Histogram::new(ts_format, bucket_secs);
+    let mut file = File::open(file_path)?;
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+
+    // Holes of sparse (pre-allocated) logs contain no lines: skip them
+    for_each_data_segment(&mut file, |segment| histogram.add_reader(segment, &mut buffer))?;
+
+    Ok(histogram.finish())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate_log(lines: usize) -> Vec<u8> {
+        let mut log = Vec::new();
+        let mut rng = 1u64;
+        let mut secs = 0u32;
+        for i in 0..lines {
+            rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+            secs += ((rng >> 16) % 7) as u32;
+            log.extend_from_slice(
+                format!(
+                    "2024-03-{:02}T{:02}:{:02}:{:02} worker={} event\n",
+                    1 + secs / 86400,
+                    secs / 3600 % 24,
+                    secs / 60 % 60,
+                    secs % 60,
+                    i
+                )
+                .as_bytes(),
+            );
+        }
+        log
+    }
+
+    #[test]
+    fn test_basic_buckets() {
+        let log = b"2024-01-15 10:30:05 a\n2024-01-15 10:30:59 b\n\
+                    not a timestamp\n2024-01-15 10:32:00 c";
+        assert_eq!(
+            bucket_by_time(log, TsFormat::Iso8601, 60),
+            vec![(1705314600, 2), (1705314720, 1)]
+        );
+        assert_eq!(bucket_by_time(log, TsFormat::Iso8601, 3600), vec![(1705312800, 3)]);
+        assert!(bucket_by_time(b"", TsFormat::Iso8601, 60).is_empty());
+    }
+
+    #[test]
+    fn test_out_of_order_lines() {
+        let log = b"120 a\n0 b\n130 c\n10 d\n";
+        assert_eq!(bucket_by_time(log, TsFormat::UnixSeconds, 60), vec![(0, 2), (120, 2)]);
+    }
+
+    #[test]
+    fn test_matches_naive() {
+        let log = generate_log(5000);
+        let mut naive: BTreeMap<i64, usize> = BTreeMap::new();
+        for line in log.split(|&b| b == b'\n') {
+            if let Some(ts) = crate::timestamp::parse_iso8601_scalar(line) {
+                *naive.entry(ts - ts.rem_euclid(300)).or_insert(0) += 1;
+            }
+        }
+        assert_eq!(
+            bucket_by_time(&log, TsFormat::Iso8601, 300),
+            naive.into_iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_from_file_matches_in_memory() {
+        let log = generate_log(100_000);
+        let path = "/tmp/test_bucket_by_time.log";
+        std::fs::write(path, &log).unwrap();
+
+        let from_file = bucket_by_time_from_file(path, TsFormat::Iso8601, 60).unwrap();
+        assert_eq!(from_file, bucket_by_time(&log, TsFormat::Iso8601, 60));
+
+        std::fs::remove_file(path).ok();
+    }
+}
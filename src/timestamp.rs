@@ -0,0 +1,289 @@
+//! Parse log timestamps into Unix seconds using SWAR.
+//!
+//! Based on: https://lemire.me/blog/2023/07/01/parsing-time-stamps-faster-with-simd-instructions/
+//!
+//! A timestamp like `20240115103000` is 14 digits in fixed positions. Instead
+//! of a digit-by-digit loop (or `strptime`), we load the digits as two u64,
+//! validate all of them at once and combine adjacent digits into two-digit
+//! fields with one multiply.
+//!
+//! Supported formats (see [`TsFormat`]):
+//! - `2024-01-15T10:30:00` / `2024-01-15 10:30:00` (ISO 8601, no zone, UTC assumed)
+//! - `20240115103000` (compact)
+//! - `1705314600` (Unix seconds)
+
+use crate::number_spans::digit_mask_swar;
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                       SWAR: Two-Digit Fields
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Load 8 ASCII digits "20240115" as a little-endian u64 and subtract '0':
+//
+//   bytes:  [2][0][2][4][0][1][1][5]
+//
+// v * 10 + (v >> 8) puts first*10 + second in the low byte of each 16-bit lane:
+//
+//   lanes:  [20][24][01][15]      (masked with 0x00FF00FF00FF00FF)
+
+/// Combine 8 ASCII digits into four two-digit values (SWAR version).
+///
+/// Returns None unless all 8 bytes are digits. Lane i (bits 16i..16i+8) holds
+/// the value of digits 2i and 2i+1.
+#[inline]
+pub fn parse_digit_pairs_swar(x: u64) -> Option<u64> {
+    if digit_mask_swar(x) != 0x8080808080808080u64 {
+        return None;
+    }
+
+    let v = x - 0x3030303030303030u64;
+    Some((v * 10 + (v >> 8)) & 0x00FF00FF00FF00FFu64)
+}
+
+#[inline]
+fn lane(pairs: u64, i: u32) -> u32 {
+    ((pairs >> (16 * i)) & 0xFF) as u32
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                            Calendar Math
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[inline]
+fn is_leap_year(year: u32) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+#[inline]
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+///
+/// Howard Hinnant's `days_from_civil` (shift the year to start in March so
+/// the leap day is last, then count whole eras of 400 years).
+fn days_from_civil(year: u32, month: u32, day: u32) -> i64 {
+    let y = year as i64 - (month <= 2) as i64;
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Validate the fields and convert them to Unix seconds.
+fn to_unix_seconds(year: u32, month: u32, day: u32, hour: u32, min: u32, sec: u32) -> Option<i64> {
+    if !(1..=12).contains(&month)
+        || day == 0
+        || day > days_in_month(year, month)
+        || hour > 23
+        || min > 59
+        || sec > 59
+    {
+        return None;
+    }
+
+    let days = days_from_civil(year, month, day);
+    Some(days * 86400 + (hour * 3600 + min * 60 + sec) as i64)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                Parsers
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Parse `YYYYMMDDhhmmss` (SWAR version).
+///
+/// # Example
+/// ```
+/// use scratchpad::timestamp::parse_compact_swar;
+///
+/// assert_eq!(parse_compact_swar(b"20240115103000"), Some(1705314600));
+/// assert_eq!(parse_compact_swar(b"20241301000000"), None); // month 13
+/// ```
+pub fn parse_compact_swar(digits: &[u8; 14]) -> Option<i64> {
+    let date = u64::from_le_bytes(digits[0..8].try_into().unwrap());
+
+    // "hhmmss" padded with "00" to a full word
+    let mut time = [b'0'; 8];
+    time[..6].copy_from_slice(&digits[8..14]);
+    let time = u64::from_le_bytes(time);
+
+    let date = parse_digit_pairs_swar(date)?;
+    let time = parse_digit_pairs_swar(time)?;
+
+    to_unix_seconds(
+        lane(date, 0) * 100 + lane(date, 1),
+        lane(date, 2),
+        lane(date, 3),
+        lane(time, 0),
+        lane(time, 1),
+        lane(time, 2),
+    )
+}
+
+/// Parse `YYYY-MM-DDThh:mm:ss` or `YYYY-MM-DD hh:mm:ss` (SWAR version).
+///
+/// The separators are checked, then the 14 digits are gathered into the
+/// compact layout and handed to [`parse_compact_swar`].
+pub fn parse_iso8601_swar(ts: &[u8; 19]) -> Option<i64> {
+    if ts[4] != b'-'
+        || ts[7] != b'-'
+        || (ts[10] != b'T' && ts[10] != b' ')
+        || ts[13] != b':'
+        || ts[16] != b':'
+    {
+        return None;
+    }
+
+    let digits: [u8; 14] = [
+        ts[0], ts[1], ts[2], ts[3], ts[5], ts[6], ts[8], ts[9], ts[11], ts[12], ts[14], ts[15],
+        ts[17], ts[18],
+    ];
+    parse_compact_swar(&digits)
+}
+
+/// Parse `YYYY-MM-DD?hh:mm:ss` digit by digit (scalar reference).
+pub fn parse_iso8601_scalar(ts: &[u8]) -> Option<i64> {
+    if ts.len() < 19 {
+        return None;
+    }
+
+    let num = |range: std::ops::Range<usize>| -> Option<u32> {
+        ts[range]
+            .iter()
+            .try_fold(0u32, |acc, &b| b.is_ascii_digit().then(|| acc * 10 + (b - b'0') as u32))
+    };
+
+    if ts[4] != b'-'
+        || ts[7] != b'-'
+        || !matches!(ts[10], b'T' | b' ')
+        || ts[13] != b':'
+        || ts[16] != b':'
+    {
+        return None;
+    }
+
+    to_unix_seconds(num(0..4)?, num(5..7)?, num(8..10)?, num(11..13)?, num(14..16)?, num(17..19)?)
+}
+
+/// Layout of the timestamp at the start of each log line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TsFormat {
+    /// `YYYY-MM-DDThh:mm:ss` or `YYYY-MM-DD hh:mm:ss`, anything may follow.
+    Iso8601,
+    /// `YYYYMMDDhhmmss`.
+    Compact,
+    /// Leading run of digits, seconds since the Unix epoch.
+    UnixSeconds,
+}
+
+impl TsFormat {
+    /// Parse the timestamp at the start of `line` into Unix seconds.
+    ///
+    /// Returns None if the line does not start with a valid timestamp.
+    pub fn parse_prefix(&self, line: &[u8]) -> Option<i64> {
+        match self {
+            TsFormat::Iso8601 => parse_iso8601_swar(line.get(..19)?.try_into().unwrap()),
+            TsFormat::Compact => parse_compact_swar(line.get(..14)?.try_into().unwrap()),
+            TsFormat::UnixSeconds => {
+                let len = line.iter().take_while(|b| b.is_ascii_digit()).count();
+                if len == 0 || len > 18 {
+                    return None;
+                }
+                Some(
+                    line[..len]
+                        .iter()
+                        .fold(0i64, |acc, &d| acc * 10 + (d - b'0') as i64),
+                )
+            }
+        }
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digit_pairs() {
+        let pairs = parse_digit_pairs_swar(u64::from_le_bytes(*b"20240115")).unwrap();
+        assert_eq!(
+            [
+                lane(pairs, 0),
+                lane(pairs, 1),
+                lane(pairs, 2),
+                lane(pairs, 3)
+            ],
+            [20, 24, 1, 15]
+        );
+        assert_eq!(parse_digit_pairs_swar(u64::from_le_bytes(*b"2024-01-")), None);
+    }
+
+    #[test]
+    fn test_known_timestamps() {
+        assert_eq!(parse_iso8601_swar(b"1970-01-01T00:00:00"), Some(0));
+        assert_eq!(parse_iso8601_swar(b"2000-02-29 23:59:59"), Some(951868799));
+        assert_eq!(parse_iso8601_swar(b"2024-01-15T10:30:00"), Some(1705314600));
+        assert_eq!(parse_compact_swar(b"20240115103000"), Some(1705314600));
+        assert_eq!(parse_iso8601_swar(b"1969-12-31T23:59:59"), Some(-1));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(parse_iso8601_swar(b"2023-02-29T00:00:00"), None);
+        assert_eq!(parse_iso8601_swar(b"2024-00-10T00:00:00"), None);
+        assert_eq!(parse_iso8601_swar(b"2024-01-10T24:00:00"), None);
+        assert_eq!(parse_iso8601_swar(b"2024-01-10T00:60:00"), None);
+        assert_eq!(parse_iso8601_swar(b"2024/01/10T00:00:00"), None);
+        assert_eq!(parse_iso8601_swar(b"2024-01-1xT00:00:00"), None);
+        assert_eq!(parse_compact_swar(b"2024011510300a"), None);
+    }
+
+    #[test]
+    fn test_matches_scalar() {
+        // Walk a range of dates, plus field values just past the limits
+        for year in [1970u32, 1999, 2000, 2023, 2024, 2100] {
+            for month in 0..=13u32 {
+                for day in [0u32, 1, 15, 28, 29, 30, 31, 32] {
+                    let ts = format!(
+                        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+                        year,
+                        month,
+                        day,
+                        day % 25,
+                        59,
+                        7
+                    );
+                    let ts: &[u8; 19] = ts.as_bytes().try_into().unwrap();
+                    assert_eq!(
+                        parse_iso8601_swar(ts),
+                        parse_iso8601_scalar(ts),
+                        "Mismatch for {:?}",
+                        ts
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_prefix() {
+        let line = b"2024-01-15 10:30:00 INFO started";
+        assert_eq!(TsFormat::Iso8601.parse_prefix(line), Some(1705314600));
+        assert_eq!(TsFormat::Iso8601.parse_prefix(b"2024-01-15"), None);
+        assert_eq!(TsFormat::Compact.parse_prefix(b"20240115103000|x"), Some(1705314600));
+        assert_eq!(TsFormat::UnixSeconds.parse_prefix(b"1705314600 GET /"), Some(1705314600));
+        assert_eq!(TsFormat::UnixSeconds.parse_prefix(b"GET /"), None);
+    }
+}
@@ -0,0 +1,248 @@
+//! io_uring file input (feature `uring`, Linux only).
+//!
+//! A buffered `read()` loop alternates between waiting for the disk and
+//! scanning: while the scanner works on one buffer, no read is pending. On a
+//! cold file the disk then idles for the whole scan of every buffer.
+//! [`UringReader`] keeps several large reads queued in the kernel instead, so
+//! the next chunks are on their way while the current one is consumed:
+//!
+//! ```text
+//!   read():    [read 0][scan 0][read 1][scan 1][read 2][scan 2]
+//!   io_uring:  [read 0][read 1][read 2][read 3]...
+//!                      [scan 0][scan 1][scan 2]...
+//! ```
+//!
+//! It implements [`Read`], so anything taking a reader runs on top of it
+//! unchanged: `count_pattern_matches_uring` is the buffered pattern counter
+//! over a `UringReader`. Handing out the chunks through `read()` costs a
+//! memcpy into the caller's buffer, cheap next to the I/O it overlaps.
+//!
+//! `cold_disk_bench` (run with `--features uring`) on a warm page cache,
+//! where there is no disk wait to hide:
+//!
+//! | File   | 4 KB `read()` | io_uring  |
+//! |--------|---------------|-----------|
+//! | 50 KB  | 1.44 GB/s     | 0.36 GB/s |
+//! | 500 KB | 1.47 GB/s     | 1.20 GB/s |
+//! | 10 MB  | 1.32 GB/s     | 1.44 GB/s |
+//!
+//! Setting up the ring costs about 0.1 ms, so small files are better read
+//! directly. The bench only drops the page cache between runs with `purge`,
+//! on macOS where io_uring does not exist, so there are no cold numbers yet.
+//!
+//! The file size is taken when the reader is created: bytes appended later
+//! are not read.
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, Read},
+    os::unix::{fs::FileExt, io::AsRawFd},
+    path::Path,
+};
+
+use io_uring::{opcode, types, IoUring};
+
+/// Size of each read.
+pub const CHUNK_SIZE: usize = 256 * 1024;
+
+/// Reads kept in flight.
+pub const DEPTH: usize = 4;
+
+/// A file read through io_uring, [`DEPTH`] reads of [`CHUNK_SIZE`] ahead.
+pub struct UringReader {
+    ring: IoUring,
+    file: File,
+    len: u64,
+    buffers: Vec<Box<[u8]>>,
+    /// Result of the read into each buffer, once completed
+    results: Vec<Option<i32>>,
+    /// Buffers with a read in flight or completed, in file order, with the
+    /// offset they were read from
+    queue: VecDeque<(usize, u64)>,
+    /// Offset of the next read to submit
+    next_offset: u64,
+    /// The buffer being consumed: index, bytes in it, bytes consumed
+    current: Option<(usize, usize, usize)>,
+}
+
+impl UringReader {
+    /// Open `path` and queue the first reads.
+    ///
+    /// Fails like `File::open`, or if io_uring is unavailable (kernels before
+    /// 5.6, seccomp filters in some containers).
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        let mut reader = Self {
+            ring: IoUring::new(DEPTH as u32)?,
+            file,
+            len,
+            buffers: (0..DEPTH).map(|_| vec![0u8; CHUNK_SIZE].into_boxed_slice()).collect(),
+            results: vec![None; DEPTH],
+            queue: VecDeque::with_capacity(DEPTH),
+            next_offset: 0,
+            current: None,
+        };
+        for buffer in 0..DEPTH {
+            reader.submit_read(buffer)?;
+        }
+        Ok(reader)
+    }
+
+    /// Queue a read of the next chunk into `buffer`, unless the file is done.
+    fn submit_read(&mut self, buffer: usize) -> io::Result<()> {
+        if self.next_offset >= self.len {
+            return Ok(());
+        }
+
+        let entry = opcode::Read::new(
+            types::Fd(self.file.as_raw_fd()),
+            self.buffers[buffer].as_mut_ptr(),
+            CHUNK_SIZE as u32,
+        )
+        .offset(self.next_offset)
+        .build()
+        .user_data(buffer as u64);
+
+        // SAFETY: the buffer is heap-allocated, owned by `self` and not
+        // touched until its completion is reaped (see `Drop`)
+        unsafe {
+            self.ring
+                .submission()
+                .push(&entry)
+                .map_err(|_| io::Error::other("io_uring submission queue full"))?;
+        }
+        self.ring.submit()?;
+
+        self.results[buffer] = None;
+        self.queue.push_back((buffer, self.next_offset));
+        self.next_offset += CHUNK_SIZE as u64;
+        Ok(())
+    }
+
+    /// Wait for the oldest queued read and return its buffer and length.
+    fn next_chunk(&mut self) -> io::Result<Option<(usize, usize)>> {
+        let Some(&(buffer, offset)) = self.queue.front() else {
+            return Ok(None);
+        };
+
+        while self.results[buffer].is_none() {
+            self.ring.submit_and_wait(1)?;
+            for cqe in self.ring.completion() {
+                self.results[cqe.user_data() as usize] = Some(cqe.result());
+            }
+        }
+        self.queue.pop_front();
+
+        let result = self.results[buffer].unwrap();
+        if result < 0 {
+            return Err(io::Error::from_raw_os_error(-result));
+        }
+
+        // A short read before the end: fill the rest of the chunk directly,
+        // the reads queued after it start at the next chunk
+        let expected = (self.len - offset).min(CHUNK_SIZE as u64) as usize;
+        let mut filled = result as usize;
+        while filled < expected {
+            let rest = &mut self.buffers[buffer][filled..expected];
+            match self.file.read_at(rest, offset + filled as u64) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(Some((buffer, filled)))
+    }
+}
+
+impl Read for UringReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some((buffer, filled, consumed)) = self.current {
+                if consumed < filled {
+                    let n = buf.len().min(filled - consumed);
+                    buf[..n].copy_from_slice(&self.buffers[buffer][consumed..consumed + n]);
+                    self.current = Some((buffer, filled, consumed + n));
+                    return Ok(n);
+                }
+                // Consumed: reuse the buffer for the next chunk
+                self.current = None;
+                self.submit_read(buffer)?;
+            }
+
+            match self.next_chunk()? {
+                Some((buffer, filled)) => self.current = Some((buffer, filled, 0)),
+                None => return Ok(0),
+            }
+        }
+    }
+}
+
+impl Drop for UringReader {
+    fn drop(&mut self) {
+        // The kernel may still write into the buffers: wait for every read
+        // before they are freed
+        let mut in_flight = self.queue.iter().filter(|&&(b, _)| self.results[b].is_none()).count();
+        while in_flight > 0 {
+            if self.ring.submit_and_wait(1).is_err() {
+                break;
+            }
+            for cqe in self.ring.completion() {
+                self.results[cqe.user_data() as usize] = Some(cqe.result());
+                in_flight -= 1;
+            }
+        }
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_parse_buffer_size_impact::{
+        count_pattern_matches_from_file, count_pattern_matches_uring,
+    };
+
+    #[test]
+    fn test_uring_reads_whole_file() {
+        let path = "/tmp/test_uring.csv";
+        // More chunks than buffers, and a partial last chunk
+        let mut data = Vec::new();
+        for i in 0..100_000 {
+            let university = ["MIT", "Harvard"][i % 2];
+            data.extend_from_slice(format!("Person{},{}\n", i, university).as_bytes());
+        }
+        assert!(data.len() > (DEPTH + 1) * CHUNK_SIZE);
+        std::fs::write(path, &data).unwrap();
+
+        let mut reader = match UringReader::open(path) {
+            Ok(reader) => reader,
+            // No io_uring in this environment (old kernel, seccomp)
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return,
+            Err(e) => panic!("{}", e),
+        };
+        let mut read = Vec::new();
+        reader.read_to_end(&mut read).unwrap();
+        assert!(read == data);
+        assert_eq!(
+            count_pattern_matches_uring(path, b"Harvard").unwrap(),
+            count_pattern_matches_from_file(path, b"Harvard").unwrap()
+        );
+
+        // Dropped with reads in flight
+        let mut reader = UringReader::open(path).unwrap();
+        reader.read_exact(&mut [0u8; 10]).unwrap();
+        drop(reader);
+
+        std::fs::write(path, b"").unwrap();
+        assert_eq!(UringReader::open(path).unwrap().read(&mut [0u8; 8]).unwrap(), 0);
+        let _ = std::fs::remove_file(path);
+
+        assert!(UringReader::open("/tmp/does_not_exist.csv").is_err());
+    }
+}
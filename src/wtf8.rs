@@ -0,0 +1,192 @@
+//! Detect and replace WTF-8 encoded lone surrogates.
+//!
+//! Based on: https://simonsapin.github.io/wtf-8/
+//!
+//! JavaScript strings are UTF-16 and may hold unpaired surrogates
+//! (U+D800..U+DFFF). Converted naively, each becomes a 3-byte sequence
+//!
+//!   ED A0..BF xx
+//!
+//! which is valid WTF-8 but not UTF-8, and strict JSON parsers reject it. No
+//! other byte sequence starts with ED followed by A0..BF, so detection is a
+//! two-byte pattern: an ED byte whose successor has its top three bits `101`.
+//!
+//! Per 64-byte block, ED bytes are found with [`eq_bitmask_64`] (NEON or
+//! SWAR); text without any ED byte (all ASCII, and most other scripts) costs
+//! nothing more. Only blocks containing ED compute the successor mask.
+
+use crate::bitmask::{eq_bitmask_64, eq_mask_swar, movemask_swar, padded_block};
+
+// ───────────────────────────────────────────────────────────────────────────
+//                         Scalar Reference
+// ───────────────────────────────────────────────────────────────────────────
+
+/// Find the offset of the first lone surrogate, byte by byte (scalar reference).
+pub fn find_invalid_surrogate_scalar(input: &[u8]) -> Option<usize> {
+    input
+        .windows(2)
+        .position(|w| w[0] == 0xED && (0xA0..=0xBF).contains(&w[1]))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                              Block Detection
+// ═══════════════════════════════════════════════════════════════════════════
+//
+//   input:       .. ED A0 80 .. | ED 9F ..      (ED 9F is U+D7xx, fine)
+//   ed:             1           |  1
+//   high_101:          1        |
+//   ed << 1:           1        |     1
+//   lone = (ed << 1 | carry) & high_101
+//
+// An ED in the last byte of a block is carried into bit 0 of the next one.
+
+/// Bit i set if `(block[i] & 0xE0) == 0xA0`, i.e. the byte is in A0..=BF.
+#[inline]
+fn a0_bf_bitmask_64(block: &[u8; 64]) -> u64 {
+    let mut result = 0u64;
+
+    for (i, chunk) in block.chunks_exact(8).enumerate() {
+        let x = u64::from_le_bytes(chunk.try_into().unwrap()) & 0xE0E0E0E0E0E0E0E0u64;
+        result |= movemask_swar(eq_mask_swar(x, 0xA0)) << (i * 8);
+    }
+
+    result
+}
+
+/// Find the offset of the first lone surrogate (the ED byte).
+///
+/// A trailing `ED` without a successor is a truncated sequence, not a
+/// surrogate, and is not reported.
+///
+/// # Example
+/// ```
+/// use scratchpad::wtf8::find_invalid_surrogate;
+///
+/// // "a", then U+D83D alone (the high half of an emoji), then "b"
+/// assert_eq!(find_invalid_surrogate(b"a\xED\xA0\xBDb"), Some(1));
+/// assert_eq!(find_invalid_surrogate("a\u{1F600}b".as_bytes()), None);
+/// ```
+pub fn find_invalid_surrogate(input: &[u8]) -> Option<usize> {
+    let mut prev_ed = 0u64;
+
+    for (b, chunk) in input.chunks(64).enumerate() {
+        let block = if chunk.len() == 64 {
+            chunk.try_into().unwrap()
+        } else {
+            padded_block(chunk)
+        };
+
+        let ed = eq_bitmask_64(&block, 0xED);
+        if ed == 0 && prev_ed == 0 {
+            continue;
+        }
+
+        let lone = ((ed << 1) | prev_ed) & a0_bf_bitmask_64(&block);
+        if lone != 0 {
+            // The match bit is on the successor; the ED is one byte earlier
+            return Some(b * 64 + lone.trailing_zeros() as usize - 1);
+        }
+        prev_ed = ed >> 63;
+    }
+
+    None
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                               Sanitizing
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Replace every lone surrogate with U+FFFD, appending to `out`.
+///
+/// The 3-byte surrogate sequence is replaced by the 3-byte `EF BF BD`, so the
+/// length is unchanged. A surrogate sequence cut off at the end of `input`
+/// (`ED A0..BF`) is replaced by a whole U+FFFD too: the output never ends in
+/// a partial character.
+pub fn replace_lone_surrogates_into(input: &[u8], out: &mut Vec<u8>) {
+    let mut rest = input;
+
+    while let Some(pos) = find_invalid_surrogate(rest) {
+        out.extend_from_slice(&rest[..pos]);
+        out.extend_from_slice("\u{FFFD}".as_bytes());
+        rest = &rest[(pos + 3).min(rest.len())..];
+    }
+
+    out.extend_from_slice(rest);
+}
+
+/// Replace every lone surrogate with U+FFFD.
+///
+/// # Example
+/// ```
+/// use scratchpad::wtf8::replace_lone_surrogates;
+///
+/// assert_eq!(replace_lone_surrogates(b"x\xED\xB0\x80y"), "x\u{FFFD}y".as_bytes());
+/// ```
+pub fn replace_lone_surrogates(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    replace_lone_surrogates_into(input, &mut out);
+    out
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Tests
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_surrogate_range() {
+        // U+D7FF (ED 9F BF) and U+E000 (EE 80 80) are ordinary characters
+        assert_eq!(find_invalid_surrogate("\u{D7FF}\u{E000}".as_bytes()), None);
+        assert_eq!(find_invalid_surrogate(b"\xED\xA0\x80"), Some(0)); // U+D800
+        assert_eq!(find_invalid_surrogate(b"\xED\xBF\xBF"), Some(0)); // U+DFFF
+        assert_eq!(find_invalid_surrogate(b"abc\xED"), None);
+        assert_eq!(find_invalid_surrogate(b""), None);
+    }
+
+    #[test]
+    fn test_across_block_boundary() {
+        for pos in [0usize, 62, 63, 64, 127, 128, 200] {
+            let mut data = "\u{D7FF}".repeat(100).into_bytes();
+            data.truncate(pos);
+            data.extend_from_slice(b"\xED\xAD\x80tail");
+            assert_eq!(find_invalid_surrogate(&data), Some(pos), "Mismatch for pos={}", pos);
+        }
+    }
+
+    #[test]
+    fn test_matches_scalar() {
+        let alphabet = [b'a', 0xED, 0xA0, 0x9F, 0xBF, 0xC0, 0x80];
+        let mut rng = 8u64;
+        for _ in 0..300 {
+            let len = {
+                rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+                ((rng >> 16) % 200) as usize
+            };
+            let data: Vec<u8> = (0..len)
+                .map(|_| {
+                    rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+                    if (rng >> 16) & 15 == 0 {
+                        alphabet[((rng >> 20) % alphabet.len() as u64) as usize]
+                    } else {
+                        b'a'
+                    }
+                })
+                .collect();
+            assert_eq!(find_invalid_surrogate(&data), find_invalid_surrogate_scalar(&data));
+        }
+    }
+
+    #[test]
+    fn test_replace() {
+        // The last surrogate is cut off after two bytes
+        let input = b"\xED\xA0\xBD\xED\xB8\x80 ok \xED\xA0";
+        let out = replace_lone_surrogates(input);
+        assert_eq!(out, "\u{FFFD}\u{FFFD} ok \u{FFFD}".as_bytes());
+        assert!(std::str::from_utf8(&out).is_ok());
+        assert_eq!(find_invalid_surrogate(&out), None);
+        assert_eq!(replace_lone_surrogates(b"clean"), b"clean");
+    }
+}
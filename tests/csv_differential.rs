@@ -0,0 +1,245 @@
+//! Differential test: every parser in this crate against the `csv` crate.
+//!
+//! The unit tests check the parsers against each other and a handful of
+//! hand-written inputs. Here the reference is an independent, widely used
+//! implementation: on the shared fixtures (see `datagen::fixtures`) and on
+//! generated well-formed CSV (proptest), every counter must report the `csv`
+//! crate's field and row counts, and [`CsvReader`] must return the same
+//! field contents.
+//!
+//! The few places where this crate parts from the `csv` crate on purpose are
+//! listed in [`DIVERGENCES`]; each one is pinned by an example, and the
+//! generated inputs are compared with it taken into account.
+//!
+//! Throughput on the same fixtures is reported by
+//! `cargo bench --bench csv_differential_bench`.
+
+use proptest::prelude::*;
+use scratchpad::{
+    csv_dialect::strip_bom,
+    csv_index::{count_csv_rows, csv_stats, parse_csv_index, parse_csv_parallel},
+    csv_reader::CsvReader,
+    csv_state_machine::{
+        parse_csv_hybrid, parse_csv_if_else, parse_csv_reader, parse_csv_state_machine, tokenize,
+    },
+    datagen::fixtures,
+};
+
+const ROWS: usize = 2000;
+
+/// Field and row counts according to the `csv` crate.
+fn csv_crate_counts(data: &[u8]) -> (usize, usize) {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(data);
+    let mut record = csv::ByteRecord::new();
+    let (mut fields, mut rows) = (0, 0);
+    while reader.read_byte_record(&mut record).unwrap() {
+        fields += record.len();
+        rows += 1;
+    }
+    (fields, rows)
+}
+
+/// Records according to the `csv` crate.
+fn csv_crate_records(data: &[u8]) -> Vec<Vec<Vec<u8>>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(data);
+    reader
+        .byte_records()
+        .map(|record| record.unwrap().iter().map(<[u8]>::to_vec).collect())
+        .collect()
+}
+
+/// Collapse `""` to `"`, as [`CsvReader`] leaves it doubled.
+fn collapse_quotes(field: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(field.len());
+    let mut i = 0;
+    while i < field.len() {
+        out.push(field[i]);
+        i += if field[i..].starts_with(b"\"\"") { 2 } else { 1 };
+    }
+    out
+}
+
+/// Records according to [`CsvReader`], quotes collapsed.
+fn reader_records(data: &[u8]) -> Vec<Vec<Vec<u8>>> {
+    CsvReader::new(strip_bom(data))
+        .map_records(|r| r.iter().map(collapse_quotes).collect())
+        .collect()
+}
+
+/// Check that every counter reports `expected`.
+fn assert_counts(name: &str, data: &[u8], expected: (usize, usize)) {
+    let stats = csv_stats(data);
+    let counts = [
+        ("state machine", parse_csv_state_machine(data)),
+        ("hybrid", parse_csv_hybrid(data)),
+        ("if/else", parse_csv_if_else(data)),
+        ("index", parse_csv_index(data)),
+        ("parallel", parse_csv_parallel(data, 4)),
+        ("stream", parse_csv_reader(data).unwrap()),
+        ("stats", (stats.fields, stats.rows)),
+    ];
+    for (parser, found) in counts {
+        assert_eq!(found, expected, "{}: {} disagrees with the csv crate", name, parser);
+    }
+    assert_eq!(tokenize(data).len(), expected.0, "{}: tokenize", name);
+    assert_eq!(count_csv_rows(data), expected.1, "{}: count_csv_rows", name);
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                           Known Divergences
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// A place where this crate and the `csv` crate count differently on purpose.
+struct Divergence {
+    name: &'static str,
+    example: &'static [u8],
+    ours: (usize, usize),
+    csv_crate: (usize, usize),
+}
+
+/// The allow-list: every other difference from the `csv` crate is a bug.
+const DIVERGENCES: &[Divergence] = &[
+    // An empty line is a record of one empty field, as `CsvReader` returns it
+    // and `csv_write::write_record` would write it; the `csv` crate skips it.
+    // Generated inputs have their blank lines added to the `csv` crate's counts
+    Divergence { name: "blank line", example: b"a\n\n", ours: (2, 2), csv_crate: (1, 1) },
+    // Only `\n` ends a record (a `\r` before it is dropped); the `csv` crate
+    // also ends one at a lone `\r`. Generated inputs have `\r` only in quoted
+    // fields and before `\n`
+    Divergence { name: "lone CR", example: b"a\rb", ours: (1, 1), csv_crate: (2, 2) },
+];
+
+#[test]
+fn test_divergences_are_known() {
+    for divergence in DIVERGENCES {
+        let Divergence { name, example, ours, csv_crate } = *divergence;
+        assert_eq!(csv_crate_counts(example), csv_crate, "{}: csv crate", name);
+        assert_counts(name, example, ours);
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                Fixtures
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[test]
+fn test_counts_match_csv_crate() {
+    for (name, data) in fixtures(ROWS) {
+        let expected = csv_crate_counts(&data);
+        assert!(expected.1 > 0, "{}: empty fixture", name);
+        assert_counts(name, &data, expected);
+    }
+}
+
+#[test]
+fn test_fields_match_csv_crate() {
+    for (name, data) in fixtures(ROWS) {
+        let expected = csv_crate_records(&data);
+        let found = reader_records(&data);
+        assert_eq!(found.len(), expected.len(), "{}: record count", name);
+        for (row, (found, expected)) in found.iter().zip(&expected).enumerate() {
+            assert_eq!(found, expected, "{}: mismatch in record {}", name, row);
+        }
+    }
+}
+
+#[test]
+fn test_edge_cases_match_csv_crate() {
+    let inputs: [&[u8]; 10] = [
+        b"a,",
+        b"a,b\n,",
+        b",",
+        b",a\n",
+        b",\n,",
+        b"\"a\nb\",",
+        b"a,\"\"",
+        b"\"\"\n",
+        b"a\r\nb,\r\n",
+        b"",
+    ];
+    for data in inputs {
+        let name = format!("{:?}", String::from_utf8_lossy(data));
+        assert_counts(&name, data, csv_crate_counts(data));
+        assert_eq!(reader_records(data), csv_crate_records(data), "{}", name);
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                            Generated Inputs
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// A field: unquoted (no delimiter, quote or line break), or quoted with
+/// anything inside, quotes doubled.
+fn field() -> impl Strategy<Value = Vec<u8>> {
+    let unquoted = prop::collection::vec(prop::sample::select(&b"ab 1"[..]), 0..6);
+    let quoted =
+        prop::collection::vec(prop::sample::select(&b"a,\"\n\r "[..]), 0..6).prop_map(|body| {
+            let mut field = vec![b'"'];
+            for byte in body {
+                field.push(byte);
+                if byte == b'"' {
+                    field.push(b'"');
+                }
+            }
+            field.push(b'"');
+            field
+        });
+    prop_oneof![3 => unquoted, 1 => quoted]
+}
+
+/// Well-formed CSV: records of 1 to 4 fields, each ended by `\n` or `\r\n`
+/// but the last, which may be unterminated. Along with it, for every record
+/// in it, whether that record is a blank line (see [`DIVERGENCES`]).
+fn well_formed_csv() -> impl Strategy<Value = (Vec<u8>, Vec<bool>)> {
+    let record = (prop::collection::vec(field(), 1..5), any::<bool>());
+    (prop::collection::vec(record, 0..40), any::<bool>()).prop_map(|(records, terminate_last)| {
+        let mut data = Vec::new();
+        let mut blank = Vec::new();
+        for (i, (fields, crlf)) in records.iter().enumerate() {
+            let start = data.len();
+            data.extend_from_slice(&fields.join(&b","[..]));
+            let is_blank = data.len() == start;
+
+            if i + 1 < records.len() || terminate_last {
+                data.extend_from_slice(if *crlf { b"\r\n" } else { b"\n" });
+            } else if is_blank {
+                // Nothing at all after the last line break: no record
+                break;
+            }
+            blank.push(is_blank);
+        }
+        (data, blank)
+    })
+}
+
+proptest! {
+    #[test]
+    fn counts_match_csv_crate((data, blank) in well_formed_csv()) {
+        let (fields, rows) = csv_crate_counts(&data);
+        let blank_lines = blank.iter().filter(|&&b| b).count();
+        assert_counts("generated", &data, (fields + blank_lines, rows + blank_lines));
+    }
+
+    #[test]
+    fn fields_match_csv_crate((data, blank) in well_formed_csv()) {
+        let found = reader_records(&data);
+        prop_assert_eq!(found.len(), blank.len());
+
+        // Blank lines are one empty field here, and skipped by the csv crate
+        let mut not_blank = Vec::new();
+        for (record, is_blank) in found.into_iter().zip(&blank) {
+            if *is_blank {
+                prop_assert_eq!(record, vec![Vec::<u8>::new()]);
+            } else {
+                not_blank.push(record);
+            }
+        }
+        prop_assert_eq!(not_blank, csv_crate_records(&data));
+    }
+}
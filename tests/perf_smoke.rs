@@ -0,0 +1,316 @@
+//! Performance smoke test: every SIMD kernel must be at least as fast as its
+//! scalar reference on a representative input.
+//!
+//! The benches print numbers for a human to read; this turns the one claim
+//! that must always hold into a failing test. It only means something in an
+//! optimized build, so it is behind a feature and skipped in debug builds:
+//!
+//!   cargo test --release --features perf-smoke --test perf_smoke
+//!
+//! Each side is timed as the best of several alternating runs (the minimum
+//! filters out scheduler noise), and SIMD may be up to `SLACK` slower before
+//! the test fails, so that two paths that are genuinely equal do not flake.
+//! Kernels whose dispatcher has no SIMD path on the running CPU (NEON-only
+//! kernels on x86, ...) are skipped: both sides would run the same scalar
+//! loop, and the comparison would only time noise.
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use scratchpad::{
+    byte_set, cms, cpu, hll, json_escape_SWAR, json_escape_utf16, json_number, sha256, utf8, wtf8,
+};
+
+const RUNS: usize = 7;
+const SLACK: f64 = 1.05;
+const INPUT_SIZE: usize = 1 << 20;
+
+/// Time of `iterations` calls.
+fn time<T>(iterations: usize, f: &mut impl FnMut() -> T) -> Duration {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        black_box(f());
+    }
+    start.elapsed()
+}
+
+fn assert_simd_not_slower<A, B>(
+    name: &str,
+    iterations: usize,
+    mut scalar: impl FnMut() -> A,
+    mut simd: impl FnMut() -> B,
+) {
+    if cfg!(debug_assertions) {
+        eprintln!("{}: skipped, run with --release", name);
+        return;
+    }
+
+    // Warm up both sides, then alternate runs so both see the same machine
+    // state (frequency, cache, other load)
+    time(1, &mut scalar);
+    time(1, &mut simd);
+
+    let mut scalar_time = Duration::MAX;
+    let mut simd_time = Duration::MAX;
+    for _ in 0..RUNS {
+        scalar_time = scalar_time.min(time(iterations, &mut scalar));
+        simd_time = simd_time.min(time(iterations, &mut simd));
+    }
+    let ratio = simd_time.as_secs_f64() / scalar_time.as_secs_f64();
+
+    eprintln!(
+        "{:40} scalar {:>10.2?}  simd {:>10.2?}  ({:.2}x)",
+        name,
+        scalar_time,
+        simd_time,
+        1.0 / ratio
+    );
+    assert!(
+        ratio <= SLACK,
+        "{}: SIMD path is {:.2}x slower than scalar ({:?} vs {:?})",
+        name,
+        ratio,
+        simd_time,
+        scalar_time
+    );
+}
+
+/// Whether the dispatcher of `name` has a SIMD path on this CPU; prints why
+/// the comparison is skipped if not.
+fn has_simd_path(name: &str, available: bool) -> bool {
+    if !available {
+        eprintln!("{}: skipped, only the scalar path exists on this CPU", name);
+    }
+    available
+}
+
+/// Printable ASCII with no escapable bytes.
+fn clean_text(len: usize) -> Vec<u8> {
+    (b'a'..=b'z').cycle().take(len).collect()
+}
+
+/// Text with an escapable byte every 100 bytes or so.
+fn mixed_text(len: usize) -> Vec<u8> {
+    let mut rng = 12345u64;
+    (0..len)
+        .map(|_| {
+            rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+            match (rng >> 16) % 100 {
+                0 => b'"',
+                1 => b'\n',
+                _ => b'a' + ((rng >> 24) % 26) as u8,
+            }
+        })
+        .collect()
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                                 Escaping
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[test]
+fn perf_json_count_escapable() {
+    let input = mixed_text(INPUT_SIZE);
+    assert_simd_not_slower(
+        "json_escape_SWAR::count_escapable_bytes",
+        20,
+        || json_escape_SWAR::count_escapable_bytes_scalar(black_box(&input)),
+        || json_escape_SWAR::count_escapable_bytes(black_box(&input)),
+    );
+}
+
+#[test]
+fn perf_json_has_escapable() {
+    let input = clean_text(INPUT_SIZE);
+    assert_simd_not_slower(
+        "json_escape_SWAR::has_json_escapable_byte",
+        20,
+        || json_escape_SWAR::has_json_escapable_byte_scalar(black_box(&input)),
+        || json_escape_SWAR::has_json_escapable_byte(black_box(&input)),
+    );
+}
+
+#[test]
+fn perf_byte_set_find_first() {
+    let input = clean_text(INPUT_SIZE);
+    assert_simd_not_slower(
+        "byte_set::ByteSet::find_first (HTML)",
+        20,
+        || byte_set::HTML.find_first_scalar(black_box(&input)),
+        || byte_set::HTML.find_first(black_box(&input)),
+    );
+}
+
+#[test]
+fn perf_byte_set_copy_until() {
+    let input = clean_text(INPUT_SIZE);
+    let mut out = Vec::with_capacity(INPUT_SIZE);
+    let mut out_simd = Vec::with_capacity(INPUT_SIZE);
+    assert_simd_not_slower(
+        "byte_set::ByteSet::copy_until (JSON)",
+        20,
+        || {
+            out.clear();
+            let n = byte_set::JSON
+                .find_first_scalar(black_box(&input))
+                .unwrap_or(input.len());
+            out.extend_from_slice(&input[..n]);
+            n
+        },
+        || {
+            out_simd.clear();
+            byte_set::JSON.copy_until(black_box(&input), &mut out_simd)
+        },
+    );
+}
+
+#[test]
+fn perf_json_escape_utf16() {
+    let input: Vec<u16> = clean_text(INPUT_SIZE / 2)
+        .iter()
+        .map(|&b| b as u16)
+        .collect();
+    assert_simd_not_slower(
+        "json_escape_utf16::has_json_escapable_utf16",
+        20,
+        || json_escape_utf16::has_json_escapable_utf16_scalar(black_box(&input)),
+        || json_escape_utf16::has_json_escapable_utf16(black_box(&input)),
+    );
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                               Validation
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[test]
+fn perf_wtf8_find_invalid_surrogate() {
+    let input = clean_text(INPUT_SIZE);
+    assert_simd_not_slower(
+        "wtf8::find_invalid_surrogate",
+        20,
+        || wtf8::find_invalid_surrogate_scalar(black_box(&input)),
+        || wtf8::find_invalid_surrogate(black_box(&input)),
+    );
+}
+
+#[test]
+fn perf_utf8_valid_up_to() {
+    // ASCII with an accented word every few hundred bytes
+    let mut input = Vec::with_capacity(INPUT_SIZE);
+    while input.len() < INPUT_SIZE {
+        input.extend_from_slice(&clean_text(300));
+        input.extend_from_slice("Zürich ".as_bytes());
+    }
+    assert_simd_not_slower(
+        "utf8::valid_up_to",
+        20,
+        || utf8::valid_up_to_scalar(black_box(&input)),
+        || utf8::valid_up_to(black_box(&input)),
+    );
+}
+
+#[test]
+fn perf_json_number() {
+    // Realistic numbers (digit runs of 1 to 14 bytes) are short enough for
+    // the scalar path, where the DFA would lose
+    assert!((0..10_000u64).all(|i| {
+        let n = format!("-{}.{}e+{}", i.wrapping_mul(2654435761), i.wrapping_mul(40503), i % 300);
+        n.len() < json_number::DFA_MIN_LEN
+    }));
+
+    let long: Vec<Vec<u8>> = (0..10_000u64)
+        .map(|i| format!("-{}{:030}.{}e-{}", i % 9 + 1, i, i.pow(3), i % 300).into_bytes())
+        .collect();
+    assert!(long.iter().all(|n| n.len() >= json_number::DFA_MIN_LEN));
+    assert_simd_not_slower(
+        "json_number::is_valid_json_number",
+        20,
+        || {
+            long.iter()
+                .filter(|n| json_number::is_valid_json_number_scalar(black_box(n)))
+                .count()
+        },
+        || {
+            long.iter()
+                .filter(|n| json_number::is_valid_json_number(black_box(n)))
+                .count()
+        },
+    );
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                            Hashing & Sketches
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[test]
+fn perf_sha256_compress() {
+    if !has_simd_path("sha256::compress", cpu::has_sha2()) {
+        return;
+    }
+    let input = clean_text(INPUT_SIZE);
+    assert_simd_not_slower(
+        "sha256::compress",
+        5,
+        || {
+            let mut state = [0u32; 8];
+            sha256::compress_scalar(&mut state, black_box(&input));
+            state
+        },
+        || sha256::sha256(black_box(&input)),
+    );
+}
+
+#[test]
+fn perf_hll_max_registers() {
+    if !has_simd_path("hll::max_registers", cpu::has_neon()) {
+        return;
+    }
+    let src: Vec<u8> = mixed_text(INPUT_SIZE).iter().map(|b| b & 63).collect();
+    let mut dst = vec![0u8; INPUT_SIZE];
+    let mut dst_simd = vec![0u8; INPUT_SIZE];
+    assert_simd_not_slower(
+        "hll::max_registers",
+        50,
+        || hll::max_registers_scalar(black_box(&mut dst), black_box(&src)),
+        || hll::max_registers(black_box(&mut dst_simd), black_box(&src)),
+    );
+}
+
+#[test]
+fn perf_cms_add_counters() {
+    if !has_simd_path("cms::add_counters", cpu::has_neon()) {
+        return;
+    }
+    let src: Vec<u32> = (0..INPUT_SIZE as u32 / 4).collect();
+    let mut dst = vec![0u32; src.len()];
+    let mut dst_simd = vec![0u32; src.len()];
+    assert_simd_not_slower(
+        "cms::add_counters",
+        50,
+        || cms::add_counters_scalar(black_box(&mut dst), black_box(&src)),
+        || cms::add_counters(black_box(&mut dst_simd), black_box(&src)),
+    );
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//                               Line Feeds
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// The regression this test exists for: NEON lost to scalar at K=72.
+#[cfg(target_arch = "aarch64")]
+#[test]
+fn perf_line_feed_every_k_bytes() {
+    use scratchpad::line_feed_every_k_bytes::{insert_line_feed_neon, insert_line_feed_scalar};
+
+    let input = clean_text(INPUT_SIZE);
+    for k in [16, 32, 64, 72, 76] {
+        assert_simd_not_slower(
+            &format!("line_feed_every_k_bytes (K={})", k),
+            10,
+            || insert_line_feed_scalar(black_box(&input), k),
+            || insert_line_feed_neon(black_box(&input), k).unwrap(),
+        );
+    }
+}
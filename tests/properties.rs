@@ -0,0 +1,201 @@
+//! Properties of the kernels on arbitrary inputs (proptest).
+//!
+//! `conformance` checks every version against its scalar reference; these
+//! check what any correct output must look like, so a bug the reference
+//! shares still fails. On a failure, proptest shrinks the input to a minimal
+//! one and keeps its seed in `tests/properties.proptest-regressions`, which
+//! is replayed first on the next run.
+
+use std::io::{self, Read};
+
+use proptest::prelude::*;
+use scratchpad::{
+    csv_dialect::Dialect,
+    csv_reader::for_each_record_in_reader,
+    csv_state_machine::{parse_csv_reader, parse_csv_state_machine, CsvStreamParser},
+    html_escape::{
+        escape_html, find_first_html_escapable, has_html_escapable_byte, needs_html_escape_scalar,
+    },
+    json_escape_SWAR::{
+        count_escapable_bytes, escape_json, find_first_escapable, has_json_escapable_byte,
+        needs_json_escape_scalar,
+    },
+    json_escape_utf16::{has_json_escapable_utf16, needs_json_escape_utf16_scalar},
+    line_feed_every_k_bytes::{insert_line_feed, insert_line_feed_scalar},
+    sql_escape::{has_sql_escapable_byte, needs_sql_escape_scalar},
+};
+
+/// Text with now and then an arbitrary byte: a buffer of uniform bytes has
+/// an escape in its first few bytes, and never exercises a clean block.
+fn text() -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec(prop_oneof![15 => b' '..=b'~', 1 => any::<u8>()], 0..600)
+}
+
+/// UTF-16 text, with now and then an arbitrary unit.
+fn utf16() -> impl Strategy<Value = Vec<u16>> {
+    prop::collection::vec(prop_oneof![8 => 0x20..0x300u16, 1 => any::<u16>()], 0..300)
+}
+
+/// Bytes of CSV syntax, quotes unbalanced as often as not.
+fn csv() -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec(prop::sample::select(&b"ab,,\"\"\n\r "[..]), 0..400)
+}
+
+/// Read sizes for a [`Chunked`] reader.
+fn chunk_sizes() -> impl Strategy<Value = Vec<usize>> {
+    prop::collection::vec(1..70usize, 1..20)
+}
+
+/// Reads `data` in pieces of `sizes` bytes, round-robin.
+struct Chunked<'a> {
+    data: &'a [u8],
+    sizes: &'a [usize],
+    reads: usize,
+}
+
+impl<'a> Chunked<'a> {
+    fn new(data: &'a [u8], sizes: &'a [usize]) -> Self {
+        Chunked { data, sizes, reads: 0 }
+    }
+}
+
+impl Read for Chunked<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.sizes[self.reads % self.sizes.len()]
+            .min(buf.len())
+            .min(self.data.len());
+        buf[..n].copy_from_slice(&self.data[..n]);
+        self.data = &self.data[n..];
+        self.reads += 1;
+        Ok(n)
+    }
+}
+
+/// The fields of every record, read with `buffer_size` byte buffers.
+fn records(reader: &mut impl Read, buffer_size: usize) -> Vec<Vec<Vec<u8>>> {
+    let mut records = Vec::new();
+    for_each_record_in_reader(reader, buffer_size, |record| {
+        records.push(record.to_vec());
+        Ok(())
+    })
+    .unwrap();
+    records
+}
+
+proptest! {
+    // ═══════════════════════════════════════════════════════════════════════
+    //                              Line Feeds
+    // ═══════════════════════════════════════════════════════════════════════
+
+    #[test]
+    fn line_feed_length(input in text(), k in 1..100usize) {
+        let expected = input.len() + input.len() / k;
+        prop_assert_eq!(insert_line_feed(&input, k).len(), expected);
+        prop_assert_eq!(insert_line_feed_scalar(&input, k).len(), expected);
+    }
+
+    #[test]
+    fn line_feed_every_k_plus_1th_byte(input in text(), k in 1..100usize) {
+        let output = insert_line_feed(&input, k);
+        let mut rest = Vec::with_capacity(input.len());
+        for (i, &byte) in output.iter().enumerate() {
+            if (i + 1) % (k + 1) == 0 {
+                prop_assert_eq!(byte, b'\n', "byte {} of {}", i, output.len());
+            } else {
+                rest.push(byte);
+            }
+        }
+        // Nothing but the line feeds was added
+        prop_assert_eq!(rest, input);
+    }
+
+    #[test]
+    fn line_feed_k_zero(input in text()) {
+        prop_assert_eq!(insert_line_feed(&input, 0), input);
+    }
+
+    // ═══════════════════════════════════════════════════════════════════════
+    //                               Escaping
+    // ═══════════════════════════════════════════════════════════════════════
+
+    #[test]
+    fn json_detection_is_any(input in text()) {
+        let escapable = |b: &u8| needs_json_escape_scalar(*b);
+        prop_assert_eq!(has_json_escapable_byte(&input), input.iter().any(escapable));
+        prop_assert_eq!(find_first_escapable(&input), input.iter().position(escapable));
+        let count = input.iter().filter(|b| escapable(b)).count();
+        prop_assert_eq!(count_escapable_bytes(&input), count);
+    }
+
+    #[test]
+    fn html_and_sql_detection_is_any(input in text()) {
+        let html = |b: &u8| needs_html_escape_scalar(*b);
+        prop_assert_eq!(has_html_escapable_byte(&input), input.iter().any(html));
+        prop_assert_eq!(find_first_html_escapable(&input), input.iter().position(html));
+        let sql = input.iter().any(|&b| needs_sql_escape_scalar(b));
+        prop_assert_eq!(has_sql_escapable_byte(&input), sql);
+    }
+
+    #[test]
+    fn utf16_detection_is_any(units in utf16()) {
+        let any = units.iter().any(|&u| needs_json_escape_utf16_scalar(u));
+        prop_assert_eq!(has_json_escapable_utf16(&units), any);
+    }
+
+    #[test]
+    fn escaped_json_has_no_escapable_byte_left(input in text()) {
+        // Every quote and backslash left is part of an escape sequence
+        let escaped = escape_json(&input);
+        prop_assert!(!escaped.iter().any(|&b| b < 0x20));
+        let mut bytes = escaped.iter();
+        while let Some(&b) = bytes.next() {
+            prop_assert_ne!(b, b'"');
+            if b == b'\\' {
+                let next = bytes.next().copied().unwrap_or(0);
+                prop_assert!(b"\"\\/bfnrtu".contains(&next), "\\ then {:?}", next as char);
+            }
+        }
+    }
+
+    #[test]
+    fn escaped_html_has_only_entities(input in text()) {
+        let escaped = escape_html(&input);
+        prop_assert!(!escaped.iter().any(|&b| matches!(b, b'<' | b'>' | b'"' | b'\'')));
+        let ampersands = escaped.iter().filter(|&&b| b == b'&').count();
+        let entities = input.iter().filter(|&&b| needs_html_escape_scalar(b)).count();
+        prop_assert_eq!(ampersands, entities);
+    }
+
+    // ═══════════════════════════════════════════════════════════════════════
+    //                          Streaming CSV Parsers
+    // ═══════════════════════════════════════════════════════════════════════
+
+    #[test]
+    fn csv_counts_chunking_invariant(data in csv(), sizes in chunk_sizes()) {
+        let expected = parse_csv_state_machine(&data);
+
+        let mut parser = CsvStreamParser::new(&Dialect::CSV).unwrap();
+        let mut rest = &data[..];
+        for &size in sizes.iter().cycle() {
+            if rest.is_empty() {
+                break;
+            }
+            let (chunk, tail) = rest.split_at(size.min(rest.len()));
+            parser.feed(chunk);
+            rest = tail;
+        }
+        prop_assert_eq!(parser.finish(), expected);
+        prop_assert_eq!(parse_csv_reader(Chunked::new(&data, &sizes)).unwrap(), expected);
+    }
+
+    #[test]
+    fn csv_records_chunking_invariant(
+        data in csv(),
+        sizes in chunk_sizes(),
+        buffer_size in 1..64usize,
+    ) {
+        // Small buffers have to grow for the longer records
+        let expected = records(&mut &data[..], 64 << 10);
+        prop_assert_eq!(records(&mut Chunked::new(&data, &sizes), buffer_size), expected);
+    }
+}